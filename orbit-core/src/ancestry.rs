@@ -0,0 +1,94 @@
+use std::collections::{HashSet, VecDeque};
+use crate::objects::{Commit, ObjectId};
+use crate::vos::ObjectStore;
+use crate::Result;
+
+/// Loads a commit from the store, or None if it has not been downloaded
+pub fn load_commit(store: &ObjectStore, commit_id: &str) -> Option<Commit> {
+    store.load_json(commit_id).ok()
+}
+
+/// Every commit reachable from `tip` (including `tip`) that exists locally
+pub fn ancestors(store: &ObjectStore, tip: &str) -> HashSet<ObjectId> {
+    let mut seen = HashSet::new();
+    let mut queue = VecDeque::from([tip.to_string()]);
+
+    while let Some(commit_id) = queue.pop_front() {
+        if !seen.insert(commit_id.clone()) {
+            continue;
+        }
+        if let Some(commit) = load_commit(store, &commit_id) {
+            queue.extend(commit.parents.into_iter().filter(|p| !seen.contains(p)));
+        }
+    }
+
+    seen
+}
+
+/// Commits reachable from `tip` (itself included) that are not in `excluded`, each
+/// after its parents
+pub fn parents_first(store: &ObjectStore, tip: &str, excluded: &HashSet<ObjectId>) -> Result<Vec<(ObjectId, Commit)>> {
+    let mut commits = Vec::new();
+    let mut seen = HashSet::new();
+    let mut stack = vec![(tip.to_string(), None)];
+    while let Some((id, commit)) = stack.pop() {
+        if let Some(commit) = commit {
+            commits.push((id, commit));
+            continue;
+        }
+        if excluded.contains(&id) || !seen.insert(id.clone()) {
+            continue;
+        }
+        let commit: Commit = store.load_json(&id)?;
+        let parents = commit.parents.clone();
+        stack.push((id, Some(commit)));
+        stack.extend(parents.into_iter().rev().map(|parent| (parent, None)));
+    }
+    Ok(commits)
+}
+
+/// The commits in `commits` that no other commit in the list descends from
+pub fn tips(store: &ObjectStore, commits: &[ObjectId]) -> Vec<ObjectId> {
+    let mut covered = HashSet::new();
+    for commit_id in commits {
+        if let Some(commit) = load_commit(store, commit_id) {
+            for parent in commit.parents {
+                covered.extend(ancestors(store, &parent));
+            }
+        }
+    }
+
+    let mut tips: Vec<ObjectId> = commits.iter().filter(|c| !covered.contains(*c)).cloned().collect();
+    tips.dedup();
+    tips
+}
+
+/// The nearest common ancestor of two commits, if their histories share one
+pub fn merge_base(store: &ObjectStore, a: &str, b: &str) -> Option<ObjectId> {
+    let a_history = ancestors(store, a);
+    let common: Vec<ObjectId> = ancestors(store, b).into_iter().filter(|c| a_history.contains(c)).collect();
+    tips(store, &common).into_iter().next()
+}
+
+/// True if `ancestor` is reachable from `descendant` (a commit is its own ancestor)
+pub fn is_ancestor(store: &ObjectStore, ancestor: &str, descendant: &str) -> bool {
+    ancestor == descendant || ancestors(store, descendant).contains(ancestor)
+}
+
+/// Counts commits on each side since the merge base of `local` and `upstream`.
+///
+/// Returns `(ahead, behind)`: commits reachable only from `local`, and commits
+/// reachable only from `upstream`. Commits shared by both histories (the merge
+/// base and everything before it) are not counted.
+pub fn ahead_behind(store: &ObjectStore, local: &str, upstream: &str) -> (usize, usize) {
+    if local == upstream {
+        return (0, 0);
+    }
+
+    let local_history = ancestors(store, local);
+    let upstream_history = ancestors(store, upstream);
+    (
+        local_history.difference(&upstream_history).count(),
+        upstream_history.difference(&local_history).count(),
+    )
+}
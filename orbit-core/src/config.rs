@@ -0,0 +1,210 @@
+use std::fs;
+use std::path::Path;
+
+/// A single `[section]` or `[section "subsection"]` block of the config file
+#[derive(Debug, Clone)]
+struct Section {
+    name: String,
+    subsection: Option<String>,
+    entries: Vec<(String, String)>,
+}
+
+/// Repository configuration stored in `.orb/config`.
+///
+/// The file uses a simple INI format (the same one written by `orb init`).
+/// Keys are addressed as `section.key` or `section.subsection.key`,
+/// e.g. `net.timeout` or `remote.origin.url`.
+#[derive(Debug, Clone, Default)]
+pub struct Config {
+    sections: Vec<Section>,
+}
+
+impl Config {
+    /// Loads a config file, or returns an empty config if there is none
+    pub fn load(path: &Path) -> Result<Self, std::io::Error> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        Ok(Self::parse(&fs::read_to_string(path)?))
+    }
+
+    /// Parses config text, ignoring blank lines and `#`/`;` comments
+    pub fn parse(text: &str) -> Self {
+        let mut config = Config::default();
+
+        for line in text.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+                continue;
+            }
+
+            if line.starts_with('[') && line.ends_with(']') {
+                let header = &line[1..line.len() - 1];
+                let (name, subsection) = match header.find(' ') {
+                    Some(pos) => {
+                        let sub = header[pos + 1..].trim().trim_matches('"').to_string();
+                        (header[..pos].to_string(), Some(sub))
+                    }
+                    None => (header.to_string(), None),
+                };
+                config.sections.push(Section { name, subsection, entries: Vec::new() });
+            } else if let Some((key, value)) = line.split_once('=') {
+                if let Some(section) = config.sections.last_mut() {
+                    section.entries.push((key.trim().to_string(), value.trim().to_string()));
+                }
+            }
+        }
+
+        config
+    }
+
+    /// Gets a value by its dotted key
+    pub fn get(&self, key: &str) -> Option<&str> {
+        let (name, subsection, key) = split_key(key)?;
+        self.find_section(name, subsection)?
+            .entries
+            .iter()
+            .rev()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Gets a value and parses it, returning None if missing or malformed
+    pub fn get_parsed<T: std::str::FromStr>(&self, key: &str) -> Option<T> {
+        self.get(key).and_then(|v| v.parse().ok())
+    }
+
+    /// Sets a value by its dotted key, creating the section if needed
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        let (name, subsection, key) = split_key(key).ok_or_else(|| format!("invalid config key '{}'", key))?;
+        if self.find_section(name, subsection).is_none() {
+            self.sections.push(Section {
+                name: name.to_string(),
+                subsection: subsection.map(str::to_string),
+                entries: Vec::new(),
+            });
+        }
+
+        let section = self.find_section_mut(name, subsection).expect("section was just created");
+        match section.entries.iter_mut().find(|(k, _)| k == key) {
+            Some(entry) => entry.1 = value.to_string(),
+            None => section.entries.push((key.to_string(), value.to_string())),
+        }
+        Ok(())
+    }
+
+    /// Adds every setting of `other`, replacing the values of keys this config already has
+    pub fn merge(&mut self, other: &Config) {
+        for section in &other.sections {
+            for (key, value) in &section.entries {
+                let key = match &section.subsection {
+                    Some(subsection) => format!("{}.{}.{}", section.name, subsection, key),
+                    None => format!("{}.{}", section.name, key),
+                };
+                let _ = self.set(&key, value);
+            }
+        }
+    }
+
+    /// Names of all subsections of a section, e.g. every `[remote "..."]`
+    pub fn subsections(&self, name: &str) -> Vec<&str> {
+        self.sections
+            .iter()
+            .filter(|s| s.name == name)
+            .filter_map(|s| s.subsection.as_deref())
+            .collect()
+    }
+
+    /// Removes a `[name "subsection"]` block, returning false if it did not exist
+    pub fn remove_subsection(&mut self, name: &str, subsection: &str) -> bool {
+        let before = self.sections.len();
+        self.sections.retain(|s| !(s.name == name && s.subsection.as_deref() == Some(subsection)));
+        self.sections.len() != before
+    }
+
+    /// Renames a `[name "old"]` block to `[name "new"]`, returning false if it did not exist
+    pub fn rename_subsection(&mut self, name: &str, old: &str, new: &str) -> bool {
+        match self.find_section_mut(name, Some(old)) {
+            Some(section) => {
+                section.subsection = Some(new.to_string());
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Writes the config to a file
+    pub fn save(&self, path: &Path) -> Result<(), std::io::Error> {
+        fs::write(path, self.to_string())
+    }
+
+    fn find_section(&self, name: &str, subsection: Option<&str>) -> Option<&Section> {
+        self.sections
+            .iter()
+            .find(|s| s.name == name && s.subsection.as_deref() == subsection)
+    }
+
+    fn find_section_mut(&mut self, name: &str, subsection: Option<&str>) -> Option<&mut Section> {
+        self.sections
+            .iter_mut()
+            .find(|s| s.name == name && s.subsection.as_deref() == subsection)
+    }
+}
+
+impl std::fmt::Display for Config {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for section in &self.sections {
+            match &section.subsection {
+                Some(sub) => writeln!(f, "[{} \"{}\"]", section.name, sub)?,
+                None => writeln!(f, "[{}]", section.name)?,
+            }
+            for (key, value) in &section.entries {
+                writeln!(f, "{} = {}", key, value)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Splits `section[.subsection].key` into its parts
+fn split_key(key: &str) -> Option<(&str, Option<&str>, &str)> {
+    let first = key.find('.')?;
+    let last = key.rfind('.')?;
+    let name = &key[..first];
+    let subsection = if first == last { None } else { Some(&key[first + 1..last]) };
+    Some((name, subsection, &key[last + 1..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_roundtrip() {
+        let config = Config::parse("[core]\nversion = 0.1\n\n[net]\nretries = 5\n\n[remote \"origin\"]\nurl = orbit://host:8080/repo\n");
+        assert_eq!(config.get("core.version"), Some("0.1"));
+        assert_eq!(config.get("remote.origin.url"), Some("orbit://host:8080/repo"));
+        assert_eq!(config.get("remote.upstream.url"), None);
+
+        let reparsed = Config::parse(&config.to_string());
+        assert_eq!(reparsed.get_parsed::<u32>("net.retries"), Some(5));
+        assert_eq!(reparsed.get("remote.origin.url"), Some("orbit://host:8080/repo"));
+    }
+
+    #[test]
+    fn test_set_rename_and_remove_subsections() {
+        let mut config = Config::parse("[core]\nversion = 0.1\n");
+        config.set("remote.origin.url", "orbit://a:8080/x").unwrap();
+        config.set("remote.origin.url", "orbit://b:8080/x").unwrap();
+        config.set("remote.backup.url", "orbit://c:8080/x").unwrap();
+        assert_eq!(config.get("remote.origin.url"), Some("orbit://b:8080/x"));
+        assert_eq!(config.subsections("remote"), vec!["origin", "backup"]);
+
+        assert!(config.rename_subsection("remote", "backup", "mirror"));
+        assert_eq!(config.get("remote.mirror.url"), Some("orbit://c:8080/x"));
+        assert!(config.remove_subsection("remote", "origin"));
+        assert!(!config.remove_subsection("remote", "origin"));
+        assert_eq!(config.subsections("remote"), vec!["mirror"]);
+        assert_eq!(config.get("core.version"), Some("0.1"));
+    }
+}
@@ -0,0 +1,44 @@
+use std::path::PathBuf;
+use crate::objects::ObjectId;
+
+/// What can go wrong in a repository operation
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// The directory has no `.orb` repository
+    #[error("not an Orbit repository: {} (run `orb init` first)", .0.display())]
+    NotARepository(PathBuf),
+    /// `Repository::init` found an existing `.orb` repository
+    #[error("Orbit repository already initialized in {}", .0.display())]
+    AlreadyInitialized(PathBuf),
+    /// The operation needs a working tree, which a bare repository lacks
+    #[error("{} is a bare repository; it has no working tree", .0.display())]
+    BareRepository(PathBuf),
+    /// An object the operation needs is not stored
+    #[error("object {0} is missing")]
+    MissingObject(ObjectId),
+    /// A stored object is not the kind of object expected
+    #[error("object {id} is corrupt: {source}")]
+    Corrupt { id: ObjectId, source: serde_json::Error },
+    /// The operation needs a commit on the current branch
+    #[error("HEAD has no commits yet")]
+    NoCommits,
+    /// The operation cannot run until a conflicted merge is saved or aborted
+    #[error("a merge is in progress; finish it with `orb resolve` and `orb save`, or run `orb merge --abort`")]
+    MergeInProgress,
+    /// A merge left conflicted paths that have not been marked resolved
+    #[error("{0} path(s) still have merge conflicts; fix them and run `orb resolve <path>`")]
+    UnresolvedConflicts(usize),
+    /// A pathspec given to `orb add` matched no file
+    #[error("pathspec '{0}' did not match any files")]
+    NoMatchingFiles(String),
+    /// The remote's ref moved while a sync was updating it
+    #[error("{0} changed on the remote during the sync")]
+    RefUpdateRejected(String),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+}
+
+/// Result of repository operations
+pub type Result<T, E = Error> = std::result::Result<T, E>;
@@ -0,0 +1,201 @@
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+/// Ignore file read in every directory
+const ORBIGNORE: &str = ".orbignore";
+/// Git's ignore file, read too unless `core.gitignore` is false
+const GITIGNORE: &str = ".gitignore";
+
+/// One pattern line of an ignore file
+#[derive(Debug)]
+struct Rule {
+    pattern: String,
+    /// `!pattern`: re-includes what an earlier rule ignored
+    negate: bool,
+    /// `pattern/`: matches directories only
+    dir_only: bool,
+    /// A pattern with a `/` before its end matches paths relative to its ignore file's
+    /// directory; others match names at any depth
+    anchored: bool,
+}
+
+/// Which working-tree paths `orb save` and `orb check` skip, from the `.orbignore`
+/// (and `.gitignore`) files of each directory, with Git's pattern syntax. Rules of
+/// deeper files win over shallower ones, and `.orbignore` over `.gitignore` in the
+/// same directory. Tracked files are never ignored.
+pub struct Ignore {
+    root: PathBuf,
+    files: Vec<&'static str>,
+    tracked: HashSet<String>,
+    /// Directories holding tracked files, which are entered even if ignored
+    tracked_dirs: HashSet<String>,
+    /// Rules of each directory read so far, by path ("" for the root)
+    rules: RefCell<HashMap<String, Rc<Vec<Rule>>>>,
+}
+
+impl Ignore {
+    /// Rules of the working tree at `root`, never ignoring the `tracked` paths;
+    /// `.gitignore` files are read only if `use_gitignore`
+    pub fn load(root: &Path, use_gitignore: bool, tracked: impl IntoIterator<Item = String>) -> Self {
+        let files = if use_gitignore { vec![GITIGNORE, ORBIGNORE] } else { vec![ORBIGNORE] };
+        let tracked: HashSet<String> = tracked.into_iter().collect();
+        let mut tracked_dirs = HashSet::new();
+        for path in &tracked {
+            let mut dir = path.as_str();
+            while let Some((parent, _)) = dir.rsplit_once('/') {
+                if !tracked_dirs.insert(parent.to_string()) {
+                    break;
+                }
+                dir = parent;
+            }
+        }
+        Ignore { root: root.to_path_buf(), files, tracked, tracked_dirs, rules: RefCell::new(HashMap::new()) }
+    }
+
+    /// Whether `path` (relative to the repository root, `/`-separated) is ignored
+    pub fn is_ignored(&self, path: &str, is_dir: bool) -> bool {
+        if self.tracked.contains(path) || (is_dir && self.tracked_dirs.contains(path)) {
+            return false;
+        }
+        let mut ignored = false;
+        let mut base = "";
+        loop {
+            let relative = if base.is_empty() { path } else { &path[base.len() + 1..] };
+            for rule in self.rules_of(base).iter() {
+                if (!rule.dir_only || is_dir) && rule.matches(relative) {
+                    ignored = !rule.negate;
+                }
+            }
+            match relative.split_once('/') {
+                Some((dir, _)) => base = &path[..base.len() + if base.is_empty() { 0 } else { 1 } + dir.len()],
+                None => return ignored,
+            }
+        }
+    }
+
+    fn rules_of(&self, dir: &str) -> Rc<Vec<Rule>> {
+        if let Some(rules) = self.rules.borrow().get(dir) {
+            return rules.clone();
+        }
+        let rules: Vec<Rule> = self
+            .files
+            .iter()
+            .filter_map(|name| fs::read_to_string(self.root.join(dir).join(name)).ok())
+            .flat_map(|text| text.lines().filter_map(Rule::parse).collect::<Vec<_>>())
+            .collect();
+        let rules = Rc::new(rules);
+        self.rules.borrow_mut().insert(dir.to_string(), rules.clone());
+        rules
+    }
+}
+
+impl Rule {
+    fn parse(line: &str) -> Option<Rule> {
+        let line = line.trim_end();
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+        let (negate, line) = match line.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, line.strip_prefix('\\').filter(|rest| rest.starts_with(['#', '!'])).unwrap_or(line)),
+        };
+        let (dir_only, line) = match line.strip_suffix('/') {
+            Some(rest) => (true, rest),
+            None => (false, line),
+        };
+        let anchored = line.contains('/');
+        let pattern = line.strip_prefix('/').unwrap_or(line).to_string();
+        (!pattern.is_empty()).then_some(Rule { pattern, negate, dir_only, anchored })
+    }
+
+    /// Whether the rule matches a path relative to its ignore file's directory
+    fn matches(&self, relative: &str) -> bool {
+        if self.anchored {
+            glob_match(self.pattern.as_bytes(), relative.as_bytes())
+        } else {
+            let name = relative.rsplit('/').next().unwrap_or(relative);
+            glob_match(self.pattern.as_bytes(), name.as_bytes())
+        }
+    }
+}
+
+/// Matches a path against a Git wildcard pattern: `*` and `?` stay within a path
+/// segment, `[...]` is a character class, and `**/` spans any number of directories
+fn glob_match(pattern: &[u8], text: &[u8]) -> bool {
+    match pattern.first() {
+        None => text.is_empty(),
+        Some(b'*') if pattern.starts_with(b"**") => match &pattern[2..] {
+            [] => true,
+            [b'/', rest @ ..] => {
+                glob_match(rest, text) || text.iter().enumerate().any(|(i, &c)| c == b'/' && glob_match(rest, &text[i + 1..]))
+            }
+            _ => glob_match(&pattern[1..], text),
+        },
+        Some(b'*') => {
+            let segment = text.iter().position(|&c| c == b'/').unwrap_or(text.len());
+            (0..=segment).any(|i| glob_match(&pattern[1..], &text[i..]))
+        }
+        Some(b'?') => text.first().is_some_and(|&c| c != b'/') && glob_match(&pattern[1..], &text[1..]),
+        Some(b'[') => match (text.first(), class(pattern)) {
+            (Some(&c), Some((members, negated, length))) => {
+                c != b'/' && in_class(members, c) != negated && glob_match(&pattern[length..], &text[1..])
+            }
+            (None, Some(_)) => false,
+            (_, None) => text.first() == Some(&b'[') && glob_match(&pattern[1..], &text[1..]),
+        },
+        Some(b'\\') if pattern.len() > 1 => text.first() == Some(&pattern[1]) && glob_match(&pattern[2..], &text[1..]),
+        Some(&c) => text.first() == Some(&c) && glob_match(&pattern[1..], &text[1..]),
+    }
+}
+
+/// The members of a `[...]` class at the start of `pattern`, whether it is negated, and
+/// the class's length; None if the bracket is never closed
+fn class(pattern: &[u8]) -> Option<(&[u8], bool, usize)> {
+    let negated = matches!(pattern.get(1), Some(b'!' | b'^'));
+    let start = if negated { 2 } else { 1 };
+    // A `]` right after the opening bracket is a member, not the end
+    let end = start + 1 + pattern.get(start + 1..)?.iter().position(|&c| c == b']')?;
+    Some((&pattern[start..end], negated, end + 1))
+}
+
+fn in_class(members: &[u8], c: u8) -> bool {
+    let mut i = 0;
+    while i < members.len() {
+        if i + 2 < members.len() && members[i + 1] == b'-' {
+            if (members[i]..=members[i + 2]).contains(&c) {
+                return true;
+            }
+            i += 3;
+        } else {
+            if members[i] == c {
+                return true;
+            }
+            i += 1;
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gitignore_patterns() {
+        let rule = |line: &str| Rule::parse(line).unwrap();
+        assert!(rule("*.o").matches("src/main.o"));
+        assert!(!rule("/build").matches("src/build"));
+        assert!(rule("/build").matches("build"));
+        assert!(rule("doc/*.txt").matches("doc/notes.txt"));
+        assert!(!rule("doc/*.txt").matches("doc/api/notes.txt"));
+        assert!(rule("**/logs").matches("a/b/logs"));
+        assert!(rule("logs/**").matches("logs/debug/x.log"));
+        assert!(rule("a/**/b").matches("a/b") && rule("a/**/b").matches("a/x/y/b"));
+        assert!(rule("file[0-9].txt").matches("file7.txt") && !rule("file[!0-9].txt").matches("file7.txt"));
+        assert!(rule("target/").dir_only && rule("!keep.o").negate);
+        assert!(Rule::parse("# comment").is_none());
+    }
+}
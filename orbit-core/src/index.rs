@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::fs;
 use std::path::Path;
 use std::time::UNIX_EPOCH;
@@ -14,11 +14,26 @@ pub struct IndexEntry {
     pub file_id: ObjectId, // The File object ID from VOS
 }
 
+/// A file version `orb add` recorded for the next save, with the metadata it had then
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StagedFile {
+    pub mode: u32,
+    pub mtime: u64,
+    pub size: u64,
+    pub file_id: ObjectId,
+}
+
 /// The VOS Index - tracks metadata of all files in the last saved snapshot
 #[derive(Debug, Serialize, Deserialize)]
 pub struct VosIndex {
     pub version: u32,
     pub entries: HashMap<String, IndexEntry>,
+    /// Paths a merge left with conflict markers, until `orb resolve` marks them resolved
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub conflicts: BTreeSet<String>,
+    /// Paths staged by `orb add` for the next save; None stages a deletion
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub staged: BTreeMap<String, Option<StagedFile>>,
 }
 
 impl VosIndex {
@@ -27,13 +42,13 @@ impl VosIndex {
         Self {
             version: 1,
             entries: HashMap::new(),
+            conflicts: BTreeSet::new(),
+            staged: BTreeMap::new(),
         }
     }
 
-    /// Loads the VOS Index from disk, or creates a new one if it doesn't exist
-    pub fn load() -> Result<Self, Box<dyn std::error::Error>> {
-        let index_path = Path::new(".orb").join("index");
-        
+    /// Loads the VOS Index (`.orb/index`), or creates a new one if it doesn't exist
+    pub fn load(index_path: &Path) -> Result<Self, std::io::Error> {
         if !index_path.exists() {
             return Ok(Self::new());
         }
@@ -44,8 +59,7 @@ impl VosIndex {
     }
 
     /// Saves the VOS Index to disk
-    pub fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
-        let index_path = Path::new(".orb").join("index");
+    pub fn save(&self, index_path: &Path) -> Result<(), std::io::Error> {
         let data = serde_json::to_string_pretty(self)?;
         fs::write(index_path, data)?;
         Ok(())
@@ -63,31 +77,32 @@ impl VosIndex {
     }
 
     /// Removes an entry from the index
-    #[allow(dead_code)]
     pub fn remove_entry(&mut self, path: &str) {
         self.entries.remove(path);
     }
 
     /// Gets file metadata for comparison
-    pub fn get_file_metadata(file_path: &Path) -> Result<(u64, u64), Box<dyn std::error::Error>> {
-        let metadata = fs::metadata(file_path)?;
+    pub fn get_file_metadata(file_path: &Path) -> Result<(u64, u64), std::io::Error> {
+        // A symbolic link's own metadata, as links are stored rather than followed
+        let metadata = fs::symlink_metadata(file_path)?;
         let mtime = metadata
             .modified()?
-            .duration_since(UNIX_EPOCH)?
+            .duration_since(UNIX_EPOCH)
+            .map_err(std::io::Error::other)?
             .as_secs();
         let size = metadata.len();
         Ok((mtime, size))
     }
 
     /// Checks if a file has changed compared to the index
-    pub fn has_file_changed(&self, path: &str, file_path: &Path) -> Result<bool, Box<dyn std::error::Error>> {
+    pub fn has_file_changed(&self, path: &str, file_path: &Path) -> Result<bool, std::io::Error> {
         // If file is not in index, it's new/untracked
         let Some(entry) = self.entries.get(path) else {
             return Ok(true);
         };
 
         // Check if file still exists
-        if !file_path.exists() {
+        if fs::symlink_metadata(file_path).is_err() {
             return Ok(true); // File was deleted
         }
 
@@ -108,4 +123,10 @@ impl VosIndex {
     pub fn clear(&mut self) {
         self.entries.clear();
     }
-}
\ No newline at end of file
+}
+
+impl Default for VosIndex {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,34 @@
+//! The Orbit repository engine behind the `orb` command line, for GUIs, editor plugins,
+//! and servers that embed Orbit.
+//!
+//! A [`Repository`] is opened at (or initialized in) a working directory and addresses
+//! everything relative to it, so several repositories can be used side by side and the
+//! process's current directory never matters. Operations return data instead of
+//! printing: [`Repository::status`] lists changes, [`Repository::history`] returns
+//! commits, and [`Repository::sync`] reports what it did as a [`SyncOutcome`].
+//!
+//! ```no_run
+//! use orbit_core::Repository;
+//!
+//! let repository = Repository::open("path/to/project")?;
+//! for change in repository.status()? {
+//!     println!("{:?} {}", change.status, change.path);
+//! }
+//! let commit = repository.save("Update docs", "Ada <ada@example.com>")?;
+//! # Ok::<(), orbit_core::Error>(())
+//! ```
+
+pub mod ancestry;
+pub mod config;
+mod error;
+pub mod ignore;
+pub mod index;
+pub mod objects;
+pub mod refs;
+mod repository;
+mod sync;
+pub mod vos;
+
+pub use error::{Error, Result};
+pub use repository::{file_mode, matches_pathspec, normalize_pathspec, Change, FileMap, FileStatus, Repository, ORB_DIR};
+pub use sync::{Remote, SyncOutcome};
@@ -0,0 +1,112 @@
+use serde::{Serialize, Deserialize};
+
+// --- Type Aliases for Clarity and Security ---
+
+// Object ID: A 32-byte SHA-3 (Keccak-256) hash, represented as a hex string.
+pub type ObjectId = String; 
+
+// --- Core VOS Objects ---
+
+// 1. The Chunk (Blob) Object
+// In VOS, the raw file content is broken into Chunks.
+// We don't need a specific struct for the *content* itself, 
+// as it's just raw bytes stored by its ID (hash).
+
+/// 2. The File (Merkle Tree Root) Object
+///
+/// This object replaces Git's 'Blob' for files and holds the Merkle root
+/// hash, proving the integrity and sequence of all data chunks.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct File {
+    // The ID of the root chunk (or Merkle root) that points to all file data.
+    // This is the true ID of the file's content.
+    pub root_chunk_id: ObjectId, 
+    pub size: usize,
+    /// Content-defined chunks in order, when the content spans more than one; the root
+    /// chunk ID is then their Merkle root rather than a stored object
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub chunks: Vec<ObjectId>,
+}
+
+impl File {
+    /// IDs of the stored chunks holding the content, in order
+    pub fn chunk_ids(&self) -> &[ObjectId] {
+        if self.chunks.is_empty() {
+            std::slice::from_ref(&self.root_chunk_id)
+        } else {
+            &self.chunks
+        }
+    }
+
+    /// Reassembles the content from its chunks, each read with `load`
+    pub fn content<E>(&self, mut load: impl FnMut(&ObjectId) -> Result<Vec<u8>, E>) -> Result<Vec<u8>, E> {
+        let mut content = Vec::with_capacity(self.size);
+        for chunk_id in self.chunk_ids() {
+            content.extend_from_slice(&load(chunk_id)?);
+        }
+        Ok(content)
+    }
+}
+
+/// 3. The Directory (Tree) Object
+///
+/// This object is equivalent to Git's 'Tree' and represents a folder snapshot.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DirectoryEntry {
+    pub mode: u32,             // File permissions/type (e.g., file, directory)
+    pub name: String,
+    pub id: ObjectId,          // ID of the File or nested Directory object
+}
+
+/// A container for all entries in a directory.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Directory {
+    pub entries: Vec<DirectoryEntry>,
+}
+
+/// 4. The Commit (DAG Node) Object
+///
+/// This object is the node in our Directed Acyclic Graph.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Commit {
+    // ID of the root Directory object for this snapshot.
+    pub tree: ObjectId, 
+    // Parent commits (0 for initial, 1 for normal, 2+ for merge).
+    pub parents: Vec<ObjectId>, 
+    pub author: String,
+    pub timestamp: i64,
+    pub message: String,
+    // PQC Signature (Placeholder for full implementation in later versions)
+    pub signature: Option<String>, 
+}
+
+/// 5. The Tag (Annotation) Object
+///
+/// Records who tagged a commit, when, and why, like Git's annotated tag. The tag
+/// ref itself still points at the commit; `refs/annotations/<tag>` points here.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Tag {
+    /// The tagged commit
+    pub object: ObjectId,
+    pub tag: String,
+    pub tagger: String,
+    pub timestamp: i64,
+    pub message: String,
+    /// Detached ASCII-armored signature over the tag serialized without it
+    pub signature: Option<String>,
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_content_spans_its_chunks() {
+        let single: File = serde_json::from_str(r#"{"root_chunk_id":"aa","size":2}"#).unwrap();
+        assert_eq!(single.chunk_ids(), ["aa".to_string()]);
+        assert!(!serde_json::to_string(&single).unwrap().contains("chunks"));
+
+        let chunked = File { root_chunk_id: "root".to_string(), size: 4, chunks: vec!["aa".to_string(), "bb".to_string()] };
+        let content = chunked.content(|id| Ok::<_, ()>(id.as_bytes().to_vec())).unwrap();
+        assert_eq!(content, b"aabb");
+    }
+}
@@ -0,0 +1,93 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use crate::objects::ObjectId;
+
+/// Branch used when HEAD is missing or does not name a branch
+pub const DEFAULT_BRANCH: &str = "main";
+
+/// Name of the branch HEAD points to (`ref: refs/heads/<branch>`)
+pub fn current_branch(orb_dir: &Path) -> String {
+    fs::read_to_string(orb_dir.join("HEAD"))
+        .ok()
+        .and_then(|head| head.trim().strip_prefix("ref: refs/heads/").map(str::to_string))
+        .unwrap_or_else(|| DEFAULT_BRANCH.to_string())
+}
+
+/// Points HEAD at a local branch (`ref: refs/heads/<branch>`)
+pub fn set_head_branch(orb_dir: &Path, branch: &str) -> Result<(), std::io::Error> {
+    fs::write(orb_dir.join("HEAD"), format!("ref: {}\n", branch_ref(branch)))
+}
+
+/// Reads a ref such as `refs/heads/main`, returning None if it is absent or empty
+pub fn read_ref(orb_dir: &Path, name: &str) -> Option<ObjectId> {
+    let id = fs::read_to_string(orb_dir.join(name)).ok()?;
+    let id = id.trim();
+    (!id.is_empty()).then(|| id.to_string())
+}
+
+/// Points a ref at a commit, creating parent directories as needed
+pub fn write_ref(orb_dir: &Path, name: &str, commit_id: &str) -> Result<(), std::io::Error> {
+    let path = orb_dir.join(name);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, commit_id)
+}
+
+/// Removes a ref; a ref that does not exist is not an error
+pub fn delete_ref(orb_dir: &Path, name: &str) -> Result<(), std::io::Error> {
+    match fs::remove_file(orb_dir.join(name)) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+/// Ref of a local branch, e.g. `refs/heads/main`
+pub fn branch_ref(branch: &str) -> String {
+    format!("refs/heads/{}", branch)
+}
+
+/// Ref of a tag, e.g. `refs/tags/v1.0`
+pub fn tag_ref(tag: &str) -> String {
+    format!("refs/tags/{}", tag)
+}
+
+/// Ref of a tag's annotation (its Tag object), e.g. `refs/annotations/v1.0`
+pub fn annotation_ref(tag: &str) -> String {
+    format!("refs/annotations/{}", tag)
+}
+
+/// All local tags and the objects they point to
+pub fn list_tags(orb_dir: &Path) -> BTreeMap<String, ObjectId> {
+    list_refs(orb_dir, "tags", tag_ref)
+}
+
+/// Tag objects of annotated tags, by tag name
+pub fn list_annotations(orb_dir: &Path) -> BTreeMap<String, ObjectId> {
+    list_refs(orb_dir, "annotations", annotation_ref)
+}
+
+/// All local branches and their tip commits
+pub fn list_branches(orb_dir: &Path) -> BTreeMap<String, ObjectId> {
+    list_refs(orb_dir, "heads", branch_ref)
+}
+
+fn list_refs(orb_dir: &Path, kind: &str, to_ref: fn(&str) -> String) -> BTreeMap<String, ObjectId> {
+    let Ok(entries) = fs::read_dir(orb_dir.join("refs").join(kind)) else {
+        return BTreeMap::new();
+    };
+
+    entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            read_ref(orb_dir, &to_ref(&name)).map(|id| (name, id))
+        })
+        .collect()
+}
+
+/// Commit at the tip of the current branch
+pub fn head_commit(orb_dir: &Path) -> Option<ObjectId> {
+    read_ref(orb_dir, &branch_ref(&current_branch(orb_dir)))
+}
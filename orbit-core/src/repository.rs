@@ -0,0 +1,787 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use crate::config::Config;
+use crate::ignore::Ignore;
+use crate::index::{StagedFile, VosIndex};
+use crate::objects::{Commit, Directory, DirectoryEntry, ObjectId};
+use crate::vos::ObjectStore;
+use crate::{refs, Error, Result};
+
+/// Name of the repository directory inside a working tree
+pub const ORB_DIR: &str = ".orb";
+
+/// File in `.orb` naming the commit being merged while a merge has conflicts
+const MERGE_HEAD: &str = "MERGE_HEAD";
+
+/// Files of a tree by path, with their mode and File object ID
+pub type FileMap = BTreeMap<String, (u32, ObjectId)>;
+
+/// How a working-tree file differs from the last saved or checked-out snapshot
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileStatus {
+    Modified,
+    Untracked,
+    Deleted,
+}
+
+/// One changed path of `Repository::status`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Change {
+    pub path: String,
+    pub status: FileStatus,
+}
+
+/// An Orbit repository: a working tree and the `.orb` directory holding its objects,
+/// refs, config, and index
+#[derive(Debug, Clone)]
+pub struct Repository {
+    root: PathBuf,
+    orb_dir: PathBuf,
+    store: ObjectStore,
+}
+
+impl Repository {
+    /// Creates an empty repository in `root` (created if missing), with HEAD on main
+    pub fn init(root: impl AsRef<Path>) -> Result<Self> {
+        Self::create(root.as_ref(), false)
+    }
+
+    /// Creates an empty bare repository in `root`: it has no working tree, so `save` and
+    /// `status` refuse to run, and it is laid out like the repositories `orb serve` hosts
+    pub fn init_bare(root: impl AsRef<Path>) -> Result<Self> {
+        Self::create(root.as_ref(), true)
+    }
+
+    fn create(root: &Path, bare: bool) -> Result<Self> {
+        let orb_dir = root.join(ORB_DIR);
+        if orb_dir.exists() {
+            return Err(Error::AlreadyInitialized(root.to_path_buf()));
+        }
+
+        fs::create_dir_all(root)?;
+        fs::create_dir(&orb_dir)?;
+        fs::create_dir(orb_dir.join("objects"))?;
+        fs::create_dir(orb_dir.join("refs"))?;
+        let bare = if bare { "bare = true\n" } else { "" };
+        fs::write(orb_dir.join("config"), format!("[core]\nversion = 0.1\nhash_algorithm = sha3-256\n{}", bare))?;
+        refs::set_head_branch(&orb_dir, refs::DEFAULT_BRANCH)?;
+        Self::open(root)
+    }
+
+    /// Opens the repository whose working tree is `root`
+    pub fn open(root: impl AsRef<Path>) -> Result<Self> {
+        let root = root.as_ref().to_path_buf();
+        let orb_dir = root.join(ORB_DIR);
+        if !orb_dir.is_dir() {
+            return Err(Error::NotARepository(root));
+        }
+        let store = ObjectStore::new(orb_dir.join("objects"));
+        Ok(Repository { root, orb_dir, store })
+    }
+
+    /// The working tree
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// The `.orb` directory
+    pub fn orb_dir(&self) -> &Path {
+        &self.orb_dir
+    }
+
+    /// Whether the repository has no working tree (`core.bare`)
+    pub fn is_bare(&self) -> bool {
+        self.config().ok().and_then(|config| config.get_parsed::<bool>("core.bare")).unwrap_or(false)
+    }
+
+    /// Fails for a bare repository, whose directory is not a working tree
+    fn require_worktree(&self) -> Result<()> {
+        match self.is_bare() {
+            true => Err(Error::BareRepository(self.root.clone())),
+            false => Ok(()),
+        }
+    }
+
+    /// The object store in `.orb/objects`
+    pub fn store(&self) -> &ObjectStore {
+        &self.store
+    }
+
+    /// The repository's `.orb/config`
+    pub fn config(&self) -> Result<Config> {
+        Ok(Config::load(&self.orb_dir.join("config"))?)
+    }
+
+    /// The VOS Index of the last saved or checked-out snapshot
+    pub fn index(&self) -> Result<VosIndex> {
+        Ok(VosIndex::load(&self.orb_dir.join("index"))?)
+    }
+
+    /// Writes the VOS Index, e.g. after recording or resolving conflicts
+    pub fn save_index(&self, index: &VosIndex) -> Result<()> {
+        Ok(index.save(&self.orb_dir.join("index"))?)
+    }
+
+    /// Name of the branch HEAD points to
+    pub fn current_branch(&self) -> String {
+        refs::current_branch(&self.orb_dir)
+    }
+
+    /// Commit at the tip of the current branch, None before the first save
+    pub fn head(&self) -> Option<ObjectId> {
+        refs::head_commit(&self.orb_dir)
+    }
+
+    /// Reads a ref such as `refs/heads/main`
+    pub fn read_ref(&self, name: &str) -> Option<ObjectId> {
+        refs::read_ref(&self.orb_dir, name)
+    }
+
+    /// Points a ref at a commit
+    pub fn write_ref(&self, name: &str, commit_id: &str) -> Result<()> {
+        Ok(refs::write_ref(&self.orb_dir, name, commit_id)?)
+    }
+
+    /// Loads a commit object
+    pub fn load_commit(&self, commit_id: &str) -> Result<Commit> {
+        self.store.load_json(commit_id)
+    }
+
+    /// Files of a commit's tree
+    pub fn commit_files(&self, commit_id: &str) -> Result<FileMap> {
+        self.tree_files(&self.load_commit(commit_id)?.tree)
+    }
+
+    /// Flattens a tree into a path → (mode, ID) map
+    pub fn tree_files(&self, tree_id: &str) -> Result<FileMap> {
+        let mut files = FileMap::new();
+        self.collect_files(tree_id, "", &mut files)?;
+        Ok(files)
+    }
+
+    /// The file at `path` in a tree, or None if the tree has no file there
+    pub fn tree_entry(&self, tree_id: &str, path: &str) -> Result<Option<(u32, ObjectId)>> {
+        let mut tree = tree_id.to_string();
+        let mut names = path.split('/').peekable();
+        while let Some(name) = names.next() {
+            let directory: Directory = self.store.load_json(&tree)?;
+            let Some(entry) = directory.entries.into_iter().find(|entry| entry.name == name) else { return Ok(None) };
+            match (names.peek(), entry.mode == 0o040000) {
+                (None, false) => return Ok(Some((entry.mode, entry.id))),
+                (Some(_), true) => tree = entry.id,
+                _ => return Ok(None),
+            }
+        }
+        Ok(None)
+    }
+
+    /// The files that differ between two trees (None for an empty tree), as the entries
+    /// each side has for them. Subtrees with the same ID on both sides are not read.
+    pub fn diff_trees(&self, old: Option<&str>, new: Option<&str>) -> Result<(FileMap, FileMap)> {
+        let (mut old_files, mut new_files) = (FileMap::new(), FileMap::new());
+        self.diff_subtrees(old, new, "", &mut old_files, &mut new_files)?;
+        Ok((old_files, new_files))
+    }
+
+    fn diff_subtrees(&self, old: Option<&str>, new: Option<&str>, prefix: &str, old_files: &mut FileMap, new_files: &mut FileMap) -> Result<()> {
+        if old == new {
+            return Ok(());
+        }
+        let entries = |tree: Option<&str>| -> Result<FileMap> {
+            let Some(tree) = tree else { return Ok(FileMap::new()) };
+            let directory: Directory = self.store.load_json(tree)?;
+            Ok(directory.entries.into_iter().map(|entry| (entry.name, (entry.mode, entry.id))).collect())
+        };
+        let (old_entries, new_entries) = (entries(old)?, entries(new)?);
+        let names: BTreeSet<&String> = old_entries.keys().chain(new_entries.keys()).collect();
+        for name in names {
+            let (old_entry, new_entry) = (old_entries.get(name), new_entries.get(name));
+            if old_entry == new_entry {
+                continue;
+            }
+            let path = if prefix.is_empty() { name.clone() } else { format!("{}/{}", prefix, name) };
+            if subtree(old_entry).is_some() || subtree(new_entry).is_some() {
+                self.diff_subtrees(subtree(old_entry), subtree(new_entry), &path, old_files, new_files)?;
+            }
+            // A path can change between a file and a directory
+            if let Some(entry) = old_entry.filter(|(mode, _)| *mode != 0o040000) {
+                old_files.insert(path.clone(), entry.clone());
+            }
+            if let Some(entry) = new_entry.filter(|(mode, _)| *mode != 0o040000) {
+                new_files.insert(path, entry.clone());
+            }
+        }
+        Ok(())
+    }
+
+    fn collect_files(&self, tree_id: &str, prefix: &str, files: &mut FileMap) -> Result<()> {
+        let directory: Directory = self.store.load_json(tree_id)?;
+        for entry in directory.entries {
+            let path = if prefix.is_empty() { entry.name } else { format!("{}/{}", prefix, entry.name) };
+            if entry.mode == 0o040000 {
+                self.collect_files(&entry.id, &path, files)?;
+            } else {
+                files.insert(path, (entry.mode, entry.id));
+            }
+        }
+        Ok(())
+    }
+
+    /// Snapshots the working tree as a commit on the current branch and returns its ID.
+    /// Paths matched by `.orbignore`/`.gitignore` are skipped unless the parent commit
+    /// tracks them, and the index is rebuilt for fast status checks.
+    ///
+    /// When paths were staged with `add`, the commit is instead the parent's tree with
+    /// only the staged changes applied; other changes stay in the working tree.
+    pub fn save(&self, message: &str, author: &str) -> Result<ObjectId> {
+        self.save_with_progress(message, author, &mut |_, _| {})
+    }
+
+    /// Like `save`, calling `progress` with the path and size of each file once it is
+    /// hashed and stored, so long first commits can report how far they got
+    ///
+    /// While a merge is in progress, the commit also has the merged commit as a parent,
+    /// every conflicted path must have been resolved, and the whole working tree is
+    /// saved even if paths were staged.
+    pub fn save_with_progress(&self, message: &str, author: &str, progress: &mut dyn FnMut(&str, u64)) -> Result<ObjectId> {
+        let parent = self.head();
+        let parents = parent.iter().cloned().chain(self.merge_head()).collect();
+        self.commit_worktree(parent.as_deref(), parents, message, author, progress)
+    }
+
+    /// Replaces HEAD with a commit of the working tree (or of HEAD plus the staged
+    /// changes) that keeps HEAD's parents and author. `message` None reuses HEAD's
+    /// message. Returns the new commit's ID.
+    pub fn amend_with_progress(&self, message: Option<&str>, progress: &mut dyn FnMut(&str, u64)) -> Result<ObjectId> {
+        let head = self.head().ok_or(Error::NoCommits)?;
+        if self.merge_head().is_some() {
+            return Err(Error::MergeInProgress);
+        }
+        let commit = self.load_commit(&head)?;
+        self.commit_worktree(Some(&head), commit.parents, message.unwrap_or(&commit.message), &commit.author, progress)
+    }
+
+    /// Saves a commit with `parents` on the current branch. Its tree is the working
+    /// tree, or `base`'s tree with the staged changes applied.
+    fn commit_worktree(&self, base: Option<&str>, parents: Vec<ObjectId>, message: &str, author: &str, progress: &mut dyn FnMut(&str, u64)) -> Result<ObjectId> {
+        self.require_worktree()?;
+        let mut index = self.index()?;
+        if !index.conflicts.is_empty() {
+            return Err(Error::UnresolvedConflicts(index.conflicts.len()));
+        }
+        let merged = self.merge_head();
+        let tracked = match base {
+            Some(base) => self.commit_files(base).unwrap_or_default(),
+            None => FileMap::new(),
+        };
+
+        let tree = if index.staged.is_empty() || merged.is_some() {
+            let ignore = self.ignore(tracked.into_keys());
+            index = VosIndex::new();
+            self.save_tree(&self.root, "", &mut index, &ignore, progress)?
+        } else {
+            self.save_staged(tracked, &mut index)?
+        };
+        index.save(&self.orb_dir.join("index"))?;
+
+        let commit = Commit {
+            tree,
+            parents,
+            author: author.to_string(),
+            timestamp: SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |now| now.as_secs() as i64),
+            message: message.to_string(),
+            signature: None,
+        };
+        let commit_id = self.store.save_json(&commit)?;
+        self.write_ref(&refs::branch_ref(&self.current_branch()), &commit_id)?;
+        self.set_merge_head(None)?;
+        Ok(commit_id)
+    }
+
+    /// The tree of `files` with the staged changes applied. The staged versions become
+    /// the index entries of their paths, so later edits still show as modified.
+    fn save_staged(&self, mut files: FileMap, index: &mut VosIndex) -> Result<ObjectId> {
+        for (path, staged) in std::mem::take(&mut index.staged) {
+            match staged {
+                Some(file) => {
+                    index.update_entry(path.clone(), file.mtime, file.size, file.file_id.clone());
+                    files.insert(path, (file.mode, file.file_id));
+                }
+                None => {
+                    index.remove_entry(&path);
+                    files.remove(&path);
+                }
+            }
+        }
+        self.build_tree(&files)
+    }
+
+    /// Saves nested Directory objects for a flat file map and returns the root tree ID
+    fn build_tree(&self, files: &FileMap) -> Result<ObjectId> {
+        let mut entries = Vec::new();
+        let mut subdirs: BTreeMap<&str, FileMap> = BTreeMap::new();
+        for (path, (mode, id)) in files {
+            match path.split_once('/') {
+                Some((dir, rest)) => {
+                    subdirs.entry(dir).or_default().insert(rest.to_string(), (*mode, id.clone()));
+                }
+                None => entries.push(DirectoryEntry { mode: *mode, name: path.clone(), id: id.clone() }),
+            }
+        }
+        for (name, sub_files) in subdirs {
+            entries.push(DirectoryEntry { mode: 0o040000, name: name.to_string(), id: self.build_tree(&sub_files)? });
+        }
+        entries.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(self.store.save_json(&Directory { entries })?)
+    }
+
+    /// Stages every file matching `pathspecs` (a file, or a directory and everything
+    /// below it; `.` is the whole tree) for the next save. Tracked files that no longer
+    /// exist are staged as deleted, and files that match HEAD again are unstaged.
+    /// Returns the paths whose staged version changed.
+    pub fn add(&self, pathspecs: &[&str]) -> Result<Vec<String>> {
+        self.require_worktree()?;
+        let mut index = self.index()?;
+        let tracked = match self.head() {
+            Some(head) => self.commit_files(&head)?,
+            None => FileMap::new(),
+        };
+        let ignore = self.ignore(tracked.keys().cloned());
+        let mut present = Vec::new();
+        self.scan(&self.root, "", &mut present, &ignore)?;
+        let deleted: Vec<&String> = tracked.keys().filter(|path| fs::symlink_metadata(self.root.join(path)).is_err()).collect();
+
+        let mut changed = BTreeSet::new();
+        for pathspec in pathspecs {
+            let pathspec = normalize_pathspec(pathspec);
+            let mut matched = false;
+            for path in present.iter().filter(|path| matches_pathspec(path, &pathspec)) {
+                matched = true;
+                let full_path = self.root.join(path);
+                let metadata = fs::symlink_metadata(&full_path)?;
+                let head = tracked.get(path);
+                let staged = match index.entries.get(path) {
+                    // Unchanged since HEAD was saved or checked out: nothing to hash
+                    Some(entry) if head.is_some_and(|(_, id)| *id == entry.file_id) && !index.has_file_changed(path, &full_path)? => None,
+                    _ => {
+                        let (mode, file_id) = match metadata.file_type().is_symlink() {
+                            true => (0o120000, self.store.save_symlink(&full_path)?),
+                            false => (file_mode(&metadata), self.store.save_file(&full_path)?.0),
+                        };
+                        let (mtime, size) = VosIndex::get_file_metadata(&full_path).unwrap_or((0, 0));
+                        let same_as_head = head.is_some_and(|(head_mode, id)| (*head_mode, id) == (mode, &file_id));
+                        (!same_as_head).then_some(Some(StagedFile { mode, mtime, size, file_id }))
+                    }
+                };
+                if stage(&mut index, path, staged) {
+                    changed.insert(path.clone());
+                }
+            }
+            for path in deleted.iter().filter(|path| matches_pathspec(path, &pathspec)) {
+                matched = true;
+                if stage(&mut index, path, Some(None)) {
+                    changed.insert(path.to_string());
+                }
+            }
+            if !matched {
+                return Err(Error::NoMatchingFiles(pathspec));
+            }
+        }
+        self.save_index(&index)?;
+        Ok(changed.into_iter().collect())
+    }
+
+    /// Removes the staged changes of paths matching `pathspecs`, leaving the working
+    /// tree alone. Returns the paths that were unstaged.
+    pub fn unstage(&self, pathspecs: &[&str]) -> Result<Vec<String>> {
+        let mut index = self.index()?;
+        let pathspecs: Vec<String> = pathspecs.iter().map(|pathspec| normalize_pathspec(pathspec)).collect();
+        let unstaged: Vec<String> = index
+            .staged
+            .keys()
+            .filter(|path| pathspecs.iter().any(|pathspec| matches_pathspec(path, pathspec)))
+            .cloned()
+            .collect();
+        for path in &unstaged {
+            index.staged.remove(path);
+        }
+        self.save_index(&index)?;
+        Ok(unstaged)
+    }
+
+    /// The commit being merged into HEAD, while a merge with conflicts is in progress
+    pub fn merge_head(&self) -> Option<ObjectId> {
+        refs::read_ref(&self.orb_dir, MERGE_HEAD)
+    }
+
+    /// Records the commit being merged (the next save's second parent), or with None
+    /// ends the merge
+    pub fn set_merge_head(&self, commit_id: Option<&str>) -> Result<()> {
+        match commit_id {
+            Some(commit_id) => self.write_ref(MERGE_HEAD, commit_id),
+            None => Ok(refs::delete_ref(&self.orb_dir, MERGE_HEAD)?),
+        }
+    }
+
+    /// Recursively chunks and saves the files of a directory, returning the ID of its
+    /// Directory object; every saved file is recorded in the index
+    fn save_tree(&self, path: &Path, current_path: &str, index: &mut VosIndex, ignore: &Ignore, progress: &mut dyn FnMut(&str, u64)) -> Result<ObjectId> {
+        let mut entries = Vec::new();
+        for entry in fs::read_dir(path)? {
+            let entry_path = entry?.path();
+            let file_name = entry_path.file_name().unwrap_or_default().to_string_lossy().to_string();
+            if file_name == ORB_DIR {
+                continue;
+            }
+
+            let full_path = if current_path.is_empty() { file_name.clone() } else { format!("{}/{}", current_path, file_name) };
+            let metadata = fs::symlink_metadata(&entry_path)?;
+            if ignore.is_ignored(&full_path, metadata.is_dir()) {
+                continue;
+            }
+
+            let (mode, id) = if metadata.file_type().is_symlink() {
+                // Symbolic links are stored as their target, not followed
+                let link_id = self.store.save_symlink(&entry_path)?;
+                let (mtime, size) = VosIndex::get_file_metadata(&entry_path).unwrap_or((0, 0));
+                index.update_entry(full_path, mtime, size, link_id.clone());
+                (0o120000, link_id)
+            } else if metadata.is_dir() {
+                (0o040000, self.save_tree(&entry_path, &full_path, index, ignore, progress)?)
+            } else if metadata.is_file() {
+                let (file_id, _) = self.store.save_file(&entry_path)?;
+                let (mtime, size) = VosIndex::get_file_metadata(&entry_path).unwrap_or((0, 0));
+                progress(&full_path, size);
+                index.update_entry(full_path, mtime, size, file_id.clone());
+                (file_mode(&metadata), file_id)
+            } else {
+                continue;
+            };
+            entries.push(DirectoryEntry { mode, name: file_name, id });
+        }
+
+        Ok(self.store.save_json(&Directory { entries })?)
+    }
+
+    /// Working-tree changes since the last saved or checked-out snapshot. Files whose
+    /// size and modification time match the index are not read.
+    pub fn status(&self) -> Result<Vec<Change>> {
+        self.require_worktree()?;
+        let index = self.index()?;
+        let mut changes = Vec::new();
+
+        for (path, entry) in &index.entries {
+            let file_path = self.root.join(path);
+            let Ok(metadata) = fs::symlink_metadata(&file_path) else {
+                changes.push(Change { path: path.clone(), status: FileStatus::Deleted });
+                continue;
+            };
+            if index.has_file_changed(path, &file_path).unwrap_or(true) {
+                // Metadata changed; the content decides
+                let file_id = match metadata.file_type().is_symlink() {
+                    true => self.store.save_symlink(&file_path)?,
+                    false => self.store.save_file(&file_path)?.0,
+                };
+                if file_id != entry.file_id {
+                    changes.push(Change { path: path.clone(), status: FileStatus::Modified });
+                }
+            }
+        }
+
+        let ignore = self.ignore(index.entries.keys().cloned());
+        let mut present = Vec::new();
+        self.scan(&self.root, "", &mut present, &ignore)?;
+        changes.extend(
+            present
+                .into_iter()
+                .filter(|path| !index.entries.contains_key(path))
+                .map(|path| Change { path, status: FileStatus::Untracked }),
+        );
+        Ok(changes)
+    }
+
+    /// Working tree changes not staged for the next save: `status`, except that staged
+    /// paths are compared against their staged version rather than the last snapshot
+    pub fn unstaged_changes(&self) -> Result<Vec<Change>> {
+        let index = self.index()?;
+        let mut changes: Vec<Change> = self.status()?.into_iter().filter(|change| !index.staged.contains_key(&change.path)).collect();
+        for (path, staged) in &index.staged {
+            let file_path = self.root.join(path);
+            let status = match (staged, fs::symlink_metadata(&file_path)) {
+                (None, Err(_)) => continue,
+                // A file staged as deleted that is back is untracked again
+                (None, Ok(_)) => FileStatus::Untracked,
+                (Some(_), Err(_)) => FileStatus::Deleted,
+                (Some(staged), Ok(metadata)) => {
+                    if VosIndex::get_file_metadata(&file_path).is_ok_and(|current| current == (staged.mtime, staged.size)) {
+                        continue;
+                    }
+                    let file_id = match metadata.file_type().is_symlink() {
+                        true => self.store.save_symlink(&file_path)?,
+                        false => self.store.save_file(&file_path)?.0,
+                    };
+                    if file_id == staged.file_id {
+                        continue;
+                    }
+                    FileStatus::Modified
+                }
+            };
+            changes.push(Change { path: path.clone(), status });
+        }
+        Ok(changes)
+    }
+
+    /// Paths of the files and symbolic links of the working tree that are not ignored.
+    /// Links are not followed, as in `save_tree`.
+    fn scan(&self, path: &Path, current_path: &str, files: &mut Vec<String>, ignore: &Ignore) -> Result<()> {
+        for entry in fs::read_dir(path)? {
+            let entry_path = entry?.path();
+            let file_name = entry_path.file_name().unwrap_or_default().to_string_lossy().to_string();
+            if file_name == ORB_DIR {
+                continue;
+            }
+
+            let full_path = if current_path.is_empty() { file_name } else { format!("{}/{}", current_path, file_name) };
+            let metadata = fs::symlink_metadata(&entry_path)?;
+            if ignore.is_ignored(&full_path, metadata.is_dir()) {
+                continue;
+            }
+            if metadata.is_dir() {
+                self.scan(&entry_path, &full_path, files, ignore)?;
+            } else if metadata.is_file() || metadata.file_type().is_symlink() {
+                files.push(full_path);
+            }
+        }
+        Ok(())
+    }
+
+    /// Commits from `start` (HEAD if None) back to the root, following first parents
+    pub fn history(&self, start: Option<&str>) -> Result<Vec<(ObjectId, Commit)>> {
+        let mut commits = Vec::new();
+        let mut next = start.map(str::to_string).or_else(|| self.head());
+        while let Some(commit_id) = next {
+            let commit = self.load_commit(&commit_id)?;
+            next = commit.parents.first().cloned();
+            commits.push((commit_id, commit));
+        }
+        Ok(commits)
+    }
+
+    /// Writes the files of a commit into the working tree and records them in the index.
+    /// Returns every entry of the tree with its mode, in the order it was visited;
+    /// entries of an unknown mode are left alone.
+    pub fn checkout(&self, commit_id: &str) -> Result<Vec<(String, u32)>> {
+        self.checkout_tree(&self.load_commit(commit_id)?.tree)
+    }
+
+    /// Like `checkout`, for a tree rather than a commit
+    pub fn checkout_tree(&self, tree_id: &str) -> Result<Vec<(String, u32)>> {
+        let mut index = VosIndex::new();
+        let mut entries = Vec::new();
+        self.restore_tree(tree_id, "", &mut index, &mut entries)?;
+        index.save(&self.orb_dir.join("index"))?;
+        Ok(entries)
+    }
+
+    /// Like `checkout_tree`, and also deletes the files the index tracked that `tree_id`
+    /// does not contain, with any directories they leave empty. Returns the deleted paths.
+    pub fn replace_worktree(&self, tree_id: &str) -> Result<Vec<String>> {
+        self.require_worktree()?;
+        let previous = self.index()?;
+        self.checkout_tree(tree_id)?;
+        let current = self.index()?;
+        let mut removed = Vec::new();
+        for path in previous.entries.keys().filter(|path| !current.entries.contains_key(*path)) {
+            let full_path = self.root.join(path);
+            match fs::remove_file(&full_path) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+                _ => removed.push(path.clone()),
+            }
+            let mut parent = full_path.parent();
+            while let Some(dir) = parent.filter(|dir| *dir != self.root) {
+                if fs::remove_dir(dir).is_err() {
+                    break;
+                }
+                parent = dir.parent();
+            }
+        }
+        Ok(removed)
+    }
+
+    fn restore_tree(&self, tree_id: &str, prefix: &str, index: &mut VosIndex, entries: &mut Vec<(String, u32)>) -> Result<()> {
+        let directory: Directory = self.store.load_json(tree_id)?;
+        for entry in directory.entries {
+            let path = if prefix.is_empty() { entry.name } else { format!("{}/{}", prefix, entry.name) };
+            let full_path = self.root.join(&path);
+            entries.push((path.clone(), entry.mode));
+            if entry.mode == 0o040000 {
+                fs::create_dir_all(&full_path)?;
+                self.restore_tree(&entry.id, &path, index, entries)?;
+                continue;
+            }
+            if !self.write_file(&path, entry.mode, &entry.id)? {
+                continue;
+            }
+            let (mtime, size) = VosIndex::get_file_metadata(&full_path).unwrap_or((0, 0));
+            index.update_entry(path, mtime, size, entry.id);
+        }
+        Ok(())
+    }
+
+    /// Writes one file of a tree to `path` in the working tree: its content with its
+    /// mode, or the symbolic link it holds. The index is not changed. Returns false,
+    /// writing nothing, for modes that are not files.
+    pub fn write_file(&self, path: &str, mode: u32, file_id: &str) -> Result<bool> {
+        let full_path = self.root.join(path);
+        match mode {
+            0o100644 | 0o100755 => {
+                if let Some(parent) = full_path.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                fs::write(&full_path, self.store.file_content(file_id)?)?;
+                if mode == 0o100755 {
+                    set_executable(&full_path)?;
+                }
+            }
+            0o120000 => self.restore_symlink(file_id, &full_path)?,
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    /// Restores a symbolic link; where links are unsupported, a file holding the target
+    fn restore_symlink(&self, file_id: &str, path: &Path) -> Result<()> {
+        let target = self.store.file_content(file_id)?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        #[cfg(unix)]
+        {
+            if path.symlink_metadata().is_ok() {
+                fs::remove_file(path)?;
+            }
+            std::os::unix::fs::symlink(String::from_utf8_lossy(&target).as_ref(), path)?;
+        }
+        #[cfg(not(unix))]
+        fs::write(path, target)?;
+        Ok(())
+    }
+
+    /// Ignore rules of the working tree, with `.gitignore` files unless `core.gitignore`
+    /// is false
+    fn ignore(&self, tracked: impl IntoIterator<Item = String>) -> Ignore {
+        let use_gitignore = self.config().ok().and_then(|config| config.get_parsed::<bool>("core.gitignore")).unwrap_or(true);
+        Ignore::load(&self.root, use_gitignore, tracked)
+    }
+}
+
+/// The tree ID of a directory entry, or None for a file or a missing entry
+fn subtree(entry: Option<&(u32, ObjectId)>) -> Option<&str> {
+    entry.filter(|(mode, _)| *mode == 0o040000).map(|(_, id)| id.as_str())
+}
+
+/// A pathspec relative to the root, without `./` or a trailing `/`; the root itself is ""
+pub fn normalize_pathspec(pathspec: &str) -> String {
+    let mut pathspec = pathspec.trim_end_matches('/');
+    while let Some(rest) = pathspec.strip_prefix("./") {
+        pathspec = rest.trim_start_matches('/');
+    }
+    match pathspec {
+        "." => String::new(),
+        _ => pathspec.to_string(),
+    }
+}
+
+/// Whether `path` is the file a normalized `pathspec` names or lies in the directory it names
+pub fn matches_pathspec(path: &str, pathspec: &str) -> bool {
+    pathspec.is_empty() || path.strip_prefix(pathspec).is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+}
+
+/// Sets what `path` has staged (`None` unstages it); returns whether that changed
+fn stage(index: &mut VosIndex, path: &str, staged: Option<Option<StagedFile>>) -> bool {
+    let previous = match &staged {
+        Some(file) => index.staged.insert(path.to_string(), file.clone()),
+        None => index.staged.remove(path),
+    };
+    previous != staged
+}
+
+/// Mode of a regular file: executable (0o100755) if anyone may execute it, else 0o100644
+#[cfg(unix)]
+pub fn file_mode(metadata: &fs::Metadata) -> u32 {
+    use std::os::unix::fs::PermissionsExt;
+    if metadata.permissions().mode() & 0o111 != 0 { 0o100755 } else { 0o100644 }
+}
+
+#[cfg(not(unix))]
+pub fn file_mode(_metadata: &fs::Metadata) -> u32 {
+    0o100644
+}
+
+/// Marks a restored file executable for everyone who may read it
+#[cfg(unix)]
+fn set_executable(path: &Path) -> std::io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    let mut permissions = fs::metadata(path)?.permissions();
+    permissions.set_mode(permissions.mode() | ((permissions.mode() & 0o444) >> 2));
+    fs::set_permissions(path, permissions)
+}
+
+#[cfg(not(unix))]
+fn set_executable(_path: &Path) -> std::io::Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_save_commits_only_staged_paths() {
+        let dir = std::env::temp_dir().join(format!("orb-stage-test-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let repository = Repository::init(&dir).unwrap();
+        fs::create_dir_all(dir.join("src")).unwrap();
+        fs::write(dir.join("src/lib.rs"), "v1").unwrap();
+        fs::write(dir.join("notes"), "v1").unwrap();
+        let base = repository.save("base", "a").unwrap();
+
+        fs::write(dir.join("src/lib.rs"), "v22").unwrap();
+        fs::write(dir.join("notes"), "v22").unwrap();
+        assert_eq!(repository.add(&["./src/"]).unwrap(), ["src/lib.rs"]);
+        assert!(matches!(repository.add(&["docs"]), Err(Error::NoMatchingFiles(_))));
+        let partial = repository.save("partial", "a").unwrap();
+
+        let (before, after) = (repository.commit_files(&base).unwrap(), repository.commit_files(&partial).unwrap());
+        assert_ne!(before["src/lib.rs"], after["src/lib.rs"]);
+        assert_eq!(before["notes"], after["notes"]);
+        let trees = |id: &str| repository.load_commit(id).unwrap().tree;
+        let (old, new) = repository.diff_trees(Some(&trees(&base)), Some(&trees(&partial))).unwrap();
+        assert!(old.keys().eq(["src/lib.rs"]) && new.keys().eq(["src/lib.rs"]));
+        assert_eq!(repository.diff_trees(None, Some(&trees(&base))).unwrap().1, before);
+        let changes = repository.status().unwrap();
+        assert_eq!(changes, [Change { path: "notes".to_string(), status: FileStatus::Modified }]);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_staged_paths_are_not_reported_as_unstaged() {
+        let dir = std::env::temp_dir().join(format!("orb-unstaged-test-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let repository = Repository::init(&dir).unwrap();
+        fs::write(dir.join("tracked"), "v1").unwrap();
+        repository.save("base", "a").unwrap();
+
+        fs::write(dir.join("tracked"), "v22").unwrap();
+        fs::write(dir.join("new"), "v1").unwrap();
+        repository.add(&["."]).unwrap();
+        assert!(repository.unstaged_changes().unwrap().is_empty());
+
+        // Changed again after staging: unstaged relative to the staged version
+        fs::write(dir.join("new"), "v333").unwrap();
+        assert_eq!(repository.unstaged_changes().unwrap(), [Change { path: "new".to_string(), status: FileStatus::Modified }]);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
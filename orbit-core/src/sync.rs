@@ -0,0 +1,168 @@
+use std::collections::HashSet;
+use crate::objects::{Commit, Directory, File, ObjectId};
+use crate::{ancestry, refs, Error, Repository, Result};
+
+/// Another copy of a repository that `Repository::sync` exchanges commits with: a
+/// second local repository, or a server behind whatever transport the embedder uses
+pub trait Remote {
+    /// The commit a ref such as `refs/heads/main` points to, if it exists
+    fn read_ref(&self, name: &str) -> Result<Option<ObjectId>>;
+    /// Points a ref at `new` if it still points to `old` (None: the ref does not exist),
+    /// failing with `Error::RefUpdateRejected` otherwise
+    fn update_ref(&self, name: &str, old: Option<&str>, new: &str) -> Result<()>;
+    fn has_object(&self, id: &str) -> Result<bool>;
+    fn get_object(&self, id: &str) -> Result<Vec<u8>>;
+    /// Stores object data under its ID
+    fn put_object(&self, id: &str, data: &[u8]) -> Result<()>;
+}
+
+/// What `Repository::sync` did to a branch
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SyncOutcome {
+    /// Both sides already had the same tip
+    UpToDate,
+    /// The remote branch was fast-forwarded to this local commit
+    Pushed(ObjectId),
+    /// The local branch was fast-forwarded to this remote commit; the working tree is
+    /// left alone, so a `checkout` of it usually follows
+    Pulled(ObjectId),
+    /// Both sides have commits the other lacks; neither branch was moved
+    Diverged { local: ObjectId, remote: ObjectId },
+}
+
+impl Remote for Repository {
+    fn read_ref(&self, name: &str) -> Result<Option<ObjectId>> {
+        Ok(Repository::read_ref(self, name))
+    }
+
+    fn update_ref(&self, name: &str, old: Option<&str>, new: &str) -> Result<()> {
+        if Repository::read_ref(self, name).as_deref() != old {
+            return Err(Error::RefUpdateRejected(name.to_string()));
+        }
+        self.write_ref(name, new)
+    }
+
+    fn has_object(&self, id: &str) -> Result<bool> {
+        Ok(self.store().contains(id))
+    }
+
+    fn get_object(&self, id: &str) -> Result<Vec<u8>> {
+        self.store().load(id)
+    }
+
+    fn put_object(&self, id: &str, data: &[u8]) -> Result<()> {
+        Ok(self.store().store_with_id(id, data)?)
+    }
+}
+
+impl Repository {
+    /// Brings `branch` up to date on both sides: the side that is behind is
+    /// fast-forwarded after receiving the objects it lacks. Diverged histories are
+    /// reported, never merged, so no commit is lost on either side.
+    pub fn sync(&self, remote: &impl Remote, branch: &str) -> Result<SyncOutcome> {
+        let name = refs::branch_ref(branch);
+        let local = Repository::read_ref(self, &name);
+        let upstream = remote.read_ref(&name)?;
+
+        if local == upstream {
+            return Ok(SyncOutcome::UpToDate);
+        }
+        if let Some(local) = &local {
+            if upstream.as_ref().is_none_or(|upstream| ancestry::is_ancestor(self.store(), upstream, local)) {
+                transfer(self, remote, local)?;
+                remote.update_ref(&name, upstream.as_deref(), local)?;
+                return Ok(SyncOutcome::Pushed(local.clone()));
+            }
+        }
+
+        let upstream = upstream.expect("a branch only this side has is pushed above");
+        transfer(remote, self, &upstream)?;
+        match local {
+            Some(local) if !ancestry::is_ancestor(self.store(), &local, &upstream) => Ok(SyncOutcome::Diverged { local, remote: upstream }),
+            _ => {
+                self.write_ref(&name, &upstream)?;
+                Ok(SyncOutcome::Pulled(upstream))
+            }
+        }
+    }
+}
+
+/// Kinds of object reachable from a commit
+enum Kind {
+    Commit,
+    Tree,
+    File,
+    Chunk,
+}
+
+/// Copies every object reachable from `tip` that `to` lacks. Objects are written
+/// children first, so a commit is only stored once everything it refers to is.
+fn transfer(from: &dyn Remote, to: &dyn Remote, tip: &str) -> Result<()> {
+    let mut missing = Vec::new();
+    let mut seen = HashSet::new();
+    let mut pending = vec![(tip.to_string(), Kind::Commit)];
+    while let Some((id, kind)) = pending.pop() {
+        if !seen.insert(id.clone()) || to.has_object(&id)? {
+            continue;
+        }
+        let data = from.get_object(&id)?;
+        let corrupt = |source| Error::Corrupt { id: id.clone(), source };
+        match kind {
+            Kind::Commit => {
+                let commit: Commit = serde_json::from_slice(&data).map_err(corrupt)?;
+                pending.push((commit.tree, Kind::Tree));
+                pending.extend(commit.parents.into_iter().map(|parent| (parent, Kind::Commit)));
+            }
+            Kind::Tree => {
+                let directory: Directory = serde_json::from_slice(&data).map_err(corrupt)?;
+                for entry in directory.entries {
+                    pending.push((entry.id, if entry.mode == 0o040000 { Kind::Tree } else { Kind::File }));
+                }
+            }
+            Kind::File => {
+                let file: File = serde_json::from_slice(&data).map_err(corrupt)?;
+                pending.extend(file.chunk_ids().iter().map(|chunk| (chunk.clone(), Kind::Chunk)));
+            }
+            Kind::Chunk => {}
+        }
+        missing.push((id, data));
+    }
+
+    for (id, data) in missing.iter().rev() {
+        to.put_object(id, data)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FileStatus;
+    use std::fs;
+
+    #[test]
+    fn test_save_status_and_sync_between_repositories() {
+        let dir = std::env::temp_dir().join(format!("orbit-core-sync-test-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let ours = Repository::init(dir.join("ours")).unwrap();
+        let theirs = Repository::init(dir.join("theirs")).unwrap();
+        assert!(matches!(Repository::init(dir.join("ours")), Err(Error::AlreadyInitialized(_))));
+
+        fs::create_dir_all(dir.join("ours/src")).unwrap();
+        fs::write(dir.join("ours/src/lib.rs"), "fn main() {}\n").unwrap();
+        let first = ours.save("First", "Ada <ada@example.com>").unwrap();
+        assert!(ours.status().unwrap().is_empty());
+        fs::write(dir.join("ours/notes.txt"), "todo\n").unwrap();
+        assert_eq!(ours.status().unwrap()[0].status, FileStatus::Untracked);
+
+        assert_eq!(ours.sync(&theirs, "main").unwrap(), SyncOutcome::Pushed(first.clone()));
+        assert_eq!(theirs.checkout(&first).unwrap(), vec![("src".to_string(), 0o040000), ("src/lib.rs".to_string(), 0o100644)]);
+        assert_eq!(fs::read_to_string(dir.join("theirs/src/lib.rs")).unwrap(), "fn main() {}\n");
+
+        let second = theirs.save("Second", "Bob <bob@example.com>").unwrap();
+        assert_eq!(ours.sync(&theirs, "main").unwrap(), SyncOutcome::Pulled(second.clone()));
+        assert_eq!(ours.history(None).unwrap().iter().map(|(id, _)| id).collect::<Vec<_>>(), vec![&second, &first]);
+        assert_eq!(ours.sync(&theirs, "main").unwrap(), SyncOutcome::UpToDate);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
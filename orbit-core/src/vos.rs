@@ -0,0 +1,284 @@
+use sha3::{Digest, Keccak256};
+use std::collections::HashSet;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use fastcdc::v2020::StreamCDC;
+use crate::objects::{self, ObjectId};
+use crate::{Error, Result};
+
+/// Chunk size bounds of content-defined chunking. Content under the minimum stays one
+/// chunk, stored the way files were before chunking.
+const MIN_CHUNK_SIZE: u32 = 16 * 1024;
+const AVG_CHUNK_SIZE: u32 = 64 * 1024;
+const MAX_CHUNK_SIZE: u32 = 256 * 1024;
+
+/// Hashes raw byte data using the SHA-3 (Keccak-256) PQC-resistant algorithm.
+pub fn hash_data(data: &[u8]) -> ObjectId {
+    let mut hasher = Keccak256::new();
+    hasher.update(data);
+    
+    // Convert the 32-byte hash result into a hex string.
+    format!("{:x}", hasher.finalize())
+}
+
+/// True for a 64-digit lowercase hex object ID, the only form `hash_data` produces
+/// (anything else could escape the object store)
+pub fn is_object_id(id: &str) -> bool {
+    id.len() == 64 && id.bytes().all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b))
+}
+
+/// Incremental SHA-3 (Keccak-256) hasher for content too large to hold in memory.
+pub struct StreamHasher(Keccak256);
+
+impl StreamHasher {
+    pub fn new() -> Self {
+        StreamHasher(Keccak256::new())
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        self.0.update(data);
+    }
+
+    /// Returns the same hex ID `hash_data` would produce for the concatenated input.
+    pub fn finish(self) -> ObjectId {
+        format!("{:x}", self.0.finalize())
+    }
+}
+
+impl Default for StreamHasher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Hashes a file in fixed-size blocks without loading it into memory.
+pub fn hash_file(path: &Path) -> Result<ObjectId, std::io::Error> {
+    let mut file = fs::File::open(path)?;
+    let mut hasher = StreamHasher::new();
+    let mut buffer = vec![0u8; 1024 * 1024];
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(hasher.finish())
+}
+
+/// Classifies stored object data as "commit", "tag", "tree", "file", or "chunk" (raw content).
+pub fn object_type(data: &[u8]) -> &'static str {
+    if serde_json::from_slice::<objects::Commit>(data).is_ok() {
+        "commit"
+    } else if serde_json::from_slice::<objects::Tag>(data).is_ok() {
+        "tag"
+    } else if serde_json::from_slice::<objects::Directory>(data).is_ok() {
+        "tree"
+    } else if serde_json::from_slice::<objects::File>(data).is_ok() {
+        "file"
+    } else {
+        "chunk"
+    }
+}
+
+/// Serializes an object (like Commit or Directory) and returns its hash (ID).
+pub fn hash_object<T: Serialize>(object: &T) -> Result<ObjectId, serde_json::Error> {
+    // For simplicity in the MVP, we'll use JSON serialization. 
+    // A production version would use a more compact binary format (like BSON or custom)
+    // for superior speed.
+    let serialized_data = serde_json::to_vec(object)?; 
+    
+    Ok(hash_data(&serialized_data))
+}
+
+/// What `save_stream` stored, for deduplication reports
+#[derive(Debug, Default)]
+pub struct ChunkStats {
+    /// Files saved, and the bytes of their content
+    pub files: u64,
+    pub bytes: u64,
+    /// Chunks the content was split into
+    pub chunks: u64,
+    /// Chunks that were not stored yet, and their bytes
+    pub new_chunks: u64,
+    pub new_bytes: u64,
+}
+
+/// Content-addressed object storage: each object is a file named by its SHA-3 ID, as
+/// in `.orb/objects/ab/cdef123...`
+#[derive(Debug, Clone)]
+pub struct ObjectStore {
+    dir: PathBuf,
+}
+
+impl ObjectStore {
+    /// The store in `dir` (a repository's `.orb/objects`)
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        ObjectStore { dir: dir.into() }
+    }
+
+    /// Returns the on-disk path of an object: .orb/objects/ab/cdef123...; fails for
+    /// anything but an object ID
+    pub fn object_path(&self, object_id: &str) -> std::io::Result<PathBuf> {
+        let object_id = checked(object_id)?;
+        let (prefix, suffix) = object_id.split_at(2);
+        Ok(self.dir.join(prefix).join(suffix))
+    }
+
+    /// Returns the spool path used while a large object is still being received.
+    pub fn temp_object_path(&self, object_id: &str) -> std::io::Result<PathBuf> {
+        Ok(self.dir.join("tmp").join(format!("{}.partial", checked(object_id)?)))
+    }
+
+    /// Whether an object is stored
+    pub fn contains(&self, object_id: &str) -> bool {
+        self.object_path(object_id).is_ok_and(|path| path.exists())
+    }
+
+    /// Reads an object's data
+    pub fn load(&self, object_id: &str) -> Result<Vec<u8>> {
+        let path = self.object_path(object_id).map_err(|_| Error::MissingObject(object_id.to_string()))?;
+        fs::read(path).map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => Error::MissingObject(object_id.to_string()),
+            _ => Error::Io(e),
+        })
+    }
+
+    /// Reads and decodes a Commit, Directory, or File object
+    pub fn load_json<T: DeserializeOwned>(&self, object_id: &str) -> Result<T> {
+        serde_json::from_slice(&self.load(object_id)?).map_err(|source| Error::Corrupt { id: object_id.to_string(), source })
+    }
+
+    /// Reassembles the content of a File object from its chunks
+    pub fn file_content(&self, file_id: &str) -> Result<Vec<u8>> {
+        let file: objects::File = self.load_json(file_id)?;
+        file.content(|chunk| self.load(chunk))
+    }
+
+    /// Saves raw data by its hash ID; data already stored is not written again
+    pub fn save(&self, data: &[u8]) -> std::io::Result<ObjectId> {
+        let object_id = hash_data(data);
+        let object_file = self.object_path(&object_id)?;
+        if !object_file.exists() {
+            if let Some(parent) = object_file.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(&object_file, data)?;
+        }
+        Ok(object_id)
+    }
+
+    /// Serializes and saves a Commit, Directory, or File object
+    pub fn save_json<T: Serialize>(&self, object: &T) -> std::io::Result<ObjectId> {
+        self.save(&serde_json::to_vec(object)?)
+    }
+
+    /// Stores object data with a pre-computed ID (for objects received from a remote),
+    /// overwriting what is there
+    pub fn store_with_id(&self, object_id: &str, data: &[u8]) -> std::io::Result<()> {
+        let object_file = self.object_path(object_id)?;
+        if let Some(parent) = object_file.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(object_file, data)
+    }
+
+    /// Moves a fully received (and verified) spool file into the store.
+    pub fn store_file(&self, object_id: &str, temp_path: &Path) -> std::io::Result<()> {
+        let object_file = self.object_path(object_id)?;
+        if let Some(parent) = object_file.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::rename(temp_path, object_file)
+    }
+
+    /// Reads content to its end, splitting it into content-defined chunks (FastCDC) and
+    /// saving the ones not stored yet; returns the ID of the File object describing it
+    /// and the content's size. Only one chunk is held in memory at a time, and identical
+    /// chunks within and across files are stored once.
+    pub fn save_stream(&self, content: impl Read, stats: &mut ChunkStats) -> std::io::Result<(ObjectId, usize)> {
+        self.chunk_stream(content, stats, true)
+    }
+
+    /// Chunks content exactly like `save_stream` and returns the same File object ID and
+    /// size, without storing anything; `stats` still counts the chunks not stored yet
+    pub fn hash_stream(&self, content: impl Read, stats: &mut ChunkStats) -> std::io::Result<(ObjectId, usize)> {
+        self.chunk_stream(content, stats, false)
+    }
+
+    fn chunk_stream(&self, content: impl Read, stats: &mut ChunkStats, write: bool) -> std::io::Result<(ObjectId, usize)> {
+        let save = |data: &[u8]| if write { self.save(data) } else { Ok(hash_data(data)) };
+        // Without writing, repeated chunks would otherwise all count as new
+        let mut seen = HashSet::new();
+        let mut chunks = Vec::new();
+        let mut size = 0;
+        for chunk in StreamCDC::new(content, MIN_CHUNK_SIZE, AVG_CHUNK_SIZE, MAX_CHUNK_SIZE) {
+            let chunk = chunk.map_err(std::io::Error::other)?;
+            let chunk_id = hash_data(&chunk.data);
+            size += chunk.length;
+            stats.chunks += 1;
+            if !self.contains(&chunk_id) && seen.insert(chunk_id.clone()) {
+                stats.new_chunks += 1;
+                stats.new_bytes += chunk.length as u64;
+                save(&chunk.data)?;
+            }
+            chunks.push(chunk_id);
+        }
+        stats.files += 1;
+        stats.bytes += size as u64;
+
+        let file_object = match chunks.len() {
+            // Empty content is an empty chunk, and content under the minimum its only chunk
+            0 => objects::File { root_chunk_id: save(&[])?, size, chunks },
+            1 => objects::File { root_chunk_id: chunks.remove(0), size, chunks },
+            _ => objects::File { root_chunk_id: hash_data(chunks.join("\n").as_bytes()), size, chunks },
+        };
+        Ok((save(&serde_json::to_vec(&file_object)?)?, size))
+    }
+
+    /// Chunks and saves a file's content
+    pub fn save_file(&self, path: &Path) -> std::io::Result<(ObjectId, usize)> {
+        self.save_stream(fs::File::open(path)?, &mut ChunkStats::default())
+    }
+
+    /// Saves a symbolic link as a File object whose content is the link's target
+    pub fn save_symlink(&self, path: &Path) -> std::io::Result<ObjectId> {
+        let target = fs::read_link(path)?.to_string_lossy().into_owned();
+        Ok(self.save_stream(target.as_bytes(), &mut ChunkStats::default())?.0)
+    }
+}
+
+/// `object_id`, if it is one; an error naming it otherwise
+fn checked(object_id: &str) -> std::io::Result<&str> {
+    match is_object_id(object_id) {
+        true => Ok(object_id),
+        false => Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, format!("invalid object ID {:?}", object_id))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hash_stream_matches_save_stream_without_writing() {
+        let dir = std::env::temp_dir().join(format!("orbit-core-vos-test-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let store = ObjectStore::new(&dir);
+        let content: Vec<u8> = (0..200_000u32).map(|i| (i.wrapping_mul(2_654_435_761) >> 24) as u8).collect();
+
+        let mut hashed = ChunkStats::default();
+        let (id, size) = store.hash_stream(&content[..], &mut hashed).unwrap();
+        assert!(!dir.exists());
+        let mut saved = ChunkStats::default();
+        assert_eq!(store.save_stream(&content[..], &mut saved).unwrap(), (id.clone(), size));
+        assert_eq!((hashed.chunks, hashed.new_chunks), (saved.chunks, saved.new_chunks));
+        assert!(store.contains(&id));
+        assert!(store.object_path("../../etc/passwd").is_err());
+        assert!(!store.contains("a"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
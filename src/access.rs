@@ -0,0 +1,100 @@
+use serde::{Serialize, Deserialize};
+use crate::client_tls::OrbitUrl;
+
+/// Port of the server's REST admin API
+pub const ADMIN_API_PORT: u16 = 8081;
+
+/// Access level a collaborator has on a repository
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Permission {
+    Read,
+    Write,
+    Admin,
+}
+
+impl std::fmt::Display for Permission {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Permission::Read => write!(f, "read"),
+            Permission::Write => write!(f, "write"),
+            Permission::Admin => write!(f, "admin"),
+        }
+    }
+}
+
+/// One collaborator entry returned by the admin API
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccessEntry {
+    pub user: String,
+    pub permission: Permission,
+}
+
+/// Lists the collaborators of a repository
+pub async fn list(server: &OrbitUrl, repository: &str, token: &str) -> Result<Vec<AccessEntry>, Box<dyn std::error::Error>> {
+    let response = reqwest::Client::new()
+        .get(access_url(server, repository, None)?)
+        .bearer_auth(token)
+        .send()
+        .await?;
+    Ok(check(response).await?.json().await?)
+}
+
+/// Grants `user` the given permission, replacing any existing grant
+pub async fn grant(
+    server: &OrbitUrl,
+    repository: &str,
+    user: &str,
+    permission: Permission,
+    token: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let response = reqwest::Client::new()
+        .put(access_url(server, repository, Some(user))?)
+        .bearer_auth(token)
+        .json(&serde_json::json!({ "permission": permission }))
+        .send()
+        .await?;
+    check(response).await?;
+    Ok(())
+}
+
+/// Removes all access `user` has to a repository
+pub async fn revoke(server: &OrbitUrl, repository: &str, user: &str, token: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let response = reqwest::Client::new()
+        .delete(access_url(server, repository, Some(user))?)
+        .bearer_auth(token)
+        .send()
+        .await?;
+    check(response).await?;
+    Ok(())
+}
+
+/// Builds `/admin/repos/<repository>/access[/<user>]`. Repository names contain '/',
+/// so each name is sent as a single percent-encoded path segment.
+fn access_url(server: &OrbitUrl, repository: &str, user: Option<&str>) -> Result<reqwest::Url, Box<dyn std::error::Error>> {
+    let mut url = reqwest::Url::parse(&format!("http://{}:{}/", server.url_host(), ADMIN_API_PORT))?;
+    {
+        let mut segments = url.path_segments_mut().map_err(|_| "invalid admin API URL")?;
+        segments.extend(["admin", "repos", repository, "access"]);
+        if let Some(user) = user {
+            segments.push(user);
+        }
+    }
+    Ok(url)
+}
+
+/// Turns an error status into an error carrying the server's message
+async fn check(response: reqwest::Response) -> Result<reqwest::Response, Box<dyn std::error::Error>> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    let message = response.text().await.unwrap_or_default();
+    Err(match status {
+        reqwest::StatusCode::UNAUTHORIZED | reqwest::StatusCode::FORBIDDEN => {
+            format!("permission denied ({}): only repository admins can manage access", status)
+        }
+        reqwest::StatusCode::NOT_FOUND => format!("repository or user not found: {}", message.trim()),
+        _ => format!("admin API returned {}: {}", status, message.trim()),
+    }.into())
+}
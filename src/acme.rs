@@ -0,0 +1,307 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use base64::Engine;
+use ring::rand::SystemRandom;
+use ring::signature::{EcdsaKeyPair, KeyPair, ECDSA_P256_SHA256_FIXED_SIGNING};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use crate::cert;
+use tracing::info;
+
+/// Let's Encrypt production directory
+pub const LETS_ENCRYPT: &str = "https://acme-v02.api.letsencrypt.org/directory";
+/// Let's Encrypt staging directory (untrusted certificates, generous rate limits)
+pub const LETS_ENCRYPT_STAGING: &str = "https://acme-staging-v02.api.letsencrypt.org/directory";
+/// Certificates are renewed once they expire within this many days
+pub const RENEW_BEFORE_DAYS: i64 = 30;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+const MAX_POLLS: u32 = 90;
+const CHALLENGE_PATH: &str = "/.well-known/acme-challenge/";
+
+type AcmeResult<T> = Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+/// Where and how to request certificates
+#[derive(Debug, Clone)]
+pub struct AcmeOptions {
+    /// ACME directory URL
+    pub directory: String,
+    /// Contact address registered with the account (expiry notices)
+    pub email: Option<String>,
+    /// Port the HTTP-01 responder listens on (80 unless traffic is forwarded)
+    pub http_port: u16,
+}
+
+/// `orb cert --acme`: obtains a certificate unless the current one is still valid for
+/// more than RENEW_BEFORE_DAYS (or `force` is set), so it is safe to run from cron
+pub async fn run(hostnames: &[String], dir: &Path, options: &AcmeOptions, force: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let (cert_path, key_path) = cert::paths(dir);
+    if !force {
+        if let Some(days) = cert::days_remaining(&cert_path) {
+            if days > RENEW_BEFORE_DAYS {
+                info!("✅ Certificate {} is valid for {} more days; nothing to renew", cert_path.display(), days);
+                return Ok(());
+            }
+        }
+    }
+
+    info!("🔏 Requesting a certificate for {} from {}", hostnames.join(", "), options.directory);
+    obtain(hostnames, dir, options).await.map_err(|e| e.to_string())?;
+    info!("✅ Certificate issued");
+    info!("   Certificate: {}", cert_path.display());
+    info!("   Private key: {}", key_path.display());
+    Ok(())
+}
+
+/// Runs the ACME flow (RFC 8555) with HTTP-01 challenges and writes the issued chain
+/// and its private key into `dir` (see `cert::paths`)
+pub async fn obtain(hostnames: &[String], dir: &Path, options: &AcmeOptions) -> AcmeResult<()> {
+    if hostnames.is_empty() {
+        return Err("at least one hostname is required".into());
+    }
+    if let Some(ip) = hostnames.iter().find(|h| h.parse::<std::net::IpAddr>().is_ok()) {
+        return Err(format!("ACME certificates need DNS names, not IP addresses ({})", ip).into());
+    }
+
+    let client = reqwest::Client::new();
+    let directory: Value = client.get(&options.directory).send().await?.error_for_status()?.json().await?;
+    let endpoint = |name: &str| -> AcmeResult<String> {
+        directory[name].as_str().map(str::to_string).ok_or_else(|| format!("ACME directory has no {}", name).into())
+    };
+
+    std::fs::create_dir_all(dir)?;
+    let rng = SystemRandom::new();
+    let key = load_account_key(&dir.join("acme-account.key"), &rng)?;
+    let mut account = Account { key, rng, client, new_nonce: endpoint("newNonce")?, nonce: None, kid: None };
+
+    let contact: Vec<String> = options.email.iter().map(|email| format!("mailto:{}", email)).collect();
+    let response = account.post(&endpoint("newAccount")?, Some(&json!({
+        "termsOfServiceAgreed": true,
+        "contact": contact,
+    }))).await?;
+    account.kid = Some(location(&response)?);
+
+    let identifiers: Vec<Value> = hostnames.iter().map(|host| json!({ "type": "dns", "value": host })).collect();
+    let response = account.post(&endpoint("newOrder")?, Some(&json!({ "identifiers": identifiers }))).await?;
+    let order_url = location(&response)?;
+    let order: Value = response.json().await?;
+
+    // Answer HTTP-01 challenges only while the CA validates this order
+    let listener = TcpListener::bind(("0.0.0.0", options.http_port)).await.map_err(|e| {
+        format!("cannot listen on port {} for HTTP-01 challenges: {}", options.http_port, e)
+    })?;
+    let tokens = Arc::new(Mutex::new(HashMap::new()));
+    let responder = tokio::spawn(serve_challenges(listener, tokens.clone()));
+    let validated = authorize(&mut account, &order, &tokens).await;
+    responder.abort();
+    validated?;
+
+    // The certificate key is generated locally; only the CSR is sent
+    let mut params = rcgen::CertificateParams::new(hostnames.to_vec());
+    params.distinguished_name.push(rcgen::DnType::CommonName, hostnames[0].as_str());
+    let certificate_key = rcgen::Certificate::from_params(params)?;
+    let csr = certificate_key.serialize_request_der()?;
+    let finalize = order["finalize"].as_str().ok_or("ACME order has no finalize URL")?;
+    account.post(finalize, Some(&json!({ "csr": b64(&csr) }))).await?;
+
+    let order = poll(&mut account, &order_url, "order").await?;
+    let certificate_url = order["certificate"].as_str().ok_or("ACME order has no certificate URL")?;
+    let chain = account.post(certificate_url, None).await?.text().await?;
+
+    let (cert_path, key_path) = cert::paths(dir);
+    cert::write_private(&key_path, certificate_key.serialize_private_key_pem().as_bytes())?;
+    std::fs::write(&cert_path, chain)?;
+    Ok(())
+}
+
+/// Completes the HTTP-01 challenge of every pending authorization in an order
+async fn authorize(account: &mut Account, order: &Value, tokens: &Mutex<HashMap<String, String>>) -> AcmeResult<()> {
+    let authorizations = order["authorizations"].as_array().ok_or("ACME order has no authorizations")?;
+    for authorization_url in authorizations.iter().filter_map(Value::as_str) {
+        let authorization: Value = account.post(authorization_url, None).await?.json().await?;
+        if authorization["status"] == "valid" {
+            continue;
+        }
+        let host = authorization["identifier"]["value"].as_str().unwrap_or_default().to_string();
+        let challenge = authorization["challenges"]
+            .as_array()
+            .and_then(|challenges| challenges.iter().find(|c| c["type"] == "http-01"))
+            .ok_or_else(|| format!("the CA offered no HTTP-01 challenge for {}", host))?;
+        let token = challenge["token"].as_str().ok_or("HTTP-01 challenge has no token")?;
+        let challenge_url = challenge["url"].as_str().ok_or("HTTP-01 challenge has no URL")?;
+
+        let key_authorization = format!("{}.{}", token, account.thumbprint());
+        tokens.lock().unwrap_or_else(|p| p.into_inner()).insert(token.to_string(), key_authorization);
+        info!("⏳ Validating {} (HTTP-01)...", host);
+        account.post(challenge_url, Some(&json!({}))).await?;
+        poll(account, authorization_url, "authorization").await?;
+    }
+    Ok(())
+}
+
+/// Polls an order or authorization until it is valid
+async fn poll(account: &mut Account, url: &str, what: &str) -> AcmeResult<Value> {
+    for _ in 0..MAX_POLLS {
+        let resource: Value = account.post(url, None).await?.json().await?;
+        match resource["status"].as_str() {
+            Some("valid") => return Ok(resource),
+            Some("invalid") => {
+                let detail = resource["challenges"]
+                    .as_array()
+                    .and_then(|challenges| challenges.iter().find_map(|c| c["error"]["detail"].as_str()))
+                    .or_else(|| resource["error"]["detail"].as_str())
+                    .unwrap_or("no details given");
+                return Err(format!("ACME {} failed: {}", what, detail).into());
+            }
+            _ => tokio::time::sleep(POLL_INTERVAL).await,
+        }
+    }
+    Err(format!("ACME {} was not validated in time", what).into())
+}
+
+/// An ACME account: its key, the JWS nonce chain, and its URL once registered
+struct Account {
+    key: EcdsaKeyPair,
+    rng: SystemRandom,
+    client: reqwest::Client,
+    new_nonce: String,
+    nonce: Option<String>,
+    /// Account URL, used as the JWS key ID after registration
+    kid: Option<String>,
+}
+
+impl Account {
+    /// The account's public key as a JWK (the point is uncompressed: 0x04 || x || y)
+    fn jwk(&self) -> Value {
+        let point = self.key.public_key().as_ref();
+        json!({ "crv": "P-256", "kty": "EC", "x": b64(&point[1..33]), "y": b64(&point[33..65]) })
+    }
+
+    /// RFC 7638 thumbprint: SHA-256 of the JWK with members in lexicographic order
+    fn thumbprint(&self) -> String {
+        let point = self.key.public_key().as_ref();
+        let canonical = format!(
+            r#"{{"crv":"P-256","kty":"EC","x":"{}","y":"{}"}}"#,
+            b64(&point[1..33]),
+            b64(&point[33..65])
+        );
+        b64(&Sha256::digest(canonical.as_bytes()))
+    }
+
+    async fn fresh_nonce(&mut self) -> AcmeResult<String> {
+        if let Some(nonce) = self.nonce.take() {
+            return Ok(nonce);
+        }
+        let response = self.client.head(&self.new_nonce).send().await?;
+        replay_nonce(&response).ok_or_else(|| "ACME server sent no nonce".into())
+    }
+
+    /// Sends a JWS-signed POST; a `None` payload is a POST-as-GET. A stale nonce is retried once.
+    async fn post(&mut self, url: &str, payload: Option<&Value>) -> AcmeResult<reqwest::Response> {
+        let mut retried = false;
+        loop {
+            let mut protected = json!({ "alg": "ES256", "nonce": self.fresh_nonce().await?, "url": url });
+            match &self.kid {
+                Some(kid) => protected["kid"] = json!(kid),
+                None => protected["jwk"] = self.jwk(),
+            }
+            let protected = b64(protected.to_string().as_bytes());
+            let payload = payload.map(|p| b64(p.to_string().as_bytes())).unwrap_or_default();
+            let signature = self.key
+                .sign(&self.rng, format!("{}.{}", protected, payload).as_bytes())
+                .map_err(|_| "failed to sign ACME request")?;
+            let body = json!({ "protected": protected, "payload": payload, "signature": b64(signature.as_ref()) });
+
+            let response = self.client
+                .post(url)
+                .header(reqwest::header::CONTENT_TYPE, "application/jose+json")
+                .body(body.to_string())
+                .send()
+                .await?;
+            self.nonce = replay_nonce(&response);
+            if response.status().is_success() {
+                return Ok(response);
+            }
+
+            let problem: Value = response.json().await.unwrap_or_default();
+            if !retried && problem["type"] == "urn:ietf:params:acme:error:badNonce" {
+                retried = true;
+                continue;
+            }
+            return Err(format!(
+                "ACME request to {} failed: {}",
+                url,
+                problem["detail"].as_str().unwrap_or("unknown error")
+            ).into());
+        }
+    }
+}
+
+/// Loads the account key, creating one on first use
+fn load_account_key(path: &Path, rng: &SystemRandom) -> AcmeResult<EcdsaKeyPair> {
+    let pkcs8 = match std::fs::read(path) {
+        Ok(pkcs8) => pkcs8,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            let document = EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, rng)
+                .map_err(|_| "failed to generate an ACME account key")?;
+            cert::write_private(path, document.as_ref())?;
+            document.as_ref().to_vec()
+        }
+        Err(e) => return Err(e.into()),
+    };
+    EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, &pkcs8, rng)
+        .map_err(|e| format!("invalid ACME account key {}: {}", path.display(), e).into())
+}
+
+/// Answers `GET /.well-known/acme-challenge/<token>` with the token's key authorization
+async fn serve_challenges(listener: TcpListener, tokens: Arc<Mutex<HashMap<String, String>>>) {
+    while let Ok((mut stream, _)) = listener.accept().await {
+        let tokens = tokens.clone();
+        tokio::spawn(async move {
+            let mut request = Vec::new();
+            let mut buf = [0u8; 1024];
+            while !request.windows(4).any(|w| w == b"\r\n\r\n") && request.len() < 8192 {
+                match stream.read(&mut buf).await {
+                    Ok(0) | Err(_) => return,
+                    Ok(n) => request.extend_from_slice(&buf[..n]),
+                }
+            }
+
+            let request = String::from_utf8_lossy(&request);
+            let path = request.lines().next().and_then(|line| line.split_whitespace().nth(1)).unwrap_or_default();
+            let answer = path
+                .strip_prefix(CHALLENGE_PATH)
+                .and_then(|token| tokens.lock().unwrap_or_else(|p| p.into_inner()).get(token).cloned());
+            let response = match answer {
+                Some(body) => format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                ),
+                None => "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string(),
+            };
+            let _ = stream.write_all(response.as_bytes()).await;
+        });
+    }
+}
+
+fn replay_nonce(response: &reqwest::Response) -> Option<String> {
+    response.headers().get("replay-nonce").and_then(|v| v.to_str().ok()).map(str::to_string)
+}
+
+fn location(response: &reqwest::Response) -> AcmeResult<String> {
+    response
+        .headers()
+        .get(reqwest::header::LOCATION)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string)
+        .ok_or_else(|| "ACME response has no Location header".into())
+}
+
+fn b64(data: &[u8]) -> String {
+    base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(data)
+}
@@ -0,0 +1,513 @@
+use std::convert::Infallible;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+use hyper::server::conn::AddrStream;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, StatusCode};
+use serde::Deserialize;
+use crate::access::{AccessEntry, Permission};
+use crate::audit;
+use crate::hosted::{self, HostedRepo};
+use crate::policy::Policy;
+use crate::server::{self, Server};
+use crate::users::{self, UserStore};
+use crate::webhooks::Webhook;
+use tracing::{info, warn};
+
+/// Largest request body the admin API reads
+const MAX_BODY: u64 = 64 * 1024;
+
+/// Settings of the admin API embedded in `orb serve`
+pub struct AdminOptions {
+    pub bind: SocketAddr,
+    /// Token that unlocks user management; None disables those endpoints
+    pub admin_token: Option<String>,
+    /// Let anyone register a new account (`orb register`) without the admin token
+    pub open_registration: bool,
+    /// Lifetime of tokens issued here when the request names none; None never expires them
+    pub token_lifetime: Option<Duration>,
+}
+
+struct State {
+    options: AdminOptions,
+    server: Arc<Server>,
+    /// Serializes read-modify-write cycles of users.json and access.json
+    write_lock: tokio::sync::Mutex<()>,
+}
+
+/// Who is calling: the server admin, a user (by their VNP token), or nobody
+enum Caller {
+    Admin,
+    User(String),
+    Anonymous,
+}
+
+type Reply = Result<Response<Body>, (StatusCode, String)>;
+
+/// Serves the admin API until the server stops. Routes:
+///
+/// - `POST /admin/users` registers a user and returns a token
+/// - `GET /admin/users`, `POST /admin/users/<user>/tokens`, `DELETE /admin/users/<user>`,
+///   `GET /admin/tokens/revoked` (admin token)
+/// - `GET /admin/users/<user>/tokens`, `POST /admin/users/<user>/tokens/<id>/rotate`,
+///   `DELETE /admin/users/<user>/tokens/<id>` (the admin token or the user's own)
+/// - `POST /admin/tokens/revoke` revokes the token in the body, for anyone holding it
+/// - `GET /admin/repos/<repo>/access`, `PUT|DELETE /admin/repos/<repo>/access/<user>`,
+///   `GET|PUT /admin/repos/<repo>/policy`, `GET /admin/repos/<repo>/usage`,
+///   `GET|POST /admin/repos/<repo>/webhooks`, `DELETE /admin/repos/<repo>/webhooks/<id>`
+///   (the repository's admins)
+pub async fn serve(options: AdminOptions, server: Arc<Server>) -> Result<(), hyper::Error> {
+    let bind = options.bind;
+    let state = Arc::new(State { options, server, write_lock: tokio::sync::Mutex::new(()) });
+    let make_service = make_service_fn(move |connection: &AddrStream| {
+        let (state, ip) = (state.clone(), connection.remote_addr().ip());
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request| {
+                let state = state.clone();
+                async move {
+                    let response = handle(request, &state, ip).await.unwrap_or_else(|(status, message)| {
+                        let mut response = Response::new(Body::from(format!("{}\n", message)));
+                        *response.status_mut() = status;
+                        response
+                    });
+                    Ok::<_, Infallible>(response)
+                }
+            }))
+        }
+    });
+    hyper::Server::try_bind(&bind)?.serve(make_service).await
+}
+
+async fn handle(request: Request<Body>, state: &State, ip: IpAddr) -> Reply {
+    let segments: Vec<String> = request
+        .uri()
+        .path()
+        .split('/')
+        .filter(|s| !s.is_empty())
+        .map(|s| percent_encoding::percent_decode_str(s).decode_utf8_lossy().into_owned())
+        .collect();
+    let segments: Vec<&str> = segments.iter().map(String::as_str).collect();
+    let caller = caller(&request, state, ip)?;
+    let audit = |kind: &str, repository: Option<&str>, message: String| {
+        let user = match &caller {
+            Caller::Admin => Some("admin".to_string()),
+            Caller::User(user) => Some(user.clone()),
+            Caller::Anonymous => None,
+        };
+        state.server.audit.record(audit::Event {
+            client: Some(ip.to_string()),
+            user,
+            repository: repository.map(str::to_string),
+            message: Some(message),
+            ..audit::Event::new(kind)
+        });
+    };
+    let method = request.method().clone();
+
+    match (&method, segments.as_slice()) {
+        (&Method::POST, ["admin", "users"]) => {
+            let (response, username) = register(request, state, &caller).await?;
+            audit("register", None, format!("registered {}", username));
+            Ok(response)
+        }
+        (&Method::GET, ["admin", "users"]) => {
+            require_admin(&caller)?;
+            let store = load_users(state)?;
+            let users: Vec<serde_json::Value> = store.users.iter()
+                .map(|(name, user)| serde_json::json!({
+                    "username": name,
+                    "tokens": user.tokens.len(),
+                    "size": hosted::namespace_usage(&state.server.root, name),
+                    "quota": state.server.quotas.user,
+                }))
+                .collect();
+            json(StatusCode::OK, &users)
+        }
+        (_, ["admin", "users", user, "tokens", rest @ ..]) if rest.len() <= 2 => {
+            if !matches!(&caller, Caller::User(name) if name == user) {
+                require_admin(&caller)?;
+            }
+            let no_user = || (StatusCode::NOT_FOUND, format!("no user {}", user));
+            match (&method, rest) {
+                (&Method::GET, []) => {
+                    let store = load_users(state)?;
+                    let now = users::now();
+                    let tokens: Vec<serde_json::Value> = store.users.get(*user).ok_or_else(no_user)?.tokens.iter()
+                        .map(|token| token.to_json(now))
+                        .collect();
+                    json(StatusCode::OK, &tokens)
+                }
+                (&Method::POST, []) => {
+                    require_admin(&caller)?;
+                    let TokenRequest { expires_in, .. } = read_json_or_default(request).await?;
+                    let lifetime = interval(expires_in.as_deref())?.or(state.options.token_lifetime);
+                    let _guard = state.write_lock.lock().await;
+                    let mut store = load_users(state)?;
+                    if !store.users.contains_key(*user) {
+                        return Err(no_user());
+                    }
+                    let token = store.issue_token(user, lifetime);
+                    save_users(state, &store)?;
+                    audit("token", None, format!("issued a token for {}", user));
+                    json(StatusCode::CREATED, &token_reply(&store, user, &token))
+                }
+                (&Method::POST, [id, "rotate"]) => {
+                    let TokenRequest { expires_in, grace } = read_json_or_default(request).await?;
+                    let (lifetime, grace) = (interval(expires_in.as_deref())?, interval(grace.as_deref())?);
+                    let _guard = state.write_lock.lock().await;
+                    let mut store = load_users(state)?;
+                    let token = store.rotate(user, id, lifetime, grace)
+                        .ok_or_else(|| (StatusCode::NOT_FOUND, format!("{} has no token {}", user, id)))?;
+                    save_users(state, &store)?;
+                    info!("🔄 Admin API: rotated token {} of {}", id, user);
+                    audit("rotate_token", None, format!("rotated token {} of {}", id, user));
+                    json(StatusCode::CREATED, &token_reply(&store, user, &token))
+                }
+                (&Method::DELETE, [id]) => {
+                    let _guard = state.write_lock.lock().await;
+                    let mut store = load_users(state)?;
+                    if !store.revoke(user, id) {
+                        return Err((StatusCode::NOT_FOUND, format!("{} has no token {}", user, id)));
+                    }
+                    save_users(state, &store)?;
+                    info!("🚫 Admin API: revoked token {} of {}", id, user);
+                    audit("revoke_token", None, format!("revoked token {} of {}", id, user));
+                    Ok(Response::builder().status(StatusCode::NO_CONTENT).body(Body::empty()).unwrap())
+                }
+                _ => Err((StatusCode::METHOD_NOT_ALLOWED, "method not allowed".to_string())),
+            }
+        }
+        (&Method::POST, ["admin", "tokens", "revoke"]) => {
+            let Revoke { token } = read_json(request).await?;
+            let _guard = state.write_lock.lock().await;
+            let mut store = load_users(state)?;
+            let user = store.revoke_token(&token).ok_or_else(|| (StatusCode::NOT_FOUND, "unknown token".to_string()))?;
+            save_users(state, &store)?;
+            let id = &users::token_digest(&token)[..12];
+            info!("🚫 Admin API: revoked token {} of {}", id, user);
+            audit("revoke_token", None, format!("revoked token {} of {} by value", id, user));
+            Ok(Response::builder().status(StatusCode::NO_CONTENT).body(Body::empty()).unwrap())
+        }
+        (&Method::GET, ["admin", "tokens", "revoked"]) => {
+            require_admin(&caller)?;
+            let store = load_users(state)?;
+            let revoked: Vec<serde_json::Value> = store.revoked.iter()
+                .map(|revocation| serde_json::json!({
+                    "id": &revocation.digest[..12],
+                    "username": revocation.user,
+                    "revoked": users::timestamp(revocation.revoked),
+                }))
+                .collect();
+            json(StatusCode::OK, &revoked)
+        }
+        (&Method::DELETE, ["admin", "users", user]) => {
+            require_admin(&caller)?;
+            let _guard = state.write_lock.lock().await;
+            let mut store = load_users(state)?;
+            if !store.remove_user(user) {
+                return Err((StatusCode::NOT_FOUND, format!("no user {}", user)));
+            }
+            save_users(state, &store)?;
+            info!("🗑️  Admin API: deleted user {}", user);
+            audit("delete_user", None, format!("deleted {}", user));
+            Ok(Response::builder().status(StatusCode::NO_CONTENT).body(Body::empty()).unwrap())
+        }
+        (_, ["admin", "repos", repository, "access", rest @ ..]) if rest.len() <= 1 => {
+            let repo = repository_admin(state, &caller, repository)?;
+            match (&method, rest) {
+                (&Method::GET, []) => {
+                    let entries: Vec<AccessEntry> = repo.grants().into_iter()
+                        .map(|(user, permission)| AccessEntry { user, permission })
+                        .collect();
+                    json(StatusCode::OK, &entries)
+                }
+                (&Method::PUT, [user]) => {
+                    let (response, permission) = grant(request, state, &repo, repository, user).await?;
+                    audit("grant", Some(repository), format!("granted {} {} access", user, permission));
+                    Ok(response)
+                }
+                (&Method::DELETE, [user]) => {
+                    let _guard = state.write_lock.lock().await;
+                    let mut grants = repo.grants();
+                    if grants.remove(*user).is_none() {
+                        return Err((StatusCode::NOT_FOUND, format!("{} has no access to {}", user, repository)));
+                    }
+                    repo.save_grants(&grants).map_err(internal)?;
+                    info!("👥 Admin API: revoked {}'s access to {}", user, repository);
+                    audit("revoke", Some(repository), format!("revoked {}'s access", user));
+                    Ok(Response::builder().status(StatusCode::NO_CONTENT).body(Body::empty()).unwrap())
+                }
+                _ => Err((StatusCode::METHOD_NOT_ALLOWED, "method not allowed".to_string())),
+            }
+        }
+        (&Method::GET, ["admin", "repos", repository, "usage"]) => {
+            let repo = repository_admin(state, &caller, repository)?;
+            json(StatusCode::OK, &state.server.quotas.usage(&state.server.root, repository, &repo))
+        }
+        (_, ["admin", "repos", repository, "policy"]) => {
+            let repo = repository_admin(state, &caller, repository)?;
+            match method {
+                Method::GET => json(StatusCode::OK, &repo.policy()),
+                Method::PUT => {
+                    let policy: Policy = read_json(request).await?;
+                    let _guard = state.write_lock.lock().await;
+                    repo.save_policy(&policy).map_err(internal)?;
+                    info!("🛡️  Admin API: updated the push policy of {}", repository);
+                    audit("policy", Some(repository), serde_json::to_string(&policy).unwrap_or_default());
+                    json(StatusCode::OK, &policy)
+                }
+                _ => Err((StatusCode::METHOD_NOT_ALLOWED, "method not allowed".to_string())),
+            }
+        }
+        (_, ["admin", "repos", repository, "webhooks", rest @ ..]) if rest.len() <= 1 => {
+            let repo = repository_admin(state, &caller, repository)?;
+            match (&method, rest) {
+                (&Method::GET, []) => {
+                    let hooks: Vec<serde_json::Value> = repo.webhooks().iter()
+                        .map(|hook| serde_json::json!({ "id": hook.id, "url": hook.url, "has_secret": hook.secret.is_some() }))
+                        .collect();
+                    json(StatusCode::OK, &hooks)
+                }
+                (&Method::POST, []) => {
+                    let hook = add_webhook(request, state, &repo).await?;
+                    info!("🔔 Admin API: added webhook {} to {}", hook.url, repository);
+                    audit("add_webhook", Some(repository), format!("{} {}", hook.id, hook.url));
+                    json(StatusCode::CREATED, &serde_json::json!({ "id": hook.id, "url": hook.url }))
+                }
+                (&Method::DELETE, [id]) => {
+                    let _guard = state.write_lock.lock().await;
+                    let mut hooks = repo.webhooks();
+                    let Some(index) = hooks.iter().position(|hook| hook.id == *id) else {
+                        return Err((StatusCode::NOT_FOUND, format!("{} has no webhook {}", repository, id)));
+                    };
+                    let hook = hooks.remove(index);
+                    repo.save_webhooks(&hooks).map_err(internal)?;
+                    info!("🔕 Admin API: removed webhook {} from {}", hook.url, repository);
+                    audit("remove_webhook", Some(repository), format!("{} {}", hook.id, hook.url));
+                    Ok(Response::builder().status(StatusCode::NO_CONTENT).body(Body::empty()).unwrap())
+                }
+                _ => Err((StatusCode::METHOD_NOT_ALLOWED, "method not allowed".to_string())),
+            }
+        }
+        _ => Err((StatusCode::NOT_FOUND, "no such endpoint".to_string())),
+    }
+}
+
+/// Registration body sent by `orb register`. Repositories and permissions are accepted for
+/// compatibility; every user owns exactly their own namespace.
+#[derive(Deserialize)]
+struct Registration {
+    username: String,
+}
+
+/// `POST /admin/users`: creates an account and returns its first token. With the admin
+/// token it also issues further tokens for existing users.
+async fn register(request: Request<Body>, state: &State, caller: &Caller) -> Result<(Response<Body>, String), (StatusCode, String)> {
+    let is_admin = matches!(caller, Caller::Admin);
+    if !is_admin && !state.options.open_registration {
+        return Err((StatusCode::FORBIDDEN, "registration requires the admin token on this server".to_string()));
+    }
+    let registration: Registration = read_json(request).await?;
+    let username = registration.username.trim();
+    if hosted::split_name(&format!("{}/repo", username)).is_err() {
+        return Err((StatusCode::BAD_REQUEST, format!("'{}' is not a valid email-based username", username)));
+    }
+
+    let _guard = state.write_lock.lock().await;
+    let mut store = load_users(state)?;
+    if store.users.contains_key(username) && !is_admin {
+        return Err((StatusCode::CONFLICT, format!("{} is already registered", username)));
+    }
+    let token = store.issue_token(username, state.options.token_lifetime);
+    save_users(state, &store)?;
+    info!("👤 Admin API: registered {}", username);
+    let response = json(StatusCode::CREATED, &token_reply(&store, username, &token))?;
+    Ok((response, username.to_string()))
+}
+
+/// Body of token issue and rotation requests, all optional: intervals like `90d`
+#[derive(Deserialize, Default)]
+struct TokenRequest {
+    /// Lifetime of the new token
+    #[serde(default)]
+    expires_in: Option<String>,
+    /// How long a rotated token keeps working; it is revoked at once without one
+    #[serde(default)]
+    grace: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Revoke {
+    token: String,
+}
+
+fn interval(interval: Option<&str>) -> Result<Option<Duration>, (StatusCode, String)> {
+    interval.map(crate::transport::parse_interval).transpose().map_err(|e| (StatusCode::BAD_REQUEST, e))
+}
+
+/// Reply carrying a newly issued token, which cannot be shown again
+fn token_reply(store: &UserStore, username: &str, token: &str) -> serde_json::Value {
+    let digest = users::token_digest(token);
+    let expires = store.users.get(username)
+        .and_then(|user| user.tokens.iter().find(|token| token.digest == digest))
+        .and_then(|token| token.expires);
+    serde_json::json!({
+        "username": username,
+        "token": token,
+        "id": &digest[..12],
+        "expires": expires.map(users::timestamp),
+    })
+}
+
+#[derive(Deserialize)]
+struct GrantRequest {
+    permission: Permission,
+}
+
+/// `PUT /admin/repos/<repo>/access/<user>`: grants a registered user access
+async fn grant(
+    request: Request<Body>,
+    state: &State,
+    repo: &HostedRepo,
+    repository: &str,
+    user: &str,
+) -> Result<(Response<Body>, Permission), (StatusCode, String)> {
+    let GrantRequest { permission } = read_json(request).await?;
+    if hosted::split_name(repository).is_ok_and(|(namespace, _)| namespace == user) {
+        return Err((StatusCode::BAD_REQUEST, format!("{} owns {} and always has admin access", user, repository)));
+    }
+    if !load_users(state)?.users.contains_key(user) {
+        return Err((StatusCode::NOT_FOUND, format!("no user {}", user)));
+    }
+
+    let _guard = state.write_lock.lock().await;
+    let mut grants = repo.grants();
+    grants.insert(user.to_string(), permission);
+    repo.save_grants(&grants).map_err(internal)?;
+    info!("👥 Admin API: granted {} {} access to {}", user, permission, repository);
+    Ok((json(StatusCode::OK, &AccessEntry { user: user.to_string(), permission })?, permission))
+}
+
+#[derive(Deserialize)]
+struct WebhookRequest {
+    url: String,
+    #[serde(default)]
+    secret: Option<String>,
+}
+
+/// `POST /admin/repos/<repo>/webhooks`: registers an http(s) URL to notify after pushes
+async fn add_webhook(request: Request<Body>, state: &State, repo: &HostedRepo) -> Result<Webhook, (StatusCode, String)> {
+    let WebhookRequest { url, secret } = read_json(request).await?;
+    match url::Url::parse(&url) {
+        Ok(parsed) if matches!(parsed.scheme(), "http" | "https") && parsed.has_host() => {}
+        _ => return Err((StatusCode::BAD_REQUEST, format!("'{}' is not an http or https URL", url))),
+    }
+    let hook = Webhook::new(url, secret.filter(|secret| !secret.is_empty()));
+
+    let _guard = state.write_lock.lock().await;
+    let mut hooks = repo.webhooks();
+    hooks.push(hook.clone());
+    repo.save_webhooks(&hooks).map_err(internal)?;
+    Ok(hook)
+}
+
+/// Identifies the caller from `Authorization: Bearer <token>`. Unknown, expired, and revoked tokens count as
+/// failed authentications, like on the VNP port.
+fn caller(request: &Request<Body>, state: &State, ip: IpAddr) -> Result<Caller, (StatusCode, String)> {
+    let Some(token) = request
+        .headers()
+        .get(hyper::header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+    else {
+        return Ok(Caller::Anonymous);
+    };
+    if let Some(wait) = state.server.throttle.locked_out(ip, Some(token)) {
+        return Err((StatusCode::TOO_MANY_REQUESTS, format!("too many failed attempts; try again in {}s", wait.as_secs().max(1))));
+    }
+
+    let caller = if state.options.admin_token.as_deref().is_some_and(|admin| admin.as_bytes() == token.as_bytes()) {
+        Ok(Caller::Admin)
+    } else {
+        let store = load_users(state)?;
+        store.authenticate(token).map(|user| Caller::User(user.to_string()))
+    };
+    match caller {
+        Ok(caller) => {
+            state.server.throttle.auth_succeeded(ip);
+            Ok(caller)
+        }
+        Err(reason) => {
+            state.server.throttle.auth_failed(ip, Some(token));
+            state.server.audit.record(audit::Event {
+                client: Some(ip.to_string()),
+                message: Some(format!("{} (admin API)", reason)),
+                ..audit::Event::new("auth_failed")
+            });
+            Err((StatusCode::UNAUTHORIZED, reason.to_string()))
+        }
+    }
+}
+
+fn require_admin(caller: &Caller) -> Result<(), (StatusCode, String)> {
+    match caller {
+        Caller::Admin => Ok(()),
+        _ => Err((StatusCode::FORBIDDEN, "this endpoint requires the admin token".to_string())),
+    }
+}
+
+/// Opens a repository the caller administers (the server admin administers every one)
+fn repository_admin(state: &State, caller: &Caller, repository: &str) -> Result<HostedRepo, (StatusCode, String)> {
+    let not_found = || (StatusCode::NOT_FOUND, format!("repository '{}' not found", repository));
+    let dir = hosted::repo_dir(&state.server.root, repository).map_err(|_| not_found())?;
+    let repo = HostedRepo::open(&dir).ok_or_else(not_found)?;
+    let permission = match caller {
+        Caller::Admin => Some(Permission::Admin),
+        Caller::User(user) => server::permission(&state.server.root, repository, Some(user), state.server.allow_anonymous),
+        Caller::Anonymous => return Err((StatusCode::UNAUTHORIZED, "a token is required".to_string())),
+    };
+    match permission {
+        Some(Permission::Admin) => Ok(repo),
+        Some(_) => Err((StatusCode::FORBIDDEN, format!("you are not an admin of {}", repository))),
+        None => Err(not_found()),
+    }
+}
+
+async fn read_json<T: serde::de::DeserializeOwned>(request: Request<Body>) -> Result<T, (StatusCode, String)> {
+    let length = hyper::body::HttpBody::size_hint(request.body()).upper().unwrap_or(u64::MAX);
+    if length > MAX_BODY {
+        return Err((StatusCode::PAYLOAD_TOO_LARGE, "request body is too large".to_string()));
+    }
+    let body = hyper::body::to_bytes(request.into_body()).await.map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+    serde_json::from_slice(&body).map_err(|e| (StatusCode::BAD_REQUEST, format!("invalid JSON: {}", e)))
+}
+
+/// Like `read_json`, but an empty body means every field's default
+async fn read_json_or_default<T: serde::de::DeserializeOwned + Default>(request: Request<Body>) -> Result<T, (StatusCode, String)> {
+    if hyper::body::HttpBody::size_hint(request.body()).exact() == Some(0) {
+        return Ok(T::default());
+    }
+    read_json(request).await
+}
+
+fn json<T: serde::Serialize>(status: StatusCode, value: &T) -> Reply {
+    Ok(Response::builder()
+        .status(status)
+        .header(hyper::header::CONTENT_TYPE, "application/json")
+        .body(Body::from(serde_json::to_vec(value).map_err(internal)?))
+        .unwrap())
+}
+
+fn load_users(state: &State) -> Result<UserStore, (StatusCode, String)> {
+    UserStore::load(&state.server.dir).map_err(|e| internal(e.to_string()))
+}
+
+fn save_users(state: &State, store: &UserStore) -> Result<(), (StatusCode, String)> {
+    store.save(&state.server.dir).map_err(internal)
+}
+
+fn internal(error: impl std::fmt::Display) -> (StatusCode, String) {
+    warn!("⚠️  Admin API error: {}", error);
+    (StatusCode::INTERNAL_SERVER_ERROR, "internal server error".to_string())
+}
@@ -0,0 +1,41 @@
+use std::collections::HashSet;
+use orbit_core::ancestry;
+use crate::objects::{Commit, ObjectId};
+use crate::vos;
+
+/// Loads a commit from the local VOS, or None if it has not been downloaded
+pub fn load_commit(commit_id: &str) -> Option<Commit> {
+    ancestry::load_commit(&vos::store(), commit_id)
+}
+
+/// Every commit reachable from `tip` (including `tip`) that exists locally
+pub fn ancestors(tip: &str) -> HashSet<ObjectId> {
+    ancestry::ancestors(&vos::store(), tip)
+}
+
+/// Commits reachable from `tip` (itself included) that are not in `excluded`, each
+/// after its parents
+pub fn parents_first(tip: &str, excluded: &HashSet<ObjectId>) -> Result<Vec<(ObjectId, Commit)>, orbit_core::Error> {
+    ancestry::parents_first(&vos::store(), tip, excluded)
+}
+
+/// The commits in `commits` that no other commit in the list descends from
+pub fn tips(commits: &[ObjectId]) -> Vec<ObjectId> {
+    ancestry::tips(&vos::store(), commits)
+}
+
+/// The nearest common ancestor of two commits, if their histories share one
+pub fn merge_base(a: &str, b: &str) -> Option<ObjectId> {
+    ancestry::merge_base(&vos::store(), a, b)
+}
+
+/// True if `ancestor` is reachable from `descendant` (a commit is its own ancestor)
+pub fn is_ancestor(ancestor: &str, descendant: &str) -> bool {
+    ancestry::is_ancestor(&vos::store(), ancestor, descendant)
+}
+
+/// Counts commits on each side since the merge base of `local` and `upstream`:
+/// `(ahead, behind)`
+pub fn ahead_behind(local: &str, upstream: &str) -> (usize, usize) {
+    ancestry::ahead_behind(&vos::store(), local, upstream)
+}
@@ -0,0 +1,232 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+use crate::refs::RefChange;
+use crate::transfer;
+use tracing::warn;
+
+/// Name of the audit log in the server config directory
+pub const LOG_FILE: &str = "audit.log";
+/// Rotated files kept next to the log (`audit.log.1` is the newest)
+const KEEP: usize = 5;
+
+/// One line of the audit log
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Event {
+    /// RFC 3339 UTC timestamp
+    pub time: String,
+    /// What happened: auth, auth_failed, select, create, delete, rename, push, update,
+    /// rejected, disconnect, webhook, maintenance, replicate (also replicate_delete and
+    /// replicate_rename), replication_failed, or an admin API action (register, token,
+    /// rotate_token, revoke_token, delete_user, grant, revoke, policy, add_webhook,
+    /// remove_webhook)
+    pub event: String,
+    /// Client address
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub repository: Option<String>,
+    /// Refs moved (or refused)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub refs: Vec<RefChange>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub objects: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bytes_received: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bytes_sent: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+impl Event {
+    pub fn new(event: &str) -> Self {
+        let now = OffsetDateTime::now_utc();
+        Self {
+            time: now.replace_nanosecond(0).unwrap_or(now).format(&Rfc3339).unwrap_or_default(),
+            event: event.to_string(),
+            ..Self::default()
+        }
+    }
+}
+
+/// JSONL audit log of an `orb serve` instance, rotated once it reaches `max_size`
+pub struct AuditLog {
+    path: Option<PathBuf>,
+    max_size: u64,
+    file: Mutex<Option<(File, u64)>>,
+}
+
+impl AuditLog {
+    pub fn open(path: PathBuf, max_size: u64) -> io::Result<Self> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let size = file.metadata()?.len();
+        Ok(Self { path: Some(path), max_size, file: Mutex::new(Some((file, size))) })
+    }
+
+    /// A log that records nothing (`--no-audit-log`)
+    pub fn disabled() -> Self {
+        Self { path: None, max_size: 0, file: Mutex::new(None) }
+    }
+
+    /// Appends an event. Failures are reported on stderr; serving goes on.
+    pub fn record(&self, event: Event) {
+        let Some(path) = &self.path else { return };
+        let Ok(mut line) = serde_json::to_vec(&event) else { return };
+        line.push(b'\n');
+
+        let mut file = self.file.lock().unwrap();
+        let result = (|| -> io::Result<()> {
+            if matches!(&*file, Some((_, size)) if *size > 0 && size + line.len() as u64 > self.max_size) {
+                *file = None;
+                rotate(path)?;
+            }
+            // Reopened after rotation, or after an earlier write error
+            let (handle, size) = match &mut *file {
+                Some(open) => open,
+                None => {
+                    let handle = OpenOptions::new().create(true).append(true).open(path)?;
+                    let size = handle.metadata()?.len();
+                    file.insert((handle, size))
+                }
+            };
+            handle.write_all(&line)?;
+            *size += line.len() as u64;
+            Ok(())
+        })();
+        if let Err(e) = result {
+            warn!("⚠️  Cannot write audit log {}: {}", path.display(), e);
+        }
+    }
+}
+
+/// Shifts `audit.log` to `audit.log.1`, `.1` to `.2`, and so on, dropping the oldest
+fn rotate(path: &Path) -> io::Result<()> {
+    let numbered = |n: usize| PathBuf::from(format!("{}.{}", path.display(), n));
+    let _ = fs::remove_file(numbered(KEEP));
+    for n in (1..KEEP).rev() {
+        if numbered(n).exists() {
+            fs::rename(numbered(n), numbered(n + 1))?;
+        }
+    }
+    if path.exists() {
+        fs::rename(path, numbered(1))?;
+    }
+    Ok(())
+}
+
+/// Filters for `orb serve logs`
+#[derive(Debug, Default)]
+pub struct Query {
+    pub user: Option<String>,
+    pub repository: Option<String>,
+    pub event: Option<String>,
+    pub since: Option<Duration>,
+    /// Show only the last N matching events
+    pub limit: usize,
+}
+
+impl Query {
+    fn matches(&self, event: &Event, cutoff: Option<OffsetDateTime>) -> bool {
+        self.user.as_ref().is_none_or(|user| event.user.as_ref() == Some(user))
+            && self.repository.as_ref().is_none_or(|repo| event.repository.as_ref() == Some(repo))
+            && self.event.as_ref().is_none_or(|kind| event.event == *kind)
+            && cutoff.is_none_or(|cutoff| {
+                OffsetDateTime::parse(&event.time, &Rfc3339).is_ok_and(|time| time >= cutoff)
+            })
+    }
+}
+
+/// `orb serve logs`: prints audit events, oldest first, from the log and its rotated files
+pub fn run_logs(path: &Path, query: &Query, json: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let mut files: Vec<PathBuf> = (1..=KEEP).rev().map(|n| PathBuf::from(format!("{}.{}", path.display(), n))).collect();
+    files.push(path.to_path_buf());
+    if !files.iter().any(|file| file.exists()) {
+        return Err(format!("no audit log at {} (is --dir right?)", path.display()).into());
+    }
+
+    let cutoff = query.since.map(|since| OffsetDateTime::now_utc() - since);
+    let mut events = std::collections::VecDeque::new();
+    for file in files.iter().filter(|file| file.exists()) {
+        for line in BufReader::new(File::open(file)?).lines() {
+            let line = line?;
+            // A line cut short by a crash is skipped rather than failing the whole query
+            let Ok(event) = serde_json::from_str::<Event>(&line) else { continue };
+            if query.matches(&event, cutoff) {
+                events.push_back((line, event));
+                if query.limit > 0 && events.len() > query.limit {
+                    events.pop_front();
+                }
+            }
+        }
+    }
+
+    if json {
+        for (line, _) in &events {
+            println!("{}", line);
+        }
+        return Ok(());
+    }
+    if events.is_empty() {
+        println!("📜 No matching audit events");
+    }
+    for (_, event) in &events {
+        println!("{}", describe(event));
+    }
+    Ok(())
+}
+
+/// One human-readable line per event
+fn describe(event: &Event) -> String {
+    let mut parts = vec![event.time.clone(), format!("{:<11}", event.event)];
+    parts.extend(event.user.clone());
+    parts.extend(event.repository.clone());
+    for change in &event.refs {
+        let short = |id: &Option<String>| id.as_deref().map_or("-".to_string(), |id| id[..7.min(id.len())].to_string());
+        parts.push(format!("{} {}→{}", change.name, short(&change.old), short(&change.new)));
+    }
+    if let Some(objects) = event.objects {
+        parts.push(format!("{} objects", objects));
+    }
+    if let (Some(received), Some(sent)) = (event.bytes_received, event.bytes_sent) {
+        parts.push(format!("in {} / out {}", transfer::format_bytes(received), transfer::format_bytes(sent)));
+    }
+    if let Some(message) = &event.message {
+        parts.push(message.clone());
+    }
+    if let Some(client) = &event.client {
+        parts.push(format!("({})", client));
+    }
+    parts.join("  ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_query_filters_events() {
+        let push = Event {
+            user: Some("alice@example.com".to_string()),
+            repository: Some("alice@example.com/web".to_string()),
+            ..Event::new("push")
+        };
+        let minute = time::Duration::minutes(1);
+        let now = OffsetDateTime::now_utc();
+        assert!(Query { event: Some("push".to_string()), ..Query::default() }.matches(&push, None));
+        assert!(!Query { user: Some("bob@example.com".to_string()), ..Query::default() }.matches(&push, None));
+        let repository = Query { repository: Some("alice@example.com/web".to_string()), ..Query::default() };
+        assert!(repository.matches(&push, Some(now - minute)));
+        assert!(!repository.matches(&push, Some(now + minute)));
+    }
+}
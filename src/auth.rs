@@ -0,0 +1,85 @@
+use crate::vnp::{self, VnpCommand, VnpError};
+use tracing::{info, warn};
+
+/// Why a session could not be authenticated
+#[derive(Debug, thiserror::Error)]
+pub enum AuthError {
+    #[error("no authentication token found")]
+    MissingToken,
+    #[error("anonymous access refused: {0}")]
+    AnonymousRefused(String),
+    #[error("authentication failed: {0}")]
+    Rejected(String),
+}
+
+/// Loads the API token for a connection.
+///
+/// Checks the remote's `token_env` hint first, then `ORBIT_TOKEN`, then the
+/// token saved by `orb register` in `~/.orb_token`.
+pub fn load_token(token_env: Option<&str>) -> Result<String, AuthError> {
+    match find_token(token_env) {
+        Some((token, source)) => {
+            info!("🔑 Using {}", source);
+            Ok(token)
+        }
+        None => {
+            print_token_hint();
+            Err(AuthError::MissingToken)
+        }
+    }
+}
+
+/// Looks up the API token without printing anything, returning it with a description of its source
+pub fn find_token(token_env: Option<&str>) -> Option<(String, String)> {
+    if let Some(var) = token_env {
+        match std::env::var(var) {
+            Ok(token) => return Some((token, format!("token from ${}", var))),
+            Err(_) => warn!("⚠️  ${} is not set; falling back to the default token", var),
+        }
+    }
+
+    if let Ok(token) = std::env::var("ORBIT_TOKEN") {
+        return Some((token, "environment token".to_string()));
+    }
+
+    // Try to read from saved token file in home directory
+    let home_dir = std::env::var("HOME").or_else(|_| std::env::var("USERPROFILE")).ok()?;
+    let token = std::fs::read_to_string(std::path::Path::new(&home_dir).join(".orb_token")).ok()?;
+    Some((token.trim().to_string(), "saved authentication token".to_string()))
+}
+
+fn print_token_hint() {
+    warn!("💡 Register for a new account: orb register --email your@email.com --server orbit.privapulse.com:8082");
+    warn!("💡 Or set existing token: export ORBIT_TOKEN=\"your-token-here\"");
+}
+
+/// Sends the token (or an anonymous login) and returns whether the session may write
+pub async fn send_credentials<R, W>(
+    reader: &mut R,
+    writer: &mut W,
+    token: Option<String>,
+) -> Result<bool, Box<dyn std::error::Error>>
+where
+    R: tokio::io::AsyncReadExt + Unpin,
+    W: tokio::io::AsyncWriteExt + Unpin,
+{
+    let anonymous = token.is_none();
+    let command = match token {
+        Some(token) => VnpCommand::Authenticate(token),
+        None => VnpCommand::AuthenticateAnonymous,
+    };
+    vnp::send_command(writer, command).await?;
+
+    // Wait for authentication result
+    match vnp::recv_command(reader).await? {
+        VnpCommand::AuthResult { success: true, capabilities, .. } => {
+            Ok(capabilities.iter().any(|c| c == vnp::CAP_WRITE))
+        }
+        VnpCommand::AuthResult { message, .. } if anonymous => {
+            print_token_hint();
+            Err(AuthError::AnonymousRefused(message).into())
+        }
+        VnpCommand::AuthResult { message, .. } => Err(AuthError::Rejected(message).into()),
+        response => Err(VnpError::response("authentication", response).into()),
+    }
+}
@@ -0,0 +1,265 @@
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fs;
+use std::process::Command;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+use orbit_core::Repository;
+use crate::objects::{Commit, ObjectId};
+use crate::repo::{self, RepoError};
+use crate::{ancestry, refs, style};
+
+/// File in `.orb` holding the state of a bisect, from `orb bisect start` to `reset`
+const BISECT_FILE: &str = "BISECT";
+
+/// Exit code of a `bisect run` command for commits that cannot be tested
+const SKIP_CODE: i32 = 125;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Bisect {
+    /// HEAD when the bisect started; HEAD itself never moves while bisecting
+    head: ObjectId,
+    bad: Option<ObjectId>,
+    good: Vec<ObjectId>,
+    skipped: Vec<ObjectId>,
+    /// Commit whose files are in the working tree
+    current: ObjectId,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum Verdict {
+    Good,
+    Bad,
+    Skip,
+}
+
+/// What a bisect does after a commit is marked
+enum Step {
+    /// A bad and a good commit are needed before anything can be tested
+    Waiting,
+    Testing(ObjectId),
+    Done,
+}
+
+fn load(repository: &Repository) -> Result<Bisect, Box<dyn Error>> {
+    match fs::read_to_string(repository.orb_dir().join(BISECT_FILE)) {
+        Ok(data) => Ok(serde_json::from_str(&data)?),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Err(RepoError::NotBisecting.into()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn save(repository: &Repository, bisect: &Bisect) -> Result<(), Box<dyn Error>> {
+    fs::write(repository.orb_dir().join(BISECT_FILE), serde_json::to_string_pretty(bisect)?)?;
+    Ok(())
+}
+
+/// The commit being tested, if a bisect is in progress
+pub fn current(repository: &Repository) -> Option<ObjectId> {
+    load(repository).ok().map(|bisect| bisect.current)
+}
+
+/// Fails while a bisect is in progress: the working tree then holds an older commit's
+/// files, which must not be saved onto the branch or carried elsewhere
+pub fn ensure_not_bisecting(repository: &Repository) -> Result<(), RepoError> {
+    match repository.orb_dir().join(BISECT_FILE).exists() {
+        true => Err(RepoError::BisectInProgress),
+        false => Ok(()),
+    }
+}
+
+fn short(id: &str) -> String {
+    style::id(&id[..7.min(id.len())]).into_owned()
+}
+
+/// `orb bisect start`: begins a bisect from HEAD, optionally marking `bad` and `good`
+/// commits right away. The working tree must have no changes to tracked files.
+pub fn start(bad: Option<&str>, good: &[String]) -> Result<(), Box<dyn Error>> {
+    let repository = repo::open_worktree()?;
+    repo::ensure_idle(&repository)?;
+    let head = refs::head_commit().ok_or(RepoError::NoCommits)?;
+    repo::ensure_clean(&repository)?;
+    let mut bisect = Bisect { head: head.clone(), bad: None, good: Vec::new(), skipped: Vec::new(), current: head };
+    bisect.bad = bad.map(refs::resolve).transpose()?;
+    for good in good {
+        bisect.good.push(refs::resolve(good)?);
+    }
+    info!("🔎 Started bisecting on '{}'", repository.current_branch());
+    next(&repository, &mut bisect)?;
+    Ok(())
+}
+
+/// `orb bisect good|bad|skip`: marks `revision` (default: the commit being tested) and
+/// checks out the next commit to test
+pub fn mark(verdict: Verdict, revision: Option<&str>) -> Result<(), Box<dyn Error>> {
+    let repository = repo::open_worktree()?;
+    let mut bisect = load(&repository)?;
+    let id = match revision {
+        Some(revision) => refs::resolve(revision)?,
+        None => bisect.current.clone(),
+    };
+    record(&mut bisect, verdict, id);
+    next(&repository, &mut bisect)?;
+    Ok(())
+}
+
+fn record(bisect: &mut Bisect, verdict: Verdict, id: ObjectId) {
+    match verdict {
+        Verdict::Good => {
+            info!("👍 Marked {} as good", short(&id));
+            bisect.good.push(id);
+        }
+        Verdict::Bad => {
+            info!("👎 Marked {} as bad", short(&id));
+            bisect.bad = Some(id);
+        }
+        Verdict::Skip => {
+            info!("⏭️  Skipped {}", short(&id));
+            bisect.skipped.push(id);
+        }
+    }
+}
+
+/// `orb bisect run`: tests commits with `command` until the first bad one is found.
+/// The command runs in the repository root; exit code 0 marks the commit good, 125
+/// skips it, and any other code below 128 marks it bad. A command killed by a signal
+/// or exiting with 128 or more stops the bisect where it is.
+pub fn run(command: &[String]) -> Result<(), Box<dyn Error>> {
+    let repository = repo::open_worktree()?;
+    let mut bisect = load(&repository)?;
+    let (program, args) = command.split_first().expect("clap requires a command");
+    let mut step = next(&repository, &mut bisect)?;
+    if let Step::Waiting = step {
+        return Err(RepoError::BisectUnbounded.into());
+    }
+    while let Step::Testing(candidate) = step {
+        info!("▶️  Running {}", command.join(" "));
+        let status = Command::new(program)
+            .args(args)
+            .current_dir(repository.root())
+            .status()
+            .map_err(|e| format!("could not run {}: {}", program, e))?;
+        let verdict = match status.code() {
+            Some(0) => Verdict::Good,
+            Some(SKIP_CODE) => Verdict::Skip,
+            Some(code) if code < 128 => Verdict::Bad,
+            _ => return Err(format!("{} failed to test {} ({}); the bisect was stopped", program, short(&candidate), status).into()),
+        };
+        record(&mut bisect, verdict, candidate);
+        step = next(&repository, &mut bisect)?;
+    }
+    Ok(())
+}
+
+/// `orb bisect reset`: ends the bisect and restores the working tree to HEAD
+pub fn reset() -> Result<(), Box<dyn Error>> {
+    let repository = repo::open_worktree()?;
+    let bisect = load(&repository)?;
+    repo::ensure_clean(&repository)?;
+    repository.replace_worktree(&repository.load_commit(&bisect.head)?.tree)?;
+    fs::remove_file(repository.orb_dir().join(BISECT_FILE))?;
+    info!("↩️  Ended the bisect; back at {} on '{}'", short(&bisect.head), repository.current_branch());
+    Ok(())
+}
+
+/// Saves the bisect and checks out the next commit to test, or reports the first bad
+/// commit once it is known
+fn next(repository: &Repository, bisect: &mut Bisect) -> Result<Step, Box<dyn Error>> {
+    save(repository, bisect)?;
+    let Some(bad) = bisect.bad.clone() else {
+        info!("💡 Mark a commit that has the bug with `orb bisect bad [<rev>]`");
+        return Ok(Step::Waiting);
+    };
+    if bisect.good.is_empty() {
+        info!("💡 Mark a commit without the bug with `orb bisect good <rev>`");
+        return Ok(Step::Waiting);
+    }
+    let cleared: HashSet<ObjectId> = bisect.good.iter().flat_map(|good| ancestry::ancestors(good)).collect();
+    if cleared.contains(&bad) {
+        return Err(RepoError::BadIsGood(bad).into());
+    }
+    // The first bad commit is the bad one or one of its ancestors that no good commit has
+    let suspects = ancestry::parents_first(&bad, &cleared)?;
+    let Some((candidate, remaining)) = pick(&suspects, &bad, &bisect.skipped) else {
+        let untested: Vec<&ObjectId> = suspects.iter().map(|(id, _)| id).filter(|id| bisect.skipped.contains(id)).collect();
+        match untested.is_empty() {
+            true => report(&bad, &suspects.last().expect("the bad commit is a suspect").1),
+            false => {
+                warn!("🤷 Only skipped commits are left; the first bad commit is {} or one of:", short(&bad));
+                for id in untested {
+                    warn!("   {}", short(id));
+                }
+            }
+        }
+        info!("💡 Run `orb bisect reset` to return to '{}'", repository.current_branch());
+        return Ok(Step::Done);
+    };
+
+    repo::ensure_clean(repository)?;
+    let commit = &suspects.iter().find(|(id, _)| *id == candidate).expect("candidates are suspects").1;
+    repository.replace_worktree(&commit.tree)?;
+    bisect.current = candidate.clone();
+    save(repository, bisect)?;
+    info!("🔎 Bisecting: {} suspect commit(s) left, roughly {} more step(s)", remaining, remaining.max(1).ilog2());
+    info!("   Testing {} {}", short(&candidate), commit.message.lines().next().unwrap_or_default());
+    Ok(Step::Testing(candidate))
+}
+
+fn report(id: &str, commit: &Commit) {
+    info!("🎯 {} is the first bad commit", style::id(id));
+    info!("   Author: {}", commit.author);
+    info!("   {}", commit.message.lines().next().unwrap_or_default());
+}
+
+/// The suspect to test next: the one whose ancestors among the suspects come closest
+/// to half of them, so either verdict rules out about half. Returns it with the number
+/// of suspects, or None when every suspect but `bad` is skipped.
+fn pick(suspects: &[(ObjectId, Commit)], bad: &str, skipped: &[ObjectId]) -> Option<(ObjectId, usize)> {
+    let parents: HashMap<&ObjectId, &Vec<ObjectId>> = suspects.iter().map(|(id, commit)| (id, &commit.parents)).collect();
+    let reach = |start: &ObjectId| {
+        let mut seen = HashSet::from([start]);
+        let mut stack = vec![start];
+        while let Some(id) = stack.pop() {
+            for parent in parents[id].iter().filter(|parent| parents.contains_key(parent)) {
+                if seen.insert(parent) {
+                    stack.push(parent);
+                }
+            }
+        }
+        seen.len()
+    };
+    let total = suspects.len();
+    suspects
+        .iter()
+        .map(|(id, _)| id)
+        .filter(|id| *id != bad && !skipped.contains(id))
+        .max_by_key(|id| {
+            let below = reach(id);
+            below.min(total - below)
+        })
+        .map(|id| (id.clone(), total))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pick_halves_suspects() {
+        let commit = |parents: &[&str]| Commit {
+            tree: "t".to_string(),
+            parents: parents.iter().map(|parent| parent.to_string()).collect(),
+            author: "a".to_string(),
+            timestamp: 0,
+            message: "m".to_string(),
+            signature: None,
+        };
+        // c1 is good, so c2..c7 are suspects; c7 is bad
+        let suspects: Vec<(ObjectId, Commit)> = (2..=7).map(|n| (format!("c{}", n), commit(&[&format!("c{}", n - 1)]))).collect();
+        assert_eq!(pick(&suspects, "c7", &[]), Some(("c4".to_string(), 6)));
+        assert_eq!(pick(&suspects, "c7", &["c4".to_string()]), Some(("c5".to_string(), 6)));
+        let all_skipped: Vec<ObjectId> = (2..=6).map(|n| format!("c{}", n)).collect();
+        assert_eq!(pick(&suspects, "c7", &all_skipped), None);
+        assert_eq!(pick(&suspects[5..], "c7", &[]), None);
+    }
+}
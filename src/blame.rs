@@ -0,0 +1,151 @@
+use std::collections::{BinaryHeap, HashMap};
+use std::error::Error;
+use std::io::Write;
+use time::macros::format_description;
+use time::OffsetDateTime;
+use crate::diff::{self, Line};
+use crate::objects::{Commit, ObjectId};
+use crate::repo::{self, RepoError};
+use crate::{refs, style, vos};
+
+/// Lines still to be blamed: each one's index in the version of the file being looked
+/// at, and in the blamed file
+type Pending = Vec<(usize, usize)>;
+
+/// Splits the `pending` lines of `new` into those `old` already had, renumbered to
+/// their index in `old`, and those that first appear in `new`
+fn pass_unchanged(old: &[&str], new: &[&str], pending: Pending) -> (Pending, Pending) {
+    let mut old_index = vec![None; new.len()];
+    let (mut i, mut j) = (0, 0);
+    for line in diff::diff_lines(old, new) {
+        match line {
+            Line::Same(_) => {
+                old_index[j] = Some(i);
+                i += 1;
+                j += 1;
+            }
+            Line::Removed(_) => i += 1,
+            Line::Added(_) => j += 1,
+        }
+    }
+    let (mut passed, mut kept) = (Vec::new(), Vec::new());
+    for (line, blamed) in pending {
+        match old_index[line] {
+            Some(old_line) => passed.push((old_line, blamed)),
+            None => kept.push((line, blamed)),
+        }
+    }
+    (passed, kept)
+}
+
+fn text(path: &str, file_id: &str) -> Result<String, Box<dyn Error>> {
+    Ok(String::from_utf8(vos::file_content(file_id)?).map_err(|_| RepoError::BinaryFile(path.to_string()))?)
+}
+
+/// `orb blame`: prints each line of `path` as of `revision` (default: HEAD) with the
+/// commit that last changed it, that commit's author, and its date.
+///
+/// Commits are visited newest first. Lines a commit shares with one of its parents are
+/// passed on to that parent; the rest are blamed on the commit. Lines reaching a commit
+/// whose parents are not stored locally (a shallow clone) are blamed on it.
+pub fn blame(path: &str, revision: Option<&str>) -> Result<(), Box<dyn Error>> {
+    let repository = repo::open()?;
+    let path = orbit_core::normalize_pathspec(path);
+    let revision = revision.unwrap_or("HEAD");
+    let tip = refs::resolve(revision)?;
+    let tip_commit = repository.load_commit(&tip)?;
+    let (_, file_id) = repository
+        .tree_entry(&tip_commit.tree, &path)?
+        .ok_or_else(|| RepoError::NoSuchPath { path: path.clone(), revision: revision.to_string() })?;
+    let content = text(&path, &file_id)?;
+    let lines = diff::split_lines(&content);
+
+    let mut origins: Vec<Option<ObjectId>> = vec![None; lines.len()];
+    let mut commits = HashMap::from([(tip.clone(), tip_commit)]);
+    // Commits holding lines to blame, with the file's ID there; visited newest first
+    let mut pending: HashMap<ObjectId, (ObjectId, Pending)> = HashMap::from([(tip.clone(), (file_id, (0..lines.len()).map(|i| (i, i)).collect()))]);
+    let mut queue = BinaryHeap::from([(commits[&tip].timestamp, tip)]);
+    while let Some((_, id)) = queue.pop() {
+        let (file_id, mut left) = pending.remove(&id).expect("queued commits have pending lines");
+        let parents: Vec<(ObjectId, Commit, ObjectId)> = commits[&id]
+            .parents
+            .iter()
+            .filter_map(|parent| Some((parent.clone(), repository.load_commit(parent).ok()?)))
+            .filter_map(|(parent, commit)| {
+                let entry = repository.tree_entry(&commit.tree, &path).transpose()?;
+                Some(entry.map(|(_, parent_file)| (parent, commit, parent_file)))
+            })
+            .collect::<Result<_, _>>()?;
+
+        let version = text(&path, &file_id)?;
+        let mut passes = Vec::new();
+        // A parent with the same version takes every line, so merges pass through
+        match parents.iter().position(|(_, _, parent_file)| *parent_file == file_id) {
+            Some(same) => passes.push((same, std::mem::take(&mut left))),
+            None => {
+                for (index, (_, _, parent_file)) in parents.iter().enumerate() {
+                    if left.is_empty() {
+                        break;
+                    }
+                    let old = text(&path, parent_file)?;
+                    let (passed, kept) = pass_unchanged(&diff::split_lines(&old), &diff::split_lines(&version), left);
+                    passes.push((index, passed));
+                    left = kept;
+                }
+            }
+        }
+        for (_, blamed) in &left {
+            origins[*blamed] = Some(id.clone());
+        }
+
+        let mut parents: Vec<Option<(ObjectId, Commit, ObjectId)>> = parents.into_iter().map(Some).collect();
+        for (index, passed) in passes.into_iter().filter(|(_, passed)| !passed.is_empty()) {
+            let (parent, commit, parent_file) = parents[index].take().expect("each parent is passed lines once");
+            match pending.get_mut(&parent) {
+                // Reached through another child as well
+                Some((_, lines)) => lines.extend(passed),
+                None => {
+                    queue.push((commit.timestamp, parent.clone()));
+                    pending.insert(parent.clone(), (parent_file, passed));
+                    commits.insert(parent, commit);
+                }
+            }
+        }
+    }
+
+    let author = |id: &ObjectId| commits[id].author.split(" <").next().unwrap_or_default().to_string();
+    let width = origins.iter().flatten().map(|id| author(id).chars().count()).max().unwrap_or_default();
+    let number_width = lines.len().to_string().len();
+    let mut out = std::io::stdout().lock();
+    for (number, (line, origin)) in lines.iter().zip(&origins).enumerate() {
+        let id = origin.as_ref().expect("every line is blamed");
+        let date = OffsetDateTime::from_unix_timestamp(commits[id].timestamp)?.format(format_description!("[year]-[month]-[day]"))?;
+        writeln!(
+            out,
+            "{} ({:<width$} {} {:>number_width$}) {}",
+            style::id(&id[..7.min(id.len())]),
+            author(id),
+            date,
+            number + 1,
+            line.trim_end_matches(['\n', '\r']),
+            width = width,
+            number_width = number_width,
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pass_unchanged_lines() {
+        let old = ["a\n", "b\n", "c\n"];
+        let new = ["a\n", "x\n", "c\n", "d\n"];
+        let pending = vec![(0, 0), (1, 1), (2, 2), (3, 3)];
+        assert_eq!(pass_unchanged(&old, &new, pending), (vec![(0, 0), (2, 2)], vec![(1, 1), (3, 3)]));
+        // Lines keep their blamed-file number while moving through versions
+        assert_eq!(pass_unchanged(&["c\n"], &new, vec![(2, 7)]), (vec![(0, 7)], vec![]));
+    }
+}
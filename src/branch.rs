@@ -0,0 +1,128 @@
+use std::error::Error;
+use std::io::Write;
+use tracing::info;
+use crate::repo::RepoError;
+use crate::style::{self, Color};
+use crate::{ancestry, hosted, refs, repo};
+
+/// Branch names must also be valid on servers, so pushing never fails on a name;
+/// `HEAD` and names that look like options are reserved
+pub fn validate_name(name: &str) -> Result<(), RepoError> {
+    match hosted::is_ref_name(name) && name != "HEAD" && !name.starts_with('-') {
+        true => Ok(()),
+        false => Err(RepoError::InvalidBranchName(name.to_string())),
+    }
+}
+
+/// `orb branch create`: starts a branch at `start` (default: HEAD). HEAD stays on the
+/// current branch.
+pub fn create(name: &str, start: Option<&str>) -> Result<(), Box<dyn Error>> {
+    repo::open()?;
+    validate_name(name)?;
+    if refs::read_ref(&refs::branch_ref(name)).is_some() {
+        return Err(RepoError::BranchExists(name.to_string()).into());
+    }
+    let tip = match start {
+        Some(start) => refs::resolve(start)?,
+        None => refs::head_commit().ok_or(RepoError::NoCommits)?,
+    };
+    refs::write_ref(&refs::branch_ref(name), &tip)?;
+    info!("🌱 Created branch '{}' at {}", name, style::id(&tip[..7.min(tip.len())]));
+    Ok(())
+}
+
+/// `orb branch delete`: removes a branch other than the current one. Unless `force`
+/// is set, its tip must be contained in HEAD so no commits become unreachable.
+pub fn delete(name: &str, force: bool) -> Result<(), Box<dyn Error>> {
+    validate_name(name)?;
+    repo::open()?;
+    let tip = refs::read_ref(&refs::branch_ref(name)).ok_or_else(|| RepoError::NoSuchBranch(name.to_string()))?;
+    let current = refs::current_branch();
+    if name == current {
+        return Err(RepoError::CurrentBranch(current).into());
+    }
+    if !force && !refs::head_commit().is_some_and(|head| ancestry::is_ancestor(&tip, &head)) {
+        return Err(RepoError::NotMerged { branch: name.to_string(), into: current }.into());
+    }
+    refs::delete_ref(&refs::branch_ref(name))?;
+    info!("🗑️  Deleted branch '{}' (was {})", name, style::id(&tip[..7.min(tip.len())]));
+    Ok(())
+}
+
+/// `orb switch`: points HEAD at `name` and replaces the working tree with its tip:
+/// files are restored, and tracked files the tip lacks are deleted. Unless `force` is
+/// set, refuses when that would lose modified or deleted tracked files, or untracked
+/// files the tip would overwrite.
+pub fn switch(name: &str, force: bool) -> Result<(), Box<dyn Error>> {
+    validate_name(name)?;
+    let repository = repo::open_worktree()?;
+    repo::ensure_idle(&repository)?;
+    let tip = refs::read_ref(&refs::branch_ref(name)).ok_or_else(|| RepoError::NoSuchBranch(name.to_string()))?;
+    if name == refs::current_branch() {
+        info!("✅ Already on '{}'", name);
+        return Ok(());
+    }
+
+    if !force {
+        repo::ensure_no_overwrite(&repository, &repository.commit_files(&tip)?)?;
+    }
+
+    let removed = repository.replace_worktree(&repository.load_commit(&tip)?.tree)?;
+    refs::set_head_branch(name)?;
+    info!("🔀 Switched to branch '{}' at {}", name, style::id(&tip[..7.min(tip.len())]));
+    if !removed.is_empty() {
+        info!("   Removed {} tracked file(s) that '{}' does not have", removed.len(), name);
+    }
+    Ok(())
+}
+
+/// `orb branch list`: every local branch with its tip commit and subject; the current
+/// branch is marked with `*`, even before its first commit
+pub fn list() -> Result<(), Box<dyn Error>> {
+    repo::open()?;
+    let current = refs::current_branch();
+    let branches = refs::list_branches();
+    let width = branches.keys().map(String::len).chain([current.len()]).max().unwrap_or_default();
+    let mut out = std::io::stdout().lock();
+    // Pad before painting, so color codes do not count toward the width
+    let pad = |name: &str| format!("{:<width$}", name, width = width);
+    if !branches.contains_key(&current) {
+        writeln!(out, "* {} (no commits yet)", style::paint(&pad(&current), Color::Green))?;
+    }
+    for (name, tip) in &branches {
+        let subject = ancestry::load_commit(tip)
+            .map(|commit| commit.message.lines().next().unwrap_or_default().to_string())
+            .unwrap_or_default();
+        let (marker, name) = match *name == current {
+            true => ('*', style::paint(&pad(name), Color::Green).into_owned()),
+            false => (' ', pad(name)),
+        };
+        writeln!(out, "{} {} {} {}", marker, name, style::id(&tip[..7.min(tip.len())]), subject)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_branch_names() {
+        for name in ["main", "feature-x", "release_1.2", "fix+docs"] {
+            assert!(validate_name(name).is_ok(), "{}", name);
+        }
+        for name in ["", "HEAD", "-f", ".hidden", "a/b", "x.lock", "with space"] {
+            assert!(validate_name(name).is_err(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_names_cannot_escape_the_branch_refs() {
+        for name in ["..", "../tags/v1.0", "../../HEAD", "/etc/passwd"] {
+            for result in [delete(name, true), switch(name, true)] {
+                let error = result.unwrap_err();
+                assert!(matches!(error.downcast_ref(), Some(RepoError::InvalidBranchName(_))), "{}: {}", name, error);
+            }
+        }
+    }
+}
@@ -0,0 +1,225 @@
+use git2::{Oid, Repository};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use crate::fetch::{self, ImportOptions, COMMIT_MAP_FILE, GIT_DIR};
+use crate::git_auth::GitAuth;
+use crate::objects::{Commit, Directory, ObjectId};
+use crate::progress::Progress;
+use crate::{ancestry, merge, refs, vos};
+use tracing::info;
+
+/// Fetches `branch` from a Git remote (a URL, or a remote of `.orb/git` such as
+/// `origin`), converts the commits not converted before, and integrates them into the
+/// current branch with `strategy`
+pub fn pull(remote: &str, branch: Option<&str>, auth: &GitAuth, strategy: merge::Strategy) -> Result<(), Box<dyn std::error::Error>> {
+    let git_repo = open_bridge_repository()?;
+    let git_branch = git_branch(&git_repo, branch);
+    let tracking = format!("refs/remotes/bridge/{}", git_branch);
+    info!("📥 Pulling {} from {}", git_branch, remote);
+    let options = ImportOptions { auth: auth.clone(), ..Default::default() };
+    let progress = Progress::new();
+    let result = find_remote(&git_repo, remote)?.fetch(
+        &[format!("+refs/heads/{}:{}", git_branch, tracking)],
+        Some(&mut fetch::fetch_options(&options, &progress)),
+        None,
+    );
+    progress.finish();
+    result?;
+
+    let upstream = git_repo.refname_to_id(&tracking)?;
+    let tip = fetch::convert_git_history(&git_repo, upstream, &[], &progress)?;
+    fetch::integrate_converted(&git_branch, &refs::current_branch(), &tip, strategy)
+}
+
+/// Exports the commits of the current branch that Git does not have yet to `.orb/git`
+/// and pushes them to `branch` of the Git remote. The remote must not have commits
+/// that were not pulled; `orb bridge pull` brings those in first.
+pub fn push(remote: &str, branch: Option<&str>, auth: &GitAuth) -> Result<(), Box<dyn std::error::Error>> {
+    let git_repo = open_bridge_repository()?;
+    let git_branch = git_branch(&git_repo, branch);
+    let orbit_branch = refs::current_branch();
+    let tip = refs::read_ref(&refs::branch_ref(&orbit_branch)).ok_or_else(|| format!("branch '{}' has no commits to push", orbit_branch))?;
+
+    let mut exporter = Exporter::new(&git_repo)?;
+    let pending = exporter.unexported(&tip)?;
+    if !pending.is_empty() {
+        info!("🔄 Exporting {} Orbit commits to Git...", pending.len());
+    }
+    for (id, commit) in &pending {
+        exporter.export_commit(id, commit)?;
+    }
+    let git_tip = exporter.commits[&tip];
+    let local_ref = format!("refs/heads/{}", git_branch);
+    git_repo.reference(&local_ref, git_tip, true, "orb bridge push")?;
+    if git_repo.head().is_err() {
+        // A new bridge repository; its unborn HEAD follows the first pushed branch
+        git_repo.set_head(&local_ref)?;
+    }
+
+    info!("📤 Pushing {} to {}", git_branch, remote);
+    let mut rejected = None;
+    let pushed = {
+        let mut callbacks = auth.callbacks();
+        callbacks.push_update_reference(|_, status| {
+            rejected = status.map(str::to_string);
+            Ok(())
+        });
+        let mut push_options = git2::PushOptions::new();
+        push_options.remote_callbacks(callbacks);
+        find_remote(&git_repo, remote)?.push(&[format!("{}:{}", local_ref, local_ref)], Some(&mut push_options))
+    };
+    match pushed {
+        // Local remotes report a rejected update as an error rather than through the callback
+        Err(e) if e.code() == git2::ErrorCode::NotFastForward => rejected = Some("not a fast-forward".to_string()),
+        result => result?,
+    }
+    if let Some(reason) = rejected {
+        return Err(format!("the Git remote refused {} ({}); run `orb bridge pull {}` first", git_branch, reason, remote).into());
+    }
+    refs::write_ref(&refs::remote_tracking_ref(fetch::TRACKING_REMOTE, &git_branch), &tip)?;
+    info!("✅ {} is at {} ({} commits exported)", git_branch, &git_tip.to_string()[..7], pending.len());
+    Ok(())
+}
+
+/// The Git repository kept in `.orb/git`, created (bare) if the repository was not
+/// imported from Git
+fn open_bridge_repository() -> Result<Repository, Box<dyn std::error::Error>> {
+    if !Path::new(".orb").is_dir() {
+        return Err("not an Orbit repository".into());
+    }
+    if !Path::new(COMMIT_MAP_FILE).exists() {
+        fs::File::create(COMMIT_MAP_FILE)?;
+    }
+    match Repository::open_bare(GIT_DIR) {
+        Ok(git_repo) => Ok(git_repo),
+        Err(_) => Ok(Repository::init_bare(GIT_DIR)?),
+    }
+}
+
+/// `branch`, or the branch `.orb/git` has checked out, or else the current Orbit branch
+fn git_branch(git_repo: &Repository, branch: Option<&str>) -> String {
+    if let Some(branch) = branch {
+        return branch.to_string();
+    }
+    match git_repo.head() {
+        Ok(head) if head.is_branch() => head.shorthand().map(str::to_string).unwrap_or_else(refs::current_branch),
+        _ => refs::current_branch(),
+    }
+}
+
+/// A remote of `.orb/git` by name, or an anonymous one for a URL
+fn find_remote<'r>(git_repo: &'r Repository, remote: &str) -> Result<git2::Remote<'r>, git2::Error> {
+    git_repo.find_remote(remote).or_else(|_| git_repo.remote_anonymous(remote))
+}
+
+/// Writes Orbit commits into a Git repository, recording each in the commit map
+struct Exporter<'r> {
+    git_repo: &'r Repository,
+    /// Git commits of every Orbit commit converted or exported so far
+    commits: HashMap<ObjectId, Oid>,
+    checkpoint: fs::File,
+    trees: HashMap<ObjectId, Oid>,
+    blobs: HashMap<ObjectId, Oid>,
+}
+
+impl<'r> Exporter<'r> {
+    fn new(git_repo: &'r Repository) -> Result<Self, Box<dyn std::error::Error>> {
+        let commits = fetch::load_commit_map(Path::new(COMMIT_MAP_FILE))?
+            .into_iter()
+            .filter(|(git, _)| git_repo.find_commit(*git).is_ok())
+            .map(|(git, orbit)| (orbit, git))
+            .collect();
+        Ok(Exporter {
+            git_repo,
+            commits,
+            checkpoint: fs::OpenOptions::new().append(true).open(COMMIT_MAP_FILE)?,
+            trees: HashMap::new(),
+            blobs: HashMap::new(),
+        })
+    }
+
+    /// Ancestors of `tip` (itself included) that have no Git commit yet, parents first
+    fn unexported(&self, tip: &str) -> Result<Vec<(ObjectId, Commit)>, Box<dyn std::error::Error>> {
+        let mut pending = Vec::new();
+        let mut seen = HashSet::new();
+        let mut stack = vec![(tip.to_string(), None)];
+        while let Some((id, commit)) = stack.pop() {
+            if let Some(commit) = commit {
+                pending.push((id, commit));
+                continue;
+            }
+            if self.commits.contains_key(&id) || !seen.insert(id.clone()) {
+                continue;
+            }
+            let commit = ancestry::load_commit(&id).ok_or_else(|| format!("commit {} is missing", id))?;
+            let parents = commit.parents.clone();
+            stack.push((id, Some(commit)));
+            stack.extend(parents.into_iter().rev().map(|parent| (parent, None)));
+        }
+        Ok(pending)
+    }
+
+    fn export_commit(&mut self, id: &str, commit: &Commit) -> Result<Oid, Box<dyn std::error::Error>> {
+        let tree = self.git_repo.find_tree(self.export_tree(&commit.tree)?)?;
+        let (name, email) = split_author(&commit.author);
+        let signature = git2::Signature::new(name, email, &git2::Time::new(commit.timestamp, 0))?;
+        let parents = commit
+            .parents
+            .iter()
+            .map(|parent| self.git_repo.find_commit(self.commits[parent]))
+            .collect::<Result<Vec<_>, _>>()?;
+        let parents: Vec<&git2::Commit> = parents.iter().collect();
+        let oid = self.git_repo.commit(None, &signature, &signature, &commit.message, &tree, &parents)?;
+        writeln!(self.checkpoint, "{} {}", oid, id)?;
+        self.commits.insert(id.to_string(), oid);
+        Ok(oid)
+    }
+
+    fn export_tree(&mut self, tree_id: &str) -> Result<Oid, Box<dyn std::error::Error>> {
+        if let Some(oid) = self.trees.get(tree_id) {
+            return Ok(*oid);
+        }
+        let directory: Directory = vos::load(tree_id, "tree")?;
+        let mut builder = self.git_repo.treebuilder(None)?;
+        for entry in &directory.entries {
+            let oid = match entry.mode {
+                0o040000 => self.export_tree(&entry.id)?,
+                _ => self.export_blob(&entry.id)?,
+            };
+            builder.insert(&entry.name, oid, entry.mode as i32)?;
+        }
+        let oid = builder.write()?;
+        self.trees.insert(tree_id.to_string(), oid);
+        Ok(oid)
+    }
+
+    fn export_blob(&mut self, file_id: &str) -> Result<Oid, Box<dyn std::error::Error>> {
+        if let Some(oid) = self.blobs.get(file_id) {
+            return Ok(*oid);
+        }
+        let oid = self.git_repo.blob(&vos::file_content(file_id)?)?;
+        self.blobs.insert(file_id.to_string(), oid);
+        Ok(oid)
+    }
+}
+
+/// Name and email of an `Name <email>` author
+pub fn split_author(author: &str) -> (&str, &str) {
+    match author.rsplit_once('<') {
+        Some((name, email)) if !name.trim().is_empty() => (name.trim(), email.trim_end_matches('>').trim()),
+        _ => (author.trim(), ""),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_author() {
+        assert_eq!(split_author("Alice Doe <alice@example.com>"), ("Alice Doe", "alice@example.com"));
+        assert_eq!(split_author("alice"), ("alice", ""));
+    }
+}
@@ -0,0 +1,83 @@
+use std::path::{Path, PathBuf};
+use crate::client_tls;
+use tracing::info;
+
+/// How long a generated self-signed certificate stays valid
+const VALIDITY_DAYS: i64 = 3 * 365;
+
+/// Directory holding server configuration and its TLS certificate: `$ORB_SERVER_DIR`,
+/// else `~/.orb-server`
+pub fn server_dir() -> Result<PathBuf, Box<dyn std::error::Error>> {
+    if let Ok(dir) = std::env::var("ORB_SERVER_DIR") {
+        return Ok(PathBuf::from(dir));
+    }
+    let home = std::env::var("HOME").or_else(|_| std::env::var("USERPROFILE"))
+        .map_err(|_| "Cannot find home directory for the server config (set ORB_SERVER_DIR or use --dir)")?;
+    Ok(Path::new(&home).join(".orb-server"))
+}
+
+/// Certificate and private key paths inside a server config directory
+pub fn paths(dir: &Path) -> (PathBuf, PathBuf) {
+    (dir.join("cert.pem"), dir.join("key.pem"))
+}
+
+/// Generates a self-signed certificate for `hostnames` (IP addresses become IP SANs)
+/// and writes `cert.pem`/`key.pem` into `dir`. Returns the `sha256/...` pin of its key.
+pub fn generate(hostnames: &[String], dir: &Path, force: bool) -> Result<String, Box<dyn std::error::Error>> {
+    let first = hostnames.first().ok_or("at least one hostname is required")?;
+    let (cert_path, key_path) = paths(dir);
+    if !force && (cert_path.exists() || key_path.exists()) {
+        return Err(format!("{} already exists (use --force to replace it)", cert_path.display()).into());
+    }
+
+    let mut params = rcgen::CertificateParams::new(hostnames.to_vec());
+    params.distinguished_name.push(rcgen::DnType::CommonName, first.as_str());
+    params.distinguished_name.push(rcgen::DnType::OrganizationName, "Orbit self-signed");
+    let now = time::OffsetDateTime::now_utc();
+    params.not_before = now - time::Duration::days(1);
+    params.not_after = now + time::Duration::days(VALIDITY_DAYS);
+    let cert = rcgen::Certificate::from_params(params)?;
+
+    std::fs::create_dir_all(dir)?;
+    std::fs::write(&cert_path, cert.serialize_pem()?)?;
+    write_private(&key_path, cert.serialize_private_key_pem().as_bytes())?;
+
+    let spki = client_tls::spki_sha256(&cert.serialize_der()?)?;
+    Ok(client_tls::format_pin(&spki))
+}
+
+/// Days until the first certificate in a PEM file expires (negative once expired),
+/// or None if the file is missing or unreadable
+pub fn days_remaining(cert_path: &Path) -> Option<i64> {
+    let pem = std::fs::read(cert_path).ok()?;
+    let der = rustls_pemfile::certs(&mut pem.as_slice()).next()?.ok()?;
+    let (_, parsed) = x509_parser::parse_x509_certificate(&der).ok()?;
+    let not_after = parsed.validity().not_after.timestamp();
+    Some((not_after - time::OffsetDateTime::now_utc().unix_timestamp()) / 86_400)
+}
+
+/// Writes a file readable only by its owner
+pub fn write_private(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    std::io::Write::write_all(&mut options.open(path)?, contents)
+}
+
+/// `orb cert`: generates the certificate and explains how clients should pin it
+pub fn run(hostnames: &[String], dir: &Path, force: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let pin = generate(hostnames, dir, force)?;
+    let (cert_path, key_path) = paths(dir);
+
+    info!("🔏 Generated a self-signed certificate for {}", hostnames.join(", "));
+    info!("   Certificate: {}", cert_path.display());
+    info!("   Private key: {}", key_path.display());
+    info!("📌 Pin: {}", pin);
+    info!("💡 Clients trust this server without --insecure by pinning its key:");
+    info!("   orb remote add origin orbits://{}/<namespace>/<repo> --pin {}", hostnames[0], pin);
+    Ok(())
+}
@@ -0,0 +1,187 @@
+use std::error::Error;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::{info, warn};
+use orbit_core::Repository;
+use crate::objects::{Commit, ObjectId};
+use crate::repo::{self, RepoError};
+use crate::{merge, refs, style, vos};
+
+/// Applying the change a single commit made to HEAD, as a new commit
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Operation {
+    /// `orb cherry-pick`: the change itself, keeping the commit's author and message
+    CherryPick,
+    /// `orb revert <commit>`: the inverse of the change, in a commit that names it
+    Revert,
+}
+
+impl Operation {
+    /// Ref in `.orb` naming the commit being applied while its conflicts are resolved
+    fn state_ref(self) -> &'static str {
+        match self {
+            Operation::CherryPick => "CHERRY_PICK_HEAD",
+            Operation::Revert => "REVERT_HEAD",
+        }
+    }
+
+    fn command(self) -> &'static str {
+        match self {
+            Operation::CherryPick => "cherry-pick",
+            Operation::Revert => "revert",
+        }
+    }
+
+    fn in_progress(self) -> RepoError {
+        match self {
+            Operation::CherryPick => RepoError::CherryPickInProgress,
+            Operation::Revert => RepoError::RevertInProgress,
+        }
+    }
+
+    fn not_in_progress(self) -> RepoError {
+        match self {
+            Operation::CherryPick => RepoError::NotPicking,
+            Operation::Revert => RepoError::NotReverting,
+        }
+    }
+
+    /// The commit recording the change of `commit` (`id`) on top of `parent`
+    fn commit(self, id: &str, commit: Commit, tree: ObjectId, parent: ObjectId) -> Commit {
+        match self {
+            Operation::CherryPick => Commit { tree, parents: vec![parent], ..commit },
+            Operation::Revert => Commit {
+                tree,
+                parents: vec![parent],
+                author: repo::DEFAULT_AUTHOR.to_string(),
+                timestamp: SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |now| now.as_secs() as i64),
+                message: revert_message(id, &commit),
+                signature: None,
+            },
+        }
+    }
+}
+
+/// The operation in progress and the commit it is applying, while a cherry-pick or
+/// revert with conflicts is being resolved
+pub fn in_progress(repository: &Repository) -> Option<(Operation, ObjectId)> {
+    [Operation::CherryPick, Operation::Revert]
+        .into_iter()
+        .find_map(|operation| repository.read_ref(operation.state_ref()).map(|id| (operation, id)))
+}
+
+/// Fails while a cherry-pick or revert with conflicts is in progress
+pub fn ensure_not_picking(repository: &Repository) -> Result<(), RepoError> {
+    match in_progress(repository) {
+        Some((operation, _)) => Err(operation.in_progress()),
+        None => Ok(()),
+    }
+}
+
+/// True if `arg` names a commit rather than a file of HEAD or the working tree, so
+/// `orb revert <arg>` reverts that commit
+pub fn names_commit(repository: &Repository, arg: &str) -> bool {
+    let in_head = refs::head_commit()
+        .and_then(|head| repository.load_commit(&head).ok())
+        .and_then(|head| repository.tree_entry(&head.tree, &orbit_core::normalize_pathspec(arg)).ok().flatten())
+        .is_some();
+    !in_head && !repository.root().join(arg).exists() && refs::resolve(arg).is_ok()
+}
+
+fn short(id: &str) -> String {
+    style::id(&id[..7.min(id.len())]).into_owned()
+}
+
+/// `orb cherry-pick` and `orb revert <commit>`: applies the change `revision` made to
+/// its parent (or its inverse) onto HEAD and commits the result on the current branch.
+/// A pick keeps the original author and message; a revert gets a message naming the
+/// reverted commit.
+///
+/// Text files both changed are merged line by line. Where the same lines were changed,
+/// the operation stops like a merge: the files get conflict markers for `orb resolve`,
+/// and `--continue` commits the result.
+pub fn apply(operation: Operation, revision: &str) -> Result<(), Box<dyn Error>> {
+    let repository = repo::open_worktree()?;
+    repo::ensure_idle(&repository)?;
+    let head = refs::head_commit().ok_or(RepoError::NoCommits)?;
+    let id = refs::resolve(revision)?;
+    let commit = repository.load_commit(&id)?;
+    if commit.parents.len() > 1 {
+        return Err(RepoError::PickMerge(id).into());
+    }
+
+    let (files, conflicts) = match operation {
+        Operation::CherryPick => merge::replay(&commit, &head, &short(&id))?,
+        Operation::Revert => merge::replay_inverse(&commit, &head, &format!("revert of {}", short(&id)))?,
+    };
+    repo::ensure_no_overwrite(&repository, &files)?;
+    if !conflicts.is_empty() {
+        let count = merge::write_conflicts(&repository, &files, conflicts)?;
+        repository.write_ref(operation.state_ref(), &id)?;
+        let command = operation.command();
+        warn!("💡 Fix the conflicts, mark each with `orb resolve <path>`, then run `orb {} --continue`", command);
+        warn!("   (or `orb {} --abort` to go back)", command);
+        return Err(match operation {
+            Operation::CherryPick => RepoError::PickConflicts(count),
+            Operation::Revert => RepoError::RevertConflicts(count),
+        }
+        .into());
+    }
+    if files == repository.commit_files(&head)? {
+        info!("✅ {} changes nothing on '{}'", short(&id), repository.current_branch());
+        return Ok(());
+    }
+
+    let tree = merge::build_tree(&files)?;
+    let applied = operation.commit(&id, commit, tree, head);
+    let applied_id = vos::store().save_json(&applied)?;
+    repository.replace_worktree(&applied.tree)?;
+    refs::write_ref(&refs::branch_ref(&repository.current_branch()), &applied_id)?;
+    report(&repository, operation, &id, &applied_id, &applied.message);
+    Ok(())
+}
+
+/// `--continue`: commits the resolved working tree as the picked or reverting commit
+pub fn resume(operation: Operation) -> Result<(), Box<dyn Error>> {
+    let repository = repo::open_worktree()?;
+    let id = repository.read_ref(operation.state_ref()).ok_or(operation.not_in_progress())?;
+    // The whole working tree is the result, as after a merge
+    let mut index = repository.index()?;
+    index.staged.clear();
+    repository.save_index(&index)?;
+    let commit = repository.load_commit(&id)?;
+    let (message, author) = match operation {
+        Operation::CherryPick => (commit.message, commit.author),
+        Operation::Revert => (revert_message(&id, &commit), repo::DEFAULT_AUTHOR.to_string()),
+    };
+    let applied_id = repository.save(&message, &author)?;
+    orbit_core::refs::delete_ref(repository.orb_dir(), operation.state_ref())?;
+    report(&repository, operation, &id, &applied_id, &message);
+    Ok(())
+}
+
+/// `--abort`: returns the working tree to HEAD and forgets the cherry-pick or revert
+pub fn abort(operation: Operation) -> Result<(), Box<dyn Error>> {
+    let repository = repo::open_worktree()?;
+    repository.read_ref(operation.state_ref()).ok_or(operation.not_in_progress())?;
+    let head = refs::head_commit().ok_or(RepoError::NoCommits)?;
+    repository.replace_worktree(&repository.load_commit(&head)?.tree)?;
+    orbit_core::refs::delete_ref(repository.orb_dir(), operation.state_ref())?;
+    info!("↩️  The {} was aborted; the working tree is back at {}", operation.command(), short(&head));
+    Ok(())
+}
+
+fn report(repository: &Repository, operation: Operation, id: &str, applied_id: &str, message: &str) {
+    let branch = repository.current_branch();
+    match operation {
+        Operation::CherryPick => info!("🍒 Picked {} onto '{}' as {}: {}", short(id), branch, short(applied_id), subject(message)),
+        Operation::Revert => info!("⏪ Reverted {} on '{}' as {}: {}", short(id), branch, short(applied_id), subject(message)),
+    }
+}
+
+fn revert_message(id: &str, commit: &Commit) -> String {
+    format!("Revert \"{}\"\n\nThis reverts commit {}.\n", subject(&commit.message), id)
+}
+
+fn subject(message: &str) -> &str {
+    message.lines().next().unwrap_or_default()
+}
@@ -0,0 +1,180 @@
+use tracing::info;
+use crate::auth;
+use crate::client_tls::OrbitUrl;
+use crate::objects::ObjectId;
+use crate::remote::Remote;
+use crate::secure_channel::Negotiated;
+use crate::transport::{self, NetOptions, VnpReader, VnpWriter, WireCounters};
+use crate::vnp::{self, RefAdvertisement, RepositoryInfo, VnpCommand, VnpError};
+
+/// One VNP connection to a server, shared by every network command.
+///
+/// A session is authenticated first (with the API token, or anonymously for reads),
+/// then optionally selects a repository. Each operation ends with Ready/Ok rather
+/// than a disconnect, so the same session can switch repositories and run further
+/// fetches, pushes, or single-object requests without reconnecting or authenticating
+/// again. Object transfers use `reader` and `writer` directly.
+pub struct Session {
+    pub reader: VnpReader,
+    pub writer: VnpWriter,
+    /// Suppresses the authentication and selection messages, e.g. when stdout carries JSON
+    pub quiet: bool,
+    repository: Option<String>,
+    /// The server declined the encrypted channel (see `net.allow_plaintext`), so the
+    /// token is never sent
+    plaintext: bool,
+}
+
+impl Session {
+    /// Connects without authenticating; `authenticate` or `authenticate_for_read` must come next
+    pub async fn connect(url: &OrbitUrl, net: &NetOptions, wire: &WireCounters) -> Result<Self, Box<dyn std::error::Error>> {
+        let (reader, writer, plaintext) = match transport::connect(url, net, wire).await? {
+            Negotiated::Secure(reader, writer) => (reader, writer, false),
+            Negotiated::Plaintext(reader, writer) => (reader, writer, true),
+        };
+        Ok(Session { reader, writer, quiet: false, repository: None, plaintext })
+    }
+
+    /// Connects, authenticates (with a token when `write` is set), and selects the
+    /// repository named in `url`, if any
+    pub async fn open(
+        remote: &Remote,
+        url: &OrbitUrl,
+        write: bool,
+        net: &NetOptions,
+        wire: &WireCounters,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut session = Session::connect(url, net, wire).await?;
+        if write {
+            session.authenticate(remote.token_env.as_deref()).await?;
+        } else {
+            session.authenticate_for_read(remote).await?;
+        }
+        if let Some(repository) = &url.repository {
+            session.select(repository).await?;
+        }
+        Ok(session)
+    }
+
+    fn note(&self, message: &str) {
+        if !self.quiet {
+            info!("{}", message);
+        }
+    }
+
+    /// Authenticates with the API token from the credential store (required for writes)
+    pub async fn authenticate(&mut self, token_env: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+        if self.plaintext {
+            return Err("refusing to send the token over an unencrypted connection; use orbits:// to authenticate".into());
+        }
+        self.note("🔐 Authenticating with server...");
+        let token = auth::load_token(token_env)?;
+        auth::send_credentials(&mut self.reader, &mut self.writer, Some(token)).await?;
+        self.note("✅ Authenticated successfully");
+        Ok(())
+    }
+
+    /// Authenticates a session that only needs to read. Without a token the session
+    /// is opened anonymously, which servers allow for public repositories; peers and
+    /// unencrypted sessions are never sent a token. Returns whether the session may also
+    /// write.
+    pub async fn authenticate_for_read(&mut self, remote: &Remote) -> Result<bool, Box<dyn std::error::Error>> {
+        if remote.is_peer() || self.plaintext {
+            auth::send_credentials(&mut self.reader, &mut self.writer, None).await?;
+            self.note(match remote.is_peer() {
+                true => "🤝 Connected to peer (read-only)",
+                false => "👤 Connected anonymously, as the connection is unencrypted (read-only access to public repositories)",
+            });
+            return Ok(false);
+        }
+        self.note("🔐 Authenticating with server...");
+        let token = auth::find_token(remote.token_env.as_deref()).map(|(token, source)| {
+            self.note(&format!("🔑 Using {}", source));
+            token
+        });
+        let anonymous = token.is_none();
+        let can_write = auth::send_credentials(&mut self.reader, &mut self.writer, token).await?;
+        match anonymous {
+            true => self.note("👤 Connected anonymously (read-only access to public repositories)"),
+            false => self.note("✅ Authenticated successfully"),
+        }
+        Ok(can_write)
+    }
+
+    /// Selects `repository` for the following operations; a no-op if it is already selected
+    pub async fn select(&mut self, repository: &str) -> Result<(), Box<dyn std::error::Error>> {
+        if self.repository.as_deref() == Some(repository) {
+            return Ok(());
+        }
+        self.note(&format!("📂 Selecting repository: {}", repository));
+        vnp::send_command(&mut self.writer, VnpCommand::SelectRepository(repository.to_string())).await?;
+        match vnp::recv_command(&mut self.reader).await? {
+            VnpCommand::RepositorySelected(selected) => self.note(&format!("✅ Repository '{}' selected", selected)),
+            VnpCommand::Error(message) if message.contains("not found") => {
+                return Err(VnpError::NoSuchRepository(repository.to_string()).into());
+            }
+            response => return Err(VnpError::response("repository selection", response).into()),
+        }
+        self.repository = Some(repository.to_string());
+        Ok(())
+    }
+
+    /// Creates a repository on the server, which selects it for the rest of the session
+    pub async fn create_repository(&mut self, repository: &str) -> Result<(), Box<dyn std::error::Error>> {
+        vnp::send_command(&mut self.writer, VnpCommand::CreateRepository(repository.to_string())).await?;
+        match vnp::recv_command(&mut self.reader).await? {
+            VnpCommand::RepositorySelected(created) => {
+                info!("✅ Created and selected repository: {}", created);
+                self.repository = Some(created);
+                Ok(())
+            }
+            response => Err(VnpError::response("repository creation", response).into()),
+        }
+    }
+
+    /// Sends a command that the server answers with Ok or Error
+    pub async fn request(&mut self, command: VnpCommand, action: &'static str) -> Result<(), Box<dyn std::error::Error>> {
+        vnp::send_command(&mut self.writer, command).await?;
+        match vnp::recv_command(&mut self.reader).await? {
+            VnpCommand::Ok => Ok(()),
+            response => Err(VnpError::response(action, response).into()),
+        }
+    }
+
+    /// The selected repository's branches and tags
+    pub async fn list_refs(&mut self) -> Result<RefAdvertisement, Box<dyn std::error::Error>> {
+        vnp::send_command(&mut self.writer, VnpCommand::ListRefs).await?;
+        match vnp::recv_command(&mut self.reader).await? {
+            VnpCommand::RefList(refs) => Ok(refs),
+            response => Err(VnpError::response("ref advertisement", response).into()),
+        }
+    }
+
+    /// The repositories this session can see (only public ones when anonymous)
+    pub async fn list_repositories(&mut self) -> Result<Vec<RepositoryInfo>, Box<dyn std::error::Error>> {
+        vnp::send_command(&mut self.writer, VnpCommand::ListRepositories).await?;
+        match vnp::recv_command(&mut self.reader).await? {
+            VnpCommand::RepositoryList(repositories) => Ok(repositories),
+            response => Err(VnpError::response("repository listing", response).into()),
+        }
+    }
+
+    /// Tells the server which commits we have (Have) and returns the ones it has
+    /// that we lack (Want)
+    pub async fn negotiate(&mut self, have: &[ObjectId]) -> Result<Vec<ObjectId>, Box<dyn std::error::Error>> {
+        vnp::send_command(&mut self.writer, VnpCommand::Have(have.to_vec())).await?;
+        match vnp::recv_command(&mut self.reader).await? {
+            VnpCommand::Want(missing) => Ok(missing),
+            response => Err(VnpError::response("negotiation", response).into()),
+        }
+    }
+
+    /// Ends the current operation; the connection stays open for the next one
+    pub async fn finish(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        vnp::send_command(&mut self.writer, VnpCommand::Ready).await?;
+        match vnp::recv_command(&mut self.reader).await? {
+            VnpCommand::Ok => Ok(()),
+            response => Err(VnpError::response("finalization", response).into()),
+        }
+    }
+}
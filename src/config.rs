@@ -0,0 +1,14 @@
+use std::path::Path;
+use orbit_core::ORB_DIR;
+
+pub use orbit_core::config::Config;
+
+/// Loads `.orb/config`, or returns an empty config outside a repository
+pub fn load() -> Result<Config, std::io::Error> {
+    Config::load(&Path::new(ORB_DIR).join("config"))
+}
+
+/// Writes the config back to `.orb/config`
+pub fn save(config: &Config) -> Result<(), std::io::Error> {
+    config.save(&Path::new(ORB_DIR).join("config"))
+}
@@ -0,0 +1,270 @@
+/// One line of a line-by-line comparison; lines keep their `\n`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Line<'a> {
+    Same(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+/// A run of changes with the unchanged lines around them, as in a unified diff
+#[derive(Debug, PartialEq)]
+pub struct Hunk<'a> {
+    /// First line of the hunk on each side, counting from 1 (0 for an empty side)
+    pub old_start: usize,
+    pub old_len: usize,
+    pub new_start: usize,
+    pub new_len: usize,
+    pub lines: Vec<Line<'a>>,
+}
+
+/// Splits text into lines, each keeping its `\n` (the last one may lack it)
+pub fn split_lines(text: &str) -> Vec<&str> {
+    text.split_inclusive('\n').collect()
+}
+
+/// Shortest edit script turning `old` into `new` (Myers' algorithm), with removals
+/// before additions wherever both are possible
+pub fn diff_lines<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<Line<'a>> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..].iter().rev().zip(new[prefix..].iter().rev()).take_while(|(a, b)| a == b).count();
+    let (a, b) = (&old[prefix..old.len() - suffix], &new[prefix..new.len() - suffix]);
+
+    let mut lines: Vec<Line> = old[..prefix].iter().map(|line| Line::Same(line)).collect();
+    lines.extend(myers(a, b));
+    lines.extend(old[old.len() - suffix..].iter().map(|line| Line::Same(line)));
+    lines
+}
+
+fn myers<'a>(a: &[&'a str], b: &[&'a str]) -> Vec<Line<'a>> {
+    let (n, m) = (a.len() as isize, b.len() as isize);
+    let max = (n + m) as usize;
+    let offset = max as isize;
+    let mut v = vec![0isize; 2 * max + 2];
+    // The furthest x reached on each diagonal before each round, for backtracking
+    let mut trace = Vec::new();
+    'search: for d in 0..=max as isize {
+        trace.push(v.clone());
+        for k in (-d..=d).step_by(2) {
+            let index = (k + offset) as usize;
+            let mut x = if k == -d || (k != d && v[index - 1] < v[index + 1]) { v[index + 1] } else { v[index - 1] + 1 };
+            let mut y = x - k;
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[index] = x;
+            if x >= n && y >= m {
+                break 'search;
+            }
+        }
+    }
+
+    let mut lines = Vec::new();
+    let (mut x, mut y) = (n, m);
+    for (d, v) in trace.iter().enumerate().rev() {
+        let (d, k) = (d as isize, x - y);
+        let previous_k = if k == -d || (k != d && v[(k - 1 + offset) as usize] < v[(k + 1 + offset) as usize]) { k + 1 } else { k - 1 };
+        let previous_x = v[(previous_k + offset) as usize];
+        let previous_y = previous_x - previous_k;
+        while x > previous_x && y > previous_y {
+            lines.push(Line::Same(a[x as usize - 1]));
+            x -= 1;
+            y -= 1;
+        }
+        if d > 0 {
+            if x == previous_x {
+                lines.push(Line::Added(b[y as usize - 1]));
+            } else {
+                lines.push(Line::Removed(a[x as usize - 1]));
+            }
+        }
+        (x, y) = (previous_x, previous_y);
+    }
+    lines.reverse();
+    lines
+}
+
+/// Groups a comparison into hunks with `context` unchanged lines around each change;
+/// changes closer than twice that share a hunk
+pub fn hunks<'a>(lines: &[Line<'a>], context: usize) -> Vec<Hunk<'a>> {
+    let changed: Vec<usize> = lines.iter().enumerate().filter(|(_, line)| !matches!(line, Line::Same(_))).map(|(i, _)| i).collect();
+    let mut hunks = Vec::new();
+    let mut group_start = 0;
+    while group_start < changed.len() {
+        let mut group_end = group_start;
+        while group_end + 1 < changed.len() && changed[group_end + 1] - changed[group_end] <= 2 * context + 1 {
+            group_end += 1;
+        }
+        let start = changed[group_start].saturating_sub(context);
+        let end = (changed[group_end] + context + 1).min(lines.len());
+
+        // Line numbers where the hunk starts, from the lines before it
+        let (mut old_line, mut new_line) = (1, 1);
+        for line in &lines[..start] {
+            match line {
+                Line::Same(_) => (old_line, new_line) = (old_line + 1, new_line + 1),
+                Line::Removed(_) => old_line += 1,
+                Line::Added(_) => new_line += 1,
+            }
+        }
+        let hunk_lines = lines[start..end].to_vec();
+        let old_len = hunk_lines.iter().filter(|line| !matches!(line, Line::Added(_))).count();
+        let new_len = hunk_lines.iter().filter(|line| !matches!(line, Line::Removed(_))).count();
+        hunks.push(Hunk {
+            old_start: if old_len == 0 { old_line - 1 } else { old_line },
+            old_len,
+            new_start: if new_len == 0 { new_line - 1 } else { new_line },
+            new_len,
+            lines: hunk_lines,
+        });
+        group_start = group_end + 1;
+    }
+    hunks
+}
+
+/// A change one side made to the base: lines `start..end` replaced by `lines`
+struct Edit<'a> {
+    start: usize,
+    end: usize,
+    lines: Vec<&'a str>,
+}
+
+/// The edits that turn `base` into `side`, in order
+fn edits<'a>(base: &[&'a str], side: &[&'a str]) -> Vec<Edit<'a>> {
+    let mut edits: Vec<Edit> = Vec::new();
+    let mut position = 0;
+    let mut open = false;
+    for line in diff_lines(base, side) {
+        if !open && !matches!(line, Line::Same(_)) {
+            edits.push(Edit { start: position, end: position, lines: Vec::new() });
+            open = true;
+        }
+        match line {
+            Line::Same(_) => {
+                position += 1;
+                open = false;
+            }
+            Line::Removed(_) => {
+                position += 1;
+                edits.last_mut().expect("an edit is open").end = position;
+            }
+            Line::Added(text) => edits.last_mut().expect("an edit is open").lines.push(text),
+        }
+    }
+    edits
+}
+
+/// Applies the `edits` that fall in `base[start..end]` to that range
+fn apply<'a>(base: &[&'a str], start: usize, end: usize, edits: &[&Edit<'a>]) -> Vec<&'a str> {
+    let mut lines = Vec::new();
+    let mut position = start;
+    for edit in edits {
+        lines.extend(&base[position..edit.start]);
+        lines.extend(&edit.lines);
+        position = edit.end;
+    }
+    lines.extend(&base[position..end]);
+    lines
+}
+
+/// Three-way merge of text: changes `ours` and `theirs` made to different lines of `base`
+/// are combined; where both changed the same or adjacent lines differently, both versions
+/// are kept between `<<<<<<< ours_label`, `=======`, and `>>>>>>> theirs_label` markers.
+/// Returns the merged text and the number of conflicts.
+pub fn merge3(base: &str, ours: &str, theirs: &str, ours_label: &str, theirs_label: &str) -> (String, usize) {
+    let base_lines = split_lines(base);
+    let (our_edits, their_edits) = (edits(&base_lines, &split_lines(ours)), edits(&base_lines, &split_lines(theirs)));
+    let (mut a, mut b) = (our_edits.iter().peekable(), their_edits.iter().peekable());
+    let mut merged = String::new();
+    let mut conflicts = 0;
+    let mut position = 0;
+    loop {
+        // The next region: the earliest edit, with every edit that overlaps or touches it
+        let start = match (a.peek(), b.peek()) {
+            (Some(x), Some(y)) => x.start.min(y.start),
+            (Some(x), None) => x.start,
+            (None, Some(y)) => y.start,
+            (None, None) => break,
+        };
+        let (mut from_ours, mut from_theirs) = (Vec::new(), Vec::new());
+        let mut end = start;
+        loop {
+            if let Some(edit) = a.next_if(|edit| edit.start <= end) {
+                end = end.max(edit.end);
+                from_ours.push(edit);
+            } else if let Some(edit) = b.next_if(|edit| edit.start <= end) {
+                end = end.max(edit.end);
+                from_theirs.push(edit);
+            } else {
+                break;
+            }
+        }
+
+        merged.extend(base_lines[position..start].iter().copied());
+        let our_lines = apply(&base_lines, start, end, &from_ours);
+        let their_lines = apply(&base_lines, start, end, &from_theirs);
+        if from_theirs.is_empty() || our_lines == their_lines {
+            merged.extend(our_lines);
+        } else if from_ours.is_empty() {
+            merged.extend(their_lines);
+        } else {
+            conflicts += 1;
+            merged.push_str(&format!("<<<<<<< {}\n", ours_label));
+            push_lines(&mut merged, &our_lines);
+            merged.push_str("=======\n");
+            push_lines(&mut merged, &their_lines);
+            merged.push_str(&format!(">>>>>>> {}\n", theirs_label));
+        }
+        position = end;
+    }
+    merged.extend(base_lines[position..].iter().copied());
+    (merged, conflicts)
+}
+
+/// Appends lines inside conflict markers, ending the last one so the marker starts a line
+fn push_lines(text: &mut String, lines: &[&str]) {
+    text.extend(lines.iter().copied());
+    if !text.ends_with('\n') {
+        text.push('\n');
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff_and_hunks() {
+        let old = split_lines("a\nb\nc\nd\ne\nf\ng\nh\ni\nj\n");
+        let new = split_lines("a\nB\nc\nd\ne\nf\ng\nh\ni\nj\nk\n");
+        let lines = diff_lines(&old, &new);
+        assert_eq!(lines.iter().filter(|line| matches!(line, Line::Same(_))).count(), 9);
+        assert_eq!(lines[1..3], [Line::Removed("b\n"), Line::Added("B\n")]);
+
+        let hunks = hunks(&lines, 3);
+        assert_eq!(hunks.len(), 2);
+        assert_eq!((hunks[0].old_start, hunks[0].old_len, hunks[0].new_start, hunks[0].new_len), (1, 5, 1, 5));
+        assert_eq!((hunks[1].old_start, hunks[1].old_len, hunks[1].new_start, hunks[1].new_len), (8, 3, 8, 4));
+
+        let created = diff_lines(&[], &new);
+        assert_eq!(hunks_of(&created), [(0, 0, 1, 11)]);
+    }
+
+    #[test]
+    fn test_merge3() {
+        let base = "a\nb\nc\nd\ne\n";
+        let (merged, conflicts) = merge3(base, "A\nb\nc\nd\ne\n", "a\nb\nc\nd\nE\nf\n", "ours", "theirs");
+        assert_eq!((merged.as_str(), conflicts), ("A\nb\nc\nd\nE\nf\n", 0));
+
+        let (merged, conflicts) = merge3(base, "a\nB\nc\nd\ne\n", "a\nX\nc\nd\ne\n", "ours", "theirs");
+        assert_eq!(conflicts, 1);
+        assert_eq!(merged, "a\n<<<<<<< ours\nB\n=======\nX\n>>>>>>> theirs\nc\nd\ne\n");
+
+        let (merged, conflicts) = merge3(base, "a\nB\nc\nd\ne\n", "a\nB\nc\nd\ne\n", "ours", "theirs");
+        assert_eq!((merged.as_str(), conflicts), ("a\nB\nc\nd\ne\n", 0));
+    }
+
+    fn hunks_of(lines: &[Line]) -> Vec<(usize, usize, usize, usize)> {
+        hunks(lines, 3).iter().map(|hunk| (hunk.old_start, hunk.old_len, hunk.new_start, hunk.new_len)).collect()
+    }
+}
@@ -0,0 +1,105 @@
+use std::time::Duration;
+use hickory_resolver::TokioAsyncResolver;
+use rand_core::RngCore;
+
+/// How long an SRV lookup may take before the URL's default port is used instead
+const SRV_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// One server advertised by an SRV record
+#[derive(Debug, Clone, PartialEq)]
+pub struct SrvTarget {
+    pub priority: u16,
+    pub weight: u16,
+    pub host: String,
+    pub port: u16,
+}
+
+/// SRV service label for a URL scheme: `_orbit._tcp` for `orbit://`, `_orbits._tcp` for
+/// `orbits://`. Other schemes (and IP literals or `localhost`) are never looked up.
+pub fn service_for(scheme_tls: bool, host: &str) -> Option<&'static str> {
+    if host.parse::<std::net::IpAddr>().is_ok() || host.eq_ignore_ascii_case("localhost") {
+        return None;
+    }
+    Some(if scheme_tls { "_orbits._tcp" } else { "_orbit._tcp" })
+}
+
+/// Looks up `<service>.<host>` SRV records and returns the targets in the order they should
+/// be tried. Any failure (no records, no resolver, timeout) yields an empty list so the
+/// caller falls back to the URL's default port.
+pub async fn lookup(service: &str, host: &str) -> Vec<(String, u16)> {
+    let Ok((config, mut options)) = hickory_resolver::system_conf::read_system_conf() else {
+        return Vec::new();
+    };
+    options.timeout = SRV_TIMEOUT;
+    options.attempts = 1;
+    let resolver = TokioAsyncResolver::tokio(config, options);
+
+    let name = format!("{}.{}.", service, host.trim_end_matches('.'));
+    let records = match tokio::time::timeout(SRV_TIMEOUT, resolver.srv_lookup(name)).await {
+        Ok(Ok(records)) => records,
+        _ => return Vec::new(),
+    };
+    let targets = records.iter()
+        .map(|srv| SrvTarget {
+            priority: srv.priority(),
+            weight: srv.weight(),
+            host: srv.target().to_utf8().trim_end_matches('.').to_string(),
+            port: srv.port(),
+        })
+        // A lone "." target means the service is explicitly not offered (RFC 2782)
+        .filter(|target| !target.host.is_empty())
+        .collect();
+    order(targets, || rand_core::OsRng.next_u32())
+        .into_iter()
+        .map(|target| (target.host, target.port))
+        .collect()
+}
+
+/// Orders SRV targets as RFC 2782 asks: lowest priority first, and within a priority a
+/// weighted random selection so heavier servers are picked first more often
+fn order(mut targets: Vec<SrvTarget>, mut random: impl FnMut() -> u32) -> Vec<SrvTarget> {
+    targets.sort_by_key(|target| target.priority);
+    let mut ordered = Vec::with_capacity(targets.len());
+    while !targets.is_empty() {
+        let priority = targets[0].priority;
+        let end = targets.iter().position(|t| t.priority != priority).unwrap_or(targets.len());
+        let mut group: Vec<SrvTarget> = targets.drain(..end).collect();
+        // Zero-weight targets go first so they are only chosen when nothing else is left
+        group.sort_by_key(|target| target.weight != 0);
+
+        while !group.is_empty() {
+            let total: u32 = group.iter().map(|t| t.weight as u32).sum();
+            let pick = if total == 0 { 0 } else { random() % (total + 1) };
+            let mut running = 0;
+            let index = group.iter()
+                .position(|t| {
+                    running += t.weight as u32;
+                    running >= pick
+                })
+                .unwrap_or(group.len() - 1);
+            ordered.push(group.remove(index));
+        }
+    }
+    ordered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn target(priority: u16, weight: u16, host: &str) -> SrvTarget {
+        SrvTarget { priority, weight, host: host.to_string(), port: 9000 }
+    }
+
+    #[test]
+    fn test_srv_order_priority_then_weight() {
+        let targets = vec![target(20, 0, "backup"), target(10, 1, "light"), target(10, 99, "heavy")];
+        let hosts = |ordered: Vec<SrvTarget>| ordered.into_iter().map(|t| t.host).collect::<Vec<_>>();
+
+        // A pick past the light server's share lands on the heavy one
+        assert_eq!(hosts(order(targets.clone(), || 50)), ["heavy", "light", "backup"]);
+        assert_eq!(hosts(order(targets, || 1)), ["light", "heavy", "backup"]);
+        assert_eq!(service_for(true, "example.com"), Some("_orbits._tcp"));
+        assert_eq!(service_for(false, "192.0.2.7"), None);
+    }
+}
@@ -0,0 +1,98 @@
+use std::error::Error;
+use std::process::ExitCode;
+use crate::auth::AuthError;
+use crate::repo::RepoError;
+use crate::vnp::VnpError;
+use crate::verify::IncompleteGraph;
+use crate::vos::VosError;
+use tracing::error;
+
+/// Any failure without a more specific code
+pub const GENERAL: u8 = 1;
+/// No repository, or a revision that does not name a commit
+pub const REPOSITORY: u8 = 3;
+/// A missing or unreadable object in the local object store
+pub const OBJECT_STORE: u8 = 4;
+/// The server refused the credentials
+pub const AUTHENTICATION: u8 = 5;
+/// The server failed or broke the VNP protocol
+pub const PROTOCOL: u8 = 6;
+/// The operation was interrupted
+pub const CANCELLED: u8 = 130;
+
+/// Prints a failed command's error and returns the exit code matching its cause
+pub fn failure(action: &str, error: impl Into<Box<dyn Error>>) -> ExitCode {
+    let error = error.into();
+    error!("❌ {}: {}", action, error);
+    ExitCode::from(code(error.as_ref()))
+}
+
+/// The exit code for an error, found by walking its chain of sources (including errors
+/// wrapped in `io::Error`) until one has a known type
+pub fn code(error: &(dyn Error + 'static)) -> u8 {
+    let mut next = Some(error);
+    while let Some(error) = next {
+        if let Some(code) = known_code(error) {
+            return code;
+        }
+        next = match error.downcast_ref::<std::io::Error>().and_then(|e| e.get_ref()) {
+            Some(inner) => Some(inner),
+            None => error.source(),
+        };
+    }
+    GENERAL
+}
+
+fn known_code(error: &(dyn Error + 'static)) -> Option<u8> {
+    if let Some(error) = error.downcast_ref::<RepoError>() {
+        return match error {
+            RepoError::Core(error) => core_code(error),
+            _ => Some(REPOSITORY),
+        };
+    }
+    if let Some(error) = error.downcast_ref::<orbit_core::Error>() {
+        return core_code(error);
+    }
+    if error.is::<VosError>() || error.is::<IncompleteGraph>() {
+        Some(OBJECT_STORE)
+    } else if error.is::<AuthError>() {
+        Some(AUTHENTICATION)
+    } else if error.is::<VnpError>() {
+        Some(PROTOCOL)
+    } else if error.is::<crate::transport::Cancelled>() {
+        Some(CANCELLED)
+    } else {
+        None
+    }
+}
+
+fn core_code(error: &orbit_core::Error) -> Option<u8> {
+    match error {
+        orbit_core::Error::NotARepository(_)
+        | orbit_core::Error::AlreadyInitialized(_)
+        | orbit_core::Error::BareRepository(_)
+        | orbit_core::Error::NoCommits
+        | orbit_core::Error::MergeInProgress
+        | orbit_core::Error::UnresolvedConflicts(_)
+        | orbit_core::Error::NoMatchingFiles(_) => Some(REPOSITORY),
+        orbit_core::Error::MissingObject(_) | orbit_core::Error::Corrupt { .. } => Some(OBJECT_STORE),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exit_codes_follow_the_error_chain() {
+        let boxed: Box<dyn Error> = RepoError::UnknownRevision("nope".to_string()).into();
+        assert_eq!(code(boxed.as_ref()), REPOSITORY);
+        let wrapped = std::io::Error::other(VnpError::Unexpected { phase: "negotiation" });
+        assert_eq!(code(&wrapped), PROTOCOL);
+        let missing = RepoError::Core(orbit_core::Error::MissingObject("abc".to_string()));
+        assert_eq!(code(&missing), OBJECT_STORE);
+        assert_eq!(code(&AuthError::MissingToken), AUTHENTICATION);
+        assert_eq!(code(&std::io::Error::other("disk full")), GENERAL);
+    }
+}
@@ -0,0 +1,132 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fs;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use tracing::info;
+use crate::merge::{self, FileMap};
+use crate::objects::{Commit, File, ObjectId};
+use crate::{ancestry, bridge, refs, vos};
+
+/// Writes branches and tags (all of them unless `names` picks some) as a `git
+/// fast-import` stream on stdout. Commits listed in `import_marks` count as exported
+/// already, so a stream can continue an earlier one; `export_marks` records the mark of
+/// every exported commit for the next run.
+pub fn fast_export(names: &[String], import_marks: Option<&Path>, export_marks: Option<&Path>) -> Result<(), Box<dyn std::error::Error>> {
+    let mut exported_refs = Vec::new();
+    if names.is_empty() {
+        exported_refs.extend(refs::list_branches().into_iter().map(|(name, id)| (refs::branch_ref(&name), id)));
+        exported_refs.extend(refs::list_tags().into_iter().map(|(name, id)| (refs::tag_ref(&name), id)));
+    }
+    for name in names {
+        let full = [name.to_string(), refs::branch_ref(name), refs::tag_ref(name)]
+            .into_iter()
+            .filter(|full| full.starts_with("refs/"))
+            .find_map(|full| refs::read_ref(&full).map(|id| (full, id)));
+        exported_refs.push(full.ok_or_else(|| format!("no branch or tag '{}'", name))?);
+    }
+
+    let mut marks: HashMap<ObjectId, u64> = match import_marks {
+        Some(path) => read_marks(path)?.into_iter().map(|(mark, id)| (id, mark)).collect(),
+        None => HashMap::new(),
+    };
+    let mut next_mark = marks.values().max().copied().unwrap_or(0) + 1;
+    let mut blob_marks: HashMap<ObjectId, u64> = HashMap::new();
+    let mut out = BufWriter::new(std::io::stdout().lock());
+    let mut commits = 0;
+
+    for (name, tip) in &exported_refs {
+        let exported: HashSet<ObjectId> = marks.keys().cloned().collect();
+        for (id, commit) in ancestry::parents_first(tip, &exported)? {
+            let old_files = match commit.parents.first() {
+                Some(parent) => merge::commit_files(parent)?,
+                None => FileMap::new(),
+            };
+            let new_files = merge::commit_files(&id)?;
+            let changed: Vec<(&String, &(u32, ObjectId))> = new_files.iter().filter(|(path, file)| old_files.get(*path) != Some(file)).collect();
+            for (_, (_, file_id)) in &changed {
+                if !blob_marks.contains_key(file_id) {
+                    write_blob(&mut out, file_id, next_mark)?;
+                    blob_marks.insert(file_id.clone(), next_mark);
+                    next_mark += 1;
+                }
+            }
+
+            write!(out, "commit {}\nmark :{}\n", name, next_mark)?;
+            marks.insert(id.clone(), next_mark);
+            next_mark += 1;
+            write_commit_header(&mut out, &commit)?;
+            for (i, parent) in commit.parents.iter().enumerate() {
+                let mark = marks.get(parent).ok_or_else(|| format!("parent {} of {} was not exported", parent, id))?;
+                writeln!(out, "{} :{}", if i == 0 { "from" } else { "merge" }, mark)?;
+            }
+            let removed: BTreeSet<&String> = old_files.keys().filter(|path| !new_files.contains_key(*path)).collect();
+            for path in removed {
+                writeln!(out, "D {}", quote_path(path))?;
+            }
+            for (path, (mode, file_id)) in changed {
+                writeln!(out, "M {:o} :{} {}", mode, blob_marks[file_id], quote_path(path))?;
+            }
+            out.write_all(b"\n")?;
+            commits += 1;
+        }
+        let mark = marks.get(tip).ok_or_else(|| format!("{} was not exported", tip))?;
+        write!(out, "reset {}\nfrom :{}\n\n", name, mark)?;
+    }
+    out.flush()?;
+
+    if let Some(path) = export_marks {
+        let mut by_mark: Vec<(&u64, &ObjectId)> = marks.iter().map(|(id, mark)| (mark, id)).collect();
+        by_mark.sort();
+        fs::write(path, by_mark.iter().map(|(mark, id)| format!(":{} {}\n", mark, id)).collect::<String>())?;
+    }
+    info!("✅ Exported {} commits and {} refs", commits, exported_refs.len());
+    Ok(())
+}
+
+/// `blob` command with a file's content, streamed chunk by chunk
+fn write_blob(out: &mut impl Write, file_id: &str, mark: u64) -> Result<(), Box<dyn std::error::Error>> {
+    let file: File = vos::load(file_id, "file")?;
+    write!(out, "blob\nmark :{}\ndata {}\n", mark, file.size)?;
+    for chunk in file.chunk_ids() {
+        out.write_all(&vos::load_object(chunk)?)?;
+    }
+    out.write_all(b"\n")?;
+    Ok(())
+}
+
+fn write_commit_header(out: &mut impl Write, commit: &Commit) -> std::io::Result<()> {
+    let (name, email) = bridge::split_author(&commit.author);
+    let person = format!("{} <{}> {} +0000", name, email, commit.timestamp);
+    write!(out, "author {}\ncommitter {}\ndata {}\n{}\n", person, person, commit.message.len(), commit.message)
+}
+
+/// Marks file lines `:<mark> <commit ID>`
+pub fn read_marks(path: &Path) -> Result<Vec<(u64, ObjectId)>, Box<dyn std::error::Error>> {
+    let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    text.lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let parsed = line.strip_prefix(':').and_then(|rest| rest.split_once(' ')).and_then(|(mark, id)| Some((mark.parse().ok()?, id.trim().to_string())));
+            parsed.ok_or_else(|| format!("{}: invalid mark line '{}'", path.display(), line).into())
+        })
+        .collect()
+}
+
+/// A path as fast-import expects it: quoted C-style if it starts with `"` or holds a
+/// newline, backslash or quote
+pub fn quote_path(path: &str) -> String {
+    if !path.starts_with('"') && !path.contains(['\n', '\\']) {
+        return path.to_string();
+    }
+    let mut quoted = String::from("\"");
+    for c in path.chars() {
+        match c {
+            '\n' => quoted.push_str("\\n"),
+            '\\' => quoted.push_str("\\\\"),
+            '"' => quoted.push_str("\\\""),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
@@ -0,0 +1,481 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use crate::fast_export::read_marks;
+use crate::merge::{self, FileMap};
+use crate::objects::{Commit, ObjectId};
+use crate::vos::{self, ChunkStats};
+use crate::{ancestry, refs};
+use tracing::{info, warn};
+
+/// A ref's state during an import: its tip and the files of that tip
+struct Branch {
+    tip: Option<ObjectId>,
+    files: FileMap,
+}
+
+/// Reads a `git fast-import` stream and turns it into Orbit commits
+struct Importer<R> {
+    input: R,
+    /// A line read ahead and given back
+    pending: Option<String>,
+    /// Blob marks name File objects; commit marks name commits
+    marks: HashMap<u64, ObjectId>,
+    branches: BTreeMap<String, Branch>,
+    stats: ChunkStats,
+    commits: usize,
+    tags: BTreeMap<String, ObjectId>,
+}
+
+/// What an imported stream left behind
+pub struct Imported {
+    /// Every mark of the stream: File objects of blobs, and commits
+    pub marks: HashMap<u64, ObjectId>,
+    /// Refs left alone because the imported tip does not descend from them
+    pub refused: usize,
+}
+
+/// Imports a fast-import stream from stdin (as written by `git fast-export` or `orb
+/// fast-export`). Refs are only moved forward unless `force` is set.
+pub fn fast_import(force: bool, import_marks: Option<&Path>, export_marks: Option<&Path>) -> Result<(), Box<dyn std::error::Error>> {
+    if !Path::new(".orb").is_dir() {
+        return Err("not an Orbit repository (run `orb init` first)".into());
+    }
+    let marks = match import_marks {
+        Some(path) => read_marks(path)?.into_iter().collect(),
+        None => HashMap::new(),
+    };
+    let head = refs::head_commit();
+    let imported = import_stream(std::io::stdin().lock(), marks, force)?;
+    if let Some(path) = export_marks {
+        let mut marks: Vec<(&u64, &ObjectId)> = imported.marks.iter().collect();
+        marks.sort();
+        fs::write(path, marks.iter().map(|(mark, id)| format!(":{} {}\n", mark, id)).collect::<String>())?;
+    }
+    if refs::head_commit() != head {
+        info!("💡 Run `orb checkout` to update the working directory");
+    }
+    if imported.refused > 0 {
+        return Err(format!("{} refs were not updated", imported.refused).into());
+    }
+    Ok(())
+}
+
+/// Imports the stream `generate` writes on another thread, as converters from other
+/// version control systems produce it. An error of the generator wins over the import
+/// error it causes.
+pub fn import_generated<F>(generate: F) -> Result<Imported, Box<dyn std::error::Error>>
+where
+    F: FnOnce(&mut dyn Write) -> std::io::Result<()> + Send + 'static,
+{
+    let (reader, writer) = std::io::pipe()?;
+    let producer = std::thread::spawn(move || {
+        let mut out = BufWriter::new(writer);
+        generate(&mut out).and_then(|()| out.flush())
+    });
+    let imported = import_stream(BufReader::new(reader), HashMap::new(), false);
+    let produced = producer.join().map_err(|_| "the history converter panicked")?;
+    match (imported, produced) {
+        (Ok(imported), Ok(())) => Ok(imported),
+        // The import stopped reading, so the generator could not write any more
+        (Err(e), Err(broken)) if broken.kind() == std::io::ErrorKind::BrokenPipe => Err(e),
+        (_, Err(e)) => Err(e.into()),
+        (Err(e), Ok(())) => Err(e),
+    }
+}
+
+/// Creates the commits of a stream and updates its refs, never moving a ref to a commit
+/// that does not descend from it unless `force` is set
+pub fn import_stream(input: impl BufRead, marks: HashMap<u64, ObjectId>, force: bool) -> Result<Imported, Box<dyn std::error::Error>> {
+    let mut importer = Importer {
+        input,
+        pending: None,
+        marks,
+        branches: BTreeMap::new(),
+        stats: ChunkStats::default(),
+        commits: 0,
+        tags: BTreeMap::new(),
+    };
+    importer.run()?;
+
+    let (mut updated, mut refused) = (0, 0);
+    let updates = importer.branches.iter().filter_map(|(name, branch)| Some((name, branch.tip.as_ref()?))).chain(importer.tags.iter());
+    for (name, tip) in updates {
+        let current = refs::read_ref(name);
+        if current.as_ref().is_some_and(|current| !force && !ancestry::is_ancestor(current, tip)) {
+            warn!("⚠️  Not updating {}: the imported tip does not descend from it (use --force)", name);
+            refused += 1;
+            continue;
+        }
+        refs::write_ref(name, tip)?;
+        updated += 1;
+    }
+    crate::fetch::report_deduplication(&importer.stats);
+    info!("✅ Imported {} commits; {} refs updated", importer.commits, updated);
+    Ok(Imported { marks: importer.marks, refused })
+}
+
+impl<R: BufRead> Importer<R> {
+    fn run(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        while let Some(line) = self.next_line()? {
+            let (command, argument) = line.split_once(' ').unwrap_or((line.as_str(), ""));
+            match command {
+                "" => {}
+                "blob" => self.blob()?,
+                "commit" => self.commit(argument)?,
+                "reset" => self.reset(argument)?,
+                "tag" => self.tag(argument)?,
+                "progress" => println!("{}", argument),
+                "checkpoint" | "option" => {}
+                "feature" => match argument.split('=').next().unwrap_or("") {
+                    "date-format" if argument != "date-format=raw" => return Err(format!("unsupported feature '{}'", argument).into()),
+                    "date-format" | "done" | "force" | "relative-marks" | "no-relative-marks" => {}
+                    _ => return Err(format!("unsupported feature '{}'", argument).into()),
+                },
+                "done" => break,
+                _ if command.starts_with('#') => {}
+                _ => return Err(format!("unsupported fast-import command '{}'", line).into()),
+            }
+        }
+        Ok(())
+    }
+
+    fn blob(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let mark = self.mark()?;
+        self.optional("original-oid")?;
+        let file_id = self.data_into_file()?;
+        if let Some(mark) = mark {
+            self.marks.insert(mark, file_id);
+        }
+        Ok(())
+    }
+
+    fn commit(&mut self, name: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let name = full_ref(name);
+        let mark = self.mark()?;
+        self.optional("original-oid")?;
+        let author = self.optional("author")?;
+        let committer = self.optional("committer")?.ok_or_else(|| format!("commit to {} has no committer", name))?;
+        self.optional("encoding")?;
+        let message = String::from_utf8_lossy(&self.data()?).into_owned();
+        let (author, timestamp) = parse_person(author.as_deref().unwrap_or(&committer))?;
+
+        let mut parents = Vec::new();
+        let mut files = None;
+        if let Some(from) = self.optional("from")? {
+            let parent = self.commit_ish(&from)?;
+            files = Some(match self.branches.get(&name) {
+                Some(branch) if branch.tip == parent => branch.files.clone(),
+                _ => parent.as_deref().map(merge::commit_files).transpose()?.unwrap_or_default(),
+            });
+            parents.extend(parent);
+        }
+        while let Some(merge) = self.optional("merge")? {
+            parents.extend(self.commit_ish(&merge)?);
+        }
+        let mut files = match files {
+            Some(files) => files,
+            None => match self.branches.remove(&name) {
+                Some(branch) => {
+                    parents.splice(0..0, branch.tip);
+                    branch.files
+                }
+                None => FileMap::new(),
+            },
+        };
+
+        while let Some(line) = self.next_line()? {
+            if line.is_empty() {
+                continue;
+            }
+            let (command, argument) = line.split_once(' ').unwrap_or((line.as_str(), ""));
+            match command {
+                "M" => {
+                    let (mode, rest) = argument.split_once(' ').ok_or_else(|| format!("invalid line '{}'", line))?;
+                    let (data_ref, path) = rest.split_once(' ').ok_or_else(|| format!("invalid line '{}'", line))?;
+                    let mode = match mode {
+                        "644" | "100644" => 0o100644,
+                        "755" | "100755" => 0o100755,
+                        "120000" => 0o120000,
+                        "160000" => {
+                            warn!("⚠️  Skipping submodule {}", unquote(path));
+                            continue;
+                        }
+                        _ => return Err(format!("unsupported file mode in '{}'", line).into()),
+                    };
+                    let file_id = match data_ref {
+                        "inline" => self.data_into_file()?,
+                        _ => self.file_ref(data_ref)?,
+                    };
+                    files.insert(unquote(path), (mode, file_id));
+                }
+                "D" => {
+                    let path = unquote(argument);
+                    let prefix = format!("{}/", path);
+                    files.retain(|file, _| *file != path && !file.starts_with(&prefix));
+                }
+                "C" | "R" => {
+                    let (source, target) = split_two_paths(argument).ok_or_else(|| format!("invalid line '{}'", line))?;
+                    let prefix = format!("{}/", source);
+                    let copied: Vec<(String, (u32, ObjectId))> = files
+                        .iter()
+                        .filter(|(file, _)| **file == source || file.starts_with(&prefix))
+                        .map(|(file, entry)| (format!("{}{}", target, &file[source.len()..]), entry.clone()))
+                        .collect();
+                    if command == "R" {
+                        files.retain(|file, _| *file != source && !file.starts_with(&prefix));
+                    }
+                    files.extend(copied);
+                }
+                "deleteall" => files.clear(),
+                // Notes have no Orbit equivalent
+                "N" => {}
+                _ => {
+                    self.pending = Some(line);
+                    break;
+                }
+            }
+        }
+
+        let commit = Commit {
+            tree: merge::build_tree(&files)?,
+            parents,
+            author,
+            timestamp,
+            message,
+            signature: None,
+        };
+        let commit_id = vos::save_object(&serde_json::to_vec(&commit)?);
+        if let Some(mark) = mark {
+            self.marks.insert(mark, commit_id.clone());
+        }
+        self.branches.insert(name, Branch { tip: Some(commit_id), files });
+        self.commits += 1;
+        Ok(())
+    }
+
+    /// `reset <ref>` with an optional `from`: the next commit to the ref starts there,
+    /// or (without `from`) has no parent
+    fn reset(&mut self, name: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let name = full_ref(name);
+        let tip = match self.optional("from")? {
+            Some(from) => self.commit_ish(&from)?,
+            None => None,
+        };
+        let files = tip.as_deref().map(merge::commit_files).transpose()?.unwrap_or_default();
+        self.branches.insert(name, Branch { tip, files });
+        Ok(())
+    }
+
+    /// Annotated tags become plain tags; their message and tagger are not kept
+    fn tag(&mut self, name: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.mark()?;
+        let from = self.optional("from")?.ok_or_else(|| format!("tag {} has no from", name))?;
+        self.optional("original-oid")?;
+        self.optional("tagger")?;
+        self.data()?;
+        let tip = self.commit_ish(&from)?.ok_or_else(|| format!("tag {} points at nothing", name))?;
+        self.tags.insert(refs::tag_ref(name), tip);
+        Ok(())
+    }
+
+    /// A commit named by `:<mark>`, a ref of this import, or a revision; None for the
+    /// null ID, which starts a history without a parent
+    fn commit_ish(&self, name: &str) -> Result<Option<ObjectId>, Box<dyn std::error::Error>> {
+        if let Some(mark) = name.strip_prefix(':') {
+            let mark: u64 = mark.parse().map_err(|_| format!("invalid mark '{}'", name))?;
+            return Ok(Some(self.marks.get(&mark).cloned().ok_or_else(|| format!("unknown mark {}", name))?));
+        }
+        if name.bytes().all(|c| c == b'0') {
+            return Ok(None);
+        }
+        let name = name.strip_suffix("^0").unwrap_or(name);
+        if let Some(branch) = self.branches.get(&full_ref(name)) {
+            return Ok(branch.tip.clone());
+        }
+        Ok(Some(refs::resolve(name)?))
+    }
+
+    /// The File object of a blob mark or of an Orbit file ID
+    fn file_ref(&self, data_ref: &str) -> Result<ObjectId, Box<dyn std::error::Error>> {
+        if let Some(mark) = data_ref.strip_prefix(':') {
+            let mark: u64 = mark.parse().map_err(|_| format!("invalid mark '{}'", data_ref))?;
+            return Ok(self.marks.get(&mark).cloned().ok_or_else(|| format!("unknown mark {}", data_ref))?);
+        }
+        if vos::store().contains(data_ref) {
+            return Ok(data_ref.to_string());
+        }
+        Err(format!("unknown blob '{}' (the stream must send blobs with marks)", data_ref).into())
+    }
+
+    fn mark(&mut self) -> Result<Option<u64>, Box<dyn std::error::Error>> {
+        match self.optional("mark")? {
+            Some(mark) => Ok(Some(mark.strip_prefix(':').and_then(|mark| mark.parse().ok()).ok_or_else(|| format!("invalid mark '{}'", mark))?)),
+            None => Ok(None),
+        }
+    }
+
+    /// The argument of the next line if it is `<keyword> <argument>`; otherwise the
+    /// line is kept for the next read
+    fn optional(&mut self, keyword: &str) -> std::io::Result<Option<String>> {
+        let Some(line) = self.next_line()? else { return Ok(None) };
+        match line.strip_prefix(keyword).and_then(|rest| rest.strip_prefix(' ')) {
+            Some(argument) => Ok(Some(argument.to_string())),
+            None => {
+                self.pending = Some(line);
+                Ok(None)
+            }
+        }
+    }
+
+    fn next_line(&mut self) -> std::io::Result<Option<String>> {
+        if let Some(line) = self.pending.take() {
+            return Ok(Some(line));
+        }
+        let mut line = Vec::new();
+        if self.input.read_until(b'\n', &mut line)? == 0 {
+            return Ok(None);
+        }
+        if line.ends_with(b"\n") {
+            line.pop();
+        }
+        Ok(Some(String::from_utf8_lossy(&line).into_owned()))
+    }
+
+    /// Reads the header of a `data` command, returning the exact byte count, or the
+    /// delimiter of a `data <<DELIM` block
+    fn data_header(&mut self) -> Result<Result<u64, String>, Box<dyn std::error::Error>> {
+        let header = self.next_line()?.ok_or("the stream ended before a data command")?;
+        let size = header.strip_prefix("data ").ok_or_else(|| format!("expected a data command, not '{}'", header))?;
+        Ok(match size.strip_prefix("<<") {
+            Some(delimiter) => Err(delimiter.to_string()),
+            None => Ok(size.parse().map_err(|_| format!("invalid data length '{}'", size))?),
+        })
+    }
+
+    fn data(&mut self) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        match self.data_header()? {
+            Ok(size) => {
+                let mut data = Vec::new();
+                (&mut self.input).take(size).read_to_end(&mut data)?;
+                if data.len() as u64 != size {
+                    return Err("the stream ended inside a data block".into());
+                }
+                self.skip_newline()?;
+                Ok(data)
+            }
+            Err(delimiter) => {
+                let mut data = Vec::new();
+                loop {
+                    let line = self.next_line()?.ok_or("the stream ended inside a data block")?;
+                    if line == delimiter {
+                        return Ok(data);
+                    }
+                    data.extend_from_slice(line.as_bytes());
+                    data.push(b'\n');
+                }
+            }
+        }
+    }
+
+    /// Consumes the optional newline after exact-length data
+    fn skip_newline(&mut self) -> std::io::Result<()> {
+        if self.input.fill_buf()?.first() == Some(&b'\n') {
+            self.input.consume(1);
+        }
+        Ok(())
+    }
+
+    /// Saves the content of a `data` command as a File object, streaming exact-length
+    /// data straight through the chunker
+    fn data_into_file(&mut self) -> Result<ObjectId, Box<dyn std::error::Error>> {
+        match self.data_header()? {
+            Ok(size) => {
+                let (file_id, read) = vos::save_stream((&mut self.input).take(size), &mut self.stats)?;
+                if read as u64 != size {
+                    return Err("the stream ended inside a data block".into());
+                }
+                self.skip_newline()?;
+                Ok(file_id)
+            }
+            Err(delimiter) => {
+                self.pending = Some(format!("data <<{}", delimiter));
+                let data = self.data()?;
+                Ok(vos::save_stream(&data[..], &mut self.stats)?.0)
+            }
+        }
+    }
+}
+
+/// Branch names without `refs/` are branches, as in `git fast-import`
+fn full_ref(name: &str) -> String {
+    if name.starts_with("refs/") { name.to_string() } else { refs::branch_ref(name) }
+}
+
+/// `Name <email> <seconds> <zone>` as an Orbit author and a timestamp
+fn parse_person(person: &str) -> Result<(String, i64), String> {
+    let (identity, when) = person.rsplit_once("> ").ok_or_else(|| format!("invalid identity '{}'", person))?;
+    let seconds = when.split_whitespace().next().and_then(|seconds| seconds.parse().ok()).ok_or_else(|| format!("invalid date in '{}'", person))?;
+    let identity = format!("{}>", identity).replace(" <>", "");
+    Ok((identity, seconds))
+}
+
+/// A path that may be C-style quoted
+fn unquote(path: &str) -> String {
+    match path.strip_prefix('"').and_then(|rest| rest.strip_suffix('"')) {
+        Some(quoted) => unescape(quoted).0,
+        None => path.to_string(),
+    }
+}
+
+/// The source and target of a copy or rename; a source holding spaces is quoted
+fn split_two_paths(argument: &str) -> Option<(String, String)> {
+    match argument.strip_prefix('"') {
+        Some(rest) => {
+            let (source, used) = unescape(rest);
+            Some((source, unquote(rest.get(used + 1..)?.strip_prefix(' ')?)))
+        }
+        None => argument.split_once(' ').map(|(source, target)| (source.to_string(), unquote(target))),
+    }
+}
+
+/// Decodes a quoted string's escapes up to its closing quote; returns the text and the
+/// number of bytes read before that quote
+fn unescape(quoted: &str) -> (String, usize) {
+    let bytes = quoted.as_bytes();
+    let mut text = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() && bytes[i] != b'"' {
+        if bytes[i] == b'\\' && i + 1 < bytes.len() {
+            i += 1;
+            match bytes[i] {
+                b'n' => text.push(b'\n'),
+                b't' => text.push(b'\t'),
+                b'0'..=b'7' if i + 2 < bytes.len() => {
+                    text.push(u8::from_str_radix(&quoted[i..i + 3], 8).unwrap_or(b'?'));
+                    i += 2;
+                }
+                other => text.push(other),
+            }
+        } else {
+            text.push(bytes[i]);
+        }
+        i += 1;
+    }
+    (String::from_utf8_lossy(&text).into_owned(), i)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stream_fields() {
+        assert_eq!(parse_person("Alice Doe <alice@example.com> 1700000000 +0200"), Ok(("Alice Doe <alice@example.com>".to_string(), 1700000000)));
+        assert_eq!(unquote("\"caf\\303\\251 \\\"menu\\\".txt\""), "café \"menu\".txt");
+        assert_eq!(split_two_paths("\"a b\" c d"), Some(("a b".to_string(), "c d".to_string())));
+        assert_eq!(split_two_paths("old new"), Some(("old".to_string(), "new".to_string())));
+        assert_eq!(full_ref("main"), "refs/heads/main");
+    }
+}
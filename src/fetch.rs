@@ -1,104 +1,546 @@
-use git2::{Repository, Oid, ObjectType, TreeWalkMode, TreeWalkResult};
+use git2::{Repository, Oid, ObjectType, Sort};
 use std::fs;
+use std::io::Write;
 use std::path::Path;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use crate::git_auth::GitAuth;
 use crate::vos;
-use crate::objects::{ObjectId, Commit, Directory, File, DirectoryEntry};
-use crate::repo;
+use crate::objects::{ObjectId, Commit, Directory, DirectoryEntry};
+use crate::index::VosIndex;
+use crate::progress::Progress;
+use crate::{ancestry, merge, refs, repo};
+use tracing::{info, warn};
+
+/// Where submodules' recorded commits are listed when they are not converted
+const SUBMODULES_FILE: &str = ".orb/submodules.json";
+/// Git commits converted so far and their Orbit IDs, a `<git> <orbit>` line each. Lines
+/// are appended as commits are converted, so an interrupted import can resume.
+pub const COMMIT_MAP_FILE: &str = ".orb/git-commits";
+/// Where `--keep-remote` keeps the Git repository (bare) for `orb fetch --update`
+pub const GIT_DIR: &str = ".orb/git";
+/// Remote name of the upstream branch's tracking ref, `refs/remotes/git/<branch>`
+pub const TRACKING_REMOTE: &str = "git";
+/// Branch an import creates and `orb fetch --update` advances
+const IMPORT_BRANCH: &str = "main";
+/// Converted trees and blobs remembered at most, each; past this the caches start over
+/// (converting again is only slower, since objects are content-addressed)
+const CACHE_ENTRIES: usize = 100_000;
+
+/// How `orb fetch` imports a Git repository
+#[derive(Debug, Default)]
+pub struct ImportOptions {
+    /// Fetch and convert only this many of the most recent commits
+    pub depth: Option<u32>,
+    /// Convert submodules' contents into the history instead of listing them
+    pub recurse_submodules: bool,
+    /// Keep the Git repository and its remote for later `orb fetch --update`
+    pub keep_remote: bool,
+    pub auth: GitAuth,
+}
 
 /// Fetches a Git repository and converts it to Orbit VOS format
-pub fn fetch_git_repository(url: &str, target_dir: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
-    println!("🌐 Fetching Git repository: {}", url);
+pub fn fetch_git_repository(url: &str, target_dir: Option<&str>, options: &ImportOptions) -> Result<(), Box<dyn std::error::Error>> {
+    info!("🌐 Fetching Git repository: {}", url);
     
     // Determine target directory
     let repo_name = extract_repo_name(url)?;
     let target = target_dir.unwrap_or(&repo_name);
     
-    // Check if target directory already exists
-    if Path::new(target).exists() {
+    let progress = Progress::new();
+    let git_repo = if is_interrupted_import(Path::new(target)) {
+        // The clone finished last time; conversion picks up after the last converted commit
+        info!("🔁 Resuming the interrupted import in {}", target);
+        Repository::open(target)?
+    } else if Path::new(target).exists() {
         return Err(format!("Target directory '{}' already exists", target).into());
-    }
-    
-    println!("📁 Target directory: {}", target);
-    
-    // Clone the Git repository directly to target location
-    println!("⬇️  Cloning Git repository...");
-    
-    let git_repo = Repository::clone(url, target)?;
-    println!("✅ Git repository cloned successfully");
+    } else {
+        info!("📁 Target directory: {}", target);
+        
+        // Clone the Git repository directly to target location
+        match options.depth {
+            Some(depth) => info!("⬇️  Cloning the last {} commits of the Git repository...", depth),
+            None => info!("⬇️  Cloning Git repository..."),
+        }
+        
+        let git_repo = clone_git_repository(url, Path::new(target), false, options, &progress)?;
+        fs::create_dir_all(Path::new(target).join(".orb"))?;
+        fs::File::create(Path::new(target).join(COMMIT_MAP_FILE))?;
+        info!("✅ Git repository cloned successfully");
+        git_repo
+    };
     
     // Convert Git history to Orbit while in the cloned directory
     let original_dir = std::env::current_dir()?;
     std::env::set_current_dir(target)?;
     
-    println!("� Converting Git history to Orbit VOS format...");
-    convert_git_history(&git_repo)?;
+    let submodules = if options.recurse_submodules {
+        clone_submodules(&git_repo, url, options, &progress)?
+    } else {
+        Vec::new()
+    };
+    
+    info!("� Converting Git history to Orbit VOS format...");
+    let head = git_repo.head()?.peel_to_commit()?.id();
+    let tip = convert_git_history(&git_repo, head, &submodules, &progress)
+        .map_err(|e| format!("{} (run the same `orb fetch` again to resume the import)", e))?;
+    update_head_ref(&tip)?;
+    if !options.recurse_submodules {
+        record_submodules(&git_repo)?;
+    }
     
     // Clean up Git metadata and initialize Orbit repository
-    println!("🧹 Replacing Git with Orbit VOS...");
+    info!("🧹 Replacing Git with Orbit VOS...");
+    drop(submodules);
+    if options.keep_remote {
+        keep_git_remote(git_repo, options.recurse_submodules)?;
+    } else {
+        drop(git_repo);
+    }
     cleanup_git_and_init_orbit()?;
     
     // Return to original directory
     std::env::set_current_dir(original_dir)?;
     
-    println!("");
-    println!("🎉 Git repository successfully converted to Orbit!");
-    println!("📈 Your repository now has:");
-    println!("   • Post-quantum SHA3-256 security");
-    println!("   • Content-defined chunking with deduplication");
-    println!("   • 40% faster status checks with VOS Index");
-    println!("");
-    println!("💡 Try these commands:");
-    println!("   cd {}", target);
-    println!("   orb status");
-    println!("   orb history");
+    info!("");
+    info!("🎉 Git repository successfully converted to Orbit!");
+    info!("📈 Your repository now has:");
+    info!("   • Post-quantum SHA3-256 security");
+    info!("   • Content-defined chunking with deduplication");
+    info!("   • 40% faster status checks with VOS Index");
+    info!("");
+    info!("💡 Try these commands:");
+    info!("   cd {}", target);
+    info!("   orb status");
+    info!("   orb history");
     
     Ok(())
 }
 
-/// Converts Git commit history to Orbit VOS format
-fn convert_git_history(git_repo: &Repository) -> Result<(), Box<dyn std::error::Error>> {
-    let mut commit_count = 0;
-    let mut converted_commits: HashMap<Oid, ObjectId> = HashMap::new();
+/// Creates `target` as a new Orbit repository, runs `convert` inside it and checks out
+/// the converted HEAD. The directory is removed again if the conversion fails.
+pub fn convert_into_new_repository(target: &str, convert: impl FnOnce() -> Result<(), Box<dyn std::error::Error>>) -> Result<(), Box<dyn std::error::Error>> {
+    if Path::new(target).exists() {
+        return Err(format!("Target directory '{}' already exists", target).into());
+    }
+    info!("📁 Target directory: {}", target);
+    fs::create_dir_all(target)?;
+    let original_dir = std::env::current_dir()?;
+    std::env::set_current_dir(target)?;
+    let result = repo::init().map_err(Into::into).and_then(|()| convert()).and_then(|()| match refs::head_commit() {
+        Some(_) => crate::checkout_commit(None),
+        None => {
+            warn!("⚠️  The repository has no history on {}", refs::current_branch());
+            Ok(())
+        }
+    });
+    std::env::set_current_dir(original_dir)?;
+    if result.is_err() {
+        let _ = fs::remove_dir_all(target);
+    }
+    result
+}
+
+/// Moves the Git repository to `.orb/git`, bare, so `orb fetch --update` can fetch
+/// from its remote later
+fn keep_git_remote(git_repo: Repository, recurse_submodules: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let mut config = git_repo.config()?;
+    config.set_bool("core.bare", true)?;
+    config.set_bool("orb.recurseSubmodules", recurse_submodules)?;
+    drop(config);
+    let has_remote = git_repo.find_remote("origin").is_ok();
+    drop(git_repo);
+    fs::rename(".git", GIT_DIR)?;
+    if has_remote {
+        info!("  🔗 Kept the Git remote in {}; run `orb fetch --update` to convert new upstream commits", GIT_DIR);
+    } else {
+        info!("  📦 Kept the Git repository in {}", GIT_DIR);
+    }
+    Ok(())
+}
+
+/// Converts the full history of an existing local Git checkout into Orbit format in
+/// place, without network access. The Git repository is kept (bare) in `.orb/git`, so
+/// nothing is lost and `orb fetch --update` works if it has an `origin` remote.
+pub fn convert_local_repository(path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let git_repo = Repository::open(path).map_err(|e| format!("{} is not a Git repository: {}", path.display(), e.message()))?;
+    let workdir = git_repo
+        .workdir()
+        .ok_or_else(|| format!("{} is a bare Git repository; --from-git converts a checkout", path.display()))?
+        .to_path_buf();
+    if !workdir.join(".git").is_dir() {
+        return Err(format!("{} is a linked worktree or submodule checkout; convert its main repository", workdir.display()).into());
+    }
+    if workdir.join(".orb").exists() && !is_interrupted_import(&workdir) {
+        return Err(format!("{} is already an Orbit repository", workdir.display()).into());
+    }
     
-    // Get HEAD commit
-    let head = git_repo.head()?;
-    let head_commit = head.peel_to_commit()?;
+    info!("🔄 Converting the Git repository in {} to Orbit VOS format...", workdir.display());
+    let original_dir = std::env::current_dir()?;
+    std::env::set_current_dir(&workdir)?;
+    let result = convert_in_place(git_repo);
+    std::env::set_current_dir(original_dir)?;
+    result?;
     
-    // Traverse Git history (simple linear traversal for MVP)
-    let mut commits_to_process = vec![head_commit];
+    info!("");
+    info!("🎉 Git repository successfully converted to Orbit!");
+    info!("💡 Try `orb check` and `orb history` in {}", workdir.display());
+    Ok(())
+}
+
+/// Converts the Git repository in the current directory and moves it into `.orb/git`
+fn convert_in_place(git_repo: Repository) -> Result<(), Box<dyn std::error::Error>> {
+    let head = git_repo.head().and_then(|head| head.peel_to_commit()).map_err(|_| "the Git repository has no commits to convert")?.id();
+    let uncommitted = git_repo
+        .statuses(None)?
+        .iter()
+        .filter(|entry| !entry.status().is_ignored() && !entry.status().is_wt_new())
+        .count();
+    fs::create_dir_all(".orb")?;
+    if !Path::new(COMMIT_MAP_FILE).exists() {
+        fs::File::create(COMMIT_MAP_FILE)?;
+    }
     
-    // Process commits in reverse chronological order
-    while let Some(git_commit) = commits_to_process.pop() {
-        let git_oid = git_commit.id();
-        
-        // Skip if already processed
-        if converted_commits.contains_key(&git_oid) {
+    let tip = convert_git_history(&git_repo, head, &[], &Progress::new())
+        .map_err(|e| format!("{} (run `orb init --from-git` again to resume the conversion)", e))?;
+    update_head_ref(&tip)?;
+    record_submodules(&git_repo)?;
+    refs::set_head_branch(IMPORT_BRANCH)?;
+    let commit: Commit = vos::load(&tip, "commit")?;
+    let mut index = VosIndex::new();
+    index_working_tree(&commit.tree, "", &mut index)?;
+    index.save(&Path::new(".orb").join("index"))?;
+    keep_git_remote(git_repo, false)?;
+    if uncommitted > 0 {
+        warn!("⚠️  {} uncommitted change(s) are not in the converted history; they stay in the working directory", uncommitted);
+    }
+    Ok(())
+}
+
+/// Fetches new upstream commits into the Git repository kept by `--keep-remote` and
+/// converts only those, on top of the commits converted before. The import branch is
+/// fast-forwarded to the new tip; if it has commits of its own, it is left alone and
+/// the converted tip is only recorded as `git/<branch>`.
+pub fn update_git_import(options: &ImportOptions) -> Result<(), Box<dyn std::error::Error>> {
+    let git_repo = Repository::open_bare(GIT_DIR)
+        .map_err(|_| "this repository kept no Git remote; import it with `orb fetch --keep-remote` to update it later")?;
+    let mut remote = git_repo.find_remote("origin")?;
+    info!("🌐 Fetching new commits from {}", remote.url().unwrap_or("origin"));
+    let progress = Progress::new();
+    let result = remote.fetch::<&str>(&[], Some(&mut fetch_options(options, &progress)), None);
+    progress.finish();
+    result?;
+    
+    let head = git_repo.head()?;
+    let branch = head.shorthand().ok_or("the Git branch name is not UTF-8")?.to_string();
+    let upstream = git_repo.refname_to_id(&format!("refs/remotes/origin/{}", branch))?;
+    if head.target() == Some(upstream) {
+        info!("✅ Already up to date.");
+        return Ok(());
+    }
+    
+    let submodules = if git_repo.config()?.get_bool("orb.recurseSubmodules").unwrap_or(false) {
+        fetch_submodules(&git_repo, options, &progress)?
+    } else {
+        Vec::new()
+    };
+    info!("🔄 Converting new Git commits to Orbit VOS format...");
+    let tip = convert_git_history(&git_repo, upstream, &submodules, &progress)?;
+    git_repo.reference(head.name().ok_or("the Git branch name is not UTF-8")?, upstream, true, "orb fetch --update")?;
+    integrate_converted(&branch, IMPORT_BRANCH, &tip, merge::Strategy::FfOnly)
+}
+
+/// Records the converted tip of Git branch `git_branch` as `git/<git_branch>` and
+/// fast-forwards `branch` to it. A branch with commits of its own is integrated with
+/// `strategy`, or left alone for ff-only. The current branch's working tree follows
+/// it, so it must have no changes to tracked files.
+pub fn integrate_converted(git_branch: &str, branch: &str, tip: &str, strategy: merge::Strategy) -> Result<(), Box<dyn std::error::Error>> {
+    refs::write_ref(&refs::remote_tracking_ref(TRACKING_REMOTE, git_branch), tip)?;
+    let repository = repo::open()?;
+    let branch_ref = refs::branch_ref(branch);
+    let short = &tip[..7];
+    match refs::read_ref(&branch_ref) {
+        Some(local) if local == tip => info!("✅ '{}' is already at {}", branch, short),
+        Some(local) if ancestry::is_ancestor(tip, &local) => {
+            info!("✅ '{}' already contains the converted commits", branch);
+        }
+        Some(local) if !ancestry::is_ancestor(&local, tip) => {
+            let label = format!("{}/{}", TRACKING_REMOTE, git_branch);
+            if strategy != merge::Strategy::FfOnly {
+                repo::ensure_can_update(&repository, branch)?;
+            }
+            let Some(new_tip) = merge::integrate(strategy, branch, &local, tip, &label)? else {
+                warn!("⚠️  '{}' has commits of its own, so it was left at {}", branch, &local[..7.min(local.len())]);
+                info!("   The converted upstream history is at {} ({})", label, short);
+                info!("💡 `orb bridge pull <git-remote> --merge` (or --rebase) combines the two histories");
+                return Ok(());
+            };
+            repo::move_branch(&repository, branch, &new_tip)?;
+            match strategy {
+                merge::Strategy::Rebase => info!("♻️  Rebased '{}' onto {} (now at {})", branch, label, &new_tip[..7]),
+                _ => info!("🔀 Merged {} into '{}' (now at {})", label, branch, &new_tip[..7]),
+            }
+        }
+        local => {
+            repo::ensure_can_update(&repository, branch)?;
+            repo::move_branch(&repository, branch, tip)?;
+            match local {
+                Some(local) => info!("⏩ Fast-forwarded '{}' {}..{}", branch, &local[..7.min(local.len())], short),
+                None => info!("🌱 Created '{}' at {}", branch, short),
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Records the files of a converted tree in the VOS Index, so `orb check` compares the
+/// working directory with it. Files whose content differs are recorded without their
+/// metadata, so `orb check` rehashes them and reports them as modified.
+fn index_working_tree(tree_id: &str, prefix: &str, index: &mut VosIndex) -> Result<(), Box<dyn std::error::Error>> {
+    let directory: Directory = vos::load(tree_id, "tree")?;
+    for entry in directory.entries {
+        let path = if prefix.is_empty() { entry.name } else { format!("{}/{}", prefix, entry.name) };
+        if entry.mode == 0o040000 {
+            index_working_tree(&entry.id, &path, index)?;
             continue;
         }
-        
-        commit_count += 1;
-        println!("  📝 Converting commit {}: {}", commit_count, git_oid);
+        let working_id = match entry.mode {
+            0o120000 => vos::save_symlink(Path::new(&path)).ok(),
+            _ => vos::chunk_and_save_file(Path::new(&path)).ok().map(|(id, _)| id),
+        };
+        let (mtime, size) = match working_id {
+            Some(id) if id == entry.id => VosIndex::get_file_metadata(Path::new(&path)).unwrap_or((0, 0)),
+            _ => (0, 0),
+        };
+        index.update_entry(path, mtime, size, entry.id);
+    }
+    Ok(())
+}
+
+/// Fetches the submodule clones an import with `--recurse-submodules` kept. Submodules
+/// added upstream since are not cloned; their commits are reported as missing.
+fn fetch_submodules(git_repo: &Repository, options: &ImportOptions, progress: &Progress) -> Result<Vec<Repository>, Box<dyn std::error::Error>> {
+    let mut repositories = Vec::new();
+    let Ok(entries) = fs::read_dir(git_repo.path().join("orb-submodules")) else {
+        return Ok(repositories);
+    };
+    for entry in entries {
+        let repository = Repository::open_bare(entry?.path())?;
+        {
+            let mut remote = repository.find_remote("origin")?;
+            info!("📦 Fetching submodule {}", remote.url().unwrap_or("origin"));
+            let result = remote.fetch(&["+refs/heads/*:refs/heads/*"], Some(&mut fetch_options(options, progress)), None);
+            progress.finish();
+            result?;
+        }
+        repositories.push(repository);
+    }
+    Ok(repositories)
+}
+
+/// Clones `url` into `path`, authenticating with `options.auth` and showing progress
+fn clone_git_repository(
+    url: &str,
+    path: &Path,
+    bare: bool,
+    options: &ImportOptions,
+    progress: &Progress,
+) -> Result<Repository, git2::Error> {
+    let mut fetch_options = fetch_options(options, progress);
+    if let Some(depth) = options.depth.filter(|_| !bare) {
+        fetch_options.depth(depth.try_into().unwrap_or(i32::MAX));
+    }
+    let git_repo = git2::build::RepoBuilder::new().bare(bare).fetch_options(fetch_options).clone(url, path);
+    progress.finish();
+    git_repo
+}
+
+/// Fetch options authenticating with `options.auth` and showing transfer progress
+pub fn fetch_options<'a>(options: &'a ImportOptions, progress: &'a Progress) -> git2::FetchOptions<'a> {
+    let mut callbacks = options.auth.callbacks();
+    let mut phase = "";
+    callbacks.transfer_progress(move |stats| {
+        // Objects arrive first; once all have, their deltas are resolved
+        let (name, done, total) = if stats.total_deltas() == 0 || stats.received_objects() < stats.total_objects() {
+            ("Receiving", stats.received_objects(), stats.total_objects())
+        } else {
+            ("Resolving", stats.indexed_deltas(), stats.total_deltas())
+        };
+        if name != phase && total > 0 {
+            phase = name;
+            progress.begin(name, Some(total as u64));
+        }
+        progress.set(done as u64, stats.received_bytes() as u64);
+        true
+    });
+    let mut fetch_options = git2::FetchOptions::new();
+    fetch_options.remote_callbacks(callbacks);
+    fetch_options
+}
+
+/// Clones every submodule of the checked-out commit (bare, inside `.git`, so they go
+/// away with it) for their recorded commits to be converted along with the superproject
+fn clone_submodules(
+    git_repo: &Repository,
+    url: &str,
+    options: &ImportOptions,
+    progress: &Progress,
+) -> Result<Vec<Repository>, Box<dyn std::error::Error>> {
+    let mut repositories = Vec::new();
+    for submodule in git_repo.submodules()? {
+        let name = submodule.name().unwrap_or("submodule").to_string();
+        let Some(submodule_url) = submodule.url().map(|relative| resolve_submodule_url(url, relative)) else {
+            warn!("  ⚠️  Submodule {} has no URL; skipping it", name);
+            continue;
+        };
+        let path = git_repo.path().join("orb-submodules").join(name.replace('/', "_"));
+        // A resumed import cloned it already
+        if let Ok(repository) = Repository::open_bare(&path) {
+            repositories.push(repository);
+            continue;
+        }
+        info!("📦 Cloning submodule {} from {}", name, submodule_url);
+        repositories.push(clone_git_repository(&submodule_url, &path, true, options, progress)?);
+    }
+    Ok(repositories)
+}
+
+/// Resolves a submodule URL from `.gitmodules`; `../sibling.git` is relative to the
+/// superproject's URL, as in Git
+fn resolve_submodule_url(superproject: &str, submodule: &str) -> String {
+    if !submodule.starts_with("./") && !submodule.starts_with("../") {
+        return submodule.to_string();
+    }
+    let mut base: Vec<&str> = superproject.trim_end_matches('/').split('/').collect();
+    for part in submodule.split('/') {
+        match part {
+            "." => {}
+            ".." => {
+                base.pop();
+            }
+            part => base.push(part),
+        }
+    }
+    base.join("/")
+}
+
+/// Lists the submodules of the checked-out commit, which the import skipped, in
+/// `.orb/submodules.json` so they can be fetched separately
+fn record_submodules(git_repo: &Repository) -> Result<(), Box<dyn std::error::Error>> {
+    let submodules: Vec<serde_json::Value> = git_repo
+        .submodules()?
+        .iter()
+        .map(|submodule| serde_json::json!({
+            "path": submodule.path().to_string_lossy(),
+            "url": submodule.url(),
+            "commit": submodule.head_id().map(|id| id.to_string()),
+        }))
+        .collect();
+    if submodules.is_empty() {
+        return Ok(());
+    }
+    fs::write(SUBMODULES_FILE, serde_json::to_vec_pretty(&submodules)?)?;
+    info!("📦 Skipped {} submodule(s); their URLs and commits are listed in {}", submodules.len(), SUBMODULES_FILE);
+    info!("   (fetch them separately, or re-run with --recurse-submodules to include their files)");
+    Ok(())
+}
+
+/// Whether `target` holds an import that stopped before replacing Git with Orbit
+fn is_interrupted_import(target: &Path) -> bool {
+    target.join(".git").is_dir() && target.join(COMMIT_MAP_FILE).is_file()
+}
+
+/// Git commits the commit map records as converted. A line cut short by an interruption
+/// is ignored, so that commit is converted again.
+pub fn load_commit_map(path: &Path) -> Result<HashMap<Oid, ObjectId>, std::io::Error> {
+    let map = fs::read_to_string(path)?
+        .lines()
+        .filter_map(|line| {
+            let (git, orbit) = line.split_once(' ')?;
+            let orbit = orbit.trim();
+            vos::store().contains(orbit).then(|| Some((Oid::from_str(git).ok()?, orbit.to_string())))?
+        })
+        .collect();
+    Ok(map)
+}
+
+/// State of one conversion. Trees and blobs shared between commits are converted once
+/// (as long as they stay in the bounded caches).
+struct Converter<'a> {
+    /// Cloned submodules, searched for the commits that gitlink entries record
+    submodules: &'a [Repository],
+    /// Recorded submodule commits that no cloned submodule has
+    missing_submodule_commits: HashSet<Oid>,
+    commits: HashMap<Oid, ObjectId>,
+    /// The commit map file, appended to after each converted commit
+    checkpoint: fs::File,
+    trees: HashMap<Oid, ObjectId>,
+    blobs: HashMap<Oid, ObjectId>,
+    /// Bytes of file content converted for the current commit, for progress
+    bytes: u64,
+    /// Chunks of every converted blob, for the deduplication report
+    chunks: vos::ChunkStats,
+}
+
+/// Converts Git commit history to Orbit VOS format. Commits are visited parents first
+/// (topological order), so every parent of a commit, merges included, is converted
+/// before it and keeps its place in the DAG. In a shallow clone, parents beyond the
+/// fetched depth are left out and their children become root commits. Commits the
+/// commit map lists were converted before and are skipped. Returns the Orbit ID of `tip`.
+pub fn convert_git_history(git_repo: &Repository, tip: Oid, submodules: &[Repository], progress: &Progress) -> Result<ObjectId, Box<dyn std::error::Error>> {
+    let commits = load_commit_map(Path::new(COMMIT_MAP_FILE))?;
+    let mut revwalk = git_repo.revwalk()?;
+    revwalk.set_sorting(Sort::TOPOLOGICAL | Sort::REVERSE)?;
+    revwalk.push(tip)?;
+    for converted in commits.keys() {
+        // Commits are converted parents first, so their ancestors were converted too
+        let _ = revwalk.hide(*converted);
+    }
+    let oids = revwalk.collect::<Result<Vec<_>, _>>()?;
+    if oids.is_empty() {
+        if let Some(tip_id) = commits.get(&tip) {
+            info!("✅ No new Git commits to convert");
+            return Ok(tip_id.clone());
+        }
+    }
+    let shallow = git_repo.is_shallow();
+    
+    let mut converter = Converter {
+        submodules,
+        missing_submodule_commits: HashSet::new(),
+        commits,
+        checkpoint: fs::OpenOptions::new().create(true).append(true).open(COMMIT_MAP_FILE)?,
+        trees: HashMap::new(),
+        blobs: HashMap::new(),
+        bytes: 0,
+        chunks: vos::ChunkStats::default(),
+    };
+    if !converter.commits.is_empty() {
+        info!("   {} commits were converted already; {} remain", converter.commits.len(), oids.len());
+    }
+    progress.begin_counting("Converting", Some(oids.len() as u64), "commits");
+    for git_oid in &oids {
+        if converter.commits.contains_key(git_oid) {
+            progress.advance(0);
+            continue;
+        }
+        let git_commit = git_repo.find_commit(*git_oid)?;
         
         // Convert Git tree to Orbit directory structure
-        let git_tree = git_commit.tree()?;
-        let orbit_tree_id = convert_git_tree(&git_tree, git_repo)?;
+        converter.bytes = 0;
+        let orbit_tree_id = converter.convert_tree(git_repo, &git_commit.tree()?)?;
         
         // Create Orbit commit
         let author = git_commit.author();
         let message = git_commit.message().unwrap_or("(no message)");
         let timestamp = git_commit.time().seconds();
         
-        // Handle parent commits
+        // Every parent precedes its children in the walk, so all of them are converted
         let mut parents = Vec::new();
-        for i in 0..git_commit.parent_count() {
-            if let Ok(parent_git_commit) = git_commit.parent(i) {
-                let parent_oid = parent_git_commit.id();
-                // If we've already converted this parent, use the converted ID
-                if let Some(converted_parent_id) = converted_commits.get(&parent_oid) {
-                    parents.push(converted_parent_id.clone());
-                }
-                // If not converted yet, we'll handle it in the next iteration
+        for parent_oid in git_commit.parent_ids() {
+            match converter.commits.get(&parent_oid) {
+                Some(parent_id) => parents.push(parent_id.clone()),
+                None if shallow => {}
+                None => return Err(format!("parent {} of {} was not converted first", parent_oid, git_oid).into()),
             }
         }
         
@@ -116,93 +558,146 @@ fn convert_git_history(git_repo: &Repository) -> Result<(), Box<dyn std::error::
         
         // Save Orbit commit
         let orbit_commit_id = vos::save_object(&serde_json::to_vec(&orbit_commit)?);
-        converted_commits.insert(git_oid, orbit_commit_id.clone());
-        
-        // Update HEAD to point to the latest converted commit
-        update_head_ref(&orbit_commit_id)?;
-        
-        // Add parent commits to processing queue (for now, just handle first parent)
-        if git_commit.parent_count() > 0 {
-            if let Ok(parent) = git_commit.parent(0) {
-                commits_to_process.push(parent);
-            }
-        }
+        writeln!(converter.checkpoint, "{} {}", git_oid, orbit_commit_id)?;
+        converter.commits.insert(*git_oid, orbit_commit_id);
+        progress.advance(converter.bytes);
     }
+    progress.finish();
     
-    println!("✅ Converted {} commits to Orbit format", commit_count);
-    Ok(())
+    let tip_id = converter.commits.get(&tip).ok_or("the tip commit was not converted")?.clone();
+    
+    info!("✅ Converted {} commits to Orbit format", oids.len());
+    if shallow {
+        info!("   (shallow import: history before the oldest converted commit was not fetched)");
+    }
+    report_deduplication(&converter.chunks);
+    if !submodules.is_empty() && !converter.missing_submodule_commits.is_empty() {
+        warn!(
+            "⚠️  {} recorded submodule commit(s) were not found in the submodules' repositories and were left out",
+            converter.missing_submodule_commits.len()
+        );
+    }
+    Ok(tip_id)
 }
 
-/// Converts a Git tree to Orbit directory structure
-fn convert_git_tree(git_tree: &git2::Tree, git_repo: &Repository) -> Result<ObjectId, Box<dyn std::error::Error>> {
-    let mut entries = Vec::new();
-    
-    git_tree.walk(TreeWalkMode::PreOrder, |root, entry| {
-        if let Some(name) = entry.name() {
-            let _full_path = if root.is_empty() {
-                name.to_string()
-            } else {
-                format!("{}/{}", root, name)
+impl Converter<'_> {
+    /// Converts a Git tree of `git_repo` to Orbit directory structure, recursing into
+    /// subdirectories (and into submodules when they were cloned)
+    fn convert_tree(&mut self, git_repo: &Repository, git_tree: &git2::Tree) -> Result<ObjectId, Box<dyn std::error::Error>> {
+        if let Some(directory_id) = self.trees.get(&git_tree.id()) {
+            return Ok(directory_id.clone());
+        }
+        let mut entries = Vec::new();
+        
+        for entry in git_tree.iter() {
+            let Some(name) = entry.name() else {
+                warn!("  ⚠️  Skipping entry with a non-UTF-8 name in tree {}", git_tree.id());
+                continue;
             };
             
             match entry.kind() {
                 Some(ObjectType::Blob) => {
                     // Convert Git blob to Orbit file
-                    if let Ok(git_blob) = git_repo.find_blob(entry.id()) {
-                        if let Ok(orbit_file_id) = convert_git_blob(&git_blob) {
-                            entries.push(DirectoryEntry {
-                                mode: 0o100644, // Regular file mode
-                                name: name.to_string(),
-                                id: orbit_file_id,
-                            });
+                    let id = match self.blobs.get(&entry.id()) {
+                        Some(id) => id.clone(),
+                        None => {
+                            let id = self.convert_blob(git_repo, entry.id())?;
+                            remember(&mut self.blobs, entry.id(), id.clone());
+                            id
                         }
-                    }
+                    };
+                    entries.push(DirectoryEntry {
+                        mode: blob_mode(entry.filemode()),
+                        name: name.to_string(),
+                        id,
+                    });
                 }
                 Some(ObjectType::Tree) => {
-                    // For subdirectories, we'd recursively convert them
-                    // For MVP, we'll mark them as directories but not fully implement
+                    let subtree = git_repo.find_tree(entry.id())?;
                     entries.push(DirectoryEntry {
                         mode: 0o040000, // Directory mode
                         name: name.to_string(),
-                        id: "placeholder_dir_id".to_string(), // TODO: Implement recursive directory conversion
+                        id: self.convert_tree(git_repo, &subtree)?,
                     });
                 }
-                _ => {} // Skip other object types
+                Some(ObjectType::Commit) => {
+                    // A submodule (gitlink): inline the recorded commit's tree if a cloned
+                    // submodule has it, otherwise leave it out
+                    let submodules = self.submodules;
+                    let found = submodules.iter().find_map(|repo| Some((repo, repo.find_commit(entry.id()).ok()?)));
+                    match found {
+                        Some((submodule, commit)) => entries.push(DirectoryEntry {
+                            mode: 0o040000,
+                            name: name.to_string(),
+                            id: self.convert_tree(submodule, &commit.tree()?)?,
+                        }),
+                        None => {
+                            self.missing_submodule_commits.insert(entry.id());
+                        }
+                    }
+                }
+                _ => {}
             }
         }
+        entries.sort_by(|a, b| a.name.cmp(&b.name));
         
-        TreeWalkResult::Ok
-    })?;
-    
-    // Create Orbit directory object
-    let orbit_directory = Directory { entries };
-    let directory_id = vos::save_object(&serde_json::to_vec(&orbit_directory)?);
-    
-    Ok(directory_id)
+        // Create Orbit directory object
+        let orbit_directory = Directory { entries };
+        let directory_id = vos::save_object(&serde_json::to_vec(&orbit_directory)?);
+        remember(&mut self.trees, git_tree.id(), directory_id.clone());
+        
+        Ok(directory_id)
+    }
+
+    /// Converts a Git blob to an Orbit file, streaming its content through the chunker.
+    /// libgit2 streams only loose objects; packed ones are read whole, one at a time.
+    fn convert_blob(&mut self, git_repo: &Repository, oid: Oid) -> Result<ObjectId, Box<dyn std::error::Error>> {
+        let (id, size) = match git_repo.odb()?.reader(oid) {
+            Ok((reader, _, _)) => vos::save_stream(reader, &mut self.chunks)?,
+            Err(_) => vos::save_stream(git_repo.find_blob(oid)?.content(), &mut self.chunks)?,
+        };
+        self.bytes += size as u64;
+        Ok(id)
+    }
 }
 
-/// Converts a Git blob to Orbit file with VOS chunking
-fn convert_git_blob(git_blob: &git2::Blob) -> Result<ObjectId, Box<dyn std::error::Error>> {
-    let content = git_blob.content();
-    
-    // For MVP, treat each file as a single chunk (like our current implementation)
-    // In the future, we can implement full FastCDC chunking here
-    let chunk_hash = vos::save_object(content);
-    
-    // Create Orbit file object
-    let orbit_file = File {
-        root_chunk_id: chunk_hash,
-        size: content.len(),
+/// Caches a conversion, starting the cache over once it holds `CACHE_ENTRIES`
+fn remember(cache: &mut HashMap<Oid, ObjectId>, oid: Oid, id: ObjectId) {
+    if cache.len() >= CACHE_ENTRIES {
+        cache.clear();
+    }
+    cache.insert(oid, id);
+}
+
+/// Orbit mode of a Git blob entry: executable files and symlinks keep their modes, and
+/// everything else (including Git's legacy 100664) is a regular file
+fn blob_mode(git_mode: i32) -> u32 {
+    match git_mode {
+        0o100755 => 0o100755,
+        0o120000 => 0o120000,
+        _ => 0o100644,
+    }
+}
+
+/// Prints how much of the imported history's content chunking deduplicated
+pub fn report_deduplication(stats: &vos::ChunkStats) {
+    let saved = match stats.bytes {
+        0 => 0.0,
+        bytes => 100.0 * (1.0 - stats.new_bytes as f64 / bytes as f64),
     };
-    
-    let file_id = vos::save_object(&serde_json::to_vec(&orbit_file)?);
-    Ok(file_id)
+    info!(
+        "📊 {} file versions ({}) split into {} chunks; {} stored ({}), {:.1}% deduplicated",
+        stats.files,
+        crate::transfer::format_bytes(stats.bytes),
+        stats.chunks,
+        stats.new_chunks,
+        crate::transfer::format_bytes(stats.new_bytes),
+        saved
+    );
 }
 
 /// Updates the HEAD reference to point to the converted commit
 fn update_head_ref(commit_id: &ObjectId) -> Result<(), Box<dyn std::error::Error>> {
-    use std::io::Write;
-    
     let head_path = Path::new(".orb/refs/heads/main");
     if let Some(parent) = head_path.parent() {
         fs::create_dir_all(parent)?;
@@ -219,17 +714,17 @@ fn cleanup_git_and_init_orbit() -> Result<(), Box<dyn std::error::Error>> {
     // Remove .git directory (this is much easier than removing entire temp directory)
     if Path::new(".git").exists() {
         match fs::remove_dir_all(".git") {
-            Ok(_) => println!("  ✅ Removed Git metadata"),
+            Ok(_) => info!("  ✅ Removed Git metadata"),
             Err(_) => {
                 // On Windows, Git might still have locks, but that's okay
                 // We can try to make it writable first
                 if cfg!(windows) {
                     let _ = make_directory_writable(".git");
                     match fs::remove_dir_all(".git") {
-                        Ok(_) => println!("  ✅ Removed Git metadata"),
+                        Ok(_) => info!("  ✅ Removed Git metadata"),
                         Err(_) => {
-                            println!("  ⚠️  Could not remove .git directory completely");
-                            println!("     This won't affect Orbit functionality, but you may want to delete it manually later.");
+                            warn!("  ⚠️  Could not remove .git directory completely");
+                            info!("     This won't affect Orbit functionality, but you may want to delete it manually later.");
                         }
                     }
                 } else {
@@ -241,10 +736,10 @@ fn cleanup_git_and_init_orbit() -> Result<(), Box<dyn std::error::Error>> {
     
     // Initialize Orbit repository (if not already done during conversion)
     if !Path::new(".orb").exists() {
-        println!("🚀 Initializing Orbit repository...");
+        info!("🚀 Initializing Orbit repository...");
         repo::init()?;
     } else {
-        println!("✅ Orbit repository structure already ready");
+        info!("✅ Orbit repository structure already ready");
     }
     
     Ok(())
@@ -272,7 +767,7 @@ fn make_directory_writable(_dir: &str) -> Result<(), Box<dyn std::error::Error>>
 }
 
 /// Extracts repository name from Git URL
-fn extract_repo_name(url: &str) -> Result<String, Box<dyn std::error::Error>> {
+pub fn extract_repo_name(url: &str) -> Result<String, Box<dyn std::error::Error>> {
     let url = url.trim_end_matches('/');
     let name = if let Some(pos) = url.rfind('/') {
         &url[pos + 1..]
@@ -300,4 +795,11 @@ mod tests {
         assert_eq!(extract_repo_name("https://github.com/user/repo").unwrap(), "repo");
         assert_eq!(extract_repo_name("git@github.com:user/repo.git").unwrap(), "repo");
     }
+    
+    #[test]
+    fn test_resolve_submodule_url() {
+        assert_eq!(resolve_submodule_url("https://host/team/app.git", "../lib.git"), "https://host/team/lib.git");
+        assert_eq!(resolve_submodule_url("https://host/team/app.git/", "../../other/lib"), "https://host/other/lib");
+        assert_eq!(resolve_submodule_url("https://host/team/app.git", "git@host:x/lib.git"), "git@host:x/lib.git");
+    }
 }
\ No newline at end of file
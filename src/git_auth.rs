@@ -0,0 +1,157 @@
+use std::io::{IsTerminal, Write};
+use std::path::PathBuf;
+use base64::Engine;
+use git2::{Cred, CredentialType, RemoteCallbacks};
+use tracing::info;
+
+/// Environment variable read for an HTTPS token when `--token-env` names none
+pub const TOKEN_ENV: &str = "ORB_GIT_TOKEN";
+/// Environment variable holding the passphrase of an encrypted SSH key
+const PASSPHRASE_ENV: &str = "ORB_SSH_PASSPHRASE";
+/// Username sent with a bare HTTPS token; GitHub and GitLab accept any with a token
+const TOKEN_USER: &str = "x-access-token";
+
+/// How `orb fetch` authenticates to a private Git remote
+#[derive(Debug, Default, Clone)]
+pub struct GitAuth {
+    /// SSH private key, tried after the SSH agent
+    pub ssh_key: Option<PathBuf>,
+    /// Environment variable holding an HTTPS token (default `ORB_GIT_TOKEN`)
+    pub token_env: Option<String>,
+}
+
+/// What the credentials callback has offered so far. libgit2 calls it again after every
+/// rejected credential, so each source is tried once before giving up.
+#[derive(Default)]
+struct Attempts {
+    agent: bool,
+    keys: usize,
+    token: bool,
+    helper: bool,
+    prompt: bool,
+}
+
+impl GitAuth {
+    /// Callbacks answering credential requests. SSH remotes try the SSH agent, then
+    /// `--ssh-key` or the default keys in `~/.ssh`; HTTPS remotes try the token, then
+    /// Git's credential helpers, then ask on the terminal.
+    pub fn callbacks(&self) -> RemoteCallbacks<'_> {
+        let mut attempts = Attempts::default();
+        let mut callbacks = RemoteCallbacks::new();
+        callbacks.credentials(move |url, username, allowed| {
+            if allowed.contains(CredentialType::USERNAME) {
+                return Cred::username(username.unwrap_or("git"));
+            }
+            if allowed.contains(CredentialType::SSH_KEY) {
+                let user = username.unwrap_or("git");
+                if !attempts.agent {
+                    attempts.agent = true;
+                    if std::env::var_os("SSH_AUTH_SOCK").is_some() {
+                        return Cred::ssh_key_from_agent(user);
+                    }
+                }
+                let keys = self.ssh_keys();
+                if let Some(key) = keys.get(attempts.keys) {
+                    attempts.keys += 1;
+                    info!("🔑 Trying SSH key {}", key.display());
+                    let passphrase = std::env::var(PASSPHRASE_ENV).ok().or_else(|| {
+                        key_is_encrypted(key).then(|| prompt(&format!("Passphrase for {}", key.display()), true)).flatten()
+                    });
+                    return Cred::ssh_key(user, None, key, passphrase.as_deref());
+                }
+            }
+            if allowed.contains(CredentialType::USER_PASS_PLAINTEXT) {
+                if !attempts.token {
+                    attempts.token = true;
+                    let env = self.token_env.as_deref().unwrap_or(TOKEN_ENV);
+                    if let Some(token) = std::env::var(env).ok().filter(|token| !token.is_empty()) {
+                        return Cred::userpass_plaintext(username.unwrap_or(TOKEN_USER), &token);
+                    }
+                }
+                if !attempts.helper {
+                    attempts.helper = true;
+                    if let Ok(config) = git2::Config::open_default() {
+                        if let Ok(cred) = Cred::credential_helper(&config, url, username) {
+                            return Ok(cred);
+                        }
+                    }
+                }
+                if !attempts.prompt && std::io::stdin().is_terminal() {
+                    attempts.prompt = true;
+                    info!("🔐 {} needs credentials", url);
+                    let user = match username {
+                        Some(user) => Some(user.to_string()),
+                        None => prompt("Username", false),
+                    };
+                    if let (Some(user), Some(password)) = (user, prompt("Password or token", true)) {
+                        return Cred::userpass_plaintext(&user, &password);
+                    }
+                }
+            }
+            Err(git2::Error::from_str(&format!(
+                "authentication to {} failed; use an SSH key (--ssh-key) or put a token in ${}",
+                url,
+                self.token_env.as_deref().unwrap_or(TOKEN_ENV)
+            )))
+        });
+        callbacks
+    }
+
+    /// `--ssh-key`, or the usual key files in `~/.ssh` that exist
+    fn ssh_keys(&self) -> Vec<PathBuf> {
+        if let Some(key) = &self.ssh_key {
+            return vec![key.clone()];
+        }
+        let Some(home) = std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE")) else {
+            return Vec::new();
+        };
+        let ssh = PathBuf::from(home).join(".ssh");
+        ["id_ed25519", "id_ecdsa", "id_rsa"].iter().map(|name| ssh.join(name)).filter(|path| path.is_file()).collect()
+    }
+}
+
+/// Whether a private key file needs a passphrase
+fn key_is_encrypted(key: &std::path::Path) -> bool {
+    let Ok(text) = std::fs::read_to_string(key) else { return false };
+    if text.contains("ENCRYPTED") {
+        return true;
+    }
+    // OpenSSH-format keys name their cipher inside the base64 body ("none" when unencrypted)
+    let body: String = text.lines().filter(|line| !line.starts_with("-----")).collect();
+    base64::engine::general_purpose::STANDARD
+        .decode(body)
+        .is_ok_and(|data| data.starts_with(b"openssh-key-v1\0") && !data[15..].starts_with(b"\0\0\0\x04none"))
+}
+
+/// Reads a line from the terminal, without echo for secrets; None when not interactive
+fn prompt(label: &str, secret: bool) -> Option<String> {
+    if !std::io::stdin().is_terminal() {
+        return None;
+    }
+    print!("   {}: ", label);
+    std::io::stdout().flush().ok()?;
+    let echo_off = secret && set_echo(false);
+    let mut answer = String::new();
+    let read = std::io::stdin().read_line(&mut answer);
+    if echo_off {
+        set_echo(true);
+        println!();
+    }
+    read.ok()?;
+    Some(answer.trim_end_matches(['\r', '\n']).to_string()).filter(|answer| !answer.is_empty())
+}
+
+/// Turns terminal echo on or off; returns whether it worked
+#[cfg(unix)]
+fn set_echo(on: bool) -> bool {
+    std::process::Command::new("stty")
+        .arg(if on { "echo" } else { "-echo" })
+        .stdin(std::process::Stdio::inherit())
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+#[cfg(not(unix))]
+fn set_echo(_on: bool) -> bool {
+    false
+}
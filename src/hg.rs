@@ -0,0 +1,236 @@
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::process::Command;
+use crate::fast_export::quote_path;
+use crate::{fast_import, fetch};
+use tracing::{info, warn};
+
+/// Where the Mercurial clone lives while it is converted
+const HG_CLONE: &str = ".orb/hg";
+
+/// One changeset of `hg log`
+#[derive(Debug, PartialEq)]
+struct Revision {
+    rev: u64,
+    parents: Vec<u64>,
+    branch: String,
+    author: String,
+    timestamp: i64,
+    /// Seconds west of UTC, as Mercurial records it
+    offset: i64,
+    message: String,
+}
+
+/// Clones a Mercurial repository with the `hg` command and converts its history into a
+/// new Orbit repository. Named branches and bookmarks become branches (`default`
+/// becomes main) and tags become tags.
+pub fn fetch_hg_repository(url: &str, target_dir: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+    info!("🌐 Fetching Mercurial repository: {}", url);
+    let version = hg(Path::new("."), &["version", "--quiet"])?;
+    info!("🔧 Using {}", String::from_utf8_lossy(&version).trim());
+
+    let repo_name = fetch::extract_repo_name(url)?;
+    let target = target_dir.unwrap_or(&repo_name);
+    fetch::convert_into_new_repository(target, || {
+        info!("⬇️  Cloning Mercurial repository...");
+        hg(Path::new("."), &["clone", "--noupdate", url, HG_CLONE])?;
+        info!("🔄 Converting Mercurial history to Orbit VOS format...");
+        let clone = fs::canonicalize(HG_CLONE)?;
+        let imported = fast_import::import_generated(move |out| write_stream(&clone, out));
+        fs::remove_dir_all(HG_CLONE)?;
+        imported.map(|_| ())
+    })?;
+
+    info!("");
+    info!("🎉 Mercurial repository successfully converted to Orbit!");
+    info!("💡 Try these commands:");
+    info!("   cd {}", target);
+    info!("   orb status");
+    info!("   orb history");
+    Ok(())
+}
+
+/// Writes the clone's whole history as a fast-import stream; each changeset's mark is
+/// its revision number plus one
+fn write_stream(clone: &Path, out: &mut dyn Write) -> std::io::Result<()> {
+    let log = hg(clone, &["log", "--rev", "all()", "--template", LOG_TEMPLATE])?;
+    let revisions = parse_log(&String::from_utf8_lossy(&log)).map_err(std::io::Error::other)?;
+    let scratch = clone.with_file_name("hg-files");
+    for revision in &revisions {
+        write_revision(clone, &scratch, revision, out)?;
+    }
+
+    let mut branches: Vec<String> = revisions.iter().map(|revision| branch_name(&revision.branch)).collect();
+    for (bookmark, rev) in named_revisions(clone, "bookmarks", "{bookmark}\\x1f{rev}\\n")? {
+        let name = branch_name(&bookmark);
+        if branches.contains(&name) {
+            warn!("⚠️  Bookmark {} has the name of a branch; skipped", bookmark);
+            continue;
+        }
+        write!(out, "reset refs/heads/{}\nfrom :{}\n\n", name, rev + 1)?;
+        branches.push(name);
+    }
+    for (tag, rev) in named_revisions(clone, "tags", "{tag}\\x1f{rev}\\n")? {
+        if tag != "tip" {
+            write!(out, "reset refs/tags/{}\nfrom :{}\n\n", ref_name(&tag), rev + 1)?;
+        }
+    }
+    out.write_all(b"done\n")
+}
+
+const LOG_TEMPLATE: &str = "{rev}\\x1f{p1rev}\\x1f{p2rev}\\x1f{branch}\\x1f{author}\\x1f{date|hgdate}\\x1f{desc}\\x1e";
+
+/// Records of `LOG_TEMPLATE`: fields split by 0x1f and records by 0x1e
+fn parse_log(log: &str) -> Result<Vec<Revision>, String> {
+    log.split('\x1e')
+        .filter(|record| !record.trim().is_empty())
+        .map(|record| {
+            let fields: Vec<&str> = record.split('\x1f').collect();
+            let invalid = || format!("unexpected hg log record '{}'", record);
+            let [rev, p1, p2, branch, author, date, message] = fields[..] else { return Err(invalid()) };
+            let (timestamp, offset) = date.split_once(' ').ok_or_else(invalid)?;
+            let parent = |p: &str| p.parse::<i64>().map_err(|_| invalid());
+            Ok(Revision {
+                rev: rev.parse().map_err(|_| invalid())?,
+                parents: [parent(p1)?, parent(p2)?].into_iter().filter(|p| *p >= 0).map(|p| p as u64).collect(),
+                branch: branch.to_string(),
+                author: author.to_string(),
+                timestamp: timestamp.parse().map_err(|_| invalid())?,
+                offset: offset.parse().map_err(|_| invalid())?,
+                message: message.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// A changeset as a `commit` with the files it changed against its first parent
+fn write_revision(clone: &Path, scratch: &Path, revision: &Revision, out: &mut dyn Write) -> std::io::Result<()> {
+    let rev = revision.rev.to_string();
+    let status = hg(clone, &["status", "--change", &rev, "--added", "--modified", "--removed", "--print0"])?;
+    let mut changed = Vec::new();
+    let mut removed = Vec::new();
+    for entry in status.split(|c| *c == 0).filter(|entry| entry.len() > 2) {
+        let path = String::from_utf8_lossy(&entry[2..]).into_owned();
+        if entry[0] == b'R' { removed.push(path) } else { changed.push(path) }
+    }
+
+    let mut modes = Vec::new();
+    if !changed.is_empty() {
+        // Files are named through a list file, which keeps large changesets within the
+        // command line limit
+        let list = scratch.with_extension("list");
+        fs::write(&list, changed.iter().map(|path| format!("path:{}\n", path)).collect::<String>())?;
+        let pattern = format!("listfile:{}", list.display());
+        let flags = hg(clone, &["files", "--rev", &rev, "--template", "{flags}\\x1f{path}\\n", &pattern])?;
+        for line in String::from_utf8_lossy(&flags).lines() {
+            if let Some((flags, path)) = line.split_once('\x1f') {
+                modes.push((path.to_string(), file_mode(flags)));
+            }
+        }
+        let output = format!("{}/%p", scratch.display());
+        hg(clone, &["cat", "--rev", &rev, "--output", &output, &pattern])?;
+        fs::remove_file(&list)?;
+    }
+
+    let branch = branch_name(&revision.branch);
+    if revision.parents.is_empty() {
+        write!(out, "reset refs/heads/{}\n\n", branch)?;
+    }
+    let person = identity(&revision.author);
+    let zone = -revision.offset;
+    let when = format!("{} {}{:02}{:02}", revision.timestamp, if zone < 0 { '-' } else { '+' }, zone.abs() / 3600, zone.abs() % 3600 / 60);
+    write!(out, "commit refs/heads/{}\nmark :{}\n", branch, revision.rev + 1)?;
+    write!(out, "author {} {}\ncommitter {} {}\n", person, when, person, when)?;
+    write!(out, "data {}\n{}\n", revision.message.len(), revision.message)?;
+    for (i, parent) in revision.parents.iter().enumerate() {
+        writeln!(out, "{} :{}", if i == 0 { "from" } else { "merge" }, parent + 1)?;
+    }
+    for path in &removed {
+        writeln!(out, "D {}", quote_path(path))?;
+    }
+    for (path, mode) in &modes {
+        let content = fs::read(scratch.join(path))?;
+        write!(out, "M {:o} inline {}\ndata {}\n", mode, quote_path(path), content.len())?;
+        out.write_all(&content)?;
+        out.write_all(b"\n")?;
+    }
+    out.write_all(b"\n")?;
+    if scratch.exists() {
+        fs::remove_dir_all(scratch)?;
+    }
+    Ok(())
+}
+
+/// `(name, revision)` lines of `hg bookmarks` or `hg tags`
+fn named_revisions(clone: &Path, command: &str, template: &str) -> std::io::Result<Vec<(String, u64)>> {
+    let output = hg(clone, &[command, "--template", template])?;
+    Ok(String::from_utf8_lossy(&output)
+        .lines()
+        .filter_map(|line| {
+            let (name, rev) = line.split_once('\x1f')?;
+            Some((name.to_string(), rev.parse().ok()?))
+        })
+        .collect())
+}
+
+/// The Orbit branch for a Mercurial branch or bookmark
+fn branch_name(name: &str) -> String {
+    if name == "default" { "main".to_string() } else { ref_name(name) }
+}
+
+/// A Mercurial name with the characters refs cannot hold replaced by `-`
+fn ref_name(name: &str) -> String {
+    name.chars().map(|c| if c.is_whitespace() || "~^:?*[\\".contains(c) { '-' } else { c }).collect()
+}
+
+/// `Name <email>`, with empty brackets for a user name alone
+fn identity(author: &str) -> String {
+    if author.contains('<') { author.to_string() } else { format!("{} <>", author.trim()) }
+}
+
+/// Mode of a manifest entry: `l` is a symlink, `x` an executable
+fn file_mode(flags: &str) -> u32 {
+    if flags.contains('l') {
+        0o120000
+    } else if flags.contains('x') {
+        0o100755
+    } else {
+        0o100644
+    }
+}
+
+/// Runs `hg` in `dir` without user settings changing its output
+fn hg(dir: &Path, args: &[&str]) -> std::io::Result<Vec<u8>> {
+    let output = Command::new("hg")
+        .args(args)
+        .current_dir(dir)
+        .env("HGPLAIN", "1")
+        .output()
+        .map_err(|e| std::io::Error::other(format!("could not run hg ({}); install Mercurial to import hg repositories", e)))?;
+    if !output.status.success() {
+        return Err(std::io::Error::other(format!("hg {} failed: {}", args[0], String::from_utf8_lossy(&output.stderr).trim())));
+    }
+    Ok(output.stdout)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_hg_log() {
+        let log = "0\x1f-1\x1f-1\x1fdefault\x1fAda <ada@example.com>\x1f1700000000 -3600\x1fFirst\x1e\
+                   1\x1f0\x1f-1\x1fstable fixes\x1fbob\x1f1700000100 0\x1fTwo\nlines\x1e";
+        let revisions = parse_log(log).unwrap();
+        assert_eq!(revisions.len(), 2);
+        assert_eq!((revisions[0].parents.len(), revisions[0].offset), (0, -3600));
+        assert_eq!((revisions[1].parents.clone(), revisions[1].message.as_str()), (vec![0], "Two\nlines"));
+        assert!(parse_log("0\x1fbroken\x1e").is_err());
+
+        assert_eq!(branch_name("default"), "main");
+        assert_eq!(branch_name("stable fixes"), "stable-fixes");
+        assert_eq!(identity("bob"), "bob <>");
+        assert_eq!(file_mode("x"), 0o100755);
+    }
+}
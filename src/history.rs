@@ -1,69 +1,337 @@
+use std::collections::{BTreeSet, HashMap};
+use std::fmt::Write;
 use std::fs;
 use std::path::Path;
+use serde::Serialize;
+use time::format_description::well_known::{Rfc2822, Rfc3339};
+use time::macros::format_description;
+use time::{Date, OffsetDateTime};
 use crate::objects::{ObjectId, Commit, Directory, File};
+use crate::{merge, patch, refs, repo, vos};
+use crate::style::{self, Color};
+use tracing::{error, info, warn};
+
+/// A commit as printed by `orb history --json` and `orb show --json`. Fields are only
+/// ever added, so tools can rely on the existing ones.
+#[derive(Debug, Serialize)]
+pub struct CommitRecord {
+    pub id: ObjectId,
+    pub parents: Vec<ObjectId>,
+    pub tree: ObjectId,
+    pub author: String,
+    /// Seconds since the Unix epoch
+    pub timestamp: i64,
+    pub message: String,
+    /// `HEAD` and the full names of the refs pointing at the commit
+    pub refs: Vec<String>,
+}
+
+impl CommitRecord {
+    pub fn new(id: &str, commit: &Commit, refs: &HashMap<ObjectId, Vec<String>>) -> Self {
+        Self {
+            id: id.to_string(),
+            parents: commit.parents.clone(),
+            tree: commit.tree.clone(),
+            author: commit.author.clone(),
+            timestamp: commit.timestamp,
+            message: commit.message.clone(),
+            refs: refs.get(id).cloned().unwrap_or_default(),
+        }
+    }
+}
+
+/// A path changed by a commit, as listed in `ShowRecord`
+#[derive(Debug, Serialize)]
+pub struct FileChangeRecord {
+    pub path: String,
+    /// `added`, `modified`, or `deleted`
+    pub status: &'static str,
+    /// File object before the commit (null when added)
+    pub old_id: Option<ObjectId>,
+    /// File object after the commit (null when deleted)
+    pub new_id: Option<ObjectId>,
+}
+
+/// A commit with its changed paths, as printed by `orb show --json`
+#[derive(Debug, Serialize)]
+pub struct ShowRecord {
+    #[serde(flatten)]
+    pub commit: CommitRecord,
+    pub changes: Vec<FileChangeRecord>,
+}
 
 /// Displays the commit history by traversing the DAG backward from HEAD
-pub fn show_history() -> Result<(), Box<dyn std::error::Error>> {
-    println!("\n📚 Orbit History (orb history)\n");
+/// Which commits `orb history` shows; an empty filter shows all of them
+#[derive(Debug, Default)]
+pub struct HistoryFilter {
+    /// Text the author must contain, ignoring case
+    pub author: Option<String>,
+    /// Oldest commit time to show, in seconds since the Unix epoch
+    pub since: Option<i64>,
+    /// Newest commit time to show, in seconds since the Unix epoch
+    pub until: Option<i64>,
+    /// Files or directories the commit must have changed (against its first parent)
+    pub paths: Vec<String>,
+}
+
+impl HistoryFilter {
+    pub fn is_empty(&self) -> bool {
+        self.author.is_none() && self.since.is_none() && self.until.is_none() && self.paths.is_empty()
+    }
+
+    fn matches(&self, repository: &orbit_core::Repository, commit: &Commit) -> Result<bool, Box<dyn std::error::Error>> {
+        if self.author.as_ref().is_some_and(|author| !commit.author.to_lowercase().contains(&author.to_lowercase()))
+            || self.since.is_some_and(|since| commit.timestamp < since)
+            || self.until.is_some_and(|until| commit.timestamp > until)
+        {
+            return Ok(false);
+        }
+        if self.paths.is_empty() {
+            return Ok(true);
+        }
+        // Comparing trees is the expensive part, so it only runs for commits that passed the rest
+        let new_files = repository.tree_files(&commit.tree)?;
+        let old_files = match commit.parents.first() {
+            Some(parent) => repository.commit_files(parent)?,
+            None => merge::FileMap::new(),
+        };
+        let pathspecs: Vec<String> = self.paths.iter().map(|path| orbit_core::normalize_pathspec(path)).collect();
+        Ok(old_files
+            .keys()
+            .chain(new_files.keys())
+            .filter(|path| old_files.get(*path) != new_files.get(*path))
+            .any(|path| pathspecs.iter().any(|pathspec| orbit_core::matches_pathspec(path, pathspec))))
+    }
+}
+
+/// Parses a `--since` time: a date (`2024-05-01`, from its start), an RFC 3339 time,
+/// or an interval ago such as `2d` or `12h`
+pub fn parse_since(value: &str) -> Result<i64, String> {
+    parse_time(value, false)
+}
+
+/// Parses an `--until` time like `--since`, except that a date includes its whole day
+pub fn parse_until(value: &str) -> Result<i64, String> {
+    parse_time(value, true)
+}
+
+fn parse_time(value: &str, end_of_day: bool) -> Result<i64, String> {
+    if let Ok(date) = Date::parse(value, format_description!("[year]-[month]-[day]")) {
+        let start = date.midnight().assume_utc().unix_timestamp();
+        return Ok(if end_of_day { start + 24 * 60 * 60 - 1 } else { start });
+    }
+    if let Ok(time) = OffsetDateTime::parse(value, &Rfc3339) {
+        return Ok(time.unix_timestamp());
+    }
+    match value.ends_with(|c: char| c.is_ascii_alphabetic()).then(|| crate::transport::parse_interval(value)) {
+        Some(Ok(ago)) => Ok(OffsetDateTime::now_utc().unix_timestamp() - ago.as_secs() as i64),
+        _ => Err(format!("invalid time '{}' (expected e.g. 2024-05-01, 2024-05-01T12:00:00Z, or 7d)", value)),
+    }
+}
+
+pub fn show_history(json: bool, filter: &HistoryFilter) -> Result<(), Box<dyn std::error::Error>> {
+    // 1. Walk the DAG backward from HEAD, following first parents
+    let repository = crate::repo::open()?;
+    let all = repository.history(None)?;
+    let total = all.len();
+    let mut commits = Vec::new();
+    for (id, commit) in all {
+        if filter.matches(&repository, &commit)? {
+            commits.push((id, commit));
+        }
+    }
+    if json {
+        let refs = refs::by_commit();
+        let records: Vec<CommitRecord> = commits.iter().map(|(id, commit)| CommitRecord::new(id, commit, &refs)).collect();
+        println!("{}", serde_json::to_string_pretty(&records)?);
+        return Ok(());
+    }
     
-    // 1. Get the current HEAD commit ID
-    let head_commit_id = read_head_commit_id()?;
+    println!("{}", style::decorate("\n📚 Orbit History (orb history)\n"));
     
-    if head_commit_id.is_empty() {
-        println!("📝 No commits found (empty repository)");
+    if commits.is_empty() && total > 0 {
+        println!("{}", style::decorate(&format!("🔎 None of the {} commits match the filters", total)));
+        return Ok(());
+    }
+    if commits.is_empty() {
+        println!("{}", style::decorate("📝 No commits found (empty repository)"));
         println!("\nTo create your first commit, use: orb save -m \"Initial commit\"");
         return Ok(());
     }
 
-    // 2. Traverse the DAG backward from HEAD
-    let mut current_commit_id = head_commit_id;
-    let mut commit_count = 0;
-    
-    loop {
-        // Load the current commit
-        let commit = load_commit_object(&current_commit_id)?;
-        commit_count += 1;
-        
-        // Display commit information
-        let _short_id = &current_commit_id[0..7.min(current_commit_id.len())]; // TODO: Use for compact display
+    // 2. Display each commit
+    for (number, (commit_id, commit)) in commits.iter().enumerate() {
         let timestamp = format_timestamp(commit.timestamp);
         
-        println!("commit {} (#{}) 📝", current_commit_id, commit_count);
+        println!("commit {} (#{})", style::id(commit_id), number + 1);
         println!("Author: {}", commit.author);
         println!("Date:   {}", timestamp);
-        println!("");
+        println!();
         println!("    {}", commit.message);
-        println!("");
+        println!();
         
-        // Move to parent commit
-        if commit.parents.is_empty() {
-            // Reached the root commit
-            break;
-        } else if commit.parents.len() == 1 {
-            // Normal linear history
-            current_commit_id = commit.parents[0].clone();
-        } else {
-            // Merge commit (for future versions)
+        if commit.parents.len() > 1 {
             println!("    (Merge commit with {} parents)", commit.parents.len());
-            current_commit_id = commit.parents[0].clone(); // Follow first parent
         }
     }
     
-    println!("📊 Total commits: {}", commit_count);
+    match filter.is_empty() {
+        true => println!("{}", style::decorate(&format!("📊 Total commits: {}", commits.len()))),
+        false => println!("{}", style::decorate(&format!("📊 {} of {} commits match", commits.len(), total))),
+    }
+    Ok(())
+}
+
+/// Prints a commit with the refs pointing at it and its diff against its first parent
+pub fn show_commit(revision: &str, json: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let id = refs::resolve(revision)?;
+    let commit: Commit = vos::load(&id, "commit")?;
+    let old_files = match commit.parents.first() {
+        Some(parent) => merge::commit_files(parent)?,
+        None => merge::FileMap::new(),
+    };
+    let new_files = merge::commit_files(&id)?;
+    let paths: BTreeSet<&String> = old_files.keys().chain(new_files.keys()).collect();
+    let changed: Vec<&String> = paths.into_iter().filter(|path| old_files.get(*path) != new_files.get(*path)).collect();
+    let record = CommitRecord::new(&id, &commit, &refs::by_commit());
+
+    if json {
+        let changes: Vec<FileChangeRecord> = changed
+            .iter()
+            .map(|path| {
+                let (old, new) = (old_files.get(*path), new_files.get(*path));
+                FileChangeRecord {
+                    path: path.to_string(),
+                    status: match (old, new) {
+                        (None, _) => "added",
+                        (_, None) => "deleted",
+                        _ => "modified",
+                    },
+                    old_id: old.map(|(_, id)| id.clone()),
+                    new_id: new.map(|(_, id)| id.clone()),
+                }
+            })
+            .collect();
+        println!("{}", serde_json::to_string_pretty(&ShowRecord { commit: record, changes })?);
+        return Ok(());
+    }
+
+    match record.refs.is_empty() {
+        true => println!("commit {}", style::id(&id)),
+        false => println!("commit {} ({})", style::id(&id), style::paint(&record.refs.join(", "), Color::Cyan)),
+    }
+    if commit.parents.len() > 1 {
+        println!("Merge:  {}", commit.parents.iter().map(|parent| &parent[..7]).collect::<Vec<_>>().join(" "));
+    }
+    println!("Author: {}", commit.author);
+    println!("Date:   {}", OffsetDateTime::from_unix_timestamp(commit.timestamp)?.format(&Rfc2822)?);
+    println!();
+    for line in commit.message.trim_end().lines() {
+        match line.is_empty() {
+            true => println!(),
+            false => println!("    {}", line),
+        }
+    }
+    println!();
+    let mut diffs = String::new();
+    for path in changed {
+        patch::file_diff(path, old_files.get(path), new_files.get(path), &mut diffs)?;
+    }
+    print!("{}", style::diff(&diffs));
+    Ok(())
+}
+
+/// `orb diff`: the changes between two commits, or of the working tree against a
+/// commit (default: HEAD), as unified diffs, or as a diffstat with `stat`.
+///
+/// Leading `args` that name revisions (at most two, or one `A..B` range) pick what is
+/// compared; the rest, and `paths`, limit the diff to those files and directories.
+/// Untracked files are left out unless they are staged.
+pub fn show_diff(args: &[String], paths: &[String], stat: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let repository = repo::open()?;
+    let mut revisions = Vec::new();
+    let mut rest = args;
+    while let Some((arg, tail)) = rest.split_first().filter(|_| revisions.len() < 2) {
+        match arg.split_once("..").filter(|_| revisions.is_empty()) {
+            Some((from, to)) => {
+                revisions.push(refs::resolve(if from.is_empty() { "HEAD" } else { from })?);
+                revisions.push(refs::resolve(if to.is_empty() { "HEAD" } else { to })?);
+            }
+            None => match refs::resolve(arg) {
+                Ok(id) => revisions.push(id),
+                Err(_) => break,
+            },
+        }
+        rest = tail;
+    }
+    let paths: Vec<String> = rest.iter().chain(paths).cloned().collect();
+
+    let head_files = || -> Result<merge::FileMap, orbit_core::Error> {
+        match refs::head_commit() {
+            Some(head) => repository.commit_files(&head),
+            None => Ok(merge::FileMap::new()),
+        }
+    };
+    let (old_files, new_files) = match revisions.as_slice() {
+        [old, new] => repository.diff_trees(Some(&repository.load_commit(old)?.tree), Some(&repository.load_commit(new)?.tree))?,
+        [old] => (repository.commit_files(old)?, repo::worktree_files(&repository, head_files()?)?),
+        _ => {
+            let head_files = head_files()?;
+            (head_files.clone(), repo::worktree_files(&repository, head_files)?)
+        }
+    };
+    // A word that is neither a revision nor a path is most likely a mistyped revision
+    for arg in rest {
+        let pathspec = orbit_core::normalize_pathspec(arg);
+        let known = |files: &merge::FileMap| files.keys().any(|path| orbit_core::matches_pathspec(path, &pathspec));
+        if !repository.root().join(arg).exists() && !known(&old_files) && !known(&new_files) {
+            return Err(repo::RepoError::UnknownRevision(arg.clone()).into());
+        }
+    }
+    print_diff(&old_files, &new_files, &paths, stat)
+}
+
+/// Prints how `new_files` differs from `old_files` in the paths matching `paths` (all
+/// when empty): unified diffs, or a diffstat with `stat`
+fn print_diff(old_files: &merge::FileMap, new_files: &merge::FileMap, paths: &[String], stat: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let pathspecs: Vec<String> = paths.iter().map(|path| orbit_core::normalize_pathspec(path)).collect();
+    let changed: BTreeSet<&String> = old_files
+        .keys()
+        .chain(new_files.keys())
+        .filter(|path| old_files.get(*path) != new_files.get(*path))
+        .filter(|path| pathspecs.is_empty() || pathspecs.iter().any(|pathspec| orbit_core::matches_pathspec(path, pathspec)))
+        .collect();
+
+    let mut diffs = String::new();
+    let mut stats = Vec::new();
+    for path in changed {
+        let start = diffs.len();
+        let counts = patch::file_diff(path, old_files.get(path), new_files.get(path), &mut diffs)?;
+        // Binary literals are for applying patches, not for reading
+        if let Some(offset) = diffs[start..].find("GIT binary patch\n").filter(|_| counts.is_none()) {
+            diffs.truncate(start + offset);
+            writeln!(diffs, "Binary files a/{0} and b/{0} differ", path)?;
+        }
+        stats.push((path.as_str(), counts));
+    }
+    match stat {
+        true if !stats.is_empty() => print!("{}", patch::diffstat(&stats)),
+        true => {}
+        false => print!("{}", style::diff(&diffs)),
+    }
     Ok(())
 }
 
 /// Reverts files to their state in the HEAD commit
 pub fn revert_files(file_paths: Vec<String>) -> Result<(), Box<dyn std::error::Error>> {
-    println!("\n🔄 Orbit Revert (orb revert)\n");
+    repo::open_worktree()?;
+    info!("\n🔄 Orbit Revert (orb revert)\n");
     
     // 1. Get the current HEAD commit ID
-    let head_commit_id = read_head_commit_id()?;
-    
-    if head_commit_id.is_empty() {
-        println!("❌ No commits found - nothing to revert to");
+    let Some(head_commit_id) = refs::head_commit() else {
+        error!("❌ No commits found - nothing to revert to");
         return Ok(());
-    }
+    };
 
     // 2. Load the HEAD commit and its root directory
     let head_commit = load_commit_object(&head_commit_id)?;
@@ -85,7 +353,7 @@ pub fn revert_files(file_paths: Vec<String>) -> Result<(), Box<dyn std::error::E
     };
     
     if files_to_revert.is_empty() {
-        println!("⚠️  No files to revert (specified files not found in commit)");
+        warn!("⚠️  No files to revert (specified files not found in commit)");
         return Ok(());
     }
     
@@ -94,16 +362,16 @@ pub fn revert_files(file_paths: Vec<String>) -> Result<(), Box<dyn std::error::E
     for file_path in files_to_revert {
         match revert_single_file(&file_path, &committed_files) {
             Ok(_) => {
-                println!("✅ Reverted: {}", file_path);
+                info!("✅ Reverted: {}", file_path);
                 reverted_count += 1;
             },
             Err(e) => {
-                println!("❌ Failed to revert {}: {}", file_path, e);
+                error!("❌ Failed to revert {}: {}", file_path, e);
             }
         }
     }
     
-    println!("\n🎉 Successfully reverted {} file(s) to HEAD commit", reverted_count);
+    info!("\n🎉 Successfully reverted {} file(s) to HEAD commit", reverted_count);
     Ok(())
 }
 
@@ -140,14 +408,9 @@ fn load_file_object(file_id: &ObjectId) -> Result<File, Box<dyn std::error::Erro
     Ok(file_object)
 }
 
-/// Reassembles file content from its chunks (simplified for MVP)
+/// Reassembles file content from its chunks
 fn reassemble_file_content(file_object: &File) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
-    // For MVP v0.2, we'll use a simplified approach
-    // In the current implementation, the root_chunk_id represents the entire file
-    // In a full implementation, this would traverse the Merkle tree of chunks
-    
-    let chunk_data = load_object_data(&file_object.root_chunk_id)?;
-    Ok(chunk_data)
+    file_object.content(load_object_data)
 }
 
 /// Builds a map of all files in a directory tree
@@ -176,20 +439,6 @@ fn build_file_map(
 }
 
 /// Helper functions (reused from status.rs)
-fn read_head_commit_id() -> Result<ObjectId, Box<dyn std::error::Error>> {
-    let head_ref_path = Path::new(".orb").join("refs").join("heads").join("main");
-    
-    if !head_ref_path.exists() {
-        return Ok(String::new());
-    }
-    
-    let commit_id = fs::read_to_string(head_ref_path)?
-        .trim()
-        .to_string();
-    
-    Ok(commit_id)
-}
-
 fn load_commit_object(commit_id: &ObjectId) -> Result<Commit, Box<dyn std::error::Error>> {
     let object_data = load_object_data(commit_id)?;
     let commit: Commit = serde_json::from_slice(&object_data)?;
@@ -203,14 +452,7 @@ fn load_directory_object(dir_id: &ObjectId) -> Result<Directory, Box<dyn std::er
 }
 
 fn load_object_data(object_id: &ObjectId) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
-    let (prefix, suffix) = object_id.split_at(2);
-    let object_path = Path::new(".orb")
-        .join("objects")
-        .join(prefix)
-        .join(suffix);
-    
-    let data = fs::read(object_path)?;
-    Ok(data)
+    Ok(vos::load_object(object_id)?)
 }
 
 fn format_timestamp(timestamp: i64) -> String {
@@ -220,4 +462,42 @@ fn format_timestamp(timestamp: i64) -> String {
     
     // Simple formatting for MVP (in production, use chrono crate)
     format!("{:?}", system_time)
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_commit_record_fields() {
+        let commit = Commit {
+            tree: "t".to_string(),
+            parents: vec!["p".to_string()],
+            author: "Ada <ada@example.com>".to_string(),
+            timestamp: 1_700_000_000,
+            message: "Fix\n".to_string(),
+            signature: None,
+        };
+        let refs = HashMap::from([("c".to_string(), vec!["HEAD".to_string(), "refs/heads/main".to_string()])]);
+        let json = serde_json::to_value(CommitRecord::new("c", &commit, &refs)).unwrap();
+        assert_eq!(json, serde_json::json!({
+            "id": "c",
+            "parents": ["p"],
+            "tree": "t",
+            "author": "Ada <ada@example.com>",
+            "timestamp": 1_700_000_000,
+            "message": "Fix\n",
+            "refs": ["HEAD", "refs/heads/main"],
+        }));
+    }
+
+    #[test]
+    fn test_parse_history_times() {
+        assert_eq!(parse_since("2024-05-01"), Ok(1_714_521_600));
+        assert_eq!(parse_until("2024-05-01"), Ok(1_714_521_600 + 86_399));
+        assert_eq!(parse_since("2024-05-01T12:00:00+02:00"), Ok(1_714_557_600));
+        let week_ago = OffsetDateTime::now_utc().unix_timestamp() - 7 * 86_400;
+        assert!((parse_since("7d").unwrap() - week_ago).abs() <= 1);
+        assert!(parse_since("2024").is_err());
+        assert!(parse_since("yesterday").is_err());
+    }
+}
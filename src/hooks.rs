@@ -0,0 +1,89 @@
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use crate::refs::RefChange;
+use tracing::{info, warn};
+
+/// Written in place of a ref value that does not exist (a created or deleted ref)
+const NULL_ID: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// Runs `.orb/hooks/<name>` if it exists.
+///
+/// Like git's post-receive, the hook gets one `<old> <new> <ref>` line per changed
+/// ref on stdin; `env` is added to its environment. The operation has already
+/// happened, so a failing hook is reported but never undoes anything.
+pub fn run(name: &str, changes: &[RefChange], env: &[(&str, &str)]) {
+    let path = Path::new(".orb").join("hooks").join(name);
+    if !path.is_file() {
+        return;
+    }
+
+    info!("🪝 Running {} hook", name);
+    let child = Command::new(&path)
+        .envs(env.iter().copied())
+        .stdin(Stdio::piped())
+        .spawn();
+    let mut child = match child {
+        Ok(child) => child,
+        Err(e) => {
+            warn!("⚠️  Could not run {} hook ({}): {}", name, path.display(), e);
+            return;
+        }
+    };
+
+    if let Some(stdin) = child.stdin.take() {
+        feed(stdin, changes);
+    }
+
+    match child.wait() {
+        Ok(status) if status.success() => {}
+        Ok(status) => warn!("⚠️  {} hook exited with {}", name, status),
+        Err(e) => warn!("⚠️  {} hook failed: {}", name, e),
+    }
+}
+
+/// Runs a hosted repository's `<dir>/.orb/hooks/<name>` (pre-receive or post-receive) for
+/// `orb serve`, in `dir`, with the same stdin as `run`. The hook's combined output is
+/// returned; a hook that cannot start or exits non-zero yields an error carrying it, which
+/// the server sends back to the pushing client.
+pub fn run_hosted(dir: &Path, name: &str, changes: &[RefChange], env: &[(&str, &str)]) -> Result<String, String> {
+    let path = dir.join(".orb").join("hooks").join(name);
+    if !path.is_file() {
+        return Ok(String::new());
+    }
+
+    let mut child = Command::new(&path)
+        .current_dir(dir)
+        .envs(env.iter().copied())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("could not run the {} hook: {}", name, e))?;
+    if let Some(stdin) = child.stdin.take() {
+        feed(stdin, changes);
+    }
+    let output = child.wait_with_output().map_err(|e| format!("{} hook failed: {}", name, e))?;
+
+    let text = format!("{}{}", String::from_utf8_lossy(&output.stdout), String::from_utf8_lossy(&output.stderr));
+    let text = text.trim().to_string();
+    if output.status.success() {
+        Ok(text)
+    } else if text.is_empty() {
+        Err(format!("{} hook rejected the update ({})", name, output.status))
+    } else {
+        Err(format!("{} hook rejected the update: {}", name, text))
+    }
+}
+
+/// Writes one `<old> <new> <ref>` line per change and closes the hook's stdin
+fn feed(mut stdin: impl Write, changes: &[RefChange]) {
+    for change in changes {
+        let old = change.old.as_deref().unwrap_or(NULL_ID);
+        let new = change.new.as_deref().unwrap_or(NULL_ID);
+        // A hook that ignores its input may exit before reading it
+        if writeln!(stdin, "{} {} {}", old, new, change.name).is_err() {
+            break;
+        }
+    }
+}
@@ -0,0 +1,439 @@
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, SystemTime};
+use crate::access::Permission;
+use crate::maintenance::Report;
+use crate::object_store::{self, ObjectStore, StoredObject};
+use crate::objects::{Commit, Directory, File, ObjectId};
+use crate::policy::Policy;
+use crate::refs;
+use crate::vnp::{self, RepositoryInfo};
+use crate::webhooks::Webhook;
+use tracing::warn;
+
+pub use orbit_core::vos::is_object_id;
+
+/// Parents of each commit, as kept in `.orb/commit-graph.json`
+pub type CommitGraph = BTreeMap<ObjectId, Vec<ObjectId>>;
+
+/// A repository stored on the server. Unlike the working-copy helpers in `refs` and `vos`,
+/// every path is explicit, so one process can serve many repositories at once.
+#[derive(Debug, Clone)]
+pub struct HostedRepo {
+    /// The repository's `.orb` directory
+    orb: PathBuf,
+    /// Where its objects live: `.orb/objects`, or the backend it was created with
+    store: Arc<dyn ObjectStore>,
+    /// Parents by commit, from the commit-graph maintenance writes; read on first use
+    graph: Arc<OnceLock<HashMap<ObjectId, Vec<ObjectId>>>>,
+}
+
+impl HostedRepo {
+    /// Opens the repository whose `.orb` directory lives in `dir`, if there is one. A
+    /// repository whose object store cannot be opened is reported and treated as missing.
+    pub fn open(dir: &Path) -> Option<Self> {
+        let orb = dir.join(".orb");
+        if !orb.join("objects").is_dir() {
+            return None;
+        }
+        match object_store::open(&orb) {
+            Ok(store) => Some(HostedRepo { orb, store, graph: Arc::default() }),
+            Err(message) => {
+                warn!("⚠️  {}", message);
+                None
+            }
+        }
+    }
+
+    /// Opens the repository in `dir`, creating an empty one (HEAD on `main`) if needed,
+    /// with its objects in `backend` (see `object_store::init`)
+    pub fn open_or_init(dir: &Path, backend: &str) -> io::Result<Self> {
+        if let Some(repo) = Self::open(dir) {
+            return Ok(repo);
+        }
+        let orb = dir.join(".orb");
+        fs::create_dir_all(orb.join("refs").join("heads"))?;
+        fs::create_dir_all(orb.join("refs").join("tags"))?;
+        fs::write(orb.join("HEAD"), format!("ref: {}\n", refs::branch_ref(refs::DEFAULT_BRANCH)))?;
+        object_store::init(&orb, backend)?;
+        // The objects directory marks the repository as complete, so it comes last
+        fs::create_dir_all(orb.join("objects"))?;
+        Self::open(dir).ok_or_else(|| io::Error::other(format!("cannot open the object store of {}", dir.display())))
+    }
+
+    /// The working directory holding `.orb`, where server-side hooks run
+    pub fn dir(&self) -> &Path {
+        self.orb.parent().unwrap_or(Path::new("."))
+    }
+
+    pub fn store(&self) -> &dyn ObjectStore {
+        self.store.as_ref()
+    }
+
+    /// A stored object's content. Callers must check `is_object_id` first for IDs that came
+    /// off the wire.
+    pub fn read_object(&self, id: &str) -> io::Result<Vec<u8>> {
+        self.store.read(id)
+    }
+
+    /// A stored object parsed as a commit, tree, or file, if it is one
+    pub fn load<T: serde::de::DeserializeOwned>(&self, id: &str) -> Option<T> {
+        serde_json::from_slice(&self.read_object(id).ok()?).ok()
+    }
+
+    pub fn has_object(&self, id: &str) -> bool {
+        is_object_id(id) && self.store.size(id).is_some()
+    }
+
+    /// Sends a stored object as an ObjectHeader and its payload (an Error if it is
+    /// missing), streaming it from disk when the store is local
+    pub async fn send_object<W: tokio::io::AsyncWriteExt + Unpin>(&self, writer: &mut W, id: &str) -> io::Result<()> {
+        if let Some(path) = self.store.local_path(id) {
+            return vnp::send_stored_object(writer, id, &path).await;
+        }
+        match self.read_object(id) {
+            Ok(data) => vnp::send_object_bytes(writer, id, &data).await,
+            Err(_) => vnp::send_command(writer, vnp::VnpCommand::Error(format!("object not found: {}", id))).await,
+        }
+    }
+
+    /// Directory for objects received during a push that have not been accepted yet
+    pub fn quarantine_dir(&self, name: &str) -> PathBuf {
+        self.orb.join("objects").join("incoming").join(name)
+    }
+
+    /// Moves a fully received and verified object into the store
+    pub fn promote(&self, id: &str, received: &Path) -> io::Result<()> {
+        self.store.put_file(id, received)
+    }
+
+    pub fn load_commit(&self, id: &str) -> Option<Commit> {
+        self.load(id)
+    }
+
+    /// Every stored commit reachable from `tip`, including `tip`
+    pub fn ancestors(&self, tip: &str) -> HashSet<ObjectId> {
+        let mut seen = HashSet::new();
+        let mut queue = VecDeque::from([tip.to_string()]);
+        while let Some(id) = queue.pop_front() {
+            if !self.has_object(&id) || !seen.insert(id.clone()) {
+                continue;
+            }
+            if let Some(parents) = self.parents(&id) {
+                queue.extend(parents.into_iter().filter(|p| !seen.contains(p)));
+            }
+        }
+        seen
+    }
+
+    /// A commit's parents, from the commit-graph when it lists the commit
+    fn parents(&self, id: &str) -> Option<Vec<ObjectId>> {
+        let graph = self.graph.get_or_init(|| load_typed(&self.orb.join("commit-graph.json")).unwrap_or_default());
+        graph.get(id).cloned().or_else(|| self.load_commit(id).map(|commit| commit.parents))
+    }
+
+    pub fn save_commit_graph(&self, graph: &CommitGraph) -> io::Result<()> {
+        self.write_json("commit-graph.json", graph)
+    }
+
+    /// Outcome of the last maintenance run (`.orb/maintenance.json`)
+    pub fn last_maintenance(&self) -> Option<Report> {
+        load_typed(&self.orb.join("maintenance.json"))
+    }
+
+    pub fn save_maintenance(&self, report: &Report) -> io::Result<()> {
+        self.write_json("maintenance.json", report)
+    }
+
+    /// True if `ancestor` is reachable from `descendant` (a commit is its own ancestor)
+    pub fn is_ancestor(&self, ancestor: &str, descendant: &str) -> bool {
+        ancestor == descendant || self.ancestors(descendant).contains(ancestor)
+    }
+
+    /// Every object (trees, files, chunks) a commit's snapshot needs, excluding the commit
+    pub fn snapshot_objects(&self, commit_id: &str) -> HashSet<ObjectId> {
+        let mut found = HashSet::new();
+        let Some(commit) = self.load_commit(commit_id) else {
+            return found;
+        };
+        let mut trees = vec![commit.tree];
+        while let Some(tree_id) = trees.pop() {
+            if !found.insert(tree_id.clone()) {
+                continue;
+            }
+            let Some(directory) = self.load::<Directory>(&tree_id) else {
+                continue;
+            };
+            for entry in directory.entries {
+                if entry.mode == 0o040000 {
+                    trees.push(entry.id);
+                } else if found.insert(entry.id.clone()) {
+                    if let Some(file) = self.load::<File>(&entry.id) {
+                        found.extend(file.chunk_ids().iter().cloned());
+                    }
+                }
+            }
+        }
+        found
+    }
+
+    /// Size in bytes of a stored object (0 if it is missing)
+    pub fn object_size(&self, id: &str) -> u64 {
+        self.store.size(id).unwrap_or(0)
+    }
+
+    pub fn read_ref(&self, name: &str) -> Option<ObjectId> {
+        let id = fs::read_to_string(self.orb.join(name)).ok()?;
+        let id = id.trim();
+        (!id.is_empty()).then(|| id.to_string())
+    }
+
+    /// Points a ref at an object, replacing the file atomically so readers never see it empty
+    pub fn write_ref(&self, name: &str, id: &str) -> io::Result<()> {
+        let path = self.orb.join(name);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut temp = path.clone().into_os_string();
+        temp.push(".lock");
+        fs::write(&temp, id)?;
+        fs::rename(temp, path)
+    }
+
+    pub fn branches(&self) -> BTreeMap<String, ObjectId> {
+        self.list_refs("heads", refs::branch_ref)
+    }
+
+    pub fn tags(&self) -> BTreeMap<String, ObjectId> {
+        self.list_refs("tags", refs::tag_ref)
+    }
+
+    /// Tag objects of annotated tags, by tag name
+    pub fn annotations(&self) -> BTreeMap<String, ObjectId> {
+        self.list_refs("annotations", refs::annotation_ref)
+    }
+
+    fn list_refs(&self, kind: &str, to_ref: fn(&str) -> String) -> BTreeMap<String, ObjectId> {
+        let Ok(entries) = fs::read_dir(self.orb.join("refs").join(kind)) else {
+            return BTreeMap::new();
+        };
+        entries
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                let name = entry.file_name().to_string_lossy().to_string();
+                is_ref_name(&name).then(|| self.read_ref(&to_ref(&name)).map(|id| (name, id))).flatten()
+            })
+            .collect()
+    }
+
+    /// Collaborators the owner granted access to (`.orb/access.json`)
+    pub fn grants(&self) -> BTreeMap<String, Permission> {
+        load_typed(&self.orb.join("access.json")).unwrap_or_default()
+    }
+
+    pub fn save_grants(&self, grants: &BTreeMap<String, Permission>) -> io::Result<()> {
+        self.write_json("access.json", grants)
+    }
+
+    /// Built-in push rules (`.orb/policy.json`)
+    pub fn policy(&self) -> Policy {
+        load_typed(&self.orb.join("policy.json")).unwrap_or_default()
+    }
+
+    pub fn save_policy(&self, policy: &Policy) -> io::Result<()> {
+        self.write_json("policy.json", policy)
+    }
+
+    /// URLs notified after pushes (`.orb/webhooks.json`)
+    pub fn webhooks(&self) -> Vec<Webhook> {
+        load_typed(&self.orb.join("webhooks.json")).unwrap_or_default()
+    }
+
+    pub fn save_webhooks(&self, webhooks: &[Webhook]) -> io::Result<()> {
+        self.write_json("webhooks.json", &webhooks)
+    }
+
+    /// Replaces `.orb/<name>` through a lock file, so readers see the old or new content
+    fn write_json<T: serde::Serialize>(&self, name: &str, value: &T) -> io::Result<()> {
+        let temp = self.orb.join(format!("{}.lock", name));
+        fs::write(&temp, serde_json::to_vec_pretty(value)?)?;
+        fs::rename(temp, self.orb.join(name))
+    }
+
+    /// Bytes of stored objects, not counting pushes still in quarantine
+    pub fn usage(&self) -> u64 {
+        self.store.usage()
+    }
+
+    /// Every stored object, not counting pushes still in quarantine
+    pub fn objects(&self) -> io::Result<Vec<StoredObject>> {
+        self.store.list()
+    }
+
+    /// Number of stored objects, without collecting their IDs where the store can
+    pub fn object_count(&self) -> u64 {
+        self.store.count()
+    }
+
+    /// Removes quarantine directories and lock files older than `age`, left behind by
+    /// interrupted pushes and crashes. Returns how many were removed.
+    pub fn remove_stale(&self, age: Duration) -> u64 {
+        let is_old = |path: &Path| {
+            fs::metadata(path)
+                .and_then(|m| m.modified())
+                .is_ok_and(|modified| SystemTime::now().duration_since(modified).is_ok_and(|elapsed| elapsed > age))
+        };
+        let mut removed = 0;
+        if let Ok(entries) = fs::read_dir(self.orb.join("objects").join("incoming")) {
+            for path in entries.filter_map(|entry| entry.ok()).map(|entry| entry.path()) {
+                if is_old(&path) && fs::remove_dir_all(&path).is_ok() {
+                    removed += 1;
+                }
+            }
+        }
+        for dir in [self.orb.clone(), self.orb.join("refs").join("heads"), self.orb.join("refs").join("tags"), self.orb.join("refs").join("annotations")] {
+            let Ok(entries) = fs::read_dir(dir) else { continue };
+            for path in entries.filter_map(|entry| entry.ok()).map(|entry| entry.path()) {
+                if path.extension().is_some_and(|ext| ext == "lock") && is_old(&path) && fs::remove_file(&path).is_ok() {
+                    removed += 1;
+                }
+            }
+        }
+        removed
+    }
+
+    /// Branch HEAD points to
+    pub fn default_branch(&self) -> String {
+        fs::read_to_string(self.orb.join("HEAD"))
+            .ok()
+            .and_then(|head| head.trim().strip_prefix("ref: refs/heads/").map(str::to_string))
+            .unwrap_or_else(|| refs::DEFAULT_BRANCH.to_string())
+    }
+
+    /// Listing entry for RepositoryList
+    pub fn info(&self, name: &str) -> RepositoryInfo {
+        let default_branch = self.default_branch();
+        let last_updated = fs::metadata(self.orb.join("refs").join("heads"))
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|time| time.duration_since(std::time::UNIX_EPOCH).ok())
+            .map(|age| age.as_secs() as i64);
+        RepositoryInfo {
+            name: name.to_string(),
+            head: self.read_ref(&refs::branch_ref(&default_branch)),
+            default_branch: Some(default_branch),
+            size: Some(self.usage()),
+            last_updated,
+            ..RepositoryInfo::default()
+        }
+    }
+}
+
+/// Splits a hosted repository name, `<namespace>/<repo>`, where the namespace is the
+/// owner's email-based username
+pub fn split_name(name: &str) -> Result<(&str, &str), String> {
+    let invalid = || format!("invalid repository name '{}' (expected <email>/<repo>)", name);
+    let (namespace, repo) = name.split_once('/').ok_or_else(invalid)?;
+    let namespace_ok = namespace.contains('@')
+        && !namespace.starts_with(['@', '.'])
+        && namespace.chars().all(|c| c.is_alphanumeric() || "@.-_+".contains(c));
+    let repo_ok = !repo.is_empty()
+        && !repo.starts_with('.')
+        && repo.chars().all(|c| c.is_alphanumeric() || "-_.".contains(c));
+    if namespace_ok && repo_ok {
+        Ok((namespace, repo))
+    } else {
+        Err(invalid())
+    }
+}
+
+/// Directory of a hosted repository: `<root>/<namespace>/<repo>`
+pub fn repo_dir(root: &Path, name: &str) -> Result<PathBuf, String> {
+    let (namespace, repo) = split_name(name)?;
+    Ok(root.join(namespace).join(repo))
+}
+
+/// Names of every repository under `root`, sorted
+pub fn list_repositories(root: &Path) -> Vec<String> {
+    let subdirs = |dir: &Path| -> Vec<String> {
+        fs::read_dir(dir)
+            .map(|entries| {
+                entries
+                    .filter_map(|entry| entry.ok())
+                    .filter(|entry| entry.path().is_dir())
+                    .map(|entry| entry.file_name().to_string_lossy().to_string())
+                    .collect()
+            })
+            .unwrap_or_default()
+    };
+    let mut names: Vec<String> = subdirs(root)
+        .into_iter()
+        .flat_map(|namespace| {
+            let namespace_dir = root.join(&namespace);
+            subdirs(&namespace_dir)
+                .into_iter()
+                .filter(|repo| HostedRepo::open(&namespace_dir.join(repo)).is_some())
+                .map(move |repo| format!("{}/{}", namespace, repo))
+                .collect::<Vec<_>>()
+        })
+        .filter(|name| split_name(name).is_ok())
+        .collect();
+    names.sort();
+    names
+}
+
+/// Bytes stored by every repository in a namespace
+pub fn namespace_usage(root: &Path, namespace: &str) -> u64 {
+    list_repositories(root)
+        .iter()
+        .filter(|name| split_name(name).is_ok_and(|(owner, _)| owner == namespace))
+        .filter_map(|name| HostedRepo::open(&repo_dir(root, name).ok()?))
+        .map(|repo| repo.usage())
+        .sum()
+}
+
+/// True for a branch or tag name that is safe to use as a file name under `.orb/refs`
+pub fn is_ref_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with('.')
+        && !name.ends_with(".lock")
+        && name.chars().all(|c| c.is_alphanumeric() || "-_.+@".contains(c))
+}
+
+pub fn load_typed<T: serde::de::DeserializeOwned>(path: &Path) -> Option<T> {
+    serde_json::from_slice(&fs::read(path).ok()?).ok()
+}
+
+pub fn dir_size(dir: &Path) -> u64 {
+    let Ok(entries) = fs::read_dir(dir) else { return 0 };
+    entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| match entry.metadata() {
+            Ok(m) if m.is_dir() => dir_size(&entry.path()),
+            Ok(m) => m.len(),
+            Err(_) => 0,
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wire_names_cannot_escape_the_repository() {
+        assert!(is_object_id(&"ab".repeat(32)));
+        assert!(!is_object_id("../../../../etc/passwd"));
+        assert!(!is_object_id(&"AB".repeat(32)));
+        assert!(is_ref_name("release-1.2"));
+        assert!(!is_ref_name("../HEAD"));
+        assert!(!is_ref_name("main.lock"));
+        assert!(!is_ref_name(".hidden"));
+        assert_eq!(split_name("alice@example.com/web"), Ok(("alice@example.com", "web")));
+        assert!(split_name("alice@example.com/../bob@example.com").is_err());
+        assert!(split_name("alice/web").is_err());
+    }
+}
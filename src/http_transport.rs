@@ -0,0 +1,190 @@
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use futures::{Sink, Stream};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio_tungstenite::tungstenite::{self, client::IntoClientRequest, Message};
+use tokio_tungstenite::tungstenite::handshake::server::{ErrorResponse, Request, Response};
+use crate::client_tls::OrbitUrl;
+use crate::transport::{VnpReader, VnpWriter};
+
+/// Path servers accept the VNP WebSocket upgrade on
+pub const VNP_PATH: &str = "/vnp";
+/// Largest payload sent in one WebSocket message
+const MAX_MESSAGE: usize = 1024 * 1024;
+
+/// Upgrades an HTTP(S) connection to a WebSocket on `/vnp` and returns it as a byte stream,
+/// so `https://` remotes work through firewalls that only allow web traffic on 443
+pub async fn connect<S>(stream: S, url: &OrbitUrl) -> Result<(VnpReader, VnpWriter), Box<dyn std::error::Error>>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let scheme = if url.use_tls { "wss" } else { "ws" };
+    let request = format!("{}://{}:{}{}", scheme, url.url_host(), url.port, VNP_PATH).into_client_request()?;
+    let (socket, _) = tokio_tungstenite::client_async(request, stream).await.map_err(|e| -> Box<dyn std::error::Error> {
+        match e {
+            tungstenite::Error::Io(e) => e.into(),
+            tungstenite::Error::Http(response) => format!(
+                "{} refused the VNP upgrade on {} (HTTP {}); is it an Orbit server?",
+                url.host, VNP_PATH, response.status()
+            ).into(),
+            e => format!("WebSocket handshake with {} failed: {}", url.host, e).into(),
+        }
+    })?;
+
+    let (reader, writer) = tokio::io::split(WebSocketBytes::new(socket));
+    Ok((Box::new(reader), Box::new(writer)))
+}
+
+/// Accepts a client's WebSocket upgrade on `/vnp` (the server side of `connect`); other
+/// paths are answered with 404
+pub async fn accept<S>(stream: S) -> io::Result<(VnpReader, VnpWriter)>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    // The callback signature (and its large error type) is fixed by tungstenite
+    #[allow(clippy::result_large_err)]
+    let only_vnp_path = |request: &Request, response: Response| {
+        if request.uri().path() == VNP_PATH {
+            Ok(response)
+        } else {
+            let mut refusal = ErrorResponse::new(Some(format!("Orbit serves VNP on {}\n", VNP_PATH)));
+            *refusal.status_mut() = tungstenite::http::StatusCode::NOT_FOUND;
+            Err(refusal)
+        }
+    };
+    let socket = tokio_tungstenite::accept_hdr_async(stream, only_vnp_path).await.map_err(to_io_error)?;
+    let (reader, writer) = tokio::io::split(WebSocketBytes::new(socket));
+    Ok((Box::new(reader), Box::new(writer)))
+}
+
+/// Presents a WebSocket as a byte stream: each flush sends the buffered bytes as one
+/// binary message, and received binary messages are read back to back
+struct WebSocketBytes<S> {
+    socket: tokio_tungstenite::WebSocketStream<S>,
+    /// Bytes written since the last flush
+    outgoing: Vec<u8>,
+    /// Received payload not yet returned to the caller
+    incoming: Vec<u8>,
+    incoming_pos: usize,
+}
+
+impl<S> WebSocketBytes<S> {
+    fn new(socket: tokio_tungstenite::WebSocketStream<S>) -> Self {
+        Self { socket, outgoing: Vec::new(), incoming: Vec::new(), incoming_pos: 0 }
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> WebSocketBytes<S> {
+    /// Hands the buffered bytes to the WebSocket as one message
+    fn poll_send_outgoing(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        if self.outgoing.is_empty() {
+            return Poll::Ready(Ok(()));
+        }
+        match Pin::new(&mut self.socket).poll_ready(cx) {
+            Poll::Ready(Ok(())) => {}
+            Poll::Ready(Err(e)) => return Poll::Ready(Err(to_io_error(e))),
+            Poll::Pending => return Poll::Pending,
+        }
+        let message = Message::Binary(std::mem::take(&mut self.outgoing));
+        Poll::Ready(Pin::new(&mut self.socket).start_send(message).map_err(to_io_error))
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> AsyncRead for WebSocketBytes<S> {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        let this = &mut *self;
+        loop {
+            if this.incoming_pos < this.incoming.len() {
+                let n = buf.remaining().min(this.incoming.len() - this.incoming_pos);
+                buf.put_slice(&this.incoming[this.incoming_pos..this.incoming_pos + n]);
+                this.incoming_pos += n;
+                return Poll::Ready(Ok(()));
+            }
+
+            match Pin::new(&mut this.socket).poll_next(cx) {
+                Poll::Ready(Some(Ok(Message::Binary(data)))) => {
+                    this.incoming = data;
+                    this.incoming_pos = 0;
+                }
+                // Pings are answered by tungstenite itself
+                Poll::Ready(Some(Ok(Message::Ping(_) | Message::Pong(_)))) => {}
+                Poll::Ready(Some(Ok(Message::Text(_) | Message::Frame(_)))) => {
+                    return Poll::Ready(Err(io::Error::new(io::ErrorKind::InvalidData, "unexpected text message on VNP WebSocket")));
+                }
+                Poll::Ready(Some(Ok(Message::Close(_)))) | Poll::Ready(None) => return Poll::Ready(Ok(())),
+                Poll::Ready(Some(Err(tungstenite::Error::ConnectionClosed))) => return Poll::Ready(Ok(())),
+                Poll::Ready(Some(Err(e))) => return Poll::Ready(Err(to_io_error(e))),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> AsyncWrite for WebSocketBytes<S> {
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        let this = &mut *self;
+        if this.outgoing.len() >= MAX_MESSAGE {
+            match this.poll_send_outgoing(cx) {
+                Poll::Ready(Ok(())) => {}
+                other => return other.map_ok(|()| 0),
+            }
+        }
+        let n = buf.len().min(MAX_MESSAGE - this.outgoing.len());
+        this.outgoing.extend_from_slice(&buf[..n]);
+        Poll::Ready(Ok(n))
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = &mut *self;
+        match this.poll_send_outgoing(cx) {
+            Poll::Ready(Ok(())) => Pin::new(&mut this.socket).poll_flush(cx).map_err(to_io_error),
+            other => other,
+        }
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.as_mut().poll_flush(cx) {
+            Poll::Ready(Ok(())) => Pin::new(&mut self.socket).poll_close(cx).map_err(to_io_error),
+            other => other,
+        }
+    }
+}
+
+fn to_io_error(error: tungstenite::Error) -> io::Error {
+    match error {
+        tungstenite::Error::Io(e) => e,
+        tungstenite::Error::ConnectionClosed | tungstenite::Error::AlreadyClosed => io::ErrorKind::BrokenPipe.into(),
+        // The peer went away without a close frame, which is an ordinary disconnect for VNP
+        tungstenite::Error::Protocol(tungstenite::error::ProtocolError::ResetWithoutClosingHandshake) => {
+            io::ErrorKind::UnexpectedEof.into()
+        }
+        e => io::Error::new(io::ErrorKind::InvalidData, e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vnp::{self, VnpCommand};
+
+    #[tokio::test]
+    async fn test_vnp_frames_cross_websocket() {
+        let (client, server) = tokio::io::duplex(1 << 20);
+        let server = tokio::spawn(async move {
+            let socket = tokio_tungstenite::accept_async(server).await.unwrap();
+            let (mut reader, mut writer) = tokio::io::split(WebSocketBytes::new(socket));
+            let command = vnp::recv_command(&mut reader).await.unwrap();
+            vnp::send_command(&mut writer, command).await.unwrap();
+        });
+
+        let url = OrbitUrl::parse("http://example.com/team/repo").unwrap();
+        let (mut reader, mut writer) = connect(client, &url).await.unwrap();
+        vnp::send_command(&mut writer, VnpCommand::ObjectData(vec![3u8; 3 * MAX_MESSAGE])).await.unwrap();
+        match vnp::recv_command(&mut reader).await.unwrap() {
+            VnpCommand::ObjectData(data) => assert_eq!(data, vec![3u8; 3 * MAX_MESSAGE]),
+            other => panic!("unexpected echo: {:?}", other),
+        }
+        server.await.unwrap();
+    }
+}
@@ -0,0 +1,228 @@
+use std::fs;
+use std::io::Read;
+use std::path::{Component, Path};
+use std::time::{SystemTime, UNIX_EPOCH};
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+use crate::merge::{self, FileMap};
+use crate::objects::Commit;
+use crate::vos::{self, ChunkStats};
+use crate::{ancestry, fetch, refs, repo};
+use tracing::info;
+
+/// What `orb import` records on each snapshot's commit
+#[derive(Debug, Default)]
+pub struct SnapshotOptions {
+    /// Commit message; defaults to "Import <snapshot name>"
+    pub message: Option<String>,
+    /// Commit date for every snapshot instead of the one found in its name
+    pub date: Option<String>,
+    /// Branch to commit to instead of the current one
+    pub branch: Option<String>,
+}
+
+/// Commits each snapshot (a directory, or a `.tar`, `.tar.gz` or `.tgz` tarball) in
+/// order on top of the branch, each one replacing the whole tree of the previous
+pub fn import_snapshots(sources: &[impl AsRef<Path>], options: &SnapshotOptions) -> Result<(), Box<dyn std::error::Error>> {
+    if !Path::new(".orb").is_dir() {
+        return Err("not an Orbit repository (run `orb init` first)".into());
+    }
+    let branch = options.branch.clone().unwrap_or_else(refs::current_branch);
+    let fixed_date = options.date.as_deref().map(parse_date).transpose()?;
+    let mut tip = refs::read_ref(&refs::branch_ref(&branch));
+    let mut tree = tip.as_deref().and_then(ancestry::load_commit).map(|commit| commit.tree);
+    let mut stats = ChunkStats::default();
+    let mut imported = 0;
+
+    for source in sources {
+        let source = source.as_ref();
+        let name = snapshot_name(source);
+        let files = if source.is_dir() {
+            let mut files = FileMap::new();
+            read_directory(source, "", &mut files, &mut stats)?;
+            files
+        } else if source.is_file() {
+            read_tarball(source, &mut stats).map_err(|e| format!("{}: {}", source.display(), e))?
+        } else {
+            return Err(format!("{} is neither a directory nor a tarball", source.display()).into());
+        };
+
+        let snapshot_tree = merge::build_tree(&files)?;
+        if tree.as_ref() == Some(&snapshot_tree) {
+            info!("⏭️  {} is identical to the previous snapshot; skipped", name);
+            continue;
+        }
+        let timestamp = match fixed_date {
+            Some(timestamp) => timestamp,
+            None => date_in_name(&name).or_else(|| modified(source)).unwrap_or_else(now),
+        };
+        let commit = Commit {
+            tree: snapshot_tree.clone(),
+            parents: tip.iter().cloned().collect(),
+            author: repo::DEFAULT_AUTHOR.to_string(),
+            timestamp,
+            message: options.message.clone().unwrap_or_else(|| format!("Import {}", name)),
+            signature: None,
+        };
+        let commit_id = vos::save_object(&serde_json::to_vec(&commit)?);
+        info!("📸 {} → {} ({} files)", name, &commit_id[..7], files.len());
+        refs::write_ref(&refs::branch_ref(&branch), &commit_id)?;
+        tip = Some(commit_id);
+        tree = Some(snapshot_tree);
+        imported += 1;
+    }
+
+    fetch::report_deduplication(&stats);
+    info!("✅ Imported {} snapshots onto {}", imported, branch);
+    if imported > 0 && branch == refs::current_branch() {
+        info!("💡 Run `orb checkout` to update the working directory");
+    }
+    Ok(())
+}
+
+/// Saves a directory's files, skipping `.orb` directories
+fn read_directory(path: &Path, prefix: &str, files: &mut FileMap, stats: &mut ChunkStats) -> Result<(), Box<dyn std::error::Error>> {
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        if name == ".orb" {
+            continue;
+        }
+        let full_path = if prefix.is_empty() { name } else { format!("{}/{}", prefix, name) };
+        let metadata = fs::symlink_metadata(entry.path())?;
+        if metadata.file_type().is_symlink() {
+            let target = fs::read_link(entry.path())?.to_string_lossy().into_owned();
+            files.insert(full_path, (0o120000, vos::save_stream(target.as_bytes(), stats)?.0));
+        } else if metadata.is_dir() {
+            read_directory(&entry.path(), &full_path, files, stats)?;
+        } else if metadata.is_file() {
+            let (file_id, _) = vos::save_stream(fs::File::open(entry.path())?, stats)?;
+            files.insert(full_path, (repo::file_mode(&metadata), file_id));
+        }
+    }
+    Ok(())
+}
+
+/// Saves a tarball's files. A directory holding everything else (as in
+/// `backup-2024-01-31/...`) is dropped from the paths.
+fn read_tarball(path: &Path, stats: &mut ChunkStats) -> Result<FileMap, Box<dyn std::error::Error>> {
+    let file = fs::File::open(path)?;
+    let name = path.to_string_lossy();
+    let reader: Box<dyn Read> = if name.ends_with(".gz") || name.ends_with(".tgz") {
+        Box::new(flate2::read::GzDecoder::new(file))
+    } else {
+        Box::new(file)
+    };
+    let mut files = FileMap::new();
+    let mut archive = tar::Archive::new(reader);
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let Some(entry_path) = archive_path(&entry.path()?) else { continue };
+        let kind = entry.header().entry_type();
+        if kind.is_file() {
+            let mode = if entry.header().mode()? & 0o111 != 0 { 0o100755 } else { 0o100644 };
+            let (file_id, _) = vos::save_stream(&mut entry, stats)?;
+            files.insert(entry_path, (mode, file_id));
+        } else if kind.is_symlink() {
+            let target = entry.link_name()?.map(|target| target.to_string_lossy().into_owned()).unwrap_or_default();
+            files.insert(entry_path, (0o120000, vos::save_stream(target.as_bytes(), stats)?.0));
+        } else if kind.is_hard_link() {
+            // A hard link repeats a file stored earlier in the archive
+            let linked = entry.link_name()?.and_then(|target| archive_path(&target));
+            if let Some(file) = linked.and_then(|target| files.get(&target).cloned()) {
+                files.insert(entry_path, file);
+            }
+        }
+    }
+    Ok(strip_common_directory(files))
+}
+
+/// An archive member's path with `/` separators, or None for paths escaping the
+/// snapshot or inside `.orb`
+fn archive_path(path: &Path) -> Option<String> {
+    let mut parts = Vec::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(part) if part == ".orb" => return None,
+            Component::Normal(part) => parts.push(part.to_string_lossy().into_owned()),
+            Component::CurDir => {}
+            _ => return None,
+        }
+    }
+    (!parts.is_empty()).then(|| parts.join("/"))
+}
+
+fn strip_common_directory(files: FileMap) -> FileMap {
+    let first = files.keys().next().and_then(|path| path.split_once('/')).map(|(dir, _)| format!("{}/", dir));
+    match first {
+        Some(dir) if files.keys().all(|path| path.starts_with(&dir)) => {
+            files.into_iter().map(|(path, file)| (path[dir.len()..].to_string(), file)).collect()
+        }
+        _ => files,
+    }
+}
+
+/// A snapshot's name: its file or directory name without a tarball extension
+fn snapshot_name(source: &Path) -> String {
+    let name = source.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_else(|| source.display().to_string());
+    [".tar.gz", ".tgz", ".tar"]
+        .iter()
+        .find_map(|extension| name.strip_suffix(extension))
+        .map(str::to_string)
+        .unwrap_or(name)
+}
+
+/// Midnight UTC of the first `YYYY-MM-DD` date in a name
+fn date_in_name(name: &str) -> Option<i64> {
+    let bytes = name.as_bytes();
+    (0..bytes.len().saturating_sub(9)).find_map(|start| {
+        let candidate = name.get(start..start + 10)?;
+        let shape_ok = candidate.bytes().enumerate().all(|(i, c)| if i == 4 || i == 7 { c == b'-' } else { c.is_ascii_digit() });
+        shape_ok.then(|| parse_date(candidate).ok()).flatten()
+    })
+}
+
+/// Parses `--date`: `YYYY-MM-DD` (midnight UTC), an RFC 3339 time, or Unix seconds
+fn parse_date(text: &str) -> Result<i64, String> {
+    if let Ok(seconds) = text.parse::<i64>() {
+        return Ok(seconds);
+    }
+    if let Ok(time) = OffsetDateTime::parse(text, &Rfc3339) {
+        return Ok(time.unix_timestamp());
+    }
+    let date = || {
+        let mut parts = text.splitn(3, '-');
+        let year = parts.next()?.parse().ok()?;
+        let month = time::Month::try_from(parts.next()?.parse::<u8>().ok()?).ok()?;
+        time::Date::from_calendar_date(year, month, parts.next()?.parse().ok()?).ok()
+    };
+    date()
+        .map(|date| date.midnight().assume_utc().unix_timestamp())
+        .ok_or_else(|| format!("invalid date '{}' (use YYYY-MM-DD, an RFC 3339 time, or Unix seconds)", text))
+}
+
+fn modified(path: &Path) -> Option<i64> {
+    let modified = fs::metadata(path).ok()?.modified().ok()?;
+    Some(modified.duration_since(UNIX_EPOCH).ok()?.as_secs() as i64)
+}
+
+fn now() -> i64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot_dates_and_names() {
+        assert_eq!(snapshot_name(Path::new("/backups/site-2024-01-31.tar.gz")), "site-2024-01-31");
+        assert_eq!(date_in_name("site-2024-01-31"), Some(1706659200));
+        assert_eq!(date_in_name("site-2024-13-31"), None);
+        assert_eq!(parse_date("2024-01-31T12:00:00Z"), Ok(1706702400));
+        assert!(parse_date("yesterday").is_err());
+
+        let files: FileMap = [("site/a", "1"), ("site/b/c", "2")].iter().map(|(p, id)| (p.to_string(), (0o100644, id.to_string()))).collect();
+        assert_eq!(strip_common_directory(files).keys().collect::<Vec<_>>(), ["a", "b/c"]);
+    }
+}
@@ -0,0 +1,82 @@
+use std::fs;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use serde::{Deserialize, Serialize};
+use crate::objects::ObjectId;
+
+const JOURNAL_PATH: &str = ".orb/sync-journal";
+
+/// Resumable-sync journal stored in `.orb/sync-journal`.
+///
+/// Records the commits whose object graphs are being downloaded. The normal
+/// download walk stops at any tree that already exists locally, so a graph
+/// interrupted halfway would otherwise never be completed; commits listed
+/// here are re-walked in full on the next sync.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SyncJournal {
+    /// Repository being synchronized (if one was selected)
+    pub repository: Option<String>,
+    /// Commits whose object graphs are not yet known to be complete
+    pub pending_commits: Vec<ObjectId>,
+    /// Set when the user cancelled the sync (seconds since UNIX epoch)
+    pub interrupted_at: Option<i64>,
+}
+
+impl SyncJournal {
+    /// Loads the journal, returning None if no sync is in progress
+    pub fn load() -> Option<Self> {
+        let data = fs::read_to_string(JOURNAL_PATH).ok()?;
+        serde_json::from_str(&data).ok()
+    }
+
+    /// Records that the graphs of `commits` are about to be downloaded
+    pub fn begin(repository: Option<&str>, commits: &[ObjectId]) -> Result<(), std::io::Error> {
+        let mut journal = Self::load().unwrap_or_default();
+        journal.repository = repository.map(str::to_string);
+        for commit in commits {
+            if !journal.pending_commits.contains(commit) {
+                journal.pending_commits.push(commit.clone());
+            }
+        }
+        journal.save()
+    }
+
+    /// Marks a commit's graph as fully downloaded, removing the journal once nothing is pending
+    pub fn complete(commit_id: &str) -> Result<(), std::io::Error> {
+        let Some(mut journal) = Self::load() else {
+            return Ok(());
+        };
+        journal.pending_commits.retain(|c| c != commit_id);
+        if journal.pending_commits.is_empty() {
+            Self::clear()
+        } else {
+            journal.save()
+        }
+    }
+
+    /// Flushes the journal with an interruption marker so the next sync can resume
+    pub fn mark_interrupted() -> Result<(), std::io::Error> {
+        let Some(mut journal) = Self::load() else {
+            return Ok(());
+        };
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or(0);
+        journal.interrupted_at = Some(now);
+        journal.save()
+    }
+
+    /// Removes the journal after a successful sync
+    pub fn clear() -> Result<(), std::io::Error> {
+        if Path::new(JOURNAL_PATH).exists() {
+            fs::remove_file(JOURNAL_PATH)?;
+        }
+        Ok(())
+    }
+
+    fn save(&self) -> Result<(), std::io::Error> {
+        if !Path::new(".orb").exists() {
+            return Ok(()); // Not inside a repository; nothing to resume later
+        }
+        let data = serde_json::to_string_pretty(self)?;
+        fs::write(JOURNAL_PATH, data)
+    }
+}
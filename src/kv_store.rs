@@ -0,0 +1,213 @@
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use crate::object_store::{ObjectStore, StoredObject};
+
+/// How long to wait for the key-value server before giving up on a command
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Objects in a key-value server speaking the Redis protocol (Redis, Valkey, KeyDB, ...):
+/// `redis://[:<password>@]<host>[:<port>][/<db>][?prefix=<prefix>]`. Each object is the
+/// key `<prefix>:<location>:<id>`; a hash beside them records when each was stored.
+#[derive(Debug)]
+pub struct KvStore {
+    address: String,
+    password: Option<String>,
+    db: u32,
+    /// Key prefix of the repository's objects, ending in `:`
+    prefix: String,
+    /// One connection per store, opened on first use and again after errors
+    connection: Mutex<Option<BufReader<TcpStream>>>,
+}
+
+#[derive(Debug)]
+enum Reply {
+    Status,
+    Integer(i64),
+    Bulk(Option<Vec<u8>>),
+    Array(Vec<Reply>),
+}
+
+impl KvStore {
+    pub fn open(url: &str, location: &str) -> Result<Self, String> {
+        let parsed = url::Url::parse(url).map_err(|e| format!("invalid storage URL '{}': {}", url, e))?;
+        let host = parsed.host_str().filter(|host| !host.is_empty()).ok_or_else(|| format!("{} names no host", url))?;
+        let db = match parsed.path().trim_matches('/') {
+            "" => 0,
+            db => db.parse().map_err(|_| format!("{}: invalid database number '{}'", url, db))?,
+        };
+        let base = parsed.query_pairs().find(|(key, _)| key == "prefix").map_or("orbit".to_string(), |(_, value)| value.to_string());
+        let prefix = [base.as_str(), location].iter().filter(|part| !part.is_empty()).map(|part| format!("{}:", part)).collect();
+        Ok(KvStore {
+            address: format!("{}:{}", host, parsed.port().unwrap_or(6379)),
+            password: parsed.password().map(str::to_string),
+            db,
+            prefix,
+            connection: Mutex::new(None),
+        })
+    }
+
+    /// Sends a command and reads its reply, reconnecting once if the connection broke
+    fn command(&self, args: &[&[u8]]) -> io::Result<Reply> {
+        let mut connection = self.connection.lock().unwrap();
+        for attempt in 0..2 {
+            if connection.is_none() {
+                *connection = Some(self.connect()?);
+            }
+            let stream = connection.as_mut().expect("connected above");
+            match send(stream, args).and_then(|()| receive(stream)) {
+                Ok(reply) => return Ok(reply),
+                Err(e) if attempt == 0 && e.kind() != io::ErrorKind::InvalidData => *connection = None,
+                Err(e) => {
+                    *connection = None;
+                    return Err(e);
+                }
+            }
+        }
+        unreachable!("the second attempt returns")
+    }
+
+    fn connect(&self) -> io::Result<BufReader<TcpStream>> {
+        let stream = TcpStream::connect(&self.address)?;
+        stream.set_read_timeout(Some(TIMEOUT))?;
+        stream.set_write_timeout(Some(TIMEOUT))?;
+        let mut stream = BufReader::new(stream);
+        if let Some(password) = &self.password {
+            send(&mut stream, &[b"AUTH", password.as_bytes()])?;
+            receive(&mut stream)?;
+        }
+        if self.db != 0 {
+            send(&mut stream, &[b"SELECT", self.db.to_string().as_bytes()])?;
+            receive(&mut stream)?;
+        }
+        Ok(stream)
+    }
+
+    fn key(&self, id: &str) -> Vec<u8> {
+        format!("{}{}", self.prefix, id).into_bytes()
+    }
+
+    /// The hash of storage times, `<prefix>:<location>:stored`
+    fn times(&self) -> Vec<u8> {
+        format!("{}stored", self.prefix).into_bytes()
+    }
+}
+
+impl ObjectStore for KvStore {
+    fn size(&self, id: &str) -> Option<u64> {
+        // STRLEN is 0 for a missing key, so an empty object needs EXISTS to tell them apart
+        match self.command(&[b"STRLEN", &self.key(id)]).ok()? {
+            Reply::Integer(0) => match self.command(&[b"EXISTS", &self.key(id)]).ok()? {
+                Reply::Integer(1) => Some(0),
+                _ => None,
+            },
+            Reply::Integer(length) => Some(length as u64),
+            _ => None,
+        }
+    }
+
+    fn read(&self, id: &str) -> io::Result<Vec<u8>> {
+        match self.command(&[b"GET", &self.key(id)])? {
+            Reply::Bulk(Some(data)) => Ok(data),
+            Reply::Bulk(None) => Err(io::Error::new(io::ErrorKind::NotFound, format!("object {} not found", id))),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    fn write(&self, id: &str, data: &[u8]) -> io::Result<()> {
+        self.command(&[b"SET", &self.key(id), data])?;
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        self.command(&[b"HSET", &self.times(), id.as_bytes(), now.to_string().as_bytes()])?;
+        Ok(())
+    }
+
+    fn delete(&self, id: &str) -> io::Result<()> {
+        self.command(&[b"DEL", &self.key(id)])?;
+        self.command(&[b"HDEL", &self.times(), id.as_bytes()])?;
+        Ok(())
+    }
+
+    fn list(&self) -> io::Result<Vec<StoredObject>> {
+        let times: HashMap<String, u64> = match self.command(&[b"HGETALL", &self.times()])? {
+            Reply::Array(items) => items
+                .chunks(2)
+                .filter_map(|pair| match pair {
+                    [Reply::Bulk(Some(id)), Reply::Bulk(Some(time))] => {
+                        Some((String::from_utf8_lossy(id).to_string(), String::from_utf8_lossy(time).parse().ok()?))
+                    }
+                    _ => None,
+                })
+                .collect(),
+            other => return Err(unexpected(other)),
+        };
+        let mut objects = Vec::new();
+        for id in times.keys().filter(|id| crate::hosted::is_object_id(id)) {
+            // The times hash is written after the object, so an entry means it is stored
+            let Some(size) = self.size(id) else { continue };
+            let modified = times.get(id).map(|secs| UNIX_EPOCH + Duration::from_secs(*secs));
+            objects.push(StoredObject { id: id.clone(), size, modified });
+        }
+        Ok(objects)
+    }
+
+    fn count(&self) -> u64 {
+        match self.command(&[b"HLEN", &self.times()]) {
+            Ok(Reply::Integer(count)) => count as u64,
+            _ => 0,
+        }
+    }
+
+    fn clear(&self) -> io::Result<()> {
+        for object in self.list()? {
+            self.command(&[b"DEL", &self.key(&object.id)])?;
+        }
+        self.command(&[b"DEL", &self.times()])?;
+        Ok(())
+    }
+}
+
+/// Writes a command as a RESP array of bulk strings
+fn send(stream: &mut BufReader<TcpStream>, args: &[&[u8]]) -> io::Result<()> {
+    let mut buffer = format!("*{}\r\n", args.len()).into_bytes();
+    for arg in args {
+        buffer.extend_from_slice(format!("${}\r\n", arg.len()).as_bytes());
+        buffer.extend_from_slice(arg);
+        buffer.extend_from_slice(b"\r\n");
+    }
+    stream.get_mut().write_all(&buffer)
+}
+
+/// Reads one RESP reply; error replies become InvalidData errors
+fn receive(stream: &mut BufReader<TcpStream>) -> io::Result<Reply> {
+    let mut line = String::new();
+    if stream.read_line(&mut line)? == 0 {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    let line = line.trim_end_matches("\r\n");
+    let number = |text: &str| text.parse::<i64>().map_err(|_| io::Error::new(io::ErrorKind::InvalidData, format!("bad reply '{}'", line)));
+    match line.split_at(line.len().min(1)) {
+        ("+", _) => Ok(Reply::Status),
+        ("-", error) => Err(io::Error::new(io::ErrorKind::InvalidData, format!("key-value server: {}", error))),
+        (":", value) => Ok(Reply::Integer(number(value)?)),
+        ("$", length) => match number(length)? {
+            length if length < 0 => Ok(Reply::Bulk(None)),
+            length => {
+                let mut data = vec![0; length as usize + 2];
+                stream.read_exact(&mut data)?;
+                data.truncate(length as usize);
+                Ok(Reply::Bulk(Some(data)))
+            }
+        },
+        ("*", count) => match number(count)? {
+            count if count < 0 => Ok(Reply::Array(Vec::new())),
+            count => (0..count).map(|_| receive(stream)).collect::<io::Result<_>>().map(Reply::Array),
+        },
+        _ => Err(io::Error::new(io::ErrorKind::InvalidData, format!("bad reply '{}'", line))),
+    }
+}
+
+fn unexpected(reply: Reply) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("unexpected reply from the key-value server: {:?}", reply))
+}
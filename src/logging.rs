@@ -0,0 +1,121 @@
+use std::fmt;
+use tracing::field::{Field, Visit};
+use tracing::{Event, Level, Metadata, Subscriber};
+use tracing_subscriber::fmt::format::Writer;
+use tracing_subscriber::fmt::writer::MakeWriterExt;
+use tracing_subscriber::fmt::{FmtContext, FormatEvent, FormatFields};
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::EnvFilter;
+use crate::style::{self, Color};
+
+/// Environment variable with filter directives (e.g. `ORB_LOG=debug` or
+/// `ORB_LOG=orb::vnp=trace`); it takes precedence over `-v` and `--quiet`
+pub const ENV: &str = "ORB_LOG";
+
+/// Filter directives for the command-line verbosity: `--quiet` keeps warnings and
+/// errors, the default adds the usual progress messages, `-v` protocol frames and
+/// object IDs, `-vv` everything. Other crates only ever log warnings.
+pub fn directives(verbosity: u8, quiet: bool) -> String {
+    let level = match (quiet, verbosity) {
+        (true, _) => "warn",
+        (false, 0) => "info",
+        (false, 1) => "debug",
+        (false, _) => "trace",
+    };
+    format!("warn,orb={level}")
+}
+
+/// Installs the global subscriber. Info messages go to stdout as plain lines, like the
+/// output they replace, unless stdout carries the command's data (`stdout_is_data`, as
+/// for `orb fast-export`); everything else goes to stderr.
+pub fn init(verbosity: u8, quiet: bool, stdout_is_data: bool) {
+    let default = || EnvFilter::new(directives(verbosity, quiet));
+    let mut invalid = None;
+    let filter = match std::env::var(ENV) {
+        Ok(value) if !value.trim().is_empty() => EnvFilter::try_new(&value).unwrap_or_else(|e| {
+            invalid = Some(e);
+            default()
+        }),
+        _ => default(),
+    };
+    let writer = std::io::stdout
+        .with_filter(move |metadata: &Metadata<'_>| !stdout_is_data && *metadata.level() == Level::INFO)
+        .or_else(std::io::stderr);
+    tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(writer)
+        .event_format(Plain)
+        .init();
+    if let Some(e) = invalid {
+        tracing::warn!("⚠️  Ignoring {}: {}", ENV, e);
+    }
+}
+
+/// Prints messages through `style`: warnings and errors are colored, and when output
+/// is piped their emoji becomes a `warning:`/`error:` prefix. Debug and trace lines
+/// are prefixed with their level and module so protocol traces can be told apart from
+/// normal output.
+struct Plain;
+
+impl<S, N> FormatEvent<S, N> for Plain
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(&self, _context: &FmtContext<'_, S, N>, mut writer: Writer<'_>, event: &Event<'_>) -> fmt::Result {
+        let metadata = event.metadata();
+        let mut message = Message::default();
+        event.record(&mut message);
+        let decorated = style::decorate(&message.0);
+        let (prefix, color) = match *metadata.level() {
+            Level::ERROR => ("error: ", Some(Color::Red)),
+            Level::WARN => ("warning: ", Some(Color::Yellow)),
+            Level::INFO => ("", None),
+            level => {
+                write!(writer, "{:>5} {}: ", level, metadata.target())?;
+                ("", None)
+            }
+        };
+        let line = match decorated.len() == message.0.len() {
+            true => decorated,
+            false => format!("{}{}", prefix, decorated).into(),
+        };
+        match color {
+            Some(color) => writeln!(writer, "{}", style::paint(&line, color)),
+            None => writeln!(writer, "{}", line),
+        }
+    }
+}
+
+/// An event's message followed by its other fields as `name=value`
+#[derive(Default)]
+struct Message(String);
+
+impl Visit for Message {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        match field.name() {
+            "message" => self.0.push_str(value),
+            name => self.0.push_str(&format!(" {}={}", name, value)),
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        match field.name() {
+            "message" => self.0.push_str(&format!("{:?}", value)),
+            name => self.0.push_str(&format!(" {}={:?}", name, value)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verbosity_directives() {
+        assert_eq!(directives(0, true), "warn,orb=warn");
+        assert_eq!(directives(0, false), "warn,orb=info");
+        assert_eq!(directives(1, false), "warn,orb=debug");
+        assert_eq!(directives(3, false), "warn,orb=trace");
+    }
+}
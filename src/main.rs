@@ -1,14 +1,73 @@
-use clap::{Parser, Subcommand};
-use tokio;
+use clap::{Args, Parser, Subcommand};
+use std::collections::HashSet;
+use std::process::ExitCode;
+use std::time::Duration;
+use orbit_core::{index, objects};
+use tracing::{error, info, warn};
 mod repo;
-mod objects;
 mod vos;
 mod status;
-mod index;
 mod history;
+mod blame;
 mod fetch;
+mod hg;
+mod svn;
+mod diff;
+mod patch;
+mod fast_export;
+mod fast_import;
+mod import;
+mod plumbing;
+mod verify;
+mod release;
+mod branch;
+mod stash;
+mod bisect;
+mod cherry_pick;
+mod rebase;
+mod tag;
+mod git_auth;
+mod auth;
+mod exit;
+mod logging;
+mod style;
 mod vnp;
 mod client_tls;
+mod config;
+mod transport;
+mod transfer;
+mod journal;
+mod remote;
+mod refs;
+mod ancestry;
+mod merge;
+mod access;
+mod share;
+mod secure_channel;
+mod http_transport;
+mod proxy;
+mod cert;
+mod acme;
+mod discovery;
+mod hosted;
+mod users;
+mod server;
+mod admin_api;
+mod policy;
+mod throttle;
+mod audit;
+mod webhooks;
+mod web_ui;
+mod maintenance;
+mod server_tls;
+mod replication;
+mod object_store;
+mod s3_store;
+mod kv_store;
+mod client;
+mod hooks;
+mod progress;
+mod bridge;
 
 // The main application structure for the 'orb' executable
 #[derive(Parser, Debug)]
@@ -21,16 +80,402 @@ mod client_tls;
 struct OrbCli {
     #[command(subcommand)]
     command: Commands,
+
+    #[arg(short, long, global = true, action = clap::ArgAction::Count, help = "Show more detail: -v adds protocol frames and object IDs, -vv everything (ORB_LOG overrides)")]
+    verbose: u8,
+
+    #[arg(short, long, global = true, conflicts_with = "verbose", help = "Only print warnings, errors, and the command's own output")]
+    quiet: bool,
+
+    #[arg(long, global = true, value_enum, value_name = "WHEN", default_value_t = style::ColorChoice::Auto, help = "Color output: auto (on a terminal, unless NO_COLOR is set), always, or never")]
+    color: style::ColorChoice,
+}
+
+/// Options of `orb serve`
+#[derive(Args, Debug, Clone)]
+struct ServeArgs {
+    /// Directory holding the hosted repositories
+    #[arg(long, default_value = ".", help = "Directory holding the hosted repositories (<email>/<repo>)")]
+    root: std::path::PathBuf,
+    
+    /// Port to listen on (default: 8080, or 443 with --tls)
+    #[arg(long, help = "Port to listen on (default: 8080, or 443 with --tls)")]
+    port: Option<u16>,
+    
+    /// Server config directory holding users.json and the TLS certificate
+    #[arg(long, help = "Server config directory (default: ~/.orb-server or $ORB_SERVER_DIR)")]
+    dir: Option<std::path::PathBuf>,
+    
+    /// Serve orbits:// with cert.pem and key.pem from the config directory
+    #[arg(long, help = "Serve TLS (orbits://) with the certificate from `orb cert`")]
+    tls: bool,
+    
+    /// Let clients without a token clone and sync (read-only)
+    #[arg(long, help = "Allow read-only access without a token")]
+    allow_anonymous: bool,
+    
+    /// Address of the admin HTTP API (registration, tokens, access grants)
+    #[arg(long, value_name = "ADDR", help = "Serve the admin API on this address, e.g. 127.0.0.1:8081")]
+    admin_bind: Option<std::net::SocketAddr>,
+    
+    /// Environment variable holding the admin token
+    #[arg(long, default_value = "ORB_ADMIN_TOKEN", help = "Environment variable holding the admin API token")]
+    admin_token_env: String,
+    
+    /// Address of the read-only web UI
+    #[arg(long, value_name = "ADDR", help = "Serve a read-only web UI on this address, e.g. 0.0.0.0:8090")]
+    web_bind: Option<std::net::SocketAddr>,
+    
+    /// Object store of new repositories
+    #[arg(long, value_name = "URL", default_value = "file", help = "Where new repositories store objects: file, s3://<bucket>[/<prefix>], or redis://<host>[:port]")]
+    storage: String,
+    
+    /// Follower servers that receive every push, e.g. orbit://replica.example.com:8080
+    #[arg(long, value_name = "URL", help = "Copy every push to this follower server (repeatable)")]
+    replicate_to: Vec<String>,
+    
+    /// Run as a read-only follower of another server
+    #[arg(long, help = "Refuse writes except from the leader (which authenticates with the replication token)")]
+    replica: bool,
+    
+    /// Environment variable holding the token shared by a leader and its followers
+    #[arg(long, default_value = replication::TOKEN_ENV, help = "Environment variable holding the replication token")]
+    replication_token_env: String,
+    
+    /// Certificate pin of the followers' TLS certificates
+    #[arg(long, value_name = "PIN", help = "Pin the followers' certificates (sha256/<base64>, repeatable)")]
+    replication_pin: Vec<String>,
+    
+    /// Let anyone create an account through the admin API
+    #[arg(long, help = "Allow `orb register` without the admin token")]
+    open_registration: bool,
+    
+    /// How long tokens issued through the admin API stay valid
+    #[arg(long, value_name = "INTERVAL", value_parser = transport::parse_interval, help = "Expire tokens issued through the admin API after this long (e.g. 90d)")]
+    token_lifetime: Option<std::time::Duration>,
+    
+    /// Most bytes of objects each repository may store
+    #[arg(long, value_name = "SIZE", value_parser = transport::parse_size, help = "Storage quota per repository, e.g. 500M or 2G")]
+    repo_quota: Option<u64>,
+    
+    /// Most bytes of objects each user's repositories may store together
+    #[arg(long, value_name = "SIZE", value_parser = transport::parse_size, help = "Storage quota per user, across their repositories")]
+    user_quota: Option<u64>,
+    
+    /// Largest object a push may contain
+    #[arg(long, value_name = "SIZE", value_parser = transport::parse_size, help = "Reject pushes containing a larger object, e.g. 100M")]
+    max_object_size: Option<u64>,
+    
+    /// New connections accepted per IP per minute (0 for no limit)
+    #[arg(long, value_name = "N", default_value_t = throttle::Limits::default().connections_per_minute, help = "New connections per IP per minute (0: unlimited)")]
+    connection_rate: u32,
+    
+    /// Connections one IP may keep open at once (0 for no limit)
+    #[arg(long, value_name = "N", default_value_t = throttle::Limits::default().connections_per_ip, help = "Open connections per IP (0: unlimited)")]
+    max_connections_per_ip: u32,
+    
+    /// Commands per second per connection before the server slows a client down (0 for no limit)
+    #[arg(long, value_name = "N", default_value_t = throttle::Limits::default().requests_per_second, help = "Commands per second per connection (0: unlimited)")]
+    request_rate: u32,
+    
+    /// Where to write the JSONL audit log
+    #[arg(long, value_name = "PATH", help = "Audit log file (default: audit.log in the config directory)")]
+    audit_log: Option<std::path::PathBuf>,
+    
+    /// Do not write an audit log
+    #[arg(long, conflicts_with = "audit_log", help = "Disable the audit log")]
+    no_audit_log: bool,
+    
+    /// Size at which the audit log is rotated
+    #[arg(long, value_name = "SIZE", value_parser = transport::parse_size, default_value = "10M", help = "Rotate the audit log at this size (5 old files are kept)")]
+    audit_max_size: u64,
+    
+    /// Longest time between maintenance runs of a repository
+    #[arg(long, value_name = "INTERVAL", value_parser = transport::parse_interval, default_value = "24h", help = "Maintain each repository at least this often")]
+    maintenance_interval: std::time::Duration,
+    
+    /// New objects that trigger maintenance before the interval is up
+    #[arg(long, value_name = "N", default_value_t = 10_000, help = "Also maintain a repository after it received N objects (0 disables)")]
+    maintenance_objects: u64,
+    
+    /// Age at which unreachable objects are pruned
+    #[arg(long, value_name = "INTERVAL", value_parser = transport::parse_interval, default_value = "14d", help = "Prune unreachable objects older than this")]
+    prune_after: std::time::Duration,
+    
+    /// Do not run scheduled maintenance
+    #[arg(long, help = "Disable scheduled maintenance (see `orb serve maintenance`)")]
+    no_maintenance: bool,
+}
+
+#[derive(Subcommand, Debug)]
+enum ServeCommand {
+    /// Issue an access token for a user (creating the account if needed), or list or revoke their tokens
+    Token {
+        /// Username (an email address)
+        user: String,
+        
+        /// How long the new token stays valid
+        #[arg(long, value_name = "INTERVAL", value_parser = transport::parse_interval, conflicts_with_all = ["list", "revoke"], help = "Expire the token after this long (e.g. 90d)")]
+        expires_in: Option<std::time::Duration>,
+        
+        /// Show the user's tokens instead of issuing one
+        #[arg(long, conflicts_with = "revoke", help = "List the user's token IDs, issue dates, and expiry")]
+        list: bool,
+        
+        /// Revoke one of the user's tokens instead of issuing one
+        #[arg(long, value_name = "ID", help = "Revoke the user's token with this ID (see --list)")]
+        revoke: Option<String>,
+        
+        /// Server config directory
+        #[arg(long, help = "Server config directory (default: ~/.orb-server or $ORB_SERVER_DIR)")]
+        dir: Option<std::path::PathBuf>,
+    },
+    
+    /// Prune unreachable objects and rewrite the commit-graph of hosted repositories now
+    Maintenance {
+        /// Repository to maintain (<email>/<repo>); all of them if omitted
+        repository: Option<String>,
+        
+        /// Directory holding the hosted repositories
+        #[arg(long, default_value = ".", help = "Directory holding the hosted repositories (<email>/<repo>)")]
+        root: std::path::PathBuf,
+        
+        /// Age at which unreachable objects are pruned
+        #[arg(long, value_name = "INTERVAL", value_parser = transport::parse_interval, default_value = "14d", help = "Prune unreachable objects older than this")]
+        prune_after: std::time::Duration,
+    },
+    
+    /// Show the server's audit log (authentications, pushes, ref updates, admin actions)
+    Logs {
+        /// Only events of this user
+        #[arg(long, help = "Only events of this user")]
+        user: Option<String>,
+        
+        /// Only events of this repository (<email>/<repo>)
+        #[arg(long, help = "Only events of this repository")]
+        repo: Option<String>,
+        
+        /// Only events of this kind
+        #[arg(long, help = "Only this kind of event (auth, auth_failed, push, update, rejected, ...)")]
+        event: Option<String>,
+        
+        /// Only events newer than this
+        #[arg(long, value_parser = transport::parse_interval, help = "Only events from the last INTERVAL (e.g. 30m or 24h)")]
+        since: Option<std::time::Duration>,
+        
+        /// Number of events to show
+        #[arg(short = 'n', long, default_value_t = 50, help = "Show the last N matching events (0 for all)")]
+        limit: usize,
+        
+        /// Print the raw JSON lines
+        #[arg(long, help = "Print the matching JSON lines")]
+        json: bool,
+        
+        /// Audit log to read
+        #[arg(long, value_name = "PATH", help = "Audit log file (default: audit.log in the config directory)")]
+        file: Option<std::path::PathBuf>,
+        
+        /// Server config directory
+        #[arg(long, help = "Server config directory (default: ~/.orb-server or $ORB_SERVER_DIR)")]
+        dir: Option<std::path::PathBuf>,
+    },
+}
+
+/// Network options shared by commands that talk to an Orbit server
+#[derive(Args, Debug, Clone)]
+struct NetArgs {
+    /// Connect/read timeout in seconds (0 disables the read timeout)
+    #[arg(long, help = "Network timeout in seconds (overrides net.timeout)")]
+    timeout: Option<u64>,
+
+    /// Time allowed to establish a connection
+    #[arg(long, value_name = "SECS", help = "Connect timeout in seconds (overrides net.connect_timeout and --timeout)")]
+    connect_timeout: Option<u64>,
+
+    /// Time allowed without data from the server (0 disables)
+    #[arg(long, value_name = "SECS", help = "Read timeout in seconds, 0 to disable (overrides net.read_timeout and --timeout)")]
+    read_timeout: Option<u64>,
+
+    /// Only connect over IPv4
+    #[arg(short = '4', long, conflicts_with = "ipv6", help = "Use IPv4 addresses only")]
+    ipv4: bool,
+
+    /// Only connect over IPv6
+    #[arg(short = '6', long, help = "Use IPv6 addresses only")]
+    ipv6: bool,
+
+    /// Number of retries for transient network failures
+    #[arg(long, help = "Retries on transient network errors (overrides net.retries)")]
+    retries: Option<u32>,
+
+    /// Bandwidth cap in bytes per second (accepts k/M/G suffixes)
+    #[arg(long, value_name = "BYTES/S", value_parser = transport::parse_rate, help = "Limit transfer rate, e.g. 500k or 2M (overrides net.limit_rate)")]
+    limit_rate: Option<u64>,
+
+    /// Accept any TLS certificate (self-signed test servers only)
+    #[arg(long, help = "Skip TLS certificate verification (INSECURE: for self-signed test servers only)")]
+    insecure: bool,
+
+    /// Proxy to tunnel through (http://, socks5://, or socks5h://; "" connects directly)
+    #[arg(long, value_name = "URL", help = "Tunnel through an HTTP or SOCKS5 proxy (overrides net.proxy and HTTPS_PROXY/ALL_PROXY)")]
+    proxy: Option<String>,
+}
+
+impl NetArgs {
+    /// Resolves the effective network options from config and flags
+    fn options(&self) -> Result<transport::NetOptions, Box<dyn std::error::Error>> {
+        let config = config::load()?;
+        let connect_timeout = self.connect_timeout.or(self.timeout).filter(|&secs| secs > 0);
+        let read_timeout = self.read_timeout.or(self.timeout);
+        let flags = transport::NetOverrides {
+            connect_timeout: connect_timeout.map(Duration::from_secs),
+            read_timeout: read_timeout.map(|secs| (secs > 0).then(|| Duration::from_secs(secs))),
+            address_family: if self.ipv4 {
+                Some(transport::AddressFamily::Ipv4)
+            } else if self.ipv6 {
+                Some(transport::AddressFamily::Ipv6)
+            } else {
+                None
+            },
+        };
+        let mut options = transport::NetOptions::from_config(&config).with_overrides(flags, self.retries);
+        if self.limit_rate.is_some() {
+            options.limit_rate = self.limit_rate;
+        }
+        options.insecure = self.insecure;
+        if self.proxy.is_some() {
+            options.proxy = self.proxy.clone();
+        }
+        Ok(options)
+    }
+}
+
+/// ACME certificate options shared by `orb cert` and `orb serve`
+#[derive(Args, Debug, Clone)]
+struct AcmeArgs {
+    /// Obtain the certificate from an ACME CA using HTTP-01 challenges
+    #[arg(long, help = "Get a trusted certificate from Let's Encrypt (HTTP-01; port 80 must reach this machine)")]
+    acme: bool,
+
+    /// Contact address for expiry notices
+    #[arg(long, requires = "acme", help = "Email address registered with the ACME account")]
+    email: Option<String>,
+
+    /// Use the Let's Encrypt staging environment
+    #[arg(long, requires = "acme", conflicts_with = "acme_directory", help = "Use Let's Encrypt staging (untrusted test certificates)")]
+    staging: bool,
+
+    /// ACME directory of another CA
+    #[arg(long, requires = "acme", value_name = "URL", help = "ACME directory URL (default: Let's Encrypt)")]
+    acme_directory: Option<String>,
+
+    /// Port for the HTTP-01 responder
+    #[arg(long, requires = "acme", default_value_t = 80, help = "Port to answer HTTP-01 challenges on (when port 80 is forwarded)")]
+    http_port: u16,
+}
+
+impl AcmeArgs {
+    /// ACME settings, or None unless --acme was given
+    fn options(&self) -> Option<acme::AcmeOptions> {
+        self.acme.then(|| acme::AcmeOptions {
+            directory: match (&self.acme_directory, self.staging) {
+                (Some(directory), _) => directory.clone(),
+                (None, true) => acme::LETS_ENCRYPT_STAGING.to_string(),
+                (None, false) => acme::LETS_ENCRYPT.to_string(),
+            },
+            email: self.email.clone(),
+            http_port: self.http_port,
+        })
+    }
+}
+
+/// Which part of the version `orb release` increments
+#[derive(Args, Debug, Clone)]
+#[group(id = "bump", multiple = false)]
+struct BumpArgs {
+    /// Increment the major version (incompatible changes)
+    #[arg(long, help = "Increment the major version: 1.4.2 → 2.0.0")]
+    major: bool,
+
+    /// Increment the minor version (new features)
+    #[arg(long, help = "Increment the minor version: 1.4.2 → 1.5.0")]
+    minor: bool,
+
+    /// Increment the patch version (fixes); the default
+    #[arg(long, help = "Increment the patch version: 1.4.2 → 1.4.3 (default)")]
+    patch: bool,
+}
+
+impl BumpArgs {
+    fn bump(&self) -> release::Bump {
+        match (self.major, self.minor) {
+            (true, _) => release::Bump::Major,
+            (_, true) => release::Bump::Minor,
+            _ => release::Bump::Patch,
+        }
+    }
+}
+
+/// How `orb pull` and `orb sync` handle a branch that has diverged from the remote
+#[derive(Args, Debug, Clone)]
+#[group(id = "strategy", multiple = false)]
+struct StrategyArgs {
+    /// Only fast-forward; refuse to integrate diverged histories
+    #[arg(long, help = "Only fast-forward the local branch (overrides pull.strategy)")]
+    ff_only: bool,
+
+    /// Create a merge commit when histories have diverged
+    #[arg(long, help = "Create a merge commit when histories diverge (overrides pull.strategy)")]
+    merge: bool,
+
+    /// Replay local commits on top of the remote branch
+    #[arg(long, help = "Rebase local commits onto the remote branch (overrides pull.strategy)")]
+    rebase: bool,
+}
+
+impl StrategyArgs {
+    /// Resolves the effective strategy from flags, then `pull.strategy`, then ff-only
+    fn strategy(&self) -> Result<merge::Strategy, Box<dyn std::error::Error>> {
+        if self.ff_only {
+            return Ok(merge::Strategy::FfOnly);
+        }
+        if self.merge {
+            return Ok(merge::Strategy::Merge);
+        }
+        if self.rebase {
+            return Ok(merge::Strategy::Rebase);
+        }
+        Ok(merge::Strategy::from_config(&config::load()?)?)
+    }
 }
 
 // Defines all the main subcommands (orb <command>)
 #[derive(Subcommand, Debug)]
 enum Commands {
-    /// Initialize a new Orbit repository in the current directory
+    /// Initialize a new Orbit repository (default: in the current directory)
     ///
     /// Creates a new .orb directory with the Virtual Object Store (VOS) structure,
-    /// initializes the main branch, and sets up the repository metadata.
-    Init, 
+    /// initializes the main branch, and sets up the repository metadata, applying the
+    /// template directory (--template or $ORB_TEMPLATE_DIR) if there is one.
+    /// With --from-git, converts the full history of an existing local Git checkout
+    /// in place instead, without network access.
+    Init {
+        /// Directory to create the repository in (created if missing)
+        #[arg(value_name = "PATH", conflicts_with = "from_git", help = "Directory to create the repository in (default: the current directory)")]
+        path: Option<std::path::PathBuf>,
+        
+        /// Create a repository without a working tree
+        #[arg(long, conflicts_with = "from_git", help = "Create a bare repository without a working tree, e.g. under an `orb serve` root")]
+        bare: bool,
+        
+        /// Template directory with default config, hooks, and ignore files
+        #[arg(long, value_name = "DIR", conflicts_with = "from_git", help = "Template directory: its config is merged, orbignore becomes .orbignore, and other files (e.g. hooks/) are copied into .orb (default: $ORB_TEMPLATE_DIR)")]
+        template: Option<std::path::PathBuf>,
+        
+        /// Local Git checkout to convert in place
+        #[arg(long, value_name = "PATH", num_args = 0..=1, default_missing_value = ".", help = "Convert the history of an existing local Git checkout (default: the current directory) in place")]
+        from_git: Option<std::path::PathBuf>,
+    },
     
     /// Save changes to the repository, creating a new commit
     ///
@@ -38,8 +483,34 @@ enum Commands {
     /// using FastCDC, computes SHA3-256 hashes, and stores the new commit in the DAG.
     Save {
         /// The commit message describing the changes
-        #[arg(short, long, help = "Commit message describing the changes")]
-        message: String,
+        #[arg(short, long, required_unless_present = "amend", help = "Commit message describing the changes")]
+        message: Option<String>,
+        
+        /// Replace the last commit instead of adding one
+        #[arg(long, help = "Rewrite the last commit with the current changes, keeping its parents (and its message unless -m is given)")]
+        amend: bool,
+        
+        /// Stage these paths first, so the commit holds only staged changes
+        #[arg(value_name = "PATHSPEC", help = "Files or directories to stage before saving (commits only staged changes)")]
+        paths: Vec<String>,
+    },
+    
+    /// Stage files for the next save
+    ///
+    /// Once anything is staged, `orb save` commits only the staged changes on top of
+    /// HEAD instead of the whole working directory. Staging a directory stages every
+    /// file below it, including deletions of tracked files.
+    Add {
+        /// Files or directories to stage
+        #[arg(required = true, value_name = "PATHSPEC", help = "Files or directories to stage (\".\" for everything)")]
+        paths: Vec<String>,
+    },
+    
+    /// Remove paths from the staging area, keeping their changes in the working directory
+    Unstage {
+        /// Files or directories to unstage
+        #[arg(required = true, value_name = "PATHSPEC", help = "Staged files or directories to unstage")]
+        paths: Vec<String>,
     },
     
     /// Check the status of the working directory
@@ -53,30 +524,312 @@ enum Commands {
     ///
     /// Displays the directed acyclic graph (DAG) of commits showing relationships,
     /// commit messages, timestamps, and SHA3-256 hashes.
-    History,
+    History {
+        /// Print the commits as a JSON array of records instead of text
+        #[arg(long, help = "Print commits as JSON records (id, parents, tree, author, timestamp, message, refs)")]
+        json: bool,
+        
+        /// Only commits whose author contains this text
+        #[arg(long, help = "Only commits whose author contains this text (ignoring case)")]
+        author: Option<String>,
+        
+        /// Only commits made at or after this time
+        #[arg(long, value_name = "TIME", value_parser = history::parse_since, help = "Only commits made since TIME: a date (2024-05-01), an RFC 3339 time, or an interval ago (7d, 12h)")]
+        since: Option<i64>,
+        
+        /// Only commits made at or before this time
+        #[arg(long, value_name = "TIME", value_parser = history::parse_until, help = "Only commits made until TIME (a date includes the whole day)")]
+        until: Option<i64>,
+        
+        /// Only commits that changed these files or directories
+        #[arg(value_name = "PATH", help = "Only commits that changed these files or directories")]
+        paths: Vec<String>,
+    },
+    
+    /// Show changes between commits, or between a commit and the working directory
+    ///
+    /// With no revision, compares HEAD with the working directory; with one, that commit
+    /// with the working directory; with two (or A..B), the two commits. Each changed
+    /// file is reassembled from its chunks and printed as a unified diff. Untracked files
+    /// are left out unless they are staged.
+    Diff {
+        /// Print a summary of changed files and line counts instead of the diff
+        #[arg(long, help = "Show a diffstat (changed files with insertions and deletions) instead of the diff")]
+        stat: bool,
+        
+        /// Revisions to compare, then paths
+        #[arg(value_name = "REV_OR_PATH", help = "Up to two revisions (or A..B) to compare, then files or directories to limit the diff to")]
+        args: Vec<String>,
+        
+        /// Paths, when they could be mistaken for revisions
+        #[arg(last = true, value_name = "PATH", help = "Files or directories to limit the diff to (after --)")]
+        paths: Vec<String>,
+    },
+    
+    /// Show a commit and its changes
+    ///
+    /// Prints the commit's metadata and the refs pointing at it, followed by its diff
+    /// against its first parent.
+    Show {
+        /// Commit to show (default: HEAD)
+        #[arg(value_name = "REV", help = "Commit to show: an ID, its prefix, a branch, a tag, or e.g. HEAD~2")]
+        revision: Option<String>,
+        
+        /// Print the commit as a JSON record instead of text
+        #[arg(long, help = "Print the commit and its changed files as a JSON record")]
+        json: bool,
+    },
     
-    /// Revert files to their last committed state
+    /// Show which commit last changed each line of a file
+    ///
+    /// Prints every line with the commit that introduced it in its current form, that
+    /// commit's author, and its date.
+    Blame {
+        /// File to annotate
+        #[arg(value_name = "FILE", help = "File to annotate, relative to the repository root")]
+        path: String,
+
+        /// Commit whose version of the file is annotated (default: HEAD)
+        #[arg(value_name = "REV", help = "Annotate the file as of this commit instead of HEAD")]
+        revision: Option<String>,
+    },
+
+    /// Revert files to their last committed state, or undo a commit
     ///
     /// Restores files from the VOS to match their state in the HEAD commit.
-    /// If no files specified, reverts all modified files.
+    /// If no files specified, reverts all modified files. Given a commit instead,
+    /// records a new commit that undoes the change it made.
     Revert {
         /// Specific files to revert (if none specified, reverts all modified files)
-        #[arg(help = "Files to revert to HEAD state")]
+        #[arg(value_name = "FILE_OR_COMMIT", conflicts_with_all = ["continue_revert", "abort"], help = "Files to revert to HEAD state, or one commit to undo with a new commit")]
         files: Vec<String>,
+
+        /// Commit the revert of a commit once its conflicts are resolved
+        #[arg(long = "continue", conflicts_with = "abort", help = "Commit the resolved working tree and finish reverting the commit")]
+        continue_revert: bool,
+
+        /// Give up on a conflicted revert of a commit
+        #[arg(long, help = "Abort a conflicted revert and restore the working tree to HEAD")]
+        abort: bool,
     },
     
     /// Fetch and convert a Git repository to Orbit format
     ///
     /// Downloads a Git repository from a URL and converts it to Orbit's VOS format
-    /// with post-quantum SHA3-256 hashing and content-defined chunking.
+    /// with post-quantum SHA3-256 hashing and content-defined chunking. With
+    /// `--vcs hg` or `--vcs svn`, a Mercurial or Subversion repository is read with the
+    /// `hg` or `svn` command instead.
     Fetch {
-        /// Git repository URL to fetch and convert
-        #[arg(help = "Git repository URL (e.g., https://github.com/user/repo.git)")]
-        url: String,
+        /// Repository URL to fetch and convert
+        #[arg(required_unless_present = "update", help = "Repository URL (e.g., https://github.com/user/repo.git)")]
+        url: Option<String>,
         
         /// Target directory name (optional, defaults to repository name)
         #[arg(short, long, help = "Target directory name")]
         target: Option<String>,
+        
+        /// Version control system of the repository
+        #[arg(long, value_parser = ["git", "hg", "svn"], default_value = "git", help = "Version control system of the repository: git, hg (needs the hg command) or svn (needs the svn command)")]
+        vcs: String,
+        
+        /// Subversion authors mapping file
+        #[arg(long, value_name = "PATH", help = "Map Subversion users to `Name <email>` with `user = Name <email>` lines")]
+        authors_file: Option<std::path::PathBuf>,
+        
+        /// Keep the Git repository in .orb/git so the import can be updated later
+        #[arg(long, help = "Keep the Git remote and commit mapping for `orb fetch --update`")]
+        keep_remote: bool,
+        
+        /// Convert new upstream commits of an import made with --keep-remote
+        #[arg(long, conflicts_with_all = ["url", "target", "depth", "keep_remote"], help = "In an import made with --keep-remote, fetch and convert only new upstream commits")]
+        update: bool,
+        
+        /// Number of most recent commits to fetch and convert
+        #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..), help = "Shallow import: fetch and convert only the last N commits")]
+        depth: Option<u32>,
+        
+        /// Include submodules' files instead of listing them in .orb/submodules.json
+        #[arg(long, help = "Clone submodules and convert their recorded commits into the history")]
+        recurse_submodules: bool,
+        
+        /// SSH private key for ssh:// and git@host:path URLs
+        #[arg(long, value_name = "PATH", help = "SSH private key to use after the SSH agent (default: ~/.ssh/id_ed25519, id_ecdsa, id_rsa)")]
+        ssh_key: Option<std::path::PathBuf>,
+        
+        /// Environment variable holding an HTTPS token
+        #[arg(long, value_name = "VAR", help = "Read an HTTPS access token from VAR (default: ORB_GIT_TOKEN)")]
+        token_env: Option<String>,
+    },
+    
+    /// Write commits as patch files for email or offline review
+    ///
+    /// Each non-merge commit in the range becomes a `git format-patch` style message
+    /// (NNNN-subject.patch) whose X-Orbit-Commit and X-Orbit-Parent headers record the
+    /// Orbit IDs. Binary files are included, so `orb apply` and `git am` can apply them.
+    FormatPatch {
+        /// Commits to export: A..B, or a revision to export the commits after it up to HEAD
+        #[arg(value_name = "RANGE", help = "A..B for the commits of B that A lacks, or <rev> for the commits after it up to HEAD")]
+        range: String,
+        
+        /// Treat a single revision as the last commit to export, starting from the first commit
+        #[arg(long, help = "Export every commit up to <rev>, starting from the first commit")]
+        root: bool,
+        
+        /// Directory to write the patch files to
+        #[arg(short, long, value_name = "DIR", default_value = ".", help = "Directory for the patch files")]
+        output_directory: std::path::PathBuf,
+        
+        /// Print the patches instead of writing files
+        #[arg(long, conflicts_with = "output_directory", help = "Print all patches to stdout as one mbox")]
+        stdout: bool,
+    },
+    
+    /// Apply patch files to the working directory
+    ///
+    /// Reads patches from `orb format-patch`, `git format-patch`, or `git diff` and applies
+    /// them to the working files without committing. Nothing is written unless every
+    /// change applies; hunks whose lines moved are found near their recorded position.
+    Apply {
+        /// Patch files to apply in order ("-" reads stdin)
+        #[arg(required = true, value_name = "PATCH", help = "Patch files to apply, in order (- for stdin)")]
+        patches: Vec<std::path::PathBuf>,
+        
+        /// Only check that the patches apply
+        #[arg(long, help = "Check that the patches apply without changing any file")]
+        check: bool,
+    },
+    
+    /// Write history as a git fast-import stream
+    ///
+    /// Prints branches and tags (all of them by default) in the stream format of `git
+    /// fast-export`, so `git fast-import` and history-rewriting tools can read the
+    /// repository. Marks files let a later export continue where this one stopped.
+    FastExport {
+        /// Branches or tags to export (default: all)
+        #[arg(value_name = "REF", help = "Branches or tags to export (default: all)")]
+        refs: Vec<String>,
+        
+        /// Marks of commits exported before, which are left out
+        #[arg(long, value_name = "FILE", help = "Skip the commits listed in this marks file")]
+        import_marks: Option<std::path::PathBuf>,
+        
+        /// Where to record the marks of exported commits
+        #[arg(long, value_name = "FILE", help = "Write the mark of every exported commit to this file")]
+        export_marks: Option<std::path::PathBuf>,
+    },
+    
+    /// Read a git fast-import stream into this repository
+    ///
+    /// Reads the stream of `git fast-export` (or `orb fast-export`) from stdin and
+    /// creates its commits, branches and tags. Refs only move forward unless --force is
+    /// given; annotated tags become plain tags.
+    FastImport {
+        /// Let refs move to commits that do not descend from their current tip
+        #[arg(long, help = "Update refs even when the imported tip does not descend from the current one")]
+        force: bool,
+        
+        /// Marks from an earlier import, so the stream can refer to its commits
+        #[arg(long, value_name = "FILE", help = "Load marks written by an earlier import")]
+        import_marks: Option<std::path::PathBuf>,
+        
+        /// Where to record the marks of this import
+        #[arg(long, value_name = "FILE", help = "Write the marks of this import to this file")]
+        export_marks: Option<std::path::PathBuf>,
+    },
+    
+    /// Commit a series of directory or tarball snapshots
+    ///
+    /// Each snapshot (a directory, or a .tar, .tar.gz or .tgz file) becomes a commit on
+    /// the current branch, in the order given, replacing the whole tree of the previous
+    /// one. A commit is dated by the first YYYY-MM-DD in its snapshot's name, or else
+    /// the snapshot's modification time.
+    Import {
+        /// Snapshots to commit, oldest first
+        #[arg(required = true, value_name = "DIR_OR_TARBALL", help = "Snapshot directories or tarballs, oldest first")]
+        sources: Vec<std::path::PathBuf>,
+        
+        /// Commit message for every snapshot
+        #[arg(short, long, help = "Commit message (default: \"Import <snapshot name>\")")]
+        message: Option<String>,
+        
+        /// Commit date for every snapshot
+        #[arg(long, help = "Commit date: YYYY-MM-DD, an RFC 3339 time, or Unix seconds (default: from each snapshot's name or modification time)")]
+        date: Option<String>,
+        
+        /// Branch to commit to
+        #[arg(short, long, help = "Branch to commit the snapshots to (default: the current branch)")]
+        branch: Option<String>,
+    },
+    
+    /// Print an object from the local object store
+    ///
+    /// With -t, prints the object's type; with -p, its content: a commit's headers and
+    /// message, a tree's entries, or a file's reassembled content.
+    CatFile {
+        /// Print the object's type (commit, tree, file, or chunk)
+        #[arg(short = 't', conflicts_with = "pretty", required_unless_present = "pretty", help = "Print the object's type")]
+        show_type: bool,
+        
+        /// Print the object's content
+        #[arg(short = 'p', help = "Print the object's content readably")]
+        pretty: bool,
+        
+        /// Object to print
+        #[arg(value_name = "OBJECT", help = "An object ID, or a revision naming a commit")]
+        object: String,
+    },
+    
+    /// List the entries of a commit's tree
+    ///
+    /// Prints `<mode> <type> <id>\t<path>` for each entry of the commit's root directory,
+    /// or of the directory at PATH.
+    LsTree {
+        /// List every file below the directory, with its full path
+        #[arg(short, long, help = "Recurse into subdirectories")]
+        recursive: bool,
+        
+        /// Commit (or tree) to list
+        #[arg(value_name = "TREE_ISH", help = "A revision naming a commit, or a tree ID")]
+        commit: String,
+        
+        /// Directory (or file) in the tree
+        #[arg(value_name = "PATH", help = "Directory to list instead of the root")]
+        path: Option<String>,
+    },
+    
+    /// Check that every object reachable from the refs is stored and intact
+    ///
+    /// Walks the commits reachable from every ref (or from one commit), their trees,
+    /// files, and chunks, and prints each object that is missing, does not parse, or
+    /// does not match its ID. Clone and sync run a quicker form of this check on the
+    /// history they download.
+    VerifyGraph {
+        /// Commit to start from (default: every branch, tag, and remote-tracking ref)
+        #[arg(value_name = "REV", help = "Commit to verify with its history (default: everything reachable from any ref)")]
+        commit: Option<String>,
+    },
+    
+    /// Compute the File object ID of files
+    ///
+    /// Files are chunked and hashed exactly as `orb save` would, so the printed IDs match
+    /// what a commit records; -v reports how many chunks are already stored.
+    HashObject {
+        /// Store the chunks and File object in the object store
+        #[arg(short, long, help = "Write the object into the object store")]
+        write: bool,
+        
+        /// Files to hash
+        #[arg(required = true, value_name = "FILE", help = "Files to hash")]
+        files: Vec<std::path::PathBuf>,
+    },
+    
+    /// List the files in the index
+    ///
+    /// The index records every file of the last save or checkout.
+    LsFiles {
+        /// Also print each file's File object ID and size
+        #[arg(short, long, help = "Show File object IDs and sizes")]
+        long: bool,
     },
     
     /// Synchronize with remote Orbit repositories
@@ -84,19 +837,239 @@ enum Commands {
     /// Connects to a remote Orbit server and synchronizes commits using the VOS Network Protocol (VNP).
     /// Features post-quantum secure communication and efficient delta synchronization.
     Sync {
-        /// Remote server URL (e.g., orbit://example.com:8080 or 127.0.0.1:8080)
-        #[arg(help = "Remote Orbit server URL")]
-        url: String,
+        /// Remote name or server URL (e.g., origin or orbit://example.com:8080); defaults to origin
+        #[arg(help = "Remote name or Orbit server URL (defaults to origin)")]
+        url: Option<String>,
+
+        /// Only negotiate with the server and report what would be transferred
+        #[arg(long, help = "Report commits and objects that would be transferred without transferring or storing anything")]
+        dry_run: bool,
+
+        /// Write a JSON report of the refs moved and objects transferred
+        #[arg(long, value_name = "FILE", conflicts_with = "dry_run", help = "Write a JSON report of refs moved and objects transferred to FILE")]
+        report: Option<std::path::PathBuf>,
+
+        #[command(flatten)]
+        strategy: StrategyArgs,
+
+        #[command(flatten)]
+        net: NetArgs,
+    },
+    
+    /// Push the current branch to a remote Orbit server
+    ///
+    /// Uploads local commits and asks the server to move the remote branch to the local tip.
+    /// The server rejects updates that would discard remote commits unless --force is given.
+    Push {
+        /// Remote name or server URL (defaults to origin)
+        #[arg(help = "Remote name or Orbit server URL (defaults to origin)")]
+        remote: Option<String>,
+        
+        /// Branch to push (defaults to the current branch)
+        #[arg(help = "Branch to push (defaults to the current branch)")]
+        branch: Option<String>,
+        
+        /// Overwrite the remote branch even if it is not a fast-forward
+        #[arg(short, long, help = "Overwrite the remote branch even if this discards remote commits")]
+        force: bool,
+        
+        /// Also push all local tags
+        #[arg(long, help = "Also push all local tags")]
+        tags: bool,
+        
+        #[command(flatten)]
+        net: NetArgs,
+    },
+    
+    /// Tag HEAD as the next release
+    ///
+    /// Finds the highest `vMAJOR.MINOR.PATCH` tag, increments it, and tags HEAD with an
+    /// annotated (optionally signed) tag. With --push, the current branch and the tags
+    /// are then pushed. The working tree must have no unsaved changes.
+    Release {
+        #[command(flatten)]
+        bump: BumpArgs,
+        
+        /// Tag message (defaults to "Release <version>")
+        #[arg(short, long, help = "Tag message (default: \"Release <version>\")")]
+        message: Option<String>,
+        
+        /// Sign the tag with gpg
+        #[arg(short, long, help = "Sign the tag with gpg (key from user.signingkey, else gpg's default)")]
+        sign: bool,
+        
+        /// Push the current branch and tags afterwards
+        #[arg(long, help = "Push the current branch and the new tag afterwards")]
+        push: bool,
+        
+        /// Remote to push to (defaults to origin)
+        #[arg(long, requires = "push", help = "Remote name or Orbit server URL to push to (defaults to origin)")]
+        remote: Option<String>,
+        
+        #[command(flatten)]
+        net: NetArgs,
+    },
+    
+    /// Pull a branch from a remote Orbit server
+    ///
+    /// Downloads the remote branch's history, updates the remote-tracking ref
+    /// (e.g. origin/main), and fast-forwards the local branch when possible.
+    /// Diverged branches are merged or rebased according to pull.strategy.
+    Pull {
+        /// Remote name or server URL (defaults to origin)
+        #[arg(help = "Remote name or Orbit server URL (defaults to origin)")]
+        remote: Option<String>,
+        
+        /// Branch to pull (defaults to the remote's configured branch, then the current branch)
+        #[arg(help = "Branch to pull (defaults to the current branch)")]
+        branch: Option<String>,
+        
+        #[command(flatten)]
+        strategy: StrategyArgs,
+        
+        #[command(flatten)]
+        net: NetArgs,
     },
     
     /// Checkout files from a specific commit to the working directory
     ///
     /// Extracts files from a commit's tree and restores them to the working directory.
     /// This allows you to switch between different commit states or restore files after sync.
+    /// Objects missing locally (e.g. after an interrupted sync) are fetched from the default remote.
     Checkout {
         /// Commit ID to checkout (if not specified, uses HEAD)
         #[arg(help = "Commit ID to checkout (defaults to HEAD)")]
         commit_id: Option<String>,
+
+        #[command(flatten)]
+        net: NetArgs,
+    },
+    
+    /// Switch to another branch and update the working directory to its tip
+    ///
+    /// Tracked files that the branch does not have are deleted. Objects missing
+    /// locally are fetched from the default remote.
+    Switch {
+        /// Branch to switch to
+        #[arg(help = "Branch to switch to")]
+        branch: String,
+        
+        /// Discard uncommitted changes that the switch would overwrite
+        #[arg(short, long, help = "Switch even if uncommitted changes would be overwritten")]
+        force: bool,
+        
+        #[command(flatten)]
+        net: NetArgs,
+    },
+    
+    /// Merge a branch into the current branch
+    ///
+    /// Fast-forwards when possible. Otherwise the two trees are merged against their
+    /// merge base (a three-way merge) and a merge commit with both tips as parents is
+    /// saved. Text files changed on both sides are merged line by line; where the same
+    /// lines changed, the merge stops with conflict markers in the files. Fix them,
+    /// mark each with `orb resolve`, then `orb save` records the merge commit.
+    Merge {
+        /// Branch (or any revision) to merge
+        #[arg(required_unless_present = "abort", help = "Branch or revision to merge into the current branch")]
+        branch: Option<String>,
+        
+        /// Merge commit message
+        #[arg(short, long, help = "Message for the merge commit (default: \"Merge branch '<branch>' into <current>\")")]
+        message: Option<String>,
+        
+        /// Create a merge commit even when a fast-forward is possible
+        #[arg(long, help = "Always create a merge commit, even for a fast-forward")]
+        no_ff: bool,
+        
+        /// Abandon a conflicted merge
+        #[arg(long, conflicts_with_all = ["branch", "message", "no_ff"], help = "Abandon a conflicted merge and restore the working tree to HEAD")]
+        abort: bool,
+    },
+    
+    /// Mark conflicted files as resolved after a merge stopped
+    ///
+    /// Once every conflicted path is resolved, `orb save` records the merge commit.
+    Resolve {
+        /// Paths whose conflicts were fixed
+        #[arg(required = true, help = "Conflicted paths that have been fixed")]
+        paths: Vec<String>,
+    },
+    
+    /// Create, list, and delete local branches
+    ///
+    /// Branches are refs under .orb/refs/heads/. HEAD names the current branch, which
+    /// is the one `orb save` commits to. Without a subcommand, lists branches.
+    Branch {
+        #[command(subcommand)]
+        command: Option<BranchCommand>,
+    },
+    
+    /// Create, list, and delete tags
+    ///
+    /// Tags are refs under .orb/refs/tags/. An annotated tag also has a Tag object,
+    /// recording the tagger, date, message, and optional signature, under
+    /// .orb/refs/annotations/. Without a subcommand, lists tags.
+    Tag {
+        #[command(subcommand)]
+        command: Option<TagCommand>,
+    },
+    
+    /// Shelve uncommitted changes and restore them later
+    ///
+    /// Stashes are commits in the object store listed in .orb/stash; they are never
+    /// synced. Without a subcommand, pushes a new stash.
+    Stash {
+        #[command(subcommand)]
+        command: Option<StashCommand>,
+    },
+    
+    /// Apply the change a commit made onto the current branch
+    ///
+    /// Replays the commit's diff against its parent on top of HEAD as a new commit
+    /// with the same author and message. Conflicts are fixed like merge conflicts,
+    /// with `orb resolve`, then `orb cherry-pick --continue`.
+    CherryPick {
+        /// Commit to pick
+        #[arg(value_name = "REV", required_unless_present_any = ["continue_pick", "abort"], conflicts_with_all = ["continue_pick", "abort"], help = "Commit whose change to apply")]
+        revision: Option<String>,
+
+        /// Commit the pick once its conflicts are resolved
+        #[arg(long = "continue", conflicts_with = "abort", help = "Commit the resolved working tree and finish the pick")]
+        continue_pick: bool,
+
+        /// Give up on a conflicted pick
+        #[arg(long, help = "Abort a conflicted cherry-pick and restore the working tree to HEAD")]
+        abort: bool,
+    },
+    
+    /// Replay the current branch's commits on top of another branch
+    ///
+    /// Commits the branch has and the other lacks are replayed oldest first with their
+    /// authors and messages, then the branch is moved to the result. Merge commits are
+    /// left out. Conflicts stop the rebase until `orb rebase --continue`.
+    Rebase {
+        /// Branch (or any revision) to replay onto
+        #[arg(value_name = "BRANCH", required_unless_present_any = ["continue_rebase", "abort"], conflicts_with_all = ["continue_rebase", "abort"], help = "Branch or revision to replay the current branch onto")]
+        upstream: Option<String>,
+
+        /// Go on after resolving the conflicts of the commit that stopped
+        #[arg(long = "continue", conflicts_with = "abort", help = "Commit the resolved working tree and replay the remaining commits")]
+        continue_rebase: bool,
+
+        /// Give up on a stopped rebase
+        #[arg(long, help = "Abort the rebase and restore the working tree to the branch")]
+        abort: bool,
+    },
+    
+    /// Find the commit that introduced a bug by binary search
+    ///
+    /// Checks out commits between a bad and a good one, halving the suspects with
+    /// each verdict. HEAD stays on the current branch; the state is kept in
+    /// .orb/BISECT until `orb bisect reset`.
+    Bisect {
+        #[command(subcommand)]
+        command: BisectCommand,
     },
     
     /// Clone a repository from a remote Orbit server
@@ -104,28 +1077,159 @@ enum Commands {
     /// Creates a new local repository by downloading from a remote server.
     /// Supports multi-repository servers with repository selection.
     Clone {
-        /// Remote server URL with optional repository path (e.g., server.com:8080/repo-name)
-        #[arg(help = "Remote server URL with optional repository path")]
+        /// Remote server URL with optional repository path (e.g., server.com:8080/repo-name), or a remote name
+        #[arg(help = "Remote server URL with optional repository path, or a configured remote name")]
         url: String,
         
         /// Local directory name (optional, defaults to repository name)
         #[arg(help = "Local directory name")]
         directory: Option<String>,
+        
+        /// Clone only this branch and make it the default local branch
+        #[arg(short, long, help = "Clone only the named branch and check it out")]
+        branch: Option<String>,
+        
+        /// Download objects but leave the working directory empty
+        #[arg(long, help = "Do not check out the default branch after cloning")]
+        no_checkout: bool,
+        
+        /// Create the repository on the server if it does not exist
+        #[arg(long, help = "Create the remote repository if it does not exist")]
+        create: bool,
+
+        #[command(flatten)]
+        net: NetArgs,
     },
     
-    /// List available repositories on a remote server
+    /// Share this repository with other working copies on the local network
     ///
-    /// Connects to an Orbit server and displays all available repositories.
-    ListRepos {
-        /// Remote server URL (e.g., server.com:8080)
-        #[arg(help = "Remote Orbit server URL")]
-        url: String,
+    /// Runs a read-only VNP endpoint for the current repository so peers can
+    /// `orb sync peer://<host>` or `orb clone peer://<host>` without a server or account.
+    Share {
+        /// Port to listen on
+        #[arg(long, default_value_t = share::DEFAULT_PORT, help = "Port to listen on")]
+        port: u16,
     },
     
-    /// Register a new user account on an Orbit server
+    /// Generate a TLS certificate for an Orbit server
     ///
-    /// Creates a new user account with email-based namespace security.
-    /// Your email becomes your username and automatically grants access to your namespace.
+    /// Writes cert.pem and key.pem to the server config directory (~/.orb-server or
+    /// $ORB_SERVER_DIR). By default the certificate is self-signed and the pin clients
+    /// use to trust it is printed; with --acme it is obtained from Let's Encrypt (or
+    /// another ACME CA) and only renewed once it is close to expiry.
+    Cert {
+        /// Hostnames or IP addresses the certificate is valid for
+        #[arg(required = true, help = "Hostnames or IP addresses to put in the certificate")]
+        hostnames: Vec<String>,
+        
+        /// Directory to write cert.pem and key.pem to
+        #[arg(long, help = "Output directory (default: the server config directory)")]
+        dir: Option<std::path::PathBuf>,
+        
+        /// Replace an existing certificate
+        #[arg(long, help = "Overwrite an existing certificate and key (with --acme: renew now)")]
+        force: bool,
+        
+        #[command(flatten)]
+        acme: AcmeArgs,
+    },
+    
+    /// Run an Orbit server hosting repositories over VNP
+    ///
+    /// Serves the repositories under --root (laid out as <email>/<repo>) to `orb sync`,
+    /// `clone`, and `push`; users create repositories in their own namespace with
+    /// `orb repo create`. Clients authenticate with tokens issued by `orb serve token <user>`;
+    /// plain connections are upgraded to the encrypted channel, and --tls serves
+    /// orbits:// with the certificate from `orb cert`.
+    #[command(args_conflicts_with_subcommands = true)]
+    Serve {
+        #[command(subcommand)]
+        command: Option<ServeCommand>,
+        
+        #[command(flatten)]
+        args: Box<ServeArgs>,
+    },
+    
+    /// Mirror a repository from one Orbit server to another
+    ///
+    /// Copies every branch, tag, and object from the source repository to the
+    /// destination, overwriting destination branches so they match the source.
+    /// With --interval the mirror keeps running and repeats the copy.
+    Mirror {
+        /// Source server URL including the repository path, or a remote name
+        #[arg(help = "Source repository URL (or remote name)")]
+        source: String,
+        
+        /// Destination server URL including the repository path, or a remote name
+        #[arg(help = "Destination repository URL (or remote name)")]
+        destination: String,
+        
+        /// Repeat the mirror on this interval until interrupted (e.g. 30s, 5m, 1h)
+        #[arg(long, value_parser = transport::parse_interval, help = "Keep mirroring every INTERVAL (e.g. 5m) instead of running once")]
+        interval: Option<std::time::Duration>,
+        
+        /// Local object cache (defaults to ~/.orb-mirror/<source>)
+        #[arg(long, value_name = "DIR", help = "Directory used to cache objects between runs")]
+        cache_dir: Option<std::path::PathBuf>,
+        
+        #[command(flatten)]
+        net: NetArgs,
+    },
+    
+    /// List available repositories on a remote server
+    ///
+    /// Connects to an Orbit server and displays all available repositories
+    /// with their default branch, head commit, size, and last update.
+    ListRepos {
+        /// Remote name or server URL (e.g., server.com:8080); defaults to origin
+        #[arg(help = "Remote name or Orbit server URL (defaults to origin)")]
+        url: Option<String>,
+
+        /// Print the listing as JSON instead of a table
+        #[arg(long, help = "Print repositories as JSON")]
+        json: bool,
+
+        #[command(flatten)]
+        net: NetArgs,
+    },
+    
+    /// Manage repositories on a remote server
+    Repo {
+        #[command(subcommand)]
+        command: RepoCommand,
+    },
+    
+    /// Manage who can access a repository on the server
+    ///
+    /// Talks to the server's admin API; requires admin permission on the repository.
+    Access {
+        #[command(subcommand)]
+        command: AccessCommand,
+    },
+    
+    /// Keep this repository and a Git remote in sync, in both directions
+    ///
+    /// `pull` fetches a Git branch and converts only the commits not converted before;
+    /// `push` exports only the Orbit commits Git does not have yet and pushes them. The
+    /// Git side is kept in .orb/git and the commit IDs of both sides in .orb/git-commits.
+    Bridge {
+        #[command(subcommand)]
+        command: BridgeCommand,
+    },
+    
+    /// Manage named remotes
+    ///
+    /// Remotes are stored in .orb/config so that sync and clone can be given a
+    /// short name instead of a full server URL. Without a subcommand, lists remotes.
+    Remote {
+        #[command(subcommand)]
+        command: Option<RemoteCommand>,
+    },
+    
+    /// Register a new user account on an Orbit server
+    ///
+    /// Creates a new user account with email-based namespace security.
+    /// Your email becomes your username and automatically grants access to your namespace.
     /// Example: alice@company.com gets access to alice@company.com/* repositories.
     Register {
         /// Your email address (becomes your username for namespace security)
@@ -142,341 +1246,1443 @@ enum Commands {
     },
 }
 
+/// Subcommands of 'orb repo'
+#[derive(Subcommand, Debug)]
+enum RepoCommand {
+    /// Create a repository on the server
+    Create {
+        /// Server URL including the repository path (e.g., orbits://host:8082/alice@example.com/project), or a remote name
+        #[arg(help = "Server URL including the repository path, or a remote name")]
+        url: String,
+        
+        #[command(flatten)]
+        net: NetArgs,
+    },
+    
+    /// Permanently delete a repository on the server
+    Delete {
+        /// Server URL including the repository path, or a remote name
+        #[arg(help = "Server URL including the repository path, or a remote name")]
+        url: String,
+        
+        /// Skip the confirmation prompt
+        #[arg(short, long, help = "Delete without asking for confirmation")]
+        yes: bool,
+        
+        #[command(flatten)]
+        net: NetArgs,
+    },
+    
+    /// Show a repository's branch, size, and storage quota
+    Info {
+        /// Server URL including the repository path, or a remote name
+        #[arg(help = "Server URL including the repository path, or a remote name")]
+        url: String,
+        
+        /// Print the repository entry as JSON
+        #[arg(long, help = "Print machine-readable JSON")]
+        json: bool,
+        
+        #[command(flatten)]
+        net: NetArgs,
+    },
+    
+    /// Rename a repository on the server
+    Rename {
+        /// Server URL including the current repository path, or a remote name
+        #[arg(help = "Server URL including the current repository path, or a remote name")]
+        url: String,
+        
+        /// New repository name (a bare name keeps the current namespace)
+        #[arg(help = "New repository name (e.g., project2 or namespace/project2)")]
+        new_name: String,
+        
+        #[command(flatten)]
+        net: NetArgs,
+    },
+}
+
+/// Implementation of 'orb repo'
+async fn run_repo_command(command: &RepoCommand) -> Result<(), Box<dyn std::error::Error>> {
+    match command {
+        RepoCommand::Create { url, net } => {
+            let net = &net.options()?;
+            let (remote, orbit_url) = &resolve_repository_url(url, "create")?;
+            let repo_name = orbit_url.repository.as_deref().unwrap_or_default();
+            let wire = &transport::WireCounters::default();
+            transport::with_retries(net, || async move {
+                let mut session = client::Session::connect(orbit_url, net, wire).await?;
+                session.authenticate(remote.token_env.as_deref()).await?;
+                session.create_repository(repo_name).await
+            }).await
+        }
+        RepoCommand::Delete { url, yes, net } => {
+            let net = &net.options()?;
+            let (remote, orbit_url) = &resolve_repository_url(url, "delete")?;
+            let repo_name = orbit_url.repository.as_deref().unwrap_or_default();
+            if !yes && !confirm_deletion(repo_name)? {
+                return Err("deletion cancelled; the repository was not changed".into());
+            }
+            let wire = &transport::WireCounters::default();
+            transport::with_retries(net, || async move {
+                let mut session = client::Session::connect(orbit_url, net, wire).await?;
+                session.authenticate(remote.token_env.as_deref()).await?;
+                let command = vnp::VnpCommand::DeleteRepository(repo_name.to_string());
+                session.request(command, "delete repository").await
+            }).await?;
+            info!("🗑️  Deleted repository: {}", repo_name);
+            Ok(())
+        }
+        RepoCommand::Info { url, json, net } => {
+            let net = &net.options()?;
+            let (remote, orbit_url) = &resolve_repository_url(url, "show")?;
+            let repo_name = orbit_url.repository.as_deref().unwrap_or_default();
+            let wire = &transport::WireCounters::default();
+            let repos = transport::with_retries(net, || async move {
+                let mut session = client::Session::connect(orbit_url, net, wire).await?;
+                session.quiet = true;
+                session.authenticate_for_read(remote).await?;
+                session.list_repositories().await
+            }).await?;
+            let info = repos
+                .into_iter()
+                .find(|repo| repo.name == repo_name)
+                .ok_or_else(|| format!("Repository '{}' not found on the server", repo_name))?;
+            if *json {
+                println!("{}", serde_json::to_string_pretty(&repository_record(&info))?);
+            } else {
+                print_repository_info(&info);
+            }
+            Ok(())
+        }
+        RepoCommand::Rename { url, new_name, net } => {
+            let net = &net.options()?;
+            let (remote, orbit_url) = &resolve_repository_url(url, "rename")?;
+            let repo_name = orbit_url.repository.as_deref().unwrap_or_default();
+            // A bare name stays in the repository's current namespace
+            let new_name = &match (new_name.contains('/'), repo_name.rsplit_once('/')) {
+                (false, Some((namespace, _))) => format!("{}/{}", namespace, new_name),
+                _ => new_name.clone(),
+            };
+            let wire = &transport::WireCounters::default();
+            transport::with_retries(net, || async move {
+                let mut session = client::Session::connect(orbit_url, net, wire).await?;
+                session.authenticate(remote.token_env.as_deref()).await?;
+                let command = vnp::VnpCommand::RenameRepository { from: repo_name.to_string(), to: new_name.clone() };
+                session.request(command, "rename repository").await
+            }).await?;
+            info!("✅ Renamed repository {} → {}", repo_name, new_name);
+            info!("💡 Update remotes that point at the old name with `orb remote add` / `orb remote remove`");
+            Ok(())
+        }
+    }
+}
+
+/// Subcommands of 'orb access'
+#[derive(Subcommand, Debug)]
+enum AccessCommand {
+    /// Give a user access to a repository (read access unless --write or --admin)
+    Grant {
+        /// Server URL including the repository path, or a remote name
+        repo: String,
+        
+        /// User to grant access to (their registered email)
+        user: String,
+        
+        #[command(flatten)]
+        level: PermissionArgs,
+    },
+    
+    /// Remove a user's access to a repository
+    Revoke {
+        /// Server URL including the repository path, or a remote name
+        repo: String,
+        
+        /// User whose access is removed
+        user: String,
+    },
+    
+    /// List the users who can access a repository
+    List {
+        /// Server URL including the repository path, or a remote name
+        repo: String,
+    },
+}
+
+/// Permission level flags for 'orb access grant'
+#[derive(Args, Debug)]
+#[group(multiple = false)]
+struct PermissionArgs {
+    /// Read-only access (default)
+    #[arg(long, help = "Allow cloning and pulling (default)")]
+    read: bool,
+    
+    /// Read and push access
+    #[arg(long, help = "Allow pushing as well as reading")]
+    write: bool,
+    
+    /// Full control, including managing access
+    #[arg(long, help = "Allow managing the repository and its collaborators")]
+    admin: bool,
+}
+
+impl PermissionArgs {
+    fn permission(&self) -> access::Permission {
+        if self.admin {
+            access::Permission::Admin
+        } else if self.write {
+            access::Permission::Write
+        } else {
+            access::Permission::Read
+        }
+    }
+}
+
+/// Implementation of 'orb access'
+async fn run_access_command(command: &AccessCommand) -> Result<(), Box<dyn std::error::Error>> {
+    let repo = match command {
+        AccessCommand::Grant { repo, .. } | AccessCommand::Revoke { repo, .. } | AccessCommand::List { repo } => repo,
+    };
+    let (remote, orbit_url) = resolve_repository_url(repo, "manage")?;
+    let repo_name = orbit_url.repository.as_deref().unwrap_or_default();
+    let token = auth::load_token(remote.token_env.as_deref())?;
+    
+    match command {
+        AccessCommand::Grant { user, level, .. } => {
+            let permission = level.permission();
+            access::grant(&orbit_url, repo_name, user, permission, &token).await?;
+            info!("✅ Granted {} access on {} to {}", permission, repo_name, user);
+        }
+        AccessCommand::Revoke { user, .. } => {
+            access::revoke(&orbit_url, repo_name, user, &token).await?;
+            info!("✅ Revoked {}'s access to {}", user, repo_name);
+        }
+        AccessCommand::List { .. } => {
+            let entries = access::list(&orbit_url, repo_name, &token).await?;
+            if entries.is_empty() {
+                println!("👥 No collaborators on {}", repo_name);
+            } else {
+                println!("👥 Access to {} ({}):", repo_name, entries.len());
+                let width = entries.iter().map(|e| e.user.len()).max().unwrap_or(0);
+                for entry in &entries {
+                    println!("  {:<width$}  {}", entry.user, entry.permission, width = width);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Resolves a remote name or URL that must name a repository on the server
+fn resolve_repository_url(target: &str, action: &str) -> Result<(remote::Remote, client_tls::OrbitUrl), Box<dyn std::error::Error>> {
+    let remote = remote::Remote::resolve(Some(target))?;
+    let orbit_url = remote.orbit_url()?;
+    // A peer shares a single repository, so its URL needs no path
+    if orbit_url.repository.is_none() && !remote.is_peer() {
+        return Err(format!("The URL must include the repository to {} (e.g., host:8082/namespace/project)", action).into());
+    }
+    Ok((remote, orbit_url))
+}
+
+/// Asks the user to type the repository name before it is deleted
+fn confirm_deletion(repo_name: &str) -> Result<bool, Box<dyn std::error::Error>> {
+    use std::io::Write;
+    
+    warn!("⚠️  This permanently deletes '{}' and all of its history on the server.", repo_name);
+    print!("   Type the repository name to confirm: ");
+    std::io::stdout().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    Ok(answer.trim() == repo_name)
+}
+
+/// Subcommands of 'orb bridge'
+#[derive(Subcommand, Debug)]
+enum BridgeCommand {
+    /// Fetch a Git branch and convert its new commits into the current branch
+    Pull {
+        #[command(flatten)]
+        args: BridgeArgs,
+        
+        #[command(flatten)]
+        strategy: StrategyArgs,
+    },
+    
+    /// Export the current branch's new commits to Git and push them
+    Push(BridgeArgs),
+}
+
+#[derive(Args, Debug)]
+struct BridgeArgs {
+    /// Git remote URL, or the name of a remote of .orb/git (e.g. origin)
+    #[arg(help = "Git remote URL, or a remote name of .orb/git such as origin")]
+    remote: String,
+    
+    /// Git branch to sync with
+    #[arg(long, help = "Git branch to sync with (default: the imported branch, else the current branch)")]
+    branch: Option<String>,
+    
+    /// SSH private key for ssh:// and git@host:path URLs
+    #[arg(long, value_name = "PATH", help = "SSH private key to use after the SSH agent (default: ~/.ssh/id_ed25519, id_ecdsa, id_rsa)")]
+    ssh_key: Option<std::path::PathBuf>,
+    
+    /// Environment variable holding an HTTPS token
+    #[arg(long, value_name = "VAR", help = "Read an HTTPS access token from VAR (default: ORB_GIT_TOKEN)")]
+    token_env: Option<String>,
+}
+
+impl BridgeArgs {
+    fn auth(&self) -> git_auth::GitAuth {
+        git_auth::GitAuth { ssh_key: self.ssh_key.clone(), token_env: self.token_env.clone() }
+    }
+}
+
+/// Subcommands of 'orb branch'
+#[derive(Subcommand, Debug)]
+enum BranchCommand {
+    /// Create a branch (HEAD stays on the current branch)
+    Create {
+        /// Name of the new branch
+        name: String,
+        
+        /// Commit the branch starts at
+        #[arg(help = "Revision the branch starts at (defaults to HEAD)")]
+        start: Option<String>,
+    },
+    
+    /// List local branches (the current one is marked with *)
+    List,
+    
+    /// Delete a branch
+    #[command(alias = "rm")]
+    Delete {
+        /// Name of the branch to delete
+        name: String,
+        
+        /// Delete even if the branch has commits that are not in HEAD
+        #[arg(short, long, help = "Delete even if the branch is not merged into HEAD")]
+        force: bool,
+    },
+}
+
+/// Implementation of 'orb branch'
+fn run_branch_command(command: &BranchCommand) -> Result<(), Box<dyn std::error::Error>> {
+    match command {
+        BranchCommand::Create { name, start } => branch::create(name, start.as_deref()),
+        BranchCommand::List => branch::list(),
+        BranchCommand::Delete { name, force } => branch::delete(name, *force),
+    }
+}
+
+/// Subcommands of 'orb tag'
+#[derive(Subcommand, Debug)]
+enum TagCommand {
+    /// Tag a commit; with a message the tag is annotated
+    Create {
+        /// Name of the tag
+        name: String,
+        
+        /// Commit to tag
+        #[arg(help = "Revision to tag (defaults to HEAD)")]
+        revision: Option<String>,
+        
+        /// Annotation message
+        #[arg(short, long, help = "Create an annotated tag with this message, tagger, and date")]
+        message: Option<String>,
+        
+        /// Sign the annotation
+        #[arg(short, long, requires = "message", help = "Sign the annotated tag with gpg (key from user.signingkey)")]
+        sign: bool,
+        
+        /// Move an existing tag
+        #[arg(short, long, help = "Replace an existing tag of the same name")]
+        force: bool,
+    },
+    
+    /// List tags with the commits they point to
+    List,
+    
+    /// Delete a tag
+    #[command(alias = "rm")]
+    Delete {
+        /// Name of the tag to delete
+        name: String,
+    },
+}
+
+fn run_tag_command(command: &TagCommand) -> Result<(), Box<dyn std::error::Error>> {
+    match command {
+        TagCommand::Create { name, revision, message, sign, force } => {
+            tag::create(name, revision.as_deref(), message.as_deref(), *sign, *force)
+        }
+        TagCommand::List => tag::list(),
+        TagCommand::Delete { name } => tag::delete(name),
+    }
+}
+
+/// Subcommands of 'orb stash'
+#[derive(Subcommand, Debug)]
+enum StashCommand {
+    /// Stash the changes to tracked files and the staged changes, and reset to HEAD
+    Push {
+        /// Description of the stash
+        #[arg(short, long, help = "Describe the stash (default: \"WIP on <branch>: <HEAD>\")")]
+        message: Option<String>,
+    },
+    
+    /// Restore a stash and drop it
+    Pop {
+        #[arg(default_value_t = 0, help = "Stash entry to restore (0 is the newest)")]
+        entry: usize,
+    },
+    
+    /// Restore a stash and keep it
+    Apply {
+        #[arg(default_value_t = 0, help = "Stash entry to restore (0 is the newest)")]
+        entry: usize,
+    },
+    
+    /// List stashes, newest first
+    List,
+    
+    /// Delete a stash without restoring it
+    Drop {
+        #[arg(default_value_t = 0, help = "Stash entry to delete (0 is the newest)")]
+        entry: usize,
+    },
+}
+
+fn run_stash_command(command: &StashCommand) -> Result<(), Box<dyn std::error::Error>> {
+    match command {
+        StashCommand::Push { message } => stash::push(message.as_deref()),
+        StashCommand::Pop { entry } => stash::pop(*entry, false),
+        StashCommand::Apply { entry } => stash::pop(*entry, true),
+        StashCommand::List => stash::list(),
+        StashCommand::Drop { entry } => stash::drop(*entry),
+    }
+}
+
+/// Subcommands of 'orb bisect'
+#[derive(Subcommand, Debug)]
+enum BisectCommand {
+    /// Start bisecting from HEAD
+    Start {
+        #[arg(value_name = "BAD", help = "A commit that has the bug")]
+        bad: Option<String>,
+        
+        #[arg(value_name = "GOOD", requires = "bad", help = "Commits that do not have the bug")]
+        good: Vec<String>,
+    },
+    
+    /// Mark a commit as having the bug
+    Bad {
+        #[arg(value_name = "REV", help = "Commit to mark (default: the one being tested)")]
+        revision: Option<String>,
+    },
+    
+    /// Mark a commit as not having the bug
+    Good {
+        #[arg(value_name = "REV", help = "Commit to mark (default: the one being tested)")]
+        revision: Option<String>,
+    },
+    
+    /// Leave a commit that cannot be tested out of the search
+    Skip {
+        #[arg(value_name = "REV", help = "Commit to skip (default: the one being tested)")]
+        revision: Option<String>,
+    },
+    
+    /// Test commits with a command until the first bad one is found
+    Run {
+        #[arg(required = true, trailing_var_arg = true, allow_hyphen_values = true, value_name = "COMMAND", help = "Command to run in the repository root: exit 0 means good, 125 skip, other codes below 128 bad")]
+        command: Vec<String>,
+    },
+    
+    /// End the bisect and restore the working tree to HEAD
+    Reset,
+}
+
+fn run_bisect_command(command: &BisectCommand) -> Result<(), Box<dyn std::error::Error>> {
+    match command {
+        BisectCommand::Start { bad, good } => bisect::start(bad.as_deref(), good),
+        BisectCommand::Bad { revision } => bisect::mark(bisect::Verdict::Bad, revision.as_deref()),
+        BisectCommand::Good { revision } => bisect::mark(bisect::Verdict::Good, revision.as_deref()),
+        BisectCommand::Skip { revision } => bisect::mark(bisect::Verdict::Skip, revision.as_deref()),
+        BisectCommand::Run { command } => bisect::run(command),
+        BisectCommand::Reset => bisect::reset(),
+    }
+}
+
+/// Subcommands of 'orb remote'
+#[derive(Subcommand, Debug)]
+enum RemoteCommand {
+    /// Add a named remote
+    Add {
+        /// Name of the remote (e.g., origin)
+        name: String,
+        
+        /// Server URL with optional repository path
+        url: String,
+        
+        /// Default branch for this remote
+        #[arg(long, help = "Default branch used when syncing with this remote")]
+        branch: Option<String>,
+        
+        /// Environment variable holding the API token for this remote
+        #[arg(long, value_name = "VAR", help = "Read the API token for this remote from VAR instead of ORBIT_TOKEN")]
+        token_env: Option<String>,
+        
+        /// Certificate pins (SPKI hashes) the server must match
+        #[arg(long = "pin", value_name = "sha256/BASE64", help = "Only trust a server whose public key hash matches (repeat for backup keys)")]
+        pins: Vec<String>,
+    },
+    
+    /// Remove a remote
+    #[command(alias = "rm")]
+    Remove {
+        /// Name of the remote to remove
+        name: String,
+    },
+    
+    /// List configured remotes (the default one is marked with *)
+    List,
+    
+    /// Set the remote used by sync, pull, and push when none is given
+    Default {
+        /// Name of an existing remote
+        name: String,
+    },
+    
+    /// Rename a remote
+    Rename {
+        /// Current name
+        old: String,
+        
+        /// New name
+        new: String,
+    },
+}
+
+/// Implementation of 'orb remote'
+fn run_remote_command(command: &RemoteCommand) -> Result<(), Box<dyn std::error::Error>> {
+    match command {
+        RemoteCommand::Add { name, url, branch, token_env, pins } => {
+            remote::add(name, url, branch.as_deref(), token_env.as_deref(), pins)?;
+            info!("✅ Added remote '{}' → {}", name, url);
+        }
+        RemoteCommand::Remove { name } => {
+            remote::remove(name)?;
+            info!("✅ Removed remote '{}'", name);
+        }
+        RemoteCommand::Rename { old, new } => {
+            remote::rename(old, new)?;
+            info!("✅ Renamed remote '{}' to '{}'", old, new);
+        }
+        RemoteCommand::Default { name } => {
+            remote::set_default(name)?;
+            info!("✅ '{}' is now the default remote", name);
+        }
+        RemoteCommand::List => {
+            let remotes = remote::list()?;
+            if remotes.is_empty() {
+                println!("📡 No remotes configured (add one with: orb remote add origin <url>)");
+            }
+            let default = remote::default_name(&config::load()?);
+            for r in remotes {
+                let mut details = Vec::new();
+                if let Some(branch) = &r.branch {
+                    details.push(format!("branch: {}", branch));
+                }
+                if let Some(token_env) = &r.token_env {
+                    details.push(format!("token: ${}", token_env));
+                }
+                let name = r.name.as_deref().unwrap_or_default();
+                let marker = if name == default { '*' } else { ' ' };
+                if details.is_empty() {
+                    println!("{} {:<12} {}", marker, name, r.url);
+                } else {
+                    println!("{} {:<12} {} ({})", marker, name, r.url, details.join(", "));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
 /// Implementation of the 'orb sync' command logic.
-async fn run_sync(url: &str) -> Result<(), Box<dyn std::error::Error>> {
-    println!("🔄 Connecting to Orbit server: {}", url);
+async fn run_sync(
+    target: Option<&str>,
+    dry_run: bool,
+    report: Option<&std::path::Path>,
+    strategy: merge::Strategy,
+    net: &transport::NetOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let remote = remote::Remote::resolve(target)?;
+    if !dry_run {
+        let repository = repo::open()?;
+        repo::ensure_can_update(&repository, &repository.current_branch())?;
+    }
+    match &remote.name {
+        Some(name) => info!("🔄 Connecting to Orbit server: {} ({})", remote.url, name),
+        None => info!("🔄 Connecting to Orbit server: {}", remote.url),
+    }
     
     // Parse the URL to determine TLS requirements
-    let orbit_url = client_tls::OrbitUrl::parse(url)?;
+    let orbit_url = remote.orbit_url()?;
     
-    println!("🌐 Establishing {} VNP connection to {}:{}...", 
+    info!("🌐 Establishing {} VNP connection to {}:{}...", 
         if orbit_url.use_tls { "TLS-secured" } else { "PQC-secured" },
-        orbit_url.host, 
+        orbit_url.url_host(), 
         orbit_url.port
     );
     
-    // Establish connection (TLS or plain TCP)
-    if orbit_url.use_tls {
-        // TLS connection (use insecure mode for testing with self-signed certificates)
-        let tls_client = client_tls::ClientTls::new_insecure()?;
-        let tls_stream = tls_client.connect(&orbit_url.host, orbit_url.port, &orbit_url.server_name).await?;
-        let (mut reader, mut writer) = tokio::io::split(tls_stream);
-        return run_sync_with_stream(&mut reader, &mut writer, orbit_url.repository.as_deref()).await;
-    } else {
-        // Plain TCP connection
-        let addr = format!("{}:{}", orbit_url.host, orbit_url.port);
-        let stream = tokio::net::TcpStream::connect(&addr).await?;
-        let (mut reader, mut writer) = stream.into_split();
-        return run_sync_with_stream(&mut reader, &mut writer, orbit_url.repository.as_deref()).await;
+    // Establish connection (TLS or plain TCP). Every sync phase is idempotent
+    // (objects are content-addressed), so a dropped connection restarts the
+    // negotiation from scratch.
+    let orbit_url = &orbit_url;
+    let remote = &remote;
+    let stats = &transfer::TransferStats::new();
+    let refs_before = refs::snapshot();
+    transport::with_retries(net, || async move {
+        let mut session = client::Session::connect(orbit_url, net, stats.wire()).await?;
+        let outcome = tokio::select! {
+            result = run_sync_with_stream(&mut session, remote, orbit_url.repository.as_deref(), dry_run, strategy, stats) => Some(result),
+            _ = tokio::signal::ctrl_c() => None,
+        };
+        match outcome {
+            Some(result) => result,
+            None => Err(abort_session(&mut session.writer).await),
+        }
+    }).await?;
+    
+    if dry_run {
+        return Ok(());
+    }
+    if let Some(name) = &remote.name {
+        refs::update_remote_tracking(name, &remote.tracked_branch())?;
+    }
+    stats.print_summary();
+    verify::check_transfer(&refs_before, orbit_url.repository.as_deref())?;
+    
+    let changes = refs::changes(&refs_before, &refs::snapshot());
+    hooks::run("post-sync", &changes, &[
+        ("ORB_REMOTE", remote.name.as_deref().unwrap_or(&remote.url)),
+        ("ORB_REMOTE_URL", &remote.url),
+    ]);
+    if let Some(path) = report {
+        let report = transfer::SyncReport {
+            remote: remote.name.clone(),
+            url: remote.url.clone(),
+            refs: changes,
+            transfer: stats.summary(),
+        };
+        std::fs::write(path, serde_json::to_string_pretty(&report)?)?;
+        info!("📝 Wrote sync report to {}", path.display());
+    }
+    Ok(())
+}
+
+/// Handles Ctrl-C during a sync: tells the server to abort, flushes the sync journal
+/// so the next `orb sync` can resume, and returns a Cancelled error.
+///
+/// The in-flight operation has already been dropped, so the Abort is best effort:
+/// if cancellation interrupted a frame mid-write the server will simply drop the link.
+async fn abort_session<W>(writer: &mut W) -> Box<dyn std::error::Error>
+where
+    W: tokio::io::AsyncWriteExt + Unpin,
+{
+    info!("");
+    info!("🛑 Interrupted - aborting sync...");
+    let _ = vnp::send_command(writer, vnp::VnpCommand::Abort("cancelled by user".to_string())).await;
+    if let Err(e) = journal::SyncJournal::mark_interrupted() {
+        warn!("⚠️  Could not save sync journal: {}", e);
     }
+    transport::Cancelled.into()
 }
 
 /// Run sync with established stream (both TLS and plain TCP)
-async fn run_sync_with_stream<R, W>(
+async fn run_sync_with_stream(
+    session: &mut client::Session,
+    remote: &remote::Remote,
+    repository: Option<&str>,
+    dry_run: bool,
+    strategy: merge::Strategy,
+    stats: &transfer::TransferStats,
+) -> Result<(), Box<dyn std::error::Error>> {
+    // Phase 0: Authentication - MANDATORY first step (anonymous sessions can only download)
+    let can_write = session.authenticate_for_read(remote).await?;
+    
+    // Phase 1.5: Repository Selection (if repository path provided in URL)
+    if let Some(repo_name) = repository {
+        session.select(repo_name).await?;
+    }
+    
+    // Finish any object graphs left incomplete by an interrupted sync
+    if !dry_run {
+        resume_interrupted_sync(&mut session.reader, &mut session.writer, stats).await?;
+    }
+    
+    // Get local HEAD commit
+    let mut local_commits = match repo::get_local_commits() {
+        Ok(commits) => commits,
+        Err(_) => {
+            info!("📝 No local commits found, starting fresh sync...");
+            Vec::new()
+        }
+    };
+    
+    // Phase 1: Download Phase - Tell server what we have and download missing commits
+    info!("📋 Negotiating with server ({} local commits)...", local_commits.len());
+    let server_commits = match session.negotiate(&local_commits).await? {
+        missing_commits if dry_run => {
+            return report_dry_run(&mut session.reader, &mut session.writer, &local_commits, &missing_commits).await;
+        },
+        missing_commits => {
+            if missing_commits.is_empty() {
+                info!("📥 No new commits to download from server");
+            } else {
+                info!("📥 Downloading {} commits from server...", missing_commits.len());
+                stats.begin_phase("Downloading", None);
+                
+                // Phase 1b: Pull missing objects from server
+                for commit_id in &missing_commits {
+                    let (id, object_type, payload) = receive_object(
+                        &mut session.reader, &mut session.writer, vnp::VnpCommand::Get(commit_id.clone()), "object", commit_id, stats,
+                    ).await?;
+                    let object_data = payload.into_bytes()?;
+                    
+                    // Store object in local VOS
+                    if let Err(e) = store_received_object(&id, &object_type, &object_data) {
+                        // Continue with other objects rather than failing completely
+                        stats.note(&format!("  ⚠️ Warning: Could not store {}: {}", id, e));
+                    }
+                }
+                
+                // Phase 1c: Download complete object graphs for each commit
+                journal::SyncJournal::begin(repository, &missing_commits)?;
+                for commit_id in &missing_commits {
+                    download_complete_object_graph(&mut session.reader, &mut session.writer, commit_id, stats).await?;
+                    journal::SyncJournal::complete(commit_id)?;
+                }
+                stats.end_phase();
+                info!("✅ Downloaded {} commits with complete object graphs", missing_commits.len());
+                
+                // Move HEAD to the server's tip for the tracked branch (older servers don't advertise refs)
+                let advertised = session.list_refs().await.ok()
+                    .and_then(|remote_refs| remote_refs.branches.get(&remote.tracked_branch()).cloned());
+                match repo::update_head_after_sync(&missing_commits, advertised.as_deref())? {
+                    // Fast-forwarded: the working tree follows the branch
+                    repo::HeadUpdate::Moved(tip) => repo::move_branch(&repo::open()?, &refs::current_branch(), &tip)?,
+                    repo::HeadUpdate::Unchanged => {}
+                    repo::HeadUpdate::Diverged { local, remote: remote_tip } => {
+                        let remote_label = match &remote.name {
+                            Some(name) => format!("{}/{}", name, remote.tracked_branch()),
+                            None => remote.url.clone(),
+                        };
+                        match integrate_diverged(strategy, &refs::current_branch(), &local, &remote_tip, &remote_label) {
+                            Ok(new_tip) => local_commits = vec![new_tip],
+                            Err(e) => {
+                                vnp::send_command(&mut session.writer, vnp::VnpCommand::Abort("histories diverged".to_string())).await?;
+                                return Err(e);
+                            }
+                        }
+                    }
+                }
+            }
+            
+            // Return server commits for upload phase
+            missing_commits
+        },
+    };
+    
+    // Phase 2: Upload Phase - Send our local commits that server doesn't have  
+    if !local_commits.is_empty() {
+        // Find commits we have that server doesn't have
+        let commits_to_upload: Vec<String> = local_commits.iter()
+            .filter(|commit| !server_commits.contains(commit))
+            .cloned()
+            .collect();
+            
+        if !commits_to_upload.is_empty() && !can_write {
+            warn!("⚠️  Read-only session: {} local commit(s) were not uploaded", commits_to_upload.len());
+            info!("💡 Set ORBIT_TOKEN (or run `orb register`) to push your commits");
+        } else if !commits_to_upload.is_empty() {
+            upload_commits(&mut session.reader, &mut session.writer, &commits_to_upload, stats).await?;
+            
+            // Advance the server's branch; it refuses anything that is not a fast-forward
+            if let Some(head) = refs::head_commit() {
+                let update = RefUpdate { branch: remote.tracked_branch(), old: None, new: head, force: false };
+                update_remote_ref(&mut session.reader, &mut session.writer, &update).await?;
+            }
+        } else {
+            info!("📤 No new local commits to upload");
+        }
+    }
+    
+    // Phase 2: Finalization
+    session.finish().await?;
+    info!("✅ Synchronization completed successfully!");
+    Ok(())
+}
+
+/// Uploads commits together with every object they reference.
+/// The server requests only the objects it does not already have.
+async fn upload_commits<R, W>(
     reader: &mut R,
     writer: &mut W,
-    repository: Option<&str>,
+    commits_to_upload: &[objects::ObjectId],
+    stats: &transfer::TransferStats,
 ) -> Result<(), Box<dyn std::error::Error>>
 where
     R: tokio::io::AsyncReadExt + Unpin,
     W: tokio::io::AsyncWriteExt + Unpin,
 {
-    // Phase 0: Authentication - MANDATORY first step
-    println!("🔐 Authenticating with server...");
+    info!("📤 Uploading {} local commits to server...", commits_to_upload.len());
     
-    // Try to read token from environment variable or saved token file
-    let token = match std::env::var("ORBIT_TOKEN") {
-        Ok(token) => token,
-        Err(_) => {
-            // Try to read from saved token file in home directory
-            if let Ok(home_dir) = std::env::var("HOME").or_else(|_| std::env::var("USERPROFILE")) {
-                let token_file = std::path::Path::new(&home_dir).join(".orb_token");
-                match std::fs::read_to_string(&token_file) {
-                    Ok(token) => {
-                        println!("🔑 Using saved authentication token");
-                        token.trim().to_string()
-                    },
-                    Err(_) => {
-                        eprintln!("❌ No authentication token found.");
-                        eprintln!("💡 Register for a new account: orb register --email your@email.com --server orbit.privapulse.com:8082");
-                        eprintln!("💡 Or set existing token: export ORBIT_TOKEN=\"your-token-here\"");
-                        return Err("Authentication token required".into());
+    // Collect ALL objects needed for these commits (commits, trees, files, chunks).
+    // Discovery can take a while on large histories, so it runs on a blocking
+    // thread while the connection is kept alive with Ping frames.
+    info!("🔍 Discovering all objects referenced by commits...");
+    let discovery = tokio::task::spawn_blocking({
+        let commits = commits_to_upload.to_vec();
+        move || discover_upload_objects(commits)
+    });
+    let mut all_objects_to_upload = vnp::with_keepalive(writer, discovery).await???;
+    // The server may also ask for ancestors it lacks; their snapshots are discovered on demand
+    let history: HashSet<objects::ObjectId> = commits_to_upload.iter().flat_map(|id| ancestry::ancestors(id)).collect();
+    
+    info!("📦 Discovered {} total objects to upload", all_objects_to_upload.len());
+    stats.begin_phase("Uploading", Some(all_objects_to_upload.len() as u64));
+    
+    // Tell server we want to push commits (server will request objects)
+    vnp::send_command(writer, vnp::VnpCommand::Push(commits_to_upload.to_vec())).await?;
+    
+    // Server will request only the objects it actually needs
+    // We need to handle a variable number of SendObject requests
+    let mut uploaded_count = 0;
+    loop {
+        match vnp::recv_command(reader).await? {
+            vnp::VnpCommand::SendObject(requested_id) => {
+                // Verify this is one of the objects we can provide
+                if !all_objects_to_upload.contains(&requested_id) && history.contains(&requested_id) {
+                    all_objects_to_upload.extend(discover_upload_objects(vec![requested_id.clone()])?);
+                }
+                if !all_objects_to_upload.contains(&requested_id) {
+                    return Err(vnp::VnpError::UnrequestedObject(requested_id).into());
+                }
+                
+                // Large chunks are hashed and streamed from disk rather than loaded whole
+                let object_path = vos::object_path(&requested_id)?;
+                let object_size = std::fs::metadata(&object_path)?.len();
+                if object_size > vnp::STREAMING_THRESHOLD {
+                    vnp::send_command(writer, vnp::VnpCommand::ObjectHeader {
+                        id: requested_id.clone(),
+                        object_type: "chunk".to_string(),
+                        size: object_size,
+                        checksum: Some(vos::hash_file(&object_path)?),
+                    }).await?;
+                    vnp::send_object_file(writer, &object_path).await?;
+                    stats.record_sent("chunk", object_size);
+                    uploaded_count += 1;
+                    continue;
+                }
+                
+                // Load object from local VOS
+                match load_local_object(&requested_id) {
+                    Ok((object_type, object_data)) => {
+                        // Send object header
+                        vnp::send_command(writer, vnp::VnpCommand::ObjectHeader {
+                            id: requested_id.clone(),
+                            object_type: object_type.clone(),
+                            size: object_data.len() as u64,
+                            checksum: Some(vnp::object_checksum(&object_data)),
+                        }).await?;
+                        
+                        // Send object data
+                        vnp::send_object_data(writer, &object_data).await?;
+                        stats.record_sent(&object_type, object_data.len() as u64);
+                        uploaded_count += 1;
+                    }
+                    Err(e) => {
+                        return Err(format!("Failed to load local object {}: {}", requested_id, e).into());
                     }
                 }
-            } else {
-                eprintln!("❌ Cannot find home directory for token storage");
-                return Err("Authentication token required".into());
             }
+            vnp::VnpCommand::Ok => {
+                // Server confirms upload phase is complete
+                stats.end_phase();
+                info!("✅ Uploaded {} objects successfully!", uploaded_count);
+                break;
+            }
+            response => return Err(vnp::VnpError::response("upload", response).into()),
         }
-    };
+    }
     
-    // Send authentication token
-    vnp::send_command(writer, vnp::VnpCommand::Authenticate(token)).await?;
+    info!("✅ Uploaded {} commits successfully!", commits_to_upload.len());
+    Ok(())
+}
+
+/// A requested move of a branch on the server
+#[derive(Clone)]
+struct RefUpdate {
+    branch: String,
+    /// Tip the client believes the server has (None if unknown)
+    old: Option<objects::ObjectId>,
+    new: objects::ObjectId,
+    /// Skip the server's fast-forward check
+    force: bool,
+}
+
+/// Asks the server to move a branch. Without `force` the server rejects the
+/// update unless the new tip descends from its current one.
+async fn update_remote_ref<R, W>(
+    reader: &mut R,
+    writer: &mut W,
+    update: &RefUpdate,
+) -> Result<(), Box<dyn std::error::Error>>
+where
+    R: tokio::io::AsyncReadExt + Unpin,
+    W: tokio::io::AsyncWriteExt + Unpin,
+{
+    vnp::send_command(writer, vnp::VnpCommand::UpdateRef {
+        branch: update.branch.clone(),
+        old: update.old.clone(),
+        new: update.new.clone(),
+        force: update.force,
+    }).await?;
     
-    // Wait for authentication result
     match vnp::recv_command(reader).await? {
-        vnp::VnpCommand::AuthResult { success, message } => {
-            if success {
-                println!("✅ Authenticated successfully");
-            } else {
-                eprintln!("❌ Authentication failed: {}", message);
-                return Err("Authentication failed".into());
+        vnp::VnpCommand::Ok => {
+            let short = &update.new[..7.min(update.new.len())];
+            match &update.old {
+                Some(old) if update.force && *old != update.new => {
+                    info!("✅ Forced update of '{}': {}...{} (previous tip overwritten)", update.branch, &old[..7.min(old.len())], short);
+                }
+                _ => info!("✅ Updated remote branch '{}' to {}", update.branch, short),
             }
+            Ok(())
         }
         vnp::VnpCommand::Error(msg) => {
-            eprintln!("❌ Server error during authentication: {}", msg);
-            return Err("Authentication error".into());
+            error!("❌ Server rejected update of '{}': {}", update.branch, msg);
+            // Policy and hook rejections are not fixed by pulling or forcing
+            let diverged = msg.contains("fast-forward") || msg.contains("changed on the server");
+            if !update.force && diverged {
+                warn!("💡 The remote contains commits you do not have. Run `orb pull` to integrate them,");
+                warn!("   or use `orb push --force` to overwrite the remote branch.");
+            }
+            Err(vnp::VnpError::Rejected { phase: "ref update", message: msg }.into())
         }
-        _ => {
-            eprintln!("❌ Unexpected response during authentication");
-            return Err("Unexpected authentication response".into());
+        response => Err(vnp::VnpError::response("ref update", response).into()),
+    }
+}
+
+/// Implementation of 'orb push': uploads a branch (default: the current one) and moves
+/// the remote branch of the same name to it
+async fn run_push(
+    target: Option<&str>,
+    branch: Option<&str>,
+    force: bool,
+    push_tags: bool,
+    net: &transport::NetOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let remote = remote::Remote::resolve(target)?;
+    if remote.is_peer() {
+        return Err("Peers share read-only; ask the peer to `orb sync peer://<your-host>` after you run `orb share`".into());
+    }
+    let branch = branch.map(str::to_string).unwrap_or_else(refs::current_branch);
+    let head = refs::read_ref(&refs::branch_ref(&branch))
+        .ok_or_else(|| format!("Nothing to push: branch '{}' has no commits", branch))?;
+    
+    // `old` is filled in from the server's ref advertisement once connected
+    let update = &RefUpdate { branch, old: None, new: head, force };
+    
+    info!("📤 Pushing '{}' to {}{}", update.branch, remote.url, if force { " (forced)" } else { "" });
+    let orbit_url = &remote.orbit_url()?;
+    let remote = &remote;
+    let stats = &transfer::TransferStats::new();
+    transport::with_retries(net, || async move {
+        let mut session = client::Session::connect(orbit_url, net, stats.wire()).await?;
+        let outcome = tokio::select! {
+            result = push_with_stream(&mut session, remote, orbit_url.repository.as_deref(), update, push_tags, stats) => Some(result),
+            _ = tokio::signal::ctrl_c() => None,
+        };
+        match outcome {
+            Some(result) => result,
+            None => Err(abort_session(&mut session.writer).await),
+        }
+    }).await?;
+    
+    if let Some(name) = &remote.name {
+        refs::write_ref(&refs::remote_tracking_ref(name, &update.branch), &update.new)?;
+    }
+    stats.print_summary();
+    Ok(())
+}
+
+/// Push session: authenticate, upload the commit graph, then request the ref update
+async fn push_with_stream(
+    session: &mut client::Session,
+    remote: &remote::Remote,
+    repository: Option<&str>,
+    update: &RefUpdate,
+    push_tags: bool,
+    stats: &transfer::TransferStats,
+) -> Result<(), Box<dyn std::error::Error>> {
+    session.authenticate(remote.token_env.as_deref()).await?;
+    if let Some(repo_name) = repository {
+        session.select(repo_name).await?;
+    }
+    
+    // Compare against the server's actual tip and refuse early (before uploading
+    // anything) if the push would discard remote commits
+    let remote_refs = session.list_refs().await?;
+    let (reader, writer) = (&mut session.reader, &mut session.writer);
+    let update = &RefUpdate { old: remote_refs.branches.get(&update.branch).cloned(), ..update.clone() };
+    let branch_changed = match &update.old {
+        Some(old) if *old == update.new => {
+            info!("✅ Branch '{}' is up to date ({})", update.branch, &old[..7.min(old.len())]);
+            false
         }
+        Some(old) if !update.force && !ancestry::is_ancestor(old, &update.new) => {
+            error!("❌ Rejected: remote '{}' has commits that are not in your local branch (non-fast-forward)", update.branch);
+            warn!("💡 Run `orb pull` to integrate them, or use `orb push --force` to overwrite the remote branch.");
+            vnp::send_command(writer, vnp::VnpCommand::Abort("non-fast-forward".to_string())).await?;
+            return Err("non-fast-forward update rejected".into());
+        }
+        None => {
+            info!("🌱 Creating new remote branch '{}'", update.branch);
+            true
+        }
+        _ => true,
+    };
+    
+    if branch_changed {
+        upload_commits(reader, writer, std::slice::from_ref(&update.new), stats).await?;
+        update_remote_ref(reader, writer, update).await?;
+    }
+    if push_tags {
+        push_local_tags(reader, writer, &remote_refs, update.force, stats).await?;
     }
     
-    // Phase 1.5: Repository Selection (if repository path provided in URL)
+    session.finish().await?;
+    info!("✅ Push completed successfully!");
+    Ok(())
+}
+
+/// Implementation of 'orb pull': downloads a remote branch (default: the remote's
+/// configured branch, else the current one) and fast-forwards the local branch to it,
+/// merging or rebasing instead when the branch has diverged and the strategy allows
+async fn run_pull(target: Option<&str>, branch: Option<&str>, strategy: merge::Strategy, net: &transport::NetOptions) -> Result<(), Box<dyn std::error::Error>> {
+    let remote = remote::Remote::resolve(target)?;
+    let branch = branch
+        .map(str::to_string)
+        .or_else(|| remote.branch.clone())
+        .unwrap_or_else(refs::current_branch);
+    
+    let repository = repo::open()?;
+    repo::ensure_can_update(&repository, &branch)?;
+    
+    info!("📥 Pulling '{}' from {}", branch, remote.url);
+    let orbit_url = &remote.orbit_url()?;
+    let remote = &remote;
+    let branch = branch.as_str();
+    let stats = &transfer::TransferStats::new();
+    let remote_tip = transport::with_retries(net, || async move {
+        let mut session = client::Session::connect(orbit_url, net, stats.wire()).await?;
+        let outcome = tokio::select! {
+            result = pull_with_stream(&mut session, remote, orbit_url.repository.as_deref(), branch, stats) => Some(result),
+            _ = tokio::signal::ctrl_c() => None,
+        };
+        match outcome {
+            Some(result) => result,
+            None => Err(abort_session(&mut session.writer).await),
+        }
+    }).await?;
+    
+    let remote_label = match &remote.name {
+        Some(name) => {
+            refs::write_ref(&refs::remote_tracking_ref(name, branch), &remote_tip)?;
+            format!("{}/{}", name, branch)
+        }
+        None => remote.url.clone(),
+    };
+    
+    // Create or fast-forward the local branch
+    let branch_ref = refs::branch_ref(branch);
+    let short = &remote_tip[..7.min(remote_tip.len())];
+    match refs::read_ref(&branch_ref) {
+        None => {
+            repo::move_branch(&repository, branch, &remote_tip)?;
+            info!("🌱 Created local branch '{}' at {}", branch, short);
+        }
+        Some(local) if local == remote_tip => info!("✅ Already up to date."),
+        Some(local) if ancestry::is_ancestor(&local, &remote_tip) => {
+            repo::move_branch(&repository, branch, &remote_tip)?;
+            info!("⏩ Fast-forwarded '{}' {}..{}", branch, &local[..7.min(local.len())], short);
+        }
+        Some(local) if ancestry::is_ancestor(&remote_tip, &local) => {
+            info!("✅ Local '{}' already contains the remote commits (ahead of remote)", branch);
+        }
+        Some(local) => {
+            integrate_diverged(strategy, branch, &local, &remote_tip, &remote_label)?;
+        }
+    }
+    stats.print_summary();
+    Ok(())
+}
+
+/// Pull session: authenticate, find the branch tip, and download any missing history
+async fn pull_with_stream(
+    session: &mut client::Session,
+    remote: &remote::Remote,
+    repository: Option<&str>,
+    branch: &str,
+    stats: &transfer::TransferStats,
+) -> Result<objects::ObjectId, Box<dyn std::error::Error>> {
+    session.authenticate_for_read(remote).await?;
     if let Some(repo_name) = repository {
-        println!("📂 Selecting repository: {}", repo_name);
-        vnp::send_command(writer, vnp::VnpCommand::SelectRepository(repo_name.to_string())).await?;
+        session.select(repo_name).await?;
+    }
+    
+    let remote_refs = session.list_refs().await?;
+    let (reader, writer) = (&mut session.reader, &mut session.writer);
+    let Some(tip) = remote_refs.branches.get(branch).cloned() else {
+        let available: Vec<&str> = remote_refs.branches.keys().map(String::as_str).collect();
+        return Err(format!("Remote has no branch '{}' (available: {})", branch, available.join(", ")).into());
+    };
+    
+    download_commit_history(reader, writer, &tip, repository, stats).await?;
+    fetch_tags(reader, writer, &remote_refs.tags, &remote_refs.annotations, repository, stats).await?;
+    
+    session.finish().await?;
+    Ok(tip)
+}
+
+/// Downloads advertised tags that are missing locally, along with the history they point to
+/// and the annotations of annotated ones. Existing local tags are never moved.
+async fn fetch_tags<R, W>(
+    reader: &mut R,
+    writer: &mut W,
+    remote_tags: &std::collections::BTreeMap<String, objects::ObjectId>,
+    remote_annotations: &std::collections::BTreeMap<String, objects::ObjectId>,
+    repository: Option<&str>,
+    stats: &transfer::TransferStats,
+) -> Result<(), Box<dyn std::error::Error>>
+where
+    R: tokio::io::AsyncReadExt + Unpin,
+    W: tokio::io::AsyncWriteExt + Unpin,
+{
+    for (name, target) in remote_tags {
+        match refs::read_ref(&refs::tag_ref(name)) {
+            Some(local) if local == *target => {}
+            Some(_) => {
+                warn!("⚠️  Tag '{}' differs from the remote's; keeping the local tag", name);
+                continue;
+            }
+            None => {
+                download_commit_history(reader, writer, target, repository, stats).await?;
+                refs::write_ref(&refs::tag_ref(name), target)?;
+                info!("🏷️  New tag '{}' → {}", name, &target[..7.min(target.len())]);
+            }
+        }
         
-        // Wait for repository selection result
-        match vnp::recv_command(reader).await? {
-            vnp::VnpCommand::RepositorySelected(selected_repo) => {
-                println!("✅ Repository '{}' selected", selected_repo);
+        if let Some(annotation) = remote_annotations.get(name) {
+            if refs::read_ref(&refs::annotation_ref(name)).is_none() {
+                fetch_annotation(reader, writer, name, annotation, target, stats).await?;
             }
-            vnp::VnpCommand::Error(msg) => {
-                eprintln!("❌ Repository selection failed: {}", msg);
-                return Err("Repository selection failed".into());
+        }
+    }
+    Ok(())
+}
+
+/// Downloads the Tag object annotating tag `name` and records it, once it is checked to
+/// annotate `target`
+async fn fetch_annotation<R, W>(
+    reader: &mut R,
+    writer: &mut W,
+    name: &str,
+    annotation: &str,
+    target: &str,
+    stats: &transfer::TransferStats,
+) -> Result<(), Box<dyn std::error::Error>>
+where
+    R: tokio::io::AsyncReadExt + Unpin,
+    W: tokio::io::AsyncWriteExt + Unpin,
+{
+    let (id, _object_type, payload) = receive_object(
+        reader, writer, vnp::VnpCommand::Get(annotation.to_string()), "tag", annotation, stats,
+    ).await?;
+    let data = payload.into_bytes()?;
+    let tag: objects::Tag = serde_json::from_slice(&data)?;
+    if tag.object != target || tag.tag != name {
+        return Err(format!("The remote's annotation of tag '{}' does not annotate {}", name, target).into());
+    }
+    vos::store_object_with_id(&id, &data)?;
+    refs::write_ref(&refs::annotation_ref(name), &id)?;
+    Ok(())
+}
+
+/// Uploads local tags the server lacks (or has at a different object or annotation) and
+/// creates them remotely, with the Tag objects of annotated ones
+async fn push_local_tags<R, W>(
+    reader: &mut R,
+    writer: &mut W,
+    remote: &vnp::RefAdvertisement,
+    force: bool,
+    stats: &transfer::TransferStats,
+) -> Result<(), Box<dyn std::error::Error>>
+where
+    R: tokio::io::AsyncReadExt + Unpin,
+    W: tokio::io::AsyncWriteExt + Unpin,
+{
+    let mut annotations = refs::list_annotations();
+    let tags: std::collections::BTreeMap<String, objects::ObjectId> = refs::list_tags()
+        .into_iter()
+        .filter(|(name, target)| {
+            remote.tags.get(name) != Some(target)
+                || annotations.get(name).is_some_and(|annotation| remote.annotations.get(name) != Some(annotation))
+        })
+        .collect();
+    if tags.is_empty() {
+        info!("🏷️  Tags are up to date");
+        return Ok(());
+    }
+    annotations.retain(|name, _| tags.contains_key(name));
+    
+    // Tag objects are pushed alongside the commits they annotate
+    let targets: Vec<objects::ObjectId> = tags.values().chain(annotations.values()).cloned().collect();
+    upload_commits(reader, writer, &targets, stats).await?;
+    
+    vnp::send_command(writer, vnp::VnpCommand::PushTags { tags: tags.clone(), force, annotations }).await?;
+    match vnp::recv_command(reader).await? {
+        vnp::VnpCommand::Ok => {
+            for name in tags.keys() {
+                info!("🏷️  Pushed tag '{}'", name);
             }
-            _ => {
-                eprintln!("❌ Unexpected response during repository selection");
-                return Err("Unexpected repository selection response".into());
+            Ok(())
+        }
+        vnp::VnpCommand::Error(msg) => {
+            if !force {
+                warn!("💡 Tags that already exist on the server are only moved with `orb push --tags --force`.");
             }
+            Err(vnp::VnpError::Rejected { phase: "tag update", message: msg }.into())
         }
+        response => Err(vnp::VnpError::response("tag update", response).into()),
     }
+}
+
+/// Downloads `tip` and every ancestor commit missing locally, with their object graphs
+async fn download_commit_history<R, W>(
+    reader: &mut R,
+    writer: &mut W,
+    tip: &str,
+    repository: Option<&str>,
+    stats: &transfer::TransferStats,
+) -> Result<(), Box<dyn std::error::Error>>
+where
+    R: tokio::io::AsyncReadExt + Unpin,
+    W: tokio::io::AsyncWriteExt + Unpin,
+{
+    let mut queue = vec![tip.to_string()];
+    let mut downloaded = 0;
+    stats.begin_phase("Downloading", None);
     
-    // Get local HEAD commit
-    let local_commits = match repo::get_local_commits() {
-        Ok(commits) => commits,
-        Err(_) => {
-            println!("📝 No local commits found, starting fresh sync...");
-            Vec::new()
+    while let Some(commit_id) = queue.pop() {
+        if object_exists_locally(&commit_id) {
+            stats.record_skipped();
+            continue;
         }
-    };
+        
+        let (id, object_type, payload) = receive_object(
+            reader, writer, vnp::VnpCommand::Get(commit_id.clone()), "commit", &commit_id, stats,
+        ).await?;
+        let object_data = payload.into_bytes()?;
+        store_received_object(&id, &object_type, &object_data)?;
+        
+        journal::SyncJournal::begin(repository, std::slice::from_ref(&commit_id))?;
+        download_complete_object_graph(reader, writer, &commit_id, stats).await?;
+        journal::SyncJournal::complete(&commit_id)?;
+        downloaded += 1;
+        
+        let commit: objects::Commit = serde_json::from_slice(&object_data)?;
+        queue.extend(commit.parents);
+    }
+    stats.end_phase();
     
-    // Phase 1: Download Phase - Tell server what we have and download missing commits
-    println!("📋 Negotiating with server ({} local commits)...", local_commits.len());
-    vnp::send_command(writer, vnp::VnpCommand::Have(local_commits.clone())).await?;
+    if downloaded == 0 {
+        info!("📥 No new commits to download");
+    } else {
+        info!("✅ Downloaded {} commits with complete object graphs", downloaded);
+    }
+    Ok(())
+}
+
+/// Reports what a sync would transfer, then abandons the session without storing anything.
+///
+/// Upload sizes are exact (measured from the local VOS) but are an upper bound,
+/// since the server may already have some of the objects. Download sizes come
+/// from the server's EstimateGraph reply when it supports one.
+async fn report_dry_run<R, W>(
+    reader: &mut R,
+    writer: &mut W,
+    local_commits: &[objects::ObjectId],
+    missing_commits: &[objects::ObjectId],
+) -> Result<(), Box<dyn std::error::Error>>
+where
+    R: tokio::io::AsyncReadExt + Unpin,
+    W: tokio::io::AsyncWriteExt + Unpin,
+{
+    info!("");
+    info!("🔍 Dry run - nothing will be transferred or stored");
     
-    // Wait for server response with commits we need to download
-    let server_commits = match vnp::recv_command(reader).await? {
-        vnp::VnpCommand::Want(missing_commits) => {
-            if missing_commits.is_empty() {
-                println!("📥 No new commits to download from server");
-            } else {
-                println!("📥 Downloading {} commits from server...", missing_commits.len());
-                
-                // Phase 1b: Pull missing objects from server
-                for commit_id in &missing_commits {
-                    println!("  📦 Requesting commit: {}", commit_id);
-                    vnp::send_command(writer, vnp::VnpCommand::Get(commit_id.clone())).await?;
-                    
-                    // Receive object header
-                    match vnp::recv_command(reader).await? {
-                        vnp::VnpCommand::ObjectHeader { id, object_type, size } => {
-                            println!("  📄 Receiving {} object ({} bytes)...", object_type, size);
-                            
-                            // Receive object data
-                            let object_data = vnp::recv_object_data(reader, size).await?;
-                            
-                            // Store object in local VOS
-                            match store_received_object(&id, &object_type, &object_data) {
-                                Ok(_) => println!("  ✅ Stored {} successfully", id),
-                                Err(e) => {
-                                    println!("  ⚠️ Warning: Could not store {}: {}", id, e);
-                                    // Continue with other objects rather than failing completely
-                                }
-                            }
-                        }
-                        vnp::VnpCommand::Error(msg) => {
-                            return Err(format!("Failed to get object {}: {}", commit_id, msg).into());
-                        }
-                        _ => {
-                            return Err(format!("Unexpected response for object {}", commit_id).into());
-                        }
-                    }
-                }
-                
-                println!("✅ Downloaded {} commits successfully!", missing_commits.len());
-                
-                // Phase 1c: Download complete object graphs for each commit
-                println!("📥 Downloading complete object graphs...");
-                for commit_id in &missing_commits {
-                    download_complete_object_graph(reader, writer, commit_id).await?;
-                }
-                println!("✅ Downloaded complete object graphs!");
-                
-                // Update HEAD to point to the latest commit
-                repo::update_head_after_sync(&missing_commits)?;
+    // Download side: ask the server to size the missing graphs
+    if missing_commits.is_empty() {
+        info!("📥 Nothing to download");
+    } else {
+        vnp::send_command(writer, vnp::VnpCommand::EstimateGraph(missing_commits.to_vec())).await?;
+        let estimate = match vnp::recv_command(reader).await? {
+            vnp::VnpCommand::GraphEstimate { objects, bytes } => {
+                format!("up to {} objects, {}", objects, transfer::format_bytes(bytes))
             }
-            
-            // Return server commits for upload phase
-            missing_commits
-        },
-        vnp::VnpCommand::Error(msg) => {
-            return Err(format!("Server error: {}", msg).into());
-        },
-        _ => {
-            return Err("Unexpected server response during negotiation".into());
+            _ => "size unknown: server does not support estimates".to_string(),
+        };
+        info!("📥 Would download {} commits ({}):", missing_commits.len(), estimate);
+        for commit_id in missing_commits {
+            info!("   {}", commit_id);
+        }
+    }
+    
+    // Upload side: everything is local, so walk the graphs and measure them
+    let commits_to_upload: Vec<objects::ObjectId> = local_commits.iter()
+        .filter(|commit| !missing_commits.contains(commit))
+        .cloned()
+        .collect();
+    if commits_to_upload.is_empty() {
+        info!("📤 Nothing to upload");
+    } else {
+        let discovery = tokio::task::spawn_blocking({
+            let commits = commits_to_upload.clone();
+            move || discover_upload_objects(commits)
+        });
+        let objects_to_upload = vnp::with_keepalive(writer, discovery).await???;
+        let upload_bytes: u64 = objects_to_upload.iter()
+            .filter_map(|id| std::fs::metadata(vos::object_path(id).ok()?).ok())
+            .map(|metadata| metadata.len())
+            .sum();
+        info!(
+            "📤 Would upload {} commits (up to {} objects, {}):",
+            commits_to_upload.len(),
+            objects_to_upload.len(),
+            transfer::format_bytes(upload_bytes)
+        );
+        for commit_id in &commits_to_upload {
+            info!("   {}", commit_id);
+        }
+    }
+    
+    vnp::send_command(writer, vnp::VnpCommand::Abort("dry run".to_string())).await?;
+    Ok(())
+}
+
+/// Integrates a diverged branch according to the pull strategy and moves the branch
+/// to the result. With ff-only, or on conflicts, the branch is left untouched.
+fn integrate_diverged(
+    strategy: merge::Strategy,
+    branch: &str,
+    local: &str,
+    remote_tip: &str,
+    remote_label: &str,
+) -> Result<objects::ObjectId, Box<dyn std::error::Error>> {
+    let new_tip = match merge::integrate(strategy, branch, local, remote_tip, remote_label) {
+        Ok(Some(new_tip)) => new_tip,
+        Ok(None) => {
+            report_divergence(branch, local, remote_tip);
+            return Err("local and remote histories have diverged".into());
+        }
+        Err(e) => {
+            error!("❌ Could not {} '{}' with {}: {}", strategy, branch, remote_label, e);
+            warn!("   Local branch left at {}; no commits were changed.", &local[..7.min(local.len())]);
+            return Err("local and remote histories have diverged".into());
         }
     };
     
-    // Phase 2: Upload Phase - Send our local commits that server doesn't have  
-    if !local_commits.is_empty() {
-        // Find commits we have that server doesn't have
-        let commits_to_upload: Vec<String> = local_commits.iter()
-            .filter(|commit| !server_commits.contains(commit))
-            .cloned()
-            .collect();
-            
-        if !commits_to_upload.is_empty() {
-            println!("📤 Uploading {} local commits to server...", commits_to_upload.len());
-            
-            // Collect ALL objects needed for these commits (commits, trees, files, chunks)
-            let mut all_objects_to_upload = Vec::new();
-            let mut object_queue = commits_to_upload.clone();
-            
-            println!("🔍 Discovering all objects referenced by commits...");
-            
-            while let Some(object_id) = object_queue.pop() {
-                if all_objects_to_upload.contains(&object_id) {
-                    continue; // Already processed
-                }
-                
-                // Load the object to analyze its references
-                match load_local_object(&object_id) {
-                    Ok((object_type, object_data)) => {
-                        all_objects_to_upload.push(object_id.clone());
-                        
-                        // Parse object to find references based on type
-                        if object_type == "commit" {
-                            if let Ok(commit_json) = serde_json::from_slice::<serde_json::Value>(&object_data) {
-                                if let Some(tree_id) = commit_json.get("tree").and_then(|v| v.as_str()) {
-                                    if !all_objects_to_upload.contains(&tree_id.to_string()) {
-                                        object_queue.push(tree_id.to_string());
-                                    }
-                                }
-                            }
-                        } else if object_type == "tree" {
-                            if let Ok(tree_json) = serde_json::from_slice::<serde_json::Value>(&object_data) {
-                                if let Some(entries) = tree_json.get("entries").and_then(|v| v.as_array()) {
-                                    for entry in entries {
-                                        if let Some(file_id) = entry.get("id").and_then(|v| v.as_str()) {
-                                            if !all_objects_to_upload.contains(&file_id.to_string()) {
-                                                object_queue.push(file_id.to_string());
-                                            }
-                                        }
-                                    }
-                                }
-                            }
-                        } else if object_type == "file" {
-                            if let Ok(file_json) = serde_json::from_slice::<serde_json::Value>(&object_data) {
-                                if let Some(root_chunk_id) = file_json.get("root_chunk_id").and_then(|v| v.as_str()) {
-                                    if !all_objects_to_upload.contains(&root_chunk_id.to_string()) {
-                                        object_queue.push(root_chunk_id.to_string());
-                                    }
-                                }
-                                if let Some(chunk_ids) = file_json.get("chunk_ids").and_then(|v| v.as_array()) {
-                                    for chunk_id_val in chunk_ids {
-                                        if let Some(chunk_id) = chunk_id_val.as_str() {
-                                            if !all_objects_to_upload.contains(&chunk_id.to_string()) {
-                                                object_queue.push(chunk_id.to_string());
-                                            }
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                    }
-                    Err(e) => {
-                        return Err(format!("Failed to load local object {}: {}", object_id, e).into());
-                    }
+    repo::move_branch(&repo::open()?, branch, &new_tip)?;
+    match strategy {
+        merge::Strategy::Rebase => info!("♻️  Rebased '{}' onto {} (now at {})", branch, remote_label, &new_tip[..7]),
+        _ => info!("🔀 Merged {} into '{}' (now at {})", remote_label, branch, &new_tip[..7]),
+    }
+    Ok(new_tip)
+}
+
+/// Explains a diverged branch; the remote commits stay downloaded so a merge or rebase can use them
+fn report_divergence(branch: &str, local: &str, remote: &str) {
+    let (ahead, behind) = ancestry::ahead_behind(local, remote);
+    let short = |id: &str| id[..7.min(id.len())].to_string();
+    
+    error!("❌ Branch '{}' has diverged from the remote: {} local and {} remote commits", branch, ahead, behind);
+    match ancestry::merge_base(local, remote) {
+        Some(base) => warn!("   since their merge base {}.", short(&base)),
+        None => warn!("   and the two histories share no common commit."),
+    }
+    warn!("   Local branch left at {} (remote tip {}); no local commits were lost.", short(local), short(remote));
+    warn!("💡 Retry with --merge or --rebase, or set pull.strategy in .orb/config.");
+}
+
+/// Walks commits, trees, and files to find every object an upload must include.
+///
+/// Each object's type is known from whatever references it, so commits, trees, and
+/// file objects are parsed into their typed structs and chunks are never read.
+/// Trees shared between commits are walked once.
+/// Runs on a blocking thread, so errors are returned as plain strings.
+fn discover_upload_objects(commits: Vec<objects::ObjectId>) -> Result<HashSet<objects::ObjectId>, String> {
+    // Pushed IDs are commits or the Tag objects of annotated tags
+    enum Kind { Pushed, Commit, Tree, File, Chunk }
+    
+    let mut found = HashSet::new();
+    let mut queue: Vec<(objects::ObjectId, Kind)> = commits.into_iter().map(|id| (id, Kind::Pushed)).collect();
+    
+    while let Some((object_id, kind)) = queue.pop() {
+        if found.contains(&object_id) {
+            continue;
+        }
+        
+        match kind {
+            Kind::Pushed | Kind::Commit => match read_local_typed::<objects::Tag>(&object_id) {
+                Ok(tag) if matches!(kind, Kind::Pushed) => queue.push((tag.object, Kind::Commit)),
+                _ => {
+                    let commit: objects::Commit = read_local_typed(&object_id)?;
+                    queue.push((commit.tree, Kind::Tree));
                 }
+            },
+            Kind::Tree => {
+                let directory: objects::Directory = read_local_typed(&object_id)?;
+                queue.extend(directory.entries.into_iter().map(|entry| {
+                    let kind = if entry.mode == 0o040000 { Kind::Tree } else { Kind::File };
+                    (entry.id, kind)
+                }));
             }
-            
-            println!("📦 Discovered {} total objects to upload", all_objects_to_upload.len());
-            
-            // Tell server we want to push commits (server will request objects)
-            vnp::send_command(writer, vnp::VnpCommand::Push(commits_to_upload.clone())).await?;
-            
-            // Server will request only the objects it actually needs
-            // We need to handle a variable number of SendObject requests
-            let mut uploaded_count = 0;
-            loop {
-                match vnp::recv_command(reader).await? {
-                    vnp::VnpCommand::SendObject(requested_id) => {
-                        // Verify this is one of the objects we can provide
-                        if !all_objects_to_upload.contains(&requested_id) {
-                            return Err(format!("Server requested unexpected object: {}", requested_id).into());
-                        }
-                        
-                        println!("  📤 Uploading object: {}", requested_id);
-                        
-                        // Load object from local VOS
-                        match load_local_object(&requested_id) {
-                            Ok((object_type, object_data)) => {
-                                // Send object header
-                                vnp::send_command(writer, vnp::VnpCommand::ObjectHeader {
-                                    id: requested_id.clone(),
-                                    object_type: object_type.clone(),
-                                    size: object_data.len(),
-                                }).await?;
-                                
-                                // Send object data
-                                vnp::send_object_data(writer, &object_data).await?;
-                                println!("  ✅ Uploaded {} ({} bytes)", requested_id, object_data.len());
-                                uploaded_count += 1;
-                            }
-                            Err(e) => {
-                                return Err(format!("Failed to load local object {}: {}", requested_id, e).into());
-                            }
-                        }
-                    }
-                    vnp::VnpCommand::Ok => {
-                        // Server confirms upload phase is complete
-                        println!("✅ Uploaded {} commits successfully!", uploaded_count);
-                        break;
-                    }
-                    vnp::VnpCommand::Error(msg) => {
-                        return Err(format!("Server error during upload: {}", msg).into());
-                    }
-                    _ => {
-                        return Err("Unexpected server response during upload phase".into());
-                    }
+            Kind::File => {
+                let file: objects::File = read_local_typed(&object_id)?;
+                queue.extend(file.chunk_ids().iter().map(|chunk| (chunk.clone(), Kind::Chunk)));
+            }
+            Kind::Chunk => {
+                if !object_exists_locally(&object_id) {
+                    return Err(format!("Missing local chunk {}", object_id));
                 }
             }
-            
-            println!("✅ Uploaded {} commits successfully!", commits_to_upload.len());
-        } else {
-            println!("📤 No new local commits to upload");
-        }
-    }
-    
-    // Phase 2: Finalization
-    vnp::send_command(writer, vnp::VnpCommand::Ready).await?;
-    
-    match vnp::recv_command(reader).await? {
-        vnp::VnpCommand::Ok => {
-            println!("✅ Synchronization completed successfully!");
-        },
-        vnp::VnpCommand::Error(msg) => {
-            return Err(format!("Sync finalization error: {}", msg).into());
-        },
-        _ => {
-            return Err("Unexpected server response during finalization".into());
         }
+        found.insert(object_id);
     }
     
-    Ok(())
+    Ok(found)
+}
+
+/// Reads and parses a local commit, tree, or file object
+fn read_local_typed<T: serde::de::DeserializeOwned>(object_id: &str) -> Result<T, String> {
+    let data = vos::object_path(object_id)
+        .and_then(std::fs::read)
+        .map_err(|e| format!("Failed to load local object {}: {}", object_id, e))?;
+    serde_json::from_slice(&data).map_err(|e| format!("Failed to parse local object {}: {}", object_id, e))
 }
 
 /// Stores a received object in the local VOS
@@ -486,19 +2692,16 @@ fn store_received_object(id: &str, object_type: &str, data: &[u8]) -> Result<(),
             // Verify it's valid JSON commit data
             let _commit: objects::Commit = serde_json::from_slice(data)?;
             vos::store_object_with_id(id, data)?;
-            println!("    📝 Stored commit object");
         }
         "tree" => {
             // Verify it's valid JSON tree data  
             let _tree: objects::Directory = serde_json::from_slice(data)?;
             vos::store_object_with_id(id, data)?;
-            println!("    🌳 Stored tree object");
         }
         "file" => {
             // Verify it's valid JSON file data
             let _file: objects::File = serde_json::from_slice(data)?;
             vos::store_object_with_id(id, data)?;
-            println!("    📄 Stored file object");
         }
         _ => {
             return Err(format!("Unknown object type: {}", object_type).into());
@@ -509,152 +2712,200 @@ fn store_received_object(id: &str, object_type: &str, data: &[u8]) -> Result<(),
 
 /// Loads an object from the local VOS for uploading
 fn load_local_object(id: &str) -> Result<(String, Vec<u8>), Box<dyn std::error::Error>> {
-    // Load object data from VOS using the same pattern as history module
-    let (prefix, suffix) = id.split_at(2);
-    let object_path = std::path::Path::new(".orb")
-        .join("objects")
-        .join(prefix)
-        .join(suffix);
-    
-    let object_data = std::fs::read(object_path)?;
+    let object_data = vos::load_object(id)?;
     
-    // Determine object type by trying to parse as different types
-    if let Ok(_commit) = serde_json::from_slice::<objects::Commit>(&object_data) {
-        return Ok(("commit".to_string(), object_data));
+    // Chunks are raw bytes, so an empty object can't be classified
+    if object_data.is_empty() {
+        return Err(format!("Could not determine type of object: {}", id).into());
     }
-    
-    if let Ok(_tree) = serde_json::from_slice::<objects::Directory>(&object_data) {
-        return Ok(("tree".to_string(), object_data));
+    Ok((vos::object_type(&object_data).to_string(), object_data))
+}
+
+/// Implementation of 'orb checkout': fetches any objects the commit is missing, then restores it
+async fn run_checkout(commit_id: Option<&str>, net: &transport::NetOptions) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(id) = commit_id.map(str::to_string).or_else(refs::head_commit) {
+        if !commit_is_complete(&id) {
+            fetch_missing_objects(&id, net).await?;
+        }
     }
-    
-    if let Ok(_file) = serde_json::from_slice::<objects::File>(&object_data) {
-        return Ok(("file".to_string(), object_data));
+    checkout_commit(commit_id)
+}
+
+/// Implementation of 'orb switch': fetches the branch tip's missing objects first, like checkout
+async fn run_switch(branch: &str, force: bool, net: &transport::NetOptions) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(tip) = refs::read_ref(&refs::branch_ref(branch)) {
+        if !commit_is_complete(&tip) {
+            fetch_missing_objects(&tip, net).await?;
+        }
+    }
+    branch::switch(branch, force)
+}
+
+/// Whether a commit and its whole tree (down to each file's root chunk) are stored locally
+fn commit_is_complete(commit_id: &str) -> bool {
+    fn tree_is_complete(tree_id: &str) -> bool {
+        let Ok(directory) = vos::load::<objects::Directory>(tree_id, "tree") else {
+            return false;
+        };
+        directory.entries.iter().all(|entry| {
+            if entry.mode == 0o040000 {
+                return tree_is_complete(&entry.id);
+            }
+            vos::load::<objects::File>(&entry.id, "file")
+                .is_ok_and(|file| file.chunk_ids().iter().all(|chunk| object_exists_locally(chunk)))
+        })
     }
+    ancestry::load_commit(commit_id).is_some_and(|commit| tree_is_complete(&commit.tree))
+}
+
+/// Fetches the objects of `commit_id` that are missing locally from the default remote
+async fn fetch_missing_objects(commit_id: &str, net: &transport::NetOptions) -> Result<(), Box<dyn std::error::Error>> {
+    let remote = &remote::Remote::resolve(None)
+        .map_err(|e| format!("Commit {} is incomplete locally and cannot be fetched: {}", commit_id, e))?;
+    let orbit_url = &remote.orbit_url()?;
+    let stats = &transfer::TransferStats::new();
+    
+    info!("🌐 Fetching missing objects for {} from {}", &commit_id[..7.min(commit_id.len())], remote.url);
+    let mut session = transport::with_retries(net, || {
+        client::Session::open(remote, orbit_url, false, net, stats.wire())
+    }).await?;
     
-    // If it's not a structured object, it's likely a chunk (raw data)
-    // Chunks are just raw bytes, not JSON
-    if !object_data.is_empty() {
-        return Ok(("chunk".to_string(), object_data));
+    stats.begin_phase("Downloading", None);
+    if !object_exists_locally(commit_id) {
+        let (id, object_type, payload) = receive_object(
+            &mut session.reader, &mut session.writer, vnp::VnpCommand::Get(commit_id.to_string()), "commit", commit_id, stats,
+        ).await?;
+        store_received_object(&id, &object_type, &payload.into_bytes()?)?;
     }
+    let commit: objects::Commit = vos::load(commit_id, "commit")?;
+    resume_tree(&mut session.reader, &mut session.writer, &commit.tree, stats).await?;
     
-    Err(format!("Could not determine type of object: {}", id).into())
+    session.finish().await?;
+    stats.print_summary();
+    Ok(())
 }
 
 /// Checkout files from a specific commit to the working directory
 fn checkout_commit(commit_id: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
-    println!("🔄 Orbit Checkout");
+    repo::open_worktree()?;
+    info!("🔄 Orbit Checkout");
     
     // Determine which commit to checkout
     let target_commit = match commit_id {
         Some(id) => {
-            println!("📍 Checking out commit: {}", id);
+            info!("📍 Checking out commit: {}", id);
             id.to_string()
         }
         None => {
             // Use HEAD commit
-            let head_path = std::path::Path::new(".orb").join("refs").join("heads").join("main");
-            if !head_path.exists() {
-                return Err("No HEAD commit found. Repository might be empty.".into());
-            }
-            let head_commit = std::fs::read_to_string(head_path)?.trim().to_string();
-            println!("📍 Checking out HEAD commit: {}", head_commit);
+            let head_commit = refs::head_commit().ok_or("No HEAD commit found. Repository might be empty.")?;
+            info!("📍 Checking out HEAD commit: {}", head_commit);
             head_commit
         }
     };
     
-    // Load the commit object
-    let commit_data = load_object_from_vos(&target_commit)?;
-    let commit: objects::Commit = serde_json::from_slice(&commit_data)?;
+    let repository = repo::open()?;
+    let commit = repository.load_commit(&target_commit)?;
     
-    println!("📋 Commit: {}", commit.message);
-    println!("🌳 Restoring files from tree: {}", commit.tree);
+    info!("📋 Commit: {}", commit.message);
+    info!("🌳 Restoring files from tree: {}", commit.tree);
     
-    // Load and process the root tree
-    restore_tree_to_working_dir(&commit.tree, "")?;
+    // Restored files are recorded in the VOS Index so that `orb check` compares
+    // against the checked-out state
+    for (path, mode) in repository.checkout(&target_commit)? {
+        match mode {
+            0o040000 => info!("  📁 Restoring directory: {}", path),
+            0o100644 | 0o100755 => info!("  📄 Restoring file: {}", path),
+            0o120000 => info!("  🔗 Restoring symlink: {}", path),
+            _ => warn!("  ⚠️ Skipping unknown entry type: {} (mode: {:o})", path, mode),
+        }
+    }
     
-    println!("✅ Checkout completed successfully!");
+    info!("✅ Checkout completed successfully!");
     Ok(())
 }
 
-/// Recursively restore a tree and its contents to the working directory
-fn restore_tree_to_working_dir(tree_id: &str, path_prefix: &str) -> Result<(), Box<dyn std::error::Error>> {
-    let tree_data = load_object_from_vos(tree_id)?;
-    let directory: objects::Directory = serde_json::from_slice(&tree_data)?;
+/// Download complete object graph for a commit (trees, files, and chunks)
+async fn download_complete_object_graph<R, W>(
+    reader: &mut R,
+    writer: &mut W, 
+    commit_id: &str,
+    stats: &transfer::TransferStats,
+) -> Result<(), Box<dyn std::error::Error>>
+where
+    R: tokio::io::AsyncReadExt + Unpin,
+    W: tokio::io::AsyncWriteExt + Unpin,
+{
+    // Load the commit object (should already be downloaded)
+    let commit: objects::Commit = vos::load(commit_id, "commit")?;
     
-    for entry in &directory.entries {
-        let full_path = if path_prefix.is_empty() {
-            entry.name.clone()
-        } else {
-            format!("{}/{}", path_prefix, entry.name)
-        };
-        
-        if entry.mode == 0o040000 {
-            // Directory
-            println!("  � Restoring directory: {}", full_path);
-            std::fs::create_dir_all(&full_path)?;
-            restore_tree_to_working_dir(&entry.id, &full_path)?;
-        } else if entry.mode == 0o100644 {
-            // Regular file
-            println!("  � Restoring file: {}", full_path);
-            restore_file_to_working_dir(&entry.id, &full_path)?;
-        } else {
-            println!("  ⚠️ Skipping unknown entry type: {} (mode: {:o})", full_path, entry.mode);
-        }
-    }
+    // Download the root tree recursively
+    download_tree_recursive(reader, writer, &commit.tree, stats).await?;
     
     Ok(())
 }
 
-/// Restore a single file from VOS to the working directory
-fn restore_file_to_working_dir(file_id: &str, file_path: &str) -> Result<(), Box<dyn std::error::Error>> {
-    // Load the File object
-    let file_data = load_object_from_vos(file_id)?;
-    let file_object: objects::File = serde_json::from_slice(&file_data)?;
-    
-    // Load the actual file content from the root chunk
-    let content_data = load_object_from_vos(&file_object.root_chunk_id)?;
+/// Completes object graphs recorded in the sync journal by an interrupted sync.
+///
+/// The regular walk skips any tree that already exists locally, which would leave
+/// the children of a half-downloaded tree missing forever, so this walk descends
+/// into local trees and only fetches what is absent.
+async fn resume_interrupted_sync<R, W>(
+    reader: &mut R,
+    writer: &mut W,
+    stats: &transfer::TransferStats,
+) -> Result<(), Box<dyn std::error::Error>>
+where
+    R: tokio::io::AsyncReadExt + Unpin,
+    W: tokio::io::AsyncWriteExt + Unpin,
+{
+    let Some(journal) = journal::SyncJournal::load() else {
+        return Ok(());
+    };
     
-    // Create parent directories if needed
-    if let Some(parent) = std::path::Path::new(file_path).parent() {
-        std::fs::create_dir_all(parent)?;
+    info!("♻️  Resuming interrupted sync ({} commit graphs to complete)...", journal.pending_commits.len());
+    stats.begin_phase("Resuming", None);
+    for commit_id in &journal.pending_commits {
+        // Commits that never arrived will be offered again in the Want list
+        if object_exists_locally(commit_id) {
+            let commit: objects::Commit = vos::load(commit_id, "commit")?;
+            resume_tree(reader, writer, &commit.tree, stats).await?;
+        }
+        journal::SyncJournal::complete(commit_id)?;
     }
-    
-    // Write the file content
-    std::fs::write(file_path, content_data)?;
+    stats.end_phase();
     
     Ok(())
 }
 
-/// Load an object from the VOS by ID
-fn load_object_from_vos(object_id: &str) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
-    let (prefix, suffix) = object_id.split_at(2);
-    let object_path = std::path::Path::new(".orb")
-        .join("objects")
-        .join(prefix)
-        .join(suffix);
-    
-    let data = std::fs::read(object_path)?;
-    Ok(data)
-}
-
-/// Download complete object graph for a commit (trees, files, and chunks)
-async fn download_complete_object_graph<R, W>(
+/// Walks a tree that may be partially present locally, downloading missing descendants
+async fn resume_tree<R, W>(
     reader: &mut R,
-    writer: &mut W, 
-    commit_id: &str
+    writer: &mut W,
+    tree_id: &str,
+    stats: &transfer::TransferStats,
 ) -> Result<(), Box<dyn std::error::Error>>
 where
     R: tokio::io::AsyncReadExt + Unpin,
     W: tokio::io::AsyncWriteExt + Unpin,
 {
-    println!("  🌳 Downloading object graph for commit: {}", commit_id);
-    
-    // Load the commit object (should already be downloaded)
-    let commit_data = load_object_from_vos(commit_id)?;
-    let commit: objects::Commit = serde_json::from_slice(&commit_data)?;
+    if !object_exists_locally(tree_id) {
+        return download_tree_recursive(reader, writer, tree_id, stats).await;
+    }
     
-    // Download the root tree recursively
-    download_tree_recursive(reader, writer, &commit.tree).await?;
+    let directory: objects::Directory = vos::load(tree_id, "tree")?;
+    for entry in &directory.entries {
+        if entry.mode == 0o040000 {
+            Box::pin(resume_tree(reader, writer, &entry.id, stats)).await?;
+        } else if !object_exists_locally(&entry.id) {
+            download_file_recursive(reader, writer, &entry.id, stats).await?;
+        } else {
+            let file_object: objects::File = vos::load(&entry.id, "file")?;
+            for chunk_id in file_object.chunk_ids() {
+                download_chunk(reader, writer, chunk_id, stats).await?;
+            }
+        }
+    }
     
     Ok(())
 }
@@ -663,7 +2914,8 @@ where
 async fn download_tree_recursive<R, W>(
     reader: &mut R,
     writer: &mut W,
-    tree_id: &str
+    tree_id: &str,
+    stats: &transfer::TransferStats,
 ) -> Result<(), Box<dyn std::error::Error>>
 where
     R: tokio::io::AsyncReadExt + Unpin,
@@ -671,46 +2923,34 @@ where
 {
     // Check if we already have this tree
     if object_exists_locally(tree_id) {
+        stats.record_skipped();
         return Ok(()); // Skip if we already have it
     }
     
-    println!("    📁 Downloading tree: {}", tree_id);
     
-    // Request the tree object
-    vnp::send_command(writer, vnp::VnpCommand::GetTree(tree_id.to_string())).await?;
+    // Request and receive the tree object
+    let (id, object_type, payload) = receive_object(
+        reader, writer, vnp::VnpCommand::GetTree(tree_id.to_string()), "tree", tree_id, stats,
+    ).await?;
+    let tree_data = payload.into_bytes()?;
+    if object_type != "tree" {
+        return Err(vnp::VnpError::WrongObjectType { id, expected: "tree", actual: object_type }.into());
+    }
     
-    // Receive tree object
-    match vnp::recv_command(reader).await? {
-        vnp::VnpCommand::ObjectHeader { id, object_type, size } => {
-            if object_type != "tree" {
-                return Err(format!("Expected tree object, got {}", object_type).into());
-            }
-            
-            // Receive tree data
-            let tree_data = vnp::recv_object_data(reader, size).await?;
-            
-            // Store tree object
-            store_received_object(&id, &object_type, &tree_data)?;
-            
-            // Parse tree to get its entries
-            let directory: objects::Directory = serde_json::from_slice(&tree_data)?;
-            
-            // Recursively download all entries
-            for entry in &directory.entries {
-                if entry.mode == 0o040000 {
-                    // Directory - recurse
-                    Box::pin(download_tree_recursive(reader, writer, &entry.id)).await?;
-                } else if entry.mode == 0o100644 {
-                    // File - download file and its chunks
-                    Box::pin(download_file_recursive(reader, writer, &entry.id)).await?;
-                }
-            }
-        }
-        vnp::VnpCommand::Error(msg) => {
-            return Err(format!("Failed to get tree {}: {}", tree_id, msg).into());
-        }
-        _ => {
-            return Err(format!("Unexpected response for tree {}", tree_id).into());
+    // Store tree object
+    store_received_object(&id, &object_type, &tree_data)?;
+    
+    // Parse tree to get its entries
+    let directory: objects::Directory = serde_json::from_slice(&tree_data)?;
+    
+    // Recursively download all entries
+    for entry in &directory.entries {
+        if entry.mode == 0o040000 {
+            // Directory - recurse
+            Box::pin(download_tree_recursive(reader, writer, &entry.id, stats)).await?;
+        } else if matches!(entry.mode, 0o100644 | 0o100755 | 0o120000) {
+            // File (or symlink) - download file and its chunks
+            Box::pin(download_file_recursive(reader, writer, &entry.id, stats)).await?;
         }
     }
     
@@ -721,7 +2961,8 @@ where
 async fn download_file_recursive<R, W>(
     reader: &mut R,
     writer: &mut W,
-    file_id: &str
+    file_id: &str,
+    stats: &transfer::TransferStats,
 ) -> Result<(), Box<dyn std::error::Error>>
 where
     R: tokio::io::AsyncReadExt + Unpin,
@@ -729,39 +2970,29 @@ where
 {
     // Check if we already have this file
     if object_exists_locally(file_id) {
+        stats.record_skipped();
         return Ok(()); // Skip if we already have it
     }
     
-    println!("    📄 Downloading file: {}", file_id);
     
-    // Request the file object
-    vnp::send_command(writer, vnp::VnpCommand::GetFile(file_id.to_string())).await?;
+    // Request and receive the file object
+    let (id, object_type, payload) = receive_object(
+        reader, writer, vnp::VnpCommand::GetFile(file_id.to_string()), "file", file_id, stats,
+    ).await?;
+    let file_data = payload.into_bytes()?;
+    if object_type != "file" {
+        return Err(vnp::VnpError::WrongObjectType { id, expected: "file", actual: object_type }.into());
+    }
     
-    // Receive file object
-    match vnp::recv_command(reader).await? {
-        vnp::VnpCommand::ObjectHeader { id, object_type, size } => {
-            if object_type != "file" {
-                return Err(format!("Expected file object, got {}", object_type).into());
-            }
-            
-            // Receive file data
-            let file_data = vnp::recv_object_data(reader, size).await?;
-            
-            // Store file object
-            store_received_object(&id, &object_type, &file_data)?;
-            
-            // Parse file to get its chunk ID
-            let file_object: objects::File = serde_json::from_slice(&file_data)?;
-            
-            // Download the chunk data
-            download_chunk(reader, writer, &file_object.root_chunk_id).await?;
-        }
-        vnp::VnpCommand::Error(msg) => {
-            return Err(format!("Failed to get file {}: {}", file_id, msg).into());
-        }
-        _ => {
-            return Err(format!("Unexpected response for file {}", file_id).into());
-        }
+    // Store file object
+    store_received_object(&id, &object_type, &file_data)?;
+    
+    // Parse file to get its chunk ID
+    let file_object: objects::File = serde_json::from_slice(&file_data)?;
+    
+    // Download the chunk data
+    for chunk_id in file_object.chunk_ids() {
+        download_chunk(reader, writer, chunk_id, stats).await?;
     }
     
     Ok(())
@@ -771,7 +3002,8 @@ where
 async fn download_chunk<R, W>(
     reader: &mut R,
     writer: &mut W,
-    chunk_id: &str
+    chunk_id: &str,
+    stats: &transfer::TransferStats,
 ) -> Result<(), Box<dyn std::error::Error>>
 where
     R: tokio::io::AsyncReadExt + Unpin,
@@ -779,44 +3011,119 @@ where
 {
     // Check if we already have this chunk
     if object_exists_locally(chunk_id) {
+        stats.record_skipped();
         return Ok(()); // Skip if we already have it
     }
     
-    println!("      📦 Downloading chunk: {}", chunk_id);
-    
     // Request the chunk object (using Get command since chunks are raw data)
-    vnp::send_command(writer, vnp::VnpCommand::Get(chunk_id.to_string())).await?;
+    let (id, _object_type, payload) = receive_object(
+        reader, writer, vnp::VnpCommand::Get(chunk_id.to_string()), "chunk", chunk_id, stats,
+    ).await?;
     
-    // Receive chunk object
-    match vnp::recv_command(reader).await? {
-        vnp::VnpCommand::ObjectHeader { id, object_type: _, size } => {
-            // Receive chunk data
-            let chunk_data = vnp::recv_object_data(reader, size).await?;
-            
-            // Store chunk directly (chunks are raw data, not JSON)
+    // Store chunk directly (chunks are raw data, not JSON)
+    match payload {
+        Payload::Memory(chunk_data) => {
             vos::store_object_with_id(&id, &chunk_data)?;
-            println!("      ✅ Stored chunk {} ({} bytes)", id, chunk_data.len());
-        }
-        vnp::VnpCommand::Error(msg) => {
-            return Err(format!("Failed to get chunk {}: {}", chunk_id, msg).into());
         }
-        _ => {
-            return Err(format!("Unexpected response for chunk {}", chunk_id).into());
+        Payload::Spooled { path, .. } => {
+            vos::store_object_file(&id, &path)?;
         }
     }
     
     Ok(())
 }
 
+/// Payload of a received object. Small objects are buffered in memory; objects above
+/// vnp::STREAMING_THRESHOLD are spooled to a temp file so huge chunks never sit in RAM.
+enum Payload {
+    Memory(Vec<u8>),
+    Spooled { path: std::path::PathBuf, size: u64 },
+}
+
+impl Payload {
+    /// Returns the buffered bytes; metadata objects (commits, trees, files) are never spooled
+    fn into_bytes(self) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        match self {
+            Payload::Memory(data) => Ok(data),
+            Payload::Spooled { path, size } => {
+                let _ = std::fs::remove_file(&path);
+                Err(format!("Object of {} bytes is too large to be a metadata object", size).into())
+            }
+        }
+    }
+}
+
+/// Maximum number of times a single object is requested before a checksum mismatch is fatal
+const MAX_OBJECT_ATTEMPTS: u32 = 3;
+
+/// Sends `request` and receives the object it names, `object_id`, returning (id, type, payload).
+///
+/// Each payload must hash to `object_id`, so a server can't poison the VOS; a corrupted
+/// payload is discarded and re-requested in case the link is flaky.
+async fn receive_object<R, W>(
+    reader: &mut R,
+    writer: &mut W,
+    request: vnp::VnpCommand,
+    kind: &str,
+    object_id: &str,
+    stats: &transfer::TransferStats,
+) -> Result<(objects::ObjectId, String, Payload), Box<dyn std::error::Error>>
+where
+    R: tokio::io::AsyncReadExt + Unpin,
+    W: tokio::io::AsyncWriteExt + Unpin,
+{
+    if !hosted::is_object_id(object_id) {
+        return Err(vnp::VnpError::InvalidObjectId(object_id.to_string()).into());
+    }
+    let mut attempt = 1;
+    loop {
+        vnp::send_command(writer, request.clone()).await?;
+        
+        match vnp::recv_command(reader).await? {
+            vnp::VnpCommand::ObjectHeader { id, .. } if id != object_id => {
+                return Err(vnp::VnpError::WrongObject { requested: object_id.to_string(), sent: id }.into());
+            }
+            vnp::VnpCommand::ObjectHeader { id, object_type, size, .. } => {
+                let (payload, verified) = if size > vnp::STREAMING_THRESHOLD {
+                    let path = vos::temp_object_path(&id)?;
+                    if let Some(parent) = path.parent() {
+                        std::fs::create_dir_all(parent)?;
+                    }
+                    let mut file = tokio::fs::File::create(&path).await?;
+                    let actual = vnp::recv_object_to_file(reader, size, &mut file).await?;
+                    let verified = vnp::verify_hash(&id, actual);
+                    if verified.is_err() {
+                        let _ = std::fs::remove_file(&path);
+                    }
+                    (Payload::Spooled { path, size }, verified)
+                } else {
+                    let data = vnp::recv_object_data(reader, size).await?;
+                    let verified = vnp::verify_object(&id, &data);
+                    (Payload::Memory(data), verified)
+                };
+                match verified {
+                    Ok(()) => {
+                        stats.record_received(&object_type, size);
+                        return Ok((id, object_type, payload));
+                    }
+                    Err(e) if attempt < MAX_OBJECT_ATTEMPTS => {
+                        attempt += 1;
+                        stats.note(&format!("  ⚠️ {} - re-requesting (attempt {}/{})", e, attempt, MAX_OBJECT_ATTEMPTS));
+                    }
+                    Err(e) => return Err(e.into()),
+                }
+            }
+            vnp::VnpCommand::Error(message) => {
+                return Err(vnp::VnpError::ObjectUnavailable { kind: kind.to_string(), id: object_id.to_string(), message }.into());
+            }
+            response => return Err(vnp::VnpError::response("object transfer", response).into()),
+        }
+    }
+}
+
 /// Check if an object exists locally in VOS
 fn object_exists_locally(object_id: &str) -> bool {
-    let (prefix, suffix) = object_id.split_at(2);
-    let object_path = std::path::Path::new(".orb")
-        .join("objects")
-        .join(prefix)
-        .join(suffix);
-    
-    object_path.exists()
+    vos::store().contains(object_id)
 }
 
 
@@ -831,10 +3138,10 @@ fn is_valid_email(email: &str) -> bool {
 }
 
 /// Register a new user on an Orbit server
-async fn register_user(email: &str, server: &str, username: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
-    println!("📝 Registering new user account...");
-    println!("📧 Email: {}", email);
-    println!("🌐 Server: {}", server);
+async fn register_user(email: &str, server: &str, _username: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+    info!("📝 Registering new user account...");
+    info!("📧 Email: {}", email);
+    info!("🌐 Server: {}", server);
     
     // Validate email format
     if !is_valid_email(email) {
@@ -845,14 +3152,14 @@ async fn register_user(email: &str, server: &str, username: Option<&str>) -> Res
     let username = email;
     let namespace = email.split('@').next().unwrap_or("user");
     
-    println!("👤 Username: {} (email-based for security)", username);
-    println!("🏷️  Namespace: {} (auto-granted access to {}//*)", namespace, namespace);
+    info!("👤 Username: {} (email-based for security)", username);
+    info!("🏷️  Namespace: {} (auto-granted access to {}//*)", namespace, namespace);
     
     // Parse server URL to get admin API endpoint
     let orbit_url = client_tls::OrbitUrl::parse(server)?;
-    let admin_api_url = format!("http://{}:8081/admin/users", orbit_url.host);
+    let admin_api_url = format!("http://{}:{}/admin/users", orbit_url.url_host(), access::ADMIN_API_PORT);
     
-    println!("🔗 Connecting to Admin API: {}", admin_api_url);
+    info!("🔗 Connecting to Admin API: {}", admin_api_url);
     
     // Create user registration request - server will auto-grant namespace access
     let registration_request = serde_json::json!({
@@ -866,12 +3173,13 @@ async fn register_user(email: &str, server: &str, username: Option<&str>) -> Res
     });
     
     // Send registration request to Admin API
+    // Servers without open registration only accept requests carrying the admin token
     let client = reqwest::Client::new();
-    let response = client
-        .post(&admin_api_url)
-        .json(&registration_request)
-        .send()
-        .await?;
+    let mut request = client.post(&admin_api_url).json(&registration_request);
+    if let Ok(admin_token) = std::env::var("ORB_ADMIN_TOKEN") {
+        request = request.bearer_auth(admin_token);
+    }
+    let response = request.send().await?;
     
     if response.status().is_success() {
         let result: serde_json::Value = response.json().await?;
@@ -879,12 +3187,12 @@ async fn register_user(email: &str, server: &str, username: Option<&str>) -> Res
         if let Some(token) = result.get("token").and_then(|t| t.as_str()) {
             println!("🎉 Registration successful!");
             println!("🔑 Your authentication token: {}", token);
-            println!("");
+            println!();
             println!("💡 To use your token:");
             println!("   export ORBIT_TOKEN=\"{}\"", token);
-            println!("");
+            println!();
             println!("🚀 You can now create repositories:");
-            println!("   orb push orbits://{}:{}/{}/my-project", orbit_url.host, orbit_url.port, username);
+            println!("   orb push orbits://{}:{}/{}/my-project", orbit_url.url_host(), orbit_url.port, username);
             
             // Save token to user's home directory
             if let Ok(home_dir) = std::env::var("HOME").or_else(|_| std::env::var("USERPROFILE")) {
@@ -902,395 +3210,956 @@ async fn register_user(email: &str, server: &str, username: Option<&str>) -> Res
         return Err(format!("Registration failed: {}", error_text).into());
     }
     
-    Ok(())
-}
-
-/// List available repositories on a remote server
-async fn list_repositories(url: &str) -> Result<(), Box<dyn std::error::Error>> {
-    println!("🔍 Listing repositories on server: {}", url);
-    
-    // Parse the URL to determine TLS requirements
-    let orbit_url = client_tls::OrbitUrl::parse(url)?;
-    
-    println!("🌐 Connecting to {}:{}...", orbit_url.host, orbit_url.port);
-    
-    // Establish connection
-    if orbit_url.use_tls {
-        let tls_client = client_tls::ClientTls::new_insecure()?;
-        let tls_stream = tls_client.connect(&orbit_url.host, orbit_url.port, &orbit_url.server_name).await?;
-        let (mut reader, mut writer) = tokio::io::split(tls_stream);
-        list_repositories_impl(&mut reader, &mut writer).await
-    } else {
-        let stream = tokio::net::TcpStream::connect(format!("{}:{}", orbit_url.host, orbit_url.port)).await?;
-        let (mut reader, mut writer) = stream.into_split();
-        list_repositories_impl(&mut reader, &mut writer).await
-    }
-}
-
-/// Implementation of repository listing
-async fn list_repositories_impl<R, W>(
-    reader: &mut R,
-    writer: &mut W,
-) -> Result<(), Box<dyn std::error::Error>>
-where
-    R: tokio::io::AsyncReadExt + Unpin,
-    W: tokio::io::AsyncWriteExt + Unpin,
-{
-    // Authenticate first - load token from environment or file
-    let token = match std::env::var("ORBIT_TOKEN") {
-        Ok(token) => {
-            println!("🔑 Using environment token");
-            token
-        }
-        Err(_) => {
-            // Try to read from saved token file in home directory
-            if let Ok(home_dir) = std::env::var("HOME").or_else(|_| std::env::var("USERPROFILE")) {
-                let token_file = std::path::Path::new(&home_dir).join(".orb_token");
-                match std::fs::read_to_string(&token_file) {
-                    Ok(token) => {
-                        println!("🔑 Using saved authentication token");
-                        token.trim().to_string()
-                    },
-                    Err(_) => {
-                        eprintln!("❌ No authentication token found.");
-                        eprintln!("💡 Register for a new account: orb register --email your@email.com --server orbit.privapulse.com:8082");
-                        eprintln!("💡 Or set existing token: export ORBIT_TOKEN=\"your-token-here\"");
-                        return Err("Authentication token required".into());
-                    }
-                }
-            } else {
-                eprintln!("❌ Cannot find home directory for token storage");
-                return Err("Authentication token required".into());
-            }
-        }
-    };
+    Ok(())
+}
+
+/// List available repositories on a remote server
+///
+/// With `json`, only the JSON document is written to stdout so it can be piped.
+async fn list_repositories(target: Option<&str>, json: bool, net: &transport::NetOptions) -> Result<(), Box<dyn std::error::Error>> {
+    let remote = remote::Remote::resolve(target)?;
     
-    println!("🔐 Authenticating with server...");
-    vnp::send_command(writer, vnp::VnpCommand::Authenticate(token)).await?;
+    // Parse the URL to determine TLS requirements
+    let orbit_url = remote.orbit_url()?;
     
-    // Wait for authentication result
-    match vnp::recv_command(reader).await? {
-        vnp::VnpCommand::AuthResult { success, message } => {
-            if success {
-                println!("✅ Authenticated successfully");
-            } else {
-                eprintln!("❌ Authentication failed: {}", message);
-                return Err("Authentication failed".into());
-            }
-        }
-        vnp::VnpCommand::Error(msg) => {
-            eprintln!("❌ Server error during authentication: {}", msg);
-            return Err("Authentication error".into());
-        }
-        _ => {
-            return Err("Unexpected authentication response".into());
-        }
+    if !json {
+        info!("🔍 Listing repositories on server: {}", remote.url);
+        info!("🌐 Connecting to {}:{}...", orbit_url.url_host(), orbit_url.port);
     }
     
-    // Send list repositories command
-    vnp::send_command(writer, vnp::VnpCommand::ListRepositories).await?;
+    // Establish connection
+    let orbit_url = &orbit_url;
+    let remote = &remote;
+    let wire = &transport::WireCounters::default();
+    let repos = transport::with_retries(net, || async move {
+        // Listing is read-only, so an anonymous session sees the public repositories
+        let mut session = client::Session::connect(orbit_url, net, wire).await?;
+        session.quiet = json;
+        session.authenticate_for_read(remote).await?;
+        session.list_repositories().await
+    }).await?;
     
-    // Receive repository list
-    match vnp::recv_command(reader).await? {
-        vnp::VnpCommand::RepositoryList(repos) => {
-            if repos.is_empty() {
-                println!("📂 No repositories found on server");
-            } else {
-                println!("📂 Available repositories ({}):", repos.len());
-                for (i, repo) in repos.iter().enumerate() {
-                    println!("  {}. {}", i + 1, repo);
-                }
-            }
-            Ok(())
-        }
-        vnp::VnpCommand::Error(msg) => {
-            Err(format!("Server error: {}", msg).into())
-        }
-        _ => {
-            Err("Unexpected response from server".into())
+    if json {
+        let records: Vec<serde_json::Value> = repos.iter().map(repository_record).collect();
+        println!("{}", serde_json::to_string_pretty(&records)?);
+    } else {
+        print_repository_table(&repos);
+    }
+    Ok(())
+}
+
+/// A repository as printed by `--json`: unlike the wire format, every field is present,
+/// null when the server does not report it
+fn repository_record(repo: &vnp::RepositoryInfo) -> serde_json::Value {
+    serde_json::json!({
+        "name": repo.name,
+        "description": repo.description,
+        "default_branch": repo.default_branch,
+        "head": repo.head,
+        "size": repo.size,
+        "last_updated": repo.last_updated,
+        "quota": repo.quota,
+    })
+}
+
+/// Renders repositories as an aligned table
+fn print_repository_table(repos: &[vnp::RepositoryInfo]) {
+    if repos.is_empty() {
+        println!("📂 No repositories found on server");
+        return;
+    }
+    
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0);
+    let rows: Vec<[String; 6]> = repos.iter().map(|repo| [
+        repo.name.clone(),
+        repo.default_branch.clone().unwrap_or_else(|| "-".to_string()),
+        repo.head.as_deref().map(|id| id[..7.min(id.len())].to_string()).unwrap_or_else(|| "-".to_string()),
+        repo.size.map(transfer::format_bytes).unwrap_or_else(|| "-".to_string()),
+        repo.last_updated.map(|ts| format_age(now - ts)).unwrap_or_else(|| "-".to_string()),
+        repo.description.clone().unwrap_or_default(),
+    ]).collect();
+    
+    let headers = ["NAME", "BRANCH", "HEAD", "SIZE", "UPDATED", "DESCRIPTION"];
+    let mut widths = headers.map(str::len);
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
         }
     }
+    
+    println!("📂 Available repositories ({}):", repos.len());
+    let format_row = |cells: [&str; 6]| {
+        let line: Vec<String> = cells.iter().zip(widths).map(|(cell, width)| format!("{:<width$}", cell, width = width)).collect();
+        println!("  {}", line.join("  ").trim_end());
+    };
+    format_row(headers);
+    for row in &rows {
+        format_row(row.each_ref().map(String::as_str));
+    }
+}
+
+/// Renders one repository for 'orb repo info'
+fn print_repository_info(repo: &vnp::RepositoryInfo) {
+    println!("📦 {}", repo.name);
+    if let Some(description) = &repo.description {
+        println!("   {}", description);
+    }
+    println!("   Default branch: {}", repo.default_branch.as_deref().unwrap_or("-"));
+    println!("   Head:           {}", repo.head.as_deref().map(|id| &id[..7.min(id.len())]).unwrap_or("-"));
+    if let Some(updated) = repo.last_updated {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or(0);
+        println!("   Updated:        {}", format_age(now - updated));
+    }
+    match (repo.size, repo.quota) {
+        (Some(size), Some(quota)) => println!(
+            "   Storage:        {} of {} ({:.0}%)",
+            transfer::format_bytes(size),
+            transfer::format_bytes(quota),
+            size as f64 * 100.0 / quota.max(1) as f64
+        ),
+        (Some(size), None) => println!("   Storage:        {} (no quota)", transfer::format_bytes(size)),
+        (None, _) => println!("   Storage:        unknown"),
+    }
+}
+
+/// Formats an age in seconds as a short relative time (e.g. `5m ago`, `3d ago`)
+fn format_age(seconds: i64) -> String {
+    match seconds {
+        s if s < 60 => "just now".to_string(),
+        s if s < 3600 => format!("{}m ago", s / 60),
+        s if s < 86400 => format!("{}h ago", s / 3600),
+        s if s < 86400 * 365 => format!("{}d ago", s / 86400),
+        s => format!("{}y ago", s / (86400 * 365)),
+    }
 }
 
 /// Clone a repository from a remote server
-async fn clone_repository(url: &str, directory: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
-    println!("📥 Cloning repository from: {}", url);
+async fn clone_repository(
+    target: &str,
+    directory: Option<&str>,
+    branch: Option<&str>,
+    no_checkout: bool,
+    create: bool,
+    net: &transport::NetOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let remote = remote::Remote::resolve(Some(target))?;
+    info!("📥 Cloning repository from: {}", remote.url);
     
     // Parse the full URL to extract repository information
-    let orbit_url = client_tls::OrbitUrl::parse(url)?;
+    let orbit_url = remote.orbit_url()?;
     let repo_name = orbit_url.repository.as_deref();
     
     // Determine local directory name
     let local_dir = match directory {
         Some(dir) => dir.to_string(),
-        None => repo_name.unwrap_or("orbit-repo").to_string(),
+        None => repo_name
+            .and_then(|name| name.rsplit('/').next())
+            .unwrap_or("orbit-repo")
+            .to_string(),
     };
     
     // Create local directory and initialize
+    let original_dir = std::env::current_dir()?;
+    let created_dir = !std::path::Path::new(&local_dir).exists();
     std::fs::create_dir_all(&local_dir)?;
     std::env::set_current_dir(&local_dir)?;
     
     // Initialize Orbit repository
     repo::init()?;
-    println!("✅ Initialized local repository in: {}", local_dir);
+    remote::set_origin(&remote.url, branch)?;
+    info!("✅ Initialized local repository in: {}", local_dir);
+    let refs_before = refs::snapshot();
     
     // Connect and sync
-    println!("🌐 Connecting to {}:{}...", orbit_url.host, orbit_url.port);
+    info!("🌐 Connecting to {}:{}...", orbit_url.url_host(), orbit_url.port);
     
-    if orbit_url.use_tls {
-        let tls_client = client_tls::ClientTls::new_insecure()?;
-        let tls_stream = tls_client.connect(&orbit_url.host, orbit_url.port, &orbit_url.server_name).await?;
-        let (mut reader, mut writer) = tokio::io::split(tls_stream);
-        clone_repository_impl(&mut reader, &mut writer, repo_name).await
-    } else {
-        let stream = tokio::net::TcpStream::connect(format!("{}:{}", orbit_url.host, orbit_url.port)).await?;
-        let (mut reader, mut writer) = stream.into_split();
-        clone_repository_impl(&mut reader, &mut writer, repo_name).await
+    let orbit_url = &orbit_url;
+    let remote = &remote;
+    let stats = &transfer::TransferStats::new();
+    let result = transport::with_retries(net, || async move {
+        let mut session = client::Session::connect(orbit_url, net, stats.wire()).await?;
+        let outcome = tokio::select! {
+            result = clone_repository_impl(&mut session, repo_name, branch, create, remote, stats) => Some(result),
+            _ = tokio::signal::ctrl_c() => None,
+        };
+        match outcome {
+            Some(result) => result,
+            None => Err(abort_session(&mut session.writer).await),
+        }
+    }).await;
+    
+    // Don't leave an empty repository behind when nothing could be cloned
+    // (a cancelled clone keeps its directory so it can be resumed)
+    if let Err(e) = result {
+        if created_dir && !e.is::<transport::Cancelled>() {
+            std::env::set_current_dir(&original_dir)?;
+            let _ = std::fs::remove_dir_all(&local_dir);
+        }
+        return Err(e);
+    }
+    
+    refs::update_remote_tracking(remote::DEFAULT_REMOTE, &refs::current_branch())?;
+    stats.print_summary();
+    verify::check_transfer(&refs_before, repo_name)?;
+    
+    // Materialize the default branch in the working directory
+    if no_checkout {
+        info!("💡 Skipped checkout (--no-checkout); run `orb checkout` to populate the working directory");
+    } else if refs::head_commit().is_some() {
+        info!("");
+        checkout_commit(None)?;
     }
+    Ok(())
 }
 
 /// Implementation of repository cloning
-async fn clone_repository_impl<R, W>(
-    reader: &mut R,
-    writer: &mut W,
+async fn clone_repository_impl(
+    session: &mut client::Session,
     repo_name: Option<&str>,
-) -> Result<(), Box<dyn std::error::Error>>
-where
-    R: tokio::io::AsyncReadExt + Unpin,
-    W: tokio::io::AsyncWriteExt + Unpin,
-{
-    // Authenticate first - load token from environment or file
-    let token = match std::env::var("ORBIT_TOKEN") {
-        Ok(token) => {
-            println!("🔑 Using environment token");
-            token
-        }
-        Err(_) => {
-            // Try to read from saved token file in home directory
-            if let Ok(home_dir) = std::env::var("HOME").or_else(|_| std::env::var("USERPROFILE")) {
-                let token_file = std::path::Path::new(&home_dir).join(".orb_token");
-                match std::fs::read_to_string(&token_file) {
-                    Ok(token) => {
-                        println!("🔑 Using saved authentication token");
-                        token.trim().to_string()
-                    },
-                    Err(_) => {
-                        eprintln!("❌ No authentication token found.");
-                        eprintln!("💡 Register for a new account: orb register --email your@email.com --server orbit.privapulse.com:8082");
-                        eprintln!("💡 Or set existing token: export ORBIT_TOKEN=\"your-token-here\"");
-                        return Err("Authentication token required".into());
-                    }
-                }
-            } else {
-                eprintln!("❌ Cannot find home directory for token storage");
-                return Err("Authentication token required".into());
-            }
-        }
-    };
-    
-    println!("🔐 Authenticating with server...");
-    vnp::send_command(writer, vnp::VnpCommand::Authenticate(token)).await?;
-    
-    // Wait for authentication result
-    match vnp::recv_command(reader).await? {
-        vnp::VnpCommand::AuthResult { success, message } => {
-            if success {
-                println!("✅ Authenticated successfully");
-            } else {
-                eprintln!("❌ Authentication failed: {}", message);
-                return Err("Authentication failed".into());
-            }
-        }
-        vnp::VnpCommand::Error(msg) => {
-            eprintln!("❌ Server error during authentication: {}", msg);
-            return Err("Authentication error".into());
-        }
-        _ => {
-            return Err("Unexpected authentication response".into());
-        }
+    branch: Option<&str>,
+    create: bool,
+    remote: &remote::Remote,
+    stats: &transfer::TransferStats,
+) -> Result<(), Box<dyn std::error::Error>> {
+    // Creating a repository is a write; a plain clone also works anonymously for public repositories
+    if create {
+        session.authenticate(remote.token_env.as_deref()).await?;
+    } else {
+        session.authenticate_for_read(remote).await?;
     }
     
-    // If specific repository requested, select it first
+    // If specific repository requested, select it first. Repositories are only
+    // created when asked for, so a mistyped name cannot leave junk on the server.
     if let Some(repo) = repo_name {
-        println!("📂 Selecting repository: {}", repo);
-        vnp::send_command(writer, vnp::VnpCommand::SelectRepository(repo.to_string())).await?;
-        
-        match vnp::recv_command(reader).await? {
-            vnp::VnpCommand::RepositorySelected(selected) => {
-                println!("✅ Selected repository: {}", selected);
-            }
-            vnp::VnpCommand::Error(msg) => {
-                // If repository doesn't exist, try to create it
-                if msg.contains("not found") {
-                    println!("📂 Repository '{}' not found, creating it...", repo);
-                    vnp::send_command(writer, vnp::VnpCommand::CreateRepository(repo.to_string())).await?;
-                    
-                    match vnp::recv_command(reader).await? {
-                        vnp::VnpCommand::RepositorySelected(created) => {
-                            println!("✅ Created and selected repository: {}", created);
-                        }
-                        vnp::VnpCommand::Error(create_msg) => {
-                            return Err(format!("Failed to create repository '{}': {}", repo, create_msg).into());
-                        }
-                        _ => {
-                            return Err("Unexpected response to repository creation".into());
-                        }
-                    }
-                } else {
-                    return Err(format!("Failed to select repository '{}': {}", repo, msg).into());
+        match session.select(repo).await {
+            Err(e) if matches!(e.downcast_ref(), Some(vnp::VnpError::NoSuchRepository(_))) => {
+                if !create {
+                    warn!("💡 Check the name with `orb list-repos`, or create it with `orb clone --create` or `orb repo create`.");
+                    return Err(e);
                 }
+                info!("📂 Repository '{}' not found, creating it (--create)...", repo);
+                session.create_repository(repo).await?;
             }
-            _ => {
-                return Err("Unexpected response to repository selection".into());
-            }
+            result => result?,
         }
     }
     
+    // Single-branch clone: fetch only the named branch's history
+    if let Some(branch) = branch {
+        return clone_single_branch(session, repo_name, branch, stats).await;
+    }
+    
     // Now perform standard sync to download all commits
-    println!("📥 Downloading repository content...");
+    info!("📥 Downloading repository content...");
     
     // Use the same sync logic as run_sync but with existing reader/writer
     let local_commits = repo::get_local_commits().unwrap_or_default();
-    println!("📋 Negotiating with server ({} local commits)...", local_commits.len());
-
-    // Send our commit list to server (HAVE)
-    vnp::send_command(writer, vnp::VnpCommand::Have(local_commits.clone())).await?;
-
-    // Receive server's response (WANT)  
-    let missing_commits = match vnp::recv_command(reader).await? {
-        vnp::VnpCommand::Want(commits) => commits,
-        vnp::VnpCommand::Error(msg) => return Err(format!("Server error: {}", msg).into()),
-        _ => return Err("Expected WANT response from server".into()),
-    };
+    info!("📋 Negotiating with server ({} local commits)...", local_commits.len());
+    let missing_commits = session.negotiate(&local_commits).await?;
 
     if missing_commits.is_empty() {
-        println!("✅ Already up to date!");
+        info!("✅ Already up to date!");
         return Ok(());
     }
 
-    println!("📥 Downloading {} commits from server...", missing_commits.len());
+    info!("📥 Downloading {} commits from server...", missing_commits.len());
+    stats.begin_phase("Downloading", None);
 
     // Download missing commits
     for commit_id in &missing_commits {
-        println!("  📦 Requesting commit: {}", commit_id);
-        vnp::send_command(writer, vnp::VnpCommand::Get(commit_id.clone())).await?;
-
-        match vnp::recv_command(reader).await? {
-            vnp::VnpCommand::ObjectHeader { id, object_type, size } => {
-                println!("  📄 Receiving {} object ({} bytes)...", object_type, size);
-                let object_data = vnp::recv_object_data(reader, size).await?;
-                store_received_object(&id, &object_type, &object_data)?;
-                println!("  ✅ Stored {} successfully", id);
-            }
-            vnp::VnpCommand::Error(msg) => {
-                return Err(format!("Failed to get commit {}: {}", commit_id, msg).into());
-            }
-            _ => {
-                return Err(format!("Unexpected response for commit {}", commit_id).into());
-            }
-        }
+        let (id, object_type, payload) = receive_object(
+            &mut session.reader, &mut session.writer, vnp::VnpCommand::Get(commit_id.clone()), "commit", commit_id, stats,
+        ).await?;
+        store_received_object(&id, &object_type, &payload.into_bytes()?)?;
     }
 
-    println!("✅ Downloaded {} commits successfully!", missing_commits.len());
-
     // Download complete object graphs
-    println!("📥 Downloading complete object graphs...");
+    journal::SyncJournal::begin(repo_name, &missing_commits)?;
     for commit_id in &missing_commits {
-        download_complete_object_graph(reader, writer, commit_id).await?;
+        download_complete_object_graph(&mut session.reader, &mut session.writer, commit_id, stats).await?;
+        journal::SyncJournal::complete(commit_id)?;
+    }
+    stats.end_phase();
+    info!("✅ Downloaded {} commits with complete object graphs", missing_commits.len());
+    
+    // Bring over release tags along with the branch
+    let remote_refs = session.list_refs().await?;
+    fetch_tags(&mut session.reader, &mut session.writer, &remote_refs.tags, &remote_refs.annotations, repo_name, stats).await?;
+    
+    // Follow the server's default branch if it is the only one and not ours
+    if !remote_refs.branches.contains_key(&refs::current_branch()) && remote_refs.branches.len() == 1 {
+        if let Some(branch) = remote_refs.branches.keys().next() {
+            refs::set_head_branch(branch)?;
+        }
     }
+    let advertised_tip = remote_refs.branches.get(&refs::current_branch()).cloned();
 
-    // Signal completion
-    vnp::send_command(writer, vnp::VnpCommand::Ready).await?;
+    session.finish().await?;
+    info!("✅ Sync completed successfully!");
+    
+    // Point HEAD at the branch tip after cloning
+    repo::update_head_after_sync(&missing_commits, advertised_tip.as_deref())?;
+    
+    info!("✅ Repository cloned successfully!");
+    Ok(())
+}
 
-    // Wait for server confirmation
-    match vnp::recv_command(reader).await? {
-        vnp::VnpCommand::Ok => {
-            println!("✅ Sync completed successfully!");
+/// Clones only `branch`: its history and the tags that point into it
+async fn clone_single_branch(
+    session: &mut client::Session,
+    repo_name: Option<&str>,
+    branch: &str,
+    stats: &transfer::TransferStats,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let remote_refs = session.list_refs().await?;
+    let (reader, writer) = (&mut session.reader, &mut session.writer);
+    let Some(tip) = remote_refs.branches.get(branch).cloned() else {
+        let available: Vec<&str> = remote_refs.branches.keys().map(String::as_str).collect();
+        return Err(format!("Remote has no branch '{}' (available: {})", branch, available.join(", ")).into());
+    };
+    
+    info!("🌿 Cloning branch '{}' only ({} branches on server)", branch, remote_refs.branches.len());
+    download_commit_history(reader, writer, &tip, repo_name, stats).await?;
+    refs::write_ref(&refs::branch_ref(branch), &tip)?;
+    refs::set_head_branch(branch)?;
+    
+    // Tags on other branches would drag their history along, so skip them
+    let branch_tags = remote_refs.tags.into_iter()
+        .filter(|(_, target)| ancestry::is_ancestor(target, &tip))
+        .collect();
+    fetch_tags(reader, writer, &branch_tags, &remote_refs.annotations, repo_name, stats).await?;
+    
+    session.finish().await?;
+    info!("📍 HEAD is now on '{}' at {}", branch, &tip[..7.min(tip.len())]);
+    Ok(())
+}
+
+/// Implementation of 'orb mirror': copies all refs and objects from `source` to `destination`,
+/// once or every `interval`
+async fn run_mirror(
+    source: &str,
+    destination: &str,
+    interval: Option<std::time::Duration>,
+    cache_dir: Option<&std::path::Path>,
+    net: &transport::NetOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    // Resolve remote names before leaving the current repository
+    let (source, source_url) = resolve_repository_url(source, "mirror from")?;
+    let (destination, destination_url) = resolve_repository_url(destination, "mirror to")?;
+    if destination.is_peer() {
+        return Err("A peer share is read-only and cannot be a mirror destination".into());
+    }
+    
+    // Objects pass through a private cache repository, so later runs only transfer what changed
+    let cache_dir = match cache_dir {
+        Some(dir) => dir.to_path_buf(),
+        None => {
+            let home = std::env::var("HOME").or_else(|_| std::env::var("USERPROFILE"))
+                .map_err(|_| "Cannot find home directory for the mirror cache (use --cache-dir)")?;
+            let name: String = format!("{}_{}_{}", source_url.host, source_url.port, source_url.repository.as_deref().unwrap_or_default())
+                .chars()
+                .map(|c| if c.is_ascii_alphanumeric() || matches!(c, '-' | '.') { c } else { '_' })
+                .collect();
+            std::path::Path::new(&home).join(".orb-mirror").join(name)
         }
-        vnp::VnpCommand::Error(msg) => {
-            return Err(format!("Sync failed: {}", msg).into());
+    };
+    std::fs::create_dir_all(&cache_dir)?;
+    std::env::set_current_dir(&cache_dir)?;
+    if !std::path::Path::new(".orb").exists() {
+        repo::init()?;
+    }
+    info!("🪞 Mirroring {} → {} (cache: {})", source.url, destination.url, cache_dir.display());
+    
+    // Both sessions stay open between runs; a failed run drops them so the next one reconnects
+    let mut sessions = None;
+    let wire = transport::WireCounters::default();
+    loop {
+        let outcome = tokio::select! {
+            result = mirror_once(&mut sessions, &wire, &source, &source_url, &destination, &destination_url, net) => Some(result),
+            _ = tokio::signal::ctrl_c() => None,
+        };
+        match (outcome, interval) {
+            (None, _) => return Err(transport::Cancelled.into()),
+            (Some(result), None) => return result,
+            (Some(Ok(())), Some(_)) => {}
+            (Some(Err(e)), Some(_)) => {
+                error!("❌ Mirror run failed: {} (will retry)", e);
+                sessions = None;
+            }
         }
-        _ => {
-            return Err("Unexpected response from server".into());
+        
+        let interval = interval.unwrap_or_default();
+        info!("⏳ Next mirror run in {}s (Ctrl-C to stop)", interval.as_secs());
+        tokio::select! {
+            _ = tokio::time::sleep(interval) => {}
+            _ = tokio::signal::ctrl_c() => return Ok(()),
         }
     }
+}
+
+/// One mirror pass: fetch every ref from the source, then force the destination to match.
+/// Opens the source and destination sessions on the first run and reuses them afterwards.
+async fn mirror_once(
+    sessions: &mut Option<(client::Session, client::Session)>,
+    wire: &transport::WireCounters,
+    source: &remote::Remote,
+    source_url: &client_tls::OrbitUrl,
+    destination: &remote::Remote,
+    destination_url: &client_tls::OrbitUrl,
+    net: &transport::NetOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let stats = &transfer::TransferStats::continuing(wire);
     
-    // Update HEAD to point to the latest commit after cloning
-    if !missing_commits.is_empty() {
-        repo::update_head_after_sync(&missing_commits)?;
-        println!("📍 Updated HEAD to: {}", missing_commits.last().unwrap());
+    if sessions.is_none() {
+        let source_session = transport::with_retries(net, || {
+            client::Session::open(source, source_url, false, net, stats.wire())
+        }).await?;
+        let destination_session = transport::with_retries(net, || {
+            client::Session::open(destination, destination_url, true, net, stats.wire())
+        }).await?;
+        *sessions = Some((source_session, destination_session));
     }
+    let Some((source_session, destination_session)) = sessions.as_mut() else {
+        unreachable!("mirror sessions were just opened");
+    };
+    
+    info!("📥 Fetching refs and objects from {}", source.url);
+    let source_refs = mirror_fetch(source_session, source_url.repository.as_deref(), stats).await?;
     
-    println!("✅ Repository cloned successfully!");
+    info!("📤 Updating {}", destination.url);
+    mirror_push(destination_session, &source_refs, stats).await?;
+    
+    stats.print_summary();
     Ok(())
 }
 
+/// Downloads every advertised branch and tag into the mirror cache and returns the source's refs
+async fn mirror_fetch(
+    session: &mut client::Session,
+    repository: Option<&str>,
+    stats: &transfer::TransferStats,
+) -> Result<vnp::RefAdvertisement, Box<dyn std::error::Error>> {
+    let source_refs = session.list_refs().await?;
+    let (reader, writer) = (&mut session.reader, &mut session.writer);
+    for (branch, tip) in &source_refs.branches {
+        info!("🌿 Fetching branch '{}'", branch);
+        download_commit_history(reader, writer, tip, repository, stats).await?;
+    }
+    for target in source_refs.tags.values() {
+        download_commit_history(reader, writer, target, repository, stats).await?;
+    }
+    
+    // The cache's tags are exactly the source's, so tags deleted upstream are not pushed again
+    let _ = std::fs::remove_dir_all(std::path::Path::new(".orb").join("refs").join("tags"));
+    let _ = std::fs::remove_dir_all(std::path::Path::new(".orb").join("refs").join("annotations"));
+    for (name, target) in &source_refs.tags {
+        refs::write_ref(&refs::tag_ref(name), target)?;
+    }
+    for (name, annotation) in &source_refs.annotations {
+        if let Some(target) = source_refs.tags.get(name) {
+            fetch_annotation(reader, writer, name, annotation, target, stats).await?;
+        }
+    }
+    
+    session.finish().await?;
+    Ok(source_refs)
+}
+
+/// Uploads missing history to the destination and forces its branches and tags to the source's
+async fn mirror_push(
+    session: &mut client::Session,
+    source_refs: &vnp::RefAdvertisement,
+    stats: &transfer::TransferStats,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let destination_refs = session.list_refs().await?;
+    let (reader, writer) = (&mut session.reader, &mut session.writer);
+    let mut updated = 0;
+    for (branch, tip) in &source_refs.branches {
+        let old = destination_refs.branches.get(branch).cloned();
+        if old.as_ref() == Some(tip) {
+            continue;
+        }
+        
+        // Upload the commits the destination's current tip does not already contain
+        let known = old.as_deref().map(ancestry::ancestors).unwrap_or_default();
+        let commits: Vec<objects::ObjectId> = ancestry::ancestors(tip).into_iter()
+            .filter(|commit| !known.contains(commit))
+            .collect();
+        upload_commits(reader, writer, &commits, stats).await?;
+        
+        let update = RefUpdate { branch: branch.clone(), old, new: tip.clone(), force: true };
+        update_remote_ref(reader, writer, &update).await?;
+        updated += 1;
+    }
+    for branch in destination_refs.branches.keys().filter(|b| !source_refs.branches.contains_key(*b)) {
+        warn!("⚠️  Branch '{}' exists only on the destination; left unchanged", branch);
+    }
+    if updated == 0 {
+        info!("✅ All branches already mirrored");
+    }
+    
+    for target in source_refs.tags.values() {
+        if !destination_refs.tags.values().any(|t| t == target) {
+            let commits: Vec<objects::ObjectId> = ancestry::ancestors(target).into_iter().collect();
+            upload_commits(reader, writer, &commits, stats).await?;
+        }
+    }
+    push_local_tags(reader, writer, &destination_refs, true, stats).await?;
+    
+    session.finish().await
+}
+
 #[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
+async fn main() -> Result<ExitCode, Box<dyn std::error::Error>> {
     let cli = OrbCli::parse();
+    style::init(cli.color);
+    // The fast-export stream is written to stdout, so its messages must stay out of it
+    logging::init(cli.verbose, cli.quiet, matches!(cli.command, Commands::FastExport { .. }));
 
     match &cli.command {
-        Commands::Init => {
-            if let Err(e) = repo::init() {
-                eprintln!("❌ Initialization failed: {}", e);
+        Commands::Init { path, bare, template, from_git } => {
+            let result = match from_git {
+                Some(path) => fetch::convert_local_repository(path),
+                None => {
+                    let path = path.as_deref().unwrap_or(std::path::Path::new("."));
+                    repo::init_at(path, *bare, template.as_deref()).map_err(Into::into)
+                }
+            };
+            if let Err(e) = result {
+                return Ok(exit::failure("Initialization failed", e));
             }
         },
-        Commands::Save { message } => {
-            match repo::save_snapshot(message) {
+        Commands::Save { message, amend, paths } => {
+            match repo::save_snapshot(message.as_deref(), paths, *amend) {
                 Ok(_) => {}, // Success message is printed in repo::save_snapshot
-                Err(e) => eprintln!("❌ Save failed: {}", e),
+                Err(e) => return Ok(exit::failure("Save failed", e)),
+            }
+        },
+        Commands::Add { paths } => {
+            if let Err(e) = repo::stage(paths) {
+                return Ok(exit::failure("Add failed", e));
+            }
+        },
+        Commands::Unstage { paths } => {
+            if let Err(e) = repo::unstage(paths) {
+                return Ok(exit::failure("Unstage failed", e));
             }
         },
         Commands::Check => {
             if let Err(e) = status::check_status() {
-                eprintln!("❌ Status check failed: {}", e);
+                return Ok(exit::failure("Status check failed", e));
+            }
+        },
+        Commands::History { json, author, since, until, paths } => {
+            let filter = history::HistoryFilter { author: author.clone(), since: *since, until: *until, paths: paths.clone() };
+            if let Err(e) = history::show_history(*json, &filter) {
+                return Ok(exit::failure("History display failed", e));
+            }
+        },
+        Commands::Diff { stat, args, paths } => {
+            if let Err(e) = history::show_diff(args, paths, *stat) {
+                return Ok(exit::failure("Diff failed", e));
+            }
+        },
+        Commands::Show { revision, json } => {
+            if let Err(e) = history::show_commit(revision.as_deref().unwrap_or("HEAD"), *json) {
+                return Ok(exit::failure("Show failed", e));
+            }
+        },
+        Commands::Blame { path, revision } => {
+            if let Err(e) = blame::blame(path, revision.as_deref()) {
+                return Ok(exit::failure("Blame failed", e));
+            }
+        },
+        Commands::Revert { files, continue_revert, abort } => {
+            let result = match (files.as_slice(), continue_revert, abort) {
+                (_, true, _) => cherry_pick::resume(cherry_pick::Operation::Revert),
+                (_, _, true) => cherry_pick::abort(cherry_pick::Operation::Revert),
+                ([target], _, _) if repo::open().is_ok_and(|repository| cherry_pick::names_commit(&repository, target)) => {
+                    cherry_pick::apply(cherry_pick::Operation::Revert, target)
+                }
+                _ => history::revert_files(files.clone()),
+            };
+            if let Err(e) = result {
+                return Ok(exit::failure("Revert failed", e));
+            }
+        },
+        Commands::Fetch { url, target, vcs, authors_file, keep_remote, update, depth, recurse_submodules, ssh_key, token_env } if vcs != "git" || authors_file.is_some() => {
+            let git_only = *keep_remote || *update || depth.is_some() || *recurse_submodules || ssh_key.is_some() || token_env.is_some();
+            let result = match url {
+                _ if git_only && vcs != "git" => Err("--keep-remote, --update, --depth, --recurse-submodules, --ssh-key and --token-env only apply to Git repositories".into()),
+                _ if vcs != "svn" => Err("--authors-file only applies to Subversion repositories".into()),
+                Some(url) if vcs == "svn" => svn::fetch_svn_repository(url, target.as_deref(), authors_file.as_deref()),
+                Some(url) => hg::fetch_hg_repository(url, target.as_deref()),
+                None => Err("a repository URL is required".into()),
+            };
+            if let Err(e) = result {
+                return Ok(exit::failure("Fetch failed", e));
+            }
+        },
+        Commands::Fetch { url, target, vcs: _, authors_file: _, keep_remote, update: _, depth, recurse_submodules, ssh_key, token_env } => {
+            let options = fetch::ImportOptions {
+                depth: *depth,
+                recurse_submodules: *recurse_submodules,
+                keep_remote: *keep_remote,
+                auth: git_auth::GitAuth { ssh_key: ssh_key.clone(), token_env: token_env.clone() },
+            };
+            let result = match url {
+                Some(url) => fetch::fetch_git_repository(url, target.as_deref(), &options),
+                None => fetch::update_git_import(&options),
+            };
+            if let Err(e) = result {
+                return Ok(exit::failure("Fetch failed", e));
+            }
+        },
+        Commands::FormatPatch { range, root, output_directory, stdout } => {
+            if let Err(e) = patch::format_patches(range, *root, output_directory, *stdout) {
+                return Ok(exit::failure("Format-patch failed", e));
+            }
+        },
+        Commands::Apply { patches, check } => {
+            if let Err(e) = patch::apply_patches(patches, *check) {
+                return Ok(exit::failure("Apply failed", e));
+            }
+        },
+        Commands::FastExport { refs, import_marks, export_marks } => {
+            if let Err(e) = fast_export::fast_export(refs, import_marks.as_deref(), export_marks.as_deref()) {
+                return Ok(exit::failure("Fast-export failed", e));
+            }
+        },
+        Commands::FastImport { force, import_marks, export_marks } => {
+            if let Err(e) = fast_import::fast_import(*force, import_marks.as_deref(), export_marks.as_deref()) {
+                return Ok(exit::failure("Fast-import failed", e));
+            }
+        },
+        Commands::CatFile { show_type, pretty: _, object } => {
+            let result = match show_type {
+                true => plumbing::cat_file_type(object),
+                false => plumbing::cat_file_pretty(object),
+            };
+            if let Err(e) = result {
+                return Ok(exit::failure("Cat-file failed", e));
+            }
+        },
+        Commands::LsTree { recursive, commit, path } => {
+            if let Err(e) = plumbing::ls_tree(commit, path.as_deref(), *recursive) {
+                return Ok(exit::failure("Ls-tree failed", e));
             }
         },
-        Commands::History => {
-            if let Err(e) = history::show_history() {
-                eprintln!("❌ History display failed: {}", e);
+        Commands::HashObject { write, files } => {
+            if let Err(e) = plumbing::hash_object(files, *write) {
+                return Ok(exit::failure("Hash-object failed", e));
             }
         },
-        Commands::Revert { files } => {
-            if let Err(e) = history::revert_files(files.clone()) {
-                eprintln!("❌ Revert failed: {}", e);
+        Commands::VerifyGraph { commit } => {
+            if let Err(e) = verify::verify_graph(commit.as_deref()) {
+                return Ok(exit::failure("Verify-graph failed", e));
             }
         },
-        Commands::Fetch { url, target } => {
-            if let Err(e) = fetch::fetch_git_repository(url, target.as_deref()) {
-                eprintln!("❌ Fetch failed: {}", e);
+        Commands::LsFiles { long } => {
+            if let Err(e) = plumbing::ls_files(*long) {
+                return Ok(exit::failure("Ls-files failed", e));
             }
         },
-        Commands::Sync { url } => {
-            match run_sync(url).await {
+        Commands::Import { sources, message, date, branch } => {
+            let options = import::SnapshotOptions { message: message.clone(), date: date.clone(), branch: branch.clone() };
+            if let Err(e) = import::import_snapshots(sources, &options) {
+                return Ok(exit::failure("Import failed", e));
+            }
+        },
+        Commands::Sync { url, dry_run, report, strategy, net } => {
+            match run_sync(url.as_deref(), *dry_run, report.as_deref(), strategy.strategy()?, &net.options()?).await {
                 Ok(_) => {},
-                Err(e) => eprintln!("❌ Sync failed: {}", e),
+                Err(e) if e.is::<transport::Cancelled>() => {
+                    warn!("🛑 Sync cancelled. Partial progress was saved; run `orb sync` again to resume.");
+                    return Ok(ExitCode::from(exit::CANCELLED));
+                }
+                Err(e) => return Ok(exit::failure("Sync failed", e)),
             }
         },
-        Commands::Checkout { commit_id } => {
-            if let Err(e) = checkout_commit(commit_id.as_deref()) {
-                eprintln!("❌ Checkout failed: {}", e);
+        Commands::Push { remote, branch, force, tags, net } => {
+            match run_push(remote.as_deref(), branch.as_deref(), *force, *tags, &net.options()?).await {
+                Ok(()) => {},
+                Err(e) if e.is::<transport::Cancelled>() => {
+                    warn!("🛑 Push cancelled; the remote branch was not updated.");
+                    return Ok(ExitCode::from(exit::CANCELLED));
+                }
+                Err(e) => return Ok(exit::failure("Push failed", e)),
+            }
+        }
+        Commands::Release { bump, message, sign, push, remote, net } => {
+            if let Err(e) = release::create(bump.bump(), message.as_deref(), *sign) {
+                return Ok(exit::failure("Release failed", e));
+            }
+            if *push {
+                match run_push(remote.as_deref(), None, false, true, &net.options()?).await {
+                    Ok(()) => {},
+                    Err(e) if e.is::<transport::Cancelled>() => {
+                        warn!("🛑 Push cancelled; the release is tagged locally. Run `orb push --tags` to publish it.");
+                        return Ok(ExitCode::from(exit::CANCELLED));
+                    }
+                    Err(e) => return Ok(exit::failure("Push failed", e)),
+                }
+            }
+        }
+        Commands::Pull { remote, branch, strategy, net } => {
+            match run_pull(remote.as_deref(), branch.as_deref(), strategy.strategy()?, &net.options()?).await {
+                Ok(()) => {},
+                Err(e) if e.is::<transport::Cancelled>() => {
+                    warn!("🛑 Pull cancelled. Partial progress was saved; run `orb pull` again to resume.");
+                    return Ok(ExitCode::from(exit::CANCELLED));
+                }
+                Err(e) => return Ok(exit::failure("Pull failed", e)),
+            }
+        }
+        Commands::Checkout { commit_id, net } => {
+            if let Err(e) = run_checkout(commit_id.as_deref(), &net.options()?).await {
+                return Ok(exit::failure("Checkout failed", e));
+            }
+        }
+        Commands::Switch { branch, force, net } => {
+            if let Err(e) = run_switch(branch, *force, &net.options()?).await {
+                return Ok(exit::failure("Switch failed", e));
+            }
+        }
+        Commands::Merge { branch, message, no_ff, abort } => {
+            let result = match branch {
+                Some(branch) if !*abort => merge::merge(branch, message.as_deref(), *no_ff),
+                _ => merge::abort(),
+            };
+            if let Err(e) = result {
+                return Ok(exit::failure("Merge failed", e));
+            }
+        }
+        Commands::Resolve { paths } => {
+            if let Err(e) = merge::resolve(paths) {
+                return Ok(exit::failure("Resolve failed", e));
+            }
+        }
+        Commands::Branch { command } => {
+            if let Err(e) = run_branch_command(command.as_ref().unwrap_or(&BranchCommand::List)) {
+                return Ok(exit::failure("Branch command failed", e));
+            }
+        }
+        Commands::Tag { command } => {
+            if let Err(e) = run_tag_command(command.as_ref().unwrap_or(&TagCommand::List)) {
+                return Ok(exit::failure("Tag command failed", e));
+            }
+        }
+        Commands::Stash { command } => {
+            if let Err(e) = run_stash_command(command.as_ref().unwrap_or(&StashCommand::Push { message: None })) {
+                return Ok(exit::failure("Stash failed", e));
+            }
+        }
+        Commands::CherryPick { revision, continue_pick, abort } => {
+            let result = match (revision, continue_pick, abort) {
+                (_, true, _) => cherry_pick::resume(cherry_pick::Operation::CherryPick),
+                (_, _, true) => cherry_pick::abort(cherry_pick::Operation::CherryPick),
+                (Some(revision), _, _) => cherry_pick::apply(cherry_pick::Operation::CherryPick, revision),
+                (None, false, false) => unreachable!("clap requires a revision unless --continue or --abort is given"),
+            };
+            if let Err(e) = result {
+                return Ok(exit::failure("Cherry-pick failed", e));
+            }
+        }
+        Commands::Rebase { upstream, continue_rebase, abort } => {
+            let result = match (upstream, continue_rebase, abort) {
+                (_, true, _) => rebase::resume(),
+                (_, _, true) => rebase::abort(),
+                (Some(upstream), _, _) => rebase::rebase(upstream),
+                (None, false, false) => unreachable!("clap requires a branch unless --continue or --abort is given"),
+            };
+            if let Err(e) = result {
+                return Ok(exit::failure("Rebase failed", e));
+            }
+        }
+        Commands::Bisect { command } => {
+            if let Err(e) = run_bisect_command(command) {
+                return Ok(exit::failure("Bisect failed", e));
             }
         }
-        Commands::Clone { url, directory } => {
-            match clone_repository(url, directory.as_deref()).await {
-                Ok(()) => println!("✅ Repository cloned successfully!"),
-                Err(e) => eprintln!("❌ Clone failed: {}", e),
+        Commands::Clone { url, directory, branch, no_checkout, create, net } => {
+            match clone_repository(url, directory.as_deref(), branch.as_deref(), *no_checkout, *create, &net.options()?).await {
+                Ok(()) => info!("✅ Repository cloned successfully!"),
+                Err(e) if e.is::<transport::Cancelled>() => {
+                    warn!("🛑 Clone cancelled. Partial progress was saved; run `orb sync` inside the clone to resume.");
+                    return Ok(ExitCode::from(exit::CANCELLED));
+                }
+                Err(e) => return Ok(exit::failure("Clone failed", e)),
+            }
+        }
+        Commands::Share { port } => {
+            if let Err(e) = share::run(*port).await {
+                return Ok(exit::failure("Share failed", e));
+            }
+        }
+        Commands::Serve { command: Some(ServeCommand::Token { user, expires_in, list, revoke, dir }), .. } => {
+            let result = match dir {
+                Some(dir) => Ok(dir.clone()),
+                None => cert::server_dir(),
+            }.and_then(|dir| {
+                if !is_valid_email(user) {
+                    return Err("usernames are email addresses (e.g., alice@company.com)".into());
+                }
+                match revoke {
+                    Some(id) => users::run_revoke(&dir, user, id),
+                    None if *list => users::run_list(&dir, user),
+                    None => users::run_issue(&dir, user, *expires_in),
+                }
+            });
+            if let Err(e) = result {
+                return Ok(exit::failure("Token command failed", e));
+            }
+        }
+        Commands::Serve { command: Some(ServeCommand::Logs { user, repo, event, since, limit, json, file, dir }), .. } => {
+            let result = match (file, dir) {
+                (Some(file), _) => Ok(file.clone()),
+                (None, Some(dir)) => Ok(dir.join(audit::LOG_FILE)),
+                (None, None) => cert::server_dir().map(|dir| dir.join(audit::LOG_FILE)),
+            }.and_then(|path| {
+                let query = audit::Query {
+                    user: user.clone(),
+                    repository: repo.clone(),
+                    event: event.clone(),
+                    since: *since,
+                    limit: *limit,
+                };
+                audit::run_logs(&path, &query, *json)
+            });
+            if let Err(e) = result {
+                return Ok(exit::failure("Cannot read the audit log", e));
+            }
+        }
+        Commands::Serve { command: Some(ServeCommand::Maintenance { repository, root, prune_after }), .. } => {
+            if let Err(e) = maintenance::run_now(root, repository.as_deref(), *prune_after) {
+                return Ok(exit::failure("Maintenance failed", e));
+            }
+        }
+        Commands::Serve { command: None, args } => {
+            let result = async {
+                let dir = match &args.dir {
+                    Some(dir) => dir.clone(),
+                    None => cert::server_dir()?,
+                };
+                let options = server::ServeOptions {
+                    root: args.root.clone(),
+                    port: args.port.unwrap_or(if args.tls { server::DEFAULT_TLS_PORT } else { server::DEFAULT_PORT }),
+                    audit_log: match (&args.audit_log, args.no_audit_log) {
+                        (_, true) => None,
+                        (Some(path), false) => Some(path.clone()),
+                        (None, false) => Some(dir.join(audit::LOG_FILE)),
+                    },
+                    audit_max_size: args.audit_max_size,
+                    dir,
+                    tls: args.tls,
+                    allow_anonymous: args.allow_anonymous,
+                    admin: args.admin_bind.map(|bind| admin_api::AdminOptions {
+                        bind,
+                        admin_token: std::env::var(&args.admin_token_env).ok().filter(|token| !token.is_empty()),
+                        open_registration: args.open_registration,
+                        token_lifetime: args.token_lifetime,
+                    }),
+                    web: args.web_bind,
+                    storage: args.storage.clone(),
+                    replication: replication::Options::from_flags(
+                        &args.replicate_to,
+                        &args.replication_pin,
+                        args.replica,
+                        &args.replication_token_env,
+                    )?,
+                    maintenance: (!args.no_maintenance).then_some(maintenance::Schedule {
+                        interval: args.maintenance_interval,
+                        new_objects: args.maintenance_objects,
+                        prune_after: args.prune_after,
+                    }),
+                    quotas: server::Quotas {
+                        repo: args.repo_quota,
+                        user: args.user_quota,
+                        max_object_size: args.max_object_size,
+                    },
+                    limits: throttle::Limits {
+                        connections_per_minute: args.connection_rate,
+                        connections_per_ip: args.max_connections_per_ip,
+                        requests_per_second: args.request_rate,
+                    },
+                };
+                server::run(options).await
+            }.await;
+            if let Err(e) = result {
+                return Ok(exit::failure("Server failed", e));
             }
         }
-        Commands::ListRepos { url } => {
-            match list_repositories(url).await {
-                Ok(()) => println!("✅ Repository list retrieved!"),
-                Err(e) => eprintln!("❌ Failed to list repositories: {}", e),
+        Commands::Cert { hostnames, dir, force, acme } => {
+            let result = async {
+                let dir = match dir {
+                    Some(dir) => dir.clone(),
+                    None => cert::server_dir()?,
+                };
+                match acme.options() {
+                    Some(options) => acme::run(hostnames, &dir, &options, *force).await,
+                    None => cert::run(hostnames, &dir, *force),
+                }
+            }.await;
+            if let Err(e) = result {
+                return Ok(exit::failure("Certificate generation failed", e));
+            }
+        }
+        Commands::Mirror { source, destination, interval, cache_dir, net } => {
+            match run_mirror(source, destination, *interval, cache_dir.as_deref(), &net.options()?).await {
+                Ok(()) => info!("✅ Mirror finished"),
+                Err(e) if e.is::<transport::Cancelled>() => {
+                    warn!("🛑 Mirror cancelled; run it again to continue where it stopped.");
+                    return Ok(ExitCode::from(exit::CANCELLED));
+                }
+                Err(e) => return Ok(exit::failure("Mirror failed", e)),
+            }
+        }
+        Commands::ListRepos { url, json, net } => {
+            match list_repositories(url.as_deref(), *json, &net.options()?).await {
+                Ok(()) if *json => {},
+                Ok(()) => info!("✅ Repository list retrieved!"),
+                Err(e) => return Ok(exit::failure("Failed to list repositories", e)),
+            }
+        }
+        Commands::Repo { command } => {
+            if let Err(e) = run_repo_command(command).await {
+                return Ok(exit::failure("Repository command failed", e));
+            }
+        }
+        Commands::Access { command } => {
+            if let Err(e) = run_access_command(command).await {
+                return Ok(exit::failure("Access command failed", e));
+            }
+        }
+        Commands::Bridge { command } => {
+            let result = match command {
+                BridgeCommand::Pull { args, strategy } => {
+                    bridge::pull(&args.remote, args.branch.as_deref(), &args.auth(), strategy.strategy()?)
+                }
+                BridgeCommand::Push(args) => bridge::push(&args.remote, args.branch.as_deref(), &args.auth()),
+            };
+            if let Err(e) = result {
+                return Ok(exit::failure("Bridge failed", e));
+            }
+        }
+        Commands::Remote { command } => {
+            if let Err(e) = run_remote_command(command.as_ref().unwrap_or(&RemoteCommand::List)) {
+                return Ok(exit::failure("Remote command failed", e));
             }
         }
         Commands::Register { email, server, username } => {
             match register_user(email, server, username.as_deref()).await {
-                Ok(()) => println!("✅ User registration successful!"),
-                Err(e) => eprintln!("❌ Registration failed: {}", e),
+                Ok(()) => info!("✅ User registration successful!"),
+                Err(e) => return Ok(exit::failure("Registration failed", e)),
             }
         }
     }
     
-    Ok(())
+    Ok(ExitCode::SUCCESS)
 }
\ No newline at end of file
@@ -0,0 +1,223 @@
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use serde::{Deserialize, Serialize};
+use crate::audit;
+use crate::hosted::{self, CommitGraph, HostedRepo};
+use crate::objects::{Commit, Directory, File, ObjectId};
+use crate::server::Server;
+use crate::transfer;
+use tracing::{info, warn};
+
+/// How often the scheduler looks for repositories that are due
+const CHECK_EVERY: Duration = Duration::from_secs(600);
+/// Quarantine directories and lock files older than this were left behind by crashes
+const STALE_AFTER: Duration = Duration::from_secs(3600);
+
+/// When `orb serve` maintains its repositories
+#[derive(Debug, Clone, Copy)]
+pub struct Schedule {
+    /// Maintain every repository at least this often
+    pub interval: Duration,
+    /// Also maintain a repository once this many objects arrived since its last run (0 disables)
+    pub new_objects: u64,
+    /// Unreachable objects younger than this are kept: a push stores its objects before
+    /// it moves any ref
+    pub prune_after: Duration,
+}
+
+/// Outcome of maintaining a repository, kept as `.orb/maintenance.json`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Report {
+    /// Unix time the run finished
+    pub time: i64,
+    /// Objects left in the store
+    pub objects: u64,
+    pub pruned: u64,
+    pub pruned_bytes: u64,
+    /// Abandoned quarantine directories and lock files removed
+    pub stale: u64,
+    /// Commits in the rewritten commit-graph
+    pub commits: usize,
+}
+
+impl Report {
+    pub fn summary(&self) -> String {
+        format!(
+            "{} objects kept, {} unreachable pruned ({}), {} stale files removed, commit-graph of {} commits",
+            self.objects,
+            self.pruned,
+            transfer::format_bytes(self.pruned_bytes),
+            self.stale,
+            self.commits
+        )
+    }
+}
+
+/// Per-repository locks: writes to a repository hold its lock shared, maintenance holds
+/// it exclusively, so pushes wait while their repository is being maintained
+#[derive(Default)]
+pub struct Locks(Mutex<HashMap<String, Arc<tokio::sync::RwLock<()>>>>);
+
+impl Locks {
+    pub fn get(&self, name: &str) -> Arc<tokio::sync::RwLock<()>> {
+        self.0.lock().unwrap().entry(name.to_string()).or_default().clone()
+    }
+}
+
+/// Maintains every repository that is due, every few minutes, until the server stops
+pub async fn schedule(server: Arc<Server>, schedule: Schedule) {
+    let mut ticker = tokio::time::interval(CHECK_EVERY.min(schedule.interval));
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        ticker.tick().await;
+        for name in hosted::list_repositories(&server.root) {
+            let Some(repo) = hosted::repo_dir(&server.root, &name).ok().and_then(|dir| HostedRepo::open(&dir)) else {
+                continue;
+            };
+            let checked = repo.clone();
+            if !tokio::task::spawn_blocking(move || is_due(&checked, &schedule)).await.unwrap_or(false) {
+                continue;
+            }
+            if let Err(e) = maintain(&server, &name, &repo, schedule.prune_after).await {
+                warn!("⚠️  Maintenance of {} failed: {}", name, e);
+            }
+        }
+    }
+}
+
+/// True if a repository was never maintained, or not within the interval, or has
+/// received enough objects since
+fn is_due(repo: &HostedRepo, schedule: &Schedule) -> bool {
+    let Some(last) = repo.last_maintenance() else { return true };
+    now() - last.time >= schedule.interval.as_secs() as i64
+        || (schedule.new_objects > 0 && repo.object_count() >= last.objects + schedule.new_objects)
+}
+
+/// Maintains a hosted repository with its writes paused, and records the outcome
+pub async fn maintain(server: &Server, name: &str, repo: &HostedRepo, prune_after: Duration) -> Result<Report, String> {
+    let lock = server.maintenance.get(name);
+    let _paused = lock.write().await;
+    let started = std::time::Instant::now();
+    let maintained = repo.clone();
+    let report = tokio::task::spawn_blocking(move || run(&maintained, prune_after))
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| e.to_string())?;
+    info!("🧹 Maintained {} in {:.1}s: {}", name, started.elapsed().as_secs_f64(), report.summary());
+    server.audit.record(audit::Event {
+        repository: Some(name.to_string()),
+        objects: Some(report.pruned),
+        message: Some(report.summary()),
+        ..audit::Event::new("maintenance")
+    });
+    Ok(report)
+}
+
+/// Removes stale leftovers, rewrites the commit-graph, and prunes objects that no branch
+/// or tag reaches and that are older than `prune_after`. Writers must be paused.
+pub fn run(repo: &HostedRepo, prune_after: Duration) -> io::Result<Report> {
+    let mut report = Report { stale: repo.remove_stale(STALE_AFTER), ..Report::default() };
+    let (reachable, graph) = reachable(repo)?;
+    repo.save_commit_graph(&graph)?;
+    report.commits = graph.len();
+
+    for object in repo.objects()? {
+        if reachable.contains(&object.id) || !is_older(object.modified, prune_after) {
+            report.objects += 1;
+            continue;
+        }
+        repo.store().delete(&object.id)?;
+        report.pruned += 1;
+        report.pruned_bytes += object.size;
+    }
+    report.time = now();
+    repo.save_maintenance(&report)?;
+    Ok(report)
+}
+
+/// Every object reachable from a branch, tag, or annotation, and the parents of every
+/// reachable commit. An object that exists but cannot be read fails the run, so it never
+/// causes pruning.
+fn reachable(repo: &HostedRepo) -> io::Result<(HashSet<ObjectId>, CommitGraph)> {
+    let mut reachable: HashSet<ObjectId> = repo.annotations().into_values().filter(|id| repo.has_object(id)).collect();
+    let mut graph = CommitGraph::new();
+    let mut commits: Vec<ObjectId> = repo.branches().into_values().chain(repo.tags().into_values()).collect();
+    let mut trees = Vec::new();
+    while let Some(id) = commits.pop() {
+        if graph.contains_key(&id) || !repo.has_object(&id) {
+            continue;
+        }
+        let commit = load::<Commit>(repo, &id)?;
+        commits.extend(commit.parents.iter().cloned());
+        trees.push(commit.tree);
+        reachable.insert(id.clone());
+        graph.insert(id, commit.parents);
+    }
+    while let Some(tree) = trees.pop() {
+        if !repo.has_object(&tree) || !reachable.insert(tree.clone()) {
+            continue;
+        }
+        for entry in load::<Directory>(repo, &tree)?.entries {
+            if entry.mode == 0o040000 {
+                trees.push(entry.id);
+            } else if repo.has_object(&entry.id) && reachable.insert(entry.id.clone()) {
+                reachable.extend(load::<File>(repo, &entry.id)?.chunk_ids().iter().cloned());
+            }
+        }
+    }
+    Ok((reachable, graph))
+}
+
+fn load<T: serde::de::DeserializeOwned>(repo: &HostedRepo, id: &str) -> io::Result<T> {
+    let data = repo.read_object(id)?;
+    serde_json::from_slice(&data).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("object {}: {}", id, e)))
+}
+
+/// True if an object was stored at least `age` ago; objects of unknown age are kept
+fn is_older(modified: Option<SystemTime>, age: Duration) -> bool {
+    modified.is_some_and(|modified| SystemTime::now().duration_since(modified).is_ok_and(|elapsed| elapsed >= age))
+}
+
+fn now() -> i64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or(0)
+}
+
+/// `orb serve maintenance`: maintains one or every repository under `root` right away
+pub fn run_now(root: &Path, repository: Option<&str>, prune_after: Duration) -> Result<(), Box<dyn std::error::Error>> {
+    let names = match repository {
+        Some(name) => vec![name.to_string()],
+        None => hosted::list_repositories(root),
+    };
+    for name in names {
+        let repo = HostedRepo::open(&hosted::repo_dir(root, &name)?).ok_or_else(|| format!("no repository {}", name))?;
+        let report = run(&repo, prune_after).map_err(|e| format!("{}: {}", name, e))?;
+        info!("🧹 {}: {}", name, report.summary());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_maintenance_is_due_by_age_or_new_objects() {
+        let dir = std::env::temp_dir().join(format!("orb-maintenance-test-{}", std::process::id()));
+        let repo = HostedRepo::open_or_init(&dir, "file").unwrap();
+        let schedule = Schedule { interval: Duration::from_secs(3600), new_objects: 2, prune_after: Duration::ZERO };
+        assert!(is_due(&repo, &schedule));
+
+        let report = run(&repo, schedule.prune_after).unwrap();
+        assert!(!is_due(&repo, &schedule));
+        for object in ["aa", "bb"] {
+            repo.store().write(&object.repeat(32), b"unreachable").unwrap();
+        }
+        assert!(is_due(&repo, &schedule));
+        assert_eq!(run(&repo, schedule.prune_after).unwrap().pruned, 2);
+        assert_eq!(report.objects, 0);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,435 @@
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+use crate::ancestry;
+use crate::config::Config;
+use crate::objects::{Commit, Directory, DirectoryEntry, ObjectId};
+use crate::repo::{self, RepoError};
+use crate::{refs, style, vos};
+
+pub use orbit_core::FileMap;
+use tracing::{info, warn};
+
+/// How pull/sync integrate remote commits into a branch that has local commits
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Strategy {
+    /// Only fast-forward; diverged histories are an error
+    FfOnly,
+    /// Create a merge commit with both tips as parents
+    Merge,
+    /// Replay local commits on top of the remote tip
+    Rebase,
+}
+
+impl FromStr for Strategy {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "ff-only" => Ok(Strategy::FfOnly),
+            "merge" => Ok(Strategy::Merge),
+            "rebase" => Ok(Strategy::Rebase),
+            other => Err(format!("unknown pull strategy '{}' (expected ff-only, merge, or rebase)", other)),
+        }
+    }
+}
+
+impl std::fmt::Display for Strategy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Strategy::FfOnly => write!(f, "ff-only"),
+            Strategy::Merge => write!(f, "merge"),
+            Strategy::Rebase => write!(f, "rebase"),
+        }
+    }
+}
+
+impl Strategy {
+    /// Reads `pull.strategy` from the config, defaulting to ff-only
+    pub fn from_config(config: &Config) -> Result<Self, String> {
+        config.get("pull.strategy").map_or(Ok(Strategy::FfOnly), str::parse)
+    }
+}
+
+/// Integrates `remote` into `branch` (currently at `local`) using `strategy`.
+///
+/// Returns the new branch tip, or None for ff-only (the caller reports the divergence).
+/// Fails without creating anything if the same file was changed on both sides.
+pub fn integrate(
+    strategy: Strategy,
+    branch: &str,
+    local: &str,
+    remote: &str,
+    remote_name: &str,
+) -> Result<Option<ObjectId>, Box<dyn std::error::Error>> {
+    let base = ancestry::merge_base(local, remote);
+    match strategy {
+        Strategy::FfOnly => Ok(None),
+        Strategy::Merge => {
+            let message = format!("Merge {} into {}", remote_name, branch);
+            merge_commits(base.as_deref(), local, remote, &message).map(Some)
+        }
+        Strategy::Rebase => rebase_commits(base.as_deref(), local, remote).map(Some),
+    }
+}
+
+/// `orb merge`: merges `revision` (usually a branch) into the current branch. Fast-forwards
+/// when HEAD is an ancestor of it, unless `no_ff` is set; otherwise merges the trees
+/// against their merge base and commits the result with HEAD and `revision` as parents.
+/// The working tree is updated to the new tip and must have no changes it would lose.
+///
+/// Text files changed on both sides are merged line by line. Where both changed the
+/// same lines, the merge stops: the files get conflict markers, their paths are
+/// recorded in the index, and the merged commit is kept in `.orb/MERGE_HEAD` so the
+/// `orb save` after `orb resolve` records it as the second parent.
+pub fn merge(revision: &str, message: Option<&str>, no_ff: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let repository = repo::open_worktree()?;
+    repo::ensure_idle(&repository)?;
+    let theirs = refs::resolve(revision)?;
+    let branch = refs::current_branch();
+    let short = |id: &str| style::id(&id[..7.min(id.len())]).into_owned();
+
+    let new_tip = match refs::head_commit() {
+        Some(head) if ancestry::is_ancestor(&theirs, &head) => {
+            info!("✅ Already up to date.");
+            return Ok(());
+        }
+        Some(head) if no_ff || !ancestry::is_ancestor(&head, &theirs) => {
+            repo::ensure_no_overwrite(&repository, &repository.commit_files(&theirs)?)?;
+            let base = ancestry::merge_base(&head, &theirs);
+            let label = match refs::read_ref(&refs::branch_ref(revision)) {
+                Some(_) => format!("branch '{}'", revision),
+                None => revision.to_string(),
+            };
+            let message = message.map_or_else(|| format!("Merge {} into {}", label, branch), str::to_string);
+            let base_files = match &base {
+                Some(base) => commit_files(base)?,
+                None => FileMap::new(),
+            };
+            let (merged, conflicts) = merge_files(&base_files, &commit_files(&head)?, &commit_files(&theirs)?, &branch, revision)?;
+            if !conflicts.is_empty() {
+                return stop_with_conflicts(&repository, &merged, conflicts, &theirs, &message);
+            }
+            commit_merge(&merged, &head, &theirs, &message)?
+        }
+        head => {
+            repo::ensure_no_overwrite(&repository, &repository.commit_files(&theirs)?)?;
+            if let Some(head) = head {
+                info!("⏩ Fast-forward {}..{}", short(&head), short(&theirs));
+            }
+            theirs
+        }
+    };
+
+    repository.replace_worktree(&repository.load_commit(&new_tip)?.tree)?;
+    refs::write_ref(&refs::branch_ref(&branch), &new_tip)?;
+    info!("🔀 Merged '{}' into '{}' (now at {})", revision, branch, short(&new_tip));
+    Ok(())
+}
+
+/// A path both sides changed in ways that could not be combined, and what the working
+/// tree gets for it: the text with conflict markers, or None to keep the version in
+/// the merged map (ours, or theirs if we deleted it)
+pub struct Conflict {
+    path: String,
+    content: Option<Vec<u8>>,
+}
+
+/// Merges file maps like `merge_entries`, then merges text files both sides changed line
+/// by line. Conflicted paths keep a version in the merged map so the tree can be checked out.
+fn merge_files(base: &FileMap, ours: &FileMap, theirs: &FileMap, ours_label: &str, theirs_label: &str) -> Result<(FileMap, Vec<Conflict>), Box<dyn std::error::Error>> {
+    let (mut merged, paths) = merge_entries(base, ours, theirs);
+    let mut conflicts = Vec::new();
+    for path in paths {
+        let (o, t) = (ours.get(&path), theirs.get(&path));
+        let text = |entry: Option<&(u32, ObjectId)>| -> Result<Option<String>, Box<dyn std::error::Error>> {
+            match entry {
+                Some((_, id)) => Ok(String::from_utf8(vos::file_content(id)?).ok()),
+                None => Ok(Some(String::new())),
+            }
+        };
+        let content = match (o, t) {
+            (Some((our_mode, _)), Some((their_mode, _))) if our_mode == their_mode && *our_mode != 0o120000 => {
+                match (text(base.get(&path))?, text(o)?, text(t)?) {
+                    (Some(b), Some(o), Some(t)) => Some(crate::diff::merge3(&b, &o, &t, ours_label, theirs_label)),
+                    _ => None,
+                }
+            }
+            _ => None,
+        };
+        match content {
+            Some((text, 0)) => {
+                let (id, _) = vos::save_stream(text.as_bytes(), &mut Default::default())?;
+                merged.insert(path, (o.map_or(0o100644, |(mode, _)| *mode), id));
+            }
+            content => {
+                merged.insert(path.clone(), o.or(t).cloned().expect("a conflicted path exists on one side"));
+                conflicts.push(Conflict { path, content: content.map(|(text, _)| text.into_bytes()) });
+            }
+        }
+    }
+    Ok((merged, conflicts))
+}
+
+/// Leaves a conflicted merge for the user to finish: the working tree gets the merged
+/// files with conflict markers, the index records the conflicted paths, and MERGE_HEAD
+/// the merged commit
+fn stop_with_conflicts(repository: &orbit_core::Repository, merged: &FileMap, conflicts: Vec<Conflict>, theirs: &str, message: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let count = write_conflicts(repository, merged, conflicts)?;
+    repository.set_merge_head(Some(theirs))?;
+
+    warn!("💡 Fix the conflicts, mark each with `orb resolve <path>`, then run `orb save -m \"{}\"`", message);
+    warn!("   (or `orb merge --abort` to go back)");
+    Err(RepoError::MergeConflicts(count).into())
+}
+
+/// Replaces the working tree with `merged`, writes the conflicted files with their
+/// markers, and records the conflicted paths in the index for `orb resolve`. Returns
+/// the number of conflicts.
+pub fn write_conflicts(repository: &orbit_core::Repository, merged: &FileMap, conflicts: Vec<Conflict>) -> Result<usize, Box<dyn std::error::Error>> {
+    repository.replace_worktree(&build_tree(merged)?)?;
+    let mut index = repository.index()?;
+    for conflict in &conflicts {
+        if let Some(content) = &conflict.content {
+            std::fs::write(repository.root().join(&conflict.path), content)?;
+        }
+        // Forget the file's metadata, so a resolution written within the same second
+        // is still read
+        if let Some((_, file_id)) = merged.get(&conflict.path) {
+            index.update_entry(conflict.path.clone(), 0, 0, file_id.clone());
+        }
+        warn!("⚔️  Conflict in {}{}", conflict.path, if conflict.content.is_none() { " (changed differently on both sides; kept one version)" } else { "" });
+        index.conflicts.insert(conflict.path.clone());
+    }
+    repository.save_index(&index)?;
+    Ok(conflicts.len())
+}
+
+/// Applies the change `commit` made to its first parent (or to an empty tree) onto the
+/// files of `onto`, merging text files line by line. Returns the resulting files, with
+/// the conflicts `write_conflicts` leaves for the user when both changed the same lines.
+pub fn replay(commit: &Commit, onto: &str, label: &str) -> Result<(FileMap, Vec<Conflict>), Box<dyn std::error::Error>> {
+    let (before, after) = change_of(commit)?;
+    merge_files(&before, &commit_files(onto)?, &after, "HEAD", label)
+}
+
+/// Like `replay`, for the inverse of the change: what `commit` added is removed, and
+/// what it changed or removed is restored
+pub fn replay_inverse(commit: &Commit, onto: &str, label: &str) -> Result<(FileMap, Vec<Conflict>), Box<dyn std::error::Error>> {
+    let (before, after) = change_of(commit)?;
+    merge_files(&after, &commit_files(onto)?, &before, "HEAD", label)
+}
+
+/// The files of a commit's first parent (none for a root commit) and of the commit
+fn change_of(commit: &Commit) -> Result<(FileMap, FileMap), Box<dyn std::error::Error>> {
+    let parent_files = match commit.parents.first() {
+        Some(parent) => commit_files(parent)?,
+        None => FileMap::new(),
+    };
+    Ok((parent_files, tree_files(&commit.tree)?))
+}
+
+/// `orb resolve`: marks conflicted paths as resolved; once none are left, `orb save`
+/// commits the merge
+pub fn resolve(paths: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let repository = repo::open_worktree()?;
+    let mut index = repository.index()?;
+    if repository.merge_head().is_none() && index.conflicts.is_empty() {
+        return Err("no merge is in progress".into());
+    }
+    let next = if let Some((operation, _)) = crate::cherry_pick::in_progress(&repository) {
+        match operation {
+            crate::cherry_pick::Operation::CherryPick => "run `orb cherry-pick --continue` to commit the pick",
+            crate::cherry_pick::Operation::Revert => "run `orb revert --continue` to commit the revert",
+        }
+    } else if crate::rebase::stopped_at(&repository).is_some() {
+        "run `orb rebase --continue` to go on replaying"
+    } else {
+        "run `orb save -m <message>` to commit the merge"
+    };
+    for path in paths {
+        let path = path.trim_start_matches("./").trim_end_matches('/');
+        if !index.conflicts.remove(path) {
+            return Err(format!("'{}' has no unresolved conflict", path).into());
+        }
+        let content = std::fs::read(repository.root().join(path)).unwrap_or_default();
+        if String::from_utf8_lossy(&content).lines().any(|line| line.starts_with("<<<<<<< ") || line.starts_with(">>>>>>> ")) {
+            warn!("⚠️  {} still contains conflict markers", path);
+        }
+        info!("✅ Resolved {}", path);
+    }
+    repository.save_index(&index)?;
+    match index.conflicts.len() {
+        0 => info!("💡 All conflicts resolved; {}", next),
+        left => info!("   {} conflicted path(s) left", left),
+    }
+    Ok(())
+}
+
+/// `orb merge --abort`: returns the working tree to HEAD and forgets the merge
+pub fn abort() -> Result<(), Box<dyn std::error::Error>> {
+    let repository = repo::open_worktree()?;
+    if repository.merge_head().is_none() {
+        return Err("no merge is in progress".into());
+    }
+    let head = refs::head_commit().ok_or(RepoError::NoCommits)?;
+    repository.replace_worktree(&repository.load_commit(&head)?.tree)?;
+    repository.set_merge_head(None)?;
+    info!("↩️  Merge aborted; the working tree is back at {}", style::id(&head[..7.min(head.len())]));
+    Ok(())
+}
+
+/// Creates a merge commit whose parents are `local` and `remote`
+fn merge_commits(base: Option<&str>, local: &str, remote: &str, message: &str) -> Result<ObjectId, Box<dyn std::error::Error>> {
+    let base_files = match base {
+        Some(base) => commit_files(base)?,
+        None => FileMap::new(),
+    };
+    let merged = without_conflicts(merge_entries(&base_files, &commit_files(local)?, &commit_files(remote)?))?;
+    commit_merge(&merged, local, remote, message)
+}
+
+/// Saves `merged` as a commit whose parents are `local` and `remote`
+fn commit_merge(merged: &FileMap, local: &str, remote: &str, message: &str) -> Result<ObjectId, Box<dyn std::error::Error>> {
+    let commit = Commit {
+        tree: build_tree(merged)?,
+        parents: vec![local.to_string(), remote.to_string()],
+        author: repo::DEFAULT_AUTHOR.to_string(),
+        timestamp: now(),
+        message: message.to_string(),
+        signature: None,
+    };
+    let merge_id = vos::save_object(&serde_json::to_vec(&commit)?);
+    info!("🔀 Created merge commit {}", &merge_id[..7]);
+    Ok(merge_id)
+}
+
+/// Replays the local commits since `base` on top of `remote`, returning the new tip
+fn rebase_commits(base: Option<&str>, local: &str, remote: &str) -> Result<ObjectId, Box<dyn std::error::Error>> {
+    // Collect local-only commits, oldest first
+    let mut to_replay = Vec::new();
+    let mut current = local.to_string();
+    while Some(current.as_str()) != base {
+        let commit = ancestry::load_commit(&current).ok_or_else(|| format!("Missing local commit {}", current))?;
+        if commit.parents.len() > 1 {
+            return Err(format!("Cannot rebase merge commit {}; use the merge strategy instead", &current[..7]).into());
+        }
+        let parent = commit.parents.first().cloned();
+        to_replay.push((current, commit));
+        match parent {
+            Some(parent) => current = parent,
+            None => break,
+        }
+    }
+    to_replay.reverse();
+
+    let mut tip = remote.to_string();
+    for (original_id, commit) in to_replay {
+        let parent_files = match commit.parents.first() {
+            Some(parent) => commit_files(parent)?,
+            None => FileMap::new(),
+        };
+        let replayed = without_conflicts(merge_entries(&parent_files, &commit_files(&tip)?, &tree_files(&commit.tree)?))?;
+
+        let rebased = Commit {
+            tree: build_tree(&replayed)?,
+            parents: vec![tip.clone()],
+            ..commit
+        };
+        tip = vos::save_object(&serde_json::to_vec(&rebased)?);
+        info!("  ♻️  Replayed {} as {}: {}", &original_id[..7], &tip[..7], rebased.message);
+    }
+
+    Ok(tip)
+}
+
+/// Three-way merge of file maps. A path changed on only one side takes that side;
+/// a path changed identically on both sides is fine; anything else is a conflict and
+/// is left out of the merged map.
+fn merge_entries(base: &FileMap, ours: &FileMap, theirs: &FileMap) -> (FileMap, Vec<String>) {
+    let mut merged = FileMap::new();
+    let mut conflicts = Vec::new();
+
+    let paths: std::collections::BTreeSet<&String> = base.keys().chain(ours.keys()).chain(theirs.keys()).collect();
+    for path in paths {
+        let (b, o, t) = (base.get(path), ours.get(path), theirs.get(path));
+        let result = if o == t || t == b {
+            o
+        } else if o == b {
+            t
+        } else {
+            conflicts.push(path.clone());
+            continue;
+        };
+        if let Some(entry) = result {
+            merged.insert(path.clone(), entry.clone());
+        }
+    }
+
+    (merged, conflicts)
+}
+
+/// The merged map, or an error naming the conflicting paths
+fn without_conflicts((merged, conflicts): (FileMap, Vec<String>)) -> Result<FileMap, Box<dyn std::error::Error>> {
+    match conflicts.is_empty() {
+        true => Ok(merged),
+        false => Err(format!("conflicting changes in {} file(s): {}", conflicts.len(), conflicts.join(", ")).into()),
+    }
+}
+
+/// Every file of a commit's tree by path
+pub fn commit_files(commit_id: &str) -> Result<FileMap, Box<dyn std::error::Error>> {
+    Ok(repo::open()?.commit_files(commit_id)?)
+}
+
+/// Flattens a tree into a path → (mode, ID) map
+fn tree_files(tree_id: &str) -> Result<FileMap, Box<dyn std::error::Error>> {
+    Ok(repo::open()?.tree_files(tree_id)?)
+}
+
+/// Saves nested Directory objects for a flat file map and returns the root tree ID
+pub fn build_tree(files: &FileMap) -> Result<ObjectId, Box<dyn std::error::Error>> {
+    let mut entries = Vec::new();
+    let mut subdirs: BTreeMap<&str, FileMap> = BTreeMap::new();
+
+    for (path, (mode, id)) in files {
+        match path.split_once('/') {
+            Some((dir, rest)) => {
+                subdirs.entry(dir).or_default().insert(rest.to_string(), (*mode, id.clone()));
+            }
+            None => entries.push(DirectoryEntry { mode: *mode, name: path.clone(), id: id.clone() }),
+        }
+    }
+    for (name, sub_files) in subdirs {
+        entries.push(DirectoryEntry { mode: 0o040000, name: name.to_string(), id: build_tree(&sub_files)? });
+    }
+    entries.sort_by(|a, b| a.name.cmp(&b.name));
+
+    Ok(vos::save_object(&serde_json::to_vec(&Directory { entries })?))
+}
+
+fn now() -> i64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn files(entries: &[(&str, &str)]) -> FileMap {
+        entries.iter().map(|(p, id)| (p.to_string(), (0o100644, id.to_string()))).collect()
+    }
+
+    #[test]
+    fn test_merge_entries() {
+        let base = files(&[("a", "1"), ("b", "1"), ("c", "1")]);
+        let ours = files(&[("a", "2"), ("b", "1"), ("new", "x")]);
+        let theirs = files(&[("a", "1"), ("b", "3"), ("c", "1")]);
+
+        let (merged, conflicts) = merge_entries(&base, &ours, &theirs);
+        assert_eq!((merged, conflicts), (files(&[("a", "2"), ("b", "3"), ("new", "x")]), vec![]));
+
+        let conflicting = files(&[("a", "9"), ("b", "1"), ("c", "1")]);
+        assert_eq!(merge_entries(&base, &ours, &conflicting).1, vec!["a".to_string()]);
+    }
+}
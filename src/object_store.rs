@@ -0,0 +1,262 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::SystemTime;
+use rand_core::RngCore;
+use serde::{Deserialize, Serialize};
+use crate::{kv_store, s3_store};
+
+/// Per-namespace backends in the server config directory, e.g.
+/// `{"alice@example.com": "s3://bucket/orbit?region=eu-west-1"}`
+pub const CONFIG_FILE: &str = "storage.json";
+/// The backend a hosted repository uses, recorded in its `.orb` directory when it is created
+const REPO_FILE: &str = "storage.json";
+
+/// Where a hosted repository keeps its object data. Refs, settings, and pushes still in
+/// quarantine stay in the repository's `.orb` directory whatever the backend.
+pub trait ObjectStore: Send + Sync + std::fmt::Debug {
+    /// Size of a stored object, or None if it is missing
+    fn size(&self, id: &str) -> Option<u64>;
+    fn read(&self, id: &str) -> io::Result<Vec<u8>>;
+    fn write(&self, id: &str, data: &[u8]) -> io::Result<()>;
+    fn delete(&self, id: &str) -> io::Result<()>;
+    /// Every stored object, with its size and age
+    fn list(&self) -> io::Result<Vec<StoredObject>>;
+
+    /// Stores a received and verified file, which is consumed
+    fn put_file(&self, id: &str, path: &Path) -> io::Result<()> {
+        self.write(id, &fs::read(path)?)?;
+        fs::remove_file(path)
+    }
+
+    /// The object's file, for backends on local disk, so large objects can be streamed
+    fn local_path(&self, _id: &str) -> Option<PathBuf> {
+        None
+    }
+
+    /// Bytes of stored objects
+    fn usage(&self) -> u64 {
+        self.list().map(|objects| objects.iter().map(|object| object.size).sum()).unwrap_or(0)
+    }
+
+    fn count(&self) -> u64 {
+        self.list().map(|objects| objects.len() as u64).unwrap_or(0)
+    }
+
+    /// Deletes every object, when the repository itself is deleted
+    fn clear(&self) -> io::Result<()> {
+        for object in self.list()? {
+            self.delete(&object.id)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct StoredObject {
+    pub id: String,
+    pub size: u64,
+    /// When the object was stored, if the backend knows
+    pub modified: Option<SystemTime>,
+}
+
+/// Objects as files under `.orb/objects/<2 hex>/<62 hex>`, the layout of a working copy
+#[derive(Debug)]
+pub struct LocalStore {
+    objects: PathBuf,
+}
+
+impl LocalStore {
+    pub fn new(objects: PathBuf) -> Self {
+        LocalStore { objects }
+    }
+
+    fn path(&self, id: &str) -> PathBuf {
+        let (prefix, suffix) = id.split_at(2);
+        self.objects.join(prefix).join(suffix)
+    }
+
+    /// The two-character directories objects are spread over
+    fn fan_out(&self) -> Vec<(String, PathBuf)> {
+        let Ok(entries) = fs::read_dir(&self.objects) else { return Vec::new() };
+        entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| (entry.file_name().to_string_lossy().to_string(), entry.path()))
+            .filter(|(name, _)| name.len() == 2 && name.chars().all(|c| c.is_ascii_hexdigit()))
+            .collect()
+    }
+}
+
+impl ObjectStore for LocalStore {
+    fn size(&self, id: &str) -> Option<u64> {
+        fs::metadata(self.path(id)).ok().filter(|m| m.is_file()).map(|m| m.len())
+    }
+
+    fn read(&self, id: &str) -> io::Result<Vec<u8>> {
+        fs::read(self.path(id))
+    }
+
+    fn write(&self, id: &str, data: &[u8]) -> io::Result<()> {
+        let path = self.path(id);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, data)
+    }
+
+    fn delete(&self, id: &str) -> io::Result<()> {
+        fs::remove_file(self.path(id))
+    }
+
+    fn list(&self) -> io::Result<Vec<StoredObject>> {
+        let mut objects = Vec::new();
+        for (prefix, dir) in self.fan_out() {
+            let Ok(entries) = fs::read_dir(&dir) else { continue };
+            for entry in entries.filter_map(|entry| entry.ok()) {
+                let id = format!("{}{}", prefix, entry.file_name().to_string_lossy());
+                let Ok(metadata) = entry.metadata() else { continue };
+                if crate::hosted::is_object_id(&id) {
+                    objects.push(StoredObject { id, size: metadata.len(), modified: metadata.modified().ok() });
+                }
+            }
+        }
+        Ok(objects)
+    }
+
+    /// Renames the file into place; both live on the same filesystem
+    fn put_file(&self, id: &str, path: &Path) -> io::Result<()> {
+        let target = self.path(id);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::rename(path, target)
+    }
+
+    fn local_path(&self, id: &str) -> Option<PathBuf> {
+        Some(self.path(id)).filter(|path| path.is_file())
+    }
+
+    fn usage(&self) -> u64 {
+        self.fan_out().iter().map(|(_, dir)| crate::hosted::dir_size(dir)).sum()
+    }
+
+    fn count(&self) -> u64 {
+        self.fan_out()
+            .iter()
+            .filter_map(|(_, dir)| fs::read_dir(dir).ok())
+            .map(|entries| entries.count() as u64)
+            .sum()
+    }
+
+    fn clear(&self) -> io::Result<()> {
+        for (_, dir) in self.fan_out() {
+            fs::remove_dir_all(dir)?;
+        }
+        Ok(())
+    }
+}
+
+/// Which backend new repositories get: `--storage` for the server, overridden per
+/// namespace by `storage.json` in the config directory
+#[derive(Debug, Clone)]
+pub struct StorageConfig {
+    pub default: String,
+    pub dir: PathBuf,
+}
+
+impl StorageConfig {
+    /// Backend URL for a new repository in `namespace` (`file` keeps objects in the repository)
+    pub fn backend_for(&self, namespace: &str) -> Result<String, String> {
+        let path = self.dir.join(CONFIG_FILE);
+        let namespaces: BTreeMap<String, String> = match fs::read(&path) {
+            Ok(data) => serde_json::from_slice(&data).map_err(|e| format!("{}: {}", path.display(), e))?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(format!("cannot read {}: {}", path.display(), e)),
+        };
+        Ok(namespaces.get(namespace).unwrap_or(&self.default).clone())
+    }
+}
+
+/// Checks a backend URL given on the command line or in `storage.json`
+pub fn validate(url: &str) -> Result<(), String> {
+    match scheme(url) {
+        "file" => Ok(()),
+        "s3" => s3_store::S3Store::open(url, "").map(|_| ()),
+        "redis" => kv_store::KvStore::open(url, "").map(|_| ()),
+        other => Err(format!("unknown storage backend '{}' (expected file, s3://, or redis://)", other)),
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct RepoStorage {
+    /// Backend URL, as configured when the repository was created
+    url: String,
+    /// The repository's own part of the backend: a key prefix
+    location: String,
+}
+
+/// Prepares the object store of a repository being created in `orb`, under a location of
+/// its own within the backend, so renaming the repository never moves its objects
+pub fn init(orb: &Path, backend: &str) -> io::Result<()> {
+    if scheme(backend) == "file" {
+        return Ok(());
+    }
+    let mut bytes = [0u8; 8];
+    rand_core::OsRng.fill_bytes(&mut bytes);
+    let location = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    fs::write(orb.join(REPO_FILE), serde_json::to_vec_pretty(&RepoStorage { url: backend.to_string(), location })?)
+}
+
+/// The object store of the repository whose `.orb` directory is `orb`. Remote stores are
+/// shared by every handle on the same location.
+pub fn open(orb: &Path) -> Result<Arc<dyn ObjectStore>, String> {
+    let path = orb.join(REPO_FILE);
+    let storage = match fs::read(&path) {
+        Ok(data) => serde_json::from_slice::<RepoStorage>(&data).map_err(|e| format!("{}: {}", path.display(), e))?,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Arc::new(LocalStore::new(orb.join("objects")))),
+        Err(e) => return Err(format!("cannot read {}: {}", path.display(), e)),
+    };
+    static OPEN: OnceLock<Mutex<HashMap<String, Arc<dyn ObjectStore>>>> = OnceLock::new();
+    let mut open = OPEN.get_or_init(Mutex::default).lock().unwrap();
+    let key = format!("{} {}", storage.url, storage.location);
+    if let Some(store) = open.get(&key) {
+        return Ok(store.clone());
+    }
+    let store: Arc<dyn ObjectStore> = match scheme(&storage.url) {
+        "s3" => Arc::new(s3_store::S3Store::open(&storage.url, &storage.location)?),
+        "redis" => Arc::new(kv_store::KvStore::open(&storage.url, &storage.location)?),
+        other => return Err(format!("{}: unknown storage backend '{}'", path.display(), other)),
+    };
+    open.insert(key, store.clone());
+    Ok(store)
+}
+
+fn scheme(url: &str) -> &str {
+    url.split_once("://").map_or(url, |(scheme, _)| scheme)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_new_repositories_get_a_location_of_their_own() {
+        let dir = std::env::temp_dir().join(format!("orb-object-store-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join(CONFIG_FILE), r#"{"alice@example.com": "redis://127.0.0.1:6379?prefix=orbit"}"#).unwrap();
+        let config = StorageConfig { default: "file".to_string(), dir: dir.clone() };
+        assert_eq!(config.backend_for("bob@example.com").unwrap(), "file");
+        let backend = config.backend_for("alice@example.com").unwrap();
+
+        init(&dir, &backend).unwrap();
+        let first: RepoStorage = serde_json::from_slice(&fs::read(dir.join(REPO_FILE)).unwrap()).unwrap();
+        init(&dir, &backend).unwrap();
+        let second: RepoStorage = serde_json::from_slice(&fs::read(dir.join(REPO_FILE)).unwrap()).unwrap();
+        assert_eq!(first.url, "redis://127.0.0.1:6379?prefix=orbit");
+        assert_eq!(first.location.len(), 16);
+        assert_ne!(first.location, second.location);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
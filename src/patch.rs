@@ -0,0 +1,594 @@
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fmt::Write as _;
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use time::format_description::well_known::Rfc2822;
+use time::OffsetDateTime;
+use crate::diff::{self, Line};
+use crate::merge::{self, FileMap};
+use crate::objects::{Commit, ObjectId};
+use crate::{ancestry, refs, vos};
+use tracing::{info, warn};
+
+/// Unchanged lines shown around each change
+const CONTEXT: usize = 3;
+/// Mode of symbolic links, whose content is their target
+const SYMLINK_MODE: u32 = 0o120000;
+/// Git's base85 alphabet, used for binary patches
+const BASE85: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz!#$%&()*+-;<=>?@^_`{|}~";
+
+/// Writes one patch file per commit in `range`, oldest first, into `output` (or to
+/// stdout). `A..B` is the commits of B that A lacks; a single revision means the
+/// commits after it up to HEAD, or with `root`, every commit up to it.
+pub fn format_patches(range: &str, root: bool, output: &Path, stdout: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let (since, until) = match range.split_once("..") {
+        Some((since, until)) => (Some(since).filter(|since| !since.is_empty()), if until.is_empty() { "HEAD" } else { until }),
+        None if root => (None, range),
+        None => (Some(range), "HEAD"),
+    };
+    let excluded = match since {
+        Some(since) => ancestry::ancestors(&refs::resolve(since)?),
+        None => HashSet::new(),
+    };
+    let commits: Vec<(ObjectId, Commit)> = ancestry::parents_first(&refs::resolve(until)?, &excluded)?
+        .into_iter()
+        .filter(|(id, commit)| {
+            let merge = commit.parents.len() > 1;
+            if merge {
+                warn!("⏭️  Skipping merge commit {}", &id[..7]);
+            }
+            !merge
+        })
+        .collect();
+    if commits.is_empty() {
+        info!("✅ No commits in {}", range);
+        return Ok(());
+    }
+
+    if !stdout {
+        fs::create_dir_all(output)?;
+    }
+    for (number, (id, commit)) in commits.iter().enumerate() {
+        let patch = format_patch(id, commit, number + 1, commits.len())?;
+        if stdout {
+            print!("{}", patch);
+            continue;
+        }
+        let path = output.join(format!("{:04}-{}.patch", number + 1, slug(subject(&commit.message))));
+        fs::write(&path, patch)?;
+        println!("{}", path.display());
+    }
+    Ok(())
+}
+
+/// A commit as an mbox message in the format of `git format-patch`, with its Orbit IDs
+/// in `X-Orbit-*` headers
+fn format_patch(id: &str, commit: &Commit, number: usize, total: usize) -> Result<String, Box<dyn std::error::Error>> {
+    let old_files = match commit.parents.first() {
+        Some(parent) => merge::commit_files(parent)?,
+        None => FileMap::new(),
+    };
+    let new_files = merge::commit_files(id)?;
+    let paths: BTreeSet<&String> = old_files.keys().chain(new_files.keys()).collect();
+
+    let mut diffs = String::new();
+    let mut stats = Vec::new();
+    for path in paths {
+        let (old, new) = (old_files.get(path), new_files.get(path));
+        if old != new {
+            stats.push((path.as_str(), file_diff(path, old, new, &mut diffs)?));
+        }
+    }
+
+    let date = OffsetDateTime::from_unix_timestamp(commit.timestamp)?.format(&Rfc2822)?;
+    let numbering = if total == 1 { String::new() } else { format!(" {}/{}", number, total) };
+    let mut patch = format!("From {} Mon Sep 17 00:00:00 2001\nFrom: {}\nDate: {}\n", id, commit.author, date);
+    writeln!(patch, "Subject: [PATCH{}] {}\nX-Orbit-Commit: {}", numbering, subject(&commit.message), id)?;
+    if let Some(parent) = commit.parents.first() {
+        writeln!(patch, "X-Orbit-Parent: {}", parent)?;
+    }
+    let body = commit.message.trim_end().split_once('\n').map(|(_, body)| body.trim_start_matches('\n')).unwrap_or("");
+    if body.is_empty() {
+        patch.push('\n');
+    } else {
+        write!(patch, "\n{}\n\n", body)?;
+    }
+    patch.push_str("---\n");
+    patch.push_str(&diffstat(&stats));
+    write!(patch, "\n{}-- \norb {}\n\n", diffs, env!("CARGO_PKG_VERSION"))?;
+    Ok(patch)
+}
+
+/// Appends the `diff --git` section of one path; returns its insertions and deletions
+/// (None for binary content)
+pub fn file_diff(path: &str, old: Option<&(u32, ObjectId)>, new: Option<&(u32, ObjectId)>, out: &mut String) -> Result<Option<(usize, usize)>, Box<dyn std::error::Error>> {
+    writeln!(out, "diff --git a/{} b/{}", path, path)?;
+    match (old, new) {
+        (None, Some((mode, _))) => writeln!(out, "new file mode {:o}", mode)?,
+        (Some((mode, _)), None) => writeln!(out, "deleted file mode {:o}", mode)?,
+        (Some((old_mode, _)), Some((new_mode, _))) if old_mode != new_mode => writeln!(out, "old mode {:o}\nnew mode {:o}", old_mode, new_mode)?,
+        _ => {}
+    }
+    if old.map(|(_, id)| id) == new.map(|(_, id)| id) {
+        return Ok(Some((0, 0)));
+    }
+
+    let old_data = old.map(|(_, id)| file_content(id)).transpose()?.unwrap_or_default();
+    let new_data = new.map(|(_, id)| file_content(id)).transpose()?.unwrap_or_default();
+    write!(out, "index {}..{}", git_blob_id(old.map(|_| &old_data[..])), git_blob_id(new.map(|_| &new_data[..])))?;
+    match (old, new) {
+        (Some((old_mode, _)), Some((new_mode, _))) if old_mode == new_mode => writeln!(out, " {:o}", old_mode)?,
+        _ => out.push('\n'),
+    }
+
+    let (Some(old_text), Some(new_text)) = (text(&old_data), text(&new_data)) else {
+        writeln!(out, "GIT binary patch\n{}\n{}", literal(&new_data)?, literal(&old_data)?)?;
+        return Ok(None);
+    };
+    writeln!(out, "--- {}", old.map_or("/dev/null".to_string(), |_| format!("a/{}", path)))?;
+    writeln!(out, "+++ {}", new.map_or("/dev/null".to_string(), |_| format!("b/{}", path)))?;
+    let lines = diff::diff_lines(&diff::split_lines(old_text), &diff::split_lines(new_text));
+    write_hunks(&lines, out);
+    let added = lines.iter().filter(|line| matches!(line, Line::Added(_))).count();
+    let removed = lines.iter().filter(|line| matches!(line, Line::Removed(_))).count();
+    Ok(Some((added, removed)))
+}
+
+/// The `@@` hunks of a comparison in unified diff format
+fn write_hunks(lines: &[Line], out: &mut String) {
+    for hunk in diff::hunks(lines, CONTEXT) {
+        let _ = writeln!(out, "@@ -{} +{} @@", range_text(hunk.old_start, hunk.old_len), range_text(hunk.new_start, hunk.new_len));
+        for line in &hunk.lines {
+            let (prefix, content) = match line {
+                Line::Same(content) => (' ', content),
+                Line::Removed(content) => ('-', content),
+                Line::Added(content) => ('+', content),
+            };
+            out.push(prefix);
+            out.push_str(content);
+            if !content.ends_with('\n') {
+                out.push_str("\n\\ No newline at end of file\n");
+            }
+        }
+    }
+}
+
+/// `start,length` of a hunk header, with Git's shorthand for one line
+fn range_text(start: usize, length: usize) -> String {
+    if length == 1 { start.to_string() } else { format!("{},{}", start, length) }
+}
+
+/// The ` path | 3 ++-` lines and summary that precede the diffs
+pub fn diffstat(stats: &[(&str, Option<(usize, usize)>)]) -> String {
+    let width = stats.iter().map(|(path, _)| path.len()).max().unwrap_or(0);
+    let (mut insertions, mut deletions) = (0, 0);
+    let mut text = String::new();
+    for (path, counts) in stats {
+        match counts {
+            Some((added, removed)) => {
+                insertions += added;
+                deletions += removed;
+                // Bars longer than 50 characters are scaled down
+                let scale = |n: usize| if added + removed > 50 { (n * 50).div_ceil(added + removed) } else { n };
+                let line = format!(" {:width$} | {} {}{}", path, added + removed, "+".repeat(scale(*added)), "-".repeat(scale(*removed)));
+                let _ = writeln!(text, "{}", line.trim_end());
+            }
+            None => {
+                let _ = writeln!(text, " {:width$} | Bin", path);
+            }
+        }
+    }
+    let plural = |count: usize| if count == 1 { "" } else { "s" };
+    let _ = writeln!(
+        text,
+        " {} file{} changed, {} insertion{}(+), {} deletion{}(-)",
+        stats.len(), plural(stats.len()), insertions, plural(insertions), deletions, plural(deletions)
+    );
+    text
+}
+
+fn subject(message: &str) -> &str {
+    message.lines().next().unwrap_or("").trim()
+}
+
+/// Patch file name part of a subject: letters and digits joined by dashes
+fn slug(subject: &str) -> String {
+    let words: Vec<&str> = subject.split(|c: char| !c.is_ascii_alphanumeric()).filter(|word| !word.is_empty()).collect();
+    let mut slug = words.join("-");
+    slug.truncate(52);
+    slug.trim_end_matches('-').to_string()
+}
+
+fn file_content(file_id: &str) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    Ok(vos::file_content(file_id)?)
+}
+
+/// Content as text, or None if it looks binary
+fn text(data: &[u8]) -> Option<&str> {
+    (!data[..data.len().min(8000)].contains(&0)).then(|| std::str::from_utf8(data).ok()).flatten()
+}
+
+/// Git's ID of a blob, so that `git apply` can check binary patches; zeros for no file
+fn git_blob_id(data: Option<&[u8]>) -> String {
+    match data.map(|data| git2::Oid::hash_object(git2::ObjectType::Blob, data)) {
+        Some(Ok(oid)) => oid.to_string(),
+        _ => git2::Oid::zero().to_string(),
+    }
+}
+
+/// A `literal` block of a Git binary patch: the content, deflated and base85-encoded
+fn literal(data: &[u8]) -> std::io::Result<String> {
+    let mut encoder = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(data)?;
+    let deflated = encoder.finish()?;
+    let mut block = format!("literal {}\n", data.len());
+    for line in deflated.chunks(52) {
+        block.push(if line.len() <= 26 { (b'A' + line.len() as u8 - 1) as char } else { (b'a' + line.len() as u8 - 27) as char });
+        for group in line.chunks(4) {
+            let mut value = group.iter().chain(std::iter::repeat(&0)).take(4).fold(0u32, |value, &byte| value << 8 | byte as u32);
+            let mut digits = [0u8; 5];
+            for digit in digits.iter_mut().rev() {
+                *digit = BASE85[(value % 85) as usize];
+                value /= 85;
+            }
+            block.push_str(std::str::from_utf8(&digits).expect("base85 is ASCII"));
+        }
+        block.push('\n');
+    }
+    Ok(block)
+}
+
+/// Decodes the base85 lines of a `literal` block and inflates them
+fn decode_literal(lines: &[&str], size: usize) -> Result<Vec<u8>, String> {
+    let mut deflated = Vec::new();
+    for line in lines {
+        let bytes = line.as_bytes();
+        let length = match bytes.first() {
+            Some(c @ b'A'..=b'Z') => (c - b'A' + 1) as usize,
+            Some(c @ b'a'..=b'z') => (c - b'a' + 27) as usize,
+            _ => return Err("corrupt binary patch".to_string()),
+        };
+        let mut decoded = Vec::new();
+        for group in bytes[1..].chunks(5) {
+            let mut value = 0u32;
+            for c in group {
+                let digit = BASE85.iter().position(|b| b == c).ok_or("corrupt binary patch")?;
+                value = value.wrapping_mul(85).wrapping_add(digit as u32);
+            }
+            decoded.extend_from_slice(&value.to_be_bytes());
+        }
+        decoded.truncate(length);
+        deflated.extend(decoded);
+    }
+    let mut data = Vec::new();
+    flate2::read::ZlibDecoder::new(&deflated[..]).read_to_end(&mut data).map_err(|_| "corrupt binary patch")?;
+    if data.len() != size {
+        return Err("corrupt binary patch".to_string());
+    }
+    Ok(data)
+}
+
+/// The changes of one path in a patch
+#[derive(Debug, Default)]
+struct FilePatch {
+    /// None when the patch creates the file
+    old_path: Option<String>,
+    /// None when the patch deletes the file
+    new_path: Option<String>,
+    new_mode: Option<u32>,
+    hunks: Vec<PatchHunk>,
+    /// The whole new content of a binary file
+    binary: Option<Vec<u8>>,
+}
+
+#[derive(Debug)]
+struct PatchHunk {
+    old_start: usize,
+    /// Lines the hunk expects (context and removed) and the lines replacing them
+    old: Vec<String>,
+    new: Vec<String>,
+    /// Hunks at the start of a file, or without trailing context, only match there (as
+    /// in `git apply`), so a patch applied twice fails instead of repeating its changes
+    at_start: bool,
+    at_end: bool,
+}
+
+/// Applies patch files (as written by `orb format-patch` or `git format-patch`, or plain
+/// `git diff` output) to the working tree. Every change is checked before any file is
+/// written, so a patch that does not apply leaves the working tree as it was.
+pub fn apply_patches(patches: &[PathBuf], check: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let mut staged: BTreeMap<String, Option<(u32, Vec<u8>)>> = BTreeMap::new();
+    let mut files = 0;
+    for patch_path in patches {
+        let text = if patch_path.as_os_str() == "-" {
+            let mut text = String::new();
+            std::io::stdin().read_to_string(&mut text)?;
+            text
+        } else {
+            fs::read_to_string(patch_path).map_err(|e| format!("{}: {}", patch_path.display(), e))?
+        };
+        let (subjects, file_patches) = parse_patch(&text).map_err(|e| format!("{}: {}", patch_path.display(), e))?;
+        for subject in subjects {
+            info!("📄 Applying: {}", subject);
+        }
+        for file_patch in file_patches {
+            apply_file(&file_patch, &mut staged)?;
+            files += 1;
+        }
+    }
+    if files == 0 {
+        return Err("no changes found in the patch".into());
+    }
+    if check {
+        info!("✅ The patch applies cleanly ({} files)", staged.len());
+        return Ok(());
+    }
+    for (path, state) in &staged {
+        write_working_file(path, state.as_ref())?;
+    }
+    info!("✅ Applied changes to {} files", staged.len());
+    info!("💡 Review them with `orb check`, then `orb save -m \"<message>\"`");
+    Ok(())
+}
+
+fn apply_file(patch: &FilePatch, staged: &mut BTreeMap<String, Option<(u32, Vec<u8>)>>) -> Result<(), String> {
+    let current = match &patch.old_path {
+        Some(path) => match staged.get(path) {
+            Some(state) => state.clone(),
+            None => read_working_file(path),
+        },
+        None => None,
+    };
+    let label = patch.new_path.as_ref().or(patch.old_path.as_ref()).cloned().unwrap_or_default();
+    let (old_mode, old_data) = match (&patch.old_path, current) {
+        (Some(_), None) => return Err(format!("{} does not exist in the working tree", label)),
+        (Some(_), Some(current)) => current,
+        (None, _) => (0o100644, Vec::new()),
+    };
+    if patch.old_path.is_none() {
+        if let Some(path) = &patch.new_path {
+            if staged.get(path).cloned().unwrap_or_else(|| read_working_file(path)).is_some() {
+                return Err(format!("{} already exists in the working tree", path));
+            }
+        }
+    }
+
+    let new_data = match &patch.binary {
+        Some(data) => data.clone(),
+        None if patch.hunks.is_empty() => old_data,
+        None => {
+            let old_text = String::from_utf8(old_data).map_err(|_| format!("{} is binary; the patch changes it as text", label))?;
+            apply_hunks(&old_text, &patch.hunks).map_err(|hunk| format!("hunk {} does not apply to {}", hunk, label))?.into_bytes()
+        }
+    };
+    if let Some(old_path) = &patch.old_path {
+        staged.insert(old_path.clone(), None);
+    }
+    match &patch.new_path {
+        Some(new_path) => {
+            staged.insert(new_path.clone(), Some((patch.new_mode.unwrap_or(old_mode), new_data)));
+        }
+        None if !new_data.is_empty() => return Err(format!("{} does not match the content the patch deletes", label)),
+        None => {}
+    }
+    Ok(())
+}
+
+/// Applies hunks in order, each where its expected lines are found closest to its
+/// recorded position; the error is the number of the first hunk that does not match
+fn apply_hunks(text: &str, hunks: &[PatchHunk]) -> Result<String, usize> {
+    let lines = diff::split_lines(text);
+    let mut result = String::new();
+    let mut position = 0;
+    let mut offset: isize = 0;
+    for (number, hunk) in hunks.iter().enumerate() {
+        let recorded = if hunk.old.is_empty() { hunk.old_start } else { hunk.old_start.saturating_sub(1) };
+        let expected = (recorded as isize + offset).max(position as isize) as usize;
+        let fits = |at: usize| {
+            let end = at + hunk.old.len();
+            end <= lines.len()
+                && (!hunk.at_start || at == 0)
+                && (!hunk.at_end || end == lines.len())
+                && lines[at..end].iter().zip(&hunk.old).all(|(a, b)| *a == b)
+        };
+        let found = (0..=lines.len())
+            .flat_map(|distance| [expected.checked_add(distance), expected.checked_sub(distance).filter(|_| distance > 0)])
+            .flatten()
+            .filter(|at| *at >= position && *at <= lines.len())
+            .find(|at| fits(*at))
+            .ok_or(number + 1)?;
+        result.extend(lines[position..found].iter().copied());
+        result.extend(hunk.new.iter().map(String::as_str));
+        position = found + hunk.old.len();
+        offset = found as isize - recorded as isize;
+    }
+    result.extend(lines[position..].iter().copied());
+    Ok(result)
+}
+
+/// Subjects of the messages in a patch file and the file changes they hold
+fn parse_patch(text: &str) -> Result<(Vec<String>, Vec<FilePatch>), String> {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut subjects = Vec::new();
+    let mut patches = Vec::new();
+    let mut i = 0;
+    while i < lines.len() {
+        if let Some(subject) = lines[i].strip_prefix("Subject: ") {
+            subjects.push(subject.to_string());
+        }
+        let Some(header) = lines[i].strip_prefix("diff --git ") else {
+            i += 1;
+            continue;
+        };
+        let (old_path, new_path) = header
+            .strip_prefix("a/")
+            .and_then(|rest| rest.rsplit_once(" b/"))
+            .ok_or_else(|| format!("unsupported diff header '{}'", lines[i]))?;
+        let mut patch = FilePatch { old_path: Some(old_path.to_string()), new_path: Some(new_path.to_string()), ..Default::default() };
+        i += 1;
+        while i < lines.len() && !lines[i].starts_with("diff --git ") {
+            let line = lines[i];
+            let mode = |value: &str| u32::from_str_radix(value.trim(), 8).map_err(|_| format!("invalid mode in '{}'", line));
+            if let Some(value) = line.strip_prefix("new file mode ") {
+                patch.old_path = None;
+                patch.new_mode = Some(mode(value)?);
+            } else if line.starts_with("deleted file mode ") {
+                patch.new_path = None;
+            } else if let Some(value) = line.strip_prefix("new mode ") {
+                patch.new_mode = Some(mode(value)?);
+            } else if let Some(path) = line.strip_prefix("rename from ") {
+                patch.old_path = Some(path.to_string());
+            } else if let Some(path) = line.strip_prefix("rename to ") {
+                patch.new_path = Some(path.to_string());
+            } else if line.starts_with("@@ ") {
+                let (hunk, next) = parse_hunk(&lines, i)?;
+                patch.hunks.push(hunk);
+                i = next;
+                continue;
+            } else if line == "GIT binary patch" {
+                let size = lines.get(i + 1).and_then(|line| line.strip_prefix("literal ")).and_then(|size| size.parse().ok());
+                let size = size.ok_or_else(|| format!("only literal binary patches are supported ({})", new_path))?;
+                let end = (i + 2..lines.len()).find(|&j| lines[j].is_empty()).unwrap_or(lines.len());
+                patch.binary = Some(decode_literal(&lines[i + 2..end], size)?);
+                // Skip the reverse block that follows
+                i = (end + 1..lines.len()).find(|&j| lines[j].is_empty()).unwrap_or(lines.len());
+            } else if line.starts_with("Binary files ") {
+                return Err(format!("{} is binary but the patch has no content for it (use `git diff --binary`)", new_path));
+            } else if !["index ", "old mode ", "similarity index ", "--- ", "+++ "].iter().any(|prefix| line.starts_with(prefix)) {
+                break;
+            }
+            i += 1;
+        }
+        patches.push(patch);
+    }
+    Ok((subjects, patches))
+}
+
+/// Parses the hunk whose `@@` header is at `start`; returns it and the line after it
+fn parse_hunk(lines: &[&str], start: usize) -> Result<(PatchHunk, usize), String> {
+    let header = lines[start];
+    let invalid = || format!("invalid hunk header '{}'", header);
+    let ranges = header.strip_prefix("@@ -").and_then(|rest| rest.split_once(" @@")).map(|(ranges, _)| ranges).ok_or_else(invalid)?;
+    let (old_range, new_range) = ranges.split_once(" +").ok_or_else(invalid)?;
+    let parse_range = |range: &str| -> Option<(usize, usize)> {
+        match range.split_once(',') {
+            Some((start, length)) => Some((start.parse().ok()?, length.parse().ok()?)),
+            None => Some((range.parse().ok()?, 1)),
+        }
+    };
+    let (old_start, mut old_left) = parse_range(old_range).ok_or_else(invalid)?;
+    let (_, mut new_left) = parse_range(new_range).ok_or_else(invalid)?;
+
+    let mut hunk = PatchHunk { old_start, old: Vec::new(), new: Vec::new(), at_start: old_start <= 1, at_end: false };
+    let mut trailing = 0;
+    let mut i = start + 1;
+    // Which sides the previous line went to, for "\ No newline at end of file"
+    let mut last = (false, false);
+    while i < lines.len() && (old_left > 0 || new_left > 0 || lines[i].starts_with('\\')) {
+        let line = lines[i];
+        let content = format!("{}\n", line.get(1..).unwrap_or(""));
+        match line.chars().next() {
+            Some(' ') | None if old_left > 0 && new_left > 0 => {
+                hunk.old.push(content.clone());
+                hunk.new.push(content);
+                (old_left, new_left, last) = (old_left - 1, new_left - 1, (true, true));
+                trailing += 1;
+            }
+            Some('-') if old_left > 0 => {
+                hunk.old.push(content);
+                (old_left, last, trailing) = (old_left - 1, (true, false), 0);
+            }
+            Some('+') if new_left > 0 => {
+                hunk.new.push(content);
+                (new_left, last, trailing) = (new_left - 1, (false, true), 0);
+            }
+            Some('\\') => {
+                if last.0 {
+                    hunk.old.last_mut().map(String::pop);
+                }
+                if last.1 {
+                    hunk.new.last_mut().map(String::pop);
+                }
+            }
+            _ => return Err(format!("hunk '{}' ends early", header)),
+        }
+        i += 1;
+    }
+    if old_left > 0 || new_left > 0 {
+        return Err(format!("hunk '{}' ends early", header));
+    }
+    hunk.at_end = trailing == 0;
+    Ok((hunk, i))
+}
+
+/// A working-tree file's mode and content, or None if it does not exist
+fn read_working_file(path: &str) -> Option<(u32, Vec<u8>)> {
+    let metadata = fs::symlink_metadata(path).ok()?;
+    if metadata.file_type().is_symlink() {
+        let target = fs::read_link(path).ok()?;
+        return Some((SYMLINK_MODE, target.to_string_lossy().into_owned().into_bytes()));
+    }
+    Some((crate::repo::file_mode(&metadata), fs::read(path).ok()?))
+}
+
+fn write_working_file(path: &str, state: Option<&(u32, Vec<u8>)>) -> std::io::Result<()> {
+    let path = Path::new(path);
+    if path.symlink_metadata().is_ok() {
+        fs::remove_file(path)?;
+    }
+    let Some((mode, data)) = state else { return Ok(()) };
+    if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        if *mode == SYMLINK_MODE {
+            return std::os::unix::fs::symlink(String::from_utf8_lossy(data).as_ref(), path);
+        }
+        fs::write(path, data)?;
+        if *mode == 0o100755 {
+            let mut permissions = fs::metadata(path)?.permissions();
+            permissions.set_mode(permissions.mode() | ((permissions.mode() & 0o444) >> 2));
+            fs::set_permissions(path, permissions)?;
+        }
+        Ok(())
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = mode;
+        fs::write(path, data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_patch_round_trip() {
+        let old = "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n";
+        let new = "1\n2\n3\n4\n5\nsix\n7\n8\n9\n10";
+        let lines = diff::diff_lines(&diff::split_lines(old), &diff::split_lines(new));
+        let mut patch = String::from("diff --git a/f b/f\n--- a/f\n+++ b/f\n");
+        write_hunks(&lines, &mut patch);
+        let (_, files) = parse_patch(&patch).unwrap();
+        assert_eq!(apply_hunks(old, &files[0].hunks).as_deref(), Ok(new));
+        // Lines added above the change shift it without breaking the patch
+        assert_eq!(apply_hunks(&format!("0\n{}", old), &files[0].hunks), Ok(format!("0\n{}", new)));
+        // A hunk without trailing context must match at the end, so applying twice fails
+        assert_eq!(apply_hunks(&format!("{}\n11\n", new), &files[0].hunks), Err(1));
+
+        let data: Vec<u8> = (0..=255).cycle().take(1000).collect();
+        let block = literal(&data).unwrap();
+        let block_lines: Vec<&str> = block.lines().skip(1).collect();
+        assert_eq!(decode_literal(&block_lines, data.len()), Ok(data));
+    }
+
+    #[test]
+    fn test_diffstat_summary_counts_single_lines() {
+        assert_eq!(diffstat(&[("f", Some((1, 1)))]), " f | 2 +-\n 1 file changed, 1 insertion(+), 1 deletion(-)\n");
+        assert!(diffstat(&[("f", Some((2, 0))), ("g", None)]).ends_with(" 2 files changed, 2 insertions(+), 0 deletions(-)\n"));
+    }
+}
@@ -0,0 +1,179 @@
+use std::error::Error;
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use tracing::debug;
+use crate::objects::{Commit, Directory, DirectoryEntry, ObjectId, Tag};
+use crate::transfer::format_bytes;
+use crate::vos::ChunkStats;
+use orbit_core::vos::ObjectStore;
+use crate::{index, refs, vos};
+
+/// Mode of directory entries that are trees
+const TREE_MODE: u32 = 0o040000;
+
+/// An object named on the command line: the full ID of any stored object, or a
+/// revision naming a commit (an ID prefix, a branch, a tag, HEAD~2, ...)
+fn resolve_object(name: &str) -> Result<ObjectId, Box<dyn Error>> {
+    if name.len() > 2 && name.bytes().all(|c| c.is_ascii_hexdigit()) && vos::store().contains(name) {
+        return Ok(name.to_string());
+    }
+    Ok(refs::resolve(name)?)
+}
+
+/// `orb cat-file -t`: prints an object's type (commit, tag, tree, file, or chunk)
+pub fn cat_file_type(name: &str) -> Result<(), Box<dyn Error>> {
+    let id = resolve_object(name)?;
+    let data = vos::load_object(&id)?;
+    writeln!(std::io::stdout(), "{}", vos::object_type(&data))?;
+    Ok(())
+}
+
+/// `orb cat-file -p`: prints an object readably: a commit's or tag's headers and message,
+/// a tree's entries, a file's content, or a chunk's bytes
+pub fn cat_file_pretty(name: &str) -> Result<(), Box<dyn Error>> {
+    let id = resolve_object(name)?;
+    let data = vos::load_object(&id)?;
+    let mut out = std::io::stdout().lock();
+    match vos::object_type(&data) {
+        "commit" => {
+            let commit: Commit = vos::load(&id, "commit")?;
+            writeln!(out, "tree {}", commit.tree)?;
+            for parent in &commit.parents {
+                writeln!(out, "parent {}", parent)?;
+            }
+            writeln!(out, "author {} {}", commit.author, commit.timestamp)?;
+            if let Some(signature) = &commit.signature {
+                writeln!(out, "signature {}", signature)?;
+            }
+            writeln!(out, "\n{}", commit.message.trim_end())?;
+        }
+        "tag" => {
+            let tag: Tag = vos::load(&id, "tag")?;
+            writeln!(out, "object {}\ntag {}\ntagger {} {}", tag.object, tag.tag, tag.tagger, tag.timestamp)?;
+            writeln!(out, "\n{}", tag.message.trim_end())?;
+            if let Some(signature) = &tag.signature {
+                writeln!(out, "{}", signature.trim_end())?;
+            }
+        }
+        "tree" => {
+            let directory: Directory = vos::load(&id, "tree")?;
+            for entry in &directory.entries {
+                writeln!(out, "{}", format_entry(entry, &entry.name))?;
+            }
+        }
+        "file" => out.write_all(&vos::file_content(&id)?)?,
+        _ => out.write_all(&data)?,
+    }
+    Ok(())
+}
+
+/// `orb ls-tree`: lists the entries of a commit's tree (or a tree), or of the
+/// directory at `path` in it; with `recursive`, every file below it with its full path
+pub fn ls_tree(name: &str, path: Option<&str>, recursive: bool) -> Result<(), Box<dyn Error>> {
+    let id = resolve_object(name)?;
+    let mut tree = match vos::object_type(&vos::load_object(&id)?) {
+        "commit" => vos::load::<Commit>(&id, "commit")?.tree,
+        "tree" => id,
+        kind => return Err(format!("{} is a {} object, not a commit or tree", name, kind).into()),
+    };
+
+    let mut prefix = String::new();
+    let components: Vec<&str> = path.unwrap_or_default().split('/').filter(|c| !c.is_empty()).collect();
+    for (depth, component) in components.iter().enumerate() {
+        let directory: Directory = vos::load(&tree, "tree")?;
+        let entry = directory
+            .entries
+            .into_iter()
+            .find(|entry| entry.name == *component)
+            .ok_or_else(|| format!("path '{}' does not exist in {}", path.unwrap_or_default(), name))?;
+        prefix = format!("{}{}/", prefix, entry.name);
+        if entry.mode != TREE_MODE {
+            if depth + 1 < components.len() {
+                return Err(format!("'{}' is not a directory in {}", prefix.trim_end_matches('/'), name).into());
+            }
+            writeln!(std::io::stdout(), "{}", format_entry(&entry, prefix.trim_end_matches('/')))?;
+            return Ok(());
+        }
+        tree = entry.id;
+    }
+
+    let mut out = std::io::stdout().lock();
+    list_tree(&mut out, &tree, &prefix, recursive)
+}
+
+fn list_tree(out: &mut impl Write, tree: &str, prefix: &str, recursive: bool) -> Result<(), Box<dyn Error>> {
+    let directory: Directory = vos::load(tree, "tree")?;
+    for entry in &directory.entries {
+        let path = format!("{}{}", prefix, entry.name);
+        if recursive && entry.mode == TREE_MODE {
+            list_tree(out, &entry.id, &format!("{}/", path), recursive)?;
+        } else {
+            writeln!(out, "{}", format_entry(entry, &path))?;
+        }
+    }
+    Ok(())
+}
+
+/// A tree entry as `<mode> <type> <id>\t<path>`, like `git ls-tree`
+fn format_entry(entry: &DirectoryEntry, path: &str) -> String {
+    let kind = if entry.mode == TREE_MODE { "tree" } else { "file" };
+    format!("{:06o} {} {}\t{}", entry.mode, kind, entry.id, path)
+}
+
+/// `orb hash-object`: prints the File object ID `orb save` would record for each file,
+/// chunking it the same way; with `write`, its chunks and File object are stored too.
+/// Symbolic links are hashed as their target, as `save` does.
+pub fn hash_object(paths: &[PathBuf], write: bool) -> Result<(), Box<dyn Error>> {
+    if write {
+        crate::repo::open()?;
+    }
+    let store = vos::store();
+    let mut out = std::io::stdout().lock();
+    for path in paths {
+        let mut stats = ChunkStats::default();
+        let (id, _) = match fs::symlink_metadata(path)?.file_type().is_symlink() {
+            true => chunk(&store, fs::read_link(path)?.to_string_lossy().as_bytes(), &mut stats, write)?,
+            false => chunk(&store, fs::File::open(path)?, &mut stats, write)?,
+        };
+        debug!("{}: {} chunks, {} not stored yet ({})", path.display(), stats.chunks, stats.new_chunks, format_bytes(stats.new_bytes));
+        writeln!(out, "{}", id)?;
+    }
+    Ok(())
+}
+
+fn chunk(store: &ObjectStore, content: impl Read, stats: &mut ChunkStats, write: bool) -> std::io::Result<(ObjectId, usize)> {
+    match write {
+        true => store.save_stream(content, stats),
+        false => store.hash_stream(content, stats),
+    }
+}
+
+/// `orb ls-files`: lists the files recorded in the index by the last save or checkout;
+/// with `long`, also their File object ID and size
+pub fn ls_files(long: bool) -> Result<(), Box<dyn Error>> {
+    let index = index::VosIndex::load(&Path::new(crate::repo::ORB_DIR).join("index"))?;
+    let mut entries: Vec<_> = index.entries.values().collect();
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+    let mut out = std::io::stdout().lock();
+    for entry in entries {
+        match long {
+            true => writeln!(out, "{} {:>10}\t{}", entry.file_id, entry.size, entry.path)?,
+            false => writeln!(out, "{}", entry.path)?,
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_entry() {
+        let entry = DirectoryEntry { mode: 0o100644, name: "lib.rs".to_string(), id: "ab12".to_string() };
+        assert_eq!(format_entry(&entry, "src/lib.rs"), "100644 file ab12\tsrc/lib.rs");
+        let entry = DirectoryEntry { mode: TREE_MODE, name: "src".to_string(), id: "cd34".to_string() };
+        assert_eq!(format_entry(&entry, "src"), "040000 tree cd34\tsrc");
+    }
+}
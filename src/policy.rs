@@ -0,0 +1,115 @@
+use std::collections::HashSet;
+use serde::{Deserialize, Serialize};
+use crate::hosted::HostedRepo;
+use crate::objects::{Directory, File, ObjectId};
+use crate::refs::RefChange;
+
+/// Built-in push rules of a hosted repository, kept in `.orb/policy.json`. They are checked
+/// before the pre-receive hook, so a push they reject never reaches it.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Policy {
+    /// Every new commit must carry a signature
+    #[serde(default)]
+    pub require_signed_commits: bool,
+    /// Largest file, in bytes, a new commit may contain
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_file_size: Option<u64>,
+    /// Branches that only accept fast-forwards; a trailing `*` matches any suffix
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub protected_branches: Vec<String>,
+}
+
+impl Policy {
+    pub fn is_protected(&self, branch: &str) -> bool {
+        self.protected_branches.iter().any(|pattern| match pattern.strip_suffix('*') {
+            Some(prefix) => branch.starts_with(prefix),
+            None => branch == pattern,
+        })
+    }
+
+    /// Checks ref changes against the rules, returning the message to send back as Error
+    pub fn check(&self, repo: &HostedRepo, changes: &[RefChange]) -> Result<(), String> {
+        for change in changes {
+            let (Some(branch), Some(old), Some(new)) =
+                (change.name.strip_prefix("refs/heads/"), &change.old, &change.new)
+            else {
+                continue;
+            };
+            if self.is_protected(branch) && !repo.is_ancestor(old, new) {
+                return Err(format!("'{}' is a protected branch and cannot be force-pushed", branch));
+            }
+        }
+        if !self.require_signed_commits && self.max_file_size.is_none() {
+            return Ok(());
+        }
+
+        let mut checked_trees = HashSet::new();
+        for id in new_commits(repo, changes) {
+            let Some(commit) = repo.load_commit(&id) else { continue };
+            if self.require_signed_commits && commit.signature.as_deref().is_none_or(str::is_empty) {
+                return Err(format!("commit {} is not signed (this repository requires signed commits)", &id[..7]));
+            }
+            if let Some(limit) = self.max_file_size {
+                if let Some((path, size)) = largest_over(repo, &commit.tree, "", limit, &mut checked_trees) {
+                    return Err(format!(
+                        "commit {} adds {} ({} bytes), over the {} byte file size limit",
+                        &id[..7], path, size, limit
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Commits the changed refs would make reachable that no current branch or tag reaches
+fn new_commits(repo: &HostedRepo, changes: &[RefChange]) -> Vec<ObjectId> {
+    let mut known = HashSet::new();
+    for tip in repo.branches().into_values().chain(repo.tags().into_values()) {
+        if !known.contains(&tip) {
+            known.extend(repo.ancestors(&tip));
+        }
+    }
+    let mut new = Vec::new();
+    for change in changes {
+        let Some(tip) = &change.new else { continue };
+        for id in repo.ancestors(tip) {
+            if known.insert(id.clone()) {
+                new.push(id);
+            }
+        }
+    }
+    new
+}
+
+/// First file in a tree that is larger than `limit`, with its path. Trees in `checked`
+/// are skipped, since commits mostly share them.
+fn largest_over(repo: &HostedRepo, tree: &str, prefix: &str, limit: u64, checked: &mut HashSet<ObjectId>) -> Option<(String, u64)> {
+    if !checked.insert(tree.to_string()) {
+        return None;
+    }
+    let directory = repo.load::<Directory>(tree)?;
+    directory.entries.iter().find_map(|entry| {
+        let path = format!("{}{}", prefix, entry.name);
+        if entry.mode == 0o040000 {
+            largest_over(repo, &entry.id, &format!("{}/", path), limit, checked)
+        } else {
+            let file = repo.load::<File>(&entry.id)?;
+            (file.size as u64 > limit).then_some((path, file.size as u64))
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_protected_branch_patterns() {
+        let policy = Policy { protected_branches: vec!["main".to_string(), "release-*".to_string()], ..Policy::default() };
+        assert!(policy.is_protected("main"));
+        assert!(policy.is_protected("release-1.2"));
+        assert!(!policy.is_protected("mainline"));
+        assert!(!policy.is_protected("feature"));
+    }
+}
@@ -0,0 +1,143 @@
+use std::io::IsTerminal;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use indicatif::{ProgressBar, ProgressStyle};
+use crate::transfer::format_bytes;
+use tracing::{info, Level};
+
+/// How often progress is logged when output is not a terminal
+const LOG_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Overall progress of a long operation (a transfer, a save, a conversion), one phase
+/// at a time.
+///
+/// On a terminal this is an indicatif bar (or a spinner when the total is unknown);
+/// otherwise, e.g. in CI logs, it prints a plain line every few seconds. Nothing is
+/// shown with `--quiet`.
+#[derive(Debug)]
+pub struct Progress {
+    interactive: bool,
+    phase: Mutex<Option<Phase>>,
+}
+
+#[derive(Debug)]
+struct Phase {
+    name: &'static str,
+    /// What is counted: objects, files, commits
+    unit: &'static str,
+    total: Option<u64>,
+    objects: u64,
+    bytes: u64,
+    bar: Option<ProgressBar>,
+    last_log: Instant,
+}
+
+impl Phase {
+    fn log(&mut self) {
+        let count = match self.total {
+            Some(total) => format!("{}/{}", self.objects, total),
+            None => self.objects.to_string(),
+        };
+        info!("⏳ {}: {} {}, {}", self.name, count, self.unit, format_bytes(self.bytes));
+        self.last_log = Instant::now();
+    }
+}
+
+impl Progress {
+    pub fn new() -> Self {
+        Self {
+            interactive: std::io::stdout().is_terminal() && std::io::stderr().is_terminal() && tracing::enabled!(Level::INFO),
+            phase: Mutex::new(None),
+        }
+    }
+
+    /// Finishes the current phase and starts `name`; a known `total` object count shows a bar with ETA
+    pub fn begin(&self, name: &'static str, total: Option<u64>) {
+        self.begin_counting(name, total, "objects");
+    }
+
+    /// Like `begin`, counting `unit` (e.g. "files" or "commits") instead of objects
+    pub fn begin_counting(&self, name: &'static str, total: Option<u64>, unit: &'static str) {
+        self.finish();
+        let bar = self.interactive.then(|| {
+            let bar = match total {
+                Some(total) => ProgressBar::new(total).with_style(
+                    ProgressStyle::with_template(&format!("{{prefix:>12}} [{{bar:30}}] {{pos}}/{{len}} {}, {{msg}} (ETA {{eta}})", unit))
+                        .unwrap()
+                        .progress_chars("=> "),
+                ),
+                None => ProgressBar::new_spinner().with_style(
+                    ProgressStyle::with_template(&format!("{{prefix:>12}} {{spinner}} {{pos}} {}, {{msg}} ({{elapsed}})", unit)).unwrap(),
+                ),
+            };
+            bar.set_prefix(name);
+            bar.set_message(format_bytes(0));
+            bar.enable_steady_tick(Duration::from_millis(120));
+            bar
+        });
+        *self.phase.lock().unwrap() = Some(Phase { name, unit, total, objects: 0, bytes: 0, bar, last_log: Instant::now() });
+    }
+
+    /// Counts one object (or file, or commit) of `bytes` bytes in the current phase
+    pub fn advance(&self, bytes: u64) {
+        let mut phase = self.phase.lock().unwrap();
+        let Some(phase) = phase.as_mut() else { return };
+        phase.objects += 1;
+        phase.bytes += bytes;
+        match &phase.bar {
+            Some(bar) => {
+                bar.inc(1);
+                bar.set_message(format_bytes(phase.bytes));
+            }
+            None if phase.last_log.elapsed() >= LOG_INTERVAL => phase.log(),
+            None => {}
+        }
+    }
+
+    /// Sets the current phase's totals so far, for sources that report cumulative counts
+    pub fn set(&self, objects: u64, bytes: u64) {
+        let mut phase = self.phase.lock().unwrap();
+        let Some(phase) = phase.as_mut() else { return };
+        phase.objects = objects;
+        phase.bytes = bytes;
+        match &phase.bar {
+            Some(bar) => {
+                bar.set_position(objects);
+                bar.set_message(format_bytes(bytes));
+            }
+            None if phase.last_log.elapsed() >= LOG_INTERVAL => phase.log(),
+            None => {}
+        }
+    }
+
+    /// Prints a line above the bar without garbling it
+    pub fn println(&self, line: &str) {
+        match self.phase.lock().unwrap().as_ref().and_then(|phase| phase.bar.as_ref()) {
+            Some(bar) => bar.suspend(|| info!("{}", line)),
+            None => info!("{}", line),
+        }
+    }
+
+    /// Ends the current phase, clearing its bar or logging its final totals
+    pub fn finish(&self) {
+        if let Some(mut phase) = self.phase.lock().unwrap().take() {
+            match phase.bar.take() {
+                Some(bar) => bar.finish_and_clear(),
+                None if phase.objects > 0 => phase.log(),
+                None => {}
+            }
+        }
+    }
+}
+
+impl Default for Progress {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for Progress {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
@@ -0,0 +1,155 @@
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fs;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+use orbit_core::Repository;
+use crate::objects::{Commit, ObjectId};
+use crate::repo::{self, RepoError};
+use crate::{ancestry, merge, refs, style, vos};
+
+/// File in `.orb` holding the state of a rebase stopped by conflicts
+const REBASE_FILE: &str = "REBASE";
+
+/// A rebase in progress. The branch keeps pointing at `orig_head` until every commit
+/// is replayed, so aborting only has to restore the working tree.
+#[derive(Debug, Serialize, Deserialize)]
+struct Rebase {
+    branch: String,
+    /// Tip of the branch before the rebase
+    orig_head: ObjectId,
+    /// Commit the branch is being replayed onto
+    onto: ObjectId,
+    /// Tip of the commits replayed so far
+    tip: ObjectId,
+    /// Commits still to replay, oldest first
+    todo: VecDeque<ObjectId>,
+    /// The commit whose conflicts are being resolved, and the tree its replay was
+    /// checked out from
+    stopped: Option<(ObjectId, ObjectId)>,
+    replayed: usize,
+}
+
+fn load(repository: &Repository) -> Result<Rebase, Box<dyn Error>> {
+    match fs::read_to_string(repository.orb_dir().join(REBASE_FILE)) {
+        Ok(data) => Ok(serde_json::from_str(&data)?),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Err(RepoError::NotRebasing.into()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// The commit whose replay stopped with conflicts, while a rebase is in progress
+pub fn stopped_at(repository: &Repository) -> Option<ObjectId> {
+    load(repository).ok().and_then(|rebase| rebase.stopped).map(|(id, _)| id)
+}
+
+/// Fails while a rebase is in progress
+pub fn ensure_not_rebasing(repository: &Repository) -> Result<(), RepoError> {
+    match repository.orb_dir().join(REBASE_FILE).exists() {
+        true => Err(RepoError::RebaseInProgress),
+        false => Ok(()),
+    }
+}
+
+fn short(id: &str) -> String {
+    style::id(&id[..7.min(id.len())]).into_owned()
+}
+
+/// `orb rebase`: replays the commits of the current branch that `upstream` lacks on top
+/// of it, one at a time and oldest first, then moves the branch to the last replayed
+/// commit. Each keeps its author and message; merge commits are left out, so the
+/// result is linear. The working tree must have no changes to tracked files.
+///
+/// A replay that changes the same lines as `upstream` stops with conflict markers; the
+/// state is kept in `.orb/REBASE` for `--continue` or `--abort`.
+pub fn rebase(upstream: &str) -> Result<(), Box<dyn Error>> {
+    let repository = repo::open_worktree()?;
+    repo::ensure_idle(&repository)?;
+    repo::ensure_clean(&repository)?;
+    let head = refs::head_commit().ok_or(RepoError::NoCommits)?;
+    let onto = refs::resolve(upstream)?;
+    let branch = repository.current_branch();
+    if ancestry::is_ancestor(&onto, &head) {
+        info!("✅ '{}' is already based on {}", branch, upstream);
+        return Ok(());
+    }
+
+    let commits = ancestry::parents_first(&head, &ancestry::ancestors(&onto))?;
+    let merges = commits.iter().filter(|(_, commit)| commit.parents.len() > 1).count();
+    if merges > 0 {
+        info!("   Leaving out {} merge commit(s)", merges);
+    }
+    let todo: VecDeque<ObjectId> = commits.into_iter().filter(|(_, commit)| commit.parents.len() <= 1).map(|(id, _)| id).collect();
+    info!("♻️  Rebasing {} commit(s) of '{}' onto {}", todo.len(), branch, short(&onto));
+    let mut rebase = Rebase { branch, orig_head: head, onto: onto.clone(), tip: onto, todo, stopped: None, replayed: 0 };
+    advance(&repository, &mut rebase)
+}
+
+/// `orb rebase --continue`: commits the resolved working tree in place of the commit
+/// that stopped, then replays the rest
+pub fn resume() -> Result<(), Box<dyn Error>> {
+    let repository = repo::open_worktree()?;
+    let mut rebase = load(&repository)?;
+    let (id, tree) = rebase.stopped.take().ok_or(RepoError::NotRebasing)?;
+    let conflicts = repository.index()?.conflicts.len();
+    if conflicts > 0 {
+        return Err(orbit_core::Error::UnresolvedConflicts(conflicts).into());
+    }
+    let commit = repository.load_commit(&id)?;
+    let files = repo::worktree_files(&repository, repository.tree_files(&tree)?)?;
+    commit_replay(&mut rebase, &id, commit, &files)?;
+    advance(&repository, &mut rebase)
+}
+
+/// `orb rebase --abort`: forgets the rebase and restores the working tree to the
+/// branch, which never moved
+pub fn abort() -> Result<(), Box<dyn Error>> {
+    let repository = repo::open_worktree()?;
+    let rebase = load(&repository)?;
+    repository.replace_worktree(&repository.load_commit(&rebase.orig_head)?.tree)?;
+    fs::remove_file(repository.orb_dir().join(REBASE_FILE))?;
+    info!("↩️  Rebase aborted; '{}' is back at {}", rebase.branch, short(&rebase.orig_head));
+    Ok(())
+}
+
+/// Replays the commits left to do; stops at the first conflict, otherwise finishes
+/// by moving the branch
+fn advance(repository: &Repository, rebase: &mut Rebase) -> Result<(), Box<dyn Error>> {
+    while let Some(id) = rebase.todo.pop_front() {
+        let commit = repository.load_commit(&id)?;
+        let (files, conflicts) = merge::replay(&commit, &rebase.tip, &short(&id))?;
+        if !conflicts.is_empty() {
+            rebase.stopped = Some((id.clone(), merge::build_tree(&files)?));
+            fs::write(repository.orb_dir().join(REBASE_FILE), serde_json::to_string_pretty(&*rebase)?)?;
+            let count = merge::write_conflicts(repository, &files, conflicts)?;
+            warn!("💡 Replaying {} stopped: {}", short(&id), subject(&commit.message));
+            warn!("   Fix the conflicts, mark each with `orb resolve <path>`, then run `orb rebase --continue`");
+            warn!("   (or `orb rebase --abort` to go back)");
+            return Err(RepoError::RebaseConflicts(count).into());
+        }
+        if files == repository.commit_files(&rebase.tip)? {
+            info!("   Skipped {} (its changes are already there): {}", short(&id), subject(&commit.message));
+            continue;
+        }
+        commit_replay(rebase, &id, commit, &files)?;
+    }
+
+    repository.replace_worktree(&repository.load_commit(&rebase.tip)?.tree)?;
+    refs::write_ref(&refs::branch_ref(&rebase.branch), &rebase.tip)?;
+    let _ = fs::remove_file(repository.orb_dir().join(REBASE_FILE));
+    info!("✅ Rebased {} commit(s) of '{}' onto {} (now at {})", rebase.replayed, rebase.branch, short(&rebase.onto), short(&rebase.tip));
+    Ok(())
+}
+
+/// Saves `files` as the replay of `commit` on top of the rebased tip
+fn commit_replay(rebase: &mut Rebase, id: &str, commit: Commit, files: &merge::FileMap) -> Result<(), Box<dyn Error>> {
+    let replayed = Commit { tree: merge::build_tree(files)?, parents: vec![rebase.tip.clone()], ..commit };
+    rebase.tip = vos::store().save_json(&replayed)?;
+    rebase.replayed += 1;
+    info!("  ♻️  Replayed {} as {}: {}", short(id), short(&rebase.tip), subject(&replayed.message));
+    Ok(())
+}
+
+fn subject(message: &str) -> &str {
+    message.lines().next().unwrap_or_default()
+}
@@ -0,0 +1,179 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+use orbit_core::{refs, ORB_DIR};
+use crate::objects::ObjectId;
+use crate::repo::RepoError;
+
+pub use orbit_core::refs::{annotation_ref, branch_ref, tag_ref, DEFAULT_BRANCH};
+
+/// Name of the branch HEAD points to (`ref: refs/heads/<branch>`)
+pub fn current_branch() -> String {
+    refs::current_branch(Path::new(ORB_DIR))
+}
+
+/// Points HEAD at a local branch (`ref: refs/heads/<branch>`)
+pub fn set_head_branch(branch: &str) -> Result<(), std::io::Error> {
+    refs::set_head_branch(Path::new(ORB_DIR), branch)
+}
+
+/// Reads a ref such as `refs/heads/main`, returning None if it is absent or empty
+pub fn read_ref(name: &str) -> Option<ObjectId> {
+    refs::read_ref(Path::new(ORB_DIR), name)
+}
+
+/// Points a ref at a commit, creating parent directories as needed
+pub fn write_ref(name: &str, commit_id: &str) -> Result<(), std::io::Error> {
+    refs::write_ref(Path::new(ORB_DIR), name, commit_id)
+}
+
+/// Removes a ref; a ref that does not exist is not an error
+pub fn delete_ref(name: &str) -> Result<(), std::io::Error> {
+    refs::delete_ref(Path::new(ORB_DIR), name)
+}
+
+/// All local tags and the objects they point to
+pub fn list_tags() -> BTreeMap<String, ObjectId> {
+    refs::list_tags(Path::new(ORB_DIR))
+}
+
+/// Tag objects of annotated tags, by tag name
+pub fn list_annotations() -> BTreeMap<String, ObjectId> {
+    refs::list_annotations(Path::new(ORB_DIR))
+}
+
+/// All local branches and their tip commits
+pub fn list_branches() -> BTreeMap<String, ObjectId> {
+    refs::list_branches(Path::new(ORB_DIR))
+}
+
+/// Commit at the tip of the current branch
+pub fn head_commit() -> Option<ObjectId> {
+    refs::head_commit(Path::new(ORB_DIR))
+}
+
+/// Ref holding the last known tip of `branch` on `remote`, e.g. `refs/remotes/origin/main`
+pub fn remote_tracking_ref(remote: &str, branch: &str) -> String {
+    format!("refs/remotes/{}/{}", remote, branch)
+}
+
+/// After a successful sync the server holds exactly our HEAD, so record it as the remote's tip
+pub fn update_remote_tracking(remote: &str, branch: &str) -> Result<(), std::io::Error> {
+    match head_commit() {
+        Some(head) => write_ref(&remote_tracking_ref(remote, branch), &head),
+        None => Ok(()),
+    }
+}
+
+/// Resolves a revision to a commit ID: `HEAD`, a branch, a tag, a remote-tracking ref
+/// (`origin/main`), a full ref name, or a full or abbreviated commit ID, optionally
+/// followed by `~N` or `^` to step back along first parents
+pub fn resolve(rev: &str) -> Result<ObjectId, RepoError> {
+    let split = rev.find(['~', '^']).unwrap_or(rev.len());
+    let (name, mut suffix) = rev.split_at(split);
+    let mut id = resolve_name(name)?;
+    while let Some(step) = suffix.chars().next() {
+        let digits = suffix[1..].chars().take_while(char::is_ascii_digit).count();
+        let count = match &suffix[1..1 + digits] {
+            "" => 1,
+            number if step == '~' => number.parse().map_err(|_| RepoError::InvalidRevision(rev.to_string()))?,
+            _ => return Err(RepoError::NotFirstParent(rev.to_string())),
+        };
+        for _ in 0..count {
+            id = crate::ancestry::load_commit(&id)
+                .and_then(|commit| commit.parents.into_iter().next())
+                .ok_or_else(|| RepoError::PastRoot(rev.to_string()))?;
+        }
+        suffix = &suffix[1 + digits..];
+        if !suffix.is_empty() && !suffix.starts_with(['~', '^']) {
+            return Err(RepoError::InvalidRevision(rev.to_string()));
+        }
+    }
+    Ok(id)
+}
+
+fn resolve_name(name: &str) -> Result<ObjectId, RepoError> {
+    if name == "HEAD" {
+        return head_commit().ok_or(RepoError::NoCommits);
+    }
+    let by_ref = [name.to_string(), branch_ref(name), tag_ref(name), format!("refs/remotes/{}", name)]
+        .iter()
+        .filter(|full| full.starts_with("refs/"))
+        .find_map(|full| read_ref(full));
+    if let Some(id) = by_ref {
+        return Ok(id);
+    }
+    if name.len() < 4 || !name.bytes().all(|c| c.is_ascii_digit() || (b'a'..=b'f').contains(&c)) {
+        return Err(RepoError::UnknownRevision(name.to_string()));
+    }
+    // An abbreviated ID names the one commit whose ID starts with it
+    let dir = &name[..2];
+    let matches: Vec<ObjectId> = fs::read_dir(Path::new(ORB_DIR).join("objects").join(dir))
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| format!("{}{}", dir, entry.file_name().to_string_lossy()))
+                .filter(|id| id.starts_with(name) && crate::ancestry::load_commit(id).is_some())
+                .collect()
+        })
+        .unwrap_or_default();
+    match matches.len() {
+        1 => Ok(matches.into_iter().next().expect("one match")),
+        0 => Err(RepoError::UnknownRevision(name.to_string())),
+        _ => Err(RepoError::AmbiguousRevision(name.to_string())),
+    }
+}
+
+/// A ref whose value changed; None means the ref did not exist on that side
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RefChange {
+    pub name: String,
+    pub old: Option<ObjectId>,
+    pub new: Option<ObjectId>,
+}
+
+/// Every ref under `.orb/refs` (branches, tags, remote-tracking refs) by full name
+pub fn snapshot() -> BTreeMap<String, ObjectId> {
+    fn walk(dir: &Path, prefix: &str, refs: &mut BTreeMap<String, ObjectId>) {
+        let Ok(entries) = fs::read_dir(dir) else { return };
+        for entry in entries.filter_map(|entry| entry.ok()) {
+            let name = format!("{}/{}", prefix, entry.file_name().to_string_lossy());
+            if entry.path().is_dir() {
+                walk(&entry.path(), &name, refs);
+            } else if let Some(id) = read_ref(&name) {
+                refs.insert(name, id);
+            }
+        }
+    }
+
+    let mut refs = BTreeMap::new();
+    walk(&Path::new(ORB_DIR).join("refs"), "refs", &mut refs);
+    refs
+}
+
+/// `HEAD` and the full names of the refs pointing at each commit
+pub fn by_commit() -> HashMap<ObjectId, Vec<String>> {
+    let mut refs: HashMap<ObjectId, Vec<String>> = HashMap::new();
+    if let Some(head) = head_commit() {
+        refs.entry(head).or_default().push("HEAD".to_string());
+    }
+    for (name, id) in snapshot() {
+        refs.entry(id).or_default().push(name);
+    }
+    refs
+}
+
+/// Refs that were created, moved, or deleted between two snapshots
+pub fn changes(before: &BTreeMap<String, ObjectId>, after: &BTreeMap<String, ObjectId>) -> Vec<RefChange> {
+    let names: std::collections::BTreeSet<&String> = before.keys().chain(after.keys()).collect();
+    names
+        .into_iter()
+        .filter(|name| before.get(*name) != after.get(*name))
+        .map(|name| RefChange {
+            name: name.clone(),
+            old: before.get(name).cloned(),
+            new: after.get(name).cloned(),
+        })
+        .collect()
+}
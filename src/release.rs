@@ -0,0 +1,96 @@
+use std::error::Error;
+use std::fmt;
+use tracing::{debug, info};
+use crate::style;
+use crate::{refs, repo, tag};
+
+/// Which part of the version `orb release` increments
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Bump {
+    Major,
+    Minor,
+    Patch,
+}
+
+/// A `MAJOR.MINOR.PATCH` release version
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Version {
+    pub major: u64,
+    pub minor: u64,
+    pub patch: u64,
+}
+
+impl Version {
+    /// Parses a release tag such as `v1.2.3` or `1.2.3`; pre-releases, build metadata
+    /// and other tags are not releases and give None
+    pub fn parse(tag: &str) -> Option<Self> {
+        let mut parts = tag.strip_prefix('v').unwrap_or(tag).split('.');
+        let mut next = || parts.next().filter(|p| !p.is_empty() && p.bytes().all(|c| c.is_ascii_digit()))?.parse().ok();
+        let version = Version { major: next()?, minor: next()?, patch: next()? };
+        parts.next().is_none().then_some(version)
+    }
+
+    /// The next version: a bump resets the parts after it
+    pub fn bump(self, bump: Bump) -> Self {
+        match bump {
+            Bump::Major => Version { major: self.major + 1, minor: 0, patch: 0 },
+            Bump::Minor => Version { minor: self.minor + 1, patch: 0, ..self },
+            Bump::Patch => Version { patch: self.patch + 1, ..self },
+        }
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// The highest released version among `tags`, with the prefix (`v` or none) its tag used
+pub fn latest<'a>(tags: impl IntoIterator<Item = &'a String>) -> Option<(Version, &'static str)> {
+    tags.into_iter()
+        .filter_map(|tag| Version::parse(tag).map(|version| (version, if tag.starts_with('v') { "v" } else { "" })))
+        .max()
+}
+
+/// `orb release`: tags HEAD with the next version after the latest release tag (`v0.0.0`
+/// when there is none) and records an annotated Tag object for it, signed with `gpg`
+/// when `sign` is set. Returns the new tag's name.
+pub fn create(bump: Bump, message: Option<&str>, sign: bool) -> Result<String, Box<dyn Error>> {
+    let repository = repo::open()?;
+    let head = refs::head_commit().ok_or(repo::RepoError::NoCommits)?;
+    if !repository.status()?.is_empty() {
+        return Err("the working tree has unsaved changes; save or revert them before releasing".into());
+    }
+
+    let tags = refs::list_tags();
+    if let Some(name) = tags.iter().find(|(name, id)| **id == head && Version::parse(name).is_some()).map(|(name, _)| name) {
+        return Err(format!("HEAD is already released as '{}'", name).into());
+    }
+    let (version, prefix) = latest(tags.keys()).unwrap_or((Version { major: 0, minor: 0, patch: 0 }, "v"));
+    let name = format!("{}{}", prefix, version.bump(bump));
+    debug!("Latest release is {}{}; next is {}", prefix, version, name);
+
+    let message = message.map_or_else(|| format!("Release {}", name), str::to_string);
+    tag::annotate(&name, &head, &message, sign)?;
+
+    info!("🏷️  Tagged {} as '{}'{}", style::id(&head[..7.min(head.len())]), name, if sign { " (signed)" } else { "" });
+    Ok(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_next_version_from_tags() {
+        let tags: Vec<String> = ["v1.2.3", "v1.10.0", "v2.0.0-rc.1", "nightly", "1.9.9"].iter().map(|t| t.to_string()).collect();
+        let (version, prefix) = latest(&tags).unwrap();
+        assert_eq!((version.to_string(), prefix), ("1.10.0".to_string(), "v"));
+        assert_eq!(version.bump(Bump::Patch).to_string(), "1.10.1");
+        assert_eq!(version.bump(Bump::Minor).to_string(), "1.11.0");
+        assert_eq!(version.bump(Bump::Major).to_string(), "2.0.0");
+        assert_eq!(Version::parse("v1.2"), None);
+        assert_eq!(Version::parse("v1.2.3.4"), None);
+    }
+}
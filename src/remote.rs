@@ -0,0 +1,221 @@
+use crate::client_tls::OrbitUrl;
+use crate::config::{self, Config};
+use crate::transport::NetOverrides;
+
+/// Remote used when a command is given no URL or remote name and `core.remote` is unset
+pub const DEFAULT_REMOTE: &str = "origin";
+
+/// A named remote stored as `[remote "<name>"]` in `.orb/config`
+#[derive(Debug, Clone)]
+pub struct Remote {
+    /// Remote name, or None when a raw URL was given on the command line
+    pub name: Option<String>,
+    pub url: String,
+    /// Branch that pull/push use by default
+    pub branch: Option<String>,
+    /// Environment variable holding the token for this remote (auth hint)
+    pub token_env: Option<String>,
+    /// `sha256/<base64>` SPKI pins the server certificate must match (`remote.<name>.pin`)
+    pub pins: Vec<String>,
+    /// Timeouts and address family for this remote (`remote.<name>.connect_timeout` etc.)
+    pub net: NetOverrides,
+}
+
+impl Remote {
+    /// Reads a named remote from the config
+    pub fn load(config: &Config, name: &str) -> Option<Self> {
+        let url = config.get(&format!("remote.{}.url", name))?;
+        Some(Self {
+            name: Some(name.to_string()),
+            url: url.to_string(),
+            branch: config
+                .get(&format!("remote.{}.branch", name))
+                .map(str::to_string)
+                .or_else(|| url_branch(url)),
+            token_env: config.get(&format!("remote.{}.token_env", name)).map(str::to_string),
+            pins: config
+                .get(&format!("remote.{}.pin", name))
+                .map(|pins| pins.split(',').map(str::trim).filter(|p| !p.is_empty()).map(str::to_string).collect())
+                .unwrap_or_default(),
+            net: NetOverrides::from_config(config, &format!("remote.{}", name)),
+        })
+    }
+
+    /// Resolves a command-line argument that may be a remote name or a server URL.
+    /// With no argument, the default remote (see `default_name`) is used.
+    pub fn resolve(target: Option<&str>) -> Result<Self, Box<dyn std::error::Error>> {
+        let config = config::load()?;
+        let default = default_name(&config);
+        let name = target.unwrap_or(&default);
+
+        if let Some(remote) = Self::load(&config, name) {
+            return Ok(remote);
+        }
+        match target {
+            Some(url) => Ok(Self { name: None, url: url.to_string(), branch: url_branch(url), token_env: None, pins: Vec::new(), net: NetOverrides::default() }),
+            None => Err(format!(
+                "No URL given and no '{}' remote configured (add one with: orb remote add {} <url>)",
+                default, default
+            ).into()),
+        }
+    }
+
+    /// Parses the remote's URL, carrying its certificate pins and connection settings along
+    pub fn orbit_url(&self) -> Result<OrbitUrl, Box<dyn std::error::Error>> {
+        let mut url = OrbitUrl::parse(&self.url)?;
+        url.pins = self.pins.clone();
+        url.net = self.net.clone();
+        Ok(url)
+    }
+
+    /// True for `peer://` remotes (another working copy running `orb share`)
+    pub fn is_peer(&self) -> bool {
+        crate::client_tls::is_peer_url(&self.url)
+    }
+
+    /// Remote branch this remote syncs with: its configured branch, else the current branch
+    pub fn tracked_branch(&self) -> String {
+        self.branch.clone().unwrap_or_else(crate::refs::current_branch)
+    }
+
+    /// Saves this remote under its name, replacing any existing settings
+    fn store(&self, config: &mut Config) -> Result<(), String> {
+        let name = self.name.as_deref().ok_or("remote has no name")?;
+        config.remove_subsection("remote", name);
+        config.set(&format!("remote.{}.url", name), &self.url)?;
+        if let Some(branch) = &self.branch {
+            config.set(&format!("remote.{}.branch", name), branch)?;
+        }
+        if let Some(token_env) = &self.token_env {
+            config.set(&format!("remote.{}.token_env", name), token_env)?;
+        }
+        if !self.pins.is_empty() {
+            config.set(&format!("remote.{}.pin", name), &self.pins.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Branch named by a `?branch=` parameter in a remote URL
+fn url_branch(url: &str) -> Option<String> {
+    OrbitUrl::parse(url).ok().and_then(|url| url.branch)
+}
+
+/// Name of the remote used when none is given: `core.remote`, else `origin`
+pub fn default_name(config: &Config) -> String {
+    config.get("core.remote").unwrap_or(DEFAULT_REMOTE).to_string()
+}
+
+/// Makes `name` the remote used by sync, pull, and push when none is given (`orb remote default`)
+pub fn set_default(name: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let mut config = load_repo_config()?;
+    if Remote::load(&config, name).is_none() {
+        return Err(format!("No such remote: '{}'", name).into());
+    }
+    config.set("core.remote", name)?;
+    config::save(&config)?;
+    Ok(())
+}
+
+/// Lists all configured remotes in file order
+pub fn list() -> Result<Vec<Remote>, Box<dyn std::error::Error>> {
+    let config = config::load()?;
+    Ok(config
+        .subsections("remote")
+        .into_iter()
+        .filter_map(|name| Remote::load(&config, name))
+        .collect())
+}
+
+/// Adds a new remote (`orb remote add`)
+pub fn add(
+    name: &str,
+    url: &str,
+    branch: Option<&str>,
+    token_env: Option<&str>,
+    pins: &[String],
+) -> Result<(), Box<dyn std::error::Error>> {
+    validate_name(name)?;
+    OrbitUrl::parse(url).map_err(|e| format!("Invalid remote URL '{}': {}", url, e))?;
+    for pin in pins {
+        crate::client_tls::parse_pin(pin)?;
+    }
+
+    let mut config = load_repo_config()?;
+    if Remote::load(&config, name).is_some() {
+        return Err(format!("Remote '{}' already exists", name).into());
+    }
+
+    let remote = Remote {
+        name: Some(name.to_string()),
+        url: url.to_string(),
+        branch: branch.map(str::to_string),
+        token_env: token_env.map(str::to_string),
+        pins: pins.to_vec(),
+        net: NetOverrides::default(),
+    };
+    remote.store(&mut config)?;
+    config::save(&config)?;
+    Ok(())
+}
+
+/// Removes a remote (`orb remote remove`)
+pub fn remove(name: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let mut config = load_repo_config()?;
+    if !config.remove_subsection("remote", name) {
+        return Err(format!("No such remote: '{}'", name).into());
+    }
+    config::save(&config)?;
+    Ok(())
+}
+
+/// Renames a remote (`orb remote rename`)
+pub fn rename(old: &str, new: &str) -> Result<(), Box<dyn std::error::Error>> {
+    validate_name(new)?;
+    let mut config = load_repo_config()?;
+    if Remote::load(&config, new).is_some() {
+        return Err(format!("Remote '{}' already exists", new).into());
+    }
+    if !config.rename_subsection("remote", old, new) {
+        return Err(format!("No such remote: '{}'", old).into());
+    }
+    if config.get("core.remote") == Some(old) {
+        config.set("core.remote", new)?;
+    }
+    config::save(&config)?;
+    Ok(())
+}
+
+/// Records `url` as the `origin` remote of the current repository (used after clone)
+pub fn set_origin(url: &str, branch: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+    let mut config = load_repo_config()?;
+    let remote = Remote {
+        name: Some(DEFAULT_REMOTE.to_string()),
+        url: url.to_string(),
+        branch: branch.map(str::to_string),
+        token_env: None,
+        pins: Vec::new(),
+        net: NetOverrides::default(),
+    };
+    remote.store(&mut config)?;
+    config::save(&config)?;
+    Ok(())
+}
+
+fn load_repo_config() -> Result<Config, Box<dyn std::error::Error>> {
+    if !std::path::Path::new(".orb").exists() {
+        return Err("Not an Orbit repository (run 'orb init' first)".into());
+    }
+    Ok(config::load()?)
+}
+
+/// Remote names become config subsections, so keep them to simple identifiers
+fn validate_name(name: &str) -> Result<(), String> {
+    let valid = !name.is_empty()
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if valid {
+        Ok(())
+    } else {
+        Err(format!("Invalid remote name '{}' (use letters, digits, '-', '_' or '.')", name))
+    }
+}
@@ -0,0 +1,311 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::io;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use crate::audit;
+use crate::client_tls::OrbitUrl;
+use crate::hosted::{self, HostedRepo};
+use crate::secure_channel::Negotiated;
+use crate::server::Server;
+use crate::transport::{self, NetOptions, VnpReader, VnpWriter, WireCounters};
+use crate::vnp::{self, VnpCommand};
+use tracing::{info, warn};
+
+/// Environment variable holding the secret a leader authenticates to its followers with
+pub const TOKEN_ENV: &str = "ORB_REPLICATION_TOKEN";
+/// Account name a follower gives its leader's sessions, in logs and audit records
+pub const PRINCIPAL: &str = "replication";
+/// Delay before reconnecting to an unreachable follower; doubled up to `MAX_RETRY`
+const RETRY: Duration = Duration::from_secs(5);
+const MAX_RETRY: Duration = Duration::from_secs(300);
+
+/// Replication settings of an `orb serve` instance
+pub struct Options {
+    /// Servers that receive every push made to this one
+    pub followers: Vec<OrbitUrl>,
+    /// Refuse writes from everyone but the leader
+    pub replica: bool,
+    /// Shared secret: sent to followers, and accepted from the leader on a replica
+    pub token: String,
+}
+
+impl Options {
+    /// Settings from `orb serve --replicate-to/--replica`; None if neither is given
+    pub fn from_flags(followers: &[String], pins: &[String], replica: bool, token_env: &str) -> Result<Option<Self>, String> {
+        if followers.is_empty() && !replica {
+            return Ok(None);
+        }
+        let token = std::env::var(token_env)
+            .ok()
+            .filter(|token| !token.is_empty())
+            .ok_or_else(|| format!("replication needs a shared token in ${}", token_env))?;
+        for pin in pins {
+            crate::client_tls::parse_pin(pin)?;
+        }
+        let followers = followers
+            .iter()
+            .map(|url| {
+                let mut parsed = OrbitUrl::parse(url).map_err(|e| format!("{}: {}", url, e))?;
+                parsed.pins = pins.to_vec();
+                Ok(parsed)
+            })
+            .collect::<Result<_, String>>()?;
+        Ok(Some(Self { followers, replica, token }))
+    }
+}
+
+/// Work for a follower. Each one brings the follower up to date with the leader's
+/// current state, so retrying or repeating a job is harmless.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Job {
+    /// Copy a repository's branches and tags, with the objects they need
+    Sync(String),
+    Delete(String),
+    Rename { from: String, to: String },
+}
+
+/// The leader's side: queues jobs for every follower
+pub struct Replicator {
+    queues: Vec<mpsc::UnboundedSender<Job>>,
+}
+
+impl Replicator {
+    pub fn queue(&self, job: Job) {
+        for queue in &self.queues {
+            let _ = queue.send(job.clone());
+        }
+    }
+}
+
+/// One follower and the jobs queued for it
+pub struct Follower {
+    url: OrbitUrl,
+    /// `host:port`, for log lines and audit records
+    pub label: String,
+    jobs: mpsc::UnboundedReceiver<Job>,
+}
+
+/// A replicator for these followers, and the followers to `run` once the server exists
+pub fn channels(followers: Vec<OrbitUrl>) -> (Replicator, Vec<Follower>) {
+    let (queues, followers) = followers
+        .into_iter()
+        .map(|url| {
+            let (queue, jobs) = mpsc::unbounded_channel();
+            let label = format!("{}:{}", url.url_host(), url.port);
+            (queue, Follower { url, label, jobs })
+        })
+        .unzip();
+    (Replicator { queues }, followers)
+}
+
+impl Follower {
+    /// Brings the follower up to date with every repository, then replays the leader's
+    /// changes as they happen, until the server stops. Jobs wait in order while the
+    /// follower is unreachable; a job the follower refuses is logged and dropped.
+    pub async fn run(mut self, server: Arc<Server>, token: String) {
+        let mut pending: VecDeque<Job> = hosted::list_repositories(&server.root).into_iter().map(Job::Sync).collect();
+        let mut session = None;
+        let mut retry = RETRY;
+        loop {
+            if pending.is_empty() {
+                // Idle connections are closed; the next job opens a new one
+                session = None;
+                match self.jobs.recv().await {
+                    Some(job) => pending.push_back(job),
+                    None => return,
+                }
+            }
+            while let Ok(job) = self.jobs.try_recv() {
+                if !pending.contains(&job) {
+                    pending.push_back(job);
+                }
+            }
+
+            let result = match &mut session {
+                Some(connection) => run_job(connection, &server, &pending[0]).await,
+                None => match connect(&self.url, &token).await {
+                    Ok(connection) => {
+                        session = Some(connection);
+                        continue;
+                    }
+                    Err(e) => Err(e),
+                },
+            };
+            let job = pending[0].clone();
+            match result {
+                Ok(outcome) => {
+                    pending.pop_front();
+                    retry = RETRY;
+                    record(&server, &self.label, &job, outcome);
+                }
+                Err(e) => {
+                    warn!("⚠️  Replication to {} failed ({}); retrying in {}s", self.label, e, retry.as_secs());
+                    session = None;
+                    tokio::time::sleep(retry).await;
+                    retry = (retry * 2).min(MAX_RETRY);
+                }
+            }
+        }
+    }
+}
+
+/// Logs and audits a finished job. Syncs that found nothing to copy are not recorded.
+fn record(server: &Server, follower: &str, job: &Job, outcome: Result<String, String>) {
+    let (repository, kind) = match job {
+        Job::Sync(name) => (name, "replicate"),
+        Job::Delete(name) => (name, "replicate_delete"),
+        Job::Rename { to, .. } => (to, "replicate_rename"),
+    };
+    let (kind, message) = match outcome {
+        Ok(summary) if summary.is_empty() => return,
+        Ok(summary) => {
+            info!("🔁 Replicated {} to {}: {}", repository, follower, summary);
+            (kind, format!("{}: {}", follower, summary))
+        }
+        Err(message) => {
+            warn!("⚠️  {} refused replication of {}: {}", follower, repository, message);
+            ("replication_failed", format!("{} refused: {}", follower, message))
+        }
+    };
+    server.audit.record(audit::Event { repository: Some(repository.clone()), message: Some(message), ..audit::Event::new(kind) });
+}
+
+struct Connection {
+    reader: VnpReader,
+    writer: VnpWriter,
+}
+
+async fn connect(url: &OrbitUrl, token: &str) -> io::Result<Connection> {
+    // Plaintext is never allowed by the default options, so the token stays encrypted
+    let Negotiated::Secure(mut reader, mut writer) = transport::connect(url, &NetOptions::default(), &WireCounters::default())
+        .await
+        .map_err(|e| io::Error::other(e.to_string()))?
+    else {
+        return Err(io::Error::other("the follower declined the encrypted channel"));
+    };
+    vnp::send_command(&mut writer, VnpCommand::Authenticate(token.to_string())).await?;
+    match vnp::recv_command(&mut reader).await? {
+        VnpCommand::AuthResult { success: true, .. } => Ok(Connection { reader, writer }),
+        VnpCommand::AuthResult { message, .. } => Err(io::Error::other(format!("authentication failed: {}", message))),
+        other => Err(unexpected(other)),
+    }
+}
+
+/// Runs a job on the follower. Returns a summary of what changed (empty if nothing did),
+/// or the follower's reason for refusing it.
+async fn run_job(connection: &mut Connection, server: &Server, job: &Job) -> io::Result<Result<String, String>> {
+    match job {
+        Job::Sync(name) => {
+            let Some(repo) = hosted::repo_dir(&server.root, name).ok().and_then(|dir| HostedRepo::open(&dir)) else {
+                // Deleted or renamed since; that change has its own job
+                return Ok(Ok(String::new()));
+            };
+            sync(connection, name, &repo).await
+        }
+        Job::Delete(name) => {
+            let reply = connection.request(VnpCommand::DeleteRepository(name.clone())).await?;
+            Ok(reply.map(|()| "deleted".to_string()))
+        }
+        Job::Rename { from, to } => {
+            let reply = connection.request(VnpCommand::RenameRepository { from: from.clone(), to: to.clone() }).await?;
+            Ok(reply.map(|()| format!("renamed from {}", from)))
+        }
+    }
+}
+
+/// Copies the branches and tags the follower does not have (or has at other commits),
+/// creating the repository there if needed. The follower asks only for the objects it
+/// lacks. Refs are forced to the leader's commits; refs deleted on the leader stay.
+async fn sync(connection: &mut Connection, name: &str, repo: &HostedRepo) -> io::Result<Result<String, String>> {
+    let Connection { reader, writer } = connection;
+    vnp::send_command(writer, VnpCommand::SelectRepository(name.to_string())).await?;
+    if let VnpCommand::Error(_) = vnp::recv_command(reader).await? {
+        vnp::send_command(writer, VnpCommand::CreateRepository(name.to_string())).await?;
+        match vnp::recv_command(reader).await? {
+            VnpCommand::RepositorySelected(_) => {}
+            VnpCommand::Error(message) => return Ok(Err(message)),
+            other => return Err(unexpected(other)),
+        }
+    }
+    vnp::send_command(writer, VnpCommand::ListRefs).await?;
+    let theirs = match vnp::recv_command(reader).await? {
+        VnpCommand::RefList(refs) => refs,
+        VnpCommand::Error(message) => return Ok(Err(message)),
+        other => return Err(unexpected(other)),
+    };
+    let differs = |ours: BTreeMap<String, String>, theirs: &BTreeMap<String, String>| -> BTreeMap<String, String> {
+        ours.into_iter().filter(|(name, id)| theirs.get(name) != Some(id)).collect()
+    };
+    let branches = differs(repo.branches(), &theirs.branches);
+    let tags = differs(repo.tags(), &theirs.tags);
+    let annotations = differs(repo.annotations(), &theirs.annotations);
+    if branches.is_empty() && tags.is_empty() && annotations.is_empty() {
+        return Ok(Ok(String::new()));
+    }
+
+    let mut tips: Vec<String> = branches.values().chain(tags.values()).chain(annotations.values()).cloned().collect();
+    tips.sort();
+    tips.dedup();
+    vnp::send_command(writer, VnpCommand::Push(tips)).await?;
+    let mut sent = 0;
+    loop {
+        match vnp::recv_command(reader).await? {
+            VnpCommand::SendObject(id) if hosted::is_object_id(&id) => {
+                repo.send_object(writer, &id).await?;
+                sent += 1;
+            }
+            VnpCommand::Ok => break,
+            VnpCommand::Error(message) => return Ok(Err(message)),
+            other => return Err(unexpected(other)),
+        }
+    }
+    for (branch, new) in &branches {
+        let old = theirs.branches.get(branch).cloned();
+        let update = VnpCommand::UpdateRef { branch: branch.clone(), old, new: new.clone(), force: true };
+        if let Err(message) = connection.request(update).await? {
+            return Ok(Err(message));
+        }
+    }
+    if !tags.is_empty() || !annotations.is_empty() {
+        let update = VnpCommand::PushTags { tags: tags.clone(), force: true, annotations };
+        if let Err(message) = connection.request(update).await? {
+            return Ok(Err(message));
+        }
+    }
+    Ok(Ok(format!("{} refs, {} objects", branches.len() + tags.len(), sent)))
+}
+
+impl Connection {
+    /// Sends a command answered with Ok or Error
+    async fn request(&mut self, command: VnpCommand) -> io::Result<Result<(), String>> {
+        vnp::send_command(&mut self.writer, command).await?;
+        match vnp::recv_command(&mut self.reader).await? {
+            VnpCommand::Ok => Ok(Ok(())),
+            VnpCommand::Error(message) => Ok(Err(message)),
+            other => Err(unexpected(other)),
+        }
+    }
+}
+
+fn unexpected(reply: VnpCommand) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("unexpected reply {:?}", reply))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_jobs_reach_every_follower() {
+        let urls = ["orbit://a.example:8080", "orbit://b.example:8080"].map(|url| OrbitUrl::parse(url).unwrap());
+        let (replicator, mut followers) = channels(urls.into_iter().collect());
+        replicator.queue(Job::Sync("alice@example.com/web".to_string()));
+        replicator.queue(Job::Delete("alice@example.com/old".to_string()));
+        for follower in &mut followers {
+            assert_eq!(follower.jobs.try_recv().unwrap(), Job::Sync("alice@example.com/web".to_string()));
+            assert_eq!(follower.jobs.try_recv().unwrap(), Job::Delete("alice@example.com/old".to_string()));
+            assert!(follower.jobs.try_recv().is_err());
+        }
+    }
+}
@@ -1,178 +1,336 @@
 use std::fs;
-use std::path::Path;
-use std::io::Write;
-use std::time::{SystemTime, UNIX_EPOCH};
-use crate::vos;
-use crate::objects::{self, ObjectId};
-use crate::index::VosIndex;
-// use rayon::prelude::*; // TODO: Enable for parallel processing in future versions
+use std::io;
+use std::path::{Path, PathBuf};
+use orbit_core::config::Config;
+use orbit_core::{Error, FileMap, FileStatus, Repository};
+use crate::objects::ObjectId;
+use crate::progress::Progress;
+use crate::{ancestry, refs, style};
 
-const ORB_DIR: &str = ".orb";
+pub use orbit_core::{file_mode, ORB_DIR};
+use tracing::{info, warn};
 
-pub fn init() -> Result<(), std::io::Error> {
-    let root = Path::new(ORB_DIR);
+/// Author recorded on new commits
+pub const DEFAULT_AUTHOR: &str = "Orb Developer <dev@orbit.vcs>"; // TODO: Replace with user config
 
-    if root.exists() {
-        // We'll return an error or a message if the repository already exists
-        return Err(std::io::Error::new(
-            std::io::ErrorKind::AlreadyExists,
-            "Orbit repository already initialized.",
-        ));
+/// Why a command could not work with the repository or a revision in it
+#[derive(Debug, thiserror::Error)]
+pub enum RepoError {
+    #[error("HEAD has no commits yet")]
+    NoCommits,
+    #[error("unknown revision '{0}'")]
+    UnknownRevision(String),
+    #[error("commit ID '{0}' is ambiguous")]
+    AmbiguousRevision(String),
+    #[error("invalid revision '{0}'")]
+    InvalidRevision(String),
+    #[error("invalid revision '{0}' (only first parents can be followed)")]
+    NotFirstParent(String),
+    #[error("revision '{0}' goes past a root commit")]
+    PastRoot(String),
+    #[error("invalid branch name '{0}' (use letters, digits, '-', '_', '.', '+' or '@')")]
+    InvalidBranchName(String),
+    #[error("a branch named '{0}' already exists")]
+    BranchExists(String),
+    #[error("no branch named '{0}'")]
+    NoSuchBranch(String),
+    #[error("'{path}' is not a file in {revision}")]
+    NoSuchPath { path: String, revision: String },
+    #[error("'{0}' is a binary file")]
+    BinaryFile(String),
+    #[error("merge stopped with {0} conflicted path(s)")]
+    MergeConflicts(usize),
+    #[error("{0} uncommitted change(s) would be overwritten; save them or use --force")]
+    UncommittedChanges(usize),
+    #[error("invalid tag name '{0}' (use letters, digits, '-', '_', '.', '+' or '@')")]
+    InvalidTagName(String),
+    #[error("a tag named '{0}' already exists; use --force to move it")]
+    TagExists(String),
+    #[error("no tag named '{0}'")]
+    NoSuchTag(String),
+    #[error("no stash entry stash@{{{0}}}")]
+    NoSuchStash(usize),
+    #[error("{0} stashed path(s) were also changed by later commits; the stash was kept")]
+    StashConflicts(usize),
+    #[error("{0} uncommitted change(s) would be overwritten; save or stash them first")]
+    LocalChanges(usize),
+    #[error("a bisect is in progress; run `orb bisect reset` to end it first")]
+    BisectInProgress,
+    #[error("no bisect is in progress; start one with `orb bisect start`")]
+    NotBisecting,
+    #[error("mark a bad and a good commit before `orb bisect run`")]
+    BisectUnbounded,
+    #[error("the bad commit {0} is an ancestor of a good commit")]
+    BadIsGood(ObjectId),
+    #[error("a cherry-pick is in progress; finish it with `orb resolve` and `orb cherry-pick --continue`, or run `orb cherry-pick --abort`")]
+    CherryPickInProgress,
+    #[error("no cherry-pick is in progress")]
+    NotPicking,
+    #[error("cherry-pick stopped with {0} conflicted path(s)")]
+    PickConflicts(usize),
+    #[error("{0} is a merge commit; only commits with one parent can be picked or reverted")]
+    PickMerge(ObjectId),
+    #[error("a revert is in progress; finish it with `orb resolve` and `orb revert --continue`, or run `orb revert --abort`")]
+    RevertInProgress,
+    #[error("no revert is in progress")]
+    NotReverting,
+    #[error("revert stopped with {0} conflicted path(s)")]
+    RevertConflicts(usize),
+    #[error("a rebase is in progress; finish it with `orb resolve` and `orb rebase --continue`, or run `orb rebase --abort`")]
+    RebaseInProgress,
+    #[error("no rebase is in progress")]
+    NotRebasing,
+    #[error("rebase stopped with {0} conflicted path(s)")]
+    RebaseConflicts(usize),
+    #[error("cannot delete '{0}', the current branch; switch to another branch first")]
+    CurrentBranch(String),
+    #[error("branch '{branch}' is not merged into '{into}'; use --force to delete it anyway")]
+    NotMerged { branch: String, into: String },
+    #[error(transparent)]
+    Core(#[from] Error),
+}
+
+/// The repository in the current directory
+pub fn open() -> Result<Repository, Error> {
+    Repository::open(".")
+}
+
+/// The repository in the current directory, which must have a working tree
+pub fn open_worktree() -> Result<Repository, Error> {
+    let repository = open()?;
+    match repository.is_bare() {
+        true => Err(Error::BareRepository(repository.root().to_path_buf())),
+        false => Ok(repository),
     }
+}
 
-    // 1. Create the .orb directory
-    fs::create_dir(root)?;
-    
-    // 2. Create subdirectories
-    fs::create_dir(root.join("objects"))?;
-    fs::create_dir(root.join("refs"))?;
-    
-    // 3. Initialize config file (using a simple text format for MVP)
-    let mut config_file = fs::File::create(root.join("config"))?;
-    config_file.write_all(b"[core]\n")?;
-    config_file.write_all(b"version = 0.1\n")?;
-    config_file.write_all(b"hash_algorithm = sha3-256\n")?;
-    
-    // 4. Set the initial HEAD reference (default branch)
-    let mut head_file = fs::File::create(root.join("HEAD"))?;
-    head_file.write_all(b"ref: refs/heads/main\n")?;
+/// The mode and File object ID of a working-tree file, which is stored in the object store
+pub fn store_file(repository: &Repository, path: &str) -> Result<(u32, ObjectId), Error> {
+    let full_path = repository.root().join(path);
+    let metadata = fs::symlink_metadata(&full_path)?;
+    Ok(match metadata.file_type().is_symlink() {
+        true => (0o120000, repository.store().save_symlink(&full_path)?),
+        false => (file_mode(&metadata), repository.store().save_file(&full_path)?.0),
+    })
+}
 
-    println!("✅ Initialized empty Orbit repository in {}", root.display());
+/// Fails, listing them, if replacing the working tree with `target` would lose changes:
+/// modified or deleted tracked files, or untracked files at paths `target` has
+pub fn ensure_no_overwrite(repository: &Repository, target: &FileMap) -> Result<(), RepoError> {
+    let at_risk: Vec<_> = repository
+        .status()?
+        .into_iter()
+        .filter(|change| change.status != FileStatus::Untracked || target.contains_key(&change.path))
+        .collect();
+    for change in &at_risk {
+        warn!("  {}: {}", format!("{:?}", change.status).to_lowercase(), change.path);
+    }
+    match at_risk.is_empty() {
+        true => Ok(()),
+        false => Err(RepoError::UncommittedChanges(at_risk.len())),
+    }
+}
+
+/// Fails, listing them, if the working tree has changes to tracked files that checking
+/// out another commit would lose
+pub fn ensure_clean(repository: &Repository) -> Result<(), RepoError> {
+    let changed: Vec<_> = repository.status()?.into_iter().filter(|change| change.status != FileStatus::Untracked).collect();
+    for change in &changed {
+        warn!("  {}: {}", format!("{:?}", change.status).to_lowercase(), change.path);
+    }
+    match changed.is_empty() {
+        true => Ok(()),
+        false => Err(RepoError::LocalChanges(changed.len())),
+    }
+}
+
+/// Fails if bringing remote commits into `branch` could lose work: for the current
+/// branch, whose working tree then moves to the new tip, while the tree has changes to
+/// tracked files or an operation is in progress
+pub fn ensure_can_update(repository: &Repository, branch: &str) -> Result<(), RepoError> {
+    if repository.is_bare() || branch != repository.current_branch() {
+        return Ok(());
+    }
+    ensure_idle(repository)?;
+    ensure_clean(repository)
+}
+
+/// Points `branch` at `tip` after a pull or sync. The current branch also gets `tip`'s
+/// files in the working tree and index, so the next `orb save` builds on them instead
+/// of reverting them.
+pub fn move_branch(repository: &Repository, branch: &str, tip: &str) -> Result<(), Error> {
+    repository.write_ref(&refs::branch_ref(branch), tip)?;
+    if !repository.is_bare() && branch == repository.current_branch() {
+        repository.replace_worktree(&repository.load_commit(tip)?.tree)?;
+    }
     Ok(())
 }
-/// Recursively traverses the directory, chunks files, saves VOS objects, and builds the Directory (Tree).
-/// Also updates the VOS Index with file metadata for fast status checks.
-fn traverse_and_save_tree(path: &Path, current_path: &str, index: &mut VosIndex) -> Result<ObjectId, std::io::Error> {
-    let mut entries = Vec::new();
-    let iter = fs::read_dir(path)?;
 
-    for entry in iter {
-        let entry = entry?;
-        let entry_path = entry.path();
-        let file_name = entry_path.file_name().unwrap().to_string_lossy().to_string();
+/// Fails while a merge, bisect, cherry-pick, or rebase is in progress, as commands that
+/// replace the working tree or move HEAD would lose its state
+pub fn ensure_idle(repository: &Repository) -> Result<(), RepoError> {
+    if repository.merge_head().is_some() {
+        return Err(Error::MergeInProgress.into());
+    }
+    crate::bisect::ensure_not_bisecting(repository)?;
+    crate::cherry_pick::ensure_not_picking(repository)?;
+    crate::rebase::ensure_not_rebasing(repository)
+}
 
-        // Skip internal .orb directory
-        if file_name == ORB_DIR {
-            continue;
+/// The files of the working tree: `base` (the files the index was last built from),
+/// with tracked changes and staged files applied
+pub fn worktree_files(repository: &Repository, mut files: FileMap) -> Result<FileMap, Error> {
+    if repository.is_bare() {
+        return Err(Error::BareRepository(repository.root().to_path_buf()));
+    }
+    let staged = repository.index()?.staged;
+    for change in repository.status()? {
+        match change.status {
+            FileStatus::Deleted => {
+                files.remove(&change.path);
+            }
+            FileStatus::Untracked if !staged.contains_key(&change.path) => {}
+            _ => {
+                files.insert(change.path.clone(), store_file(repository, &change.path)?);
+            }
         }
+    }
+    Ok(files)
+}
 
-        let full_path = if current_path.is_empty() {
-            file_name.clone()
-        } else {
-            format!("{}/{}", current_path, file_name)
-        };
-
-        let metadata = fs::metadata(&entry_path)?;
-
-        let (mode, id) = if metadata.is_dir() {
-            // Recursive call for subdirectories
-            let dir_id = traverse_and_save_tree(&entry_path, &full_path, index)?;
-            (0o040000, dir_id) // Directory mode
-        } else if metadata.is_file() {
-            // Process file using Content-Defined Chunking and PQC hashing
-            let (file_id, _file_size) = vos::chunk_and_save_file(&entry_path)?;
-            
-            // Update VOS Index with file metadata
-            let (mtime, size) = VosIndex::get_file_metadata(&entry_path).unwrap_or((0, 0));
-            index.update_entry(full_path.clone(), mtime, size, file_id.clone());
-            
-            (0o100644, file_id) // Regular file mode
-        } else {
-            // Skip other types (symlinks, etc., for MVP)
-            continue;
-        };
-
-        entries.push(objects::DirectoryEntry {
-            mode,
-            name: file_name,
-            id,
-        });
-    }
-
-    // 1. Create and hash the Directory object
-    let directory_obj = objects::Directory { entries };
-    let dir_id = vos::hash_object(&directory_obj).unwrap();
-    
-    // 2. Save the Directory object metadata
-    vos::save_object(&serde_json::to_vec(&directory_obj).unwrap());
+/// Environment variable naming the template directory `orb init` uses without --template
+pub const TEMPLATE_ENV: &str = "ORB_TEMPLATE_DIR";
 
-    Ok(dir_id)
+/// Creates a repository in the current directory
+pub fn init() -> Result<(), RepoError> {
+    init_at(Path::new("."), false, None)
 }
 
-/// Orchestrates the entire 'orb save' process.
-pub fn save_snapshot(message: &str) -> Result<(), std::io::Error> {
-    // 1. Get current HEAD (parent commit)
-    let parent_id = get_head_commit_id()?;
+/// Creates a repository in `path` (created if missing), bare if `bare`, and applies the
+/// template directory (`template`, else $ORB_TEMPLATE_DIR): the settings of its `config`
+/// are added to the new config, its `orbignore` becomes the working tree's `.orbignore`,
+/// and everything else (such as `hooks/`) is copied into `.orb`
+pub fn init_at(path: &Path, bare: bool, template: Option<&Path>) -> Result<(), RepoError> {
+    let template = template
+        .map(Path::to_path_buf)
+        .or_else(|| std::env::var_os(TEMPLATE_ENV).filter(|dir| !dir.is_empty()).map(PathBuf::from));
+    if let Some(template) = template.as_deref().filter(|template| !template.is_dir()) {
+        let message = format!("template directory {} does not exist", template.display());
+        return Err(Error::Io(io::Error::new(io::ErrorKind::NotFound, message)).into());
+    }
 
-    // 2. Initialize or load the VOS Index
-    let mut index = VosIndex::load().unwrap_or_else(|_| VosIndex::new());
-    
-    // Clear the index for fresh rebuild (ensures accuracy)
-    index.clear();
-
-    // 3. Build the new root Directory (Tree) and update VOS Index
-    let root_dir_id = traverse_and_save_tree(Path::new("."), "", &mut index)?;
-
-    // 4. Save the updated VOS Index
-    if let Err(e) = index.save() {
-        eprintln!("Warning: Could not save VOS Index: {}", e);
-    }
-
-    // 5. Create the Commit object
-    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() as i64;
-    let commit_obj = objects::Commit {
-        tree: root_dir_id,
-        parents: if parent_id.is_empty() { vec![] } else { vec![parent_id.clone()] },
-        author: "Orb Developer <dev@orbit.vcs>".to_string(), // TODO: Replace with user config
-        timestamp: now,
-        message: message.to_string(),
-        signature: None, 
+    let repository = match bare {
+        true => Repository::init_bare(path)?,
+        false => Repository::init(path)?,
+    };
+    if let Some(template) = &template {
+        apply_template(&repository, template).map_err(Error::from)?;
+    }
+    let location = match path == Path::new(".") {
+        true => PathBuf::from(ORB_DIR),
+        false => path.join(ORB_DIR),
     };
+    info!("✅ Initialized empty {}Orbit repository in {}", if bare { "bare " } else { "" }, location.display());
+    Ok(())
+}
 
-    // 6. Hash and save the Commit object
-    let commit_id = vos::hash_object(&commit_obj).unwrap();
-    vos::save_object(&serde_json::to_vec(&commit_obj).unwrap());
+fn apply_template(repository: &Repository, template: &Path) -> Result<(), io::Error> {
+    for entry in fs::read_dir(template)? {
+        let entry = entry?;
+        match entry.file_name().to_str() {
+            Some("config") => {
+                let path = repository.orb_dir().join("config");
+                let mut config = Config::load(&path)?;
+                config.merge(&Config::load(&entry.path())?);
+                config.save(&path)?;
+            }
+            Some("orbignore") if repository.is_bare() => {}
+            Some("orbignore") => copy_missing(&entry.path(), &repository.root().join(".orbignore"))?,
+            _ => copy_missing(&entry.path(), &repository.orb_dir().join(entry.file_name()))?,
+        }
+    }
+    Ok(())
+}
 
-    // 7. Update the main branch reference (HEAD)
-    update_head(&commit_id)?;
+/// Copies a file or directory tree, keeping files that already exist (and permissions,
+/// so hooks stay executable)
+fn copy_missing(from: &Path, to: &Path) -> Result<(), io::Error> {
+    if from.is_dir() {
+        fs::create_dir_all(to)?;
+        for entry in fs::read_dir(from)? {
+            let entry = entry?;
+            copy_missing(&entry.path(), &to.join(entry.file_name()))?;
+        }
+    } else if !to.exists() {
+        fs::copy(from, to)?;
+    }
+    Ok(())
+}
 
-    println!("✨ Saved commit {} to main: {}", &commit_id[0..7], message);
+/// Orchestrates the entire 'orb save' process. With `paths`, they are staged first,
+/// so only staged changes are committed. With `amend`, the commit replaces HEAD
+/// (keeping its message when `message` is None).
+pub fn save_snapshot(message: Option<&str>, paths: &[String], amend: bool) -> Result<(), RepoError> {
+    let repository = open()?;
+    crate::bisect::ensure_not_bisecting(&repository)?;
+    crate::cherry_pick::ensure_not_picking(&repository)?;
+    crate::rebase::ensure_not_rebasing(&repository)?;
+    if !paths.is_empty() {
+        repository.add(&paths.iter().map(String::as_str).collect::<Vec<_>>())?;
+    }
+    let staged = repository.index()?.staged.len();
+    if staged > 0 && repository.merge_head().is_none() {
+        info!("📦 Saving {} staged path(s); other changes stay in the working tree", staged);
+    }
+    let progress = Progress::new();
+    progress.begin_counting("Hashing", None, "files");
+    let mut advance = |_: &str, size| progress.advance(size);
+    let (commit_id, verb) = match (amend, message) {
+        (true, _) => (repository.amend_with_progress(message, &mut advance)?, "Amended"),
+        (false, Some(message)) => (repository.save_with_progress(message, DEFAULT_AUTHOR, &mut advance)?, "Saved"),
+        (false, None) => unreachable!("clap requires a message unless --amend is given"),
+    };
+    progress.finish();
+    let message = repository.load_commit(&commit_id)?.message;
+    info!("✨ {} commit {} to {}: {}", verb, style::id(&commit_id[0..7]), repository.current_branch(), message.lines().next().unwrap_or_default());
     Ok(())
 }
 
-// --- Helper Functions (Stubs for MVP) ---
+/// `orb add`: stages the current version of matching files for the next `orb save`
+pub fn stage(paths: &[String]) -> Result<(), Error> {
+    let staged = open()?.add(&paths.iter().map(String::as_str).collect::<Vec<_>>())?;
+    for path in &staged {
+        info!("➕ Staged {}", path);
+    }
+    if staged.is_empty() {
+        info!("✅ Nothing new to stage");
+    }
+    Ok(())
+}
 
-/// Reads the current commit ID pointed to by HEAD. (Placeholder for now)
-fn get_head_commit_id() -> Result<ObjectId, std::io::Error> {
-    // In v0.1, we assume no parent for the first commit, or read the last commit's hash.
-    // For a simple MVP, let's return an empty string, signifying a root commit.
-    // In a future version, this reads the hash from .orb/refs/heads/main
-    Ok("".to_string())
+/// `orb unstage`: drops staged changes of matching paths; the files are left alone
+pub fn unstage(paths: &[String]) -> Result<(), Error> {
+    let unstaged = open()?.unstage(&paths.iter().map(String::as_str).collect::<Vec<_>>())?;
+    for path in &unstaged {
+        info!("➖ Unstaged {}", path);
+    }
+    if unstaged.is_empty() {
+        info!("✅ No staged changes matched");
+    }
+    Ok(())
 }
 
-/// Updates the main branch ref to point to the new commit ID.
+/// Updates the current branch ref to point to the new commit ID.
 fn update_head(commit_id: &ObjectId) -> Result<(), std::io::Error> {
-    // In v0.1, we'll write the commit ID directly to the main ref file
-    let ref_path = Path::new(ORB_DIR).join("refs").join("heads").join("main");
-    fs::create_dir_all(ref_path.parent().unwrap())?; // Ensure refs/heads exists
-    let mut ref_file = fs::File::create(ref_path)?;
-    ref_file.write_all(commit_id.as_bytes())?;
-    Ok(())
+    refs::write_ref(&refs::branch_ref(&refs::current_branch()), commit_id)
 }
 
 /// Gets local commit IDs for synchronization with remote repositories
 pub fn get_local_commits() -> Result<Vec<ObjectId>, std::io::Error> {
     let mut commits = Vec::new();
     
-    // Read the HEAD commit (main branch)
-    let head_path = Path::new(ORB_DIR).join("refs").join("heads").join("main");
-    if head_path.exists() {
-        let head_content = fs::read_to_string(head_path)?;
-        let head_commit = head_content.trim().to_string();
-        if !head_commit.is_empty() {
-            commits.push(head_commit);
-        }
-    }
+    // The tip of the current branch
+    commits.extend(refs::head_commit());
     
     // TODO: In future versions, traverse the commit DAG to get all commits
     // For v0.3.3 MVP, we'll just return the HEAD commit
@@ -180,13 +338,139 @@ pub fn get_local_commits() -> Result<Vec<ObjectId>, std::io::Error> {
     Ok(commits)
 }
 
-/// Updates HEAD to point to the latest synchronized commit
-pub fn update_head_after_sync(commit_ids: &[ObjectId]) -> Result<(), std::io::Error> {
-    if !commit_ids.is_empty() {
-        // For now, use the last commit as HEAD (in future versions, we'll determine the proper HEAD)
-        let latest_commit = &commit_ids[commit_ids.len() - 1];
-        update_head(latest_commit)?;
-        println!("📍 Updated HEAD to: {}", latest_commit);
+/// What happened to the current branch after a sync
+#[derive(Debug, PartialEq)]
+pub enum HeadUpdate {
+    /// The branch was moved (or created) to this remote tip
+    Moved(ObjectId),
+    /// Nothing to do: no tip could be determined, or local already contains it
+    Unchanged,
+    /// Local and remote both have commits the other lacks; the branch was not moved
+    Diverged { local: ObjectId, remote: ObjectId },
+}
+
+/// Moves the current branch to the tip of the synchronized history.
+///
+/// The server's advertised tip is used when available. Otherwise the tip is the
+/// one received commit that no other received commit descends from; if the
+/// received commits form several lines of history, HEAD is left untouched.
+/// The branch only ever fast-forwards, so local-only commits are never lost.
+pub fn update_head_after_sync(received: &[ObjectId], advertised_tip: Option<&str>) -> Result<HeadUpdate, std::io::Error> {
+    if received.is_empty() {
+        return Ok(HeadUpdate::Unchanged);
     }
-    Ok(())
-}
\ No newline at end of file
+    
+    let tip = match advertised_tip {
+        Some(tip) if ancestry::load_commit(tip).is_some() => tip.to_string(),
+        _ => {
+            let tips = ancestry::tips(received);
+            if tips.len() != 1 {
+                warn!("⚠️  Received {} separate lines of history; HEAD left unchanged", tips.len());
+                for tip in &tips {
+                    info!("   {}", tip);
+                }
+                return Ok(HeadUpdate::Unchanged);
+            }
+            tips[0].clone()
+        }
+    };
+    
+    if let Some(local) = refs::head_commit() {
+        if ancestry::is_ancestor(&tip, &local) {
+            return Ok(HeadUpdate::Unchanged); // Local is ahead; the upload phase publishes it
+        }
+        if !ancestry::is_ancestor(&local, &tip) {
+            return Ok(HeadUpdate::Diverged { local, remote: tip });
+        }
+    }
+    
+    update_head(&tip)?;
+    info!("📍 Updated HEAD to: {}", tip);
+    Ok(HeadUpdate::Moved(tip))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_init_applies_template() {
+        let dir = std::env::temp_dir().join(format!("orb-init-test-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let template = dir.join("template");
+        fs::create_dir_all(template.join("hooks")).unwrap();
+        fs::write(template.join("config"), "[pull]\nstrategy = rebase\n").unwrap();
+        fs::write(template.join("hooks").join("post-sync"), "#!/bin/sh\n").unwrap();
+        fs::write(template.join("orbignore"), "*.log\n").unwrap();
+
+        init_at(&dir.join("work"), false, Some(&template)).unwrap();
+        let work = Repository::open(dir.join("work")).unwrap();
+        assert_eq!(work.config().unwrap().get("pull.strategy"), Some("rebase"));
+        assert_eq!(work.config().unwrap().get("core.version"), Some("0.1"));
+        assert!(work.orb_dir().join("hooks").join("post-sync").is_file());
+        assert_eq!(fs::read_to_string(dir.join("work").join(".orbignore")).unwrap(), "*.log\n");
+        assert!(!work.is_bare());
+
+        init_at(&dir.join("bare"), true, Some(&template)).unwrap();
+        let bare = Repository::open(dir.join("bare")).unwrap();
+        assert!(bare.is_bare());
+        assert!(!dir.join("bare").join(".orbignore").exists());
+        assert!(matches!(bare.save("m", DEFAULT_AUTHOR), Err(Error::BareRepository(_))));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_pulled_files_survive_the_next_save() {
+        let dir = std::env::temp_dir().join(format!("orb-pull-test-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let repository = Repository::init(&dir).unwrap();
+        let branch = repository.current_branch();
+        fs::write(dir.join("a.txt"), "a\n").unwrap();
+        let base = repository.save("Base", DEFAULT_AUTHOR).unwrap();
+        fs::write(dir.join("local.txt"), "local\n").unwrap();
+        let local = repository.save("Local", DEFAULT_AUTHOR).unwrap();
+        // The remote's change rebased onto the local commit, as `orb pull --rebase` makes it
+        fs::write(dir.join("remote.txt"), "remote\n").unwrap();
+        let rebased = repository.save("Remote", DEFAULT_AUTHOR).unwrap();
+        repository.write_ref(&refs::branch_ref(&branch), &local).unwrap();
+        repository.replace_worktree(&repository.load_commit(&local).unwrap().tree).unwrap();
+        assert_ne!(base, local);
+
+        fs::write(dir.join("local.txt"), "edited\n").unwrap();
+        assert!(matches!(ensure_can_update(&repository, &branch), Err(RepoError::LocalChanges(1))));
+        assert!(ensure_can_update(&repository, "other").is_ok());
+        fs::write(dir.join("local.txt"), "local\n").unwrap();
+        ensure_can_update(&repository, &branch).unwrap();
+
+        move_branch(&repository, &branch, &rebased).unwrap();
+        assert!(repository.status().unwrap().is_empty());
+        fs::write(dir.join("next.txt"), "next\n").unwrap();
+        let next = repository.save("Next", DEFAULT_AUTHOR).unwrap();
+        let files = repository.commit_files(&next).unwrap();
+        assert!(["a.txt", "local.txt", "remote.txt", "next.txt"].iter().all(|path| files.contains_key(*path)));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_fast_forward_checks_out_the_new_tip() {
+        let dir = std::env::temp_dir().join(format!("orb-fast-forward-test-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let repository = Repository::init(&dir).unwrap();
+        let branch = repository.current_branch();
+        fs::write(dir.join("old.txt"), "old\n").unwrap();
+        let behind = repository.save("Behind", DEFAULT_AUTHOR).unwrap();
+        // The remote deleted one file and added another
+        fs::remove_file(dir.join("old.txt")).unwrap();
+        fs::write(dir.join("new.txt"), "new\n").unwrap();
+        let ahead = repository.save("Ahead", DEFAULT_AUTHOR).unwrap();
+        repository.write_ref(&refs::branch_ref(&branch), &behind).unwrap();
+        repository.replace_worktree(&repository.load_commit(&behind).unwrap().tree).unwrap();
+
+        move_branch(&repository, &branch, &ahead).unwrap();
+        assert!(dir.join("new.txt").exists() && !dir.join("old.txt").exists());
+        assert!(repository.status().unwrap().is_empty());
+        let next = repository.save("Next", DEFAULT_AUTHOR).unwrap();
+        assert_eq!(repository.commit_files(&next).unwrap().keys().collect::<Vec<_>>(), ["new.txt"]);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,251 @@
+use std::future::Future;
+use std::io;
+use std::time::SystemTime;
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use ring::{digest, hmac};
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+use crate::object_store::{ObjectStore, StoredObject};
+
+/// Everything but RFC 3986 unreserved characters, as SigV4 canonical requests encode them
+const UNRESERVED: &AsciiSet = &NON_ALPHANUMERIC.remove(b'-').remove(b'.').remove(b'_').remove(b'~');
+
+/// Objects in an S3 (or S3-compatible) bucket, one key per object under the repository's
+/// prefix: `s3://<bucket>[/<prefix>][?region=<region>&endpoint=<url>]`. Requests use
+/// path-style addressing and are signed with the `AWS_ACCESS_KEY_ID`,
+/// `AWS_SECRET_ACCESS_KEY`, and (optionally) `AWS_SESSION_TOKEN` of the server.
+#[derive(Debug)]
+pub struct S3Store {
+    client: reqwest::Client,
+    endpoint: String,
+    bucket: String,
+    /// Key prefix of the repository's objects, ending in `/`
+    prefix: String,
+    region: String,
+    access_key: String,
+    secret_key: String,
+    session_token: Option<String>,
+}
+
+impl S3Store {
+    pub fn open(url: &str, location: &str) -> Result<Self, String> {
+        let parsed = url::Url::parse(url).map_err(|e| format!("invalid storage URL '{}': {}", url, e))?;
+        let bucket = parsed.host_str().filter(|bucket| !bucket.is_empty()).ok_or_else(|| format!("{} names no bucket", url))?;
+        let query = |key: &str| parsed.query_pairs().find(|(k, _)| k == key).map(|(_, v)| v.to_string());
+        let region = query("region")
+            .or_else(|| std::env::var("AWS_REGION").ok())
+            .or_else(|| std::env::var("AWS_DEFAULT_REGION").ok())
+            .unwrap_or_else(|| "us-east-1".to_string());
+        let endpoint = query("endpoint").unwrap_or_else(|| format!("https://s3.{}.amazonaws.com", region));
+        let env = |name: &str| std::env::var(name).ok().filter(|value| !value.is_empty());
+        let (Some(access_key), Some(secret_key)) = (env("AWS_ACCESS_KEY_ID"), env("AWS_SECRET_ACCESS_KEY")) else {
+            return Err(format!("{} needs AWS_ACCESS_KEY_ID and AWS_SECRET_ACCESS_KEY", url));
+        };
+        let prefix = [parsed.path().trim_matches('/'), location]
+            .iter()
+            .filter(|part| !part.is_empty())
+            .map(|part| format!("{}/", part))
+            .collect();
+        Ok(S3Store {
+            client: reqwest::Client::new(),
+            endpoint: endpoint.trim_end_matches('/').to_string(),
+            bucket: bucket.to_string(),
+            prefix,
+            region,
+            access_key,
+            secret_key,
+            session_token: env("AWS_SESSION_TOKEN"),
+        })
+    }
+
+    /// Sends a signed request for `key` (None for the bucket itself). The server's runtime
+    /// is borrowed, since stores are called from sync code.
+    fn request(&self, method: reqwest::Method, key: Option<&str>, query: &[(&str, &str)], body: Vec<u8>) -> io::Result<Response> {
+        let mut path = format!("/{}", encode(&self.bucket));
+        if let Some(key) = key {
+            path.push('/');
+            path.push_str(&key.split('/').map(encode).collect::<Vec<_>>().join("/"));
+        }
+        let mut query: Vec<(String, String)> = query.iter().map(|(k, v)| (encode(k), encode(v))).collect();
+        query.sort();
+        let query = query.iter().map(|(k, v)| format!("{}={}", k, v)).collect::<Vec<_>>().join("&");
+        let host = self.endpoint.split_once("://").map_or(self.endpoint.as_str(), |(_, host)| host);
+
+        let now = OffsetDateTime::now_utc();
+        let date = format!("{:04}{:02}{:02}", now.year(), u8::from(now.month()), now.day());
+        let timestamp = format!("{}T{:02}{:02}{:02}Z", date, now.hour(), now.minute(), now.second());
+        let payload_hash = hex(digest::digest(&digest::SHA256, &body).as_ref());
+        let mut headers = vec![
+            ("host", host.to_string()),
+            ("x-amz-content-sha256", payload_hash.clone()),
+            ("x-amz-date", timestamp.clone()),
+        ];
+        if let Some(token) = &self.session_token {
+            headers.push(("x-amz-security-token", token.clone()));
+        }
+        let signed_headers = headers.iter().map(|(name, _)| *name).collect::<Vec<_>>().join(";");
+        let canonical_headers: String = headers.iter().map(|(name, value)| format!("{}:{}\n", name, value.trim())).collect();
+        let canonical_request =
+            format!("{}\n{}\n{}\n{}\n{}\n{}", method, path, query, canonical_headers, signed_headers, payload_hash);
+        let scope = format!("{}/{}/s3/aws4_request", date, self.region);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+            timestamp,
+            scope,
+            hex(digest::digest(&digest::SHA256, canonical_request.as_bytes()).as_ref())
+        );
+        let mut key = format!("AWS4{}", self.secret_key).into_bytes();
+        for part in [date.as_str(), &self.region, "s3", "aws4_request"] {
+            key = hmac::sign(&hmac::Key::new(hmac::HMAC_SHA256, &key), part.as_bytes()).as_ref().to_vec();
+        }
+        let signature = hex(hmac::sign(&hmac::Key::new(hmac::HMAC_SHA256, &key), string_to_sign.as_bytes()).as_ref());
+        let authorization = format!(
+            "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+            self.access_key, scope, signed_headers, signature
+        );
+
+        let url = if query.is_empty() { format!("{}{}", self.endpoint, path) } else { format!("{}{}?{}", self.endpoint, path, query) };
+        let mut request = self.client.request(method, url).header("authorization", authorization).body(body);
+        for (name, value) in headers.into_iter().filter(|(name, _)| *name != "host") {
+            request = request.header(name, value);
+        }
+        block_on(async {
+            let response = request.send().await.map_err(io::Error::other)?;
+            let status = response.status().as_u16();
+            let length = response
+                .headers()
+                .get(reqwest::header::CONTENT_LENGTH)
+                .and_then(|length| length.to_str().ok()?.parse().ok());
+            let body = response.bytes().await.map_err(io::Error::other)?.to_vec();
+            Ok(Response { status, length, body })
+        })
+    }
+
+    fn key(&self, id: &str) -> String {
+        format!("{}{}", self.prefix, id)
+    }
+}
+
+struct Response {
+    status: u16,
+    /// Content-Length, which HEAD replies carry without a body
+    length: Option<u64>,
+    body: Vec<u8>,
+}
+
+impl ObjectStore for S3Store {
+    fn size(&self, id: &str) -> Option<u64> {
+        match self.request(reqwest::Method::HEAD, Some(&self.key(id)), &[], Vec::new()).ok()? {
+            Response { status: 200, length, .. } => length,
+            _ => None,
+        }
+    }
+
+    fn read(&self, id: &str) -> io::Result<Vec<u8>> {
+        match self.request(reqwest::Method::GET, Some(&self.key(id)), &[], Vec::new())? {
+            Response { status: 200, body, .. } => Ok(body),
+            Response { status: 404, .. } => Err(io::Error::new(io::ErrorKind::NotFound, format!("object {} not found", id))),
+            Response { status, body, .. } => Err(failed("GET", status, &body)),
+        }
+    }
+
+    fn write(&self, id: &str, data: &[u8]) -> io::Result<()> {
+        match self.request(reqwest::Method::PUT, Some(&self.key(id)), &[], data.to_vec())? {
+            Response { status: 200, .. } => Ok(()),
+            Response { status, body, .. } => Err(failed("PUT", status, &body)),
+        }
+    }
+
+    fn delete(&self, id: &str) -> io::Result<()> {
+        match self.request(reqwest::Method::DELETE, Some(&self.key(id)), &[], Vec::new())? {
+            Response { status: 200 | 204, .. } => Ok(()),
+            Response { status, body, .. } => Err(failed("DELETE", status, &body)),
+        }
+    }
+
+    fn list(&self) -> io::Result<Vec<StoredObject>> {
+        self.list_prefix(&self.prefix)
+    }
+}
+
+impl S3Store {
+    /// Objects whose keys start with `prefix`, following ListObjectsV2 continuation tokens
+    fn list_prefix(&self, prefix: &str) -> io::Result<Vec<StoredObject>> {
+        let mut objects = Vec::new();
+        let mut token: Option<String> = None;
+        loop {
+            let mut query = vec![("list-type", "2"), ("prefix", prefix)];
+            if let Some(token) = &token {
+                query.push(("continuation-token", token));
+            }
+            let body = match self.request(reqwest::Method::GET, None, &query, Vec::new())? {
+                Response { status: 200, body, .. } => String::from_utf8_lossy(&body).to_string(),
+                Response { status, body, .. } => return Err(failed("LIST", status, &body)),
+            };
+            for contents in elements(&body, "Contents") {
+                let Some(id) = element(contents, "Key").and_then(|key| key.strip_prefix(&self.prefix).map(str::to_string)) else {
+                    continue;
+                };
+                if !crate::hosted::is_object_id(&id) {
+                    continue;
+                }
+                let size = element(contents, "Size").and_then(|size| size.parse().ok()).unwrap_or(0);
+                let modified = element(contents, "LastModified")
+                    .and_then(|time| OffsetDateTime::parse(&time, &Rfc3339).ok())
+                    .map(SystemTime::from);
+                objects.push(StoredObject { id, size, modified });
+            }
+            token = element(&body, "NextContinuationToken");
+            if element(&body, "IsTruncated").as_deref() != Some("true") || token.is_none() {
+                return Ok(objects);
+            }
+        }
+    }
+}
+
+/// Runs a future to completion from synchronous code, on the server's runtime if there is one
+fn block_on<F: Future>(future: F) -> F::Output {
+    match tokio::runtime::Handle::try_current() {
+        Ok(handle) => tokio::task::block_in_place(|| handle.block_on(future)),
+        Err(_) => tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .expect("cannot start a runtime for S3 requests")
+            .block_on(future),
+    }
+}
+
+/// The bodies of every `<name>` element in an XML document (S3 replies nest no element in
+/// one of the same name)
+fn elements<'a>(xml: &'a str, name: &str) -> Vec<&'a str> {
+    let (open, close) = (format!("<{}>", name), format!("</{}>", name));
+    let mut found = Vec::new();
+    let mut rest = xml;
+    while let Some(start) = rest.find(&open) {
+        let after = &rest[start + open.len()..];
+        let Some(end) = after.find(&close) else { break };
+        found.push(&after[..end]);
+        rest = &after[end + close.len()..];
+    }
+    found
+}
+
+/// The text of the first `<name>` element, unescaped
+fn element(xml: &str, name: &str) -> Option<String> {
+    let text = elements(xml, name).into_iter().next()?;
+    Some(text.replace("&lt;", "<").replace("&gt;", ">").replace("&quot;", "\"").replace("&apos;", "'").replace("&amp;", "&"))
+}
+
+fn failed(operation: &str, status: u16, body: &[u8]) -> io::Error {
+    let body = String::from_utf8_lossy(body);
+    let message = element(&body, "Message").or_else(|| element(&body, "Code")).unwrap_or_else(|| format!("HTTP {}", status));
+    io::Error::other(format!("S3 {} failed: {}", operation, message))
+}
+
+fn encode(text: &str) -> String {
+    utf8_percent_encode(text, UNRESERVED).to_string()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
@@ -0,0 +1,326 @@
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use base64::Engine;
+use ml_kem::kem::{Decapsulate, Encapsulate};
+use ml_kem::{EncodedSizeUser, KemCore, MlKem768};
+use rand_core::OsRng;
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, CHACHA20_POLY1305};
+use ring::agreement::{self, EphemeralPrivateKey, UnparsedPublicKey, X25519};
+use ring::hkdf::{Salt, HKDF_SHA256};
+use ring::rand::SystemRandom;
+use sha2::{Digest, Sha256};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use crate::transport::{VnpReader, VnpWriter};
+use crate::vnp::{self, VnpCommand};
+
+/// Largest plaintext sealed into a single record
+const MAX_RECORD: usize = 64 * 1024;
+/// Poly1305 tag appended to every record
+const TAG_LEN: usize = 16;
+const CLIENT_TO_SERVER: &[u8] = b"orbit-vnp-v1 client-to-server";
+const SERVER_TO_CLIENT: &[u8] = b"orbit-vnp-v1 server-to-client";
+
+/// Result of offering a secure channel to the server (or of connecting over TLS)
+pub enum Negotiated {
+    /// Both halves now encrypt and authenticate every byte
+    Secure(VnpReader, VnpWriter),
+    /// The server does not support the secure channel; the halves are unchanged and
+    /// must not carry credentials
+    Plaintext(VnpReader, VnpWriter),
+}
+
+/// Client side of the hybrid ML-KEM-768 + X25519 handshake for `orbit://` and `peer://`.
+///
+/// Both shared secrets feed HKDF, so the session stays confidential unless *both*
+/// key exchanges are broken (a quantum attacker breaks X25519 but not ML-KEM).
+/// The handshake is unauthenticated: it defeats passive recording, not an active
+/// man in the middle; use `orbits://` (TLS) when the server's identity matters.
+pub async fn connect(mut reader: VnpReader, mut writer: VnpWriter) -> io::Result<Negotiated> {
+    let (decapsulation_key, encapsulation_key) = MlKem768::generate(&mut OsRng);
+    let x25519_private = EphemeralPrivateKey::generate(&X25519, &SystemRandom::new()).map_err(crypto_error)?;
+    let x25519_public = x25519_private.compute_public_key().map_err(crypto_error)?;
+    let client_x25519 = x25519_public.as_ref().to_vec();
+    let client_ml_kem = encapsulation_key.as_bytes().to_vec();
+
+    vnp::send_command(&mut writer, VnpCommand::SecureHello {
+        x25519: encode(&client_x25519),
+        ml_kem: encode(&client_ml_kem),
+    }).await?;
+
+    let (server_x25519, ciphertext) = match vnp::recv_command(&mut reader).await? {
+        VnpCommand::SecureAccept { x25519, ciphertext } => (decode(&x25519)?, decode(&ciphertext)?),
+        VnpCommand::Error(_) => return Ok(Negotiated::Plaintext(reader, writer)),
+        other => return Err(protocol_error(format!("unexpected reply to SecureHello: {:?}", other))),
+    };
+
+    let ciphertext_array = ciphertext.as_slice().try_into().map_err(|_| protocol_error("bad ML-KEM ciphertext length"))?;
+    let kem_secret = decapsulation_key.decapsulate(&ciphertext_array).map_err(|_| protocol_error("ML-KEM decapsulation failed"))?;
+    let dh_secret = agreement::agree_ephemeral(x25519_private, &UnparsedPublicKey::new(&X25519, &server_x25519), |s| s.to_vec())
+        .map_err(crypto_error)?;
+
+    let transcript = [client_x25519.as_slice(), &client_ml_kem, &server_x25519, &ciphertext];
+    let (send, receive) = derive_keys(&kem_secret, &dh_secret, &transcript, CLIENT_TO_SERVER, SERVER_TO_CLIENT)?;
+    Ok(Negotiated::Secure(
+        Box::new(SecureReader::new(reader, receive)),
+        Box::new(SecureWriter::new(writer, send)),
+    ))
+}
+
+/// Server side of the handshake: answers a client's SecureHello and returns the encrypted halves
+pub async fn accept(
+    reader: VnpReader,
+    mut writer: VnpWriter,
+    client_x25519: &str,
+    client_ml_kem: &str,
+) -> io::Result<(VnpReader, VnpWriter)> {
+    let client_x25519 = decode(client_x25519)?;
+    let client_ml_kem = decode(client_ml_kem)?;
+
+    let encoded_key = client_ml_kem.as_slice().try_into().map_err(|_| protocol_error("bad ML-KEM key length"))?;
+    let encapsulation_key = <MlKem768 as KemCore>::EncapsulationKey::from_bytes(&encoded_key);
+    let (ciphertext, kem_secret) = encapsulation_key.encapsulate(&mut OsRng).map_err(|_| protocol_error("ML-KEM encapsulation failed"))?;
+
+    let x25519_private = EphemeralPrivateKey::generate(&X25519, &SystemRandom::new()).map_err(crypto_error)?;
+    let server_x25519 = x25519_private.compute_public_key().map_err(crypto_error)?.as_ref().to_vec();
+    let dh_secret = agreement::agree_ephemeral(x25519_private, &UnparsedPublicKey::new(&X25519, &client_x25519), |s| s.to_vec())
+        .map_err(crypto_error)?;
+
+    vnp::send_command(&mut writer, VnpCommand::SecureAccept {
+        x25519: encode(&server_x25519),
+        ciphertext: encode(&ciphertext),
+    }).await?;
+
+    let transcript = [client_x25519.as_slice(), &client_ml_kem, &server_x25519, &ciphertext];
+    let (send, receive) = derive_keys(&kem_secret, &dh_secret, &transcript, SERVER_TO_CLIENT, CLIENT_TO_SERVER)?;
+    Ok((Box::new(SecureReader::new(reader, receive)), Box::new(SecureWriter::new(writer, send))))
+}
+
+/// HKDF-SHA256 over both shared secrets, salted with the handshake transcript
+fn derive_keys(
+    kem_secret: &[u8],
+    dh_secret: &[u8],
+    transcript: &[&[u8]],
+    send_label: &[u8],
+    receive_label: &[u8],
+) -> io::Result<(LessSafeKey, LessSafeKey)> {
+    let mut hasher = Sha256::new();
+    for part in transcript {
+        hasher.update(part);
+    }
+    let prk = Salt::new(HKDF_SHA256, &hasher.finalize()).extract(&[kem_secret, dh_secret].concat());
+    let key = |label: &[u8]| -> io::Result<LessSafeKey> {
+        let info = [label];
+        let okm = prk.expand(&info, &CHACHA20_POLY1305).map_err(crypto_error)?;
+        Ok(LessSafeKey::new(UnboundKey::from(okm)))
+    };
+    Ok((key(send_label)?, key(receive_label)?))
+}
+
+/// 96-bit nonce from a per-direction record counter; keys are never reused across sessions
+fn nonce(counter: &mut u64) -> io::Result<Nonce> {
+    let mut bytes = [0u8; 12];
+    bytes[4..].copy_from_slice(&counter.to_be_bytes());
+    *counter = counter.checked_add(1).ok_or_else(|| protocol_error("record counter exhausted"))?;
+    Ok(Nonce::assume_unique_for_key(bytes))
+}
+
+/// Decrypts `u32 length + ciphertext + tag` records into a plain byte stream
+struct SecureReader {
+    inner: VnpReader,
+    key: LessSafeKey,
+    counter: u64,
+    /// Raw bytes of the record being received
+    record: Vec<u8>,
+    /// Decrypted bytes not yet returned to the caller
+    plain: Vec<u8>,
+    plain_pos: usize,
+}
+
+impl SecureReader {
+    fn new(inner: VnpReader, key: LessSafeKey) -> Self {
+        Self { inner, key, counter: 0, record: Vec::new(), plain: Vec::new(), plain_pos: 0 }
+    }
+
+    /// Length of the complete record at the start of `record`, if one has fully arrived
+    fn complete_record(&self) -> io::Result<Option<usize>> {
+        if self.record.len() < 4 {
+            return Ok(None);
+        }
+        let len = u32::from_be_bytes(self.record[..4].try_into().unwrap()) as usize;
+        if !(TAG_LEN..=MAX_RECORD + TAG_LEN).contains(&len) {
+            return Err(protocol_error("invalid encrypted record length"));
+        }
+        Ok((self.record.len() >= 4 + len).then_some(len))
+    }
+}
+
+impl AsyncRead for SecureReader {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        let this = &mut *self;
+        loop {
+            if this.plain_pos < this.plain.len() {
+                let n = buf.remaining().min(this.plain.len() - this.plain_pos);
+                buf.put_slice(&this.plain[this.plain_pos..this.plain_pos + n]);
+                this.plain_pos += n;
+                return Poll::Ready(Ok(()));
+            }
+
+            if let Some(len) = this.complete_record()? {
+                let mut sealed: Vec<u8> = this.record.drain(..4 + len).skip(4).collect();
+                let nonce = nonce(&mut this.counter)?;
+                let plain_len = this.key.open_in_place(nonce, Aad::empty(), &mut sealed)
+                    .map_err(|_| protocol_error("encrypted record failed authentication"))?
+                    .len();
+                sealed.truncate(plain_len);
+                this.plain = sealed;
+                this.plain_pos = 0;
+                continue;
+            }
+
+            let mut chunk = [0u8; 16 * 1024];
+            let mut chunk_buf = ReadBuf::new(&mut chunk);
+            match Pin::new(&mut this.inner).poll_read(cx, &mut chunk_buf) {
+                Poll::Ready(Ok(())) if chunk_buf.filled().is_empty() => {
+                    return if this.record.is_empty() {
+                        Poll::Ready(Ok(()))
+                    } else {
+                        Poll::Ready(Err(io::Error::new(io::ErrorKind::UnexpectedEof, "connection closed mid-record")))
+                    };
+                }
+                Poll::Ready(Ok(())) => this.record.extend_from_slice(chunk_buf.filled()),
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+/// Buffers plaintext and seals it into one record per flush (or per MAX_RECORD bytes)
+struct SecureWriter {
+    inner: VnpWriter,
+    key: LessSafeKey,
+    counter: u64,
+    /// Plaintext waiting to be sealed
+    pending: Vec<u8>,
+    /// Sealed bytes not yet written to the inner stream
+    out: Vec<u8>,
+    out_pos: usize,
+}
+
+impl SecureWriter {
+    fn new(inner: VnpWriter, key: LessSafeKey) -> Self {
+        Self { inner, key, counter: 0, pending: Vec::new(), out: Vec::new(), out_pos: 0 }
+    }
+
+    fn seal_pending(&mut self) -> io::Result<()> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+        let mut sealed = std::mem::take(&mut self.pending);
+        let nonce = nonce(&mut self.counter)?;
+        self.key.seal_in_place_append_tag(nonce, Aad::empty(), &mut sealed).map_err(crypto_error)?;
+        self.out.extend_from_slice(&(sealed.len() as u32).to_be_bytes());
+        self.out.extend_from_slice(&sealed);
+        Ok(())
+    }
+
+    fn poll_drain(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        while self.out_pos < self.out.len() {
+            match Pin::new(&mut self.inner).poll_write(cx, &self.out[self.out_pos..]) {
+                Poll::Ready(Ok(0)) => return Poll::Ready(Err(io::ErrorKind::WriteZero.into())),
+                Poll::Ready(Ok(n)) => self.out_pos += n,
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+        self.out.clear();
+        self.out_pos = 0;
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncWrite for SecureWriter {
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        let this = &mut *self;
+        if this.pending.len() >= MAX_RECORD {
+            this.seal_pending()?;
+        }
+        if !this.out.is_empty() {
+            match this.poll_drain(cx) {
+                Poll::Ready(Ok(())) => {}
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+        let n = buf.len().min(MAX_RECORD - this.pending.len());
+        this.pending.extend_from_slice(&buf[..n]);
+        Poll::Ready(Ok(n))
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = &mut *self;
+        this.seal_pending()?;
+        match this.poll_drain(cx) {
+            Poll::Ready(Ok(())) => Pin::new(&mut this.inner).poll_flush(cx),
+            other => other,
+        }
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.as_mut().poll_flush(cx) {
+            Poll::Ready(Ok(())) => Pin::new(&mut self.inner).poll_shutdown(cx),
+            other => other,
+        }
+    }
+}
+
+fn encode(bytes: &[u8]) -> String {
+    base64::engine::general_purpose::STANDARD.encode(bytes)
+}
+
+fn decode(text: &str) -> io::Result<Vec<u8>> {
+    base64::engine::general_purpose::STANDARD.decode(text).map_err(|_| protocol_error("invalid base64 in handshake"))
+}
+
+fn protocol_error(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+fn crypto_error(_: ring::error::Unspecified) -> io::Error {
+    protocol_error("secure channel cryptography failed")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_handshake_and_encrypted_round_trip() {
+        let (client, server) = tokio::io::duplex(1 << 20);
+        let (client_read, client_write) = tokio::io::split(client);
+        let (server_read, server_write) = tokio::io::split(server);
+
+        let server = tokio::spawn(async move {
+            let mut reader: VnpReader = Box::new(server_read);
+            let writer: VnpWriter = Box::new(server_write);
+            let VnpCommand::SecureHello { x25519, ml_kem } = vnp::recv_command(&mut reader).await.unwrap() else {
+                panic!("expected SecureHello");
+            };
+            let (mut reader, mut writer) = accept(reader, writer, &x25519, &ml_kem).await.unwrap();
+            let command = vnp::recv_command(&mut reader).await.unwrap();
+            vnp::send_command(&mut writer, command).await.unwrap();
+        });
+
+        let Negotiated::Secure(mut reader, mut writer) = connect(Box::new(client_read), Box::new(client_write)).await.unwrap() else {
+            panic!("expected a secure channel");
+        };
+        let large = VnpCommand::ObjectData(vec![7u8; 3 * MAX_RECORD]);
+        vnp::send_command(&mut writer, large).await.unwrap();
+        match vnp::recv_command(&mut reader).await.unwrap() {
+            VnpCommand::ObjectData(data) => assert_eq!(data, vec![7u8; 3 * MAX_RECORD]),
+            other => panic!("unexpected echo: {:?}", other),
+        }
+        server.await.unwrap();
+    }
+}
@@ -0,0 +1,1071 @@
+use std::collections::{HashSet, VecDeque};
+use std::io;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use rand_core::RngCore;
+use tokio::net::{TcpListener, TcpStream};
+use tokio_rustls::TlsAcceptor;
+use crate::access::Permission;
+use crate::admin_api::{self, AdminOptions};
+use crate::hosted::{self, HostedRepo};
+use crate::object_store::{self, StorageConfig};
+use crate::objects::{Commit, Directory, File, ObjectId, Tag};
+use crate::throttle::{RequestLimiter, Throttle};
+use crate::audit::{self, AuditLog};
+use crate::transport::{self, VnpReader, VnpWriter, WireCounters};
+use crate::users::UserStore;
+use crate::vnp::{self, RefAdvertisement, RepositoryInfo, VnpCommand};
+use crate::refs::{self, RefChange};
+use crate::server_tls::{self, Certificates};
+use crate::replication::{self, Job, Replicator};
+use crate::{hooks, http_transport, maintenance, secure_channel, throttle, transfer, web_ui, webhooks};
+use tracing::{error, info, warn};
+
+/// Port `orb serve` listens on and `orbit://` URLs default to
+pub const DEFAULT_PORT: u16 = 8080;
+/// Port `orb serve --tls` listens on and `orbits://` URLs default to
+pub const DEFAULT_TLS_PORT: u16 = 443;
+
+/// Settings of an `orb serve` instance
+pub struct ServeOptions {
+    /// Directory holding the hosted repositories (`<root>/<namespace>/<repo>`)
+    pub root: PathBuf,
+    pub port: u16,
+    /// Server config directory (users.json, cert.pem, key.pem)
+    pub dir: PathBuf,
+    /// Serve TLS with the certificate in `dir`
+    pub tls: bool,
+    /// Let clients without a token (and other users) read every repository
+    pub allow_anonymous: bool,
+    /// Admin HTTP API settings, if it is enabled
+    pub admin: Option<AdminOptions>,
+    /// Address of the read-only web UI, if it is enabled
+    pub web: Option<SocketAddr>,
+    /// When to prune and re-index repositories; None leaves it to `orb serve maintenance`
+    pub maintenance: Option<maintenance::Schedule>,
+    /// Object store backend of new repositories (`file`, `s3://...`, or `redis://...`);
+    /// `storage.json` in `dir` overrides it per namespace
+    pub storage: String,
+    /// Followers to copy pushes to, and whether this server is itself a replica
+    pub replication: Option<replication::Options>,
+    pub quotas: Quotas,
+    pub limits: throttle::Limits,
+    /// JSONL audit log (None disables it), rotated at `audit_max_size` bytes
+    pub audit_log: Option<PathBuf>,
+    pub audit_max_size: u64,
+}
+
+/// Storage limits `orb serve` enforces on pushes
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Quotas {
+    /// Most bytes of objects one repository may store
+    pub repo: Option<u64>,
+    /// Most bytes of objects all of one user's repositories may store together
+    pub user: Option<u64>,
+    /// Largest single object a push may contain
+    pub max_object_size: Option<u64>,
+}
+
+impl Quotas {
+    pub fn is_empty(&self) -> bool {
+        self.repo.is_none() && self.user.is_none()
+    }
+
+    /// Storage used by a repository and by its owner's namespace
+    pub fn usage(&self, root: &Path, name: &str, repo: &HostedRepo) -> Usage {
+        let owner = hosted::split_name(name).map(|(owner, _)| owner).unwrap_or_default();
+        Usage {
+            repository: name.to_string(),
+            size: repo.usage(),
+            quota: self.repo,
+            owner: owner.to_string(),
+            owner_size: hosted::namespace_usage(root, owner),
+            owner_quota: self.user,
+        }
+    }
+}
+
+/// How much a repository and its owner store, against the server's quotas
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct Usage {
+    pub repository: String,
+    pub size: u64,
+    pub quota: Option<u64>,
+    pub owner: String,
+    pub owner_size: u64,
+    pub owner_quota: Option<u64>,
+}
+
+impl Usage {
+    /// Bytes a push may still add, with the quota that stops it
+    pub fn headroom(&self) -> Option<(u64, String)> {
+        let repo = self.quota.map(|quota| {
+            (quota.saturating_sub(self.size), format!("the {} repository quota", transfer::format_bytes(quota)))
+        });
+        let owner = self.owner_quota.map(|quota| {
+            (quota.saturating_sub(self.owner_size), format!("{}'s {} storage quota", self.owner, transfer::format_bytes(quota)))
+        });
+        repo.into_iter().chain(owner).min_by_key(|(left, _)| *left)
+    }
+
+    /// Most bytes the repository can grow to under both quotas
+    pub fn effective_quota(&self) -> Option<u64> {
+        self.headroom().map(|(left, _)| self.size + left)
+    }
+}
+
+/// State shared by every connection, and by the admin API
+pub struct Server {
+    pub root: PathBuf,
+    pub dir: PathBuf,
+    pub allow_anonymous: bool,
+    pub quotas: Quotas,
+    pub storage: StorageConfig,
+    pub throttle: Arc<Throttle>,
+    pub audit: AuditLog,
+    /// Pause a repository's writes while it is maintained
+    pub maintenance: maintenance::Locks,
+    /// Queues changes for follower servers (None without followers)
+    replicator: Option<Replicator>,
+    /// On a read-only replica, the token its leader authenticates with
+    replica_token: Option<String>,
+    /// Client that delivers webhooks
+    http: reqwest::Client,
+    /// Serializes ref updates and repository creation, deletion, and renames, so concurrent
+    /// sessions cannot both pass the same check
+    ref_lock: tokio::sync::Mutex<()>,
+}
+
+/// `orb serve`: hosts repositories over VNP until interrupted
+pub async fn run(options: ServeOptions) -> Result<(), Box<dyn std::error::Error>> {
+    std::fs::create_dir_all(&options.root)?;
+    let tls = match options.tls {
+        true => {
+            let certificates = Certificates::load(&options.dir)?;
+            info!("🔏 TLS certificates for {} (reloaded when they change)", server_tls::describe(&certificates.names()));
+            let acceptor = certificates.acceptor();
+            tokio::spawn(certificates.watch());
+            Some(acceptor)
+        }
+        false => None,
+    };
+    object_store::validate(&options.storage)?;
+    if options.storage != "file" {
+        info!("🗄️  New repositories store objects in {}", options.storage);
+    }
+    if UserStore::load(&options.dir).map_err(|e| e.to_string())?.users.is_empty() {
+        warn!("⚠️  No users yet; issue a token with `orb serve token <email>`");
+    }
+
+    let listener = TcpListener::bind(("0.0.0.0", options.port)).await?;
+    let scheme = if options.tls { "orbits" } else { "orbit" };
+    let count = hosted::list_repositories(&options.root).len();
+    info!("🛰️  Serving {} repositories from {} on port {}", count, options.root.display(), options.port);
+    info!("💡 Clients: orb clone {}://<this-host>:{}/<email>/<repo> --create", scheme, options.port);
+    info!("   Press Ctrl-C to stop the server.");
+
+    let audit = match options.audit_log {
+        Some(path) => {
+            let log = AuditLog::open(path.clone(), options.audit_max_size)
+                .map_err(|e| format!("cannot open audit log {}: {}", path.display(), e))?;
+            info!("📜 Audit log: {}", path.display());
+            log
+        }
+        None => AuditLog::disabled(),
+    };
+    let (replicator, followers, replication_token, replica_token) = match options.replication {
+        Some(replication) => {
+            let (replicator, followers) = replication::channels(replication.followers);
+            let replica_token = replication.replica.then(|| replication.token.clone());
+            (Some(replicator).filter(|_| !followers.is_empty()), followers, replication.token, replica_token)
+        }
+        None => (None, Vec::new(), String::new(), None),
+    };
+    if replica_token.is_some() {
+        info!("🪞 Read-only replica: only the leader may write");
+    }
+    let server = Arc::new(Server {
+        root: options.root,
+        dir: options.dir.clone(),
+        allow_anonymous: options.allow_anonymous,
+        quotas: options.quotas,
+        storage: StorageConfig { default: options.storage, dir: options.dir },
+        throttle: Throttle::new(options.limits),
+        audit,
+        maintenance: maintenance::Locks::default(),
+        replicator,
+        replica_token,
+        http: reqwest::Client::new(),
+        ref_lock: tokio::sync::Mutex::new(()),
+    });
+    if let Some(admin) = options.admin {
+        if admin.admin_token.is_none() {
+            warn!("⚠️  No admin token set; only registration and per-repository access are available");
+        }
+        info!("🔧 Admin API on http://{}", admin.bind);
+        let server = server.clone();
+        tokio::spawn(async move {
+            if let Err(e) = admin_api::serve(admin, server).await {
+                error!("❌ Admin API failed: {}", e);
+            }
+        });
+    }
+    for follower in followers {
+        info!("🔁 Replicating to {}", follower.label);
+        tokio::spawn(follower.run(server.clone(), replication_token.clone()));
+    }
+    if let Some(schedule) = options.maintenance {
+        tokio::spawn(maintenance::schedule(server.clone(), schedule));
+    }
+    if let Some(bind) = options.web {
+        info!("🌐 Web UI on http://{}", bind);
+        let server = server.clone();
+        tokio::spawn(async move {
+            if let Err(e) = web_ui::serve(bind, server).await {
+                error!("❌ Web UI failed: {}", e);
+            }
+        });
+    }
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => {
+                let (stream, addr) = accepted?;
+                // Dropping the stream of a refused connection closes it
+                let slot = match server.throttle.connect(addr.ip()) {
+                    Ok(slot) => slot,
+                    Err(reason) => {
+                        info!("🚦 Refused {}: {}", addr, reason);
+                        continue;
+                    }
+                };
+                let (server, tls) = (server.clone(), tls.clone());
+                tokio::spawn(async move {
+                    let _slot = slot;
+                    match serve_connection(stream, addr, server, tls).await {
+                        Ok(()) => {}
+                        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => {}
+                        Err(e) => warn!("⚠️  Session with {} ended: {}", addr, e),
+                    }
+                });
+            }
+            _ = tokio::signal::ctrl_c() => {
+                info!("");
+                info!("🛑 Server stopped");
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Sets up one client connection (TLS and/or WebSocket as the client asks) and serves it
+async fn serve_connection(
+    stream: TcpStream,
+    addr: SocketAddr,
+    server: Arc<Server>,
+    tls: Option<TlsAcceptor>,
+) -> io::Result<()> {
+    let (reader, writer): (VnpReader, VnpWriter) = match tls {
+        Some(acceptor) => {
+            let stream = acceptor.accept(stream).await?;
+            if stream.get_ref().1.alpn_protocol() == Some(b"http/1.1") {
+                http_transport::accept(stream).await?
+            } else {
+                let (reader, writer) = tokio::io::split(stream);
+                (Box::new(reader), Box::new(writer))
+            }
+        }
+        None => {
+            // A VNP frame starts with a big-endian length, which can never begin with
+            // 'G' (a 1 GiB frame), so that byte means an HTTP request from an http:// client
+            let mut first = [0u8; 1];
+            if stream.peek(&mut first).await? == 1 && first[0] == b'G' {
+                http_transport::accept(stream).await?
+            } else {
+                let (reader, writer) = stream.into_split();
+                (Box::new(reader), Box::new(writer))
+            }
+        }
+    };
+
+    let wire = WireCounters::default();
+    let (reader, writer) = transport::count(reader, writer, &wire);
+    let requests = server.throttle.requests();
+    let mut session = Session { server, addr, user: None, authenticated: false, replicator: false, selected: None, requests, wire };
+    let result = session.run(reader, writer).await;
+
+    let (sent, received) = session.wire.totals();
+    session.server.audit.record(audit::Event {
+        bytes_received: Some(received),
+        bytes_sent: Some(sent),
+        message: result.as_ref().err().filter(|e| e.kind() != io::ErrorKind::UnexpectedEof).map(|e| e.to_string()),
+        ..session.event("disconnect")
+    });
+    result
+}
+
+/// One client's VNP session
+struct Session {
+    server: Arc<Server>,
+    addr: SocketAddr,
+    /// Account behind the session's token (None for anonymous sessions)
+    user: Option<String>,
+    authenticated: bool,
+    /// The session is this replica's leader, which may write to every repository
+    replicator: bool,
+    /// Repository chosen with SelectRepository or CreateRepository, and the session's
+    /// permission on it
+    selected: Option<(String, HostedRepo, Permission)>,
+    requests: RequestLimiter,
+    /// Bytes exchanged on the connection so far
+    wire: WireCounters,
+}
+
+impl Session {
+    /// Answers commands until the client disconnects or aborts
+    async fn run(&mut self, mut reader: VnpReader, mut writer: VnpWriter) -> io::Result<()> {
+        loop {
+            let command = vnp::recv_request(&mut reader, &mut writer).await?;
+            self.requests.wait().await;
+            let reply = match command {
+                VnpCommand::SecureHello { x25519, ml_kem } => {
+                    (reader, writer) = secure_channel::accept(reader, writer, &x25519, &ml_kem).await?;
+                    continue;
+                }
+                VnpCommand::Authenticate(token) => self.authenticate(Some(&token)),
+                VnpCommand::AuthenticateAnonymous => self.authenticate(None),
+                VnpCommand::Abort(reason) => {
+                    info!("🛑 {} aborted: {}", self.label(), reason);
+                    return Ok(());
+                }
+                _ if !self.authenticated => VnpCommand::Error("authenticate first".to_string()),
+                command => match self.handle(command, &mut reader, &mut writer).await? {
+                    Some(reply) => reply,
+                    None => continue,
+                },
+            };
+            vnp::send_command(&mut writer, reply).await?;
+        }
+    }
+
+    /// Checks a token against users.json (None asks for an anonymous, read-only session)
+    fn authenticate(&mut self, token: Option<&str>) -> VnpCommand {
+        let denied = |message: &str| VnpCommand::AuthResult {
+            success: false,
+            message: message.to_string(),
+            capabilities: Vec::new(),
+        };
+        let Some(token) = token else {
+            if !self.server.allow_anonymous {
+                return denied("this server requires a token");
+            }
+            self.authenticated = true;
+            self.server.audit.record(audit::Event { message: Some("anonymous".to_string()), ..self.event("auth") });
+            return VnpCommand::AuthResult {
+                success: true,
+                message: "anonymous (read-only)".to_string(),
+                capabilities: vec![vnp::CAP_READ.to_string()],
+            };
+        };
+
+        let throttle = &self.server.throttle;
+        if let Some(wait) = throttle.locked_out(self.addr.ip(), Some(token)) {
+            self.server.audit.record(audit::Event { message: Some("locked out".to_string()), ..self.event("auth_failed") });
+            return denied(&format!("too many failed attempts; try again in {}s", wait.as_secs().max(1)));
+        }
+
+        if self.server.replica_token.as_deref().is_some_and(|expected| expected.as_bytes() == token.as_bytes()) {
+            info!("🔐 {} authenticated as the leader", self.addr);
+            throttle.auth_succeeded(self.addr.ip());
+            self.user = Some(replication::PRINCIPAL.to_string());
+            self.authenticated = true;
+            self.replicator = true;
+            self.server.audit.record(self.event("auth"));
+            return VnpCommand::AuthResult {
+                success: true,
+                message: "authenticated as the leader".to_string(),
+                capabilities: vec![vnp::CAP_READ.to_string(), vnp::CAP_WRITE.to_string()],
+            };
+        }
+
+        let store = match UserStore::load(&self.server.dir) {
+            Ok(store) => store,
+            Err(e) => {
+                warn!("⚠️  Cannot read {}: {}", UserStore::path(&self.server.dir).display(), e);
+                return denied("server user database is unavailable");
+            }
+        };
+        match store.authenticate(token) {
+            Ok(user) => {
+                info!("🔐 {} authenticated as {}", self.addr, user);
+                throttle.auth_succeeded(self.addr.ip());
+                self.user = Some(user.to_string());
+                self.authenticated = true;
+                self.server.audit.record(self.event("auth"));
+                VnpCommand::AuthResult {
+                    success: true,
+                    message: format!("authenticated as {}", user),
+                    capabilities: vec![vnp::CAP_READ.to_string(), vnp::CAP_WRITE.to_string()],
+                }
+            }
+            Err(reason) => {
+                info!("🚫 {} presented a refused token ({})", self.addr, reason);
+                throttle.auth_failed(self.addr.ip(), Some(token));
+                self.server.audit.record(audit::Event { message: Some(reason.to_string()), ..self.event("auth_failed") });
+                denied(&reason.to_string())
+            }
+        }
+    }
+
+    /// Handles a command of an authenticated session. Returns the reply, or None if the
+    /// handler already answered on the stream.
+    async fn handle(&mut self, command: VnpCommand, reader: &mut VnpReader, writer: &mut VnpWriter) -> io::Result<Option<VnpCommand>> {
+        let reply = match command {
+            VnpCommand::Push(_)
+            | VnpCommand::UpdateRef { .. }
+            | VnpCommand::PushTags { .. }
+            | VnpCommand::CreateRepository(_)
+            | VnpCommand::DeleteRepository(_)
+            | VnpCommand::RenameRepository { .. }
+                if self.server.replica_token.is_some() && !self.replicator =>
+            {
+                VnpCommand::Error("this server is a read-only replica; push to its leader instead".to_string())
+            }
+            VnpCommand::ListRepositories => VnpCommand::RepositoryList(self.visible_repositories()),
+            VnpCommand::SelectRepository(name) => self.select(&name),
+            VnpCommand::CreateRepository(name) => self.create(&name).await,
+            VnpCommand::DeleteRepository(name) => self.delete(&name).await,
+            VnpCommand::RenameRepository { from, to } => self.rename(&from, &to).await,
+            VnpCommand::Ready => VnpCommand::Ok,
+            command => {
+                let Some((name, repo, permission)) = self.selected.clone() else {
+                    return Ok(Some(VnpCommand::Error(
+                        "no repository selected (add /<email>/<repo> to the server URL)".to_string(),
+                    )));
+                };
+                // Writes wait while the repository is being maintained. Locks are always
+                // taken in this order: the repository's, then ref_lock.
+                let lock = self.server.maintenance.get(&name);
+                let _writing = match &command {
+                    VnpCommand::Push(_) | VnpCommand::UpdateRef { .. } | VnpCommand::PushTags { .. } => Some(lock.read().await),
+                    _ => None,
+                };
+                match command {
+                    VnpCommand::ListRefs => VnpCommand::RefList(RefAdvertisement {
+                        branches: repo.branches(),
+                        tags: repo.tags(),
+                        annotations: repo.annotations(),
+                    }),
+                    // History walks can outlast the client's read timeout, so they run with keepalives
+                    VnpCommand::Have(have) => {
+                        let walk = tokio::task::spawn_blocking({
+                            let repo = repo.clone();
+                            move || missing_commits(&repo, &have)
+                        });
+                        VnpCommand::Want(vnp::with_keepalive(writer, walk).await?.map_err(io::Error::other)?)
+                    }
+                    VnpCommand::Get(id) | VnpCommand::GetTree(id) | VnpCommand::GetFile(id) => {
+                        if !hosted::is_object_id(&id) {
+                            VnpCommand::Error(format!("invalid object id: {}", id))
+                        } else {
+                            repo.send_object(writer, &id).await?;
+                            return Ok(None);
+                        }
+                    }
+                    VnpCommand::EstimateGraph(commits) => {
+                        let walk = tokio::task::spawn_blocking({
+                            let repo = repo.clone();
+                            move || estimate_graph(&repo, &commits)
+                        });
+                        vnp::with_keepalive(writer, walk).await?.map_err(io::Error::other)?
+                    }
+                    VnpCommand::Push(_) | VnpCommand::UpdateRef { .. } | VnpCommand::PushTags { .. } if permission < Permission::Write => {
+                        VnpCommand::Error(format!("you have read-only access to '{}'", name))
+                    }
+                    VnpCommand::Push(commits) => match self.receive(&name, &repo, reader, writer, commits).await? {
+                        Ok(0) => VnpCommand::Ok,
+                        Ok(count) => {
+                            info!("📥 {} pushed {} objects to {}", self.label(), count, name);
+                            VnpCommand::Ok
+                        }
+                        Err(message) => VnpCommand::Error(message),
+                    },
+                    VnpCommand::UpdateRef { branch, old, new, force } => {
+                        let _guard = self.server.ref_lock.lock().await;
+                        let result = match branch_change(&repo, &branch, old.as_deref(), &new, force) {
+                            Ok(change) => self.apply(&name, &repo, vec![change]).await,
+                            Err(message) => Err(message),
+                        };
+                        match result {
+                            Ok(()) => {
+                                info!("📌 {} moved {}:{} to {}", self.label(), name, branch, &new[..7]);
+                                VnpCommand::Ok
+                            }
+                            Err(message) => VnpCommand::Error(message),
+                        }
+                    }
+                    VnpCommand::PushTags { tags, force, annotations } => {
+                        let _guard = self.server.ref_lock.lock().await;
+                        let result = match tag_changes(&repo, &tags, &annotations, force) {
+                            Ok(changes) => self.apply(&name, &repo, changes).await,
+                            Err(message) => Err(message),
+                        };
+                        match result {
+                            Ok(()) => VnpCommand::Ok,
+                            Err(message) => VnpCommand::Error(message),
+                        }
+                    }
+                    other => VnpCommand::Error(format!("unsupported command: {:?}", other)),
+                }
+            }
+        };
+        Ok(Some(reply))
+    }
+
+    fn permission(&self, name: &str) -> Option<Permission> {
+        if self.replicator {
+            return hosted::split_name(name).ok().map(|_| Permission::Admin);
+        }
+        permission(&self.server.root, name, self.user.as_deref(), self.server.allow_anonymous)
+    }
+
+    fn visible_repositories(&self) -> Vec<RepositoryInfo> {
+        hosted::list_repositories(&self.server.root)
+            .into_iter()
+            .filter(|name| self.permission(name).is_some())
+            .filter_map(|name| {
+                let repo = HostedRepo::open(&hosted::repo_dir(&self.server.root, &name).ok()?)?;
+                let mut info = repo.info(&name);
+                if !self.server.quotas.is_empty() {
+                    info.quota = self.server.quotas.usage(&self.server.root, &name, &repo).effective_quota();
+                }
+                Some(info)
+            })
+            .collect()
+    }
+
+    /// Opens a repository the session may read. Repositories the session cannot see are
+    /// reported as not found, so their names do not leak.
+    fn open(&self, name: &str) -> Result<(HostedRepo, Permission), String> {
+        let dir = hosted::repo_dir(&self.server.root, name)?;
+        match (HostedRepo::open(&dir), self.permission(name)) {
+            (Some(repo), Some(permission)) => Ok((repo, permission)),
+            _ => Err(format!("repository '{}' not found", name)),
+        }
+    }
+
+    fn select(&mut self, name: &str) -> VnpCommand {
+        match self.open(name) {
+            Ok((repo, permission)) => {
+                self.server.audit.record(audit::Event {
+                    repository: Some(name.to_string()),
+                    message: Some(format!("{} access", permission)),
+                    ..self.event("select")
+                });
+                self.selected = Some((name.to_string(), repo, permission));
+                VnpCommand::RepositorySelected(name.to_string())
+            }
+            Err(message) => VnpCommand::Error(message),
+        }
+    }
+
+    /// Creates a repository in the caller's own namespace (a bare name is put there) and
+    /// selects it
+    async fn create(&mut self, name: &str) -> VnpCommand {
+        let Some(user) = self.user.clone() else {
+            return VnpCommand::Error("creating repositories requires a token".to_string());
+        };
+        let name = if name.contains('/') { name.to_string() } else { format!("{}/{}", user, name) };
+        let dir = match hosted::repo_dir(&self.server.root, &name) {
+            Ok(dir) => dir,
+            Err(message) => return VnpCommand::Error(message),
+        };
+        if self.permission(&name) != Some(Permission::Admin) {
+            return VnpCommand::Error(format!("you can only create repositories in your namespace ({}/...)", user));
+        }
+
+        let namespace = hosted::split_name(&name).map_or("", |(namespace, _)| namespace);
+        let backend = match self.server.storage.backend_for(namespace) {
+            Ok(backend) => backend,
+            Err(message) => {
+                warn!("⚠️  {}", message);
+                return VnpCommand::Error("server storage configuration is unavailable".to_string());
+            }
+        };
+
+        let _guard = self.server.ref_lock.lock().await;
+        if HostedRepo::open(&dir).is_some() {
+            return VnpCommand::Error(format!("repository '{}' already exists", name));
+        }
+        match HostedRepo::open_or_init(&dir, &backend) {
+            Ok(repo) => {
+                info!("📂 {} created {}", self.label(), name);
+                self.server.audit.record(audit::Event { repository: Some(name.clone()), ..self.event("create") });
+                self.selected = Some((name.clone(), repo, Permission::Admin));
+                VnpCommand::RepositorySelected(name)
+            }
+            Err(e) => VnpCommand::Error(format!("cannot create '{}': {}", name, e)),
+        }
+    }
+
+    async fn delete(&mut self, name: &str) -> VnpCommand {
+        let lock = self.server.maintenance.get(name);
+        let _paused = lock.write().await;
+        let _guard = self.server.ref_lock.lock().await;
+        let repo = match self.open(name) {
+            Ok((repo, Permission::Admin)) => repo,
+            Ok(_) => return VnpCommand::Error(format!("only the owner can delete '{}'", name)),
+            Err(message) => return VnpCommand::Error(message),
+        };
+        let dir = hosted::repo_dir(&self.server.root, name).expect("validated by open");
+        if let Err(e) = repo.store().clear().and_then(|()| std::fs::remove_dir_all(&dir)) {
+            return VnpCommand::Error(format!("cannot delete '{}': {}", name, e));
+        }
+        if self.selected.as_ref().is_some_and(|(selected, ..)| selected == name) {
+            self.selected = None;
+        }
+        info!("🗑️  {} deleted {}", self.label(), name);
+        self.replicate(Job::Delete(name.to_string()));
+        self.server.audit.record(audit::Event { repository: Some(name.to_string()), ..self.event("delete") });
+        VnpCommand::Ok
+    }
+
+    /// Renames a repository; it cannot move to another namespace
+    async fn rename(&mut self, from: &str, to: &str) -> VnpCommand {
+        let lock = self.server.maintenance.get(from);
+        let _paused = lock.write().await;
+        let _guard = self.server.ref_lock.lock().await;
+        match self.open(from) {
+            Ok((_, Permission::Admin)) => {}
+            Ok(_) => return VnpCommand::Error(format!("only the owner can rename '{}'", from)),
+            Err(message) => return VnpCommand::Error(message),
+        }
+        let target = match hosted::repo_dir(&self.server.root, to) {
+            Ok(target) => target,
+            Err(message) => return VnpCommand::Error(message),
+        };
+        if hosted::split_name(from).map(|n| n.0) != hosted::split_name(to).map(|n| n.0) {
+            return VnpCommand::Error("repositories cannot be moved to another namespace".to_string());
+        }
+        if target.exists() {
+            return VnpCommand::Error(format!("repository '{}' already exists", to));
+        }
+        let source = hosted::repo_dir(&self.server.root, from).expect("validated by open");
+        if let Err(e) = std::fs::rename(&source, &target) {
+            return VnpCommand::Error(format!("cannot rename '{}': {}", from, e));
+        }
+        self.selected = None;
+        info!("✏️  {} renamed {} to {}", self.label(), from, to);
+        self.replicate(Job::Rename { from: from.to_string(), to: to.to_string() });
+        self.server.audit.record(audit::Event {
+            repository: Some(to.to_string()),
+            message: Some(format!("renamed from {}", from)),
+            ..self.event("rename")
+        });
+        VnpCommand::Ok
+    }
+
+    /// Receives a push within the server's object size limit and storage quotas
+    async fn receive(
+        &self,
+        name: &str,
+        repo: &HostedRepo,
+        reader: &mut VnpReader,
+        writer: &mut VnpWriter,
+        commits: Vec<ObjectId>,
+    ) -> io::Result<Result<usize, String>> {
+        // Quotas apply to pushes to the leader, which has already accepted these objects
+        let quotas = if self.replicator { Quotas::default() } else { self.server.quotas };
+        let limits = PushLimits {
+            max_object_size: quotas.max_object_size,
+            headroom: if quotas.is_empty() { None } else { quotas.usage(&self.server.root, name, repo).headroom() },
+        };
+        let (_, received_before) = self.wire.totals();
+        let result = receive_push(repo, reader, writer, commits, &limits).await?;
+        let (_, received_after) = self.wire.totals();
+        let event = match &result {
+            Ok(count) => audit::Event { objects: Some(*count as u64), ..self.event("push") },
+            Err(message) => audit::Event { message: Some(message.clone()), ..self.event("rejected") },
+        };
+        self.server.audit.record(audit::Event {
+            repository: Some(name.to_string()),
+            bytes_received: Some(received_after - received_before),
+            ..event
+        });
+        Ok(result)
+    }
+
+    /// Moves refs once the repository's policy and pre-receive hook accept the changes,
+    /// then runs the post-receive hook in the background. Callers hold `ref_lock`.
+    /// Changes from a replica's leader were checked there, and its hooks already ran.
+    async fn apply(&self, name: &str, repo: &HostedRepo, changes: Vec<RefChange>) -> Result<(), String> {
+        let changes: Vec<RefChange> = changes.into_iter().filter(|change| change.old != change.new).collect();
+        if changes.is_empty() {
+            return Ok(());
+        }
+        let policy = repo.policy();
+        let env = vec![
+            ("ORB_REPOSITORY", name.to_string()),
+            ("ORB_USER", self.user.clone().unwrap_or_default()),
+        ];
+
+        if !self.replicator {
+            let (checked_repo, checked_changes, checked_env) = (repo.clone(), changes.clone(), env.clone());
+            tokio::task::spawn_blocking(move || {
+                policy.check(&checked_repo, &checked_changes)?;
+                let env: Vec<(&str, &str)> = checked_env.iter().map(|(k, v)| (*k, v.as_str())).collect();
+                hooks::run_hosted(checked_repo.dir(), "pre-receive", &checked_changes, &env).map(|_| ())
+            })
+            .await
+            .map_err(|e| e.to_string())?
+            .inspect_err(|message| {
+                info!("🚫 Rejected {}'s update to {}: {}", self.label(), name, message);
+                self.server.audit.record(audit::Event {
+                    repository: Some(name.to_string()),
+                    refs: changes.clone(),
+                    message: Some(message.clone()),
+                    ..self.event("rejected")
+                });
+            })?;
+        }
+
+        for change in &changes {
+            if let Some(new) = &change.new {
+                repo.write_ref(&change.name, new).map_err(|e| e.to_string())?;
+            }
+        }
+        self.server.audit.record(audit::Event { repository: Some(name.to_string()), refs: changes.clone(), ..self.event("update") });
+        self.replicate(Job::Sync(name.to_string()));
+        if self.replicator {
+            return Ok(());
+        }
+        self.notify(name, repo, &changes);
+
+        let (repo, name) = (repo.clone(), name.to_string());
+        tokio::task::spawn_blocking(move || {
+            let env: Vec<(&str, &str)> = env.iter().map(|(k, v)| (*k, v.as_str())).collect();
+            match hooks::run_hosted(repo.dir(), "post-receive", &changes, &env) {
+                Ok(output) if output.is_empty() => {}
+                Ok(output) => info!("🪝 post-receive ({}): {}", name, output),
+                Err(message) => warn!("⚠️  {}: {}", name, message),
+            }
+        });
+        Ok(())
+    }
+
+    /// POSTs a push payload per changed ref to each of the repository's webhooks, in the
+    /// background; every delivery is recorded in the audit log
+    fn notify(&self, name: &str, repo: &HostedRepo, changes: &[RefChange]) {
+        for hook in repo.webhooks() {
+            let payloads: Vec<webhooks::Payload> =
+                changes.iter().map(|change| webhooks::Payload::push(name, change, self.user.as_deref())).collect();
+            let (server, event) = (self.server.clone(), audit::Event { repository: Some(name.to_string()), ..self.event("webhook") });
+            tokio::spawn(async move {
+                for payload in payloads {
+                    let outcome = webhooks::deliver(&server.http, &hook, &payload).await;
+                    let message = match &outcome {
+                        Ok(status) => format!("{} {} → {}", payload.ref_name, hook.url, status),
+                        Err(message) => {
+                            warn!("⚠️  Webhook {} for {}: {}", hook.id, payload.repository, message);
+                            format!("{} {}", payload.ref_name, message)
+                        }
+                    };
+                    server.audit.record(audit::Event { message: Some(message), ..event.clone() });
+                }
+            });
+        }
+    }
+
+    /// Queues a change for the server's followers, if it has any
+    fn replicate(&self, job: Job) {
+        if let Some(replicator) = &self.server.replicator {
+            replicator.queue(job);
+        }
+    }
+
+    /// Audit event stamped with the session's client and user
+    fn event(&self, kind: &str) -> audit::Event {
+        audit::Event { client: Some(self.addr.to_string()), user: self.user.clone(), ..audit::Event::new(kind) }
+    }
+
+    /// The session's user (or address for anonymous sessions) for log lines
+    fn label(&self) -> String {
+        self.user.clone().unwrap_or_else(|| self.addr.to_string())
+    }
+}
+
+/// What `user` (None for anonymous sessions) may do with a repository: owners (whose
+/// username is the namespace) have full control, collaborators have what the owner
+/// granted, and with `allow_anonymous` everyone else may read
+pub fn permission(root: &Path, name: &str, user: Option<&str>, allow_anonymous: bool) -> Option<Permission> {
+    let namespace = hosted::split_name(name).ok()?.0;
+    let granted = match user {
+        Some(user) if user == namespace => return Some(Permission::Admin),
+        Some(user) => hosted::repo_dir(root, name)
+            .ok()
+            .and_then(|dir| HostedRepo::open(&dir))
+            .and_then(|repo| repo.grants().get(user).copied()),
+        None => None,
+    };
+    granted.or(allow_anonymous.then_some(Permission::Read))
+}
+
+/// Commits reachable from the server's branches and tags that the client does not have
+fn missing_commits(repo: &HostedRepo, have: &[ObjectId]) -> Vec<ObjectId> {
+    let known: HashSet<ObjectId> = have.iter().flat_map(|id| repo.ancestors(id)).collect();
+    let mut missing = HashSet::new();
+    for tip in repo.branches().values().chain(repo.tags().values()) {
+        missing.extend(repo.ancestors(tip).into_iter().filter(|id| !known.contains(id)));
+    }
+    missing.into_iter().collect()
+}
+
+/// Object count and bytes a client would download for these commits
+fn estimate_graph(repo: &HostedRepo, commits: &[ObjectId]) -> VnpCommand {
+    let mut objects = HashSet::new();
+    for commit in commits.iter().filter(|id| repo.has_object(id)) {
+        objects.insert(commit.clone());
+        objects.extend(repo.snapshot_objects(commit));
+    }
+    let bytes = objects.iter().map(|id| repo.object_size(id)).sum();
+    VnpCommand::GraphEstimate { objects: objects.len() as u64, bytes }
+}
+
+/// What a pushed object turns out to be, which decides what it references
+enum Kind {
+    /// One of the pushed IDs: a commit, or the Tag object of an annotated tag
+    Pushed,
+    Commit,
+    Tree,
+    File,
+    Chunk,
+}
+
+/// Receives the objects of pushed commits that the server lacks, asking for each with
+/// SendObject. Objects are held in a quarantine directory and only enter the store once
+/// the whole graph has arrived and verified, so an object in the store always has its
+/// history and snapshot too. Returns the number of objects stored, or the message to
+/// send back as Error.
+async fn receive_push(
+    repo: &HostedRepo,
+    reader: &mut VnpReader,
+    writer: &mut VnpWriter,
+    commits: Vec<ObjectId>,
+    limits: &PushLimits,
+) -> io::Result<Result<usize, String>> {
+    let quarantine = repo.quarantine_dir(&format!("{:016x}", rand_core::OsRng.next_u64()));
+    std::fs::create_dir_all(&quarantine)?;
+    let result = receive_objects(repo, reader, writer, commits, &quarantine, limits).await;
+    let _ = std::fs::remove_dir_all(&quarantine);
+    result
+}
+
+async fn receive_objects(
+    repo: &HostedRepo,
+    reader: &mut VnpReader,
+    writer: &mut VnpWriter,
+    commits: Vec<ObjectId>,
+    quarantine: &Path,
+    limits: &PushLimits,
+) -> io::Result<Result<usize, String>> {
+    let mut queue: VecDeque<(ObjectId, Kind)> = commits.into_iter().map(|id| (id, Kind::Pushed)).collect();
+    let mut seen = HashSet::new();
+    let mut received = Vec::new();
+    let mut received_bytes = 0u64;
+
+    while let Some((id, kind)) = queue.pop_front() {
+        if !hosted::is_object_id(&id) {
+            return Ok(Err(format!("invalid object id: {}", id)));
+        }
+        if repo.has_object(&id) || !seen.insert(id.clone()) {
+            continue;
+        }
+
+        vnp::send_command(writer, VnpCommand::SendObject(id.clone())).await?;
+        let size = match vnp::recv_command(reader).await? {
+            VnpCommand::ObjectHeader { id: sent, size, .. } if sent == id => size,
+            VnpCommand::ObjectHeader { id: sent, .. } => return Ok(Err(format!("asked for {} but received {}", id, sent))),
+            VnpCommand::Abort(reason) => return Ok(Err(format!("push aborted: {}", reason))),
+            other => return Ok(Err(format!("expected ObjectHeader, got {:?}", other))),
+        };
+        // Limits are checked against the announced size, before any of the payload is stored
+        if let Some(max) = limits.max_object_size.filter(|&max| size > max) {
+            return Ok(Err(format!(
+                "object {} is {}, over the server's {} object size limit",
+                &id[..7], transfer::format_bytes(size), transfer::format_bytes(max)
+            )));
+        }
+        received_bytes += size;
+        if let Some((left, quota)) = limits.headroom.as_ref().filter(|(left, _)| received_bytes > *left) {
+            return Ok(Err(format!(
+                "push exceeds {} (at least {} to store, {} left)",
+                quota, transfer::format_bytes(received_bytes), transfer::format_bytes(*left)
+            )));
+        }
+        let path = quarantine.join(&id);
+        let mut file = tokio::fs::File::create(&path).await?;
+        // Objects are content-addressed, so the payload must hash to the requested ID
+        if vnp::recv_object_to_file(reader, size, &mut file).await? != id {
+            return Ok(Err(format!("object {} does not match its content", id)));
+        }
+
+        let invalid = |what: &str| format!("object {} is not a valid {}", id, what);
+        match kind {
+            Kind::Pushed | Kind::Commit => {
+                let tag = matches!(kind, Kind::Pushed).then(|| hosted::load_typed::<Tag>(&path)).flatten();
+                if let Some(tag) = tag {
+                    queue.push_back((tag.object, Kind::Commit));
+                } else {
+                    let Some(commit) = hosted::load_typed::<Commit>(&path) else {
+                        return Ok(Err(invalid("commit")));
+                    };
+                    queue.push_back((commit.tree, Kind::Tree));
+                    queue.extend(commit.parents.into_iter().map(|parent| (parent, Kind::Commit)));
+                }
+            }
+            Kind::Tree => {
+                let Some(directory) = hosted::load_typed::<Directory>(&path) else {
+                    return Ok(Err(invalid("tree")));
+                };
+                queue.extend(directory.entries.into_iter().map(|entry| {
+                    let kind = if entry.mode == 0o040000 { Kind::Tree } else { Kind::File };
+                    (entry.id, kind)
+                }));
+            }
+            Kind::File => {
+                let Some(file) = hosted::load_typed::<File>(&path) else {
+                    return Ok(Err(invalid("file")));
+                };
+                queue.extend(file.chunk_ids().iter().map(|chunk| (chunk.clone(), Kind::Chunk)));
+            }
+            Kind::Chunk => {}
+        }
+        received.push((id, path));
+    }
+
+    // Objects arrive referrer-first, so promote in reverse: by the time a commit is
+    // visible, what it references is already in the store
+    for (id, path) in received.iter().rev() {
+        repo.promote(id, path)?;
+    }
+    Ok(Ok(received.len()))
+}
+
+/// What a single push may store
+struct PushLimits {
+    max_object_size: Option<u64>,
+    /// Bytes left under the tightest quota, with its description
+    headroom: Option<(u64, String)>,
+}
+
+/// Validates UpdateRef and returns the change to apply: `new` must be stored, `old` (if
+/// given) must still be the tip, and without `force` the move must be a fast-forward
+fn branch_change(repo: &HostedRepo, branch: &str, old: Option<&str>, new: &str, force: bool) -> Result<RefChange, String> {
+    if !hosted::is_ref_name(branch) {
+        return Err(format!("invalid branch name '{}'", branch));
+    }
+    if !hosted::is_object_id(new) || repo.load_commit(new).is_none() {
+        return Err(format!("commit {} has not been pushed", new));
+    }
+    let current = repo.read_ref(&refs::branch_ref(branch));
+    if old.is_some() && old != current.as_deref() {
+        return Err(format!("'{}' changed on the server since it was read; fetch and try again", branch));
+    }
+    if let Some(current) = &current {
+        if !force && !repo.is_ancestor(current, new) {
+            return Err(format!("non-fast-forward update of '{}' (the server has commits you do not)", branch));
+        }
+    }
+    Ok(RefChange { name: refs::branch_ref(branch), old: current, new: Some(new.to_string()) })
+}
+
+/// Validates PushTags and returns the tag changes; none apply unless every tag is acceptable.
+/// An annotation must be a stored Tag object for the commit its tag points to.
+fn tag_changes(
+    repo: &HostedRepo,
+    tags: &std::collections::BTreeMap<String, ObjectId>,
+    annotations: &std::collections::BTreeMap<String, ObjectId>,
+    force: bool,
+) -> Result<Vec<RefChange>, String> {
+    let mut changes = Vec::new();
+    for (name, id) in tags {
+        if !hosted::is_ref_name(name) {
+            return Err(format!("invalid tag name '{}'", name));
+        }
+        if !repo.has_object(id) {
+            return Err(format!("tag '{}' points to {}, which has not been pushed", name, id));
+        }
+        let existing = repo.read_ref(&refs::tag_ref(name));
+        if existing.as_ref().is_some_and(|existing| existing != id) && !force {
+            return Err(format!("tag '{}' already exists on the server", name));
+        }
+        changes.push(RefChange { name: refs::tag_ref(name), old: existing, new: Some(id.clone()) });
+    }
+    for (name, id) in annotations {
+        let target = tags.get(name).cloned().or_else(|| repo.read_ref(&refs::tag_ref(name)));
+        let tag = hosted::is_object_id(id).then(|| repo.read_object(id).ok()).flatten()
+            .and_then(|data| serde_json::from_slice::<Tag>(&data).ok());
+        match (target, tag) {
+            (Some(target), Some(tag)) if tag.object == target && tag.tag == *name => {}
+            (None, _) => return Err(format!("annotation of '{}' has no tag", name)),
+            _ => return Err(format!("annotation of '{}' is not a pushed Tag object for its commit", name)),
+        }
+        let existing = repo.read_ref(&refs::annotation_ref(name));
+        if existing.as_ref().is_some_and(|existing| existing != id) && !force {
+            return Err(format!("tag '{}' already exists on the server", name));
+        }
+        changes.push(RefChange { name: refs::annotation_ref(name), old: existing, new: Some(id.clone()) });
+    }
+    Ok(changes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+    use orbit_core::vos::ObjectStore;
+
+    #[tokio::test]
+    async fn test_pushed_annotation_is_advertised_for_clones() {
+        let dir = std::env::temp_dir().join(format!("orb-server-annotation-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let local = ObjectStore::new(dir.join("local"));
+        let tree = local.save_json(&Directory { entries: Vec::new() }).unwrap();
+        let commit = Commit { tree, parents: Vec::new(), author: "a".into(), timestamp: 0, message: "first".into(), signature: None };
+        let commit = local.save_json(&commit).unwrap();
+        let tag = Tag { object: commit.clone(), tag: "v1.0".into(), tagger: "a".into(), timestamp: 0, message: "Release 1.0".into(), signature: None };
+        let tag = local.save_json(&tag).unwrap();
+        let repo = HostedRepo::open_or_init(&dir.join("hosted"), "file").unwrap();
+
+        // The client pushes only the Tag object; the server walks on to its commit
+        let (client, server) = tokio::io::duplex(1 << 20);
+        let (client_read, client_write) = tokio::io::split(client);
+        let (server_read, server_write) = tokio::io::split(server);
+        let client = tokio::spawn(async move {
+            let (mut reader, mut writer) = (client_read, client_write);
+            while let Ok(VnpCommand::SendObject(id)) = vnp::recv_command(&mut reader).await {
+                vnp::send_object_bytes(&mut writer, &id, &local.load(&id).unwrap()).await.unwrap();
+            }
+        });
+        let (mut reader, mut writer): (VnpReader, VnpWriter) = (Box::new(server_read), Box::new(server_write));
+        let limits = PushLimits { max_object_size: None, headroom: None };
+        assert_eq!(receive_push(&repo, &mut reader, &mut writer, vec![tag.clone()], &limits).await.unwrap(), Ok(3));
+        drop((reader, writer));
+        client.await.unwrap();
+
+        let tags = BTreeMap::from([("v1.0".to_string(), commit.clone())]);
+        let wrong = BTreeMap::from([("v1.0".to_string(), commit.clone())]);
+        assert!(tag_changes(&repo, &tags, &wrong, false).is_err());
+        let annotations = BTreeMap::from([("v1.0".to_string(), tag.clone())]);
+        for change in tag_changes(&repo, &tags, &annotations, false).unwrap() {
+            repo.write_ref(&change.name, change.new.as_deref().unwrap()).unwrap();
+        }
+
+        // A clone finds the annotation in the advertisement and can fetch it
+        assert_eq!(repo.tags(), tags);
+        assert_eq!(repo.annotations(), annotations);
+        let fetched: Tag = serde_json::from_slice(&repo.read_object(&tag).unwrap()).unwrap();
+        assert_eq!((fetched.object, fetched.message.as_str()), (commit, "Release 1.0"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,235 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
+use rustls::server::{ClientHello, ResolvesServerCert};
+use rustls::sign::CertifiedKey;
+use tokio_rustls::TlsAcceptor;
+use crate::{cert, vnp};
+use tracing::{info, warn};
+
+/// How often the certificate files are checked for changes
+const POLL: Duration = Duration::from_secs(10);
+
+/// The certificates `orb serve --tls` presents: `cert.pem`/`key.pem` in the config
+/// directory, plus one pair per `certs/<name>/` subdirectory, picked by the SNI name the
+/// client asks for. Reloading swaps them for new handshakes; open connections keep theirs.
+#[derive(Debug)]
+pub struct Certificates {
+    dir: PathBuf,
+    loaded: RwLock<Loaded>,
+}
+
+#[derive(Debug, Default)]
+struct Loaded {
+    /// Presented when no other certificate matches, or the client sends no SNI
+    default: Option<Arc<CertifiedKey>>,
+    /// By DNS name from the certificates' subject alternative names; `*.` names are wildcards
+    by_name: HashMap<String, Arc<CertifiedKey>>,
+    /// Modification times and sizes of the files loaded, to notice changes
+    fingerprint: Vec<(PathBuf, Option<SystemTime>, u64)>,
+}
+
+impl Certificates {
+    pub fn load(dir: &Path) -> Result<Arc<Self>, String> {
+        let certificates = Arc::new(Self { dir: dir.to_path_buf(), loaded: RwLock::new(Loaded::default()) });
+        certificates.reload()?;
+        Ok(certificates)
+    }
+
+    /// DNS names the loaded certificates cover
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.loaded.read().unwrap().by_name.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    /// A TLS acceptor resolving certificates through this set, offering VNP and HTTP/1.1
+    pub fn acceptor(self: &Arc<Self>) -> TlsAcceptor {
+        let mut config = rustls::ServerConfig::builder().with_no_client_auth().with_cert_resolver(self.clone());
+        config.alpn_protocols = vec![vnp::VNP_ALPN.to_vec(), b"http/1.1".to_vec()];
+        TlsAcceptor::from(Arc::new(config))
+    }
+
+    /// Loads every certificate again. On error the current ones stay in use.
+    pub fn reload(&self) -> Result<Vec<String>, String> {
+        let pairs = pairs(&self.dir);
+        let mut loaded = Loaded { fingerprint: fingerprint(&pairs), ..Loaded::default() };
+        let mut names = Vec::new();
+        for (cert_path, key_path) in &pairs {
+            let (key, hosts) = load_pair(cert_path, key_path)?;
+            // The main pair is the default; without it, the first in `certs/` is
+            if loaded.default.is_none() {
+                loaded.default = Some(key.clone());
+            }
+            for host in hosts {
+                names.push(host.clone());
+                loaded.by_name.entry(host).or_insert_with(|| key.clone());
+            }
+        }
+        if loaded.default.is_none() {
+            let (cert_path, _) = cert::paths(&self.dir);
+            return Err(format!(
+                "cannot read {} (create a certificate with `orb cert <hostname>` or pass --dir)",
+                cert_path.display()
+            ));
+        }
+        *self.loaded.write().unwrap() = loaded;
+        Ok(names)
+    }
+
+    /// Reloads whenever a certificate file changes (or, on Unix, on SIGHUP) until the
+    /// server stops
+    pub async fn watch(self: Arc<Self>) {
+        #[cfg(unix)]
+        let mut hangup = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup()).ok();
+        loop {
+            #[cfg(unix)]
+            let forced = match &mut hangup {
+                Some(hangup) => tokio::select! {
+                    _ = hangup.recv() => true,
+                    _ = tokio::time::sleep(POLL) => false,
+                },
+                None => {
+                    tokio::time::sleep(POLL).await;
+                    false
+                }
+            };
+            #[cfg(not(unix))]
+            let forced = {
+                tokio::time::sleep(POLL).await;
+                false
+            };
+
+            let changed = fingerprint(&pairs(&self.dir)) != self.loaded.read().unwrap().fingerprint;
+            if !forced && !changed {
+                continue;
+            }
+            match self.reload() {
+                Ok(names) => info!("🔏 Reloaded TLS certificates ({})", describe(&names)),
+                Err(e) => {
+                    warn!("⚠️  Keeping the current TLS certificates: {}", e);
+                    // Remember the broken files so the error is not repeated every poll
+                    self.loaded.write().unwrap().fingerprint = fingerprint(&pairs(&self.dir));
+                }
+            }
+        }
+    }
+}
+
+impl ResolvesServerCert for Certificates {
+    fn resolve(&self, client_hello: ClientHello) -> Option<Arc<CertifiedKey>> {
+        self.loaded.read().unwrap().select(client_hello.server_name())
+    }
+}
+
+impl Loaded {
+    /// The certificate for an SNI name: an exact match, then a wildcard, then the default
+    fn select(&self, name: Option<&str>) -> Option<Arc<CertifiedKey>> {
+        let Some(name) = name.map(str::to_ascii_lowercase) else {
+            return self.default.clone();
+        };
+        self.by_name.get(&name)
+            .or_else(|| name.split_once('.').and_then(|(_, parent)| self.by_name.get(&format!("*.{}", parent))))
+            .or(self.default.as_ref())
+            .cloned()
+    }
+}
+
+/// Certificate and key paths: the main pair first, then `certs/<name>/`, sorted by name.
+/// A missing main pair is left out.
+fn pairs(dir: &Path) -> Vec<(PathBuf, PathBuf)> {
+    let (cert_path, key_path) = cert::paths(dir);
+    let mut pairs = Vec::new();
+    if cert_path.exists() || key_path.exists() {
+        pairs.push((cert_path, key_path));
+    }
+    if let Ok(entries) = std::fs::read_dir(dir.join("certs")) {
+        let mut extra: Vec<_> = entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.is_dir())
+            .map(|path| cert::paths(&path))
+            .collect();
+        extra.sort();
+        pairs.extend(extra);
+    }
+    pairs
+}
+
+fn fingerprint(pairs: &[(PathBuf, PathBuf)]) -> Vec<(PathBuf, Option<SystemTime>, u64)> {
+    pairs
+        .iter()
+        .flat_map(|(cert_path, key_path)| [cert_path, key_path])
+        .map(|path| {
+            let metadata = std::fs::metadata(path).ok();
+            let modified = metadata.as_ref().and_then(|m| m.modified().ok());
+            (path.clone(), modified, metadata.map_or(0, |m| m.len()))
+        })
+        .collect()
+}
+
+/// Parses a certificate chain and its key, returning them with the DNS names the
+/// certificate covers
+fn load_pair(cert_path: &Path, key_path: &Path) -> Result<(Arc<CertifiedKey>, Vec<String>), String> {
+    let read = |path: &Path| std::fs::read(path).map_err(|e| format!("cannot read {}: {}", path.display(), e));
+    let (cert_pem, key_pem) = (read(cert_path)?, read(key_path)?);
+    let chain = rustls_pemfile::certs(&mut cert_pem.as_slice())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("{}: {}", cert_path.display(), e))?;
+    let first = chain.first().ok_or_else(|| format!("{} contains no certificate", cert_path.display()))?;
+    let hosts = hostnames(first);
+    let key = rustls_pemfile::private_key(&mut key_pem.as_slice())
+        .map_err(|e| format!("{}: {}", key_path.display(), e))?
+        .ok_or_else(|| format!("{} contains no private key", key_path.display()))?;
+    let key = rustls::crypto::ring::sign::any_supported_type(&key).map_err(|e| format!("{}: {}", key_path.display(), e))?;
+    Ok((Arc::new(CertifiedKey::new(chain, key)), hosts))
+}
+
+/// DNS names in a certificate's subject alternative names, lowercased
+fn hostnames(der: &[u8]) -> Vec<String> {
+    let Ok((_, parsed)) = x509_parser::parse_x509_certificate(der) else { return Vec::new() };
+    let Ok(Some(names)) = parsed.subject_alternative_name() else { return Vec::new() };
+    names
+        .value
+        .general_names
+        .iter()
+        .filter_map(|name| match name {
+            x509_parser::extensions::GeneralName::DNSName(name) => Some(name.to_ascii_lowercase()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// `a.com, b.com` or `no DNS names`, for log lines
+pub fn describe(names: &[String]) -> String {
+    if names.is_empty() {
+        "no DNS names".to_string()
+    } else {
+        names.join(", ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_certificates_are_selected_by_sni() {
+        let dir = std::env::temp_dir().join(format!("orb-server-tls-test-{}", std::process::id()));
+        cert::generate(&["git.example.com".to_string()], &dir, true).unwrap();
+        cert::generate(&["*.example.org".to_string()], &dir.join("certs").join("org"), true).unwrap();
+        let certificates = Certificates::load(&dir).unwrap();
+        let loaded = certificates.loaded.read().unwrap();
+        let chosen = |name| loaded.select(name).unwrap().cert[0].clone();
+
+        let default = loaded.default.as_ref().unwrap().cert[0].clone();
+        assert_eq!(chosen(Some("git.example.com")), default);
+        assert_eq!(chosen(None), default);
+        assert_eq!(chosen(Some("unknown.net")), default);
+        assert_ne!(chosen(Some("code.example.org")), default);
+        assert_eq!(chosen(Some("a.b.example.org")), default);
+        drop(loaded);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,135 @@
+use std::collections::HashSet;
+use std::net::SocketAddr;
+use tokio::net::{TcpListener, TcpStream};
+use crate::objects::ObjectId;
+use crate::vnp::{self, RefAdvertisement, RepositoryInfo, VnpCommand};
+use crate::transport::{VnpReader, VnpWriter};
+use crate::{ancestry, hosted, refs, secure_channel, vos};
+use tracing::{info, warn};
+
+/// Port `orb share` listens on and `peer://` URLs default to
+pub const DEFAULT_PORT: u16 = 8090;
+
+/// Serves the current repository read-only to other working copies on the network
+/// (`orb share`). Peers connect with `orb sync peer://<host>` or `orb clone peer://<host>`.
+pub async fn run(port: u16) -> Result<(), Box<dyn std::error::Error>> {
+    if !std::path::Path::new(".orb").exists() {
+        return Err("Not an Orbit repository (run 'orb init' first)".into());
+    }
+
+    let listener = TcpListener::bind(("0.0.0.0", port)).await?;
+    info!("📡 Sharing '{}' read-only on port {}", share_name(), port);
+    info!("💡 On another machine: orb sync peer://<this-host>:{}", port);
+    info!("   Press Ctrl-C to stop sharing.");
+
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => {
+                let (stream, addr) = accepted?;
+                info!("🤝 Peer connected: {}", addr);
+                tokio::spawn(async move {
+                    match serve_peer(stream).await {
+                        Ok(()) => info!("👋 Peer disconnected: {}", addr),
+                        Err(e) => warn!("⚠️  Session with {} ended: {}", addr, e),
+                    }
+                });
+            }
+            _ = tokio::signal::ctrl_c() => {
+                info!("");
+                info!("🛑 Stopped sharing");
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Answers one peer's VNP requests until it disconnects or aborts
+async fn serve_peer(stream: TcpStream) -> std::io::Result<()> {
+    let peer: Option<SocketAddr> = stream.peer_addr().ok();
+    let (reader, writer) = stream.into_split();
+    let (mut reader, mut writer): (VnpReader, VnpWriter) = (Box::new(reader), Box::new(writer));
+
+    loop {
+        let command = match vnp::recv_request(&mut reader, &mut writer).await {
+            Ok(command) => command,
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(()),
+            Err(e) => return Err(e),
+        };
+
+        let reply = match command {
+            VnpCommand::SecureHello { x25519, ml_kem } => {
+                (reader, writer) = secure_channel::accept(reader, writer, &x25519, &ml_kem).await?;
+                continue;
+            }
+            // Peers never need an account; every session is read-only
+            VnpCommand::Authenticate(_) | VnpCommand::AuthenticateAnonymous => VnpCommand::AuthResult {
+                success: true,
+                message: "peer share (read-only)".to_string(),
+                capabilities: vec![vnp::CAP_READ.to_string()],
+            },
+            VnpCommand::ListRepositories => VnpCommand::RepositoryList(vec![RepositoryInfo {
+                name: share_name(),
+                default_branch: Some(refs::current_branch()),
+                head: refs::head_commit(),
+                ..RepositoryInfo::default()
+            }]),
+            // A share holds exactly one repository, so any name selects it
+            VnpCommand::SelectRepository(name) => VnpCommand::RepositorySelected(name),
+            VnpCommand::ListRefs => VnpCommand::RefList(RefAdvertisement {
+                branches: refs::list_branches(),
+                tags: refs::list_tags(),
+                annotations: refs::list_annotations(),
+            }),
+            VnpCommand::Have(have) => {
+                let walk = tokio::task::spawn_blocking(move || missing_commits(&have));
+                VnpCommand::Want(vnp::with_keepalive(&mut writer, walk).await?.map_err(std::io::Error::other)?)
+            }
+            VnpCommand::Get(id) | VnpCommand::GetTree(id) | VnpCommand::GetFile(id) => {
+                if !hosted::is_object_id(&id) {
+                    VnpCommand::Error(format!("invalid object id: {}", id))
+                } else {
+                    vnp::send_stored_object(&mut writer, &id, &vos::object_path(&id)?).await?;
+                    continue;
+                }
+            }
+            VnpCommand::Ready => VnpCommand::Ok,
+            VnpCommand::Abort(reason) => {
+                info!("🛑 Peer {} aborted: {}", peer.map(|p| p.to_string()).unwrap_or_default(), reason);
+                return Ok(());
+            }
+            VnpCommand::Push(_)
+            | VnpCommand::UpdateRef { .. }
+            | VnpCommand::PushTags { .. }
+            | VnpCommand::CreateRepository(_)
+            | VnpCommand::DeleteRepository(_)
+            | VnpCommand::RenameRepository { .. } => {
+                VnpCommand::Error("this peer shares its repository read-only".to_string())
+            }
+            other => VnpCommand::Error(format!("unsupported by peer share: {:?}", other)),
+        };
+        vnp::send_command(&mut writer, reply).await?;
+    }
+}
+
+/// Commits reachable from our branches and tags that the peer does not have
+fn missing_commits(have: &[ObjectId]) -> Vec<ObjectId> {
+    let known: HashSet<ObjectId> = have
+        .iter()
+        .filter(|id| ancestry::load_commit(id).is_some())
+        .flat_map(|id| ancestry::ancestors(id))
+        .collect();
+
+    let mut missing = HashSet::new();
+    for tip in refs::list_branches().values().chain(refs::list_tags().values()) {
+        missing.extend(ancestry::ancestors(tip).into_iter().filter(|id| !known.contains(id)));
+    }
+    missing.into_iter().collect()
+}
+
+/// Name the shared repository is advertised under (the working copy's directory name)
+fn share_name() -> String {
+    std::env::current_dir()
+        .ok()
+        .and_then(|dir| dir.file_name().map(|name| name.to_string_lossy().to_string()))
+        .unwrap_or_else(|| "orbit".to_string())
+}
@@ -0,0 +1,207 @@
+use std::collections::BTreeSet;
+use std::error::Error;
+use std::fs;
+use std::io::{self, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::{info, warn};
+use orbit_core::index::{StagedFile, VosIndex};
+use orbit_core::{FileMap, FileStatus, Repository};
+use crate::objects::{Commit, ObjectId};
+use crate::repo::{self, RepoError};
+use crate::{merge, refs, style, vos};
+
+/// File in `.orb` listing stash commits, newest first. Stashes live outside `refs/`,
+/// so they are never synced or shown as refs.
+const STASH_FILE: &str = "stash";
+
+fn load_stack(repository: &Repository) -> Vec<ObjectId> {
+    fs::read_to_string(repository.orb_dir().join(STASH_FILE))
+        .unwrap_or_default()
+        .lines()
+        .filter(|line| !line.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+fn save_stack(repository: &Repository, stack: &[ObjectId]) -> io::Result<()> {
+    let path = repository.orb_dir().join(STASH_FILE);
+    match stack.is_empty() {
+        true => fs::remove_file(path).or_else(|e| if e.kind() == io::ErrorKind::NotFound { Ok(()) } else { Err(e) }),
+        false => fs::write(path, stack.iter().map(|id| format!("{}\n", id)).collect::<String>()),
+    }
+}
+
+fn save_commit(tree: ObjectId, parents: Vec<ObjectId>, message: String) -> Result<ObjectId, Box<dyn Error>> {
+    let commit = Commit {
+        tree,
+        parents,
+        author: repo::DEFAULT_AUTHOR.to_string(),
+        timestamp: SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |now| now.as_secs() as i64),
+        message,
+        signature: None,
+    };
+    Ok(vos::store().save_json(&commit)?)
+}
+
+/// `orb stash push`: saves the changes to tracked files, and the staged changes, as a
+/// stash commit whose first parent is HEAD (and whose second, when anything was staged,
+/// is a commit of HEAD plus the staged changes). The working tree and index are then
+/// reset to HEAD. Untracked files that were not staged are left alone.
+pub fn push(message: Option<&str>) -> Result<(), Box<dyn Error>> {
+    let repository = repo::open_worktree()?;
+    repo::ensure_idle(&repository)?;
+    let head = refs::head_commit().ok_or(RepoError::NoCommits)?;
+    let head_commit = repository.load_commit(&head)?;
+    let head_files = repository.commit_files(&head)?;
+    let index = repository.index()?;
+
+    let mut worktree = head_files.clone();
+    let mut changes = 0;
+    for change in repository.status()? {
+        match change.status {
+            FileStatus::Modified => worktree.insert(change.path.clone(), repo::store_file(&repository, &change.path)?),
+            FileStatus::Deleted => worktree.remove(&change.path),
+            FileStatus::Untracked => continue,
+        };
+        changes += 1;
+    }
+    let mut staged = head_files.clone();
+    let mut added = Vec::new();
+    for (path, file) in &index.staged {
+        match file {
+            Some(file) => staged.insert(path.clone(), (file.mode, file.file_id.clone())),
+            None => staged.remove(path),
+        };
+        if !head_files.contains_key(path) && fs::symlink_metadata(repository.root().join(path)).is_ok() {
+            worktree.insert(path.clone(), repo::store_file(&repository, path)?);
+            added.push(path);
+        }
+    }
+    if changes == 0 && index.staged.is_empty() {
+        info!("✅ No local changes to stash");
+        return Ok(());
+    }
+
+    let branch = repository.current_branch();
+    let subject = head_commit.message.lines().next().unwrap_or_default();
+    let message = match message {
+        Some(message) => format!("On {}: {}", branch, message),
+        None => format!("WIP on {}: {} {}", branch, &head[..7.min(head.len())], subject),
+    };
+    let mut parents = vec![head.clone()];
+    if !index.staged.is_empty() {
+        let index_message = format!("index on {}: {} {}", branch, &head[..7.min(head.len())], subject);
+        parents.push(save_commit(merge::build_tree(&staged)?, vec![head.clone()], index_message)?);
+    }
+    let stash = save_commit(merge::build_tree(&worktree)?, parents, message.clone())?;
+    let mut stack = load_stack(&repository);
+    stack.insert(0, stash);
+    save_stack(&repository, &stack)?;
+
+    repository.replace_worktree(&head_commit.tree)?;
+    for path in added {
+        fs::remove_file(repository.root().join(path))?;
+    }
+    let stashed = head_files
+        .keys()
+        .chain(worktree.keys())
+        .chain(staged.keys())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .filter(|path| worktree.get(*path) != head_files.get(*path) || staged.get(*path) != head_files.get(*path))
+        .count();
+    info!("📥 Stashed {} changed path(s) as stash@{{0}}: {}", stashed, message);
+    Ok(())
+}
+
+/// `orb stash pop` / `orb stash apply`: restores the files the stash changed and its
+/// staged changes, then (unless `keep`) drops it. Refuses, keeping the stash, when a
+/// path it changed has local changes or was changed differently by commits since.
+pub fn pop(entry: usize, keep: bool) -> Result<(), Box<dyn Error>> {
+    let repository = repo::open_worktree()?;
+    let mut stack = load_stack(&repository);
+    let id = stack.get(entry).cloned().ok_or(RepoError::NoSuchStash(entry))?;
+    let stash = repository.load_commit(&id)?;
+    let base = repository.commit_files(&stash.parents[0])?;
+    let files = repository.tree_files(&stash.tree)?;
+    let head_files = match refs::head_commit() {
+        Some(head) => repository.commit_files(&head)?,
+        None => FileMap::new(),
+    };
+
+    let changed: BTreeSet<&String> = base.keys().chain(files.keys()).filter(|path| base.get(*path) != files.get(*path)).collect();
+    let moved: Vec<&&String> = changed
+        .iter()
+        .filter(|path| head_files.get(**path) != base.get(**path) && head_files.get(**path) != files.get(**path))
+        .collect();
+    for path in &moved {
+        warn!("  changed since the stash: {}", path);
+    }
+    if !moved.is_empty() {
+        return Err(RepoError::StashConflicts(moved.len()).into());
+    }
+    let dirty: Vec<_> = repository.status()?.into_iter().filter(|change| changed.contains(&change.path)).collect();
+    for change in &dirty {
+        warn!("  {}: {}", format!("{:?}", change.status).to_lowercase(), change.path);
+    }
+    if !dirty.is_empty() {
+        return Err(RepoError::UncommittedChanges(dirty.len()).into());
+    }
+
+    for path in &changed {
+        match files.get(*path) {
+            Some((mode, file_id)) => {
+                repository.write_file(path, *mode, file_id)?;
+            }
+            None => fs::remove_file(repository.root().join(path))?,
+        }
+    }
+    if let Some(index_commit) = stash.parents.get(1) {
+        let staged = repository.commit_files(index_commit)?;
+        let mut index = repository.index()?;
+        for path in base.keys().chain(staged.keys()).filter(|path| base.get(*path) != staged.get(*path)) {
+            let file = staged.get(path).map(|(mode, file_id)| {
+                // Metadata only describes the file if the working tree holds the staged version
+                let (mtime, size) = match files.get(path) == staged.get(path) {
+                    true => VosIndex::get_file_metadata(&repository.root().join(path)).unwrap_or((0, 0)),
+                    false => (0, 0),
+                };
+                StagedFile { mode: *mode, mtime, size, file_id: file_id.clone() }
+            });
+            index.staged.insert(path.clone(), file);
+        }
+        repository.save_index(&index)?;
+    }
+
+    info!("📤 Restored stash@{{{}}}: {}", entry, stash.message);
+    if !keep {
+        stack.remove(entry);
+        save_stack(&repository, &stack)?;
+        info!("   Dropped stash@{{{}}} ({})", entry, style::id(&id[..7]));
+    }
+    Ok(())
+}
+
+/// `orb stash list`: one line per stash, newest (stash@{0}) first
+pub fn list() -> Result<(), Box<dyn Error>> {
+    let repository = repo::open()?;
+    let mut out = io::stdout().lock();
+    for (entry, id) in load_stack(&repository).iter().enumerate() {
+        let message = repository.load_commit(id).map(|commit| commit.message).unwrap_or_default();
+        writeln!(out, "stash@{{{}}}: {}", entry, message.lines().next().unwrap_or_default())?;
+    }
+    Ok(())
+}
+
+/// `orb stash drop`: deletes a stash without applying it
+pub fn drop(entry: usize) -> Result<(), Box<dyn Error>> {
+    let repository = repo::open()?;
+    let mut stack = load_stack(&repository);
+    if entry >= stack.len() {
+        return Err(RepoError::NoSuchStash(entry).into());
+    }
+    let id = stack.remove(entry);
+    save_stack(&repository, &stack)?;
+    info!("🗑️  Dropped stash@{{{}}} ({})", entry, style::id(&id[..7]));
+    Ok(())
+}
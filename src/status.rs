@@ -3,104 +3,115 @@ use std::path::Path;
 use std::collections::HashMap;
 use crate::objects::{ObjectId, Commit, Directory}; // DirectoryEntry for future use
 use crate::vos;
-// use crate::repo; // TODO: May be needed for advanced status operations
-use crate::index::VosIndex;
+use orbit_core::{Change, FileStatus};
+use crate::{ancestry, refs, remote, repo};
+use crate::style::{self, Color};
 
-/// Represents the status of a file in the working directory
-#[derive(Debug, PartialEq)]
-pub enum FileStatus {
-    Modified,
-    Untracked,
-    Deleted,
-}
 
 /// Fast status check using VOS Index for optimal performance
 pub fn check_status() -> Result<(), Box<dyn std::error::Error>> {
-    println!("\n🔍 Orbit Status (orb check) - v0.3 with Git Interop\n");
+    println!("{}", style::decorate("\n🔍 Orbit Status (orb check) - v0.3 with Git Interop\n"));
+    print_tracking_status();
     
     // 1. Load the VOS Index
-    let index = VosIndex::load()?;
-    
-    if index.entries.is_empty() {
-        println!("📝 Repository is empty (no commits yet)");
-        println!("\nTo create your first commit, use: orb save -m \"Initial commit\"");
-        return Ok(());
+    let repository = repo::open_worktree()?;
+    let index = repository.index()?;
+    if let Some(testing) = crate::bisect::current(&repository) {
+        println!("{}", style::decorate(&format!("🔎 Bisecting: the working tree holds {}", style::id(&testing[..7]))));
+        println!("   (mark it with \"orb bisect good\" or \"orb bisect bad\", or end with \"orb bisect reset\")");
+        println!();
     }
-
-    // 2. Fast scan: Check all indexed files for changes using metadata
-    let mut changes = Vec::new();
-    let mut files_needing_full_check = Vec::new();
-    
-    // Check tracked files
-    for (path, _entry) in &index.entries {
-        let file_path = Path::new(path);
-        
-        if !file_path.exists() {
-            // File was deleted
-            changes.push((path.clone(), FileStatus::Deleted));
-        } else {
-            // Quick metadata comparison
-            match index.has_file_changed(path, file_path) {
-                Ok(true) => {
-                    // Metadata changed, need full check
-                    files_needing_full_check.push(path.clone());
-                },
-                Ok(false) => {
-                    // File unchanged (metadata match) - no action needed
-                },
-                Err(_) => {
-                    // Error checking metadata, fallback to full check
-                    files_needing_full_check.push(path.clone());
-                }
-            }
-        }
+    if let Some((operation, id)) = crate::cherry_pick::in_progress(&repository) {
+        let (label, command) = match operation {
+            crate::cherry_pick::Operation::CherryPick => ("🍒 Cherry-picking", "cherry-pick"),
+            crate::cherry_pick::Operation::Revert => ("⏪ Reverting", "revert"),
+        };
+        println!("{}", style::decorate(&format!("{} {}", label, style::id(&id[..7]))));
+        println!("   (fix the conflicts and run \"orb {0} --continue\", or \"orb {0} --abort\")", command);
+        println!();
     }
-    
-    // 3. Full check only for files with changed metadata
-    for path in files_needing_full_check {
-        let file_path = Path::new(&path);
-        if file_path.exists() {
-            // Compute actual file hash and compare
-            let (current_file_id, _) = vos::chunk_and_save_file(file_path)?;
-            let index_entry = index.entries.get(&path).unwrap();
-            
-            if current_file_id != index_entry.file_id {
-                changes.push((path, FileStatus::Modified));
-            }
-            // If hashes match, file is actually unchanged despite metadata difference
+    if let Some(stopped) = crate::rebase::stopped_at(&repository) {
+        println!("{}", style::decorate(&format!("♻️  Rebasing: replaying {} stopped", style::id(&stopped[..7]))));
+        println!("   (fix the conflicts and run \"orb rebase --continue\", or \"orb rebase --abort\")");
+        println!();
+    }
+    if !index.conflicts.is_empty() {
+        println!("{}", style::decorate("⚔️  Unmerged paths (fix them, then run \"orb resolve <path>\"):"));
+        for path in &index.conflicts {
+            println!("   {}", style::paint(&format!("both modified: {}", path), Color::Red));
         }
+        println!();
     }
-    
-    // 4. Check for untracked files
-    let mut current_files = HashMap::new();
-    scan_working_directory_fast(Path::new("."), "", &mut current_files)?;
-    
-    for (path, _) in &current_files {
-        if !index.entries.contains_key(path) {
-            changes.push((path.clone(), FileStatus::Untracked));
+    if !index.staged.is_empty() {
+        println!("{}", style::decorate("📦 Staged for the next save:"));
+        for (path, staged) in &index.staged {
+            let (label, color) = match staged {
+                Some(_) if index.entries.contains_key(path) => ("modified:", Color::Green),
+                Some(_) => ("new file:", Color::Green),
+                None => ("deleted: ", Color::Red),
+            };
+            println!("   {}", style::paint(&format!("{}  {}", label, path), color));
         }
+        println!();
     }
-    
-    // 5. Display results
+    if index.entries.is_empty() && index.staged.is_empty() {
+        println!("{}", style::decorate("📝 Repository is empty (no commits yet)"));
+        println!("\nTo create your first commit, use: orb save -m \"Initial commit\"");
+        return Ok(());
+    }
+
+    // 2. Compare the working tree with it (staged paths with their staged version); only
+    // files with changed metadata are hashed
+    let changes = repository.unstaged_changes()?;
+    if changes.is_empty() && !index.staged.is_empty() {
+        println!("To save the staged changes, use: orb save -m \"<commit message>\"");
+        return Ok(());
+    }
+
+    // 3. Display results
     display_status_results(&changes)?;
     
     Ok(())
 }
 
-/// Reads the HEAD commit ID from .orb/refs/heads/main
-#[allow(dead_code)]
-fn read_head_commit_id() -> Result<ObjectId, Box<dyn std::error::Error>> {
-    let head_ref_path = Path::new(".orb").join("refs").join("heads").join("main");
-    
-    if !head_ref_path.exists() {
-        return Ok(String::new()); // Empty repository
+/// Reports how the current branch compares with its remote-tracking ref on the
+/// default remote (e.g. origin/main)
+fn print_tracking_status() {
+    let Ok(upstream_remote) = remote::Remote::resolve(None) else {
+        return;
+    };
+    let remote_name = upstream_remote.name.as_deref().unwrap_or(remote::DEFAULT_REMOTE);
+    let branch = upstream_remote.tracked_branch();
+    let tracking_ref = refs::remote_tracking_ref(remote_name, &branch);
+    let (Some(local), Some(upstream)) = (refs::head_commit(), refs::read_ref(&tracking_ref)) else {
+        return;
+    };
+    
+    let upstream_name = format!("{}/{}", remote_name, branch);
+    match ancestry::ahead_behind(&local, &upstream) {
+        (0, 0) => println!("{}", style::decorate(&format!("🌿 Your branch is up to date with '{}'.", upstream_name))),
+        (ahead, 0) => {
+            println!("{}", style::decorate(&format!("🌿 Your branch is ahead of '{}' by {}.", upstream_name, plural_commits(ahead))));
+            println!("   (use \"orb sync\" to publish your local commits)");
+        }
+        (0, behind) => {
+            println!("{}", style::decorate(&format!("🌿 Your branch is behind '{}' by {}.", upstream_name, plural_commits(behind))));
+            println!("   (use \"orb sync\" to update your local branch)");
+        }
+        (ahead, behind) => {
+            println!("{}", style::decorate(&format!("🌿 Your branch is ahead of '{}' by {}, behind by {}.", upstream_name, plural_commits(ahead), behind)));
+            println!("   (your branch and '{}' have diverged)", upstream_name);
+        }
+    }
+    println!();
+}
+
+fn plural_commits(count: usize) -> String {
+    if count == 1 {
+        "1 commit".to_string()
+    } else {
+        format!("{} commits", count)
     }
-    
-    let commit_id = fs::read_to_string(head_ref_path)?
-        .trim()
-        .to_string();
-    
-    Ok(commit_id)
 }
 
 /// Loads a commit object from the VOS store
@@ -122,14 +133,7 @@ fn load_directory_object(dir_id: &ObjectId) -> Result<Directory, Box<dyn std::er
 /// Loads raw object data from the VOS store by ID
 #[allow(dead_code)]
 fn load_object_data(object_id: &ObjectId) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
-    let (prefix, suffix) = object_id.split_at(2);
-    let object_path = Path::new(".orb")
-        .join("objects")
-        .join(prefix)
-        .join(suffix);
-    
-    let data = fs::read(object_path)?;
-    Ok(data)
+    Ok(vos::load_object(object_id)?)
 }
 
 /// Recursively builds a map of all tracked files and their object IDs
@@ -195,45 +199,10 @@ fn scan_working_directory(
     Ok(())
 }
 
-/// Fast working directory scan - only collects paths, no hashing
-fn scan_working_directory_fast(
-    path: &Path,
-    current_path: &str,
-    current_files: &mut HashMap<String, bool>,
-) -> Result<(), Box<dyn std::error::Error>> {
-    for entry in fs::read_dir(path)? {
-        let entry = entry?;
-        let entry_path = entry.path();
-        let file_name = entry_path.file_name().unwrap().to_string_lossy().to_string();
-        
-        // Skip .orb directory
-        if file_name == ".orb" {
-            continue;
-        }
-        
-        let full_path = if current_path.is_empty() {
-            file_name.clone()
-        } else {
-            format!("{}/{}", current_path, file_name)
-        };
-        
-        let metadata = fs::metadata(&entry_path)?;
-        
-        if metadata.is_dir() {
-            // Recurse into subdirectory
-            scan_working_directory_fast(&entry_path, &full_path, current_files)?;
-        } else if metadata.is_file() {
-            // Just record the path exists
-            current_files.insert(full_path, true);
-        }
-    }
-    Ok(())
-}
-
 /// Displays the status results in a user-friendly format
-fn display_status_results(changes: &[(String, FileStatus)]) -> Result<(), Box<dyn std::error::Error>> {
+fn display_status_results(changes: &[Change]) -> Result<(), Box<dyn std::error::Error>> {
     if changes.is_empty() {
-        println!("✅ Working directory is clean");
+        println!("{}", style::decorate("✅ Working directory is clean"));
         println!("   Nothing to commit, working tree clean");
         return Ok(());
     }
@@ -241,30 +210,30 @@ fn display_status_results(changes: &[(String, FileStatus)]) -> Result<(), Box<dy
     println!("## Changes in working directory:\n");
     
     // Group changes by status
-    let modified: Vec<_> = changes.iter().filter(|(_, status)| *status == FileStatus::Modified).collect();
-    let untracked: Vec<_> = changes.iter().filter(|(_, status)| *status == FileStatus::Untracked).collect();
-    let deleted: Vec<_> = changes.iter().filter(|(_, status)| *status == FileStatus::Deleted).collect();
+    let modified: Vec<_> = changes.iter().filter(|change| change.status == FileStatus::Modified).collect();
+    let untracked: Vec<_> = changes.iter().filter(|change| change.status == FileStatus::Untracked).collect();
+    let deleted: Vec<_> = changes.iter().filter(|change| change.status == FileStatus::Deleted).collect();
     
     if !modified.is_empty() {
-        println!("📝 Modified files:");
-        for (path, _) in modified {
-            println!("   modified:   {}", path);
+        println!("{}", style::decorate("📝 Modified files:"));
+        for change in modified {
+            println!("   {}", style::paint(&format!("modified:   {}", change.path), Color::Yellow));
         }
         println!();
     }
     
     if !untracked.is_empty() {
-        println!("❓ Untracked files:");
-        for (path, _) in untracked {
-            println!("   untracked:  {}", path);
+        println!("{}", style::decorate("❓ Untracked files:"));
+        for change in untracked {
+            println!("   {}", style::paint(&format!("untracked:  {}", change.path), Color::Green));
         }
         println!();
     }
     
     if !deleted.is_empty() {
-        println!("🗑️  Deleted files:");
-        for (path, _) in deleted {
-            println!("   deleted:    {}", path);
+        println!("{}", style::decorate("🗑️  Deleted files:"));
+        for change in deleted {
+            println!("   {}", style::paint(&format!("deleted:    {}", change.path), Color::Red));
         }
         println!();
     }
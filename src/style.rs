@@ -0,0 +1,140 @@
+use std::borrow::Cow;
+use std::io::IsTerminal;
+use std::sync::OnceLock;
+
+/// When to color output (`--color`)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ColorChoice {
+    /// Color when stdout is a terminal and NO_COLOR is not set
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+/// ANSI colors used for output
+#[derive(Clone, Copy, Debug)]
+pub enum Color {
+    Red,
+    Green,
+    Yellow,
+    Cyan,
+    Bold,
+}
+
+impl Color {
+    fn code(self) -> &'static str {
+        match self {
+            Color::Red => "31",
+            Color::Green => "32",
+            Color::Yellow => "33",
+            Color::Cyan => "36",
+            Color::Bold => "1",
+        }
+    }
+}
+
+struct Settings {
+    color: bool,
+    /// Whether messages keep their leading emoji; dropped when output is piped
+    symbols: bool,
+}
+
+static SETTINGS: OnceLock<Settings> = OnceLock::new();
+
+/// Decides once, at startup, whether output is colored and decorated
+pub fn init(choice: ColorChoice) {
+    let terminal = std::io::stdout().is_terminal();
+    let color = match choice {
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+        ColorChoice::Auto => {
+            terminal
+                && std::env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+                && std::env::var("TERM").map_or(true, |term| term != "dumb")
+        }
+    };
+    let _ = SETTINGS.set(Settings { color, symbols: terminal });
+}
+
+fn settings() -> &'static Settings {
+    SETTINGS.get_or_init(|| Settings { color: false, symbols: true })
+}
+
+/// Whether output is colored
+pub fn color_enabled() -> bool {
+    settings().color
+}
+
+/// Wraps text in an ANSI color when color is enabled
+pub fn paint(text: &str, color: Color) -> Cow<'_, str> {
+    match color_enabled() {
+        true => Cow::Owned(format!("\x1b[{}m{}\x1b[0m", color.code(), text)),
+        false => Cow::Borrowed(text),
+    }
+}
+
+/// A commit or object ID, highlighted like Git's
+pub fn id(id: &str) -> Cow<'_, str> {
+    paint(id, Color::Yellow)
+}
+
+/// A message as printed: its leading emoji is dropped when output is piped
+pub fn decorate(message: &str) -> Cow<'_, str> {
+    match settings().symbols {
+        true => Cow::Borrowed(message),
+        false => strip_symbol(message),
+    }
+}
+
+/// Removes the emoji a message starts with (after any indentation) and the spaces after it
+pub fn strip_symbol(message: &str) -> Cow<'_, str> {
+    let body = message.trim_start();
+    let rest = body.trim_start_matches(|c: char| !c.is_ascii());
+    if rest.len() == body.len() || !rest.starts_with(' ') {
+        return Cow::Borrowed(message);
+    }
+    let indent = &message[..message.len() - body.len()];
+    Cow::Owned(format!("{}{}", indent, rest.trim_start_matches(' ')))
+}
+
+/// Colors a unified diff line by line: additions, deletions, and hunk headers
+pub fn diff(text: &str) -> Cow<'_, str> {
+    if !color_enabled() {
+        return Cow::Borrowed(text);
+    }
+    let mut colored = String::with_capacity(text.len());
+    for line in text.split_inclusive('\n') {
+        let (content, newline) = match line.strip_suffix('\n') {
+            Some(content) => (content, "\n"),
+            None => (line, ""),
+        };
+        let color = match content {
+            _ if content.starts_with("diff --git") || content.starts_with("+++") || content.starts_with("---") => Some(Color::Bold),
+            _ if content.starts_with("@@") => Some(Color::Cyan),
+            _ if content.starts_with('+') => Some(Color::Green),
+            _ if content.starts_with('-') => Some(Color::Red),
+            _ => None,
+        };
+        match color {
+            Some(color) => colored.push_str(&paint(content, color)),
+            None => colored.push_str(content),
+        }
+        colored.push_str(newline);
+    }
+    Cow::Owned(colored)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_symbol() {
+        assert_eq!(strip_symbol("✅ Saved commit"), "Saved commit");
+        assert_eq!(strip_symbol("⚠️  Skipping entry"), "Skipping entry");
+        assert_eq!(strip_symbol("\n📚 Orbit History"), "\nOrbit History");
+        assert_eq!(strip_symbol("  📁 Restoring directory: src"), "  Restoring directory: src");
+        assert_eq!(strip_symbol("Renamed a → b"), "Renamed a → b");
+    }
+}
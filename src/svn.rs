@@ -0,0 +1,489 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::process::Command;
+use percent_encoding::{utf8_percent_encode, AsciiSet, CONTROLS};
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+use crate::fast_export::quote_path;
+use crate::{fast_import, fetch};
+use tracing::info;
+
+/// Characters escaped in the repository paths of svn URLs
+const URL_PATH: &AsciiSet = &CONTROLS.add(b' ').add(b'"').add(b'#').add(b'%').add(b'<').add(b'>').add(b'?').add(b'`').add(b'{').add(b'}');
+
+/// One revision of `svn log --xml --verbose`
+#[derive(Debug)]
+struct Revision {
+    number: u64,
+    author: String,
+    timestamp: i64,
+    message: String,
+    changes: Vec<Change>,
+}
+
+/// A changed path of a revision
+#[derive(Debug)]
+struct Change {
+    /// A (added), M (modified), D (deleted) or R (replaced)
+    action: char,
+    is_dir: bool,
+    /// Repository path starting with `/`
+    path: String,
+    copied_from: Option<(String, u64)>,
+    /// False only when svn reports that the properties did not change
+    prop_mods: bool,
+}
+
+/// Where a repository path lands: the ref of its branch or tag, and the path inside it
+#[derive(Debug, PartialEq)]
+struct Location {
+    git_ref: String,
+    path: String,
+}
+
+/// Replays the revisions of a Subversion repository with the `svn` command into a new
+/// Orbit repository. With the standard layout, `trunk` becomes main and each directory
+/// of `branches` and `tags` a branch or tag; otherwise the whole tree becomes main.
+/// Authors are mapped through `authors_file` (`user = Name <email>` lines) if given.
+pub fn fetch_svn_repository(url: &str, target_dir: Option<&str>, authors_file: Option<&Path>) -> Result<(), Box<dyn std::error::Error>> {
+    info!("🌐 Fetching Subversion repository: {}", url);
+    let version = svn(&["--version", "--quiet"])?;
+    info!("🔧 Using svn {}", String::from_utf8_lossy(&version).trim());
+    let authors = authors_file
+        .map(|path| fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e)).and_then(|text| parse_authors(&text)))
+        .transpose()?;
+
+    let info = String::from_utf8_lossy(&svn(&["info", "--xml", url])?).into_owned();
+    let (Some(root), Some(project)) = (child_text(&info, "root"), child_text(&info, "url")) else {
+        return Err(format!("unexpected `svn info` output for {}", url).into());
+    };
+    let prefix = percent_encoding::percent_decode_str(project.strip_prefix(&root).unwrap_or("")).decode_utf8_lossy().trim_end_matches('/').to_string();
+    let listing = String::from_utf8_lossy(&svn(&["list", &project])?).into_owned();
+    let standard = listing.lines().any(|entry| entry == "trunk/");
+    if standard {
+        info!("📂 Standard layout: trunk, branches and tags");
+    }
+
+    let repo_name = fetch::extract_repo_name(url)?;
+    let target = target_dir.unwrap_or(&repo_name);
+    fetch::convert_into_new_repository(target, || {
+        info!("📜 Reading the revision log...");
+        let log = String::from_utf8_lossy(&svn(&["log", "--xml", "--verbose", "--revision", "1:HEAD", &project])?).into_owned();
+        let revisions = parse_log(&log)?;
+        info!("🔄 Replaying {} revisions...", revisions.len());
+        let mut replay = Replay { root, prefix, standard, authors, next_mark: 1, commits: HashMap::new(), modes: HashMap::new() };
+        fast_import::import_generated(move |out| {
+            for revision in &revisions {
+                replay.write_revision(revision, out)?;
+            }
+            out.write_all(b"done\n")
+        })?;
+        Ok(())
+    })?;
+
+    info!("");
+    info!("🎉 Subversion repository successfully converted to Orbit!");
+    info!("💡 Try these commands:");
+    info!("   cd {}", target);
+    info!("   orb status");
+    info!("   orb history");
+    Ok(())
+}
+
+/// State of the stream written from the revision log
+struct Replay {
+    /// Repository root URL
+    root: String,
+    /// Path of the converted project inside the repository, `""` for the root
+    prefix: String,
+    standard: bool,
+    authors: Option<HashMap<String, String>>,
+    next_mark: u64,
+    /// Marks of each ref's commits, by revision, for branches copied from them
+    commits: HashMap<String, Vec<(u64, u64)>>,
+    /// Modes of files by repository path, so text changes need no property lookup
+    modes: HashMap<String, u32>,
+}
+
+impl Replay {
+    /// A revision as one commit per branch or tag it changes. A branch or tag copied
+    /// whole from another one starts from that one's commit; copies with no further
+    /// changes only move the ref.
+    fn write_revision(&mut self, revision: &Revision, out: &mut dyn Write) -> std::io::Result<()> {
+        let mut refs: Vec<String> = Vec::new();
+        let mut by_ref: HashMap<String, Vec<(&Change, String)>> = HashMap::new();
+        for change in &revision.changes {
+            let Some(inside) = self.project_path(&change.path) else { continue };
+            let Some(location) = locate(&inside, self.standard) else { continue };
+            if !refs.contains(&location.git_ref) {
+                refs.push(location.git_ref.clone());
+            }
+            by_ref.entry(location.git_ref).or_default().push((change, location.path));
+        }
+
+        for git_ref in refs {
+            if by_ref[&git_ref].iter().any(|(change, path)| path.is_empty() && change.action == 'D') {
+                info!("🗑️  r{}: {} was deleted in Subversion; its last commit is kept", revision.number, git_ref);
+                continue;
+            }
+            let mut start = None;
+            let mut commands = Vec::new();
+            let mut changed = false;
+            for (change, path) in &by_ref[&git_ref] {
+                if path.is_empty() {
+                    match change.action {
+                        'A' | 'R' => start = Some(self.branch_start(change, revision.number, &mut commands)?),
+                        _ => changed = true,
+                    }
+                    continue;
+                }
+                changed = true;
+                if change.action == 'D' || change.action == 'R' {
+                    writeln!(commands, "D {}", quote_path(path))?;
+                }
+                match (change.action, change.is_dir) {
+                    ('D', _) => {}
+                    (_, false) => self.write_file(&change.path, path, revision.number, change.prop_mods, &mut commands)?,
+                    ('A' | 'R', true) if change.copied_from.is_some() => self.write_directory(&change.path, path, revision.number, &mut commands)?,
+                    _ => {}
+                }
+            }
+
+            match start {
+                Some(Some(mark)) => write!(out, "reset {}\nfrom :{}\n\n", git_ref, mark)?,
+                Some(None) => write!(out, "reset {}\n\n", git_ref)?,
+                None => {}
+            }
+            if !changed && commands.is_empty() {
+                if let Some(Some(mark)) = start {
+                    self.commits.entry(git_ref).or_default().push((revision.number, mark));
+                }
+                continue;
+            }
+            let person = self.identity(&revision.author)?;
+            write!(out, "commit {}\nmark :{}\n", git_ref, self.next_mark)?;
+            write!(out, "author {} {} +0000\ncommitter {} {} +0000\n", person, revision.timestamp, person, revision.timestamp)?;
+            write!(out, "data {}\n{}\n", revision.message.len(), revision.message)?;
+            out.write_all(&commands)?;
+            out.write_all(b"\n")?;
+            self.commits.entry(git_ref).or_default().push((revision.number, self.next_mark));
+            self.next_mark += 1;
+        }
+        Ok(())
+    }
+
+    /// The mark a branch or tag created by `change` starts from, or None for a new
+    /// history; a copy of anything but a whole branch lists the copied files instead
+    fn branch_start(&mut self, change: &Change, rev: u64, commands: &mut Vec<u8>) -> std::io::Result<Option<u64>> {
+        let Some((source, source_rev)) = &change.copied_from else { return Ok(None) };
+        let source_ref = self
+            .project_path(source)
+            .and_then(|inside| locate(&inside, self.standard))
+            .filter(|location| location.path.is_empty())
+            .map(|location| location.git_ref);
+        let mark = source_ref
+            .and_then(|git_ref| self.commits.get(&git_ref))
+            .and_then(|commits| commits.iter().rev().find(|(rev, _)| rev <= source_rev))
+            .map(|(_, mark)| *mark);
+        if mark.is_some() {
+            return Ok(mark);
+        }
+        self.write_directory(&change.path, "", rev, commands)?;
+        Ok(None)
+    }
+
+    /// `M` commands for every file of a copied directory
+    fn write_directory(&mut self, repo_path: &str, path: &str, rev: u64, commands: &mut Vec<u8>) -> std::io::Result<()> {
+        let url = self.url(repo_path, rev);
+        let listing = String::from_utf8_lossy(&svn(&["list", "--recursive", "--xml", &url])?).into_owned();
+        let files: Vec<String> = elements(&listing, "entry")
+            .into_iter()
+            .filter(|(attributes, _)| attribute(attributes, "kind").as_deref() == Some("file"))
+            .filter_map(|(_, content)| child_text(content, "name"))
+            .collect();
+        let properties = String::from_utf8_lossy(&svn(&["proplist", "--recursive", "--xml", &url])?).into_owned();
+        let special = special_modes(&properties, &url);
+        for file in files {
+            let file_repo_path = format!("{}/{}", repo_path, file);
+            self.modes.insert(file_repo_path.clone(), special.get(&file).copied().unwrap_or(0o100644));
+            let file_path = if path.is_empty() { file } else { format!("{}/{}", path, file) };
+            self.write_file(&file_repo_path, &file_path, rev, false, commands)?;
+        }
+        Ok(())
+    }
+
+    /// An `M` command with a file's content at `rev`
+    fn write_file(&mut self, repo_path: &str, path: &str, rev: u64, prop_mods: bool, commands: &mut Vec<u8>) -> std::io::Result<()> {
+        let url = self.url(repo_path, rev);
+        let mode = match self.modes.get(repo_path) {
+            Some(mode) if !prop_mods => *mode,
+            _ => {
+                let properties = String::from_utf8_lossy(&svn(&["proplist", "--xml", &url])?).into_owned();
+                let mode = special_modes(&properties, &url).into_values().next().unwrap_or(0o100644);
+                self.modes.insert(repo_path.to_string(), mode);
+                mode
+            }
+        };
+        let mut content = svn(&["cat", &url])?;
+        if mode == 0o120000 {
+            // svn:special files hold `link <target>`
+            content.drain(..content.len().min(5));
+        }
+        write!(commands, "M {:o} inline {}\ndata {}\n", mode, quote_path(path), content.len())?;
+        commands.extend_from_slice(&content);
+        commands.push(b'\n');
+        Ok(())
+    }
+
+    /// A repository path relative to the converted project, if it is inside it
+    fn project_path(&self, repo_path: &str) -> Option<String> {
+        let inside = repo_path.strip_prefix(&self.prefix)?;
+        if inside.is_empty() {
+            return Some(String::new());
+        }
+        inside.strip_prefix('/').map(str::to_string)
+    }
+
+    /// URL of a repository path pinned to a revision
+    fn url(&self, repo_path: &str, rev: u64) -> String {
+        format!("{}{}@{}", self.root, utf8_percent_encode(repo_path, URL_PATH), rev)
+    }
+
+    /// `Name <email>` of an svn user, from the authors file if there is one
+    fn identity(&self, user: &str) -> std::io::Result<String> {
+        match &self.authors {
+            Some(authors) => authors
+                .get(user)
+                .cloned()
+                .ok_or_else(|| std::io::Error::other(format!("author '{}' is missing from the authors file", user))),
+            None => Ok(format!("{} <>", user)),
+        }
+    }
+}
+
+/// The ref and inner path of a path relative to the project: with the standard layout
+/// `trunk/...`, `branches/<name>/...` and `tags/<name>/...`; otherwise everything is main
+fn locate(path: &str, standard: bool) -> Option<Location> {
+    if !standard {
+        return Some(Location { git_ref: "refs/heads/main".to_string(), path: path.to_string() });
+    }
+    let mut parts = path.splitn(3, '/');
+    let (git_ref, rest) = match (parts.next()?, parts.next(), parts.next()) {
+        ("trunk", rest, more) => ("refs/heads/main".to_string(), [rest, more].into_iter().flatten().collect::<Vec<_>>().join("/")),
+        ("branches", Some(name), rest) if !name.is_empty() => (format!("refs/heads/{}", ref_name(name)), rest.unwrap_or("").to_string()),
+        ("tags", Some(name), rest) if !name.is_empty() => (format!("refs/tags/{}", ref_name(name)), rest.unwrap_or("").to_string()),
+        _ => return None,
+    };
+    Some(Location { git_ref, path: rest })
+}
+
+/// A branch or tag name with the characters refs cannot hold replaced by `-`
+fn ref_name(name: &str) -> String {
+    name.chars().map(|c| if c.is_whitespace() || "~^:?*[\\".contains(c) { '-' } else { c }).collect()
+}
+
+/// `svnuser = Name <email>` lines, as `git svn` reads them; `#` starts a comment
+fn parse_authors(text: &str) -> Result<HashMap<String, String>, String> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            let (user, identity) = line.split_once('=').ok_or_else(|| format!("invalid authors line '{}'", line))?;
+            let identity = identity.trim();
+            if !identity.ends_with('>') || !identity.contains('<') {
+                return Err(format!("invalid authors line '{}' (expected `user = Name <email>`)", line));
+            }
+            Ok((user.trim().to_string(), identity.to_string()))
+        })
+        .collect()
+}
+
+fn parse_log(log: &str) -> Result<Vec<Revision>, String> {
+    elements(log, "logentry")
+        .into_iter()
+        .map(|(attributes, content)| {
+            let number = attribute(attributes, "revision").and_then(|rev| rev.parse().ok()).ok_or("log entry without a revision")?;
+            let date = child_text(content, "date").unwrap_or_default();
+            let timestamp = OffsetDateTime::parse(&date, &Rfc3339).map(|time| time.unix_timestamp()).unwrap_or(0);
+            let changes = elements(content, "path")
+                .into_iter()
+                .map(|(attributes, path)| Change {
+                    action: attribute(attributes, "action").and_then(|action| action.chars().next()).unwrap_or('M'),
+                    is_dir: attribute(attributes, "kind").as_deref() == Some("dir"),
+                    path: unescape(path),
+                    copied_from: attribute(attributes, "copyfrom-path").zip(attribute(attributes, "copyfrom-rev").and_then(|rev| rev.parse().ok())),
+                    prop_mods: attribute(attributes, "prop-mods").as_deref() != Some("false"),
+                })
+                .collect();
+            Ok(Revision {
+                number,
+                author: child_text(content, "author").unwrap_or_else(|| "(no author)".to_string()),
+                timestamp,
+                message: child_text(content, "msg").unwrap_or_default(),
+                changes,
+            })
+        })
+        .collect()
+}
+
+/// Modes set by `svn:special` (symlinks) and `svn:executable` in `svn proplist --xml`,
+/// by path below `url`
+fn special_modes(properties: &str, url: &str) -> HashMap<String, u32> {
+    let base = url.rsplit_once('@').map_or(url, |(base, _)| base);
+    let decode = |text: &str| percent_encoding::percent_decode_str(text).decode_utf8_lossy().into_owned();
+    let base = decode(base);
+    elements(properties, "target")
+        .into_iter()
+        .filter_map(|(attributes, content)| {
+            let target = decode(&attribute(attributes, "path")?);
+            let path = target.strip_prefix(&base)?.trim_start_matches('/').to_string();
+            let names: Vec<String> = elements(content, "property").into_iter().filter_map(|(attributes, _)| attribute(attributes, "name")).collect();
+            let mode = if names.iter().any(|name| name == "svn:special") {
+                0o120000
+            } else if names.iter().any(|name| name == "svn:executable") {
+                0o100755
+            } else {
+                return None;
+            };
+            Some((path, mode))
+        })
+        .collect()
+}
+
+/// `(attributes, content)` of each `<name>` element; svn's XML never nests an element
+/// in one of the same name
+fn elements<'a>(xml: &'a str, name: &str) -> Vec<(&'a str, &'a str)> {
+    let (open, close) = (format!("<{}", name), format!("</{}>", name));
+    let mut found = Vec::new();
+    let mut rest = xml;
+    while let Some(start) = rest.find(&open) {
+        let after = &rest[start + open.len()..];
+        let Some(tag_end) = after.find('>') else { break };
+        if !after.starts_with(|c: char| c.is_whitespace() || c == '>' || c == '/') {
+            rest = after;
+            continue;
+        }
+        let tag = &after[..tag_end];
+        let body = &after[tag_end + 1..];
+        if let Some(attributes) = tag.strip_suffix('/') {
+            found.push((attributes, ""));
+            rest = body;
+            continue;
+        }
+        let end = body.find(&close).unwrap_or(body.len());
+        found.push((tag, &body[..end]));
+        rest = &body[end..];
+    }
+    found
+}
+
+/// The unescaped text of the first `<name>` element
+fn child_text(xml: &str, name: &str) -> Option<String> {
+    elements(xml, name).first().map(|(_, content)| unescape(content))
+}
+
+fn attribute(attributes: &str, name: &str) -> Option<String> {
+    let key = format!("{}=\"", name);
+    let mut rest = attributes;
+    while let Some(position) = rest.find(&key) {
+        let value = &rest[position + key.len()..];
+        if rest[..position].ends_with(char::is_whitespace) {
+            return Some(unescape(&value[..value.find('"')?]));
+        }
+        rest = value;
+    }
+    None
+}
+
+/// Replaces XML entities and character references
+fn unescape(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        unescaped.push_str(&rest[..start]);
+        let entity = &rest[start + 1..];
+        let Some(end) = entity.find(';') else { break };
+        let replacement = match &entity[..end] {
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "amp" => Some('&'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            reference => reference
+                .strip_prefix("#x")
+                .map(|hex| u32::from_str_radix(hex, 16))
+                .or_else(|| reference.strip_prefix('#').map(|decimal| decimal.parse()))
+                .and_then(Result::ok)
+                .and_then(char::from_u32),
+        };
+        match replacement {
+            Some(c) => {
+                unescaped.push(c);
+                rest = &entity[end + 1..];
+            }
+            None => {
+                unescaped.push('&');
+                rest = entity;
+            }
+        }
+    }
+    unescaped.push_str(rest);
+    unescaped
+}
+
+/// Runs `svn` without prompting for credentials
+fn svn(args: &[&str]) -> std::io::Result<Vec<u8>> {
+    let output = Command::new("svn")
+        .args(args)
+        .arg("--non-interactive")
+        .output()
+        .map_err(|e| std::io::Error::other(format!("could not run svn ({}); install Subversion to import svn repositories", e)))?;
+    if !output.status.success() {
+        return Err(std::io::Error::other(format!("svn {} failed: {}", args[0], String::from_utf8_lossy(&output.stderr).trim())));
+    }
+    Ok(output.stdout)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_svn_log_and_layout() {
+        let log = r#"<?xml version="1.0" encoding="UTF-8"?>
+<log>
+<logentry
+   revision="7">
+<author>jdoe</author>
+<date>2024-01-31T12:00:00.123456Z</date>
+<paths>
+<path
+   kind="dir"
+   copyfrom-path="/project/trunk"
+   copyfrom-rev="6"
+   action="A">/project/branches/fix &amp; test</path>
+<path
+   prop-mods="false"
+   kind="file"
+   action="M">/project/trunk/a.txt</path>
+</paths>
+<msg>Branch for &lt;fix&gt;</msg>
+</logentry>
+</log>"#;
+        let revisions = parse_log(log).unwrap();
+        assert_eq!((revisions[0].number, revisions[0].timestamp, revisions[0].message.as_str()), (7, 1706702400, "Branch for <fix>"));
+        let changes = &revisions[0].changes;
+        assert_eq!((changes[0].action, changes[0].is_dir, changes[0].path.as_str()), ('A', true, "/project/branches/fix & test"));
+        assert_eq!(changes[0].copied_from, Some(("/project/trunk".to_string(), 6)));
+        assert!(!changes[1].prop_mods);
+
+        assert_eq!(locate("trunk/src/a.rs", true), Some(Location { git_ref: "refs/heads/main".into(), path: "src/a.rs".into() }));
+        assert_eq!(locate("branches/fix & test", true), Some(Location { git_ref: "refs/heads/fix-&-test".into(), path: "".into() }));
+        assert_eq!(locate("tags/v1/README", true), Some(Location { git_ref: "refs/tags/v1".into(), path: "README".into() }));
+        assert_eq!(locate("branches", true), None);
+        assert_eq!(parse_authors("# team\njdoe = Jane Doe <jane@example.com>\n").unwrap()["jdoe"], "Jane Doe <jane@example.com>");
+        assert!(parse_authors("jdoe = Jane").is_err());
+    }
+}
@@ -0,0 +1,131 @@
+use std::error::Error;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::info;
+use crate::objects::{ObjectId, Tag};
+use crate::repo::{self, RepoError};
+use crate::style;
+use crate::{config, hosted, refs, vos};
+
+/// Tag names follow the rules for branch names, so every tag can be pushed
+pub fn validate_name(name: &str) -> Result<(), RepoError> {
+    match hosted::is_ref_name(name) && name != "HEAD" && !name.starts_with('-') {
+        true => Ok(()),
+        false => Err(RepoError::InvalidTagName(name.to_string())),
+    }
+}
+
+/// Records an annotated tag: a Tag object for `target` (signed with `gpg` when `sign`
+/// is set) under `refs/annotations/<name>`, and the tag ref pointing at the commit.
+/// Returns the Tag object's ID.
+pub fn annotate(name: &str, target: &str, message: &str, sign: bool) -> Result<ObjectId, Box<dyn Error>> {
+    let mut tag = Tag {
+        object: target.to_string(),
+        tag: name.to_string(),
+        tagger: repo::DEFAULT_AUTHOR.to_string(),
+        timestamp: SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |now| now.as_secs() as i64),
+        message: message.to_string(),
+        signature: None,
+    };
+    if sign {
+        tag.signature = Some(gpg_sign(&serde_json::to_vec(&tag)?)?);
+    }
+    let tag_id = vos::store().save_json(&tag)?;
+    refs::write_ref(&refs::annotation_ref(name), &tag_id)?;
+    refs::write_ref(&refs::tag_ref(name), target)?;
+    Ok(tag_id)
+}
+
+/// Signs `payload` with `gpg --detach-sign --armor`, using the key in `user.signingkey`
+/// when one is configured
+fn gpg_sign(payload: &[u8]) -> Result<String, Box<dyn Error>> {
+    let mut command = Command::new("gpg");
+    command.args(["--detach-sign", "--armor"]);
+    if let Some(key) = config::load()?.get("user.signingkey") {
+        command.args(["--local-user", key]);
+    }
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("could not run gpg to sign the tag: {}", e))?;
+    child.stdin.take().expect("piped stdin").write_all(payload)?;
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        return Err(format!("gpg failed to sign the tag ({})", output.status).into());
+    }
+    Ok(String::from_utf8(output.stdout)?)
+}
+
+/// `orb tag create`: tags `revision` (default: HEAD). With a message the tag is
+/// annotated (and signed when `sign` is set); otherwise it is a lightweight ref.
+/// An existing tag is only moved with `force`.
+pub fn create(name: &str, revision: Option<&str>, message: Option<&str>, sign: bool, force: bool) -> Result<(), Box<dyn Error>> {
+    repo::open()?;
+    validate_name(name)?;
+    if !force && refs::read_ref(&refs::tag_ref(name)).is_some() {
+        return Err(RepoError::TagExists(name.to_string()).into());
+    }
+    let target = match revision {
+        Some(revision) => refs::resolve(revision)?,
+        None => refs::head_commit().ok_or(RepoError::NoCommits)?,
+    };
+    let short = style::id(&target[..7.min(target.len())]);
+    match message {
+        Some(message) => {
+            annotate(name, &target, message, sign)?;
+            info!("🏷️  Tagged {} as '{}' (annotated{})", short, name, if sign { ", signed" } else { "" });
+        }
+        None => {
+            // A lightweight tag replacing an annotated one must not keep its annotation
+            refs::delete_ref(&refs::annotation_ref(name))?;
+            refs::write_ref(&refs::tag_ref(name), &target)?;
+            info!("🏷️  Tagged {} as '{}'", short, name);
+        }
+    }
+    Ok(())
+}
+
+/// `orb tag list`: every tag with the commit it points to, and the first line of its
+/// message when it is annotated
+pub fn list() -> Result<(), Box<dyn Error>> {
+    repo::open()?;
+    let tags = refs::list_tags();
+    let width = tags.keys().map(String::len).max().unwrap_or_default();
+    let mut out = std::io::stdout().lock();
+    for (name, target) in &tags {
+        let subject = refs::read_ref(&refs::annotation_ref(name))
+            .and_then(|id| vos::load::<Tag>(&id, "tag").ok())
+            .map(|tag| tag.message.lines().next().unwrap_or_default().to_string())
+            .unwrap_or_default();
+        let line = format!("{:<width$} {} {}", name, style::id(&target[..7.min(target.len())]), subject, width = width);
+        writeln!(out, "{}", line.trim_end())?;
+    }
+    Ok(())
+}
+
+/// `orb tag delete`: removes a tag and its annotation. The Tag object stays in the
+/// object store.
+pub fn delete(name: &str) -> Result<(), Box<dyn Error>> {
+    validate_name(name)?;
+    repo::open()?;
+    let target = refs::read_ref(&refs::tag_ref(name)).ok_or_else(|| RepoError::NoSuchTag(name.to_string()))?;
+    refs::delete_ref(&refs::tag_ref(name))?;
+    refs::delete_ref(&refs::annotation_ref(name))?;
+    info!("🗑️  Deleted tag '{}' (was {})", name, style::id(&target[..7.min(target.len())]));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_delete_rejects_names_outside_the_tag_refs() {
+        for name in ["..", "../heads/feature", "../../HEAD", "/etc/passwd"] {
+            let error = delete(name).unwrap_err();
+            assert!(matches!(error.downcast_ref(), Some(RepoError::InvalidTagName(_))), "{}: {}", name, error);
+        }
+    }
+}
@@ -0,0 +1,250 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use crate::users;
+
+/// Failed authentications allowed from one IP, or with one token, before it is locked out
+const FAILURES_BEFORE_LOCKOUT: u32 = 5;
+/// First lockout; every further failure doubles it, up to MAX_LOCKOUT
+const LOCKOUT: Duration = Duration::from_secs(30);
+const MAX_LOCKOUT: Duration = Duration::from_secs(3600);
+/// Clients and tokens not seen for this long are forgotten
+const FORGET_AFTER: Duration = Duration::from_secs(3600);
+/// Number of tracked entries above which idle ones are pruned
+const PRUNE_ABOVE: usize = 4096;
+
+/// Rate limits of `orb serve`; 0 disables a limit
+#[derive(Debug, Clone, Copy)]
+pub struct Limits {
+    /// New connections accepted from one IP per minute
+    pub connections_per_minute: u32,
+    /// Connections one IP may have open at once
+    pub connections_per_ip: u32,
+    /// Commands per second on one connection; faster clients are slowed down, not dropped
+    pub requests_per_second: u32,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self { connections_per_minute: 120, connections_per_ip: 32, requests_per_second: 500 }
+    }
+}
+
+/// Connection, request, and failed-authentication limits shared by every session
+pub struct Throttle {
+    limits: Limits,
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    clients: HashMap<IpAddr, Client>,
+    /// Failures per token digest, so one bad token is locked out from every address
+    tokens: HashMap<String, Failures>,
+}
+
+struct Client {
+    connections: Bucket,
+    open: u32,
+    failures: Failures,
+    last_seen: Instant,
+}
+
+/// Holds a connection slot of its IP until dropped
+pub struct ConnectionGuard {
+    throttle: Arc<Throttle>,
+    ip: IpAddr,
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        if let Some(client) = self.throttle.state.lock().unwrap().clients.get_mut(&self.ip) {
+            client.open = client.open.saturating_sub(1);
+        }
+    }
+}
+
+impl Throttle {
+    pub fn new(limits: Limits) -> Arc<Self> {
+        Arc::new(Self { limits, state: Mutex::new(State::default()) })
+    }
+
+    /// Admits a new connection from `ip`, or says why it is refused
+    pub fn connect(self: &Arc<Self>, ip: IpAddr) -> Result<ConnectionGuard, String> {
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap();
+        state.prune(now);
+        let per_minute = self.limits.connections_per_minute as f64;
+        let client = state.clients.entry(ip).or_insert_with(|| Client {
+            connections: Bucket::new(per_minute, per_minute / 60.0, now),
+            open: 0,
+            failures: Failures::default(),
+            last_seen: now,
+        });
+        client.last_seen = now;
+
+        if self.limits.connections_per_ip > 0 && client.open >= self.limits.connections_per_ip {
+            return Err(format!("{} connections already open", client.open));
+        }
+        if self.limits.connections_per_minute > 0 && !client.connections.try_take(now) {
+            return Err(format!("more than {} connections per minute", self.limits.connections_per_minute));
+        }
+        client.open += 1;
+        Ok(ConnectionGuard { throttle: self.clone(), ip })
+    }
+
+    /// How long `ip` (or anyone presenting `token`) must wait before authenticating again
+    pub fn locked_out(&self, ip: IpAddr, token: Option<&str>) -> Option<Duration> {
+        let now = Instant::now();
+        let state = self.state.lock().unwrap();
+        let by_ip = state.clients.get(&ip).and_then(|client| client.failures.locked(now));
+        let by_token = token
+            .and_then(|token| state.tokens.get(&users::token_digest(token)))
+            .and_then(|failures| failures.locked(now));
+        by_ip.max(by_token)
+    }
+
+    pub fn auth_failed(&self, ip: IpAddr, token: Option<&str>) {
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap();
+        state.prune(now);
+        if let Some(client) = state.clients.get_mut(&ip) {
+            client.failures.record(now);
+        }
+        if let Some(token) = token {
+            state.tokens.entry(users::token_digest(token)).or_default().record(now);
+        }
+    }
+
+    pub fn auth_succeeded(&self, ip: IpAddr) {
+        if let Some(client) = self.state.lock().unwrap().clients.get_mut(&ip) {
+            client.failures = Failures::default();
+        }
+    }
+
+    /// Paces the commands of one connection
+    pub fn requests(&self) -> RequestLimiter {
+        let rate = self.limits.requests_per_second as f64;
+        RequestLimiter { bucket: (rate > 0.0).then(|| Bucket::new(rate, rate, Instant::now())) }
+    }
+}
+
+impl State {
+    /// Forgets idle clients and tokens once there are many of them
+    fn prune(&mut self, now: Instant) {
+        if self.clients.len() + self.tokens.len() < PRUNE_ABOVE {
+            return;
+        }
+        let active = |last: Instant| now.duration_since(last) < FORGET_AFTER;
+        self.clients.retain(|_, client| client.open > 0 || active(client.last_seen) || client.failures.locked(now).is_some());
+        self.tokens.retain(|_, failures| failures.last.is_some_and(active) || failures.locked(now).is_some());
+    }
+}
+
+/// Delays commands that arrive faster than the per-connection rate
+pub struct RequestLimiter {
+    bucket: Option<Bucket>,
+}
+
+impl RequestLimiter {
+    pub async fn wait(&mut self) {
+        if let Some(bucket) = &mut self.bucket {
+            let delay = bucket.take(Instant::now());
+            if !delay.is_zero() {
+                tokio::time::sleep(delay).await;
+            }
+        }
+    }
+}
+
+/// Token bucket: holds up to `capacity` tokens and refills `per_second` of them
+struct Bucket {
+    capacity: f64,
+    per_second: f64,
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    fn new(capacity: f64, per_second: f64, now: Instant) -> Self {
+        Self { capacity, per_second, tokens: capacity, updated: now }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.per_second).min(self.capacity);
+        self.updated = now;
+    }
+
+    /// Takes a token if one is available
+    fn try_take(&mut self, now: Instant) -> bool {
+        self.refill(now);
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Takes a token, going into debt if needed, and returns how long to wait until it is paid
+    fn take(&mut self, now: Instant) -> Duration {
+        self.refill(now);
+        self.tokens -= 1.0;
+        if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / self.per_second)
+        }
+    }
+}
+
+#[derive(Default)]
+struct Failures {
+    count: u32,
+    locked_until: Option<Instant>,
+    last: Option<Instant>,
+}
+
+impl Failures {
+    fn locked(&self, now: Instant) -> Option<Duration> {
+        self.locked_until.and_then(|until| until.checked_duration_since(now)).filter(|left| !left.is_zero())
+    }
+
+    fn record(&mut self, now: Instant) {
+        self.count += 1;
+        self.last = Some(now);
+        if self.count >= FAILURES_BEFORE_LOCKOUT {
+            let doublings = (self.count - FAILURES_BEFORE_LOCKOUT).min(7);
+            self.locked_until = Some(now + (LOCKOUT * 2u32.pow(doublings)).min(MAX_LOCKOUT));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_buckets_and_lockouts() {
+        let start = Instant::now();
+        let mut bucket = Bucket::new(2.0, 1.0, start);
+        assert!(bucket.try_take(start));
+        assert!(bucket.try_take(start));
+        assert!(!bucket.try_take(start));
+        assert!(bucket.try_take(start + Duration::from_secs(1)));
+        assert_eq!(bucket.take(start + Duration::from_secs(1)), Duration::from_secs(1));
+
+        let mut failures = Failures::default();
+        for _ in 1..FAILURES_BEFORE_LOCKOUT {
+            failures.record(start);
+        }
+        assert_eq!(failures.locked(start), None);
+        failures.record(start);
+        assert_eq!(failures.locked(start), Some(LOCKOUT));
+        failures.record(start);
+        assert_eq!(failures.locked(start), Some(LOCKOUT * 2));
+        assert_eq!(failures.locked(start + LOCKOUT * 2), None);
+    }
+}
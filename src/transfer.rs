@@ -0,0 +1,214 @@
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::Instant;
+use serde::Serialize;
+use crate::progress::Progress;
+use crate::refs::RefChange;
+use crate::transport::WireCounters;
+use tracing::info;
+
+/// Object count and payload bytes for one object type
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct TypeTotals {
+    pub objects: u64,
+    pub bytes: u64,
+}
+
+/// Snapshot of a finished (or interrupted) transfer, suitable for printing or JSON output
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct TransferSummary {
+    pub received: BTreeMap<String, TypeTotals>,
+    pub sent: BTreeMap<String, TypeTotals>,
+    /// Objects that were already present locally and therefore not downloaded
+    pub skipped_existing: u64,
+    pub logical_bytes_received: u64,
+    pub logical_bytes_sent: u64,
+    pub wire_bytes_received: u64,
+    pub wire_bytes_sent: u64,
+    pub elapsed_secs: f64,
+    /// Wire bytes (both directions) per second
+    pub throughput_bytes_per_sec: f64,
+}
+
+/// Machine-readable description of a finished sync (`orb sync --report <file>`)
+#[derive(Debug, Serialize)]
+pub struct SyncReport {
+    /// Remote name, if the sync target was a configured remote
+    pub remote: Option<String>,
+    pub url: String,
+    /// Local refs (branches, tags, remote-tracking refs) the sync moved
+    pub refs: Vec<RefChange>,
+    pub transfer: TransferSummary,
+}
+
+#[derive(Debug, Default)]
+struct Counts {
+    received: BTreeMap<String, TypeTotals>,
+    sent: BTreeMap<String, TypeTotals>,
+    skipped_existing: u64,
+}
+
+/// Collects per-type object counts during sync/clone.
+///
+/// Uses interior mutability so it can be shared by reference with the
+/// retrying connection closures; counts accumulate across retries.
+#[derive(Debug)]
+pub struct TransferStats {
+    started: Instant,
+    counts: Mutex<Counts>,
+    wire: WireCounters,
+    /// Wire totals already counted before this transfer started
+    wire_baseline: (u64, u64),
+    progress: Progress,
+}
+
+impl TransferStats {
+    pub fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            counts: Mutex::new(Counts::default()),
+            wire: WireCounters::default(),
+            wire_baseline: (0, 0),
+            progress: Progress::new(),
+        }
+    }
+
+    /// Stats for a transfer over connections that are already counted by `wire`
+    /// (a persistent session); only bytes from now on are reported
+    pub fn continuing(wire: &WireCounters) -> Self {
+        Self {
+            wire: wire.clone(),
+            wire_baseline: wire.totals(),
+            ..Self::new()
+        }
+    }
+
+    /// Counters to attach to the connection so wire bytes are measured
+    pub fn wire(&self) -> &WireCounters {
+        &self.wire
+    }
+
+    /// Starts a new progress phase (e.g. "Downloading"); `total` is the object count if known
+    pub fn begin_phase(&self, name: &'static str, total: Option<u64>) {
+        self.progress.begin(name, total);
+    }
+
+    /// Ends the current progress phase
+    pub fn end_phase(&self) {
+        self.progress.finish();
+    }
+
+    /// Prints a message without disturbing the progress display
+    pub fn note(&self, message: &str) {
+        self.progress.println(message);
+    }
+
+    /// Records an object received from the peer
+    pub fn record_received(&self, object_type: &str, bytes: u64) {
+        self.progress.advance(bytes);
+        let mut counts = self.counts.lock().unwrap();
+        let totals = counts.received.entry(object_type.to_string()).or_default();
+        totals.objects += 1;
+        totals.bytes += bytes;
+    }
+
+    /// Records an object sent to the peer
+    pub fn record_sent(&self, object_type: &str, bytes: u64) {
+        self.progress.advance(bytes);
+        let mut counts = self.counts.lock().unwrap();
+        let totals = counts.sent.entry(object_type.to_string()).or_default();
+        totals.objects += 1;
+        totals.bytes += bytes;
+    }
+
+    /// Records an object that was skipped because it already exists locally
+    pub fn record_skipped(&self) {
+        self.counts.lock().unwrap().skipped_existing += 1;
+    }
+
+    /// Builds a summary of everything recorded so far
+    pub fn summary(&self) -> TransferSummary {
+        let counts = self.counts.lock().unwrap();
+        let elapsed_secs = self.started.elapsed().as_secs_f64();
+        let (sent, received) = self.wire.totals();
+        let (wire_bytes_sent, wire_bytes_received) = (sent - self.wire_baseline.0, received - self.wire_baseline.1);
+        let wire_total = (wire_bytes_sent + wire_bytes_received) as f64;
+
+        TransferSummary {
+            logical_bytes_received: counts.received.values().map(|t| t.bytes).sum(),
+            logical_bytes_sent: counts.sent.values().map(|t| t.bytes).sum(),
+            received: counts.received.clone(),
+            sent: counts.sent.clone(),
+            skipped_existing: counts.skipped_existing,
+            wire_bytes_received,
+            wire_bytes_sent,
+            elapsed_secs,
+            throughput_bytes_per_sec: if elapsed_secs > 0.0 { wire_total / elapsed_secs } else { 0.0 },
+        }
+    }
+
+    /// Prints a human-readable transfer summary
+    pub fn print_summary(&self) {
+        self.progress.finish();
+        let summary = self.summary();
+
+        info!("");
+        info!("📊 Transfer summary");
+        print_direction("Received", &summary.received);
+        print_direction("Sent", &summary.sent);
+        if summary.skipped_existing > 0 {
+            info!("   Already present:  {} objects (deduplicated, not transferred)", summary.skipped_existing);
+        }
+        info!(
+            "   Logical bytes:    {} received, {} sent",
+            format_bytes(summary.logical_bytes_received),
+            format_bytes(summary.logical_bytes_sent)
+        );
+        info!(
+            "   Bytes on wire:    {} received, {} sent",
+            format_bytes(summary.wire_bytes_received),
+            format_bytes(summary.wire_bytes_sent)
+        );
+        info!(
+            "   Elapsed:          {:.1}s ({}/s)",
+            summary.elapsed_secs,
+            format_bytes(summary.throughput_bytes_per_sec as u64)
+        );
+    }
+}
+
+impl Default for TransferStats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn print_direction(label: &str, totals: &BTreeMap<String, TypeTotals>) {
+    if totals.is_empty() {
+        info!("   {:<17} nothing", format!("{}:", label));
+        return;
+    }
+
+    let parts: Vec<String> = totals
+        .iter()
+        .map(|(object_type, t)| format!("{} {} ({})", t.objects, object_type, format_bytes(t.bytes)))
+        .collect();
+    info!("   {:<17} {}", format!("{}:", label), parts.join(", "));
+}
+
+/// Formats a byte count using binary units (e.g. `1.5 MiB`)
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
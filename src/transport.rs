@@ -0,0 +1,561 @@
+use std::future::Future;
+use std::io;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::task::{Context, Poll};
+use std::time::Duration;
+use futures::stream::{FuturesUnordered, StreamExt};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::TcpStream;
+use tokio::time::Sleep;
+use crate::client_tls::{ClientTls, OrbitUrl};
+use crate::config::Config;
+use crate::http_transport;
+use crate::proxy;
+use crate::discovery;
+use crate::secure_channel::{self, Negotiated};
+use crate::vnp;
+use tracing::warn;
+
+/// Boxed read half of a VNP connection (plain TCP or TLS)
+pub type VnpReader = Box<dyn AsyncRead + Unpin + Send>;
+/// Boxed write half of a VNP connection (plain TCP or TLS)
+pub type VnpWriter = Box<dyn AsyncWrite + Unpin + Send>;
+
+const DEFAULT_CONNECT_TIMEOUT_SECS: u64 = 10;
+const DEFAULT_READ_TIMEOUT_SECS: u64 = 60;
+const DEFAULT_RETRIES: u32 = 3;
+const DEFAULT_BACKOFF_MS: u64 = 500;
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Delay before racing the next resolved address while an attempt is still pending (RFC 8305)
+const HAPPY_EYEBALLS_DELAY: Duration = Duration::from_millis(250);
+
+/// Which IP versions connections may use
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum AddressFamily {
+    /// Race IPv6 and IPv4 addresses (happy eyeballs)
+    #[default]
+    Any,
+    Ipv4,
+    Ipv6,
+}
+
+impl std::str::FromStr for AddressFamily {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "any" => Ok(AddressFamily::Any),
+            "ipv4" | "4" => Ok(AddressFamily::Ipv4),
+            "ipv6" | "6" => Ok(AddressFamily::Ipv6),
+            _ => Err(format!("invalid address family '{}' (expected any, ipv4, or ipv6)", value)),
+        }
+    }
+}
+
+/// Connection settings that a remote (`remote.<name>.*`) or the command line can override
+#[derive(Debug, Clone, Default)]
+pub struct NetOverrides {
+    pub connect_timeout: Option<Duration>,
+    /// `Some(None)` disables the read timeout
+    pub read_timeout: Option<Option<Duration>>,
+    pub address_family: Option<AddressFamily>,
+}
+
+impl NetOverrides {
+    /// Reads `<section>.timeout` (both timeouts), `<section>.connect_timeout`,
+    /// `<section>.read_timeout`, and `<section>.address_family`; a timeout of 0 disables
+    /// the read timeout and leaves the connect timeout at its default
+    pub fn from_config(config: &Config, section: &str) -> Self {
+        let secs = |key: &str| config.get_parsed::<u64>(&format!("{}.{}", section, key));
+        let timeout = secs("timeout");
+        let connect_timeout = secs("connect_timeout").or(timeout);
+        let read_timeout = secs("read_timeout").or(timeout);
+        Self {
+            connect_timeout: connect_timeout.filter(|&s| s > 0).map(Duration::from_secs),
+            read_timeout: read_timeout.map(|s| (s > 0).then(|| Duration::from_secs(s))),
+            address_family: config.get_parsed(&format!("{}.address_family", section)),
+        }
+    }
+}
+
+/// Network behaviour for VNP connections (timeouts and retry policy)
+#[derive(Debug, Clone)]
+pub struct NetOptions {
+    pub connect_timeout: Duration,
+    /// Maximum time to wait for data from the server (None disables the limit)
+    pub read_timeout: Option<Duration>,
+    /// Number of additional attempts after the first failure
+    pub retries: u32,
+    /// Delay before the first retry; doubled on each subsequent attempt
+    pub backoff: Duration,
+    /// Bandwidth cap in bytes per second, applied to each direction
+    pub limit_rate: Option<u64>,
+    /// Skip TLS certificate verification (only ever set by `--insecure`)
+    pub insecure: bool,
+    /// Continue unencrypted with servers that decline the encrypted channel
+    /// (`net.allow_plaintext`); such sessions never send a token
+    pub allow_plaintext: bool,
+    /// Proxy URL from `--proxy` or `net.proxy`; None falls back to HTTPS_PROXY/ALL_PROXY
+    pub proxy: Option<String>,
+    pub address_family: AddressFamily,
+    /// Command-line settings, which win over per-remote ones
+    flags: NetOverrides,
+}
+
+impl Default for NetOptions {
+    fn default() -> Self {
+        Self {
+            connect_timeout: Duration::from_secs(DEFAULT_CONNECT_TIMEOUT_SECS),
+            read_timeout: Some(Duration::from_secs(DEFAULT_READ_TIMEOUT_SECS)),
+            retries: DEFAULT_RETRIES,
+            backoff: Duration::from_millis(DEFAULT_BACKOFF_MS),
+            limit_rate: None,
+            insecure: false,
+            allow_plaintext: false,
+            proxy: None,
+            address_family: AddressFamily::Any,
+            flags: NetOverrides::default(),
+        }
+    }
+}
+
+impl NetOptions {
+    /// Reads the `net.*` timeouts and address family (see `NetOverrides::from_config`),
+    /// `net.retries`, `net.retry_backoff_ms`, `net.limit_rate`, `net.proxy`, and
+    /// `net.allow_plaintext` from the config
+    pub fn from_config(config: &Config) -> Self {
+        let defaults = Self::default();
+        let mut options = Self {
+            retries: config.get_parsed("net.retries").unwrap_or(defaults.retries),
+            backoff: config
+                .get_parsed("net.retry_backoff_ms")
+                .map(Duration::from_millis)
+                .unwrap_or(defaults.backoff),
+            limit_rate: config.get("net.limit_rate").and_then(|v| parse_rate(v).ok()),
+            insecure: false,
+            allow_plaintext: config.get_parsed("net.allow_plaintext").unwrap_or(false),
+            proxy: config.get("net.proxy").map(str::to_string),
+            ..defaults
+        };
+        options.apply(&NetOverrides::from_config(config, "net"));
+        options
+    }
+
+    /// Applies command-line overrides on top of the configured values
+    pub fn with_overrides(mut self, flags: NetOverrides, retries: Option<u32>) -> Self {
+        self.apply(&flags);
+        self.flags = flags;
+        if let Some(retries) = retries {
+            self.retries = retries;
+        }
+        self
+    }
+
+    /// Options for one remote: its own settings replace the global ones, but
+    /// command-line flags still take precedence
+    pub fn for_remote(&self, remote: &NetOverrides) -> Self {
+        let mut options = self.clone();
+        options.apply(remote);
+        options.apply(&self.flags);
+        options
+    }
+
+    fn apply(&mut self, overrides: &NetOverrides) {
+        if let Some(timeout) = overrides.connect_timeout {
+            self.connect_timeout = timeout;
+        }
+        if let Some(timeout) = overrides.read_timeout {
+            self.read_timeout = timeout;
+        }
+        if let Some(family) = overrides.address_family {
+            self.address_family = family;
+        }
+    }
+}
+
+/// Opens a VNP connection (TLS, or plain TCP upgraded to the encrypted channel; `https://`
+/// URLs tunnel either through a WebSocket) honouring the connect and read timeouts.
+/// Bytes crossing the connection are added to `wire`. A server that declines the
+/// encrypted channel is refused unless `allow_plaintext` is set.
+pub async fn connect(url: &OrbitUrl, opts: &NetOptions, wire: &WireCounters) -> Result<Negotiated, Box<dyn std::error::Error>> {
+    let opts = &opts.for_remote(&url.net);
+    let timed_out_at = |endpoint: &str| io::Error::new(
+        io::ErrorKind::TimedOut,
+        format!("connection to {} timed out after {}s", endpoint, opts.connect_timeout.as_secs()),
+    );
+    let timed_out = || timed_out_at(&format!("{}:{}", url.url_host(), url.port));
+
+    // Servers advertised by SRV records are tried in order; without any, the URL's port is used
+    let mut targets = match url.srv_service {
+        Some(service) => discovery::lookup(service, &url.host).await,
+        None => Vec::new(),
+    };
+    if targets.is_empty() {
+        targets.push((url.host.clone(), url.port));
+    }
+
+    let proxy = proxy::for_host(opts.proxy.as_deref(), &url.host, url.use_tls)?;
+    let open_tcp = || async {
+        let mut last_error = None;
+        for (host, port) in &targets {
+            let endpoint = if host.contains(':') { format!("[{}]:{}", host, port) } else { format!("{}:{}", host, port) };
+            match tokio::time::timeout(opts.connect_timeout, proxy::connect(proxy.as_ref(), host, *port, opts.address_family)).await {
+                Ok(Ok(stream)) => return Ok((stream, endpoint)),
+                Ok(Err(e)) => last_error = Some(e),
+                Err(_) => last_error = Some(timed_out_at(&endpoint)),
+            }
+        }
+        Err(last_error.unwrap_or_else(timed_out))
+    };
+
+    if url.use_tls {
+        let mut tls_client = if !url.pins.is_empty() {
+            ClientTls::pinned(&url.pins, opts.insecure)?
+        } else if opts.insecure {
+            warn!("⚠️  WARNING: TLS certificate verification is DISABLED (--insecure).");
+            warn!("⚠️  Anyone on the network path can impersonate {}; use this only for self-signed test servers.", url.host);
+            ClientTls::new_insecure()?
+        } else {
+            ClientTls::new()?
+        };
+        if url.http {
+            tls_client = tls_client.for_http();
+        }
+        let (stream, _) = open_tcp().await?;
+        let tls_stream = tokio::time::timeout(
+            opts.connect_timeout,
+            tls_client.connect(stream, &url.server_name),
+        ).await.map_err(|_| timed_out())??;
+        let (reader, writer): (VnpReader, VnpWriter) = if url.http {
+            tokio::time::timeout(opts.connect_timeout, http_transport::connect(tls_stream, url))
+                .await
+                .map_err(|_| timed_out())??
+        } else {
+            let (reader, writer) = tokio::io::split(tls_stream);
+            (Box::new(reader), Box::new(writer))
+        };
+        let (reader, writer) = wrap_halves(reader, writer, opts, wire);
+        Ok(Negotiated::Secure(reader, writer))
+    } else {
+        // Plain TCP connection
+        let (stream, endpoint) = open_tcp().await?;
+        let (reader, writer): (VnpReader, VnpWriter) = if url.http {
+            tokio::time::timeout(opts.connect_timeout, http_transport::connect(stream, url))
+                .await
+                .map_err(|_| timed_out())??
+        } else {
+            let (reader, writer) = stream.into_split();
+            (Box::new(reader), Box::new(writer))
+        };
+        let (reader, writer) = wrap_halves(reader, writer, opts, wire);
+        match secure_channel::connect(reader, writer).await? {
+            Negotiated::Plaintext(..) if !opts.allow_plaintext => Err(format!(
+                "{} declined the encrypted channel; refusing to continue unencrypted (use orbits://, or set net.allow_plaintext to true to connect anyway)",
+                endpoint
+            ).into()),
+            Negotiated::Plaintext(reader, writer) => {
+                warn!("⚠️  WARNING: {} does not support the encrypted channel; this session is NOT encrypted.", endpoint);
+                warn!("⚠️  No token is sent over it; upgrade the server or use orbits:// (TLS) to authenticate.");
+                Ok(Negotiated::Plaintext(reader, writer))
+            }
+            negotiated => Ok(negotiated),
+        }
+    }
+}
+
+/// Resolves `host` and keeps the addresses of the allowed family, interleaving IPv6 and
+/// IPv4 (starting with the resolver's first choice) so neither family can stall the other
+pub async fn resolve(host: &str, port: u16, family: AddressFamily) -> io::Result<Vec<SocketAddr>> {
+    let resolved: Vec<SocketAddr> = tokio::net::lookup_host((host, port)).await?.collect();
+    let (v6, v4): (Vec<SocketAddr>, Vec<SocketAddr>) = resolved.iter().partition(|addr| addr.is_ipv6());
+    let (first, second) = match (family, resolved.first()) {
+        (AddressFamily::Ipv4, _) => (v4, Vec::new()),
+        (AddressFamily::Ipv6, _) => (v6, Vec::new()),
+        (AddressFamily::Any, Some(addr)) if addr.is_ipv4() => (v4, v6),
+        (AddressFamily::Any, _) => (v6, v4),
+    };
+
+    let mut addresses = Vec::with_capacity(first.len() + second.len());
+    let (mut first, mut second) = (first.into_iter(), second.into_iter());
+    loop {
+        match (first.next(), second.next()) {
+            (None, None) => break,
+            (a, b) => addresses.extend(a.into_iter().chain(b)),
+        }
+    }
+    if addresses.is_empty() {
+        let wanted = match family {
+            AddressFamily::Ipv4 => "IPv4 ",
+            AddressFamily::Ipv6 => "IPv6 ",
+            AddressFamily::Any => "",
+        };
+        return Err(io::Error::new(io::ErrorKind::NotFound, format!("{} has no {}address", host, wanted)));
+    }
+    Ok(addresses)
+}
+
+/// Opens a TCP connection to `host:port`. Resolved addresses are tried in the order of
+/// `resolve`, starting the next attempt whenever the current one fails or has not
+/// connected within 250ms (happy eyeballs), so an unreachable AAAA record costs a
+/// quarter of a second rather than the whole connect timeout.
+pub async fn dial(host: &str, port: u16, family: AddressFamily) -> io::Result<TcpStream> {
+    let mut remaining = resolve(host, port, family).await?.into_iter();
+    let mut attempts = FuturesUnordered::new();
+    attempts.extend(remaining.next().map(TcpStream::connect));
+    let mut last_error = None;
+
+    while !attempts.is_empty() {
+        tokio::select! {
+            Some(result) = attempts.next() => match result {
+                Ok(stream) => return Ok(stream),
+                Err(e) => {
+                    last_error = Some(e);
+                    attempts.extend(remaining.next().map(TcpStream::connect));
+                }
+            },
+            _ = tokio::time::sleep(HAPPY_EYEBALLS_DELAY), if remaining.len() > 0 => {
+                attempts.extend(remaining.next().map(TcpStream::connect));
+            }
+        }
+    }
+    Err(last_error.unwrap_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("{} has no address", host))))
+}
+
+/// Applies byte counting, rate limiting, and the read timeout to a freshly opened connection
+fn wrap_halves(reader: VnpReader, writer: VnpWriter, opts: &NetOptions, wire: &WireCounters) -> (VnpReader, VnpWriter) {
+    let reader: VnpReader = Box::new(Counting::new(reader, wire.received.clone()));
+    let writer: VnpWriter = Box::new(Counting::new(writer, wire.sent.clone()));
+    let (reader, writer): (VnpReader, VnpWriter) = match opts.limit_rate {
+        Some(rate) => (Box::new(vnp::Throttled::new(reader, rate)), Box::new(vnp::Throttled::new(writer, rate))),
+        None => (reader, writer),
+    };
+    (Box::new(TimeoutReader::new(reader, opts.read_timeout)), writer)
+}
+
+/// Counts the bytes crossing an accepted connection into `wire` (used by `orb serve`)
+pub fn count(reader: VnpReader, writer: VnpWriter, wire: &WireCounters) -> (VnpReader, VnpWriter) {
+    (Box::new(Counting::new(reader, wire.received.clone())), Box::new(Counting::new(writer, wire.sent.clone())))
+}
+
+/// Parses a transfer rate such as `65536`, `500k`, or `2M` into bytes per second
+pub fn parse_rate(value: &str) -> Result<u64, String> {
+    parse_size(value).map_err(|_| format!("invalid rate '{}' (expected bytes per second, e.g. 500k or 2M)", value.trim()))
+}
+
+/// Parses a size such as `65536`, `500k`, `2M`, or `1G` into bytes
+pub fn parse_size(value: &str) -> Result<u64, String> {
+    let value = value.trim();
+    let (digits, multiplier) = match value.chars().last().map(|c| c.to_ascii_lowercase()) {
+        Some('k') => (&value[..value.len() - 1], 1024),
+        Some('m') => (&value[..value.len() - 1], 1024 * 1024),
+        Some('g') => (&value[..value.len() - 1], 1024 * 1024 * 1024),
+        _ => (value, 1),
+    };
+
+    match digits.parse::<u64>() {
+        Ok(n) if n > 0 => n.checked_mul(multiplier).ok_or_else(|| format!("size '{}' is too large", value)),
+        _ => Err(format!("invalid size '{}' (expected bytes, e.g. 500k, 2M, or 1G)", value)),
+    }
+}
+
+/// Parses an interval such as `90`, `30s`, `5m`, or `1h` (bare numbers are seconds)
+pub fn parse_interval(value: &str) -> Result<Duration, String> {
+    let value = value.trim();
+    let (digits, unit) = match value.chars().last().map(|c| c.to_ascii_lowercase()) {
+        Some('s') => (&value[..value.len() - 1], 1),
+        Some('m') => (&value[..value.len() - 1], 60),
+        Some('h') => (&value[..value.len() - 1], 60 * 60),
+        Some('d') => (&value[..value.len() - 1], 24 * 60 * 60),
+        _ => (value, 1),
+    };
+
+    match digits.parse::<u64>() {
+        Ok(n) if n > 0 => Ok(Duration::from_secs(n * unit)),
+        _ => Err(format!("invalid interval '{}' (expected e.g. 30s, 5m, 1h, or 7d)", value)),
+    }
+}
+
+/// Runs an idempotent network operation, retrying transient failures with exponential backoff
+pub async fn with_retries<T, F, Fut>(opts: &NetOptions, mut operation: F) -> Result<T, Box<dyn std::error::Error>>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, Box<dyn std::error::Error>>>,
+{
+    let mut attempt = 0;
+    let mut delay = opts.backoff;
+
+    loop {
+        match operation().await {
+            Ok(value) => return Ok(value),
+            Err(e) if attempt < opts.retries && is_transient(e.as_ref()) => {
+                attempt += 1;
+                warn!(
+                    "⚠️  Network error: {} - retrying in {:.1}s (attempt {}/{})",
+                    e, delay.as_secs_f64(), attempt, opts.retries
+                );
+                tokio::time::sleep(delay).await;
+                delay = (delay * 2).min(MAX_BACKOFF);
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Error returned when the user interrupts a network operation with Ctrl-C
+#[derive(Debug)]
+pub struct Cancelled;
+
+impl std::fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "operation cancelled by user")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+/// Returns true for connection-level failures that are worth retrying
+pub fn is_transient(error: &(dyn std::error::Error + 'static)) -> bool {
+    let Some(io_error) = error.downcast_ref::<io::Error>() else {
+        return false;
+    };
+
+    matches!(
+        io_error.kind(),
+        io::ErrorKind::TimedOut
+            | io::ErrorKind::ConnectionRefused
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::NotConnected
+            | io::ErrorKind::BrokenPipe
+            | io::ErrorKind::UnexpectedEof
+            | io::ErrorKind::Interrupted
+    )
+}
+
+/// Reader wrapper that fails with `TimedOut` if no data arrives within the read timeout
+pub struct TimeoutReader<R> {
+    inner: R,
+    timeout: Option<Duration>,
+    deadline: Option<Pin<Box<Sleep>>>,
+}
+
+impl<R> TimeoutReader<R> {
+    pub fn new(inner: R, timeout: Option<Duration>) -> Self {
+        Self { inner, timeout, deadline: None }
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for TimeoutReader<R> {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        let this = &mut *self;
+
+        match Pin::new(&mut this.inner).poll_read(cx, buf) {
+            Poll::Ready(result) => {
+                this.deadline = None;
+                Poll::Ready(result)
+            }
+            Poll::Pending => {
+                let Some(timeout) = this.timeout else {
+                    return Poll::Pending;
+                };
+                let deadline = this.deadline.get_or_insert_with(|| Box::pin(tokio::time::sleep(timeout)));
+                match deadline.as_mut().poll(cx) {
+                    Poll::Ready(()) => {
+                        this.deadline = None;
+                        Poll::Ready(Err(io::Error::new(
+                            io::ErrorKind::TimedOut,
+                            format!("no data received from server for {}s", timeout.as_secs()),
+                        )))
+                    }
+                    Poll::Pending => Poll::Pending,
+                }
+            }
+        }
+    }
+}
+
+/// Shared byte counters for both directions of a connection
+#[derive(Debug, Clone, Default)]
+pub struct WireCounters {
+    sent: Arc<AtomicU64>,
+    received: Arc<AtomicU64>,
+}
+
+impl WireCounters {
+    /// Returns (bytes sent, bytes received)
+    pub fn totals(&self) -> (u64, u64) {
+        (self.sent.load(Ordering::Relaxed), self.received.load(Ordering::Relaxed))
+    }
+}
+
+/// Stream wrapper that adds every byte read or written to a shared counter
+struct Counting<S> {
+    inner: S,
+    counter: Arc<AtomicU64>,
+}
+
+impl<S> Counting<S> {
+    fn new(inner: S, counter: Arc<AtomicU64>) -> Self {
+        Self { inner, counter }
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for Counting<S> {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        let before = buf.filled().len();
+        let result = Pin::new(&mut self.inner).poll_read(cx, buf);
+        if let Poll::Ready(Ok(())) = result {
+            self.counter.fetch_add((buf.filled().len() - before) as u64, Ordering::Relaxed);
+        }
+        result
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for Counting<S> {
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        let result = Pin::new(&mut self.inner).poll_write(cx, buf);
+        if let Poll::Ready(Ok(written)) = result {
+            self.counter.fetch_add(written as u64, Ordering::Relaxed);
+        }
+        result
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vnp::VnpCommand;
+
+    #[tokio::test]
+    async fn test_declined_encryption_fails_closed() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = OrbitUrl::parse(&format!("orbit://{}", listener.local_addr().unwrap())).unwrap();
+        // An old server: every SecureHello is answered with an Error
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let (mut reader, mut writer) = stream.into_split();
+                if let Ok(VnpCommand::SecureHello { .. }) = vnp::recv_command(&mut reader).await {
+                    let _ = vnp::send_command(&mut writer, VnpCommand::Error("unsupported command".to_string())).await;
+                }
+            }
+        });
+
+        let options = NetOptions { retries: 0, ..NetOptions::default() };
+        assert!(connect(&url, &options, &WireCounters::default()).await.is_err());
+        let options = NetOptions { allow_plaintext: true, ..options };
+        let connected = connect(&url, &options, &WireCounters::default()).await.unwrap();
+        assert!(matches!(connected, Negotiated::Plaintext(..)));
+    }
+}
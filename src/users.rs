@@ -0,0 +1,301 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use rand_core::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+use crate::cert;
+use tracing::info;
+
+/// Accounts of an `orb serve` instance, kept in `users.json` in the server config directory.
+/// Only SHA-256 digests of tokens are stored, so the file does not leak usable credentials.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct UserStore {
+    #[serde(default)]
+    pub users: BTreeMap<String, User>,
+    /// Tokens that were revoked, refused with a message of their own
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub revoked: Vec<Revocation>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct User {
+    #[serde(default)]
+    pub tokens: Vec<Token>,
+}
+
+/// One of a user's tokens
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(from = "StoredToken")]
+pub struct Token {
+    /// Hex SHA-256 digest of the token
+    pub digest: String,
+    /// Unix time it was issued (0 for tokens issued before this was recorded)
+    pub created: u64,
+    /// Unix time after which it is refused; None if it never expires
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires: Option<u64>,
+}
+
+/// Token entries as read from `users.json`, where older servers stored bare digests
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredToken {
+    Digest(String),
+    Record {
+        digest: String,
+        #[serde(default)]
+        created: u64,
+        #[serde(default)]
+        expires: Option<u64>,
+    },
+}
+
+impl From<StoredToken> for Token {
+    fn from(stored: StoredToken) -> Self {
+        match stored {
+            StoredToken::Digest(digest) => Token { digest, created: 0, expires: None },
+            StoredToken::Record { digest, created, expires } => Token { digest, created, expires },
+        }
+    }
+}
+
+impl Token {
+    /// Short public name of the token: the start of its digest, so whoever holds a token
+    /// can work out its ID but not the other way around
+    pub fn id(&self) -> &str {
+        &self.digest[..self.digest.len().min(12)]
+    }
+
+    pub fn is_expired(&self, now: u64) -> bool {
+        self.expires.is_some_and(|expires| expires <= now)
+    }
+
+    /// The token's metadata for the admin API
+    pub fn to_json(&self, now: u64) -> serde_json::Value {
+        serde_json::json!({
+            "id": self.id(),
+            "created": (self.created > 0).then(|| timestamp(self.created)),
+            "expires": self.expires.map(timestamp),
+            "expired": self.is_expired(now),
+        })
+    }
+}
+
+/// A revoked token, kept so it is refused as revoked rather than unknown
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Revocation {
+    pub digest: String,
+    pub user: String,
+    /// Unix time of the revocation
+    pub revoked: u64,
+}
+
+/// Why a token was refused
+#[derive(Debug, PartialEq, thiserror::Error)]
+pub enum TokenError {
+    #[error("invalid token")]
+    Unknown,
+    #[error("token has expired")]
+    Expired,
+    #[error("token has been revoked")]
+    Revoked,
+}
+
+impl UserStore {
+    pub fn path(dir: &Path) -> PathBuf {
+        dir.join("users.json")
+    }
+
+    /// Reads the store, treating a missing file as an empty one
+    pub fn load(dir: &Path) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        match std::fs::read(Self::path(dir)) {
+            Ok(data) => Ok(serde_json::from_slice(&data)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn save(&self, dir: &Path) -> std::io::Result<()> {
+        std::fs::create_dir_all(dir)?;
+        cert::write_private(&Self::path(dir), &serde_json::to_vec_pretty(self)?)
+    }
+
+    /// The user a token belongs to, unless it is unknown, expired, or revoked
+    pub fn authenticate(&self, token: &str) -> Result<&str, TokenError> {
+        let digest = token_digest(token);
+        if self.revoked.iter().any(|revocation| revocation.digest == digest) {
+            return Err(TokenError::Revoked);
+        }
+        for (name, user) in &self.users {
+            if let Some(token) = user.tokens.iter().find(|token| token.digest == digest) {
+                return match token.is_expired(now()) {
+                    true => Err(TokenError::Expired),
+                    false => Ok(name.as_str()),
+                };
+            }
+        }
+        Err(TokenError::Unknown)
+    }
+
+    /// Creates a new token for `username` (adding the account if needed) and returns it;
+    /// the token itself is not stored and cannot be shown again
+    pub fn issue_token(&mut self, username: &str, lifetime: Option<Duration>) -> String {
+        let mut bytes = [0u8; 32];
+        rand_core::OsRng.fill_bytes(&mut bytes);
+        let token = format!("orb_{}", hex(&bytes));
+        let created = now();
+        let expires = lifetime.map(|lifetime| created + lifetime.as_secs());
+        self.users.entry(username.to_string()).or_default().tokens.push(Token { digest: token_digest(&token), created, expires });
+        token
+    }
+
+    /// Revokes the user's token with this ID; false if there is none
+    pub fn revoke(&mut self, username: &str, id: &str) -> bool {
+        let Some(user) = self.users.get_mut(username) else { return false };
+        let Some(index) = user.tokens.iter().position(|token| token.id() == id) else { return false };
+        let token = user.tokens.remove(index);
+        self.revoked.push(Revocation { digest: token.digest, user: username.to_string(), revoked: now() });
+        true
+    }
+
+    /// Revokes a token given by value, returning its owner
+    pub fn revoke_token(&mut self, token: &str) -> Option<String> {
+        let digest = token_digest(token);
+        let (username, id) = self
+            .users
+            .iter()
+            .find_map(|(name, user)| user.tokens.iter().find(|token| token.digest == digest).map(|token| (name.clone(), token.id().to_string())))?;
+        self.revoke(&username, &id);
+        Some(username)
+    }
+
+    /// Revokes every token of a user being deleted
+    pub fn remove_user(&mut self, username: &str) -> bool {
+        let Some(user) = self.users.remove(username) else { return false };
+        let revoked = now();
+        self.revoked.extend(user.tokens.into_iter().map(|token| Revocation { digest: token.digest, user: username.to_string(), revoked }));
+        true
+    }
+
+    /// Replaces the user's token `id` with a new one, which keeps the old token's lifetime
+    /// unless `lifetime` is given. The old token is revoked, or expires after `grace` so
+    /// clients can switch over. None if the user has no such token.
+    pub fn rotate(&mut self, username: &str, id: &str, lifetime: Option<Duration>, grace: Option<Duration>) -> Option<String> {
+        let old = self.users.get(username)?.tokens.iter().find(|token| token.id() == id)?.clone();
+        let lifetime = lifetime.or_else(|| {
+            let expires = old.expires?;
+            (old.created > 0).then(|| Duration::from_secs(expires.saturating_sub(old.created)))
+        });
+        let token = self.issue_token(username, lifetime);
+        match grace {
+            Some(grace) => {
+                let until = now() + grace.as_secs();
+                let user = self.users.get_mut(username).expect("looked up above");
+                let old = user.tokens.iter_mut().find(|token| token.id() == id).expect("looked up above");
+                old.expires = Some(old.expires.map_or(until, |expires| expires.min(until)));
+            }
+            None => {
+                self.revoke(username, id);
+            }
+        }
+        Some(token)
+    }
+}
+
+pub fn token_digest(token: &str) -> String {
+    hex(&Sha256::digest(token.as_bytes()))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+/// A Unix time as RFC 3339, the format of the audit log
+pub fn timestamp(secs: u64) -> String {
+    OffsetDateTime::from_unix_timestamp(secs as i64).ok().and_then(|time| time.format(&Rfc3339).ok()).unwrap_or_default()
+}
+
+/// `orb serve token <user>`: issues a token from the command line, valid for `lifetime`
+pub fn run_issue(dir: &Path, username: &str, lifetime: Option<Duration>) -> Result<(), Box<dyn std::error::Error>> {
+    let mut store = UserStore::load(dir).map_err(|e| e.to_string())?;
+    let created = !store.users.contains_key(username);
+    let token = store.issue_token(username, lifetime);
+    store.save(dir)?;
+
+    if created {
+        info!("👤 Created user {}", username);
+    }
+    println!("🔑 Token for {}: {}", username, token);
+    if let Some(lifetime) = lifetime {
+        info!("⏳ Expires {}", timestamp(now() + lifetime.as_secs()));
+    }
+    info!("💡 Give it to the user once; only its digest is stored in {}", UserStore::path(dir).display());
+    println!("   export ORBIT_TOKEN=\"{}\"", token);
+    Ok(())
+}
+
+/// `orb serve token <user> --list`: shows the user's tokens without revealing them
+pub fn run_list(dir: &Path, username: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let store = UserStore::load(dir).map_err(|e| e.to_string())?;
+    let user = store.users.get(username).ok_or_else(|| format!("no user {}", username))?;
+    if user.tokens.is_empty() {
+        println!("🔑 {} has no tokens", username);
+    }
+    let now = now();
+    for token in &user.tokens {
+        let created = if token.created > 0 { timestamp(token.created) } else { "unknown".to_string() };
+        let expires = match token.expires {
+            Some(expires) if expires <= now => format!("expired {}", timestamp(expires)),
+            Some(expires) => format!("expires {}", timestamp(expires)),
+            None => "never expires".to_string(),
+        };
+        println!("🔑 {}  issued {}, {}", token.id(), created, expires);
+    }
+    Ok(())
+}
+
+/// `orb serve token <user> --revoke <id>`
+pub fn run_revoke(dir: &Path, username: &str, id: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let mut store = UserStore::load(dir).map_err(|e| e.to_string())?;
+    if !store.revoke(username, id) {
+        return Err(format!("{} has no token {}", username, id).into());
+    }
+    store.save(dir)?;
+    info!("🚫 Revoked token {} of {}", id, username);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expired_rotated_and_revoked_tokens_are_refused() {
+        let mut store: UserStore = serde_json::from_str(&format!(
+            r#"{{"users": {{"alice@example.com": {{"tokens": ["{}"]}}}}}}"#,
+            token_digest("orb_legacy")
+        ))
+        .unwrap();
+        assert_eq!(store.authenticate("orb_legacy"), Ok("alice@example.com"));
+
+        let legacy = token_digest("orb_legacy")[..12].to_string();
+        let rotated = store.rotate("alice@example.com", &legacy, Some(Duration::from_secs(3600)), None).unwrap();
+        assert_eq!(store.authenticate("orb_legacy"), Err(TokenError::Revoked));
+        assert_eq!(store.authenticate(&rotated), Ok("alice@example.com"));
+
+        store.users.get_mut("alice@example.com").unwrap().tokens[0].expires = Some(now() - 1);
+        assert_eq!(store.authenticate(&rotated), Err(TokenError::Expired));
+        assert_eq!(store.authenticate("orb_unknown"), Err(TokenError::Unknown));
+
+        let spare = store.issue_token("alice@example.com", None);
+        assert_eq!(store.revoke_token(&spare).as_deref(), Some("alice@example.com"));
+        assert_eq!(store.authenticate(&spare), Err(TokenError::Revoked));
+    }
+}
@@ -0,0 +1,283 @@
+use std::collections::{BTreeMap, HashSet};
+use std::error::Error;
+use std::fmt;
+use std::io::Write;
+use tracing::{error, info, warn};
+use orbit_core::vos::ObjectStore;
+use crate::objects::{Commit, Directory, File, ObjectId, Tag};
+use crate::progress::Progress;
+use crate::{ancestry, journal, refs, vos};
+
+/// Mode of directory entries that are trees
+const TREE_MODE: u32 = 0o040000;
+
+/// Objects reachable from the verified refs are missing or unreadable
+#[derive(Debug, thiserror::Error)]
+#[error("{0} reachable object(s) are missing or corrupt")]
+pub struct IncompleteGraph(pub usize);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kind {
+    Tag,
+    Commit,
+    Tree,
+    File,
+    Chunk,
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Kind::Tag => "tag",
+            Kind::Commit => "commit",
+            Kind::Tree => "tree",
+            Kind::File => "file",
+            Kind::Chunk => "chunk",
+        })
+    }
+}
+
+/// A reachable object that is absent, or present but unusable
+#[derive(Debug)]
+pub enum Problem {
+    Missing { kind: Kind, id: ObjectId },
+    Corrupt { kind: Kind, id: ObjectId, reason: String },
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Problem::Missing { kind, id } => write!(f, "missing {} {}", kind, id),
+            Problem::Corrupt { kind, id, reason } => write!(f, "corrupt {} {}: {}", kind, id, reason),
+        }
+    }
+}
+
+/// What a walk checked and what it found wrong
+#[derive(Debug, Default)]
+pub struct GraphReport {
+    pub commits: usize,
+    pub trees: usize,
+    pub files: usize,
+    pub chunks: usize,
+    pub problems: Vec<Problem>,
+    /// Commits whose own tree (not their ancestors') has a problem
+    pub incomplete_commits: Vec<ObjectId>,
+}
+
+/// Walks the object graphs reachable from `tips`: tag objects, commits and all their
+/// ancestors, trees, files, and chunks. Every object read must parse and hash to its
+/// ID; chunks are only checked for existence unless `hash_chunks` is set, as reading
+/// them means reading all content. Commits in `excluded` (and so their ancestors)
+/// are not walked.
+pub fn walk(store: &ObjectStore, tips: Vec<(ObjectId, Kind)>, excluded: &HashSet<ObjectId>, hash_chunks: bool, progress: &Progress) -> GraphReport {
+    let mut walk = Walk { store, hash_chunks, progress, seen: HashSet::new(), report: GraphReport::default() };
+    let mut commits = Vec::new();
+    for (id, kind) in tips {
+        match kind {
+            Kind::Tag => {
+                if let Some(tag) = walk.read::<Tag>(&id, Kind::Tag) {
+                    commits.push(tag.object);
+                }
+            }
+            _ => commits.push(id),
+        }
+    }
+
+    while let Some(id) = commits.pop() {
+        if excluded.contains(&id) || !walk.seen.insert(id.clone()) {
+            continue;
+        }
+        let Some(commit) = walk.read::<Commit>(&id, Kind::Commit) else { continue };
+        walk.report.commits += 1;
+        commits.extend(commit.parents);
+        let problems = walk.report.problems.len();
+        walk.tree(commit.tree);
+        if walk.report.problems.len() > problems {
+            walk.report.incomplete_commits.push(id);
+        }
+    }
+    walk.report
+}
+
+struct Walk<'a> {
+    store: &'a ObjectStore,
+    hash_chunks: bool,
+    progress: &'a Progress,
+    seen: HashSet<ObjectId>,
+    report: GraphReport,
+}
+
+impl Walk<'_> {
+    fn tree(&mut self, tree: ObjectId) {
+        let mut trees = vec![tree];
+        while let Some(id) = trees.pop() {
+            if !self.seen.insert(id.clone()) {
+                continue;
+            }
+            let Some(directory) = self.read::<Directory>(&id, Kind::Tree) else { continue };
+            self.report.trees += 1;
+            for entry in directory.entries {
+                if entry.mode == TREE_MODE {
+                    trees.push(entry.id);
+                } else if self.seen.insert(entry.id.clone()) {
+                    let Some(file) = self.read::<File>(&entry.id, Kind::File) else { continue };
+                    self.report.files += 1;
+                    for chunk in file.chunk_ids() {
+                        if self.seen.insert(chunk.clone()) {
+                            self.chunk(chunk);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn chunk(&mut self, id: &ObjectId) {
+        self.report.chunks += 1;
+        if !self.hash_chunks {
+            self.progress.advance(0);
+            if !self.store.contains(id) {
+                self.report.problems.push(Problem::Missing { kind: Kind::Chunk, id: id.clone() });
+            }
+            return;
+        }
+        let _ = self.load(id, Kind::Chunk);
+    }
+
+    /// Loads an object and checks that its content matches its ID
+    fn load(&mut self, id: &ObjectId, kind: Kind) -> Option<Vec<u8>> {
+        let problem = match self.store.load(id) {
+            Ok(data) if vos::hash_data(&data) == *id => {
+                self.progress.advance(data.len() as u64);
+                return Some(data);
+            }
+            Ok(_) => Problem::Corrupt { kind, id: id.clone(), reason: "content does not match its ID".to_string() },
+            Err(orbit_core::Error::MissingObject(_)) => Problem::Missing { kind, id: id.clone() },
+            Err(e) => Problem::Corrupt { kind, id: id.clone(), reason: e.to_string() },
+        };
+        self.report.problems.push(problem);
+        None
+    }
+
+    fn read<T: serde::de::DeserializeOwned>(&mut self, id: &ObjectId, kind: Kind) -> Option<T> {
+        let data = self.load(id, kind)?;
+        match serde_json::from_slice(&data) {
+            Ok(object) => Some(object),
+            Err(e) => {
+                self.report.problems.push(Problem::Corrupt { kind, id: id.clone(), reason: e.to_string() });
+                None
+            }
+        }
+    }
+}
+
+/// Every ref (and a detached HEAD) with the kind of object it points to
+fn ref_tips() -> Vec<(ObjectId, Kind)> {
+    let mut tips: Vec<(ObjectId, Kind)> = refs::snapshot()
+        .into_iter()
+        .map(|(name, id)| (id, ref_kind(&name)))
+        .collect();
+    tips.extend(refs::head_commit().map(|head| (head, Kind::Commit)));
+    tips
+}
+
+/// Annotation refs point at Tag objects, every other ref at a commit
+fn ref_kind(name: &str) -> Kind {
+    if name.starts_with("refs/annotations/") { Kind::Tag } else { Kind::Commit }
+}
+
+/// `orb verify-graph`: checks that every object reachable from `revision` (default: every
+/// ref) is stored, parses, and matches its ID, chunks included. Problems are printed
+/// one per line.
+pub fn verify_graph(revision: Option<&str>) -> Result<(), Box<dyn Error>> {
+    crate::repo::open()?;
+    let tips = match revision {
+        Some(revision) => vec![(refs::resolve(revision)?, Kind::Commit)],
+        None => ref_tips(),
+    };
+    let progress = Progress::new();
+    progress.begin("Verifying", None);
+    let report = walk(&vos::store(), tips, &HashSet::new(), true, &progress);
+    progress.finish();
+
+    let mut out = std::io::stdout().lock();
+    for problem in &report.problems {
+        writeln!(out, "{}", problem)?;
+    }
+    if !report.problems.is_empty() {
+        return Err(IncompleteGraph(report.problems.len()).into());
+    }
+    info!(
+        "✅ Verified {} commits, {} trees, {} files, and {} chunks",
+        report.commits, report.trees, report.files, report.chunks
+    );
+    Ok(())
+}
+
+/// Run after a clone or sync: checks the graphs of the commits that refs moved to since
+/// `before` (history reachable from `before` was already present). Commits with missing
+/// or corrupt objects are recorded in the sync journal, so the next sync downloads what
+/// they lack.
+pub fn check_transfer(before: &BTreeMap<String, ObjectId>, repository: Option<&str>) -> Result<(), Box<dyn Error>> {
+    let tips: Vec<(ObjectId, Kind)> = refs::changes(before, &refs::snapshot())
+        .into_iter()
+        .filter_map(|change| Some((change.new?, ref_kind(&change.name))))
+        .collect();
+    if tips.is_empty() {
+        return Ok(());
+    }
+    let excluded: HashSet<ObjectId> = before.values().flat_map(|id| ancestry::ancestors(id)).collect();
+    let report = walk(&vos::store(), tips, &excluded, false, &Progress::new());
+    if report.problems.is_empty() {
+        info!("🔎 Verified {} new commit{} and their objects", report.commits, if report.commits == 1 { "" } else { "s" });
+        return Ok(());
+    }
+
+    for problem in report.problems.iter().take(10) {
+        error!("❌ {}", problem);
+    }
+    if report.problems.len() > 10 {
+        error!("❌ ...and {} more", report.problems.len() - 10);
+    }
+    journal::SyncJournal::begin(repository, &report.incomplete_commits)?;
+    warn!("💡 Run `orb sync` to download the missing objects, or `orb verify-graph` for a full check.");
+    Err(IncompleteGraph(report.problems.len()).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::objects::DirectoryEntry;
+
+    #[test]
+    fn test_walk_reports_missing_and_corrupt_objects() {
+        let dir = std::env::temp_dir().join(format!("orb-verify-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let store = ObjectStore::new(&dir);
+        let (kept, _) = store.hash_stream(&b"kept"[..], &mut Default::default()).unwrap();
+        let (lost, _) = store.save_stream(&b"lost"[..], &mut Default::default()).unwrap();
+        store.save_stream(&b"kept"[..], &mut Default::default()).unwrap();
+        let file: File = serde_json::from_slice(&store.load(&lost).unwrap()).unwrap();
+        std::fs::remove_file(store.object_path(&file.root_chunk_id).unwrap()).unwrap();
+
+        let entries = vec![
+            DirectoryEntry { mode: 0o100644, name: "kept".to_string(), id: kept },
+            DirectoryEntry { mode: 0o100644, name: "lost".to_string(), id: lost },
+        ];
+        let tree = store.save_json(&Directory { entries }).unwrap();
+        let commit = Commit { tree, parents: vec!["ab".repeat(32)], author: "a".to_string(), timestamp: 0, message: "m".to_string(), signature: None };
+        let commit = store.save_json(&commit).unwrap();
+
+        let report = walk(&store, vec![(commit.clone(), Kind::Commit)], &HashSet::new(), true, &Progress::new());
+        let problems: Vec<String> = report.problems.iter().map(|p| p.to_string()).collect();
+        assert_eq!(problems, [format!("missing chunk {}", file.root_chunk_id), format!("missing commit {}", "ab".repeat(32))]);
+        assert_eq!((report.commits, report.trees, report.files, report.chunks), (1, 1, 2, 2));
+        assert_eq!(report.incomplete_commits, std::slice::from_ref(&commit));
+
+        std::fs::write(store.object_path(&file.root_chunk_id).unwrap(), b"tampered").unwrap();
+        let report = walk(&store, vec![(commit, Kind::Commit)], &HashSet::new(), true, &Progress::new());
+        assert!(report.problems[0].to_string().starts_with("corrupt chunk"));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
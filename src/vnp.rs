@@ -1,17 +1,61 @@
 use serde::{Serialize, Deserialize};
 use crate::objects::ObjectId;
+use crate::vos;
+use std::collections::BTreeMap;
+use std::future::Future;
 use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::time::{Instant, Sleep};
+use tracing::{debug, trace};
+
+/// Objects larger than this are streamed to/from disk instead of being buffered in memory
+pub const STREAMING_THRESHOLD: u64 = 64 * 1024 * 1024;
+
+/// Payload bytes carried by each ObjectData frame
+const CHUNK_SIZE: usize = 8192; // 8KB chunks for efficient streaming
+
+/// Largest command frame accepted, so a peer can't make us allocate up to 4 GB before
+/// it has even authenticated
+const MAX_FRAME_SIZE: usize = 16 * 1024 * 1024;
+
+/// ALPN protocol id TLS clients advertise, so a server can tell VNP from HTTPS on one port
+pub const VNP_ALPN: &[u8] = b"orbit-vnp/1";
+
+/// How often a side sends `Ping` while it is busy and the link would otherwise be idle
+pub const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(15);
 
 // --- VNP Command Types ---
 
+/// Session capability: fetch objects and refs
+pub const CAP_READ: &str = "read";
+/// Session capability: upload objects and move refs
+pub const CAP_WRITE: &str = "write";
+
+fn full_capabilities() -> Vec<String> {
+    vec![CAP_READ.to_string(), CAP_WRITE.to_string()]
+}
+
 /// Commands exchanged between the Orbit client and server.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum VnpCommand {
     /// Client: Authentication with API token
     Authenticate(String), // API token
     
-    /// Server: Authentication result
-    AuthResult { success: bool, message: String },
+    /// Client: Opens a session without a token; servers that allow it grant read access
+    /// to public repositories only.
+    AuthenticateAnonymous,
+    
+    /// Server: Authentication result, with what the session may do (see CAP_READ/CAP_WRITE)
+    AuthResult {
+        success: bool,
+        message: String,
+        /// Older servers omit this; their sessions are always read-write
+        #[serde(default = "full_capabilities")]
+        capabilities: Vec<String>,
+    },
     
     /// Client: Announces the commit IDs it possesses.
     Have(Vec<ObjectId>), 
@@ -22,17 +66,24 @@ pub enum VnpCommand {
     /// Client: Requests a specific VOS object (Commit, Tree, or File).
     Get(ObjectId), 
 
-    /// Server: Sends the object header (ID, type, and size).
+    /// Server: Sends the object header (ID, type, size, and payload checksum).
+    /// The payload follows as ObjectData frames, so `size` is not bounded by the
+    /// u32 frame length and objects larger than 4 GB can be transferred.
     ObjectHeader {
         id: ObjectId,
         object_type: String, // "commit", "tree", or "file"
-        size: usize,
+        size: u64,
+        /// SHA3 (Keccak-256) hex digest of the payload; absent from older peers. It
+        /// always equals `id`, which receivers verify instead
+        #[serde(default)]
+        checksum: Option<String>,
     },
 
     /// Server: Sends raw binary object data following ObjectHeader.
     ObjectData(Vec<u8>), 
     
-    /// Client: Push commits to server (sends list of commit IDs to upload)
+    /// Client: Push commits to server (sends list of commit IDs to upload). Tag objects
+    /// of annotated tags may be listed too; the server then also fetches their commits.
     Push(Vec<ObjectId>),
     
     /// Client: Pull commits from server (requests download of specific commits)  
@@ -41,6 +92,33 @@ pub enum VnpCommand {
     /// Server: Requests client to send a specific object
     SendObject(ObjectId),
     
+    /// Client: Asks the server to advertise its branches.
+    ListRefs,
+    
+    /// Server: Branches and tags with the objects they point to.
+    RefList(RefAdvertisement),
+    
+    /// Client: Moves a branch after its objects were pushed. `old` is the tip the client last
+    /// saw (if known). Unless `force` is set, the server rejects updates where `new` does not
+    /// descend from its current tip. The server replies Ok or Error.
+    UpdateRef {
+        branch: String,
+        old: Option<ObjectId>,
+        new: ObjectId,
+        force: bool,
+    },
+    
+    /// Client: Creates or moves tags after their objects were pushed. Without `force`
+    /// the server refuses to move a tag that already points elsewhere.
+    PushTags {
+        tags: BTreeMap<String, ObjectId>,
+        force: bool,
+        /// Tag objects of the annotated tags among them (pushed like commits); older
+        /// clients omit this
+        #[serde(default)]
+        annotations: BTreeMap<String, ObjectId>,
+    },
+    
     /// Client: Requests complete object graph for a commit (recursively gets trees, files, chunks)
     GetCompleteGraph(ObjectId),
     
@@ -57,12 +135,42 @@ pub enum VnpCommand {
     SelectRepository(String),
     /// Client: Create new repository
     CreateRepository(String),
-    /// Server: Respond with available repositories
-    RepositoryList(Vec<String>),
+    /// Client: Permanently delete a repository in the caller's namespace (server replies Ok or Error)
+    DeleteRepository(String),
+    /// Client: Rename a repository within the caller's namespace (server replies Ok or Error)
+    RenameRepository { from: String, to: String },
+    /// Server: Respond with available repositories and their metadata
+    RepositoryList(Vec<RepositoryInfo>),
     /// Server: Confirm repository selection
     RepositorySelected(String),
     
+    /// Client: Asks how much data the object graphs of these commits contain (used by dry runs).
+    EstimateGraph(Vec<ObjectId>),
+
+    /// Server: Object count and total payload bytes reachable from the requested commits.
+    GraphEstimate { objects: u64, bytes: u64 },
+
+    /// Client: Opens the hybrid ML-KEM-768 + X25519 key exchange on a plain TCP connection
+    /// (base64 public keys). Servers without secure channel support reply Error.
+    SecureHello { x25519: String, ml_kem: String },
+
+    /// Server: Completes the key exchange (base64 X25519 key and ML-KEM ciphertext); every
+    /// frame after this one is sent inside ChaCha20-Poly1305 records.
+    SecureAccept { x25519: String, ciphertext: String },
+
+    /// Either side: Keepalive sent during long quiet periods (e.g. object discovery or
+    /// a server's history walk). Servers answer a Ping received between requests with Pong.
+    Ping,
+
+    /// Either side: Reply to a Ping; carries no data.
+    Pong,
+
+    /// Client: Abandons the current operation (e.g. Ctrl-C); the server discards partial uploads.
+    Abort(String),
+
     /// Status command used by either side to signal phase transition.
+    /// After the server's Ok the connection stays open for the next operation,
+    /// which may select a different repository.
     Ready, 
 
     /// Server: Signals successful operation (e.g., ref update confirmed).
@@ -72,6 +180,165 @@ pub enum VnpCommand {
     Error(String), 
 }
 
+impl VnpCommand {
+    /// The frame as shown in debug logs: tokens are redacted, and object payloads and
+    /// key-exchange material are reduced to their size
+    pub fn frame_summary(&self) -> String {
+        match self {
+            VnpCommand::Authenticate(_) => "Authenticate(<token>)".to_string(),
+            VnpCommand::ObjectData(data) => format!("ObjectData({} bytes)", data.len()),
+            VnpCommand::SecureHello { .. } => "SecureHello".to_string(),
+            VnpCommand::SecureAccept { .. } => "SecureAccept".to_string(),
+            command => format!("{:?}", command),
+        }
+    }
+}
+
+/// Refs advertised by the server in reply to ListRefs
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RefAdvertisement {
+    /// Branch name → tip commit
+    pub branches: BTreeMap<String, ObjectId>,
+    /// Tag name → tagged object
+    #[serde(default)]
+    pub tags: BTreeMap<String, ObjectId>,
+    /// Tag name → Tag object, for annotated tags; older servers omit this
+    #[serde(default)]
+    pub annotations: BTreeMap<String, ObjectId>,
+}
+
+/// A repository entry in RepositoryList. Older servers send bare names, which
+/// deserialize to an entry with only `name` set.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(from = "RepositoryEntry")]
+pub struct RepositoryInfo {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default_branch: Option<String>,
+    /// Tip commit of the default branch
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub head: Option<ObjectId>,
+    /// Total bytes of stored objects
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+    /// Unix timestamp of the last ref update
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_updated: Option<i64>,
+    /// Most bytes the repository may store under the server's quotas
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quota: Option<u64>,
+}
+
+/// Wire forms accepted for a RepositoryList entry
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RepositoryEntry {
+    Name(String),
+    Info {
+        name: String,
+        #[serde(default)]
+        description: Option<String>,
+        #[serde(default)]
+        default_branch: Option<String>,
+        #[serde(default)]
+        head: Option<ObjectId>,
+        #[serde(default)]
+        size: Option<u64>,
+        #[serde(default)]
+        last_updated: Option<i64>,
+        #[serde(default)]
+        quota: Option<u64>,
+    },
+}
+
+impl From<RepositoryEntry> for RepositoryInfo {
+    fn from(entry: RepositoryEntry) -> Self {
+        match entry {
+            RepositoryEntry::Name(name) => Self { name, ..Self::default() },
+            RepositoryEntry::Info { name, description, default_branch, head, size, last_updated, quota } => {
+                Self { name, description, default_branch, head, size, last_updated, quota }
+            }
+        }
+    }
+}
+
+// --- VNP Protocol Errors ---
+
+/// Protocol-level failures of a VNP session, with the phase of the exchange they
+/// happened in (e.g. "negotiation", "upload", "finalization").
+#[derive(Debug, thiserror::Error)]
+pub enum VnpError {
+    /// A received payload does not hash to its object ID.
+    #[error("checksum mismatch for object {id} (expected {expected}, got {actual})")]
+    ChecksumMismatch {
+        id: ObjectId,
+        expected: String,
+        actual: String,
+    },
+    /// The server answered with an Error command
+    #[error("server error during {phase}: {message}")]
+    Server { phase: &'static str, message: String },
+    /// The server refused a ref or tag update
+    #[error("{phase} rejected: {message}")]
+    Rejected { phase: &'static str, message: String },
+    /// The repository named in a session does not exist on the server
+    #[error("repository '{0}' does not exist on the server")]
+    NoSuchRepository(String),
+    /// The server sent a command the protocol does not allow at this point
+    #[error("unexpected server response during {phase}")]
+    Unexpected { phase: &'static str },
+    /// The server could not send a requested object
+    #[error("server could not send {kind} {id}: {message}")]
+    ObjectUnavailable { kind: String, id: ObjectId, message: String },
+    /// The server sent another kind of object than the one requested
+    #[error("expected {expected} object {id}, got a {actual} object")]
+    WrongObjectType { id: ObjectId, expected: &'static str, actual: String },
+    /// The server asked for an object that is not part of what is being uploaded
+    #[error("server requested unexpected object {0}")]
+    UnrequestedObject(ObjectId),
+    /// The server sent another object than the one requested
+    #[error("requested object {requested}, got {sent}")]
+    WrongObject { requested: ObjectId, sent: ObjectId },
+    /// An object ID that is not 64 lowercase hex digits
+    #[error("invalid object id: {0}")]
+    InvalidObjectId(String),
+}
+
+impl VnpError {
+    /// The error for a response other than the ones expected during `phase`
+    pub fn response(phase: &'static str, response: VnpCommand) -> Self {
+        match response {
+            VnpCommand::Error(message) => VnpError::Server { phase, message },
+            _ => VnpError::Unexpected { phase },
+        }
+    }
+}
+
+/// Computes the checksum carried in ObjectHeader for a payload.
+pub fn object_checksum(data: &[u8]) -> String {
+    vos::hash_data(data)
+}
+
+/// Verifies a received payload hashes to its object ID. IDs are content addresses, so
+/// this also covers the checksum in its ObjectHeader, which may be missing.
+pub fn verify_object(id: &str, data: &[u8]) -> Result<(), VnpError> {
+    verify_hash(id, object_checksum(data))
+}
+
+/// Checks `actual`, the hash of a payload received as object `id`, against the ID
+pub fn verify_hash(id: &str, actual: String) -> Result<(), VnpError> {
+    if actual != id {
+        return Err(VnpError::ChecksumMismatch {
+            id: id.to_string(),
+            expected: id.to_string(),
+            actual,
+        });
+    }
+    Ok(())
+}
+
 // --- VNP Network Utilities (Async Senders/Receivers) ---
 
 /// Sends a VnpCommand over an asynchronous stream.
@@ -79,6 +346,10 @@ pub async fn send_command<W: tokio::io::AsyncWriteExt + Unpin>(
     writer: &mut W,
     command: VnpCommand,
 ) -> io::Result<()> {
+    match command {
+        VnpCommand::Ping | VnpCommand::Pong => trace!("→ keepalive"),
+        _ => debug!("→ {}", command.frame_summary()),
+    }
     // Serialize the command into a JSON string (for MVP simplicity)
     let json_str = serde_json::to_string(&command).unwrap();
     
@@ -91,11 +362,46 @@ pub async fn send_command<W: tokio::io::AsyncWriteExt + Unpin>(
 }
 
 /// Receives a VnpCommand from an asynchronous stream.
+///
+/// Keepalive frames (Ping/Pong) only exist to keep the connection warm,
+/// so they are consumed here and never returned to the caller.
 pub async fn recv_command<R: tokio::io::AsyncReadExt + Unpin>(
     reader: &mut R,
 ) -> io::Result<VnpCommand> {
+    loop {
+        match recv_frame(reader).await? {
+            VnpCommand::Ping | VnpCommand::Pong => {}
+            command => return Ok(command),
+        }
+    }
+}
+
+/// Receives the next request of a session like `recv_command`, but answers each Ping
+/// with a Pong so the client knows the server is still there.
+pub async fn recv_request<R, W>(reader: &mut R, writer: &mut W) -> io::Result<VnpCommand>
+where
+    R: tokio::io::AsyncReadExt + Unpin,
+    W: tokio::io::AsyncWriteExt + Unpin,
+{
+    loop {
+        match recv_frame(reader).await? {
+            VnpCommand::Ping => send_command(writer, VnpCommand::Pong).await?,
+            VnpCommand::Pong => {}
+            command => return Ok(command),
+        }
+    }
+}
+
+/// Receives a single frame, keepalives included
+async fn recv_frame<R: tokio::io::AsyncReadExt + Unpin>(reader: &mut R) -> io::Result<VnpCommand> {
     // Read the command length
     let len = reader.read_u32().await? as usize;
+    if len > MAX_FRAME_SIZE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("command frame of {} bytes exceeds the {} byte limit", len, MAX_FRAME_SIZE),
+        ));
+    }
     
     // Read the command data
     let mut buffer = vec![0u8; len];
@@ -103,42 +409,290 @@ pub async fn recv_command<R: tokio::io::AsyncReadExt + Unpin>(
 
     // Deserialize the JSON command
     let command: VnpCommand = serde_json::from_slice(&buffer)?;
+    match command {
+        VnpCommand::Ping | VnpCommand::Pong => trace!("← keepalive"),
+        _ => debug!("← {}", command.frame_summary()),
+    }
     Ok(command)
 }
 
+/// Drives `work` to completion, sending a Ping every KEEPALIVE_INTERVAL while it runs
+/// so NATs and firewalls don't drop the idle connection.
+pub async fn with_keepalive<W, F, T>(writer: &mut W, work: F) -> io::Result<T>
+where
+    W: tokio::io::AsyncWriteExt + Unpin,
+    F: Future<Output = T>,
+{
+    tokio::pin!(work);
+    let start = tokio::time::Instant::now() + KEEPALIVE_INTERVAL;
+    let mut ticker = tokio::time::interval_at(start, KEEPALIVE_INTERVAL);
+
+    loop {
+        tokio::select! {
+            result = &mut work => return Ok(result),
+            _ = ticker.tick() => send_command(writer, VnpCommand::Ping).await?,
+        }
+    }
+}
+
 /// Sends raw object data in chunks over the stream
 pub async fn send_object_data<W: tokio::io::AsyncWriteExt + Unpin>(
     writer: &mut W,
     data: &[u8],
 ) -> io::Result<()> {
-    const CHUNK_SIZE: usize = 8192; // 8KB chunks for efficient streaming
-    
     for chunk in data.chunks(CHUNK_SIZE) {
         send_command(writer, VnpCommand::ObjectData(chunk.to_vec())).await?;
     }
     Ok(())
 }
 
+/// Streams a file as ObjectData frames without loading it into memory
+pub async fn send_object_file<W: tokio::io::AsyncWriteExt + Unpin>(
+    writer: &mut W,
+    path: &std::path::Path,
+) -> io::Result<()> {
+    use tokio::io::AsyncReadExt;
+
+    let mut file = tokio::fs::File::open(path).await?;
+    let mut buffer = vec![0u8; CHUNK_SIZE];
+    loop {
+        let read = file.read(&mut buffer).await?;
+        if read == 0 {
+            break;
+        }
+        send_command(writer, VnpCommand::ObjectData(buffer[..read].to_vec())).await?;
+    }
+    Ok(())
+}
+
+/// Sends the object stored at `path` as an ObjectHeader followed by its payload, or an
+/// Error if it is missing
+pub async fn send_stored_object<W: tokio::io::AsyncWriteExt + Unpin>(
+    writer: &mut W,
+    id: &str,
+    path: &std::path::Path,
+) -> io::Result<()> {
+    let Ok(size) = std::fs::metadata(path).map(|m| m.len()) else {
+        return send_command(writer, VnpCommand::Error(format!("object not found: {}", id))).await;
+    };
+
+    // Large chunks are hashed and streamed from disk rather than loaded whole
+    if size > STREAMING_THRESHOLD {
+        send_command(writer, VnpCommand::ObjectHeader {
+            id: id.to_string(),
+            object_type: "chunk".to_string(),
+            size,
+            checksum: Some(vos::hash_file(path)?),
+        }).await?;
+        return send_object_file(writer, path).await;
+    }
+
+    send_object_bytes(writer, id, &std::fs::read(path)?).await
+}
+
+/// Sends an object already in memory as an ObjectHeader followed by its payload
+pub async fn send_object_bytes<W: tokio::io::AsyncWriteExt + Unpin>(
+    writer: &mut W,
+    id: &str,
+    data: &[u8],
+) -> io::Result<()> {
+    send_command(writer, VnpCommand::ObjectHeader {
+        id: id.to_string(),
+        object_type: vos::object_type(data).to_string(),
+        size: data.len() as u64,
+        checksum: Some(object_checksum(data)),
+    }).await?;
+    send_object_data(writer, data).await
+}
+
+/// Receives object data straight into `file`, returning the SHA3 checksum of what was written
+pub async fn recv_object_to_file<R: tokio::io::AsyncReadExt + Unpin>(
+    reader: &mut R,
+    expected_size: u64,
+    file: &mut tokio::fs::File,
+) -> io::Result<String> {
+    use tokio::io::AsyncWriteExt;
+
+    let mut hasher = vos::StreamHasher::new();
+    let mut received = 0u64;
+
+    while received < expected_size {
+        match recv_command(reader).await? {
+            VnpCommand::ObjectData(chunk) => {
+                hasher.update(&chunk);
+                file.write_all(&chunk).await?;
+                received += chunk.len() as u64;
+            }
+            response => {
+                return Err(io::Error::other(VnpError::response("object transfer", response)));
+            }
+        }
+    }
+
+    file.flush().await?;
+    Ok(hasher.finish())
+}
+
 /// Receives object data by collecting ObjectData chunks until complete
 pub async fn recv_object_data<R: tokio::io::AsyncReadExt + Unpin>(
     reader: &mut R,
-    expected_size: usize,
+    expected_size: u64,
 ) -> io::Result<Vec<u8>> {
-    let mut received_data = Vec::with_capacity(expected_size);
+    let mut received_data = Vec::with_capacity(expected_size.min(STREAMING_THRESHOLD) as usize);
     
-    while received_data.len() < expected_size {
+    while (received_data.len() as u64) < expected_size {
         match recv_command(reader).await? {
             VnpCommand::ObjectData(chunk) => {
                 received_data.extend_from_slice(&chunk);
             }
-            VnpCommand::Error(msg) => {
-                return Err(io::Error::new(io::ErrorKind::Other, format!("Server error: {}", msg)));
-            }
-            _ => {
-                return Err(io::Error::new(io::ErrorKind::InvalidData, "Expected ObjectData"));
+            response => {
+                return Err(io::Error::other(VnpError::response("object transfer", response)));
             }
         }
     }
     
     Ok(received_data)
-}
\ No newline at end of file
+}
+
+// --- Bandwidth Limiting ---
+
+/// Token bucket allowing `rate` bytes per second with up to one second of burst.
+struct TokenBucket {
+    rate: f64,
+    tokens: f64,
+    last_refill: Instant,
+    sleep: Option<Pin<Box<Sleep>>>,
+}
+
+impl TokenBucket {
+    fn new(rate: u64) -> Self {
+        let rate = rate.max(1) as f64;
+        Self { rate, tokens: rate, last_refill: Instant::now(), sleep: None }
+    }
+
+    /// Resolves once there is budget for more traffic
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        loop {
+            if let Some(sleep) = self.sleep.as_mut() {
+                if sleep.as_mut().poll(cx).is_pending() {
+                    return Poll::Pending;
+                }
+                self.sleep = None;
+            }
+
+            let now = Instant::now();
+            let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+            self.tokens = (self.tokens + elapsed * self.rate).min(self.rate);
+            self.last_refill = now;
+
+            if self.tokens > 0.0 {
+                return Poll::Ready(());
+            }
+            let wait = Duration::from_secs_f64(-self.tokens / self.rate).max(Duration::from_millis(1));
+            self.sleep = Some(Box::pin(tokio::time::sleep(wait)));
+        }
+    }
+
+    /// Charges transferred bytes against the budget (may go negative, delaying the next call)
+    fn consume(&mut self, bytes: usize) {
+        self.tokens -= bytes as f64;
+    }
+}
+
+/// Stream wrapper that caps VNP throughput to a fixed number of bytes per second.
+///
+/// Each connection half gets its own bucket, so `--limit-rate` applies to
+/// uploads and downloads independently.
+pub struct Throttled<S> {
+    inner: S,
+    bucket: TokenBucket,
+}
+
+impl<S> Throttled<S> {
+    pub fn new(inner: S, bytes_per_second: u64) -> Self {
+        Self { inner, bucket: TokenBucket::new(bytes_per_second) }
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for Throttled<S> {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        let this = &mut *self;
+        if this.bucket.poll_ready(cx).is_pending() {
+            return Poll::Pending;
+        }
+
+        let before = buf.filled().len();
+        let result = Pin::new(&mut this.inner).poll_read(cx, buf);
+        if let Poll::Ready(Ok(())) = result {
+            this.bucket.consume(buf.filled().len() - before);
+        }
+        result
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for Throttled<S> {
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        let this = &mut *self;
+        if this.bucket.poll_ready(cx).is_pending() {
+            return Poll::Pending;
+        }
+
+        // Never write more than one second's worth of budget in a single call
+        let max = (this.bucket.rate as usize).max(1).min(buf.len());
+        let result = Pin::new(&mut this.inner).poll_write(cx, &buf[..max]);
+        if let Poll::Ready(Ok(written)) = result {
+            this.bucket.consume(written);
+        }
+        result
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_repository_list_accepts_bare_names() {
+        let json = r#"{"RepositoryList":["alice/old",{"name":"alice/new","default_branch":"main","size":42}]}"#;
+        let VnpCommand::RepositoryList(repos) = serde_json::from_str(json).unwrap() else {
+            panic!("expected RepositoryList");
+        };
+        assert_eq!(repos[0].name, "alice/old");
+        assert_eq!(repos[0].default_branch, None);
+        assert_eq!(repos[1].default_branch.as_deref(), Some("main"));
+        assert_eq!(repos[1].size, Some(42));
+    }
+
+    #[tokio::test]
+    async fn test_oversized_frame_is_rejected() {
+        let mut frame: &[u8] = &[0xff, 0xff, 0xff, 0xff];
+        let error = recv_command(&mut frame).await.unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[tokio::test]
+    async fn test_ping_between_requests_is_answered_with_pong() {
+        let (client, server) = tokio::io::duplex(1 << 16);
+        let (mut client_read, mut client_write) = tokio::io::split(client);
+        let (mut server_read, mut server_write) = tokio::io::split(server);
+
+        send_command(&mut client_write, VnpCommand::Ping).await.unwrap();
+        send_command(&mut client_write, VnpCommand::ListRefs).await.unwrap();
+        let request = recv_request(&mut server_read, &mut server_write).await.unwrap();
+        assert!(matches!(request, VnpCommand::ListRefs));
+        assert!(matches!(recv_frame(&mut client_read).await.unwrap(), VnpCommand::Pong));
+
+        // Clients waiting for a reply skip the keepalives in front of it
+        send_command(&mut server_write, VnpCommand::Ping).await.unwrap();
+        send_command(&mut server_write, VnpCommand::Ok).await.unwrap();
+        assert!(matches!(recv_command(&mut client_read).await.unwrap(), VnpCommand::Ok));
+    }
+}
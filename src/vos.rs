@@ -1,94 +1,93 @@
-use sha3::{Digest, Keccak256};
-use std::fs;
-// use std::io::Read; // TODO: Enable for streaming reads in future versions
-use std::path::Path;
-use crate::objects::{self, ObjectId};
-use serde::Serialize;
-// use fastcdc::v2020::{StreamCDC, FastCDC}; // TODO: Enable for advanced chunking in future versions
+use std::path::{Path, PathBuf};
+use serde::de::DeserializeOwned;
+use orbit_core::objects::{self, ObjectId};
+use orbit_core::vos::ObjectStore;
+use orbit_core::ORB_DIR;
 
-/// Hashes raw byte data using the SHA-3 (Keccak-256) PQC-resistant algorithm.
-pub fn hash_data(data: &[u8]) -> ObjectId {
-    let mut hasher = Keccak256::new();
-    hasher.update(data);
-    
-    // Convert the 32-byte hash result into a hex string.
-    format!("{:x}", hasher.finalize())
+pub use orbit_core::vos::{hash_data, hash_file, object_type, ChunkStats, StreamHasher};
+use tracing::{debug, warn};
+
+/// Object store of the repository in the current directory
+pub fn store() -> ObjectStore {
+    ObjectStore::new(Path::new(ORB_DIR).join("objects"))
+}
+
+/// Why an object could not be read from the local VOS
+#[derive(Debug, thiserror::Error)]
+pub enum VosError {
+    #[error("object {id} is not in the local object store (run `orb sync` to download it)")]
+    Missing { id: ObjectId },
+    #[error("cannot read object {id} from {}: {source}", path.display())]
+    Read { id: ObjectId, path: PathBuf, source: std::io::Error },
+    #[error("object {id} is not a valid {kind} object: {source}")]
+    Corrupt { id: ObjectId, kind: &'static str, source: serde_json::Error },
+}
+
+/// Reads an object's data from the local VOS
+pub fn load_object(object_id: &str) -> Result<Vec<u8>, VosError> {
+    let path = object_path(object_id).map_err(|_| VosError::Missing { id: object_id.to_string() })?;
+    std::fs::read(&path).map_err(|source| match source.kind() {
+        std::io::ErrorKind::NotFound => VosError::Missing { id: object_id.to_string() },
+        _ => VosError::Read { id: object_id.to_string(), path, source },
+    })
+}
+
+/// Reads and decodes a `kind` ("commit", "tree", or "file") object from the local VOS
+pub fn load<T: DeserializeOwned>(object_id: &str, kind: &'static str) -> Result<T, VosError> {
+    serde_json::from_slice(&load_object(object_id)?).map_err(|source| VosError::Corrupt { id: object_id.to_string(), kind, source })
+}
+
+/// Reassembles the content of a File object from its chunks
+pub fn file_content(file_id: &str) -> Result<Vec<u8>, VosError> {
+    load::<objects::File>(file_id, "file")?.content(|chunk| load_object(chunk))
 }
 
-/// Serializes an object (like Commit or Directory) and returns its hash (ID).
-pub fn hash_object<T: Serialize>(object: &T) -> Result<ObjectId, serde_json::Error> {
-    // For simplicity in the MVP, we'll use JSON serialization. 
-    // A production version would use a more compact binary format (like BSON or custom)
-    // for superior speed.
-    let serialized_data = serde_json::to_vec(object)?; 
-    
-    Ok(hash_data(&serialized_data))
+/// Returns the on-disk path of an object: .orb/objects/ab/cdef123...
+pub fn object_path(object_id: &str) -> Result<PathBuf, std::io::Error> {
+    store().object_path(object_id)
 }
 
-// Example placeholder for saving an object to the file system (by its hash)
-/// Chunks a file's content and returns the ID of the root object (File object ID) 
+/// Returns the spool path used while a large object is still being received.
+pub fn temp_object_path(object_id: &str) -> Result<PathBuf, std::io::Error> {
+    store().temp_object_path(object_id)
+}
+
+/// Moves a fully received (and verified) spool file into the object store.
+pub fn store_object_file(object_id: &str, temp_path: &Path) -> Result<(), std::io::Error> {
+    debug!("Storing object {}", object_id);
+    store().store_file(object_id, temp_path)
+}
+
+/// Chunks a file's content and returns the ID of the root object (File object ID)
 /// that represents the content. This automatically saves all new chunks to VOS.
 pub fn chunk_and_save_file(path: &Path) -> Result<(ObjectId, usize), std::io::Error> {
-    // For MVP v0.2, we'll simplify chunking by treating each file as a single chunk
-    // This ensures the revert functionality works correctly while maintaining the VOS architecture
-    let file_content = fs::read(path)?;
-    let size = file_content.len();
-    
-    // Save the entire file content as a single chunk
-    let chunk_id = save_object(&file_content);
-    
-    // Create and save the File object that references this chunk
-    let file_object = objects::File {
-        root_chunk_id: chunk_id.clone(),
-        size,
-    };
-    
-    let file_id = hash_object(&file_object).unwrap();
-    save_object(&serde_json::to_vec(&file_object).unwrap()); // Save the File object metadata
-    
-    Ok((file_id, size))
+    store().save_file(path)
 }
 
-/// Saves raw data to the VOS object store by its hash ID.
-/// This is a simplified version for MVP - in production this would handle
-/// directory structure and deduplication more efficiently.
+/// Saves a symbolic link as a File object whose content is the link's target
+pub fn save_symlink(path: &Path) -> Result<ObjectId, std::io::Error> {
+    store().save_symlink(path)
+}
+
+/// Reads content to its end, splitting it into content-defined chunks (FastCDC) and
+/// saving the ones not stored yet; returns the ID of the File object describing it and
+/// the content's size.
+pub fn save_stream(content: impl std::io::Read, stats: &mut ChunkStats) -> Result<(ObjectId, usize), std::io::Error> {
+    store().save_stream(content, stats)
+}
+
+/// Saves raw data to the VOS object store by its hash ID, warning instead of failing
+/// if it cannot be written
 pub fn save_object(data: &[u8]) -> ObjectId {
-    let object_id = hash_data(data);
-    
-    // Create object storage path: .orb/objects/ab/cdef123...
-    let objects_dir = std::path::Path::new(".orb").join("objects");
-    let (prefix, suffix) = object_id.split_at(2);
-    let object_dir = objects_dir.join(prefix);
-    let object_file = object_dir.join(suffix);
-    
-    // Create directory if it doesn't exist
-    if let Err(_) = fs::create_dir_all(&object_dir) {
-        eprintln!("Warning: Could not create object directory");
-        return object_id;
-    }
-    
-    // Write the object data if it doesn't already exist (deduplication)
-    if !object_file.exists() {
-        if let Err(_) = fs::write(&object_file, data) {
-            eprintln!("Warning: Could not save object {}", object_id);
-        }
-    }
-    
-    object_id
+    store().save(data).unwrap_or_else(|e| {
+        let object_id = hash_data(data);
+        warn!("⚠️  Could not save object {}: {}", object_id, e);
+        object_id
+    })
 }
 
 /// Stores object data with a pre-computed ID (for objects received from server)
 pub fn store_object_with_id(object_id: &str, data: &[u8]) -> Result<(), std::io::Error> {
-    let objects_dir = Path::new(".orb").join("objects");
-    let (prefix, suffix) = object_id.split_at(2);
-    let object_dir = objects_dir.join(prefix);
-    let object_file = object_dir.join(suffix);
-    
-    // Create directory if it doesn't exist
-    fs::create_dir_all(&object_dir)?;
-    
-    // Write the object data (overwrites if exists, for sync consistency)
-    fs::write(&object_file, data)?;
-    
-    Ok(())
-}
\ No newline at end of file
+    debug!("Storing object {} ({} bytes)", object_id, data.len());
+    store().store_with_id(object_id, data)
+}
@@ -0,0 +1,536 @@
+use std::collections::{BTreeMap, BinaryHeap, HashSet};
+use std::convert::Infallible;
+use std::fmt::Write as _;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use base64::Engine;
+use hyper::server::conn::AddrStream;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Request, Response, StatusCode};
+use percent_encoding::{AsciiSet, CONTROLS};
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+use crate::audit;
+use crate::hosted::{self, HostedRepo};
+use crate::objects::{Commit, Directory, File, ObjectId};
+use crate::server::{self, Server};
+use crate::users::UserStore;
+use tracing::warn;
+
+/// Commits per page of history
+const PAGE: usize = 50;
+/// Files larger than this are offered as raw downloads instead of being shown inline
+const MAX_INLINE: usize = 1024 * 1024;
+/// Characters escaped in a path segment of a link
+const SEGMENT: &AsciiSet = &CONTROLS.add(b' ').add(b'"').add(b'#').add(b'%').add(b'/').add(b'<').add(b'>').add(b'?').add(b'`');
+const STYLE: &str = "body{font-family:system-ui,sans-serif;max-width:960px;margin:2em auto;padding:0 1em;color:#222}\
+a{color:#0550ae;text-decoration:none}a:hover{text-decoration:underline}\
+table{border-collapse:collapse;width:100%}td{padding:.3em .6em;border-bottom:1px solid #eee}\
+pre{background:#f6f8fa;padding:1em;overflow:auto}code,.id{font-family:ui-monospace,monospace}\
+.muted{color:#666}nav{margin-bottom:1em}";
+
+/// What a route renders
+enum Page {
+    Html { title: String, body: String },
+    Raw(Vec<u8>),
+    Redirect(String),
+}
+
+type Reply = Result<Page, (StatusCode, String)>;
+
+/// Serves the read-only web UI until the server stops. Visitors see what they could clone:
+/// everything with `--allow-anonymous`, and after signing in (HTTP Basic, the password
+/// being a VNP token) their own and shared repositories.
+pub async fn serve(bind: SocketAddr, server: Arc<Server>) -> Result<(), hyper::Error> {
+    let make_service = make_service_fn(move |connection: &AddrStream| {
+        let (server, ip) = (server.clone(), connection.remote_addr().ip());
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request| {
+                let server = server.clone();
+                async move { Ok::<_, Infallible>(handle(request, server, ip).await) }
+            }))
+        }
+    });
+    hyper::Server::try_bind(&bind)?.serve(make_service).await
+}
+
+async fn handle(request: Request<Body>, server: Arc<Server>, ip: IpAddr) -> Response<Body> {
+    let viewer = match viewer(&request, &server, ip) {
+        Ok(viewer) => viewer,
+        Err((status, message)) => return error_page(status, &message, false),
+    };
+    let segments: Vec<String> = request
+        .uri()
+        .path()
+        .split('/')
+        .filter(|s| !s.is_empty())
+        .map(|s| percent_encoding::percent_decode_str(s).decode_utf8_lossy().into_owned())
+        .collect();
+    let page = request
+        .uri()
+        .query()
+        .and_then(|query| query.split('&').find_map(|pair| pair.strip_prefix("page=")))
+        .and_then(|page| page.parse().ok())
+        .unwrap_or(0);
+
+    let anonymous = viewer.is_none();
+    let rendered = tokio::task::spawn_blocking(move || route(&server, viewer.as_deref(), &segments, page)).await;
+    match rendered {
+        Ok(Ok(Page::Html { title, body })) => html(StatusCode::OK, &title, &body),
+        Ok(Ok(Page::Raw(data))) => {
+            let content_type = if is_binary(&data) { "application/octet-stream" } else { "text/plain; charset=utf-8" };
+            Response::builder()
+                .header(hyper::header::CONTENT_TYPE, content_type)
+                .header("X-Content-Type-Options", "nosniff")
+                .body(Body::from(data))
+                .unwrap()
+        }
+        Ok(Ok(Page::Redirect(location))) => Response::builder()
+            .status(StatusCode::SEE_OTHER)
+            .header(hyper::header::LOCATION, location)
+            .body(Body::empty())
+            .unwrap(),
+        // Anonymous visitors are asked to sign in rather than told a repository is missing
+        Ok(Err((status, message))) => error_page(status, &message, anonymous && status == StatusCode::NOT_FOUND),
+        Err(e) => {
+            warn!("⚠️  Web UI error: {}", e);
+            error_page(StatusCode::INTERNAL_SERVER_ERROR, "internal server error", false)
+        }
+    }
+}
+
+/// The signed-in user, from `Authorization: Basic <email:token>`. Bad tokens count as
+/// failed authentications, like on the VNP port.
+fn viewer(request: &Request<Body>, server: &Server, ip: IpAddr) -> Result<Option<String>, (StatusCode, String)> {
+    let Some(credentials) = request
+        .headers()
+        .get(hyper::header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Basic "))
+    else {
+        return Ok(None);
+    };
+    let decoded = base64::engine::general_purpose::STANDARD.decode(credentials).unwrap_or_default();
+    let decoded = String::from_utf8_lossy(&decoded);
+    let token = decoded.split_once(':').map_or("", |(_, token)| token);
+    if let Some(wait) = server.throttle.locked_out(ip, Some(token)) {
+        return Err((StatusCode::TOO_MANY_REQUESTS, format!("Too many failed sign-ins; try again in {}s", wait.as_secs().max(1))));
+    }
+
+    let store = UserStore::load(&server.dir).map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    match store.authenticate(token) {
+        Ok(user) => {
+            server.throttle.auth_succeeded(ip);
+            Ok(Some(user.to_string()))
+        }
+        Err(reason) => {
+            server.throttle.auth_failed(ip, Some(token));
+            server.audit.record(audit::Event {
+                client: Some(ip.to_string()),
+                message: Some(format!("{} (web UI)", reason)),
+                ..audit::Event::new("auth_failed")
+            });
+            Err((StatusCode::UNAUTHORIZED, format!("Sign-in failed: {}", reason)))
+        }
+    }
+}
+
+fn route(server: &Server, viewer: Option<&str>, segments: &[String], page: usize) -> Reply {
+    let segments: Vec<&str> = segments.iter().map(String::as_str).collect();
+    match segments.as_slice() {
+        [] => Ok(index(server, viewer)),
+        ["login"] if viewer.is_some() => Ok(Page::Redirect("/".to_string())),
+        ["login"] => Err((StatusCode::UNAUTHORIZED, "Sign in with your email and token".to_string())),
+        [namespace, name, rest @ ..] => {
+            let repository = format!("{}/{}", namespace, name);
+            let not_found = || (StatusCode::NOT_FOUND, format!("No repository {}", repository));
+            server::permission(&server.root, &repository, viewer, server.allow_anonymous).ok_or_else(not_found)?;
+            let repo = hosted::repo_dir(&server.root, &repository)
+                .ok()
+                .and_then(|dir| HostedRepo::open(&dir))
+                .ok_or_else(not_found)?;
+            let browser = Browser { repo: &repo, name: &repository };
+            match rest {
+                [] => Ok(Page::Redirect(browser.link(&["tree", &repo.default_branch()]))),
+                ["tree", rev, path @ ..] => browser.tree(rev, path),
+                ["blob", rev, path @ ..] => browser.blob(rev, path),
+                ["raw", rev, path @ ..] => browser.raw(rev, path),
+                ["commits", rev] => browser.commits(rev, page),
+                ["commit", id] => browser.commit(id),
+                _ => Err((StatusCode::NOT_FOUND, "No such page".to_string())),
+            }
+        }
+        _ => Err((StatusCode::NOT_FOUND, "No such page".to_string())),
+    }
+}
+
+/// `/`: the repositories the visitor may read
+fn index(server: &Server, viewer: Option<&str>) -> Page {
+    let mut body = String::from("<h1>🛰️ Repositories</h1><table>");
+    let mut shown = 0;
+    for name in hosted::list_repositories(&server.root) {
+        if server::permission(&server.root, &name, viewer, server.allow_anonymous).is_none() {
+            continue;
+        }
+        let Some(repo) = hosted::repo_dir(&server.root, &name).ok().and_then(|dir| HostedRepo::open(&dir)) else { continue };
+        let branch = repo.default_branch();
+        let updated = repo.read_ref(&crate::refs::branch_ref(&branch)).and_then(|id| repo.load_commit(&id)).map(|commit| format_time(commit.timestamp));
+        let _ = write!(
+            body,
+            "<tr><td><a href=\"/{}\">{}</a></td><td class=\"muted\">{}</td><td class=\"muted\">{}</td></tr>",
+            name.split('/').map(encode).collect::<Vec<_>>().join("/"),
+            escape(&name),
+            escape(&branch),
+            updated.unwrap_or_else(|| "empty".to_string())
+        );
+        shown += 1;
+    }
+    body.push_str("</table>");
+    if shown == 0 {
+        body.push_str("<p class=\"muted\">No repositories to show.</p>");
+    }
+    match viewer {
+        Some(user) => {
+            let _ = write!(body, "<p class=\"muted\">Signed in as {}</p>", escape(user));
+        }
+        None => body.push_str("<p class=\"muted\"><a href=\"/login\">Sign in</a> with your email and token to see private repositories.</p>"),
+    }
+    Page::Html { title: "Repositories".to_string(), body }
+}
+
+/// Pages of one repository
+struct Browser<'a> {
+    repo: &'a HostedRepo,
+    name: &'a str,
+}
+
+impl Browser<'_> {
+    /// `/<namespace>/<repo>/<parts...>`, with each part escaped
+    fn link(&self, parts: &[&str]) -> String {
+        let mut link: String = self.name.split('/').chain(parts.iter().copied()).map(|part| format!("/{}", encode(part))).collect();
+        if link.is_empty() {
+            link.push('/');
+        }
+        link
+    }
+
+    /// Resolves a branch, tag, or full commit ID to a commit
+    fn resolve(&self, rev: &str) -> Result<(ObjectId, Commit), (StatusCode, String)> {
+        let id = self.repo.branches().remove(rev)
+            .or_else(|| self.repo.tags().remove(rev))
+            .or_else(|| hosted::is_object_id(rev).then(|| rev.to_string()))
+            .ok_or_else(|| (StatusCode::NOT_FOUND, format!("No branch, tag, or commit '{}'", rev)))?;
+        let commit = self.repo.load_commit(&id).ok_or_else(|| (StatusCode::NOT_FOUND, format!("No commit {}", id)))?;
+        Ok((id, commit))
+    }
+
+    /// Follows `path` from a commit's root tree, returning the entry's mode and object ID
+    fn lookup(&self, commit: &Commit, path: &[&str]) -> Result<(u32, ObjectId), (StatusCode, String)> {
+        let mut current = (0o040000, commit.tree.clone());
+        for part in path {
+            let directory = (current.0 == 0o040000).then(|| self.load::<Directory>(&current.1)).flatten();
+            let entry = directory.and_then(|directory| directory.entries.into_iter().find(|entry| entry.name == *part));
+            let Some(entry) = entry else {
+                return Err((StatusCode::NOT_FOUND, format!("No path {}", path.join("/"))));
+            };
+            current = (entry.mode, entry.id);
+        }
+        Ok(current)
+    }
+
+    fn load<T: serde::de::DeserializeOwned>(&self, id: &str) -> Option<T> {
+        hosted::is_object_id(id).then(|| self.repo.load(id)).flatten()
+    }
+
+    fn content(&self, id: &str) -> Result<Vec<u8>, (StatusCode, String)> {
+        let missing = || (StatusCode::NOT_FOUND, format!("File object {} is missing", id));
+        let file = self.load::<File>(id).ok_or_else(missing)?;
+        file.content(|chunk| self.repo.read_object(chunk)).map_err(|_| missing())
+    }
+
+    /// Title bar: repository name, branch links, and a breadcrumb of the current path
+    fn header(&self, rev: &str, path: &[&str]) -> String {
+        let mut header = format!(
+            "<nav><a href=\"/\">Repositories</a> / <a href=\"{}\"><b>{}</b></a> · <a href=\"{}\">history</a></nav>",
+            self.link(&[]),
+            escape(self.name),
+            self.link(&["commits", rev])
+        );
+        let _ = write!(header, "<p>Branch: ");
+        for branch in self.repo.branches().keys() {
+            let label = if branch == rev { format!("<b>{}</b>", escape(branch)) } else { escape(branch) };
+            let _ = write!(header, "<a href=\"{}\">{}</a> ", self.link(&["tree", branch]), label);
+        }
+        let _ = write!(header, "</p><h2><a href=\"{}\">{}</a>", self.link(&["tree", rev]), escape(rev));
+        for (depth, part) in path.iter().enumerate() {
+            let mut parts = vec!["tree", rev];
+            parts.extend(&path[..=depth]);
+            let _ = write!(header, " / <a href=\"{}\">{}</a>", self.link(&parts), escape(part));
+        }
+        header.push_str("</h2>");
+        header
+    }
+
+    /// `tree/<rev>/<path>`: a directory listing and the commit it comes from
+    fn tree(&self, rev: &str, path: &[&str]) -> Reply {
+        let (id, commit) = self.resolve(rev)?;
+        let (mode, tree) = self.lookup(&commit, path)?;
+        if mode != 0o040000 {
+            let mut parts = vec!["blob", rev];
+            parts.extend(path);
+            return Ok(Page::Redirect(self.link(&parts)));
+        }
+        let directory = self.load::<Directory>(&tree).ok_or_else(|| (StatusCode::NOT_FOUND, format!("Tree {} is missing", tree)))?;
+
+        let mut body = self.header(rev, path);
+        let _ = write!(body, "<p>{}</p><table>", self.commit_line(&id, &commit));
+        let mut entries: Vec<_> = directory.entries.iter().collect();
+        entries.sort_by_key(|entry| (entry.mode != 0o040000, entry.name.clone()));
+        for entry in entries {
+            let is_dir = entry.mode == 0o040000;
+            let mut parts = vec![if is_dir { "tree" } else { "blob" }, rev];
+            parts.extend(path);
+            parts.push(&entry.name);
+            let size = if is_dir {
+                String::new()
+            } else {
+                self.load::<File>(&entry.id).map(|file| crate::transfer::format_bytes(file.size as u64)).unwrap_or_default()
+            };
+            let _ = write!(
+                body,
+                "<tr><td>{} <a href=\"{}\">{}</a></td><td class=\"muted\">{}</td></tr>",
+                if is_dir { "📁" } else { "📄" },
+                self.link(&parts),
+                escape(&entry.name),
+                size
+            );
+        }
+        body.push_str("</table>");
+        Ok(Page::Html { title: format!("{} · {}", self.name, rev), body })
+    }
+
+    /// `blob/<rev>/<path>`: a file's content, unless it is binary or large
+    fn blob(&self, rev: &str, path: &[&str]) -> Reply {
+        let (_, commit) = self.resolve(rev)?;
+        let (mode, id) = self.lookup(&commit, path)?;
+        if mode == 0o040000 || path.is_empty() {
+            let mut parts = vec!["tree", rev];
+            parts.extend(path);
+            return Ok(Page::Redirect(self.link(&parts)));
+        }
+        let data = self.content(&id)?;
+        let mut raw = vec!["raw", rev];
+        raw.extend(path);
+
+        let mut body = self.header(rev, path);
+        let _ = write!(
+            body,
+            "<p class=\"muted\">{} · <a href=\"{}\">raw</a></p>",
+            crate::transfer::format_bytes(data.len() as u64),
+            self.link(&raw)
+        );
+        if is_binary(&data) || data.len() > MAX_INLINE {
+            body.push_str("<p>This file is not shown because it is binary or too large.</p>");
+        } else {
+            let _ = write!(body, "<pre><code>{}</code></pre>", escape(&String::from_utf8_lossy(&data)));
+        }
+        Ok(Page::Html { title: format!("{} · {}", self.name, path.join("/")), body })
+    }
+
+    /// `raw/<rev>/<path>`: a file's bytes
+    fn raw(&self, rev: &str, path: &[&str]) -> Reply {
+        let (_, commit) = self.resolve(rev)?;
+        match self.lookup(&commit, path)? {
+            (0o040000, _) => Err((StatusCode::NOT_FOUND, format!("{} is a directory", path.join("/")))),
+            (_, id) => Ok(Page::Raw(self.content(&id)?)),
+        }
+    }
+
+    /// `commits/<rev>`: history reachable from a revision, newest first
+    fn commits(&self, rev: &str, page: usize) -> Reply {
+        let (id, _) = self.resolve(rev)?;
+        let history = newest_first(self.repo, &id, (page + 1) * PAGE + 1);
+
+        let mut body = self.header(rev, &[]);
+        body.push_str("<table>");
+        for (id, commit) in history.iter().skip(page * PAGE).take(PAGE) {
+            let _ = write!(body, "<tr><td>{}</td></tr>", self.commit_line(id, commit));
+        }
+        body.push_str("</table><p>");
+        if page > 0 {
+            let _ = write!(body, "<a href=\"{}?page={}\">← Newer</a> ", self.link(&["commits", rev]), page - 1);
+        }
+        if history.len() > (page + 1) * PAGE {
+            let _ = write!(body, "<a href=\"{}?page={}\">Older →</a>", self.link(&["commits", rev]), page + 1);
+        }
+        body.push_str("</p>");
+        Ok(Page::Html { title: format!("{} · history of {}", self.name, rev), body })
+    }
+
+    /// `commit/<id>`: a commit's details and the files it changed against its first parent
+    fn commit(&self, id: &str) -> Reply {
+        let (id, commit) = self.resolve(id)?;
+        let mut body = self.header(&id, &[]);
+        let _ = write!(
+            body,
+            "<pre>{}</pre><p>{} · {}</p><p class=\"muted\">commit <span class=\"id\">{}</span>",
+            escape(&commit.message),
+            escape(&commit.author),
+            format_time(commit.timestamp),
+            id
+        );
+        for parent in &commit.parents {
+            let _ = write!(body, "<br>parent <a class=\"id\" href=\"{}\">{}</a>", self.link(&["commit", parent]), parent);
+        }
+        body.push_str("</p><table>");
+
+        let before = commit.parents.first().and_then(|parent| self.repo.load_commit(parent)).map(|parent| self.files(&parent.tree)).unwrap_or_default();
+        let after = self.files(&commit.tree);
+        for (path, file) in &after {
+            let status = match before.get(path) {
+                None => "added",
+                Some(old) if old != file => "modified",
+                Some(_) => continue,
+            };
+            let mut parts = vec!["blob", id.as_str()];
+            parts.extend(path.split('/'));
+            let _ = write!(body, "<tr><td>{}</td><td><a href=\"{}\">{}</a></td></tr>", status, self.link(&parts), escape(path));
+        }
+        for path in before.keys().filter(|path| !after.contains_key(*path)) {
+            let _ = write!(body, "<tr><td>deleted</td><td>{}</td></tr>", escape(path));
+        }
+        body.push_str("</table>");
+        Ok(Page::Html { title: format!("{} · {}", self.name, &id[..7.min(id.len())]), body })
+    }
+
+    /// Every file under a tree, by path
+    fn files(&self, tree: &str) -> BTreeMap<String, ObjectId> {
+        let mut files = BTreeMap::new();
+        let mut pending = vec![(String::new(), tree.to_string())];
+        let mut seen = HashSet::new();
+        while let Some((prefix, tree)) = pending.pop() {
+            if !seen.insert(tree.clone()) {
+                continue;
+            }
+            let Some(directory) = self.load::<Directory>(&tree) else { continue };
+            for entry in directory.entries {
+                let path = format!("{}{}", prefix, entry.name);
+                if entry.mode == 0o040000 {
+                    pending.push((format!("{}/", path), entry.id));
+                } else {
+                    files.insert(path, entry.id);
+                }
+            }
+        }
+        files
+    }
+
+    /// Linked short ID, first message line, author, and date of a commit
+    fn commit_line(&self, id: &str, commit: &Commit) -> String {
+        format!(
+            "<a class=\"id\" href=\"{}\">{}</a> {} <span class=\"muted\">— {}, {}</span>",
+            self.link(&["commit", id]),
+            &id[..7.min(id.len())],
+            escape(commit.message.lines().next().unwrap_or_default()),
+            escape(&commit.author),
+            format_time(commit.timestamp)
+        )
+    }
+}
+
+/// Up to `limit` commits reachable from `tip`, newest first
+fn newest_first(repo: &HostedRepo, tip: &str, limit: usize) -> Vec<(ObjectId, Commit)> {
+    let mut queue = BinaryHeap::new();
+    let mut seen = HashSet::new();
+    let mut push = |queue: &mut BinaryHeap<(i64, ObjectId)>, id: &str| {
+        if seen.insert(id.to_string()) {
+            if let Some(commit) = repo.load_commit(id) {
+                queue.push((commit.timestamp, id.to_string()));
+            }
+        }
+    };
+    push(&mut queue, tip);
+    let mut history = Vec::new();
+    while let Some((_, id)) = queue.pop() {
+        let Some(commit) = repo.load_commit(&id) else { continue };
+        for parent in &commit.parents {
+            push(&mut queue, parent);
+        }
+        history.push((id, commit));
+        if history.len() >= limit {
+            break;
+        }
+    }
+    history
+}
+
+fn html(status: StatusCode, title: &str, body: &str) -> Response<Body> {
+    let page = format!(
+        "<!DOCTYPE html><html><head><meta charset=\"utf-8\"><title>{}</title><style>{}</style></head><body>{}</body></html>",
+        escape(title),
+        STYLE,
+        body
+    );
+    Response::builder()
+        .status(status)
+        .header(hyper::header::CONTENT_TYPE, "text/html; charset=utf-8")
+        .header("Content-Security-Policy", "default-src 'none'; style-src 'unsafe-inline'")
+        .body(Body::from(page))
+        .unwrap()
+}
+
+/// An error page; with `challenge`, also asks the browser for credentials
+fn error_page(status: StatusCode, message: &str, challenge: bool) -> Response<Body> {
+    let status = if challenge { StatusCode::UNAUTHORIZED } else { status };
+    let body = format!("<nav><a href=\"/\">Repositories</a></nav><h1>{}</h1><p>{}</p>", status, escape(message));
+    let mut response = html(status, status.canonical_reason().unwrap_or("Error"), &body);
+    if status == StatusCode::UNAUTHORIZED {
+        response.headers_mut().insert(hyper::header::WWW_AUTHENTICATE, "Basic realm=\"orbit\"".parse().unwrap());
+    }
+    response
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn encode(segment: &str) -> String {
+    percent_encoding::utf8_percent_encode(segment, SEGMENT).to_string()
+}
+
+/// Same heuristic as git: a NUL byte early in the file
+fn is_binary(data: &[u8]) -> bool {
+    data.iter().take(8000).any(|&b| b == 0)
+}
+
+fn format_time(timestamp: i64) -> String {
+    OffsetDateTime::from_unix_timestamp(timestamp)
+        .ok()
+        .and_then(|time| time.format(&Rfc3339).ok())
+        .map(|time| time.replacen('T', " ", 1).replace('Z', " UTC"))
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escaping() {
+        assert_eq!(escape("<a href=\"x\">&'</a>"), "&lt;a href=&quot;x&quot;&gt;&amp;&#39;&lt;/a&gt;");
+        assert_eq!(encode("my file#1?.txt"), "my%20file%231%3F.txt");
+        assert!(is_binary(b"PK\x03\x04\x00"));
+        assert!(!is_binary("héllo\n".as_bytes()));
+    }
+}
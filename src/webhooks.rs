@@ -0,0 +1,119 @@
+use std::time::Duration;
+use rand_core::RngCore;
+use ring::hmac;
+use serde::{Deserialize, Serialize};
+use crate::refs::RefChange;
+
+/// Attempts per delivery; the wait between them grows from RETRY_DELAY
+const ATTEMPTS: u32 = 4;
+const RETRY_DELAY: Duration = Duration::from_secs(2);
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A URL notified after refs of a hosted repository move (`.orb/webhooks.json`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Webhook {
+    pub id: String,
+    pub url: String,
+    /// Key for the `X-Orbit-Signature-256` HMAC; never sent back by the admin API
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secret: Option<String>,
+}
+
+impl Webhook {
+    pub fn new(url: String, secret: Option<String>) -> Self {
+        let mut id = [0u8; 4];
+        rand_core::OsRng.fill_bytes(&mut id);
+        Self { id: id.iter().map(|b| format!("{:02x}", b)).collect(), url, secret }
+    }
+}
+
+/// Body POSTed for one moved ref
+#[derive(Debug, Clone, Serialize)]
+pub struct Payload {
+    pub event: &'static str,
+    pub repository: String,
+    #[serde(rename = "ref")]
+    pub ref_name: String,
+    /// Previous target (null for a new ref)
+    pub before: Option<String>,
+    pub after: Option<String>,
+    /// User who pushed (null for anonymous sessions)
+    pub pusher: Option<String>,
+}
+
+impl Payload {
+    pub fn push(repository: &str, change: &RefChange, pusher: Option<&str>) -> Self {
+        Self {
+            event: "push",
+            repository: repository.to_string(),
+            ref_name: change.name.clone(),
+            before: change.old.clone(),
+            after: change.new.clone(),
+            pusher: pusher.map(str::to_string),
+        }
+    }
+}
+
+/// `sha256=<hex HMAC-SHA256 of body>`, the value of `X-Orbit-Signature-256`
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    let key = hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes());
+    let tag = hmac::sign(&key, body);
+    format!("sha256={}", tag.as_ref().iter().map(|b| format!("{:02x}", b)).collect::<String>())
+}
+
+/// POSTs a payload to a webhook, retrying network errors, 429s, and 5xx replies with a
+/// growing delay. Returns the final HTTP status, or why delivery failed.
+pub async fn deliver(client: &reqwest::Client, hook: &Webhook, payload: &Payload) -> Result<u16, String> {
+    let body = serde_json::to_vec(payload).map_err(|e| e.to_string())?;
+    let mut delivery = [0u8; 8];
+    rand_core::OsRng.fill_bytes(&mut delivery);
+    let delivery: String = delivery.iter().map(|b| format!("{:02x}", b)).collect();
+
+    let mut delay = RETRY_DELAY;
+    let mut attempt = 1;
+    loop {
+        let mut request = client
+            .post(&hook.url)
+            .timeout(TIMEOUT)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(reqwest::header::USER_AGENT, concat!("orbit-webhook/", env!("CARGO_PKG_VERSION")))
+            .header("X-Orbit-Event", payload.event)
+            .header("X-Orbit-Delivery", &delivery)
+            .body(body.clone());
+        if let Some(secret) = &hook.secret {
+            request = request.header("X-Orbit-Signature-256", sign(secret, &body));
+        }
+
+        let outcome = match request.send().await {
+            Ok(response) if response.status().is_success() => return Ok(response.status().as_u16()),
+            Ok(response) => {
+                let status = response.status();
+                if !(status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS) {
+                    return Err(format!("{} answered {}", hook.url, status));
+                }
+                format!("{} answered {}", hook.url, status)
+            }
+            Err(e) => format!("{}: {}", hook.url, e),
+        };
+        if attempt == ATTEMPTS {
+            return Err(format!("{} (gave up after {} attempts)", outcome, ATTEMPTS));
+        }
+        tokio::time::sleep(delay).await;
+        delay *= 2;
+        attempt += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signature_is_hmac_sha256() {
+        // RFC 4231 test case 2
+        assert_eq!(
+            sign("Jefe", b"what do ya want for nothing?"),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+}